@@ -0,0 +1,73 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2024 Alasdair Armstrong
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// 1. Redistributions of source code must retain the above copyright
+// notice, this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright
+// notice, this list of conditions and the following disclaimer in the
+// documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Builds a small set of stage 1 translation tables using
+//! [isla_axiomatic::page_table::setup()] and dumps every non-zero
+//! descriptor. Run with:
+//!
+//! ```text
+//! cargo run -p isla-axiomatic --example page_table_setup
+//! ```
+
+use std::process;
+
+use isla_axiomatic::page_table::{self, Mapping, PageTableConfig};
+use isla_lib::bitvector::{b64::B64, BV};
+use isla_lib::ir::Val;
+use isla_lib::memory::Memory;
+
+fn main() {
+    let config = PageTableConfig { region: "stage 1", base_addr: 0x3_0000_0000, max_tables: 64 };
+
+    // Identity map the first 2M of code and data, then remap a single
+    // virtual page elsewhere in physical memory.
+    let mut mappings: Vec<Mapping> = (0..512).map(|i| Mapping::identity(0x4000_0000 + (i << 12), 3)).collect();
+    mappings.push(Mapping::new(0x1_0000_0000, 0x4020_0000, 3));
+
+    let mut memory = Memory::<B64>::new();
+    let handles = match page_table::setup(&config, &mappings, &mut memory) {
+        Ok(handles) => handles,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    };
+
+    println!("TTBR0_EL1 = 0x{:016x}", handles.ttbr(0));
+    for addr in handles.range.clone().step_by(8) {
+        match memory.read_initial(addr, 8) {
+            Ok(Val::Bits(desc)) if !desc.is_zero() => println!("0x{:x}: 0x{:016x}", addr, desc.lower_u64()),
+            Ok(_) => (),
+            Err(err) => {
+                eprintln!("Failed to read descriptor at 0x{:x}: {}", addr, err);
+                process::exit(1)
+            }
+        }
+    }
+}
@@ -30,6 +30,7 @@
 //! This module implements utilities for working with axiomatic memory
 //! models.
 
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::fmt;
@@ -39,12 +40,13 @@ use isla_lib::config::ISAConfig;
 use isla_lib::error::IslaError;
 use isla_lib::ir::{Name, SharedState, Val};
 use isla_lib::memory::Memory;
-use isla_lib::smt::{smtlib::Def, smtlib::Ty, EvPath, Event, Sym};
+use isla_lib::smt::{smtlib::Def, smtlib::Ty, Event, Sym};
 use isla_lib::source_loc::SourceLoc;
 
 use isla_mml::accessor::ModelEvent;
 use isla_mml::memory_model;
 
+pub use crate::candidates::Candidates;
 use crate::graph::GraphOpts;
 use crate::litmus::exp::Loc as LitmusLoc;
 use crate::litmus::Litmus;
@@ -56,62 +58,13 @@ pub type ThreadId = usize;
 
 pub type TranslationId = usize;
 
-/// An iterator over candidate executions
-pub struct Candidates<'ev, B> {
-    index: Vec<usize>,
-    max_index: Vec<usize>,
-    threads: &'ev [Vec<EvPath<B>>],
-    out_of_bounds: bool,
-}
-
-impl<'ev, B: BV> Candidates<'ev, B> {
-    /// Create a candidate exeuction iterator from a slice containing
-    /// vectors for each path through a thread.
-    pub fn new(threads: &'ev [Vec<EvPath<B>>]) -> Self {
-        Candidates {
-            index: vec![0; threads.len()],
-            max_index: threads.iter().map(|t| t.len()).collect(),
-            threads,
-            out_of_bounds: !threads.iter().all(|t| !t.is_empty()),
-        }
-    }
-
-    pub fn total(&self) -> usize {
-        if self.threads.is_empty() {
-            0
-        } else {
-            self.max_index.iter().product()
-        }
-    }
-}
-
-fn increment_index(index: &mut [usize], max_index: &[usize], carry: usize) -> bool {
-    if carry == index.len() {
-        return true;
-    }
-
-    index[carry] += 1;
-    if index[carry] == max_index[carry] {
-        index[carry] = 0;
-        increment_index(index, max_index, carry + 1)
-    } else {
-        false
-    }
-}
-
-impl<'ev, B: BV> Iterator for Candidates<'ev, B> {
-    type Item = Vec<&'ev [Event<B>]>;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.out_of_bounds {
-            None
-        } else {
-            let mut result = Vec::with_capacity(self.threads.len());
-            self.threads.iter().zip(self.index.iter()).for_each(|(thread, i)| result.push(thread[*i].as_ref()));
-            self.out_of_bounds = increment_index(&mut self.index, &self.max_index, 0);
-            Some(result)
-        }
-    }
+/// The position of an event within the traces of a candidate
+/// execution. A candidate has a single trace for each thread, and
+/// the event is at `index` in that trace's (filtered) event list.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TraceOrigin {
+    pub thread_id: ThreadId,
+    pub index: usize,
 }
 
 /// An AxEvent (axiomatic event) is a set of base events from an Isla
@@ -151,6 +104,8 @@ pub struct AxEvent<'ev, B> {
     pub is_ifetch: bool,
     /// Is the event associated with an address translation function?
     pub translate: Option<TranslationId>,
+    /// Where the first base event is in the candidate's traces
+    pub trace_origin: TraceOrigin,
 }
 
 impl<'ev, B: BV> ModelEvent<'ev, B> for AxEvent<'ev, B> {
@@ -414,6 +369,16 @@ pub mod relations {
             && (is_memory(ev2) || ev2.in_program_order)
     }
 
+    /// Two distinct memory accesses performed by the same
+    /// instruction, e.g. the two halves of a pair instruction
+    pub fn same_instruction<B: BV>(ev1: &AxEvent<B>, ev2: &AxEvent<B>) -> bool {
+        is_memory(ev1)
+            && is_memory(ev2)
+            && ev1.instruction_index == ev2.instruction_index
+            && ev1.thread_id == ev2.thread_id
+            && ev1.intra_instruction_index != ev2.intra_instruction_index
+    }
+
     pub fn intra_instruction_ordered<B: BV>(ev1: &AxEvent<B>, ev2: &AxEvent<B>) -> bool {
         ev1.instruction_index == ev2.instruction_index
             && ev1.thread_id == ev2.thread_id
@@ -628,7 +593,7 @@ struct MergedTranslation<'ev, B> {
     instruction_index: usize,
     intra_instruction_index: usize,
     thread_id: usize,
-    events: Vec<(usize, &'ev Event<B>)>,
+    events: Vec<(usize, &'ev Event<B>, TraceOrigin)>,
 }
 
 impl<'ev, B: BV> ExecutionInfo<'ev, B> {
@@ -755,7 +720,7 @@ impl<'ev, B: BV> ExecutionInfo<'ev, B> {
                 assert_eq!(ev.thread_id, merged.thread_id);
 
                 for base in &ev.base {
-                    merged.events.push((ev.intra_instruction_index, base))
+                    merged.events.push((ev.intra_instruction_index, base, ev.trace_origin))
                 }
                 merged.intra_instruction_index =
                     std::cmp::min(merged.intra_instruction_index, ev.intra_instruction_index);
@@ -767,7 +732,7 @@ impl<'ev, B: BV> ExecutionInfo<'ev, B> {
         });
 
         for merged in all_translations.values_mut() {
-            merged.events.sort_by_key(|(iio, _)| *iio)
+            merged.events.sort_by_key(|(iio, _, _)| *iio)
         }
 
         // Now we create a new axiomatic event for each translation sequence
@@ -783,11 +748,12 @@ impl<'ev, B: BV> ExecutionInfo<'ev, B> {
                     thread_id: merged.thread_id,
                     name: s1_name.clone(),
                     mm_name: symtab.intern_owned(s1_name),
-                    base: merged.events[0..20].iter().map(|(_, base)| *base).collect(),
+                    base: merged.events[0..20].iter().map(|(_, base, _)| *base).collect(),
                     index_set,
                     extra: Vec::new(),
                     is_ifetch: false,
                     translate: Some(trans_id),
+                    trace_origin: merged.events[0].2,
                 });
                 self.smt_events.push(AxEvent {
                     opcode: Some(merged.opcode),
@@ -797,11 +763,12 @@ impl<'ev, B: BV> ExecutionInfo<'ev, B> {
                     thread_id: merged.thread_id,
                     name: s2_name.clone(),
                     mm_name: symtab.intern_owned(s2_name),
-                    base: merged.events[20..].iter().map(|(_, base)| *base).collect(),
+                    base: merged.events[20..].iter().map(|(_, base, _)| *base).collect(),
                     index_set,
                     extra: Vec::new(),
                     is_ifetch: false,
                     translate: Some(trans_id),
+                    trace_origin: merged.events[20].2,
                 })
             } else {
                 let name = format!("TRANS_{}", trans_id);
@@ -813,11 +780,12 @@ impl<'ev, B: BV> ExecutionInfo<'ev, B> {
                     thread_id: merged.thread_id,
                     name: name.clone(),
                     mm_name: symtab.intern_owned(name),
-                    base: merged.events.iter().map(|(_, base)| *base).collect(),
+                    base: merged.events.iter().map(|(_, base, _)| *base).collect(),
                     index_set,
                     extra: Vec::new(),
                     is_ifetch: false,
                     translate: Some(trans_id),
+                    trace_origin: merged.events[0].2,
                 })
             }
         }
@@ -835,6 +803,7 @@ impl<'ev, B: BV> ExecutionInfo<'ev, B> {
 
         struct CycleEvent<'a, B> {
             tid: usize,
+            eid: usize,
             name: String,
             event: &'a Event<B>,
             in_program_order: bool,
@@ -848,6 +817,7 @@ impl<'ev, B: BV> ExecutionInfo<'ev, B> {
                 fn $f(prefix: &str, po: usize, eid: usize, tid: usize, event: &$a Event<$ty>, translate: Option<usize>) -> Self {
                     CycleEvent {
                         tid,
+                        eid,
                         name: format!("{}{}_{}_{}", prefix, po, eid, tid),
                         event,
                         in_program_order: $in_program_order,
@@ -881,6 +851,9 @@ impl<'ev, B: BV> ExecutionInfo<'ev, B> {
         let mut call_stack = CallStack::new();
 
         for (tid, thread) in candidate.iter().enumerate() {
+            // the index of the first event of each cycle in the thread's trace
+            let mut cycle_start = 0;
+
             for (po, cycle) in thread.split(|ev| ev.is_cycle()).enumerate() {
                 let mut cycle_events: Vec<CycleEvent<'_, B>> = Vec::new();
                 let mut cycle_instr: Option<B> = None;
@@ -993,7 +966,7 @@ impl<'ev, B: BV> ExecutionInfo<'ev, B> {
 
                     for (
                         iio,
-                        CycleEvent { tid, name, event, in_program_order, is_ifetch, translate, include_in_smt },
+                        CycleEvent { tid, eid, name, event, in_program_order, is_ifetch, translate, include_in_smt },
                     ) in cycle_events.drain(..).enumerate()
                     {
                         let evs = if include_in_smt && !(ignore_ifetch && is_ifetch) {
@@ -1017,9 +990,13 @@ impl<'ev, B: BV> ExecutionInfo<'ev, B> {
                             extra: vec![],
                             is_ifetch,
                             translate,
+                            trace_origin: TraceOrigin { thread_id: tid, index: cycle_start + eid },
                         })
                     }
                 }
+
+                // skip over the cycle event separating this cycle from the next
+                cycle_start += cycle.len() + 1
             }
 
             assert!(call_stack.is_empty())
@@ -0,0 +1,612 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2024 Alasdair Armstrong
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// 1. Redistributions of source code must retain the above copyright
+// notice, this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright
+// notice, this list of conditions and the following disclaimer in the
+// documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Candidate executions for litmus tests. A litmus test has a set of
+//! possible traces for each thread, and [Candidates] iterates over
+//! every combination of one trace per thread.
+//!
+//! When running a test, the choice of reads-from (rf) and coherence
+//! (co) for such a combination is left to the SMT solver, which picks
+//! them alongside the memory model. The [enumerate] function instead
+//! enumerates those choices explicitly for a single combination of
+//! traces, which is useful for inspecting and testing candidate
+//! construction without a solver or a cat model. It only imposes the
+//! constraints described by [EnumerateOpts], and returns the
+//! constraints each candidate puts on memory values rather than
+//! checking whether they are satisfiable.
+
+use std::rc::Rc;
+
+use isla_lib::bitvector::BV;
+use isla_lib::ir::Val;
+use isla_lib::smt::{EvPath, Event};
+
+use crate::axiomatic::ThreadId;
+
+/// An iterator over candidate executions
+pub struct Candidates<'ev, B> {
+    index: Vec<usize>,
+    max_index: Vec<usize>,
+    threads: &'ev [Vec<EvPath<B>>],
+    out_of_bounds: bool,
+}
+
+impl<'ev, B: BV> Candidates<'ev, B> {
+    /// Create a candidate exeuction iterator from a slice containing
+    /// vectors for each path through a thread.
+    pub fn new(threads: &'ev [Vec<EvPath<B>>]) -> Self {
+        Candidates {
+            index: vec![0; threads.len()],
+            max_index: threads.iter().map(|t| t.len()).collect(),
+            threads,
+            out_of_bounds: !threads.iter().all(|t| !t.is_empty()),
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        if self.threads.is_empty() {
+            0
+        } else {
+            self.max_index.iter().product()
+        }
+    }
+}
+
+fn increment_index(index: &mut [usize], max_index: &[usize], carry: usize) -> bool {
+    if carry == index.len() {
+        return true;
+    }
+
+    index[carry] += 1;
+    if index[carry] == max_index[carry] {
+        index[carry] = 0;
+        increment_index(index, max_index, carry + 1)
+    } else {
+        false
+    }
+}
+
+impl<'ev, B: BV> Iterator for Candidates<'ev, B> {
+    type Item = Vec<&'ev [Event<B>]>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.out_of_bounds {
+            None
+        } else {
+            let mut result = Vec::with_capacity(self.threads.len());
+            self.threads.iter().zip(self.index.iter()).for_each(|(thread, i)| result.push(thread[*i].as_ref()));
+            self.out_of_bounds = increment_index(&mut self.index, &self.max_index, 0);
+            Some(result)
+        }
+    }
+}
+
+/// Options controlling which memory events take part in [enumerate],
+/// and which candidates it produces.
+#[derive(Clone, Debug)]
+pub struct EnumerateOpts {
+    /// Leave out instruction fetches, i.e. reads before the `Instr`
+    /// event in each cycle
+    pub ignore_ifetch: bool,
+    /// Leave out page table walk accesses (those in the `stage 1` and
+    /// `stage 2` regions)
+    pub ignore_translations: bool,
+    /// Only produce candidates which are sequentially consistent per
+    /// location, i.e. where `po-loc | rf | co | fr` is acyclic. This
+    /// is what forbids reading from a program-order later write, or
+    /// reading past a program-order earlier write to the same
+    /// location.
+    pub coherence: bool,
+}
+
+impl Default for EnumerateOpts {
+    fn default() -> Self {
+        EnumerateOpts { ignore_ifetch: true, ignore_translations: true, coherence: true }
+    }
+}
+
+/// A memory read or write in a candidate execution. Events are named
+/// the same way as in [crate::axiomatic::ExecutionInfo], as
+/// `R{po}_{eid}_{tid}` and `W{po}_{eid}_{tid}`.
+#[derive(Clone, Debug)]
+pub struct CandidateEvent<'ev, B> {
+    pub name: String,
+    pub tid: ThreadId,
+    pub po: usize,
+    pub eid: usize,
+    pub base: &'ev Event<B>,
+}
+
+impl<'ev, B: BV> CandidateEvent<'ev, B> {
+    pub fn is_read(&self) -> bool {
+        self.base.is_memory_read()
+    }
+
+    pub fn is_write(&self) -> bool {
+        self.base.is_memory_write()
+    }
+
+    pub fn address(&self) -> &'ev Val<B> {
+        match self.base {
+            Event::ReadMem { address, .. } | Event::WriteMem { address, .. } => address,
+            _ => unreachable!("candidate events are always memory events"),
+        }
+    }
+
+    fn bytes(&self) -> u32 {
+        match self.base {
+            Event::ReadMem { bytes, .. } | Event::WriteMem { bytes, .. } => *bytes,
+            _ => unreachable!("candidate events are always memory events"),
+        }
+    }
+
+    /// The value read or written by the event
+    pub fn value(&self) -> &'ev Val<B> {
+        match self.base {
+            Event::ReadMem { value, .. } => value,
+            Event::WriteMem { data, .. } => data,
+            _ => unreachable!("candidate events are always memory events"),
+        }
+    }
+
+    fn po_before(&self, other: &Self) -> bool {
+        self.tid == other.tid && (self.po, self.eid) < (other.po, other.eid)
+    }
+}
+
+/// Where a read gets its value from
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RfSource {
+    /// The initial state of memory
+    Initial,
+    /// The write with the given index into [CandidateExecution::events]
+    Write(usize),
+}
+
+/// A constraint that a candidate execution places on the value of a
+/// read, which must be equal to the value of its source
+#[derive(Clone, Debug)]
+pub struct ValueConstraint<'ev, B> {
+    pub read: &'ev Val<B>,
+    pub source: ValueSource<'ev, B>,
+}
+
+#[derive(Clone, Debug)]
+pub enum ValueSource<'ev, B> {
+    /// The initial value of `bytes` bytes at `address`
+    Initial {
+        address: &'ev Val<B>,
+        bytes: u32,
+    },
+    Write(&'ev Val<B>),
+}
+
+/// A candidate execution with an explicit choice of rf and co
+#[derive(Clone, Debug)]
+pub struct CandidateExecution<'ev, B> {
+    events: Rc<[CandidateEvent<'ev, B>]>,
+    rf: Vec<(RfSource, usize)>,
+    co: Vec<Vec<usize>>,
+}
+
+impl<'ev, B: BV> CandidateExecution<'ev, B> {
+    /// The memory events of the candidate, in thread and then program
+    /// order. These are the same for every candidate produced by a
+    /// single call to [enumerate].
+    pub fn events(&self) -> &[CandidateEvent<'ev, B>] {
+        &self.events
+    }
+
+    /// Pairs of (source, read), where the read is an index into
+    /// [Self::events]. Every read appears exactly once.
+    pub fn rf(&self) -> &[(RfSource, usize)] {
+        &self.rf
+    }
+
+    /// The coherence order for each location, as a sequence of indices
+    /// into [Self::events]. The initial write is implicitly first.
+    pub fn co_orders(&self) -> &[Vec<usize>] {
+        &self.co
+    }
+
+    /// Every pair of writes (w1, w2) where w1 is coherence-before w2
+    pub fn co(&self) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+        for order in &self.co {
+            for (i, w1) in order.iter().enumerate() {
+                for w2 in &order[i + 1..] {
+                    pairs.push((*w1, *w2))
+                }
+            }
+        }
+        pairs
+    }
+
+    /// The constraints on memory values implied by the choice of rf
+    pub fn value_constraints(&self) -> Vec<ValueConstraint<'ev, B>> {
+        self.rf
+            .iter()
+            .map(|(source, r)| {
+                let read = &self.events[*r];
+                let source = match source {
+                    RfSource::Initial => ValueSource::Initial { address: read.address(), bytes: read.bytes() },
+                    RfSource::Write(w) => ValueSource::Write(self.events[*w].value()),
+                };
+                ValueConstraint { read: read.value(), source }
+            })
+            .collect()
+    }
+
+    /// The name of an rf source, with the initial state named `IW` as
+    /// it is in the graph output
+    pub fn source_name(&self, source: RfSource) -> &str {
+        match source {
+            RfSource::Initial => "IW",
+            RfSource::Write(w) => &self.events[w].name,
+        }
+    }
+}
+
+/// The memory events of a single trace for each thread
+fn memory_events<'ev, B: BV, T: AsRef<[Event<B>]>>(
+    threads: &'ev [T],
+    opts: &EnumerateOpts,
+) -> Vec<CandidateEvent<'ev, B>> {
+    let mut events = Vec::new();
+    for (tid, thread) in threads.iter().enumerate() {
+        // The first cycle is only for initialisation
+        for (po, cycle) in thread.as_ref().split(|ev| ev.is_cycle()).enumerate().skip(1) {
+            let mut seen_instr = false;
+            for (eid, event) in cycle.iter().enumerate() {
+                let prefix = match event {
+                    Event::Instr(_) => {
+                        seen_instr = true;
+                        continue;
+                    }
+                    _ if opts.ignore_translations && (event.in_region("stage 1") || event.in_region("stage 2")) => {
+                        continue
+                    }
+                    Event::ReadMem { .. } if opts.ignore_ifetch && !seen_instr => continue,
+                    Event::ReadMem { .. } => "R",
+                    Event::WriteMem { .. } => "W",
+                    _ => continue,
+                };
+                events.push(CandidateEvent {
+                    name: format!("{}{}_{}_{}", prefix, po, eid, tid),
+                    tid,
+                    po,
+                    eid,
+                    base: event,
+                })
+            }
+        }
+    }
+    events
+}
+
+fn permutations(items: &[usize]) -> Vec<Vec<usize>> {
+    if items.is_empty() {
+        return vec![vec![]];
+    }
+    let mut result = Vec::new();
+    for (i, item) in items.iter().enumerate() {
+        let mut rest = items.to_vec();
+        rest.remove(i);
+        for mut perm in permutations(&rest) {
+            perm.insert(0, *item);
+            result.push(perm)
+        }
+    }
+    result
+}
+
+/// Check that `po-loc | rf | co | fr` is acyclic for the events of a
+/// single location
+fn is_coherent<B: BV>(
+    events: &[CandidateEvent<'_, B>],
+    accesses: &[usize],
+    co: &[usize],
+    rf: &[(RfSource, usize)],
+) -> bool {
+    let co_position = |w: usize| co.iter().position(|w2| *w2 == w).unwrap();
+
+    let mut edges: Vec<(usize, usize)> = Vec::new();
+    for a1 in accesses {
+        for a2 in accesses {
+            if events[*a1].po_before(&events[*a2]) {
+                edges.push((*a1, *a2))
+            }
+        }
+    }
+    edges.extend(co.windows(2).map(|ws| (ws[0], ws[1])));
+    for (source, r) in rf {
+        let fr_from = match source {
+            RfSource::Initial => 0,
+            RfSource::Write(w) => {
+                edges.push((*w, *r));
+                co_position(*w) + 1
+            }
+        };
+        edges.extend(co[fr_from..].iter().map(|w| (*r, *w)))
+    }
+
+    // Repeatedly remove accesses with no incoming edges; anything left over is on a cycle
+    let mut remaining: Vec<usize> = accesses.to_vec();
+    loop {
+        let before = remaining.clone();
+        remaining.retain(|a| edges.iter().any(|(from, to)| to == a && before.contains(from)));
+        if remaining.is_empty() {
+            return true;
+        } else if remaining.len() == before.len() {
+            return false;
+        }
+    }
+}
+
+/// A co order and rf for the accesses to a single location
+type LocationChoice = (Vec<usize>, Vec<(RfSource, usize)>);
+
+/// The possible choices of co order and rf for the accesses to a
+/// single location
+fn location_choices<B: BV>(
+    events: &[CandidateEvent<'_, B>],
+    accesses: &[usize],
+    opts: &EnumerateOpts,
+) -> Vec<LocationChoice> {
+    let writes: Vec<usize> = accesses.iter().copied().filter(|a| events[*a].is_write()).collect();
+    let reads: Vec<usize> = accesses.iter().copied().filter(|a| events[*a].is_read()).collect();
+
+    let mut sources = vec![RfSource::Initial];
+    sources.extend(writes.iter().map(|w| RfSource::Write(*w)));
+
+    let mut choices = Vec::new();
+    for co in permutations(&writes) {
+        let mut index = vec![0; reads.len()];
+        let max_index = vec![sources.len(); reads.len()];
+        loop {
+            let rf: Vec<(RfSource, usize)> = index.iter().zip(reads.iter()).map(|(i, r)| (sources[*i], *r)).collect();
+            if !opts.coherence || is_coherent(events, accesses, &co, &rf) {
+                choices.push((co.clone(), rf))
+            }
+            if increment_index(&mut index, &max_index, 0) {
+                break;
+            }
+        }
+    }
+    choices
+}
+
+/// An iterator over the candidate executions returned by [enumerate]
+pub struct CandidateExecutions<'ev, B> {
+    events: Rc<[CandidateEvent<'ev, B>]>,
+    choices: Vec<Vec<LocationChoice>>,
+    index: Vec<usize>,
+    out_of_bounds: bool,
+}
+
+impl<'ev, B: BV> Iterator for CandidateExecutions<'ev, B> {
+    type Item = CandidateExecution<'ev, B>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.out_of_bounds {
+            return None;
+        }
+
+        let mut rf = Vec::new();
+        let mut co = Vec::new();
+        for (choices, i) in self.choices.iter().zip(self.index.iter()) {
+            let (loc_co, loc_rf) = &choices[*i];
+            rf.extend_from_slice(loc_rf);
+            if !loc_co.is_empty() {
+                co.push(loc_co.clone())
+            }
+        }
+        rf.sort_by_key(|(_, r)| *r);
+
+        let max_index: Vec<usize> = self.choices.iter().map(|choices| choices.len()).collect();
+        self.out_of_bounds = increment_index(&mut self.index, &max_index, 0);
+
+        Some(CandidateExecution { events: self.events.clone(), rf, co })
+    }
+}
+
+/// Enumerate the candidate executions for a single trace for each
+/// thread (e.g. an item from [Candidates]), with every possible choice
+/// of rf and co. Accesses are grouped into locations by comparing their
+/// addresses syntactically, so two symbolic addresses are only treated
+/// as the same location if they are the same value, and mixed-size
+/// accesses are not taken into account.
+pub fn enumerate<'ev, B: BV, T: AsRef<[Event<B>]>>(
+    threads: &'ev [T],
+    opts: &EnumerateOpts,
+) -> CandidateExecutions<'ev, B> {
+    let events = memory_events(threads, opts);
+
+    let mut locations: Vec<(&Val<B>, Vec<usize>)> = Vec::new();
+    for (i, event) in events.iter().enumerate() {
+        match locations.iter_mut().find(|(address, _)| *address == event.address()) {
+            Some((_, accesses)) => accesses.push(i),
+            None => locations.push((event.address(), vec![i])),
+        }
+    }
+
+    let choices: Vec<_> = locations.iter().map(|(_, accesses)| location_choices(&events, accesses, opts)).collect();
+    let out_of_bounds = choices.iter().any(|choices| choices.is_empty());
+
+    CandidateExecutions { index: vec![0; choices.len()], events: events.into(), choices, out_of_bounds }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashSet;
+
+    use isla_lib::bitvector::b64::B64;
+    use isla_lib::smt::{ReadOpts, Sym, WriteOpts};
+
+    const X: u64 = 0x1000;
+
+    fn instr() -> Event<B64> {
+        Event::Instr(Val::Bits(B64::from_u32(0xd503201f)))
+    }
+
+    fn write(address: u64, data: u64) -> Event<B64> {
+        Event::WriteMem {
+            value: Sym::from_u32(0),
+            write_kind: Val::Unit,
+            address: Val::Bits(B64::from_u64(address)),
+            data: Val::Bits(B64::from_u64(data)),
+            bytes: 8,
+            tag_value: None,
+            opts: WriteOpts::default(),
+            region: "default",
+        }
+    }
+
+    fn read(address: u64, value: u32) -> Event<B64> {
+        Event::ReadMem {
+            value: Val::Symbolic(Sym::from_u32(value)),
+            read_kind: Val::Unit,
+            address: Val::Bits(B64::from_u64(address)),
+            bytes: 8,
+            tag_value: None,
+            opts: ReadOpts::default(),
+            region: "default",
+        }
+    }
+
+    /// A thread trace with an initialisation cycle followed by one
+    /// cycle per event
+    fn thread(events: Vec<Event<B64>>) -> Vec<Event<B64>> {
+        let mut trace = Vec::new();
+        for event in events {
+            trace.push(Event::Cycle);
+            trace.push(instr());
+            trace.push(event)
+        }
+        trace
+    }
+
+    type Names = (Vec<(String, String)>, Vec<(String, String)>);
+
+    fn names_of(candidate: &CandidateExecution<'_, B64>) -> Names {
+        let rf = candidate
+            .rf()
+            .iter()
+            .map(|(w, r)| (candidate.source_name(*w).to_string(), candidate.events()[*r].name.clone()))
+            .collect();
+        let co = candidate
+            .co()
+            .iter()
+            .map(|(w1, w2)| (candidate.events()[*w1].name.clone(), candidate.events()[*w2].name.clone()))
+            .collect();
+        (rf, co)
+    }
+
+    fn expected(rf: &[(&str, &str)], co: &[(&str, &str)]) -> Names {
+        let pairs = |pairs: &[(&str, &str)]| pairs.iter().map(|(a, b)| (a.to_string(), b.to_string())).collect();
+        (pairs(rf), pairs(co))
+    }
+
+    #[test]
+    fn test_two_writes_one_read() {
+        let threads = vec![thread(vec![write(X, 1)]), thread(vec![write(X, 2)]), thread(vec![read(X, 0)])];
+        let candidates: Vec<_> = enumerate(&threads, &EnumerateOpts::default()).collect();
+
+        let names: Vec<String> = candidates[0].events().iter().map(|ev| ev.name.clone()).collect();
+        assert_eq!(names, vec!["W1_1_0", "W1_1_1", "R1_1_2"]);
+
+        let found: HashSet<Names> = candidates.iter().map(names_of).collect();
+        assert_eq!(candidates.len(), 6);
+        let mut all = HashSet::new();
+        for co in [[("W1_1_0", "W1_1_1")], [("W1_1_1", "W1_1_0")]] {
+            for source in ["IW", "W1_1_0", "W1_1_1"] {
+                all.insert(expected(&[(source, "R1_1_2")], &co));
+            }
+        }
+        assert_eq!(found, all)
+    }
+
+    #[test]
+    fn test_forwarding() {
+        // Thread 0 writes then reads x, thread 1 writes x
+        let threads = vec![thread(vec![write(X, 1), read(X, 0)]), thread(vec![write(X, 2)])];
+        let found: HashSet<Names> = enumerate(&threads, &EnumerateOpts::default()).map(|c| names_of(&c)).collect();
+
+        // The read can never see the initial state, and can only see
+        // the other thread's write if it is coherence-after its own
+        let all: HashSet<Names> = [
+            expected(&[("W1_1_0", "R2_1_0")], &[("W1_1_0", "W1_1_1")]),
+            expected(&[("W1_1_1", "R2_1_0")], &[("W1_1_0", "W1_1_1")]),
+            expected(&[("W1_1_0", "R2_1_0")], &[("W1_1_1", "W1_1_0")]),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(found, all);
+
+        let opts = EnumerateOpts { coherence: false, ..EnumerateOpts::default() };
+        assert_eq!(enumerate(&threads, &opts).count(), 6)
+    }
+
+    #[test]
+    fn test_po_later_write() {
+        // A read cannot read from a write later in its own thread
+        let threads = vec![thread(vec![read(X, 0), write(X, 1)])];
+        let found: Vec<Names> = enumerate(&threads, &EnumerateOpts::default()).map(|c| names_of(&c)).collect();
+        assert_eq!(found, vec![expected(&[("IW", "R1_1_0")], &[])])
+    }
+
+    #[test]
+    fn test_value_constraints() {
+        let threads = vec![thread(vec![write(X, 1)]), thread(vec![read(X, 0), read(0x2000, 1)])];
+        for candidate in enumerate(&threads, &EnumerateOpts::default()) {
+            let constraints = candidate.value_constraints();
+            assert_eq!(constraints.len(), 2);
+            assert_eq!(constraints[0].read, &Val::Symbolic(Sym::from_u32(0)));
+            match (candidate.rf()[0].0, &constraints[0].source) {
+                (RfSource::Write(_), ValueSource::Write(v)) => assert_eq!(*v, &Val::Bits(B64::from_u64(1))),
+                (RfSource::Initial, ValueSource::Initial { address, bytes: 8 }) => {
+                    assert_eq!(*address, &Val::Bits(B64::from_u64(X)))
+                }
+                (source, constraint) => panic!("{:?} does not match {:?}", source, constraint),
+            }
+            assert!(matches!(constraints[1].source, ValueSource::Initial { .. }))
+        }
+        assert_eq!(enumerate(&threads, &EnumerateOpts::default()).count(), 2)
+    }
+
+    #[test]
+    fn test_no_events() {
+        let threads: Vec<Vec<Event<B64>>> = vec![vec![], vec![Event::Cycle, instr()]];
+        let candidates: Vec<_> = enumerate(&threads, &EnumerateOpts::default()).collect();
+        assert_eq!(candidates.len(), 1);
+        assert!(candidates[0].events().is_empty())
+    }
+}
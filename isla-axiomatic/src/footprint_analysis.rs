@@ -562,7 +562,13 @@ where
     let queue = Arc::new(SegQueue::new());
 
     let now = Instant::now();
-    executor::start_multi(num_threads, None, tasks, arch.shared_state, queue.clone(), &executor::footprint_collector);
+    executor::start_multi(
+        tasks,
+        arch.shared_state,
+        queue.clone(),
+        executor::MultiCollector::Plain(&executor::footprint_collector),
+        &executor::MultiOpts::new(num_threads),
+    );
     log!(log::VERBOSE, &format!("Footprint analysis symbolic execution took: {}ms", now.elapsed().as_millis()));
 
     loop {
@@ -43,16 +43,20 @@ use crate::litmus::Litmus;
 use crate::sexp::SexpVal;
 use crate::smt_model::{pairwise::Pairs, Model, ModelParseError};
 
-mod ascii_backend;
 pub mod graph_events;
 pub mod graph_opts;
 pub mod grid_layout;
 mod gv_backend;
+pub mod text_measure;
 
-pub use ascii_backend::draw_graph_ascii;
 pub use graph_events::*;
 pub use graph_opts::*;
-pub use gv_backend::draw_graph_gv;
+pub use gv_backend::draw_graphs_html;
+pub use gv_backend::{
+    draw_graph_ascii, draw_graph_diff_gv, draw_graph_graphml, draw_graph_gv, draw_graph_svg, draw_graphs_gv,
+    dump_graph_layout,
+};
+pub use text_measure::{FontMetrics, HeuristicMeasure, TextMeasure};
 
 #[derive(Debug)]
 pub enum GraphError<'s> {
@@ -86,6 +90,13 @@ fn regname_val<B: BV>(ev: &Event<B>, symtab: &Symtab) -> Option<Val<B>> {
     regnamestr.map(Val::String)
 }
 
+/// Whether to draw a register read or write event, which we do for
+/// the registers in `show_registers`, and in debug mode for those in
+/// `show_regs`
+fn is_shown_register(regname: &str, opts: &GraphOpts) -> bool {
+    opts.shows_register(regname) || (opts.debug && opts.show_regs.contains(regname))
+}
+
 /// get tag (T | R | IF | etc) from a read event
 /// isla simply outputs one ReadMem() event for all of them.
 fn tag_from_read_event<'a, B: BV>(ev: &AxEvent<B>) -> &'a str {
@@ -118,7 +129,11 @@ fn concrete_graph_from_candidate<'m, B: BV>(
     let combined_events: Vec<_> = if opts.debug {
         exec.smt_events.iter().chain(exec.other_events.iter()).collect()
     } else {
-        exec.smt_events.iter().collect()
+        // outside of debug mode the only other events we draw are those for `show_registers`
+        let shown_register = |ev: &&AxEvent<B>| {
+            ev.base.last().and_then(|ev| register_name_string(ev, symtab)).is_some_and(|reg| opts.shows_register(&reg))
+        };
+        exec.smt_events.iter().chain(exec.other_events.iter().filter(shown_register)).collect()
     };
 
     // this re-computes the Translations even though the smt generation ages ago already did it
@@ -139,7 +154,7 @@ fn concrete_graph_from_candidate<'m, B: BV>(
             }
             Some(Event::ReadReg(_name, _, val)) => {
                 let regnamestr = register_name_string(ev.unwrap(), symtab).unwrap();
-                if opts.debug && opts.show_regs.contains(&regnamestr) {
+                if is_shown_register(&regnamestr, opts) {
                     let fieldval = regname_val(event.base().unwrap(), symtab).unwrap();
                     let graphvalue = GraphValue::from_vals("Rreg", Some(&fieldval), 8, Some(val));
                     events.insert(
@@ -150,7 +165,7 @@ fn concrete_graph_from_candidate<'m, B: BV>(
             }
             Some(Event::WriteReg(_name, _, val)) => {
                 let regnamestr = register_name_string(ev.unwrap(), symtab).unwrap();
-                if opts.debug && opts.show_regs.contains(&regnamestr) {
+                if is_shown_register(&regnamestr, opts) {
                     let fieldval = regname_val(event.base().unwrap(), symtab).unwrap();
                     let graphvalue = GraphValue::from_vals("Wreg", Some(&fieldval), 8, Some(val));
                     events.insert(
@@ -181,9 +196,37 @@ fn concrete_graph_from_candidate<'m, B: BV>(
         opts: opts.clone(),
         litmus_opts: litmus.graph_opts.clone(),
         names: names.to_u64(),
+        warnings: vec![],
     })
 }
 
+/// The relations to draw, without duplicates. If the command line
+/// names relations those take priority, then with `show_all` the
+/// builtin relations are drawn along with every relation shown by the
+/// litmus test or memory model. Otherwise the shows from the litmus
+/// test's graph section are used, then the `show` declarations from
+/// the memory model, and only without any of these the builtins.
+fn shown_relations<'a>(opts: &'a GraphOpts, litmus_shows: &'a [String], builtin_relations: &[&'a str]) -> Vec<&'a str> {
+    let cmdline_shows: &[String] = opts.shows.as_deref().unwrap_or_default();
+    let rels: Vec<&str> = if !cmdline_shows.is_empty() {
+        cmdline_shows.iter().map(String::as_str).collect()
+    } else if opts.show_all {
+        builtin_relations
+            .iter()
+            .copied()
+            .chain(litmus_shows.iter().chain(opts.model_shows.iter()).map(String::as_str))
+            .collect()
+    } else if !litmus_shows.is_empty() {
+        litmus_shows.iter().map(String::as_str).collect()
+    } else if !opts.model_shows.is_empty() {
+        opts.model_shows.iter().map(String::as_str).collect()
+    } else {
+        builtin_relations.to_vec()
+    };
+    let mut seen = HashSet::new();
+    rels.into_iter().filter(|rel| seen.insert(*rel)).collect()
+}
+
 /// run an interpretation function over the symbolic events
 /// to generate new nodes in the graph
 #[allow(clippy::too_many_arguments)]
@@ -200,7 +243,7 @@ fn update_graph_symbolic_events<'m, 'ev, Fev, Frel, B>(
 where
     B: BV,
     Fev: Fn(&mut Option<Model<'_, 'ev, B>>, GraphValue, &str, &str, &Val<B>, u32, &Val<B>) -> GraphValue,
-    Frel: Fn(&mut Option<Model<'_, 'ev, B>>, &str) -> GraphRelation,
+    Frel: Fn(&mut Option<Model<'_, 'ev, B>>, &str, &mut Vec<String>) -> GraphRelation,
 {
     let mut builtin_relations = vec!["iio", "po", "rf", "co", "addr", "data", "ctrl"];
     if ifetch {
@@ -211,36 +254,21 @@ where
     let events: Vec<_> = if opts.debug {
         exec.smt_events.iter().chain(exec.other_events.iter()).collect()
     } else {
-        exec.smt_events.iter().collect()
+        // any register events drawn for `show_registers` are already in the graph
+        exec.smt_events.iter().chain(exec.other_events.iter().filter(|ev| g.events.contains_key(&ev.name))).collect()
     };
 
     let mut event_names: Vec<&'ev str> = events.iter().map(|ev| ev.name.as_ref()).collect();
     event_names.push("IW");
 
-    // collect all relations from the litmus file, builtins and from the cat `show`s
-    // nubing away duplicates
     log!(log::GRAPH, "collecting and interpreting show relations from z3 model");
-
-    // if the cmdline has args, those take priority
-    // otherwise if the litmus specifies a meta graph section with shows, use those
-    // otherwise just use the `show ...` commands from the cat itself
-    let cmdline_shows: Vec<String> = opts.shows.clone().unwrap_or_default();
     let litmus_shows: Vec<String> = litmus.graph_opts.shows.clone().unwrap_or_default();
-    let cat_shows: Option<Vec<String>> = None;
-    let all_rels: HashSet<&str> = if !cmdline_shows.is_empty() {
-        cmdline_shows.iter().map(String::as_str).collect()
-    } else if !litmus_shows.is_empty() {
-        litmus_shows.iter().map(String::as_str).collect()
-    } else if cat_shows.is_some() {
-        todo!("shows from cat file")
-    } else {
-        builtin_relations.into_iter().collect()
-    };
+    let all_rels = shown_relations(opts, &litmus_shows, &builtin_relations);
 
     log!(log::GRAPH, format!("collected {} shows: {:?}", all_rels.len(), all_rels));
 
     for rel in all_rels {
-        g.relations.push(interpret_rel(&mut model, rel).simplify());
+        g.relations.push(interpret_rel(&mut model, rel, &mut g.warnings).simplify());
     }
 
     log!(log::GRAPH, "finished interpreting, now populating remaining symbolic entries in graph");
@@ -271,30 +299,26 @@ where
                     );
                 }
             }
-            Some(Event::ReadReg(_, _, val)) => {
-                if opts.debug && val.is_symbolic() {
-                    if let Some(gevent) = g.events.remove(&event.name) {
-                        let gval = gevent.value.unwrap();
-                        let tempval: Val<B> = Val::Unit;
-                        let graphvalue = interpret(&mut model, gval, &event.name, "Rreg", &tempval, 8, val);
-                        g.events.insert(
-                            event.name.clone(),
-                            GraphEvent::from_axiomatic(event, &litmus.objdump, Some(graphvalue)),
-                        );
-                    }
+            Some(Event::ReadReg(_, _, val)) if val.is_symbolic() => {
+                if let Some(gevent) = g.events.remove(&event.name) {
+                    let gval = gevent.value.unwrap();
+                    let tempval: Val<B> = Val::Unit;
+                    let graphvalue = interpret(&mut model, gval, &event.name, "Rreg", &tempval, 8, val);
+                    g.events.insert(
+                        event.name.clone(),
+                        GraphEvent::from_axiomatic(event, &litmus.objdump, Some(graphvalue)),
+                    );
                 }
             }
-            Some(Event::WriteReg(_, _, val)) => {
-                if opts.debug && val.is_symbolic() {
-                    if let Some(gevent) = g.events.remove(&event.name) {
-                        let gval = gevent.value.unwrap();
-                        let tempval: Val<B> = Val::Unit;
-                        let graphvalue = interpret(&mut model, gval, &event.name, "Wreg", &tempval, 8, val);
-                        g.events.insert(
-                            event.name.clone(),
-                            GraphEvent::from_axiomatic(event, &litmus.objdump, Some(graphvalue)),
-                        );
-                    }
+            Some(Event::WriteReg(_, _, val)) if val.is_symbolic() => {
+                if let Some(gevent) = g.events.remove(&event.name) {
+                    let gval = gevent.value.unwrap();
+                    let tempval: Val<B> = Val::Unit;
+                    let graphvalue = interpret(&mut model, gval, &event.name, "Wreg", &tempval, 8, val);
+                    g.events.insert(
+                        event.name.clone(),
+                        GraphEvent::from_axiomatic(event, &litmus.objdump, Some(graphvalue)),
+                    );
                 }
             }
             _ => (),
@@ -303,6 +327,33 @@ where
 
     log!(log::GRAPH, "updating graph event kinds");
     update_event_kinds(&mut g.events);
+
+    if opts.derive_fr {
+        log!(log::GRAPH, "deriving fr from rf and co");
+        g.derive_fr()
+    }
+
+    if opts.collapse_translations {
+        log!(log::GRAPH, "collapsing translation table walks");
+        g.collapse_translations()
+    }
+
+    g.split_relations_by_address();
+
+    let mut unknown_labels: Vec<&String> =
+        opts.edge_labels.keys().filter(|rel| !g.relations.iter().any(|r| &r.name == *rel)).collect();
+    unknown_labels.sort();
+    for rel in unknown_labels {
+        g.warnings.push(format!("an edge label is given for relation '{}', which is not in the graph", rel))
+    }
+
+    let mut unknown_styles: Vec<&String> =
+        opts.relation_styles.keys().filter(|rel| !g.relations.iter().any(|r| &r.name == *rel)).collect();
+    unknown_styles.sort();
+    for rel in unknown_styles {
+        g.warnings.push(format!("a style is given for relation '{}', which is not in the graph", rel))
+    }
+
     g
 }
 
@@ -356,8 +407,8 @@ pub fn graph_from_unsat<'ir, 'ev, 'm, B: BV>(
                     gv.value.or_else(|| Some(value.to_string(shared_state))),
                 )
             },
-            |_m, rel_name| {
-                let (rel_name, relty) = parse_relname_opt(rel_name);
+            |_m, rel_name, _warnings| {
+                let (rel_name, relty) = parse_relname_opt(rel_name, opts);
                 // when the smt was unsatisfiable we only have the relations from the footprint
                 // we can still enumerate those and draw them
                 if let Some(rel) = footprint_relations.get(rel_name) {
@@ -380,6 +431,26 @@ pub fn graph_from_unsat<'ir, 'ev, 'm, B: BV>(
     }
 }
 
+/// Read the edges of a relation from the model. If the relation can't
+/// be interpreted, it is drawn as empty and a warning is recorded
+/// instead, as z3 omits functions it did not need to decide
+/// satisfiability unless model completion is enabled.
+fn relation_from_model<B: BV>(
+    model: &mut Model<'_, '_, B>,
+    rel_name: &str,
+    ty: RelType,
+    warnings: &mut Vec<String>,
+) -> GraphRelation {
+    let edges: HashSet<(String, String)> = match model.interpret_rel(rel_name) {
+        Ok(edges) => edges.iter().map(|(from, to)| ((*from).to_string(), (*to).to_string())).collect(),
+        Err(err) => {
+            warnings.push(format!("relation '{}' is drawn as empty: {}", rel_name, err));
+            HashSet::new()
+        }
+    };
+    GraphRelation { name: rel_name.to_string(), ty, edges: edges.clone(), all_edges: edges }
+}
+
 /// Generate a graph from the output of a Z3 invocation that returned sat.
 #[allow(clippy::too_many_arguments)]
 pub fn graph_from_z3_output<'m, B: BV>(
@@ -447,30 +518,10 @@ pub fn graph_from_z3_output<'m, B: BV>(
                     unreachable!()
                 }
             },
-            |m, rel_name| {
-                let (rel_name, relty) = parse_relname_opt(rel_name);
+            |m, rel_name, warnings| {
+                let (rel_name, relty) = parse_relname_opt(rel_name, opts);
                 if let Some(m) = m {
-                    match m.interpret_rel(rel_name) {
-                        Ok(edges) => {
-                            let edges: HashSet<(String, String)> =
-                                edges.iter().map(|(from, to)| ((*from).to_string(), (*to).to_string())).collect();
-                            GraphRelation {
-                                name: (*rel_name).to_string(),
-                                ty: relty,
-                                edges: edges.clone(),
-                                all_edges: edges,
-                            }
-                        }
-                        Err(err) => {
-                            eprintln!("Failed to interpret relation '{}': {}", rel_name, err);
-                            GraphRelation {
-                                name: (*rel_name).to_string(),
-                                ty: relty,
-                                edges: HashSet::new(),
-                                all_edges: HashSet::new(),
-                            }
-                        }
-                    }
+                    relation_from_model(m, rel_name, relty, warnings)
                 } else {
                     unreachable!()
                 }
@@ -478,3 +529,209 @@ pub fn graph_from_z3_output<'m, B: BV>(
         )),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use isla_lib::bitvector::b64::B64;
+
+    use isla_mml::memory_model;
+
+    use super::*;
+    use crate::axiomatic::ThreadId;
+    use crate::litmus::LitmusGraphOpts;
+
+    #[test]
+    fn test_missing_relation_warning() {
+        // co is one of the builtin relations, but the model has no definition for it
+        let smtlib = "(model (define-fun rf ((x!0 Event) (x!1 Event)) Bool (and (= x!0 W0) (= x!1 R1))))";
+        let evs = ["IW", "W0", "R1"];
+        let mut model = Model::<B64>::parse(&evs, smtlib).unwrap();
+        let mut warnings = Vec::new();
+
+        let relations: Vec<GraphRelation> = ["rf", "co"]
+            .iter()
+            .map(|rel| {
+                let ty = RelType { trans: RelTransType::Normal, preferred: Vec::new() };
+                relation_from_model(&mut model, rel, ty, &mut warnings)
+            })
+            .collect();
+
+        assert_eq!(relations[0].edges.len(), 1);
+        assert!(relations[0].edges.contains(&("W0".to_string(), "R1".to_string())));
+        assert!(relations[1].edges.is_empty());
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("relation 'co' is drawn as empty"), "{}", warnings[0]);
+    }
+
+    fn relation(name: &str, edges: &[(&str, &str)]) -> GraphRelation {
+        let edges: HashSet<(String, String)> =
+            edges.iter().map(|(from, to)| (from.to_string(), to.to_string())).collect();
+        GraphRelation {
+            name: name.to_string(),
+            ty: RelType { trans: RelTransType::Normal, preferred: Vec::new() },
+            edges: edges.clone(),
+            all_edges: edges,
+        }
+    }
+
+    /// A fault event, which has no value
+    pub(super) fn fault_event(thread_id: ThreadId, po: usize, iio: usize) -> GraphEvent {
+        GraphEvent {
+            instr: None,
+            opcode: "fault".to_string(),
+            po,
+            iio,
+            thread_id,
+            name: format!("R{}_{}_{}", thread_id, po, iio),
+            value: None,
+            event_kind: GraphEventKind::Barrier(BarrierKind::Fault),
+            trace_origin: None,
+        }
+    }
+
+    /// A graph with just the given events and relations
+    pub(super) fn test_graph(events: Vec<GraphEvent>, relations: Vec<GraphRelation>, opts: &GraphOpts) -> Graph {
+        Graph {
+            events: events.into_iter().map(|ev| (ev.name.clone(), ev)).collect(),
+            sets: vec![],
+            relations,
+            show: vec![],
+            opts: opts.clone(),
+            litmus_opts: LitmusGraphOpts { force_show_events: None, shows: None },
+            names: GraphValueNames {
+                s1_ptable_names: HashMap::new(),
+                s2_ptable_names: HashMap::new(),
+                pa_names: HashMap::new(),
+                ipa_names: HashMap::new(),
+                va_names: HashMap::new(),
+                value_names: HashMap::new(),
+                paddr_names: HashMap::new(),
+            },
+            warnings: vec![],
+        }
+    }
+
+    /// A graph of memory events, given as (name, kind, address) with
+    /// `W` for writes and `R` for reads, and the relations between them
+    fn accesses_graph(accesses: &[(&str, &str, &str)], relations: Vec<GraphRelation>) -> Graph {
+        let events = accesses
+            .iter()
+            .map(|(name, kind, addr)| GraphEvent {
+                opcode: "".to_string(),
+                name: name.to_string(),
+                value: Some(GraphValue::from_fields(kind, Some(addr.to_string()), None, 8, Some("0x1".to_string()))),
+                event_kind: match *kind {
+                    "W" => GraphEventKind::WriteMem(WriteKind { to_translation_table_entry: None }),
+                    _ => GraphEventKind::ReadMem,
+                },
+                ..fault_event(0, 0, 0)
+            })
+            .collect();
+        test_graph(events, relations, &GraphOpts::default())
+    }
+
+    #[test]
+    fn test_model_shows() {
+        let mut symtab = memory_model::Symtab::new();
+        let mut arena = memory_model::ExpArena::new();
+        let mm =
+            memory_model::MemoryModel::from_string("test.cat", 0, "show po, rf\n", &mut arena, &mut symtab).unwrap();
+        let mut opts = GraphOpts {
+            model_shows: mm.shows().map(|name| symtab[name].to_string()).collect(),
+            ..GraphOpts::default()
+        };
+
+        let builtins = ["iio", "po", "rf", "co"];
+        let rels = shown_relations(&opts, &[], &builtins);
+        assert_eq!(rels, vec!["po", "rf"]);
+
+        let smtlib = "(model
+            (define-fun po ((x!0 Event) (x!1 Event)) Bool (and (= x!0 W0) (= x!1 R0)))
+            (define-fun rf ((x!0 Event) (x!1 Event)) Bool (and (= x!0 W1) (= x!1 R0)))
+            (define-fun co ((x!0 Event) (x!1 Event)) Bool (and (= x!0 W0) (= x!1 W1))))";
+        let mut model = Model::<B64>::parse(&["IW", "W0", "W1", "R0"], smtlib).unwrap();
+        let mut warnings = Vec::new();
+        let relations = rels
+            .iter()
+            .map(|rel| {
+                let ty = RelType { trans: RelTransType::Normal, preferred: Vec::new() };
+                relation_from_model(&mut model, rel, ty, &mut warnings)
+            })
+            .collect();
+        let mut g = accesses_graph(&[("W0", "W", "0x1000"), ("W1", "W", "0x1000"), ("R0", "R", "0x1000")], relations);
+        g.opts.debug = true;
+        let mut output = Vec::new();
+        draw_graph_gv(&mut output, &g, &g.opts).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("label=\" rf \""));
+        assert!(output.contains("taillabel=\" po \""));
+        assert!(!output.contains(" co "));
+
+        opts.show_all = true;
+        assert_eq!(shown_relations(&opts, &[], &builtins), builtins.to_vec());
+        let cmdline_shows = vec!["co".to_string()];
+        opts.shows = Some(cmdline_shows);
+        assert_eq!(shown_relations(&opts, &["rf".to_string()], &builtins), vec!["co"]);
+    }
+
+    #[test]
+    fn test_derive_fr() {
+        let mut g = accesses_graph(
+            &[
+                ("W0", "W", "0x1000"),
+                ("W1", "W", "0x1000"),
+                ("W2", "W", "0x2000"),
+                ("R0", "R", "0x1000"),
+                ("R1", "R", "0x1000"),
+                ("R2", "R", "0x2000"),
+            ],
+            vec![
+                relation("rf", &[("IW", "R0"), ("W0", "R1"), ("W2", "R2")]),
+                relation("co", &[("IW", "W0"), ("IW", "W1"), ("IW", "W2"), ("W0", "W1")]),
+            ],
+        );
+
+        // the read from the initial state is before both writes to its
+        // address, and the last write to an address has no fr after it
+        g.derive_fr();
+        let fr = g.relations.iter().find(|rel| rel.name == "fr").unwrap();
+        let expected: HashSet<(String, String)> = [("R0", "W0"), ("R0", "W1"), ("R1", "W1")]
+            .iter()
+            .map(|(from, to)| (from.to_string(), to.to_string()))
+            .collect();
+        assert_eq!(fr.all_edges, expected);
+
+        // an fr from the model is left alone
+        g.relations.retain(|rel| rel.name != "fr");
+        g.relations.push(relation("fr", &[]));
+        g.derive_fr();
+        assert_eq!(g.relations.iter().filter(|rel| rel.name == "fr").count(), 1);
+        assert!(g.relations.iter().all(|rel| rel.name != "fr" || rel.all_edges.is_empty()));
+    }
+
+    #[test]
+    fn test_split_relations_by_address() {
+        let mut g = accesses_graph(
+            &[("W0", "W", "0x2000"), ("W1", "W", "0x2000"), ("W2", "W", "0x1000"), ("W3", "W", "?")],
+            vec![
+                relation("rf", &[("W0", "W1")]),
+                relation("co", &[("IW", "W0"), ("W0", "W1"), ("IW", "W2"), ("W3", "W0")]),
+            ],
+        );
+        g.names.paddr_names.insert(0x2000, "y".to_string());
+        g.opts.split_by_address.insert("co".to_string());
+        g.opts.relation_styles.insert("co[y]".to_string(), "black".parse().unwrap());
+        g.split_relations_by_address();
+
+        let split: Vec<(&str, usize)> = g.relations.iter().map(|rel| (rel.name.as_str(), rel.edges.len())).collect();
+        assert_eq!(split, vec![("rf", 1), ("co[0x1000]", 1), ("co[y]", 2), ("co[?]", 1)]);
+        assert!(g.relations[3].edges.contains(&("W3".to_string(), "W0".to_string())));
+
+        // the colors go in order of address, except where already set
+        let color = |rel: &str| g.opts.relation_styles[rel].color.clone();
+        assert_eq!(color("co[0x1000]"), GraphOpts::SPLIT_ADDRESS_COLORS[0]);
+        assert_eq!(color("co[y]"), "black");
+        assert_eq!(color("co[?]"), GraphOpts::SPLIT_UNKNOWN_ADDRESS_COLOR);
+        assert!(!g.opts.relation_styles.contains_key("rf"));
+    }
+}
@@ -1,20 +1,20 @@
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use isla_lib::bitvector::BV;
 use isla_lib::ir::*;
 use isla_lib::smt::Event;
 
 use crate::axiomatic::relations;
-use crate::axiomatic::{AxEvent, ThreadId};
+use crate::axiomatic::{AxEvent, ThreadId, TraceOrigin};
 use crate::litmus::LitmusGraphOpts;
 use crate::litmus::{instruction_from_objdump, Objdump};
 use crate::page_table::PageAttrs;
 
 use super::graph_opts::*;
-use super::grid_layout::simplify_edges;
+use super::grid_layout::{event_in_shows, simplify_edges};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct GraphValueNames<T> {
     /// names associated with translation tables and their entries
     pub s1_ptable_names: HashMap<T, String>,
@@ -103,6 +103,8 @@ pub enum GraphEventKind {
     ReadMem,
     WriteMem(WriteKind),
     Translate(TranslateKind),
+    // all the translate events of an instruction, collapsed into one
+    TranslateWalk,
     ReadReg,
     WriteReg,
     Barrier(BarrierKind),
@@ -122,6 +124,8 @@ pub struct GraphEvent {
     pub name: String,
     pub value: Option<GraphValue>,
     pub event_kind: GraphEventKind,
+    /// where the event came from in the traces of the candidate
+    pub trace_origin: Option<TraceOrigin>,
 }
 
 pub fn event_kind<B: BV>(_objdump: &Objdump, ev: &AxEvent<B>) -> GraphEventKind {
@@ -352,6 +356,7 @@ impl GraphEvent {
             name: ev.name.clone(),
             value,
             event_kind: event_kind(objdump, ev),
+            trace_origin: Some(ev.trace_origin),
         }
     }
 }
@@ -360,6 +365,30 @@ pub fn format_ev_label(ev_label: &(String, String)) -> String {
     format!("{}{}", ev_label.0, ev_label.1)
 }
 
+/// Break a label into lines of at most `max_width` characters at word
+/// boundaries, with a word longer than that getting a line of its
+/// own. Lines are separated by newlines, which the dot backend draws
+/// as left-justified (`\l`) line breaks.
+pub fn wrap_label(label: String, max_width: Option<usize>) -> String {
+    let max_width = match max_width {
+        Some(max_width) if label.len() > max_width => max_width,
+        _ => return label,
+    };
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in label.split(' ') {
+        if !line.is_empty() && line.len() + 1 + word.len() > max_width {
+            lines.push(std::mem::take(&mut line))
+        } else if !line.is_empty() {
+            line.push(' ')
+        }
+        line.push_str(word)
+    }
+    lines.push(line);
+    lines.join("\n")
+}
+
 impl GraphEvent {
     fn _name_bag_for_rw_event<'names>(
         &self,
@@ -405,6 +434,24 @@ impl GraphEvent {
         }
     }
 
+    // format the node label of a collapsed translation table walk:
+    // label="T walk x→0x205800"
+    fn fmt_translate_walk(&self, opts: &GraphOpts, ev_lab: &str, names: &GraphValueNames<u64>) -> String {
+        if let Some(value) = &self.value {
+            let vastr = match &value.virtual_address {
+                Some(va) => named_str_from_addr(opts, &names.va_names, va),
+                None => "va".to_string(),
+            };
+            let pastr = match &value.address {
+                Some(pa) => named_str_from_addr(opts, &names.paddr_names, pa),
+                None => "pa".to_string(),
+            };
+            format!("{}: T walk {}→{}", ev_lab, vastr, pastr)
+        } else {
+            format!("{}: T walk", ev_lab)
+        }
+    }
+
     // format the node label with all debug info:
     // label="W_00_000: "ldr x2, [x3]": T 0x205800 (8): 3146947"
     #[allow(dead_code)]
@@ -438,9 +485,10 @@ impl GraphEvent {
     ) -> String {
         let instr = self.instr.as_ref().unwrap_or(&self.opcode);
         let ev_lab = format_ev_label(ev_label);
-        match self.event_kind {
+        let label = match self.event_kind {
             GraphEventKind::Barrier(BarrierKind::Fault) => format!("{}: {}: Fault", ev_lab, instr),
             GraphEventKind::Barrier(BarrierKind::Fence) => format!("{}: {}", ev_lab, self.fmt_barrier(opts, names)),
+            GraphEventKind::TranslateWalk => self.fmt_translate_walk(opts, &ev_lab, names),
             GraphEventKind::CacheOp => {
                 let q = "?".to_string();
                 let addr = if let Some(value) = &self.value { value.value.as_ref().unwrap_or(&q) } else { &q };
@@ -496,7 +544,8 @@ impl GraphEvent {
                     format!("{}: \"{}\"", ev_lab, instr)
                 }
             }
-        }
+        };
+        wrap_label(label, opts.max_label_width)
     }
 
     // format the node label in half form:
@@ -510,9 +559,10 @@ impl GraphEvent {
     ) -> String {
         let instr = self.instr.as_ref().unwrap_or(&self.opcode);
         let ev_lab = format!("{}{}", ev_label.0, ev_label.1);
-        match &self.event_kind {
+        let label = match &self.event_kind {
             GraphEventKind::Barrier(BarrierKind::Fault) => format!("{}: {}: Fault", ev_lab, instr),
             GraphEventKind::Barrier(BarrierKind::Fence) => format!("{}: {}", ev_lab, self.fmt_barrier(opts, names)),
+            GraphEventKind::TranslateWalk => self.fmt_translate_walk(opts, &ev_lab, names),
             _ => {
                 if let Some(value) = &self.value {
                     let q = "?".to_string();
@@ -540,7 +590,8 @@ impl GraphEvent {
                     format!("??{}:\"{}\"", self.name, instr)
                 }
             }
-        }
+        };
+        wrap_label(label, opts.max_label_width)
     }
 
     // format the node label in shortform:
@@ -557,6 +608,7 @@ impl GraphEvent {
         match &self.event_kind {
             GraphEventKind::Barrier(BarrierKind::Fault) => format!("{}: Fault", ev_lab),
             GraphEventKind::Barrier(BarrierKind::Fence) => format!("{}: {}", ev_lab, self.fmt_barrier(opts, names)),
+            GraphEventKind::TranslateWalk => self.fmt_translate_walk(opts, &ev_lab, names),
             GraphEventKind::Translate(TranslateKind { stage, level, .. }) if opts.squash_translation_labels => {
                 format!("{}: Ts{}l{}", ev_lab, stage, level)
             }
@@ -576,6 +628,20 @@ impl GraphEvent {
             }
         }
     }
+
+    /// The name of the address this event accesses, as shown in its
+    /// node label, falling back to the address in hex if it has no name
+    pub fn address_name(&self, opts: &GraphOpts, names: &GraphValueNames<u64>) -> Option<String> {
+        let addr = self.value.as_ref()?.address.as_ref()?;
+        Some(named_str_from_addr(opts, self._name_bag_for_addr(names), addr))
+    }
+
+    /// The value this event reads or writes, as shown in its node
+    /// label
+    pub fn value_name(&self, opts: &GraphOpts, names: &GraphValueNames<u64>) -> Option<String> {
+        let value = self.value.as_ref()?.value.as_ref()?;
+        Some(named_str_from_value(opts, self._name_bag_for_value(names), value))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -610,6 +676,10 @@ pub struct Graph {
     pub opts: GraphOpts,              // options from cmdline
     pub litmus_opts: LitmusGraphOpts, // options from litmus file itself
     pub names: GraphValueNames<u64>,
+    /// Problems encountered while building the graph from the solver
+    /// output, such as relations missing from the model, which mean
+    /// the graph may be incomplete
+    pub warnings: Vec<String>,
 }
 
 fn extra_color(rel: &str) -> &'static str {
@@ -672,7 +742,7 @@ pub struct RelType {
 }
 
 /// given a relation name return (base, type)
-pub fn parse_relname_opt(rel: &str) -> (&str, RelType) {
+pub fn parse_relname_opt<'a>(rel: &'a str, opts: &GraphOpts) -> (&'a str, RelType) {
     let mut relname = rel;
     let mut relty = RelType { trans: RelTransType::Normal, preferred: Vec::new() };
 
@@ -687,7 +757,7 @@ pub fn parse_relname_opt(rel: &str) -> (&str, RelType) {
     } else {
         let trans_reductions: HashSet<String> =
             GraphOpts::DEFAULT_REL_TRANSITIVE_REDUCE.iter().cloned().map(String::from).collect();
-        if trans_reductions.contains(rel) {
+        if trans_reductions.contains(rel) || opts.transitive_reduce.contains(rel) {
             relty.trans = RelTransType::TransReduction;
         }
 
@@ -701,6 +771,9 @@ pub fn parse_relname_opt(rel: &str) -> (&str, RelType) {
     (relname, relty)
 }
 
+/// An address as a number, along with the name it is drawn with
+type ConcreteAddress = (u64, String);
+
 impl Graph {
     pub fn between(&self, ev1: String, ev2: String) -> Vec<&GraphRelation> {
         let mut rels = Vec::new();
@@ -712,4 +785,195 @@ impl Graph {
         }
         rels
     }
+
+    /// The position in the candidate's traces of the event the named
+    /// graph event was built from
+    pub fn trace_origin(&self, event_name: &str) -> Option<TraceOrigin> {
+        self.events.get(event_name).and_then(|ev| ev.trace_origin)
+    }
+
+    /// The graph event built from the trace event at `origin`, if it
+    /// is part of the graph
+    pub fn event_from_trace(&self, origin: TraceOrigin) -> Option<&GraphEvent> {
+        self.events.values().find(|ev| ev.trace_origin == Some(origin))
+    }
+
+    /// Add an `fr` relation derived from `rf` and `co`, for models
+    /// which don't define it but have both of those. Each read is
+    /// from-read before the writes to its address which are
+    /// coherence-after the write it read from, which for a read from
+    /// the initial state is every write to that address.
+    pub fn derive_fr(&mut self) {
+        if self.relations.iter().any(|rel| rel.name == "fr") {
+            return;
+        }
+        let (rf, co) = match (
+            self.relations.iter().find(|rel| rel.name == "rf"),
+            self.relations.iter().find(|rel| rel.name == "co"),
+        ) {
+            (Some(rf), Some(co)) => (rf, co),
+            _ => return,
+        };
+
+        let address = |name: &str| self.events.get(name)?.value.as_ref()?.address.as_ref();
+        let mut edges: HashSet<(String, String)> = HashSet::new();
+        for (write, read) in &rf.all_edges {
+            let addr = match address(read) {
+                Some(addr) => addr,
+                None => continue,
+            };
+            let later_writes: Vec<&String> = if write == "IW" {
+                self.events
+                    .values()
+                    .filter(|ev| matches!(ev.event_kind, GraphEventKind::WriteMem(_)))
+                    .map(|ev| &ev.name)
+                    .collect()
+            } else {
+                co.all_edges.iter().filter(|(from, _)| from == write).map(|(_, to)| to).collect()
+            };
+            for later in later_writes {
+                if address(later) == Some(addr) {
+                    edges.insert((read.clone(), later.clone()));
+                }
+            }
+        }
+
+        let (_, ty) = parse_relname_opt("fr", &self.opts);
+        self.relations
+            .push(GraphRelation { name: "fr".to_string(), ty, edges: edges.clone(), all_edges: edges }.simplify())
+    }
+
+    /// Replace each relation named in `split_by_address` with one
+    /// relation per address, such as `co[x]`, where the address of an
+    /// edge is that of its source event (or its target, for edges from
+    /// the initial state). Edges between events with no concrete
+    /// address go in a catch-all relation such as `co[?]`. The
+    /// addresses are given the colors in `SPLIT_ADDRESS_COLORS` in
+    /// increasing order, wrapping around if there are more addresses
+    /// than colors, unless `relation_styles` already has a style for
+    /// the split relation.
+    pub fn split_relations_by_address(&mut self) {
+        let mut relations = Vec::new();
+        for rel in std::mem::take(&mut self.relations) {
+            if !self.opts.split_by_address.contains(&rel.name) {
+                relations.push(rel);
+                continue;
+            }
+
+            // the concrete address of each edge, or None for the catch-all
+            let address = |(from, to): &(String, String)| {
+                let ev = if from == "IW" { self.events.get(to) } else { self.events.get(from) }?;
+                let addr = ev.value.as_ref()?.address.as_ref()?;
+                let concrete = u64::from_str_radix(addr.strip_prefix("0x")?, 16).ok()?;
+                Some((concrete, ev.address_name(&self.opts, &self.names)?))
+            };
+            let mut buckets: BTreeMap<Option<ConcreteAddress>, Vec<&(String, String)>> = BTreeMap::new();
+            for edge in &rel.all_edges {
+                buckets.entry(address(edge)).or_default().push(edge)
+            }
+
+            let base_style = self.opts.relation_styles.get(&rel.name).cloned();
+            let direction = self.opts.relation_directions.get(&rel.name).copied();
+            // the catch-all sorts first, but is put after the addresses
+            let (unknown, known): (Vec<_>, Vec<_>) = buckets.into_iter().partition(|(addr, _)| addr.is_none());
+            for (i, (addr, all_edges)) in known.into_iter().chain(unknown).enumerate() {
+                let (name, color) = match addr {
+                    Some((_, addr_name)) => (
+                        format!("{}[{}]", rel.name, addr_name),
+                        GraphOpts::SPLIT_ADDRESS_COLORS[i % GraphOpts::SPLIT_ADDRESS_COLORS.len()],
+                    ),
+                    None => (format!("{}[?]", rel.name), GraphOpts::SPLIT_UNKNOWN_ADDRESS_COLOR),
+                };
+                let all_edges: HashSet<(String, String)> = all_edges.into_iter().cloned().collect();
+                let edges = rel.edges.intersection(&all_edges).cloned().collect();
+
+                self.opts.relation_styles.entry(name.clone()).or_insert_with(|| RelationStyle {
+                    color: color.to_string(),
+                    line: base_style.as_ref().map(|style| style.line).unwrap_or(LineStyle::Solid),
+                    arrowhead: base_style.as_ref().and_then(|style| style.arrowhead.clone()),
+                });
+                if let Some(direction) = direction {
+                    self.opts.relation_directions.entry(name.clone()).or_insert(direction);
+                }
+                relations.push(GraphRelation { name, ty: rel.ty.clone(), edges, all_edges })
+            }
+        }
+        self.relations = relations
+    }
+
+    /// Replace the translate events of each instruction with a single
+    /// summary event, moving any relation edges onto it. Edges within
+    /// a walk are dropped. Walks containing an event named in
+    /// `force_show_events` are left as they are.
+    pub fn collapse_translations(&mut self) {
+        let mut walks: HashMap<(ThreadId, usize), Vec<String>> = HashMap::new();
+        for ev in self.events.values() {
+            if let GraphEventKind::Translate(_) = ev.event_kind {
+                walks.entry((ev.thread_id, ev.po)).or_default().push(ev.name.clone())
+            }
+        }
+
+        walks.retain(|_, walk| {
+            !walk.iter().any(|name| {
+                let ev = &self.events[name];
+                event_in_shows(&self.opts.force_show_events, ev)
+                    || event_in_shows(&self.litmus_opts.force_show_events, ev)
+            })
+        });
+
+        let mut collapsed: HashMap<String, String> = HashMap::new();
+        for ((thread_id, po), walk) in walks {
+            let walk: Vec<GraphEvent> = walk.iter().map(|name| self.events.remove(name).unwrap()).collect();
+            let first = walk.iter().min_by_key(|ev| ev.iio).unwrap();
+
+            // the memory access of the instruction that the walk was translating for
+            let access = self.events.values().find(|ev| {
+                ev.thread_id == thread_id
+                    && ev.po == po
+                    && matches!(ev.event_kind, GraphEventKind::ReadMem | GraphEventKind::WriteMem(_))
+            });
+            let value = access.and_then(|ev| ev.value.as_ref()).map(|value| GraphValue {
+                prefix: "T walk".to_string(),
+                address: value.address.clone(),
+                virtual_address: value.virtual_address.clone(),
+                bytes: value.bytes.clone(),
+                value: None,
+            });
+
+            let summary = GraphEvent {
+                instr: first.instr.clone(),
+                opcode: first.opcode.clone(),
+                po,
+                iio: first.iio,
+                thread_id,
+                name: format!("TW{}_{}", thread_id, po),
+                value,
+                event_kind: GraphEventKind::TranslateWalk,
+                trace_origin: first.trace_origin,
+            };
+            for ev in &walk {
+                collapsed.insert(ev.name.clone(), summary.name.clone());
+            }
+            self.events.insert(summary.name.clone(), summary);
+        }
+
+        if collapsed.is_empty() {
+            return;
+        }
+
+        let rename = |name: &String| collapsed.get(name).unwrap_or(name).clone();
+        let rename_edges = |edges: &HashSet<(String, String)>| -> HashSet<(String, String)> {
+            edges.iter().map(|(from, to)| (rename(from), rename(to))).filter(|(from, to)| from != to).collect()
+        };
+
+        for rel in self.relations.iter_mut() {
+            rel.edges = simplify_edges(&rel.ty, &rename_edges(&rel.edges));
+            rel.all_edges = rename_edges(&rel.all_edges);
+        }
+
+        for set in self.sets.iter_mut() {
+            let mut seen = HashSet::new();
+            set.elems = set.elems.iter().map(rename).filter(|elem| seen.insert(elem.clone())).collect();
+        }
+    }
 }
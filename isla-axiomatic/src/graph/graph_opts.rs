@@ -1,30 +1,771 @@
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use super::text_measure::{HeuristicMeasure, TextMeasure};
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum GraphMode {
     Disabled,
     ASCII,
     Dot,
+    Svg,
+    GraphML,
+    /// every execution of a test in one interactive HTML page
+    Html,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GraphOpts {
     pub mode: GraphMode,
     pub show_regs: HashSet<String>,
+    /// registers whose read and write events are always drawn, e.g.
+    /// `TTBR0_EL1`, whether or not in debug mode
+    pub show_registers: Vec<String>,
     pub flatten: bool,
     pub debug: bool,
     pub show_all_reads: bool,
     pub shows: Option<Vec<String>>,
-    pub padding: Option<HashMap<String, f64>>,
-    pub force_show_events: Option<Vec<String>>,
+    /// the relations named by `show` declarations in the memory model,
+    /// which are drawn unless the command line or litmus test says
+    /// otherwise
+    pub model_shows: Vec<String>,
+    /// draw the builtin relations together with every relation shown by
+    /// the litmus test or memory model
+    pub show_all: bool,
+    /// overrides for the default padding around each part of the layout
+    pub padding: LayoutPadding,
+    pub force_show_events: Option<Vec<EventSpec>>,
     pub force_hide_events: Option<Vec<String>>,
     pub squash_translation_labels: bool,
     pub control_delimit: bool,
     pub human_readable_values: bool,
+    pub palette: EventPalette,
+    /// draw a legend showing the color of each relation
+    pub show_legend: bool,
+    /// user-specified styles for drawing relations, which take priority over the defaults
+    pub relation_styles: HashMap<String, RelationStyle>,
+    /// whether to draw the edges of a relation with arrows, where the default is [EdgeDirection::Auto]
+    pub relation_directions: HashMap<String, EdgeDirection>,
+    /// how the edges of each relation are labelled, overriding the
+    /// defaults given by `GraphOpts::edge_label`
+    pub edge_labels: HashMap<String, EdgeLabel>,
+    /// relations to draw transitively reduced, in addition to `DEFAULT_REL_TRANSITIVE_REDUCE`
+    pub transitive_reduce: HashSet<String>,
+    /// draw the intra-instruction order between displayed events of each instruction
+    pub show_iio: bool,
+    /// whether threads are drawn side by side as columns, or stacked as rows
+    pub orientation: GraphOrientation,
+    /// replace the translate events of each instruction with a single summary event
+    pub collapse_translations: bool,
+    /// fill the background of each thread box, cycling through these colors by thread id
+    pub thread_colors: Option<Vec<String>>,
+    /// label rf, co, and fr edges with the location they are between, e.g. `rf[x]`
+    pub edge_addresses: bool,
+    /// leave the initial state out of dot graphs when none of its edges are drawn
+    pub hide_unused_initial_state: bool,
+    /// wrap event labels longer than this many characters onto multiple lines
+    pub max_label_width: Option<usize>,
+    /// multiplies every position, node size, and font size in the
+    /// output, so 2.0 draws the same layout twice as large
+    pub scale: f64,
+    /// label each instruction box with the number of its events which are hidden
+    pub show_hidden_counts: bool,
+    /// draw a dashed box around the events of each instruction
+    pub draw_instruction_boxes: bool,
+    /// draw a dashed box around each thread, without which the thread
+    /// label is put on the box around its first instruction
+    pub draw_thread_boxes: bool,
+    /// move read and write events so that those to the same address
+    /// line up in columns across threads
+    pub align_addresses: bool,
+    /// positions to place events at instead of the computed ones,
+    /// with any events not in it laid out as usual
+    pub layout_file: Option<LayoutFile>,
+    /// draw an `fr` relation derived from `rf` and `co` when the model
+    /// doesn't define one
+    pub derive_fr: bool,
+    /// relations drawn as one relation per address, such as `co[x]`,
+    /// each in its own color
+    pub split_by_address: HashSet<String>,
+    /// how the text of node labels is measured when sizing nodes
+    #[serde(skip, default = "default_text_measure")]
+    pub text_measure: Arc<dyn TextMeasure>,
+}
+
+fn default_text_measure() -> Arc<dyn TextMeasure> {
+    Arc::new(HeuristicMeasure)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphOrientation {
+    /// threads are columns, with instructions going top-to-bottom
+    Vertical,
+    /// threads are rows, with instructions going left-to-right
+    Horizontal,
+}
+
+impl FromStr for GraphOrientation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "vertical" => Ok(GraphOrientation::Vertical),
+            "horizontal" => Ok(GraphOrientation::Horizontal),
+            _ => Err(format!("unknown graph orientation '{}', must be either vertical or horizontal", s)),
+        }
+    }
+}
+
+impl fmt::Display for GraphOrientation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GraphOrientation::Vertical => write!(f, "vertical"),
+            GraphOrientation::Horizontal => write!(f, "horizontal"),
+        }
+    }
+}
+
+/// A named set of options for a particular kind of graph. A style is
+/// expanded into the options it sets by [GraphStyle::apply], and any
+/// options given explicitly are then set on top of it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphStyle {
+    /// figures for papers, with readable values, collapsed translation
+    /// table walks, and a legend
+    Paper,
+    /// show every event with all its information
+    Debug,
+    /// the options used by the web interface
+    Web,
+    /// as few events and as little decoration as possible
+    Minimal,
+}
+
+impl GraphStyle {
+    pub const ALL: &'static [GraphStyle] =
+        &[GraphStyle::Paper, GraphStyle::Debug, GraphStyle::Web, GraphStyle::Minimal];
+
+    /// Set the options which make up this style, leaving any others as they are
+    pub fn apply(self, opts: &mut GraphOpts) {
+        match self {
+            GraphStyle::Paper => {
+                opts.debug = false;
+                opts.show_all_reads = false;
+                opts.human_readable_values = true;
+                opts.squash_translation_labels = true;
+                opts.collapse_translations = true;
+                opts.show_legend = true;
+                opts.show_iio = false;
+                opts.hide_unused_initial_state = true;
+                opts.show_hidden_counts = false;
+                opts.max_label_width = Some(40);
+            }
+            GraphStyle::Debug => {
+                opts.debug = true;
+                opts.show_all_reads = true;
+                opts.human_readable_values = false;
+                opts.squash_translation_labels = false;
+                opts.collapse_translations = false;
+                opts.show_legend = true;
+                opts.show_iio = true;
+                opts.edge_addresses = true;
+                opts.hide_unused_initial_state = false;
+                opts.show_hidden_counts = true;
+                opts.max_label_width = None;
+            }
+            GraphStyle::Web => {
+                opts.debug = false;
+                opts.show_all_reads = true;
+                opts.human_readable_values = true;
+                opts.control_delimit = true;
+            }
+            GraphStyle::Minimal => {
+                opts.debug = false;
+                opts.show_all_reads = false;
+                opts.squash_translation_labels = true;
+                opts.collapse_translations = true;
+                opts.show_legend = false;
+                opts.show_iio = false;
+                opts.hide_unused_initial_state = true;
+                opts.show_hidden_counts = false;
+                opts.padding.event.right = Some(0.2);
+            }
+        }
+    }
+}
+
+impl FromStr for GraphStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "paper" => Ok(GraphStyle::Paper),
+            "debug" => Ok(GraphStyle::Debug),
+            "web" => Ok(GraphStyle::Web),
+            "minimal" => Ok(GraphStyle::Minimal),
+            _ => Err(format!("unknown graph style '{}', must be one of paper, debug, web, or minimal", s)),
+        }
+    }
+}
+
+impl fmt::Display for GraphStyle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GraphStyle::Paper => write!(f, "paper"),
+            GraphStyle::Debug => write!(f, "debug"),
+            GraphStyle::Web => write!(f, "web"),
+            GraphStyle::Minimal => write!(f, "minimal"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineStyle {
+    Solid,
+    Dashed,
+    Dotted,
+}
+
+impl FromStr for LineStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "solid" => Ok(LineStyle::Solid),
+            "dashed" => Ok(LineStyle::Dashed),
+            "dotted" => Ok(LineStyle::Dotted),
+            _ => Err(format!("unknown line style '{}', must be one of solid, dashed, or dotted", s)),
+        }
+    }
+}
+
+impl fmt::Display for LineStyle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LineStyle::Solid => write!(f, "solid"),
+            LineStyle::Dashed => write!(f, "dashed"),
+            LineStyle::Dotted => write!(f, "dotted"),
+        }
+    }
+}
+
+/// Whether the edges of a relation are drawn with arrows
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeDirection {
+    /// draw a pair of edges in opposite directions as a single edge
+    /// without arrows, and any other edges with arrows
+    Auto,
+    /// draw every edge without arrows, and each pair of edges in
+    /// opposite directions as a single edge
+    Undirected,
+    /// draw every edge with an arrow, including both edges of a pair in
+    /// opposite directions, so 2-cycles can be seen
+    Directed,
+}
+
+impl EdgeDirection {
+    /// Parse a relation direction of the form `rel=direction`
+    pub fn parse_binding(s: &str) -> Result<(String, EdgeDirection), String> {
+        let (rel, direction) =
+            s.split_once('=').ok_or_else(|| format!("'{}' must be of form rel=auto|undirected|directed", s))?;
+        Ok((rel.to_string(), direction.parse()?))
+    }
+}
+
+impl FromStr for EdgeDirection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(EdgeDirection::Auto),
+            "undirected" => Ok(EdgeDirection::Undirected),
+            "directed" => Ok(EdgeDirection::Directed),
+            _ => Err(format!("unknown edge direction '{}', must be one of auto, undirected, or directed", s)),
+        }
+    }
+}
+
+impl fmt::Display for EdgeDirection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EdgeDirection::Auto => write!(f, "auto"),
+            EdgeDirection::Undirected => write!(f, "undirected"),
+            EdgeDirection::Directed => write!(f, "directed"),
+        }
+    }
+}
+
+/// How the edges of a relation are labelled
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum EdgeLabel {
+    /// leave the edges unlabelled
+    Hide,
+    /// label the edges with this instead of the name of the relation,
+    /// e.g. `τ` for `trf`
+    Abbreviate(String),
+    /// put the label by the tail of each edge, which leaves more room
+    /// along short edges
+    TailLabel,
+    /// put the label halfway along each edge
+    Label,
+}
+
+impl EdgeLabel {
+    /// Parse an edge label policy of the form `rel=policy`
+    pub fn parse_binding(s: &str) -> Result<(String, EdgeLabel), String> {
+        let (rel, label) =
+            s.split_once('=').ok_or_else(|| format!("'{}' must be of form rel=hide|taillabel|label|<abbrev>", s))?;
+        Ok((rel.to_string(), label.parse()?))
+    }
+}
+
+impl FromStr for EdgeLabel {
+    type Err = String;
+
+    /// Parse one of `hide`, `taillabel`, or `label`, with anything
+    /// else taken as an abbreviation
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" => Err("an edge label must not be empty, use 'hide' to leave edges unlabelled".to_string()),
+            "hide" => Ok(EdgeLabel::Hide),
+            "taillabel" => Ok(EdgeLabel::TailLabel),
+            "label" => Ok(EdgeLabel::Label),
+            abbrev => Ok(EdgeLabel::Abbreviate(abbrev.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for EdgeLabel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EdgeLabel::Hide => write!(f, "hide"),
+            EdgeLabel::Abbreviate(abbrev) => write!(f, "{}", abbrev),
+            EdgeLabel::TailLabel => write!(f, "taillabel"),
+            EdgeLabel::Label => write!(f, "label"),
+        }
+    }
+}
+
+/// A description of the events to always show in a graph
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum EventSpec {
+    /// a single event by name, e.g. `R0_1_0`
+    Name(String),
+    /// every event of the instructions with program order in the
+    /// (inclusive) range on a thread, e.g. `0:2` or `0:1-3`
+    Instrs { thread_id: usize, po: (usize, usize) },
+    /// the translate events for a stage and level of the instructions
+    /// with program order in the (inclusive) range on a thread, e.g.
+    /// `T0:1:s1l3` or `T0:1-3:s1l3`
+    Translate { thread_id: usize, po: (usize, usize), stage: usize, level: usize },
+}
+
+impl EventSpec {
+    pub const GRAMMAR: &'static str =
+        "an event name, <thread>:<po>[-<po>] for all events of instructions, or T<thread>:<po>[-<po>]:s<stage>l<level> for translate events";
+
+    /// Parse a comma-separated list of event specifiers
+    pub fn parse_list(s: &str) -> Result<Vec<EventSpec>, String> {
+        s.split(',').map(str::parse).collect()
+    }
+}
+
+fn parse_po_range(s: &str) -> Option<(usize, usize)> {
+    match s.split_once('-') {
+        Some((from, to)) => {
+            let (from, to) = (from.parse().ok()?, to.parse().ok()?);
+            if from <= to {
+                Some((from, to))
+            } else {
+                None
+            }
+        }
+        None => s.parse().ok().map(|po| (po, po)),
+    }
+}
+
+impl FromStr for EventSpec {
+    type Err = String;
+
+    /// Specifiers containing a `:` describe events by their thread and
+    /// program order, and anything else is the name of an event
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid event specifier '{}', must be {}", s, EventSpec::GRAMMAR);
+
+        if !s.contains(':') {
+            return if s.is_empty() || s.contains(char::is_whitespace) {
+                Err(invalid())
+            } else {
+                Ok(EventSpec::Name(s.to_string()))
+            };
+        }
+
+        match *s.split(':').collect::<Vec<&str>>().as_slice() {
+            [tid, po] => Ok(EventSpec::Instrs {
+                thread_id: tid.parse().map_err(|_| invalid())?,
+                po: parse_po_range(po).ok_or_else(invalid)?,
+            }),
+            [tid, po, stage_level] => {
+                let thread_id = tid.strip_prefix('T').and_then(|tid| tid.parse().ok()).ok_or_else(invalid)?;
+                let (stage, level) =
+                    stage_level.strip_prefix('s').and_then(|sl| sl.split_once('l')).ok_or_else(invalid)?;
+                Ok(EventSpec::Translate {
+                    thread_id,
+                    po: parse_po_range(po).ok_or_else(invalid)?,
+                    stage: stage.parse().map_err(|_| invalid())?,
+                    level: level.parse().map_err(|_| invalid())?,
+                })
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+fn fmt_po_range(f: &mut fmt::Formatter, (from, to): (usize, usize)) -> fmt::Result {
+    if from == to {
+        write!(f, "{}", from)
+    } else {
+        write!(f, "{}-{}", from, to)
+    }
+}
+
+impl fmt::Display for EventSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EventSpec::Name(name) => write!(f, "{}", name),
+            EventSpec::Instrs { thread_id, po } => {
+                write!(f, "{}:", thread_id)?;
+                fmt_po_range(f, *po)
+            }
+            EventSpec::Translate { thread_id, po, stage, level } => {
+                write!(f, "T{}:", thread_id)?;
+                fmt_po_range(f, *po)?;
+                write!(f, ":s{}l{}", stage, level)
+            }
+        }
+    }
+}
+
+/// How to draw the edges of a relation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RelationStyle {
+    pub color: String,
+    pub line: LineStyle,
+    /// the graphviz arrowhead shape, e.g. `vee` or `dot`
+    pub arrowhead: Option<String>,
+}
+
+impl RelationStyle {
+    /// Parse a relation style of the form `rel=color[,line[,arrowhead]]`
+    pub fn parse_binding(s: &str) -> Result<(String, RelationStyle), String> {
+        let (rel, style) = s.split_once('=').ok_or_else(|| format!("'{}' must be of form rel=color,...", s))?;
+        Ok((rel.to_string(), style.parse()?))
+    }
+}
+
+impl FromStr for RelationStyle {
+    type Err = String;
+
+    /// Parse a relation style of the form `color[,line[,arrowhead]]`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match *s.split(',').collect::<Vec<&str>>().as_slice() {
+            [color] if !color.is_empty() => {
+                Ok(RelationStyle { color: color.to_string(), line: LineStyle::Solid, arrowhead: None })
+            }
+            [color, line] if !color.is_empty() => {
+                Ok(RelationStyle { color: color.to_string(), line: line.parse()?, arrowhead: None })
+            }
+            [color, line, arrowhead] if !color.is_empty() => Ok(RelationStyle {
+                color: color.to_string(),
+                line: line.parse()?,
+                arrowhead: Some(arrowhead.to_string()),
+            }),
+            _ => Err(format!("invalid relation style '{}', must be of form color[,line[,arrowhead]]", s)),
+        }
+    }
+}
+
+/// Padding (in inches) on each side of a part of the layout, where
+/// `None` means use the default for that side
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct SidePadding {
+    pub up: Option<f64>,
+    pub down: Option<f64>,
+    pub left: Option<f64>,
+    pub right: Option<f64>,
+}
+
+/// User overrides for the padding around each part of the graph
+/// layout: the initial write node, the box containing all the threads,
+/// each thread, each instruction, and each event
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct LayoutPadding {
+    pub iw: SidePadding,
+    pub threads: SidePadding,
+    pub thread: SidePadding,
+    pub instr: SidePadding,
+    pub event: SidePadding,
+}
+
+impl LayoutPadding {
+    pub const NAMES: &'static [&'static str] = &["iw", "threads", "thread", "instr", "event"];
+
+    pub const DIRECTIONS: &'static [&'static str] = &["up", "down", "left", "right"];
+
+    fn valid_keys() -> String {
+        let keys: Vec<String> = Self::NAMES
+            .iter()
+            .flat_map(|name| Self::DIRECTIONS.iter().map(move |dir| format!("{}-{}", name, dir)))
+            .collect();
+        keys.join(", ")
+    }
+
+    /// Override the padding for a key of the form `name-direction`,
+    /// where the name is one of `LayoutPadding::NAMES` and the direction
+    /// one of `LayoutPadding::DIRECTIONS`
+    pub fn set(&mut self, key: &str, value: f64) -> Result<(), String> {
+        let unknown = || format!("unknown padding '{}', must be one of {}", key, Self::valid_keys());
+        let (name, dir) = key.rsplit_once('-').ok_or_else(unknown)?;
+        let side = match name {
+            "iw" => &mut self.iw,
+            "threads" => &mut self.threads,
+            "thread" => &mut self.thread,
+            "instr" => &mut self.instr,
+            "event" => &mut self.event,
+            _ => return Err(unknown()),
+        };
+        let field = match dir {
+            "up" => &mut side.up,
+            "down" => &mut side.down,
+            "left" => &mut side.left,
+            "right" => &mut side.right,
+            _ => return Err(unknown()),
+        };
+        *field = Some(value);
+        Ok(())
+    }
+}
+
+impl FromStr for LayoutPadding {
+    type Err = String;
+
+    /// Parse padding overrides of the form `name-direction=value,...`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut padding = LayoutPadding::default();
+        for padeq in s.split(',') {
+            let (key, value) =
+                padeq.split_once('=').ok_or_else(|| format!("'{}' must be of form name-direction=value", padeq))?;
+            let value =
+                value.parse::<f64>().map_err(|_| format!("padding value '{}' for {} is not a number", value, key))?;
+            padding.set(key, value)?
+        }
+        Ok(padding)
+    }
+}
+
+impl TryFrom<HashMap<String, f64>> for LayoutPadding {
+    type Error = String;
+
+    fn try_from(hmap: HashMap<String, f64>) -> Result<Self, Self::Error> {
+        let mut padding = LayoutPadding::default();
+        for (key, value) in hmap {
+            padding.set(&key, value)?
+        }
+        Ok(padding)
+    }
+}
+
+/// The fill colors used for each kind of event node
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EventPalette {
+    pub translate_s1: String,
+    pub translate_s2: String,
+    pub translate_walk: String,
+    pub tte_write: String,
+    pub ifetch: String,
+    pub read: String,
+    pub write: String,
+    pub barrier: String,
+    pub register: String,
+    pub other: String,
+}
+
+impl Default for EventPalette {
+    fn default() -> Self {
+        EventPalette {
+            translate_s1: "darkslategray1".to_string(),
+            translate_s2: "wheat1".to_string(),
+            translate_walk: "paleturquoise3".to_string(),
+            tte_write: "plum1".to_string(),
+            ifetch: "lightgrey".to_string(),
+            read: "white".to_string(),
+            write: "mistyrose".to_string(),
+            barrier: "gold".to_string(),
+            register: "lightgoldenrod".to_string(),
+            other: "white".to_string(),
+        }
+    }
+}
+
+impl EventPalette {
+    pub const KINDS: &'static [&'static str] = &[
+        "translate-s1",
+        "translate-s2",
+        "translate-walk",
+        "tte-write",
+        "ifetch",
+        "read",
+        "write",
+        "barrier",
+        "register",
+        "other",
+    ];
+
+    /// Override the color for a kind of event, where the kind is one
+    /// of the names in `EventPalette::KINDS`
+    pub fn set(&mut self, kind: &str, color: &str) -> Result<(), String> {
+        let field = match kind {
+            "translate-s1" => &mut self.translate_s1,
+            "translate-s2" => &mut self.translate_s2,
+            "translate-walk" => &mut self.translate_walk,
+            "tte-write" => &mut self.tte_write,
+            "ifetch" => &mut self.ifetch,
+            "read" => &mut self.read,
+            "write" => &mut self.write,
+            "barrier" => &mut self.barrier,
+            "register" => &mut self.register,
+            "other" => &mut self.other,
+            _ => return Err(format!("unknown event kind '{}', must be one of {}", kind, Self::KINDS.join(", "))),
+        };
+        *field = color.to_string();
+        Ok(())
+    }
+}
+
+/// Where an event was placed by a layout, in points
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventPlacement {
+    /// the centre of the event
+    pub pos: (i64, i64),
+    /// the width and height of the node shape, which is recomputed
+    /// from its label when the layout is loaded
+    pub size: (usize, usize),
+}
+
+/// A computed layout, as a map from event names to their placements,
+/// which can be saved as JSON so that positions can be adjusted by
+/// hand and reused when re-rendering the same test
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct LayoutFile {
+    pub events: BTreeMap<String, EventPlacement>,
+}
+
+impl LayoutFile {
+    pub fn from_json(s: &str) -> Result<Self, String> {
+        serde_json::from_str(s).map_err(|e| format!("invalid layout file: {}", e))
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("layout files are always serializable")
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| format!("could not read layout file {}: {}", path.display(), e))?;
+        Self::from_json(&contents)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_json() + "\n")
+            .map_err(|e| format!("could not write layout file {}: {}", path.display(), e))
+    }
+}
+
+/// The options used when none are given, which draw no graphs
+impl Default for GraphOpts {
+    fn default() -> Self {
+        GraphOpts {
+            mode: GraphMode::Disabled,
+            show_regs: GraphOpts::DEFAULT_SHOW_REGS.iter().cloned().map(String::from).collect(),
+            show_registers: Vec::new(),
+            flatten: false,
+            debug: false,
+            show_all_reads: false,
+            shows: None,
+            model_shows: Vec::new(),
+            show_all: false,
+            padding: LayoutPadding::default(),
+            force_show_events: None,
+            force_hide_events: None,
+            squash_translation_labels: false,
+            control_delimit: false,
+            human_readable_values: false,
+            palette: EventPalette::default(),
+            show_legend: false,
+            relation_styles: HashMap::new(),
+            relation_directions: HashMap::new(),
+            edge_labels: HashMap::new(),
+            transitive_reduce: HashSet::new(),
+            show_iio: false,
+            orientation: GraphOrientation::Vertical,
+            collapse_translations: false,
+            thread_colors: None,
+            edge_addresses: false,
+            hide_unused_initial_state: true,
+            max_label_width: None,
+            scale: 1.0,
+            show_hidden_counts: true,
+            draw_instruction_boxes: true,
+            draw_thread_boxes: true,
+            align_addresses: false,
+            layout_file: None,
+            derive_fr: true,
+            split_by_address: HashSet::new(),
+            text_measure: default_text_measure(),
+        }
+    }
 }
 
 impl GraphOpts {
+    /// Whether the edges of a relation are drawn with arrows
+    pub fn relation_direction(&self, rel: &str) -> EdgeDirection {
+        self.relation_directions.get(rel).copied().unwrap_or(EdgeDirection::Auto)
+    }
+
+    /// How the edges of a relation are labelled, where by default `po`
+    /// edges are only labelled in debug mode, and `po` and `po-loc`
+    /// edges (which are usually short) have the label by their tail
+    pub fn edge_label(&self, rel: &str) -> EdgeLabel {
+        if let Some(label) = self.edge_labels.get(rel) {
+            return label.clone();
+        }
+        match rel {
+            "po" if !self.debug => EdgeLabel::Hide,
+            "po" | "po-loc" => EdgeLabel::TailLabel,
+            _ => EdgeLabel::Label,
+        }
+    }
+
+    /// Whether the events for a register are always drawn, where a
+    /// register given without a field also matches each of its fields
+    pub fn shows_register(&self, name: &str) -> bool {
+        let base = name.split('.').next().unwrap_or(name);
+        self.show_registers.iter().any(|reg| reg == name || reg == base)
+    }
+
+    /// The default options with a style applied
+    pub fn with_style(style: GraphStyle) -> Self {
+        let mut opts = GraphOpts::default();
+        style.apply(&mut opts);
+        opts
+    }
+
     pub const DEFAULT_SHOW_REGS: &'static [&'static str] = &[
         "R0", "R1", "R2", "R3", "R4", "R5", "R6", "R7", "R8", "R9", "R10", "R11", "R12", "R13", "R14", "R15", "R16",
         "R18", "R18", "R19", "R20", "R21", "R22", "R23", "R24", "R25", "R26", "R27", "R28", "R29", "R30", "R31", "SP",
@@ -34,6 +775,19 @@ impl GraphOpts {
     pub const ARMV8_ADDR_TRANS_SHOW_REGS: &'static [&'static str] =
         &["TTBR0_EL1", "TTBR1_EL1", "TTBR0_EL2", "VTTBR_EL2"];
 
+    /// light enough that the event fill colors still stand out against them
+    pub const DEFAULT_THREAD_COLORS: &'static [&'static str] =
+        &["aliceblue", "honeydew", "lavenderblush", "lightyellow", "lavender", "mintcream"];
+
+    /// the colors given to each address of a relation split by address,
+    /// in increasing order of address
+    pub const SPLIT_ADDRESS_COLORS: &'static [&'static str] =
+        &["crimson", "blue", "darkgreen", "darkorange", "purple", "deeppink", "teal", "saddlebrown"];
+
+    /// the color of the edges of a relation split by address whose
+    /// events have no concrete address
+    pub const SPLIT_UNKNOWN_ADDRESS_COLOR: &'static str = "gray40";
+
     /// by default we transitively reduce some relations to make them smaller
     /// can explicitly do this by postfixing a relation with -
     /// can also do the opposite by postfixing a relation with + to get the transitive closure instead.
@@ -52,3 +806,155 @@ impl GraphOpts {
         ("co", "wco"),
     ];
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_relation_style() {
+        let (rel, style) = RelationStyle::parse_binding("trf=red,dashed").unwrap();
+        assert_eq!(rel, "trf");
+        assert_eq!(style, RelationStyle { color: "red".to_string(), line: LineStyle::Dashed, arrowhead: None });
+
+        let (_, style) = RelationStyle::parse_binding("tob=blue").unwrap();
+        assert_eq!(style.line, LineStyle::Solid);
+
+        let (_, style) = RelationStyle::parse_binding("ob=green,dotted,vee").unwrap();
+        assert_eq!(style.arrowhead.as_deref(), Some("vee"));
+
+        assert!(RelationStyle::parse_binding("trf").is_err());
+        assert!(RelationStyle::parse_binding("trf=").is_err());
+        assert!(RelationStyle::parse_binding("trf=red,wavy").is_err());
+        assert!(RelationStyle::parse_binding("trf=red,dashed,vee,extra").is_err());
+    }
+
+    #[test]
+    fn test_graph_styles() {
+        for style in GraphStyle::ALL {
+            assert_eq!(style.to_string().parse::<GraphStyle>(), Ok(*style))
+        }
+        assert!("fancy".parse::<GraphStyle>().is_err());
+
+        let paper = GraphOpts::with_style(GraphStyle::Paper);
+        assert!(!paper.debug && !paper.show_all_reads && !paper.show_iio && !paper.show_hidden_counts);
+        assert!(paper.human_readable_values && paper.squash_translation_labels && paper.collapse_translations);
+        assert!(paper.show_legend && paper.hide_unused_initial_state);
+        assert_eq!(paper.max_label_width, Some(40));
+
+        let debug = GraphOpts::with_style(GraphStyle::Debug);
+        assert!(debug.debug && debug.show_all_reads && debug.show_iio && debug.show_hidden_counts);
+        assert!(!debug.human_readable_values && !debug.squash_translation_labels && !debug.collapse_translations);
+        assert!(debug.show_legend && debug.edge_addresses && !debug.hide_unused_initial_state);
+        assert_eq!(debug.max_label_width, None);
+
+        let web = GraphOpts::with_style(GraphStyle::Web);
+        assert!(!web.debug && web.show_all_reads && web.human_readable_values && web.control_delimit);
+        assert!(!web.show_legend && !web.show_iio && !web.squash_translation_labels);
+
+        let minimal = GraphOpts::with_style(GraphStyle::Minimal);
+        assert!(!minimal.debug && !minimal.show_all_reads && !minimal.show_legend && !minimal.show_iio);
+        assert!(minimal.squash_translation_labels && minimal.collapse_translations);
+        assert!(minimal.hide_unused_initial_state && !minimal.show_hidden_counts);
+        assert_eq!(minimal.padding.event, SidePadding { right: Some(0.2), ..SidePadding::default() });
+
+        // a style only sets its own options
+        let mut opts = GraphOpts { scale: 2.0, orientation: GraphOrientation::Horizontal, ..GraphOpts::default() };
+        for style in GraphStyle::ALL {
+            style.apply(&mut opts);
+            assert_eq!(opts.scale, 2.0);
+            assert_eq!(opts.orientation, GraphOrientation::Horizontal)
+        }
+    }
+
+    #[test]
+    fn test_parse_edge_direction() {
+        assert_eq!(EdgeDirection::parse_binding("co=directed"), Ok(("co".to_string(), EdgeDirection::Directed)));
+        assert_eq!(EdgeDirection::parse_binding("sloc=undirected").unwrap().1, EdgeDirection::Undirected);
+        assert!(EdgeDirection::parse_binding("co").is_err());
+        assert!(EdgeDirection::parse_binding("co=both").is_err());
+
+        let mut opts = GraphOpts::default();
+        opts.relation_directions.insert("co".to_string(), EdgeDirection::Directed);
+        assert_eq!(opts.relation_direction("co"), EdgeDirection::Directed);
+        assert_eq!(opts.relation_direction("rf"), EdgeDirection::Auto);
+    }
+
+    #[test]
+    fn test_parse_edge_label() {
+        assert_eq!(EdgeLabel::parse_binding("po=hide"), Ok(("po".to_string(), EdgeLabel::Hide)));
+        assert_eq!(EdgeLabel::parse_binding("trf=τ").unwrap().1, EdgeLabel::Abbreviate("τ".to_string()));
+        assert_eq!(EdgeLabel::parse_binding("rf=taillabel").unwrap().1, EdgeLabel::TailLabel);
+        assert!(EdgeLabel::parse_binding("rf").is_err());
+        assert!(EdgeLabel::parse_binding("rf=").is_err());
+
+        let mut opts = GraphOpts::default();
+        assert_eq!(opts.edge_label("po"), EdgeLabel::Hide);
+        assert_eq!(opts.edge_label("po-loc"), EdgeLabel::TailLabel);
+        assert_eq!(opts.edge_label("rf"), EdgeLabel::Label);
+        opts.debug = true;
+        assert_eq!(opts.edge_label("po"), EdgeLabel::TailLabel);
+        opts.edge_labels.insert("po".to_string(), EdgeLabel::Hide);
+        assert_eq!(opts.edge_label("po"), EdgeLabel::Hide);
+    }
+
+    #[test]
+    fn test_parse_event_spec() {
+        assert_eq!("R0_1_0".parse(), Ok(EventSpec::Name("R0_1_0".to_string())));
+        assert_eq!("TW0_1".parse(), Ok(EventSpec::Name("TW0_1".to_string())));
+        assert_eq!("0:2".parse(), Ok(EventSpec::Instrs { thread_id: 0, po: (2, 2) }));
+        assert_eq!("1:0-3".parse(), Ok(EventSpec::Instrs { thread_id: 1, po: (0, 3) }));
+        assert_eq!("T0:1:s1l3".parse(), Ok(EventSpec::Translate { thread_id: 0, po: (1, 1), stage: 1, level: 3 }));
+        assert_eq!("T0:1-3:s2l10".parse(), Ok(EventSpec::Translate { thread_id: 0, po: (1, 3), stage: 2, level: 10 }));
+        assert_eq!(
+            EventSpec::parse_list("R0_1_0,0:2").unwrap(),
+            vec![EventSpec::Name("R0_1_0".to_string()), EventSpec::Instrs { thread_id: 0, po: (2, 2) }]
+        );
+
+        for spec in ["R0_1_0", "0:2", "1:0-3", "T0:1:s1l3", "T0:1-3:s2l10"] {
+            assert_eq!(spec.parse::<EventSpec>().unwrap().to_string(), spec)
+        }
+
+        for bad in
+            ["", "T0:1", "T0:x:s1l3", "T0:1:s1", "T0:1:l3s1", "0:3-1", "0:", "x:1", "0:1:2:3", "T0:1:s1lx", "a b"]
+        {
+            let err = bad.parse::<EventSpec>().unwrap_err();
+            assert!(err.contains(&format!("'{}'", bad)) && err.contains(EventSpec::GRAMMAR), "{}", err)
+        }
+        assert!(EventSpec::parse_list("R0_1_0,,0:2").is_err());
+    }
+
+    #[test]
+    fn test_shows_register() {
+        let mut opts = GraphOpts::default();
+        assert!(!opts.shows_register("TTBR0_EL1"));
+        opts.show_registers = vec!["TTBR0_EL1".to_string(), "PSTATE".to_string(), "SCTLR_EL1.M".to_string()];
+        assert!(opts.shows_register("TTBR0_EL1"));
+        assert!(opts.shows_register("PSTATE.EL"));
+        assert!(opts.shows_register("SCTLR_EL1.M"));
+        assert!(!opts.shows_register("SCTLR_EL1"));
+        assert!(!opts.shows_register("TTBR1_EL1"));
+    }
+
+    #[test]
+    fn test_parse_padding() {
+        let padding: LayoutPadding = "iw-left=4,threads-down=2.5,event-right=0".parse().unwrap();
+        assert_eq!(padding.iw.left, Some(4.0));
+        assert_eq!(padding.threads.down, Some(2.5));
+        assert_eq!(padding.event.right, Some(0.0));
+        assert_eq!(padding.iw.right, None);
+        assert_eq!(padding.thread, SidePadding::default());
+
+        let err = "instr-sideways=1".parse::<LayoutPadding>().unwrap_err();
+        assert!(err.contains("instr-sideways"));
+        assert!(err.contains("iw-up") && err.contains("event-right"));
+        assert!("node-up=1".parse::<LayoutPadding>().is_err());
+        assert!("iw-up".parse::<LayoutPadding>().is_err());
+        assert!("iw-up=wide".parse::<LayoutPadding>().is_err());
+
+        let hmap: HashMap<String, f64> = [("thread-up".to_string(), 1.0)].into_iter().collect();
+        assert_eq!(LayoutPadding::try_from(hmap).unwrap().thread.up, Some(1.0));
+        let hmap: HashMap<String, f64> = [("thread".to_string(), 1.0)].into_iter().collect();
+        assert!(LayoutPadding::try_from(hmap).is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::graph::GraphOpts;
+use crate::graph::{EventSpec, GraphOpts};
 use std::collections::{HashMap, HashSet};
 
 use super::graph_events::*;
@@ -100,7 +100,10 @@ impl<'ev, A: std::fmt::Debug> std::fmt::Debug for GridNode<'ev, A> {
 impl<'ev, A> GridNode<'ev, A> {
     fn fill_label(&mut self, long: bool, multi: bool, opts: &GraphOpts, names: &GraphValueNames<u64>) {
         if let Some(ev) = self.ev {
-            if long {
+            if matches!(ev.event_kind, GraphEventKind::ReadReg | GraphEventKind::WriteReg) {
+                // the short label of a register event leaves out its value
+                self.label = ev.fmt_label_medium(opts, &self.ev_label, names);
+            } else if long {
                 self.label = ev.fmt_label_long(opts, &self.ev_label, names);
             } else {
                 self.label = ev.fmt_label_short(opts, &self.ev_label, names);
@@ -141,50 +144,27 @@ impl<'ev, A> GridNode<'ev, A> {
     }
 }
 
-pub fn event_in_shows(shows: &Option<Vec<String>>, ev: &GraphEvent) -> bool {
-    if let Some(evs) = shows {
-        for show_ev in evs.iter() {
-            if show_ev.starts_with('T') {
-                /* name like T0:1:s1l3 for translate thread 0, instr 1, s1l3 translate */
-                let stripped = show_ev.strip_prefix('T').unwrap();
-                let sections: Vec<&str> = stripped.split(':').collect();
-                let tid: usize =
-                    sections.first().expect("expected T0:1:s1l3 format").parse().expect("expected tid to be integer");
-                let po: usize =
-                    sections.get(1).expect("expected T0:1:s1l3 format").parse().expect("expected po to be integer");
-                let sl = sections.get(2).expect("expected T0:1:s1l3 format");
-                let stage: usize = sl
-                    .chars()
-                    .nth(1)
-                    .expect("expected stage/level to be sXlY format")
-                    .to_string()
-                    .parse::<usize>()
-                    .expect("expected stage to be integer");
-                let level: usize = sl
-                    .chars()
-                    .nth(3)
-                    .expect("expected stage/level to be sXlY format")
-                    .to_string()
-                    .parse::<usize>()
-                    .expect("expected level to be integer");
-                if ev.po == po && ev.thread_id == tid {
-                    if let GraphEventKind::Translate(TranslateKind { stage: ev_stage, level: ev_level, for_s1 }) =
-                        ev.event_kind
-                    {
-                        if let None | Some(0) = for_s1 {
-                            if ev_stage == stage && ev_level == level {
-                                return true;
-                            }
-                        }
-                    }
-                }
-            } else if show_ev == &ev.name {
-                return true;
-            }
+/// Whether an event is described by any of the given specifiers
+pub fn event_in_shows(shows: &Option<Vec<EventSpec>>, ev: &GraphEvent) -> bool {
+    let in_range =
+        |thread_id: usize, (from, to): (usize, usize)| ev.thread_id == thread_id && from <= ev.po && ev.po <= to;
+
+    shows.iter().flatten().any(|spec| match spec {
+        EventSpec::Name(name) => *name == ev.name,
+        EventSpec::Instrs { thread_id, po } => in_range(*thread_id, *po),
+        EventSpec::Translate { thread_id, po, stage, level } => {
+            in_range(*thread_id, *po)
+                && matches!(
+                    ev.event_kind,
+                    GraphEventKind::Translate(TranslateKind { stage: ev_stage, level: ev_level, for_s1: None | Some(0) })
+                        if ev_stage == *stage && ev_level == *level
+                )
         }
-    }
+    })
+}
 
-    false
+fn is_symmetric(edges: &HashSet<(String, String)>) -> bool {
+    edges.iter().all(|(from, to)| edges.contains(&(to.clone(), from.clone())))
 }
 
 /// given a relation as a set of pairs of nodes
@@ -267,6 +247,9 @@ fn transitively_close(edges: &HashSet<(String, String)>) -> HashSet<(String, Str
 
 pub fn simplify_edges(relty: &RelType, edges: &HashSet<(String, String)>) -> HashSet<(String, String)> {
     let modified = match relty.trans {
+        // The reduction of a symmetric relation would drop edges that
+        // are only implied by going back and forth, so leave them alone
+        RelTransType::TransReduction if is_symmetric(edges) => edges.clone(),
         RelTransType::TransReduction => transitively_reduce(edges),
         RelTransType::TransClosure => transitively_close(edges),
         RelTransType::Normal => edges.clone(),
@@ -786,10 +769,545 @@ impl GridDimensions {
     }
 }
 
+/// layouts are computed with a scale of 72ppi
+pub const POINTS_PER_INCH: f64 = 72.0;
+
+/// The scale at which a layout is drawn. Layouts are always computed
+/// in points at [POINTS_PER_INCH], and every length written out is
+/// multiplied by the scale factor, so drawing at a different scale
+/// changes the size of the output but never the layout itself.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scale {
+    pub factor: f64,
+}
+
+impl Default for Scale {
+    fn default() -> Self {
+        Scale { factor: 1.0 }
+    }
+}
+
+impl Scale {
+    pub fn new(factor: f64) -> Self {
+        Scale { factor }
+    }
+
+    /// a position or length in layout points, in points at this scale
+    pub fn points(&self, p: i64) -> i64 {
+        (p as f64 * self.factor).round() as i64
+    }
+
+    /// a length in layout points, in inches at this scale
+    pub fn inches_from_points(&self, p: usize) -> f64 {
+        (p as f64) * self.factor / POINTS_PER_INCH
+    }
+
+    /// a length in inches, in points at this scale
+    pub fn points_from_inches(&self, i: f64) -> usize {
+        (i * POINTS_PER_INCH * self.factor).round() as usize
+    }
+}
+
+/// Anything that can be placed by a [GraphLayout] only needs a name
+/// and a size
+pub trait Dimensions {
+    /// the name identifying the node in [GraphLayout::positions]
+    fn name(&self) -> String;
+    /// the width (in points) of the node itself, excluding padding
+    fn width(&self) -> usize;
+    /// the height (in points) of the node itself, excluding padding
+    fn height(&self) -> usize;
+}
+
+/// padding around a child
+/// in inches
+#[derive(Debug, Clone, Default)]
+pub struct Padding {
+    pub up: f64,
+    pub down: f64,
+    pub left: f64,
+    pub right: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Layout {
+    /// padding around the child
+    /// up, down, left, right
+    /// in points
+    pub padding: Padding,
+    /// alignment within the column
+    pub alignment: Align,
+    /// the position (in points) to place the child at
+    /// this gets filled in later by the layouter
+    /// for a Node this is the centre of the node
+    pub pos: Option<(i64, i64)>,
+    /// the position (in points) of the top-left of the bounding box
+    pub bb_pos: Option<(i64, i64)>,
+    /// if false, do not render in the final image
+    pub show: bool,
+    /// if false, the node has 0width and 0height for layouting purposes
+    pub skinny: bool,
+}
+
+impl Layout {
+    /// a shown, unpositioned layout with the given padding and alignment
+    pub fn new(padding: Padding, alignment: Align) -> Self {
+        Layout { padding, alignment, pos: None, bb_pos: None, show: true, skinny: false }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum LayoutNode<N> {
+    Node(N),
+    SubCluster(GraphLayout<N>),
+}
+
+#[derive(Debug, Clone)]
+pub struct GridChild<N> {
+    /// the node
+    pub node: LayoutNode<N>,
+    /// layout information about the child
+    pub layout: Layout,
+}
+
+/// a GraphLayout is a hierarchical row/column layout
+///
+/// Each cell holds either a node or a nested layout. Once every cell
+/// is filled in, [GraphLayout::accumulate_positions] places each node
+/// so that every row is as tall as its tallest cell and every column
+/// as wide as its widest, and [GraphLayout::positions] reads them
+/// back. [GraphLayout::flatten] can be used first to merge the nested
+/// layouts into a single grid, which lines up the rows and columns
+/// of neighbouring clusters.
+#[derive(Debug, Clone)]
+pub struct GraphLayout<N> {
+    pub(crate) children: HashMap<(usize, usize), GridChild<N>>,
+}
+
+impl<N> Default for GraphLayout<N> {
+    fn default() -> Self {
+        GraphLayout { children: HashMap::new() }
+    }
+}
+
+impl<N: Dimensions + std::fmt::Debug> GridChild<N> {
+    /// the width (in points) of the node or the child grid
+    pub fn compute_width(&self) -> usize {
+        if self.layout.skinny {
+            return 0;
+        }
+
+        let ww: usize = Scale::default().points_from_inches(self.layout.padding.left + self.layout.padding.right);
+        match &self.node {
+            LayoutNode::Node(n) => n.width() + ww,
+            LayoutNode::SubCluster(cluster) => cluster.compute_width() + ww,
+        }
+    }
+
+    /// the height (in points) of the node or the child grid
+    pub fn compute_height(&self) -> usize {
+        if self.layout.skinny {
+            return 0;
+        }
+
+        let wh: usize = Scale::default().points_from_inches(self.layout.padding.up + self.layout.padding.down);
+        match &self.node {
+            LayoutNode::Node(n) => n.height() + wh,
+            LayoutNode::SubCluster(cluster) => cluster.compute_height() + wh,
+        }
+    }
+}
+
+impl<N> GridChild<N> {
+    pub fn unwrap_node(&self) -> &N {
+        if let LayoutNode::Node(n) = &self.node {
+            n
+        } else {
+            panic!("cannot unwrap SubCluster")
+        }
+    }
+
+    pub fn unwrap_cluster(&self) -> &GraphLayout<N> {
+        if let LayoutNode::SubCluster(n) = &self.node {
+            n
+        } else {
+            panic!("cannot unwrap Node")
+        }
+    }
+
+    pub fn unwrap_node_mut(&mut self) -> &mut N {
+        if let LayoutNode::Node(n) = &mut self.node {
+            n
+        } else {
+            panic!("cannot unwrap SubCluster")
+        }
+    }
+
+    pub fn unwrap_cluster_mut(&mut self) -> &mut GraphLayout<N> {
+        if let LayoutNode::SubCluster(n) = &mut self.node {
+            n
+        } else {
+            panic!("cannot unwrap Node")
+        }
+    }
+}
+
+impl<N: Dimensions + std::fmt::Debug> GraphLayout<N> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// place a node (or a nested layout) at the given row and column,
+    /// returning whatever was there before
+    pub fn insert(&mut self, row: usize, col: usize, node: LayoutNode<N>, layout: Layout) -> Option<GridChild<N>> {
+        self.children.insert((row, col), GridChild { node, layout })
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> Option<&GridChild<N>> {
+        self.children.get(&(row, col))
+    }
+
+    pub fn get_mut(&mut self, row: usize, col: usize) -> Option<&mut GridChild<N>> {
+        self.children.get_mut(&(row, col))
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.children.keys().map(|(r, _)| r).max().map(|x| x + 1).unwrap_or(0)
+    }
+
+    pub fn num_cols(&self) -> usize {
+        self.children.keys().map(|(_, c)| c).max().map(|x| x + 1).unwrap_or(0)
+    }
+
+    fn compute_max_width_heights(&self) -> (HashMap<usize, usize>, HashMap<usize, usize>) {
+        let mut widths: HashMap<usize, usize> = HashMap::new();
+        let mut heights: HashMap<usize, usize> = HashMap::new();
+
+        for r in 0..self.num_rows() {
+            for c in 0..self.num_cols() {
+                let (w, h) = if let Some(child) = self.children.get(&(r, c)) {
+                    (child.compute_width(), child.compute_height())
+                } else {
+                    (0, 0)
+                };
+
+                heights.entry(r).or_insert(0);
+                widths.entry(c).or_insert(0);
+
+                heights.insert(r, std::cmp::max(heights[&r], h));
+                widths.insert(c, std::cmp::max(widths[&c], w));
+            }
+        }
+
+        (widths, heights)
+    }
+
+    /// the width (in points) of the whole grid
+    pub fn compute_width(&self) -> usize {
+        let (widths, _) = self.compute_max_width_heights();
+        widths.values().sum::<usize>()
+    }
+
+    /// the height (in points) of the whole grid
+    pub fn compute_height(&self) -> usize {
+        let (_, heights) = self.compute_max_width_heights();
+        heights.values().sum::<usize>()
+    }
+
+    fn accumulate_max_widths_heights(
+        &self,
+        start_x: i64,
+        start_y: i64,
+        widths: &HashMap<usize, usize>,
+        heights: &HashMap<usize, usize>,
+    ) -> (HashMap<usize, i64>, HashMap<usize, i64>) {
+        let mut acc_widths: HashMap<usize, i64> = HashMap::new();
+        let mut acc_heights: HashMap<usize, i64> = HashMap::new();
+
+        let mut acc_width: i64 = start_x;
+        let mut acc_height: i64 = start_y;
+
+        for r in 0..self.num_rows() {
+            acc_heights.insert(r, acc_height);
+            acc_height += heights[&r] as i64;
+        }
+
+        for c in 0..self.num_cols() {
+            acc_widths.insert(c, acc_width);
+            acc_width += widths[&c] as i64;
+        }
+
+        (acc_widths, acc_heights)
+    }
+
+    /// explode every nested layout into this one, so the result is a
+    /// single grid of nodes
+    ///
+    /// a row or column holding a nested layout is split into as many
+    /// rows or columns as that layout has, and single nodes sharing the
+    /// column are placed in one of the new columns by their alignment
+    pub fn flatten(&mut self) {
+        let mut row_exploders: HashMap<usize, usize> = HashMap::new();
+        let mut col_exploders: HashMap<usize, usize> = HashMap::new();
+
+        for r in 0..self.num_rows() {
+            row_exploders.entry(r).or_insert(1);
+            for c in 0..self.num_cols() {
+                let node = self.children.get(&(r, c));
+                col_exploders.entry(c).or_insert(1);
+                if let Some(GridChild { node: LayoutNode::SubCluster(cluster), .. }) = node {
+                    if let Some(v) = col_exploders.insert(c, cluster.num_cols()) {
+                        col_exploders.insert(c, std::cmp::max(v, cluster.num_cols()));
+                    }
+                    if let Some(v) = row_exploders.insert(r, cluster.num_rows()) {
+                        row_exploders.insert(r, std::cmp::max(v, cluster.num_rows()));
+                    }
+                }
+            }
+        }
+
+        let (cum_cols, cum_rows) = self.accumulate_max_widths_heights(0, 0, &col_exploders, &row_exploders);
+        let mut new_children: HashMap<(usize, usize), GridChild<N>> = HashMap::new();
+        let mut count_subclusters = 0;
+
+        for ((r, c), child_node) in self.children.drain() {
+            let row_start = cum_rows.get(&r).unwrap_or(&0);
+            let col_start = cum_cols.get(&c).unwrap_or(&0);
+            let (row_start, col_start) = (*row_start as usize, *col_start as usize);
+            match child_node.node {
+                LayoutNode::SubCluster(mut cluster) => {
+                    count_subclusters += 1;
+
+                    let maxrow: usize = cluster.children.keys().map(|(r, _)| *r).max().unwrap_or(1);
+                    let maxcol: usize = cluster.children.keys().map(|(_, c)| *c).max().unwrap_or(1);
+
+                    for ((subrow, subcol), mut n) in cluster.children.drain() {
+                        if subrow == 0 {
+                            n.layout.padding.up = child_node.layout.padding.up;
+                        };
+                        if subcol == 0 {
+                            n.layout.padding.left = child_node.layout.padding.left;
+                        }
+                        if subrow == maxrow {
+                            n.layout.padding.down = child_node.layout.padding.down;
+                        }
+                        if subcol == maxcol {
+                            n.layout.padding.right = child_node.layout.padding.right;
+                        }
+
+                        match new_children.insert((row_start + subrow, col_start + subcol), n) {
+                            None => {}
+                            Some(old) => {
+                                panic!(
+                                    "oops! placed a subcluster child at already-existing addr ({}+{},{}+{}): {:?}",
+                                    row_start, subrow, col_start, subcol, old
+                                );
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    // if we had a single node and the ones below/above got split up
+                    // we have to decide which column to place this single node in now
+                    // and we use the alignment to decide ...
+                    let new_cols = *col_exploders.get(&c).unwrap();
+                    let subcoloffs = match child_node.layout.alignment {
+                        Align::Left => 0,
+                        Align::Middle => new_cols / 2,
+                        Align::Right => new_cols - 1,
+                    };
+
+                    match new_children.insert((row_start, col_start + subcoloffs), child_node) {
+                        None => {}
+                        Some(old) => {
+                            panic!("oops! placed a second child at {:?}: {:?}", (row_start, col_start), old);
+                        }
+                    }
+                }
+            }
+        }
+
+        self.children = new_children;
+
+        // if there were any clusters left
+        // recurse and explode those too
+        if count_subclusters > 0 {
+            self.flatten()
+        }
+    }
+
+    /// go through all children and attach a physical position
+    /// (in points) at which to place the node.
+    ///
+    /// a subcluster position is marked by the top-left of the bounding box
+    /// whereas a node's position is marked by the centre of the physical node
+    pub fn accumulate_positions(&mut self, start_x: i64, start_y: i64) {
+        let (max_widths, max_heights) = self.compute_max_width_heights();
+        let (cum_widths, cum_heights) = self.accumulate_max_widths_heights(start_x, start_y, &max_widths, &max_heights);
+
+        for (&(r, c), child) in self.children.iter_mut() {
+            let (x, y) = (cum_widths[&c], cum_heights[&r]);
+            let node_width = child.compute_width() as i64;
+            let col_width = max_widths[&c] as i64;
+            let node_layout = &child.layout;
+
+            // the breathing room around
+            let (wxl, wyu) = (
+                Scale::default().points_from_inches(node_layout.padding.left) as i64,
+                Scale::default().points_from_inches(node_layout.padding.up) as i64,
+            );
+
+            // align left/middle/right according to layout instructions
+            let xleft = match node_layout.alignment {
+                Align::Left => x,
+                Align::Middle => x + col_width / 2 - node_width / 2,
+                Align::Right => x + col_width - node_width,
+            };
+
+            match child.node {
+                LayoutNode::Node(ref n) => {
+                    let (actual_node_width, actual_node_height) = (n.width() as i64, n.height() as i64);
+
+                    // graphviz "pos" is middle of node
+                    // so we +w/2,h/2 to make the pos be the top-left
+                    child.layout.bb_pos = Some((xleft, y));
+                    child.layout.pos = Some((xleft + wxl + actual_node_width / 2, y + wyu + actual_node_height / 2));
+                }
+                LayoutNode::SubCluster(ref mut cluster) => {
+                    child.layout.bb_pos = Some((x, y));
+                    child.layout.pos = Some((x, y));
+                    cluster.accumulate_positions(xleft + wxl, y + wyu);
+                }
+            };
+        }
+    }
+
+    /// move every positioned child in the layout by (dx, dy) points
+    pub fn shift(&mut self, (dx, dy): (i64, i64)) {
+        for child in self.children.values_mut() {
+            child.layout.pos = child.layout.pos.map(|(x, y)| (x + dx, y + dy));
+            child.layout.bb_pos = child.layout.bb_pos.map(|(x, y)| (x + dx, y + dy));
+            if let LayoutNode::SubCluster(cluster) = &mut child.node {
+                cluster.shift((dx, dy))
+            }
+        }
+    }
+
+    /// the (top-left, bottom-right) corners (in points) of the bounding box
+    /// around the real nodes in the layout, or None if none have been positioned
+    pub fn bounding_box(&self, only_visible: bool) -> Option<((i64, i64), (i64, i64))> {
+        let mut tl: (i64, i64) = (i64::MAX, i64::MAX);
+        let mut br: (i64, i64) = (0, 0);
+        let mut found = false;
+        for n in self.iter_nodes(only_visible, true) {
+            if let LayoutNode::Node(node) = &n.node {
+                let (nw, nh) = (node.width() as i64, node.height() as i64);
+
+                // use the pos of the bounding box
+                // not the centre of the node
+                if let Some((x, y)) = n.layout.bb_pos {
+                    found = true;
+                    br = (br.0.max(x + nw), br.1.max(y + nh));
+                    tl = (tl.0.min(x), tl.1.min(y));
+                };
+            };
+        }
+
+        if found {
+            Some((tl, br))
+        } else {
+            None
+        }
+    }
+
+    /// the name, centre position, and size (all in points) of each
+    /// shown node, in row-major order through the nested layouts
+    ///
+    /// nodes are only positioned by [GraphLayout::accumulate_positions],
+    /// so any that have not been are left out
+    pub fn positions(&self) -> impl Iterator<Item = (String, (i64, i64), (usize, usize))> + '_ {
+        let mut addrs: Vec<&(usize, usize)> = self.children.keys().collect();
+        addrs.sort();
+        addrs.into_iter().flat_map(move |addr| {
+            let child = &self.children[addr];
+            let positions: Box<dyn Iterator<Item = _>> = match &child.node {
+                _ if !child.layout.show => Box::new(std::iter::empty()),
+                LayoutNode::Node(n) => {
+                    Box::new(child.layout.pos.map(|pos| (n.name(), pos, (n.width(), n.height()))).into_iter())
+                }
+                LayoutNode::SubCluster(cluster) => Box::new(cluster.positions()),
+            };
+            positions
+        })
+    }
+
+    pub fn iter_nodes(&self, only_visible: bool, only_real: bool) -> Vec<&GridChild<N>> {
+        let mut nodes: Vec<&GridChild<N>> = Vec::new();
+
+        for c in self.children.values() {
+            if !c.layout.show && only_visible {
+                continue;
+            }
+
+            if c.layout.skinny && only_real {
+                continue;
+            }
+
+            match &c.node {
+                LayoutNode::Node(_) => nodes.push(c),
+                LayoutNode::SubCluster(cluster) => {
+                    let sub_nodes = cluster.iter_nodes(only_visible, only_real);
+                    nodes.extend(sub_nodes);
+                }
+            }
+        }
+
+        nodes
+    }
+
+    pub fn iter_nodes_mut(&mut self, only_visible: bool, only_real: bool) -> Vec<&mut GridChild<N>> {
+        let mut nodes: Vec<&mut GridChild<N>> = Vec::new();
+
+        for c in self.children.values_mut() {
+            if !c.layout.show && only_visible {
+                continue;
+            }
+
+            if c.layout.skinny && only_real {
+                continue;
+            }
+
+            match c.node {
+                LayoutNode::Node(_) => nodes.push(c),
+                LayoutNode::SubCluster(ref mut cluster) => {
+                    let sub_nodes = cluster.iter_nodes_mut(only_visible, only_real);
+                    nodes.extend(sub_nodes);
+                }
+            }
+        }
+
+        nodes
+    }
+
+    pub fn find_node_mut(&mut self, name: &str) -> Option<&mut GridChild<N>> {
+        for n in self.iter_nodes_mut(false, false) {
+            if let LayoutNode::Node(node) = &n.node {
+                if node.name() == name {
+                    return Some(n);
+                }
+            }
+        }
+
+        None
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::graph::tests::{fault_event, test_graph};
+
     #[test]
     fn test_ev_labeller() {
         let mut evlab = EventLabeller::default();
@@ -815,4 +1333,330 @@ mod tests {
         evlab.char_idx = 702;
         assert!(evlab.prefix() == "aaa");
     }
+
+    fn test_opts() -> GraphOpts {
+        GraphOpts { show_regs: HashSet::new(), derive_fr: false, ..GraphOpts::default() }
+    }
+
+    #[test]
+    fn test_many_instrs_labelled() {
+        // 31 single-event instructions followed by a two-event instruction, more than a-z can label
+        let mut events: Vec<GraphEvent> = (0..31).map(|po| fault_event(0, po, 0)).collect();
+        events.push(fault_event(0, 31, 0));
+        events.push(fault_event(0, 31, 1));
+
+        let opts = test_opts();
+        let g = test_graph(events, vec![], &opts);
+
+        let layout = GridLayout::from_graph(&g, &opts);
+        let instrs = &layout.threads[0].instr_instances;
+        assert_eq!(instrs.len(), 32);
+
+        let labels: Vec<&(String, String)> = instrs.iter().flat_map(|i| i.events()).map(|n| &n.ev_label).collect();
+        assert_eq!(labels[0], &("a".to_string(), "".to_string()));
+        assert_eq!(labels[25], &("z".to_string(), "".to_string()));
+        assert_eq!(labels[26], &("aa".to_string(), "".to_string()));
+        assert_eq!(labels[30], &("ae".to_string(), "".to_string()));
+        assert_eq!(labels[31], &("af".to_string(), "0".to_string()));
+        assert_eq!(labels[32], &("af".to_string(), "1".to_string()));
+    }
+
+    #[test]
+    fn test_event_in_shows() {
+        let shows = |specs: &[&str]| Some(specs.iter().map(|spec| spec.parse().unwrap()).collect());
+        let translate = |thread_id, po, stage, level, for_s1| GraphEvent {
+            event_kind: GraphEventKind::Translate(TranslateKind { stage, level, for_s1 }),
+            ..fault_event(thread_id, po, level)
+        };
+
+        assert!(!event_in_shows(&None, &fault_event(0, 1, 0)));
+        assert!(event_in_shows(&shows(&["R0_1_0"]), &fault_event(0, 1, 0)));
+        assert!(!event_in_shows(&shows(&["R0_1_0"]), &fault_event(0, 1, 1)));
+
+        assert!(event_in_shows(&shows(&["0:2"]), &fault_event(0, 2, 3)));
+        assert!(!event_in_shows(&shows(&["0:2"]), &fault_event(1, 2, 0)));
+        assert!(event_in_shows(&shows(&["1:1-3"]), &fault_event(1, 3, 0)));
+        assert!(!event_in_shows(&shows(&["1:1-3"]), &fault_event(1, 4, 0)));
+
+        assert!(event_in_shows(&shows(&["T0:1:s1l3"]), &translate(0, 1, 1, 3, None)));
+        assert!(event_in_shows(&shows(&["T0:1:s1l3"]), &translate(0, 1, 1, 3, Some(0))));
+        assert!(!event_in_shows(&shows(&["T0:1:s1l3"]), &translate(0, 1, 1, 3, Some(1))));
+        assert!(!event_in_shows(&shows(&["T0:1:s1l3"]), &translate(0, 1, 2, 3, None)));
+        assert!(!event_in_shows(&shows(&["T0:1:s1l3"]), &fault_event(0, 1, 3)));
+        assert!(event_in_shows(&shows(&["T0:0-2:s1l3"]), &translate(0, 2, 1, 3, None)));
+        assert!(!event_in_shows(&shows(&["T0:0-2:s1l3"]), &translate(0, 3, 1, 3, None)));
+    }
+
+    #[test]
+    fn test_transitive_reduce() {
+        use crate::graph::graph_events::parse_relname_opt;
+
+        // co over a chain of 5 writes, including all the implied edges
+        let writes: Vec<String> = (0..5).map(|i| format!("W{}", i)).collect();
+        let mut co = HashSet::new();
+        for i in 0..5 {
+            for j in (i + 1)..5 {
+                co.insert((writes[i].clone(), writes[j].clone()));
+            }
+        }
+        assert_eq!(co.len(), 10);
+
+        let mut opts = test_opts();
+        let (_, relty) = parse_relname_opt("rf", &opts);
+        assert_eq!(simplify_edges(&relty, &co).len(), 10);
+
+        opts.transitive_reduce.insert("rf".to_string());
+        let (_, relty) = parse_relname_opt("rf", &opts);
+        let reduced = simplify_edges(&relty, &co);
+        assert_eq!(reduced.len(), 4);
+        for i in 0..4 {
+            assert!(reduced.contains(&(writes[i].clone(), writes[i + 1].clone())))
+        }
+
+        // symmetric relations are left untouched
+        let mut symmetric = co.clone();
+        symmetric.extend(co.iter().map(|(from, to)| (to.clone(), from.clone())));
+        assert_eq!(simplify_edges(&relty, &symmetric), symmetric);
+    }
+
+    #[test]
+    fn test_collapse_translations() {
+        use crate::graph::graph_events::parse_relname_opt;
+
+        // a stage 1 walk for a read on thread 0, and a write on thread 1
+        let mut events: Vec<GraphEvent> = (0..3)
+            .map(|level| GraphEvent {
+                name: format!("T0_1_{}", level),
+                event_kind: GraphEventKind::Translate(TranslateKind { stage: 1, level, for_s1: None }),
+                ..fault_event(0, 1, level)
+            })
+            .collect();
+        events.push(GraphEvent {
+            name: "R0_1_3".to_string(),
+            value: Some(GraphValue::from_fields("R", Some("0x1000".to_string()), None, 8, None)),
+            event_kind: GraphEventKind::ReadMem,
+            ..fault_event(0, 1, 3)
+        });
+        events.push(GraphEvent { event_kind: GraphEventKind::ReadMem, ..fault_event(1, 0, 0) });
+
+        let relation = |name: &str, edges: &[(&str, &str)]| {
+            let edges: HashSet<(String, String)> =
+                edges.iter().map(|(from, to)| (from.to_string(), to.to_string())).collect();
+            let (_, ty) = parse_relname_opt(name, &test_opts());
+            GraphRelation { name: name.to_string(), ty, edges: edges.clone(), all_edges: edges }
+        };
+        let relations = vec![
+            relation("iio", &[("T0_1_0", "T0_1_1"), ("T0_1_1", "T0_1_2"), ("T0_1_2", "R0_1_3")]),
+            relation("trf", &[("R1_0_0", "T0_1_0"), ("R1_0_0", "T0_1_2")]),
+        ];
+
+        let mut g = test_graph(events.clone(), relations.clone(), &test_opts());
+        g.collapse_translations();
+
+        let mut names: Vec<&str> = g.events.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        assert_eq!(names, vec!["R0_1_3", "R1_0_0", "TW0_1"]);
+        let walk = &g.events["TW0_1"];
+        assert!(matches!(walk.event_kind, GraphEventKind::TranslateWalk));
+        assert_eq!(walk.value.as_ref().and_then(|v| v.address.as_deref()), Some("0x1000"));
+
+        let edges = |g: &Graph, rel: &str| g.relations.iter().find(|r| r.name == rel).unwrap().edges.clone();
+        assert_eq!(edges(&g, "iio"), HashSet::from([("TW0_1".to_string(), "R0_1_3".to_string())]));
+        assert_eq!(edges(&g, "trf"), HashSet::from([("R1_0_0".to_string(), "TW0_1".to_string())]));
+
+        // naming any event of the walk keeps the whole walk
+        let mut opts = test_opts();
+        opts.force_show_events = Some(vec!["T0:1:s1l1".parse().unwrap()]);
+        let mut g = test_graph(events, relations, &opts);
+        g.collapse_translations();
+        assert_eq!(g.events.len(), 5);
+        assert_eq!(edges(&g, "trf").len(), 2);
+    }
+
+    #[test]
+    fn test_trace_origin_round_trip() {
+        use isla_lib::bitvector::{b64::B64, BV};
+        use isla_lib::config::ISAConfig;
+        use isla_lib::ir::{Def, IRTypeInfo, Name, SharedState, Symtab, Ty, Val};
+        use isla_lib::smt::{Event, ReadOpts, Sym, WriteOpts};
+        use isla_mml::memory_model;
+
+        use crate::axiomatic::{ExecutionInfo, TraceOrigin};
+        use crate::litmus::Objdump;
+
+        let mut ir_symtab = Symtab::new();
+        let pc = ir_symtab.intern("zPC");
+        let defs: Vec<Def<Name, B64>> = vec![Def::Register(pc, Ty::Bits(64), vec![])];
+        let type_info = IRTypeInfo::new(&defs);
+        let config = r#"
+            pc = "PC"
+            assembler = "false"
+            objdump = "false"
+            nm = "false"
+            linker = "false"
+
+            [mmu]
+            page_table_base = "0x300000"
+            page_size = "4096"
+            s2_page_table_base = "0x200000"
+            s2_page_size = "4096"
+
+            [threads]
+            base = "0x400000"
+            top = "0x500000"
+            stride = "0x1000"
+
+            [symbolic_addrs]
+            base = "0x600000"
+            top = "0x700000"
+            stride = "0x10"
+        "#;
+        let isa_config: ISAConfig<B64> = ISAConfig::parse(config, None, &ir_symtab, &type_info).unwrap();
+        let shared_state = SharedState::new(
+            ir_symtab,
+            &defs,
+            type_info,
+            HashSet::new(),
+            HashSet::new(),
+            HashSet::new(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+        );
+
+        // a thread performing a read then a write, each in their own instruction
+        let trace: Vec<Event<B64>> = vec![
+            Event::Cycle,
+            Event::Instr(Val::Bits(B64::from_u32(0xf9400020))),
+            Event::ReadMem {
+                value: Val::Symbolic(Sym::from_u32(0)),
+                read_kind: Val::Unit,
+                address: Val::Bits(B64::from_u64(0x1000)),
+                bytes: 8,
+                tag_value: None,
+                opts: ReadOpts::default(),
+                region: "default",
+            },
+            Event::Cycle,
+            Event::Instr(Val::Bits(B64::from_u32(0xf9000020))),
+            Event::WriteMem {
+                value: Sym::from_u32(1),
+                write_kind: Val::Unit,
+                address: Val::Bits(B64::from_u64(0x2000)),
+                data: Val::Bits(B64::from_u64(1)),
+                bytes: 8,
+                tag_value: None,
+                opts: WriteOpts::default(),
+                region: "default",
+            },
+        ];
+        let candidate: Vec<&[Event<B64>]> = vec![&trace];
+
+        let mut mm_symtab = memory_model::Symtab::new();
+        let exec =
+            ExecutionInfo::from(&candidate, &shared_state, &isa_config, &test_opts(), false, &mut mm_symtab).unwrap();
+        let events: Vec<GraphEvent> =
+            exec.smt_events.iter().map(|ev| GraphEvent::from_axiomatic(ev, &Objdump::empty(), None)).collect();
+        let g = test_graph(events, vec![], &test_opts());
+
+        assert_eq!(g.events.len(), 2);
+        for name in g.events.keys() {
+            let origin = g.trace_origin(name).unwrap();
+            assert_eq!(origin.thread_id, 0);
+            match &trace[origin.index] {
+                Event::ReadMem { .. } => assert!(name.starts_with('R')),
+                Event::WriteMem { .. } => assert!(name.starts_with('W')),
+                ev => panic!("graph event {} originates from unexpected trace event {:?}", name, ev),
+            }
+            assert_eq!(&g.event_from_trace(origin).unwrap().name, name)
+        }
+        assert!(g.event_from_trace(TraceOrigin { thread_id: 0, index: 1 }).is_none());
+    }
+
+    #[derive(Debug, Clone)]
+    struct Block(&'static str, usize, usize);
+
+    impl Dimensions for Block {
+        fn name(&self) -> String {
+            self.0.to_string()
+        }
+
+        fn width(&self) -> usize {
+            self.1
+        }
+
+        fn height(&self) -> usize {
+            self.2
+        }
+    }
+
+    fn block_layout() -> GraphLayout<Block> {
+        let node = |name, w, h| LayoutNode::Node(Block(name, w, h));
+        let mut cluster = GraphLayout::new();
+        cluster.insert(0, 0, node("D", 4, 4), Layout::new(Padding::default(), Align::Left));
+        cluster.insert(1, 0, node("E", 4, 4), Layout::new(Padding::default(), Align::Left));
+
+        let mut layout = GraphLayout::new();
+        layout.insert(0, 0, node("A", 10, 10), Layout::new(Padding::default(), Align::Middle));
+        layout.insert(0, 1, node("B", 20, 10), Layout::new(Padding::default(), Align::Middle));
+        layout.insert(1, 0, node("C", 10, 30), Layout::new(Padding::default(), Align::Middle));
+        layout.insert(1, 1, LayoutNode::SubCluster(cluster), Layout::new(Padding::default(), Align::Left));
+        layout
+    }
+
+    #[test]
+    fn test_scale() {
+        let unit = Scale::default();
+        assert_eq!(unit.points_from_inches(0.5), 36);
+        assert_eq!(unit.inches_from_points(36), 0.5);
+        let double = Scale::new(2.0);
+        assert_eq!(double.points_from_inches(0.5), 72);
+        assert_eq!(double.inches_from_points(36), 1.0);
+        assert_eq!(double.points(-13), -26);
+        assert_eq!(Scale::new(0.5).points(13), 7);
+    }
+
+    #[test]
+    fn test_graph_layout_positions() {
+        let mut layout = block_layout();
+        assert_eq!(layout.positions().count(), 0);
+
+        layout.accumulate_positions(0, 0);
+        let positions: Vec<_> = layout.positions().collect();
+        assert_eq!(
+            positions,
+            vec![
+                ("A".to_string(), (5, 5), (10, 10)),
+                ("B".to_string(), (20, 5), (20, 10)),
+                ("C".to_string(), (5, 25), (10, 30)),
+                ("D".to_string(), (12, 12), (4, 4)),
+                ("E".to_string(), (12, 16), (4, 4)),
+            ]
+        );
+        assert_eq!(layout.bounding_box(true), Some(((0, 0), (30, 40))));
+
+        layout.get_mut(0, 1).unwrap().layout.show = false;
+        assert!(layout.positions().all(|(name, _, _)| name != "B"))
+    }
+
+    #[test]
+    fn test_graph_layout_flatten() {
+        let mut layout = block_layout();
+        layout.flatten();
+        assert_eq!((layout.num_rows(), layout.num_cols()), (3, 2));
+        assert!(layout.get(2, 1).is_some());
+
+        layout.accumulate_positions(0, 0);
+        let positions: Vec<_> = layout.positions().map(|(name, pos, _)| (name, pos)).collect();
+        assert_eq!(
+            positions,
+            vec![
+                ("A".to_string(), (5, 5)),
+                ("B".to_string(), (20, 5)),
+                ("C".to_string(), (5, 25)),
+                ("D".to_string(), (12, 12)),
+                ("E".to_string(), (12, 42)),
+            ]
+        );
+    }
 }
@@ -1,63 +1,33 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::io;
+use std::sync::Arc;
 
 use isla_lib::log;
 
 use super::graph_events::*;
 use super::graph_opts::*;
 use super::grid_layout::*;
+use super::text_measure::*;
 
+use crate::axiomatic::ThreadId;
 use crate::litmus::LitmusGraphOpts;
 
-/// padding around a child
-/// in inches
-#[derive(Debug, Clone)]
-struct Padding {
-    up: f64,
-    down: f64,
-    left: f64,
-    right: f64,
-}
-
-#[derive(Debug, Clone)]
-struct Layout {
-    /// padding around the child
-    /// up, down, left, right
-    /// in points
-    padding: Padding,
-    /// alignment within the column
-    alignment: Align,
-    /// the position (in points) to place the child at
-    /// this gets filled in later by the layouter
-    /// for a Node this is the centre of the node
-    pos: Option<(i64, i64)>,
-    /// the position (in points) of the top-left of the bounding box
-    bb_pos: Option<(i64, i64)>,
-    /// if false, do not render in the final image
-    show: bool,
-    /// if false, the node has 0width and 0height for layouting purposes
-    skinny: bool,
-}
-
-#[derive(Debug, Clone)]
-enum GVGridNode<'a> {
-    Node(PositionedGraphNode<'a>),
-    SubCluster(GraphLayout<'a>),
-}
+use routing::{Point, Rect};
 
-#[derive(Debug, Clone)]
-struct GVGridChild<'a> {
-    /// the node
-    node: GVGridNode<'a>,
-    /// layout information about the child
-    layout: Layout,
-}
+mod ascii;
+mod dot;
+mod graphml;
+mod html;
+mod routing;
+mod svg;
+pub use ascii::draw_graph_ascii;
+pub use graphml::draw_graph_graphml;
+pub use html::draw_graphs_html;
+pub use svg::draw_graph_svg;
 
-/// a GraphLayout is a hierarchical row/column layout
-#[derive(Debug, Clone)]
-struct GraphLayout<'a> {
-    children: HashMap<(usize, usize), GVGridChild<'a>>,
-}
+type GVGridNode<'ev> = LayoutNode<PositionedGraphNode<'ev>>;
+type GVGridChild<'ev> = GridChild<PositionedGraphNode<'ev>>;
+type GVGraphLayout<'ev> = GraphLayout<PositionedGraphNode<'ev>>;
 
 #[derive(Debug, Clone)]
 pub struct Style {
@@ -68,45 +38,36 @@ pub struct Style {
     pub dimensions: (f64, f64),
 }
 
-fn event_style<T>(gn: &GridNode<'_, T>) -> Style {
-    // TODO: BS: do we want to colour-code event types?
-    // e.g. Ts2 => wheat1, Ts1 => darkslategray1
-    if let Some(ev) = gn.ev {
+fn event_style<T>(gn: &GridNode<'_, T>, palette: &EventPalette) -> Style {
+    // a collapsed translation table walk stands in for many events,
+    // so draw it with a bold border to set it apart from them
+    let is_walk = matches!(gn.ev.map(|ev| &ev.event_kind), Some(GraphEventKind::TranslateWalk));
+    // register events are drawn as ellipses, so they stand out from the memory events around them
+    let is_register =
+        matches!(gn.ev.map(|ev| &ev.event_kind), Some(GraphEventKind::ReadReg | GraphEventKind::WriteReg));
+
+    let bg_color = if let Some(ev) = gn.ev {
         match ev.event_kind {
-            GraphEventKind::Translate(_) if true => Style {
-                bg_color: "darkslategray1".to_string(),
-                node_shape: "box".to_string(),
-                node_style: "filled".to_string(),
-                dimensions: (0.0, 0.0),
-            },
-            GraphEventKind::Translate(TranslateKind { stage: 1, .. })
-            | GraphEventKind::WriteMem(WriteKind { to_translation_table_entry: Some(1) }) => Style {
-                bg_color: "white".to_string(),
-                node_shape: "box".to_string(),
-                node_style: "filled".to_string(),
-                dimensions: (0.0, 0.0),
-            },
-            GraphEventKind::Translate(TranslateKind { stage: 2, .. })
-            | GraphEventKind::WriteMem(WriteKind { to_translation_table_entry: Some(2) }) => Style {
-                bg_color: "white".to_string(),
-                node_shape: "box".to_string(),
-                node_style: "filled".to_string(),
-                dimensions: (0.0, 0.0),
-            },
-            _ => Style {
-                bg_color: "white".to_string(),
-                node_shape: "box".to_string(),
-                node_style: "filled".to_string(),
-                dimensions: (0.0, 0.0),
-            },
+            GraphEventKind::TranslateWalk => &palette.translate_walk,
+            GraphEventKind::Translate(TranslateKind { stage: 1, .. }) => &palette.translate_s1,
+            GraphEventKind::Translate(TranslateKind { stage: 2, .. }) => &palette.translate_s2,
+            GraphEventKind::WriteMem(WriteKind { to_translation_table_entry: Some(_) }) => &palette.tte_write,
+            GraphEventKind::Ifetch => &palette.ifetch,
+            GraphEventKind::ReadMem => &palette.read,
+            GraphEventKind::WriteMem(WriteKind { to_translation_table_entry: None }) => &palette.write,
+            GraphEventKind::Barrier(_) => &palette.barrier,
+            GraphEventKind::ReadReg | GraphEventKind::WriteReg => &palette.register,
+            _ => &palette.other,
         }
     } else {
-        Style {
-            bg_color: "white".to_string(),
-            node_shape: "box".to_string(),
-            node_style: "filled".to_string(),
-            dimensions: (0.0, 0.0),
-        }
+        &palette.other
+    };
+
+    Style {
+        bg_color: bg_color.clone(),
+        node_shape: if is_register { "oval".to_string() } else { "box".to_string() },
+        node_style: if is_walk { "\"filled,bold\"".to_string() } else { "filled".to_string() },
+        dimensions: (0.0, 0.0),
     }
 }
 
@@ -116,6 +77,8 @@ struct PositionedGraphNode<'ev> {
     /// style information about the node
     /// to be passed to graphviz
     style: Style,
+    /// measures the label, to size the node around it
+    measure: Arc<dyn TextMeasure>,
 }
 
 impl<'ev> PositionedGraphNode<'ev> {
@@ -129,394 +92,82 @@ impl<'ev> PositionedGraphNode<'ev> {
 }
 
 const FONTSIZE: usize = 44;
-// with a scale of 72ppi
-const SCALE: f64 = 72.0;
-
-fn inches_from_points(p: usize) -> f64 {
-    (p as f64) / SCALE
-}
 
-fn points_from_inches(i: f64) -> usize {
-    (i * SCALE).round() as usize
+/// Write the attributes shared by every dot graph, with the font sizes
+/// multiplied by the scale
+fn write_graph_attrs(f: &mut dyn io::Write, scale: Scale) -> io::Result<()> {
+    writeln!(f, "    splines=true;")?;
+    writeln!(f, "    node [fontsize={}, fontname=aerial];", scale.points(FONTSIZE as i64))?;
+    writeln!(f, "    edge [fontsize={}, fontname=aerial, arrowsize=2];", scale.points(FONTSIZE as i64))?;
+    writeln!(f, "    graph [fontsize={}, fontname=aerial];", scale.points(40))
 }
 
-fn escape_str(s: String) -> String {
-    format!("{:?}", s)
-}
+impl Dimensions for PositionedGraphNode<'_> {
+    fn name(&self) -> String {
+        PositionedGraphNode::name(self)
+    }
 
-impl PositionedGraphNode<'_> {
-    /// the width (in points) of the actual underlying node shape
-    fn compute_width(&self) -> usize {
-        (FONTSIZE * 3 / 5) * self.ev.label.len()
+    fn width(&self) -> usize {
+        self.compute_width()
     }
 
-    /// the height (in points) of the actual underlying node shape
-    fn compute_height(&self) -> usize {
-        SCALE as usize
+    fn height(&self) -> usize {
+        self.compute_height()
     }
 }
 
-impl<'ev> GVGridChild<'ev> {
-    /// the width (in points) of the node or the child grid
+impl PositionedGraphNode<'_> {
+    /// the width (in points) of the actual underlying node shape, which
+    /// fits the longest line of the label
     fn compute_width(&self) -> usize {
-        if self.layout.skinny {
-            return 0;
-        }
-
-        let ww: usize = points_from_inches(self.layout.padding.left + self.layout.padding.right);
-        match &self.node {
-            GVGridNode::Node(pgn) => pgn.compute_width() + ww,
-            GVGridNode::SubCluster(cluster) => cluster.compute_width() + ww,
-        }
+        measure_label(&*self.measure, &self.ev.label, FONTSIZE).0
     }
 
-    /// the height (in points) of the node or the child grid
+    /// the height (in points) of the actual underlying node shape, with
+    /// room for each extra line of a wrapped label
     fn compute_height(&self) -> usize {
-        if self.layout.skinny {
-            return 0;
-        }
-
-        let wh: usize = points_from_inches(self.layout.padding.up + self.layout.padding.down);
-        match &self.node {
-            GVGridNode::Node(pgn) => pgn.compute_height() + wh,
-            GVGridNode::SubCluster(cluster) => cluster.compute_height() + wh,
-        }
+        let (_, height) = measure_label(&*self.measure, &self.ev.label, FONTSIZE);
+        POINTS_PER_INCH as usize + height.saturating_sub(self.measure.line_height(FONTSIZE))
     }
+}
 
+impl<'ev> GVGridChild<'ev> {
     /// a graphviz line for an event node
     /// in the following format:
     /// R1_79_0 [shape=box,pos="13,17!",label=<LABEL FORMAT>,fillcolor=wheat1,style=filled];
-    fn fmt_as_node(&self) -> String {
+    fn fmt_as_node(&self, scale: Scale) -> String {
         if let GVGridNode::Node(pge) = &self.node {
-            let node_attrs: Vec<(String, String)> = vec![
-                ("fillcolor".to_string(), pge.style.bg_color.to_string()),
+            let mut node_attrs: Vec<(String, String)> = vec![
+                ("fillcolor".to_string(), dot::id(&pge.style.bg_color).into_owned()),
                 ("style".to_string(), pge.style.node_style.to_string()),
                 (
                     "pos".to_string(),
-                    if let Some((x, y)) = self.layout.pos { format!("\"{},{}!\"", x, -y) } else { "\"\"".to_string() },
+                    if let Some((x, y)) = self.layout.pos {
+                        format!("\"{},{}!\"", scale.points(x), -scale.points(y))
+                    } else {
+                        "\"\"".to_string()
+                    },
                 ),
                 ("shape".to_string(), pge.style.node_shape.to_string()),
-                ("label".to_string(), escape_str(pge.ev.label.clone())),
+                ("label".to_string(), dot::quote_left_justified(&pge.ev.label)),
                 ("width".to_string(), pge.style.dimensions.0.to_string()),
                 ("height".to_string(), pge.style.dimensions.1.to_string()),
             ];
 
+            // record where the event came from, so the graph can be related back to the traces
+            if let Some(origin) = pge.ev.ev.and_then(|ev| ev.trace_origin) {
+                node_attrs.push(("comment".to_string(), format!("\"trace {}:{}\"", origin.thread_id, origin.index)))
+            }
+
             let attrs = node_attrs.iter().map(|(attr, val)| format!("{}={}", attr, val)).collect::<Vec<_>>().join(", ");
-            format!("{} [{}]", pge.name(), attrs)
+            format!("{} [{}]", dot::id(&pge.name()), attrs)
         } else {
             "N/A".to_string()
         }
     }
-
-    #[allow(dead_code)]
-    fn unwrap_node(&self) -> &PositionedGraphNode<'ev> {
-        if let GVGridNode::Node(n) = &self.node {
-            n
-        } else {
-            panic!("cannot unwrap SubCluster")
-        }
-    }
-
-    #[allow(dead_code)]
-    fn unwrap_cluster(&self) -> &GraphLayout<'ev> {
-        if let GVGridNode::SubCluster(n) = &self.node {
-            n
-        } else {
-            panic!("cannot unwrap Node")
-        }
-    }
-
-    #[allow(dead_code)]
-    fn unwrap_node_mut(&mut self) -> &mut PositionedGraphNode<'ev> {
-        if let GVGridNode::Node(n) = &mut self.node {
-            n
-        } else {
-            panic!("cannot unwrap SubCluster")
-        }
-    }
-
-    #[allow(dead_code)]
-    fn unwrap_cluster_mut(&mut self) -> &mut GraphLayout<'ev> {
-        if let GVGridNode::SubCluster(n) = &mut self.node {
-            n
-        } else {
-            panic!("cannot unwrap Node")
-        }
-    }
 }
 
-impl<'g> GraphLayout<'g> {
-    fn num_rows(&self) -> usize {
-        self.children.keys().map(|(r, _)| r).max().map(|x| x + 1).unwrap_or(0)
-    }
-
-    fn num_cols(&self) -> usize {
-        self.children.keys().map(|(_, c)| c).max().map(|x| x + 1).unwrap_or(0)
-    }
-
-    fn compute_max_width_heights(&self) -> (HashMap<usize, usize>, HashMap<usize, usize>) {
-        let mut widths: HashMap<usize, usize> = HashMap::new();
-        let mut heights: HashMap<usize, usize> = HashMap::new();
-
-        for r in 0..self.num_rows() {
-            for c in 0..self.num_cols() {
-                let (w, h) = if let Some(child) = self.children.get(&(r, c)) {
-                    (child.compute_width(), child.compute_height())
-                } else {
-                    (0, 0)
-                };
-
-                heights.entry(r).or_insert(0);
-                widths.entry(c).or_insert(0);
-
-                heights.insert(r, std::cmp::max(heights[&r], h));
-                widths.insert(c, std::cmp::max(widths[&c], w));
-            }
-        }
-
-        (widths, heights)
-    }
-
-    fn compute_width(&self) -> usize {
-        let (widths, _) = self.compute_max_width_heights();
-        widths.values().sum::<usize>()
-    }
-
-    fn compute_height(&self) -> usize {
-        let (_, heights) = self.compute_max_width_heights();
-        heights.values().sum::<usize>()
-    }
-
-    fn accumulate_max_widths_heights(
-        &self,
-        start_x: i64,
-        start_y: i64,
-        widths: &HashMap<usize, usize>,
-        heights: &HashMap<usize, usize>,
-    ) -> (HashMap<usize, i64>, HashMap<usize, i64>) {
-        let mut acc_widths: HashMap<usize, i64> = HashMap::new();
-        let mut acc_heights: HashMap<usize, i64> = HashMap::new();
-
-        let mut acc_width: i64 = start_x;
-        let mut acc_height: i64 = start_y;
-
-        for r in 0..self.num_rows() {
-            acc_heights.insert(r, acc_height);
-            acc_height += heights[&r] as i64;
-        }
-
-        for c in 0..self.num_cols() {
-            acc_widths.insert(c, acc_width);
-            acc_width += widths[&c] as i64;
-        }
-
-        (acc_widths, acc_heights)
-    }
-
-    fn flatten(&mut self) {
-        let mut row_exploders: HashMap<usize, usize> = HashMap::new();
-        let mut col_exploders: HashMap<usize, usize> = HashMap::new();
-
-        for r in 0..self.num_rows() {
-            row_exploders.entry(r).or_insert(1);
-            for c in 0..self.num_cols() {
-                let node = self.children.get(&(r, c));
-                col_exploders.entry(c).or_insert(1);
-                if let Some(GVGridChild { node: GVGridNode::SubCluster(cluster), .. }) = node {
-                    if let Some(v) = col_exploders.insert(c, cluster.num_cols()) {
-                        col_exploders.insert(c, std::cmp::max(v, cluster.num_cols()));
-                    }
-                    if let Some(v) = row_exploders.insert(r, cluster.num_rows()) {
-                        row_exploders.insert(r, std::cmp::max(v, cluster.num_rows()));
-                    }
-                }
-            }
-        }
-
-        let (cum_cols, cum_rows) = self.accumulate_max_widths_heights(0, 0, &col_exploders, &row_exploders);
-        let mut new_children: HashMap<(usize, usize), GVGridChild> = HashMap::new();
-        let mut count_subclusters = 0;
-
-        for ((r, c), child_node) in self.children.drain() {
-            let row_start = cum_rows.get(&r).unwrap_or(&0);
-            let col_start = cum_cols.get(&c).unwrap_or(&0);
-            let (row_start, col_start) = (*row_start as usize, *col_start as usize);
-            match child_node.node {
-                GVGridNode::SubCluster(mut cluster) => {
-                    count_subclusters += 1;
-
-                    let maxrow: usize = cluster.children.keys().map(|(r, _)| *r).max().unwrap_or(1);
-                    let maxcol: usize = cluster.children.keys().map(|(_, c)| *c).max().unwrap_or(1);
-
-                    for ((subrow, subcol), mut n) in cluster.children.drain() {
-                        if subrow == 0 {
-                            n.layout.padding.up = child_node.layout.padding.up;
-                        };
-                        if subcol == 0 {
-                            n.layout.padding.left = child_node.layout.padding.left;
-                        }
-                        if subrow == maxrow {
-                            n.layout.padding.down = child_node.layout.padding.down;
-                        }
-                        if subcol == maxcol {
-                            n.layout.padding.right = child_node.layout.padding.right;
-                        }
-
-                        match new_children.insert((row_start + subrow, col_start + subcol), n) {
-                            None => {}
-                            Some(old) => {
-                                panic!(
-                                    "oops! placed a subcluster child at already-existing addr ({}+{},{}+{}): {:?}",
-                                    row_start, subrow, col_start, subcol, old
-                                );
-                            }
-                        }
-                    }
-                }
-                _ => {
-                    // if we had a single node and the ones below/above got split up
-                    // we have to decide which column to place this single node in now
-                    // and we use the alignment to decide ...
-                    let new_cols = *col_exploders.get(&c).unwrap();
-                    let subcoloffs = match child_node.layout.alignment {
-                        Align::Left => 0,
-                        Align::Middle => new_cols / 2,
-                        Align::Right => new_cols - 1,
-                    };
-
-                    match new_children.insert((row_start, col_start + subcoloffs), child_node) {
-                        None => {}
-                        Some(old) => {
-                            panic!("oops! placed a second child at {:?}: {:?}", (row_start, col_start), old);
-                        }
-                    }
-                }
-            }
-        }
-
-        self.children = new_children;
-
-        // if there were any clusters left
-        // recurse and explode those too
-        if count_subclusters > 0 {
-            self.flatten()
-        }
-    }
-
-    /// go through all children and attach a physical position
-    /// (in points) at which to place the node.
-    ///
-    /// a subcluster position is marked by the top-left of the bounding box
-    /// whereas a node's position is marked by the centre of the physical node
-    fn accumulate_positions(&mut self, start_x: i64, start_y: i64) {
-        let (max_widths, max_heights) = self.compute_max_width_heights();
-        let (cum_widths, cum_heights) = self.accumulate_max_widths_heights(start_x, start_y, &max_widths, &max_heights);
-
-        for (&(r, c), child) in self.children.iter_mut() {
-            let (x, y) = (cum_widths[&c], cum_heights[&r]);
-            let node_width = child.compute_width() as i64;
-            let _node_height = child.compute_height() as i64;
-            let col_width = max_widths[&c] as i64;
-            let node_layout = &child.layout;
-
-            // the breathing room around
-            let (wxl, _wxr, wyu, _wyd) = (
-                points_from_inches(node_layout.padding.left) as i64,
-                points_from_inches(node_layout.padding.right) as i64,
-                points_from_inches(node_layout.padding.up) as i64,
-                points_from_inches(node_layout.padding.down) as i64,
-            );
-
-            // align left/middle/right according to layout instructions
-            let xleft = match node_layout.alignment {
-                Align::Left => x,
-                Align::Middle => x + col_width / 2 - node_width / 2,
-                Align::Right => x + col_width - node_width,
-            };
-
-            match child.node {
-                GVGridNode::Node(ref mut pgn) => {
-                    let (actual_node_width, actual_node_height) =
-                        (pgn.compute_width() as i64, pgn.compute_height() as i64);
-
-                    // graphviz "pos" is middle of node
-                    // so we +w/2,h/2 to make the pos be the top-left
-                    child.layout.bb_pos = Some((xleft, y));
-                    child.layout.pos = Some((xleft + wxl + actual_node_width / 2, y + wyu + actual_node_height / 2));
-                    pgn.style.dimensions = (
-                        inches_from_points(actual_node_width as usize),
-                        inches_from_points(actual_node_height as usize),
-                    );
-                }
-                GVGridNode::SubCluster(ref mut cluster) => {
-                    child.layout.bb_pos = Some((x, y));
-                    child.layout.pos = Some((x, y));
-                    cluster.accumulate_positions(xleft + wxl, y + wyu);
-                }
-            };
-        }
-    }
-
-    fn iter_nodes<'a>(&'a self, only_visible: bool, only_real: bool) -> Vec<&GVGridChild<'a>> {
-        let mut nodes: Vec<&GVGridChild<'a>> = Vec::new();
-
-        for c in self.children.values() {
-            if !c.layout.show && only_visible {
-                continue;
-            }
-
-            if c.layout.skinny && only_real {
-                continue;
-            }
-
-            match &c.node {
-                GVGridNode::Node(_) => nodes.push(c),
-                GVGridNode::SubCluster(cluster) => {
-                    let sub_nodes = cluster.iter_nodes(only_visible, only_real);
-                    nodes.extend(sub_nodes);
-                }
-            }
-        }
-
-        nodes
-    }
-
-    fn iter_nodes_mut(&mut self, only_visible: bool, only_real: bool) -> Vec<&mut GVGridChild<'g>> {
-        let mut nodes: Vec<&mut GVGridChild<'g>> = Vec::new();
-
-        for c in self.children.values_mut() {
-            if !c.layout.show && only_visible {
-                continue;
-            }
-
-            if c.layout.skinny && only_real {
-                continue;
-            }
-
-            match c.node {
-                GVGridNode::Node(_) => nodes.push(c),
-                GVGridNode::SubCluster(ref mut cluster) => {
-                    let sub_nodes = cluster.iter_nodes_mut(only_visible, only_real);
-                    nodes.extend(sub_nodes);
-                }
-            }
-        }
-
-        nodes
-    }
-
-    fn find_node_mut(&mut self, name: &str) -> Option<&mut GVGridChild<'g>> {
-        for n in self.iter_nodes_mut(false, false) {
-            if let GVGridNode::Node(pge) = &n.node {
-                if pge.name() == name {
-                    return Some(n);
-                }
-            }
-        }
-
-        None
-    }
-
+impl<'ev> GVGraphLayout<'ev> {
     fn po(&self) -> Option<usize> {
         for c in self.iter_nodes(false, false) {
             if let GVGridNode::Node(pgn) = &c.node {
@@ -589,12 +240,38 @@ impl PositionedGraphNode<'_> {
     }
 }
 
+/// Where the cluster containing all the threads is placed in the
+/// top-level layout, either below or to the right of the initial state
+fn threads_addr(orientation: GraphOrientation) -> (usize, usize) {
+    match orientation {
+        GraphOrientation::Vertical => (1, 0),
+        GraphOrientation::Horizontal => (0, 1),
+    }
+}
+
+/// Where each thread is placed within the threads cluster
+fn thread_addr(orientation: GraphOrientation, tid: usize) -> (usize, usize) {
+    match orientation {
+        GraphOrientation::Vertical => (0, tid),
+        GraphOrientation::Horizontal => (tid, 0),
+    }
+}
+
+/// Where each instruction is placed within its thread
+fn instr_addr(orientation: GraphOrientation, iid: usize) -> (usize, usize) {
+    match orientation {
+        GraphOrientation::Vertical => (iid, 0),
+        GraphOrientation::Horizontal => (0, iid),
+    }
+}
+
 fn produce_node_layout<'ev>(
     graph: &'ev Graph,
     _litmus_opts: &LitmusGraphOpts,
     opts: &GraphOpts,
     _pas: HashSet<&String>,
-) -> GraphLayout<'ev> {
+    show_iw: bool,
+) -> GVGraphLayout<'ev> {
     use GridInstrInstance::*;
     let grid: GridLayout<'ev, usize> = GridLayout::from_graph(graph, opts).annotate_widths(|gn| gn.label.len());
 
@@ -606,69 +283,60 @@ fn produce_node_layout<'ev>(
     let mut thread_ids: Vec<usize> = tids.into_iter().collect();
     thread_ids.sort_unstable();
 
-    let get_pad_or_default = |name: String, default: f64| match &opts.padding {
-        Some(hmap) => match hmap.get(&name) {
+    let make_padding = |name: &str, side: &SidePadding, up: f64, down: f64, left: f64, right: f64| {
+        let get_pad_or_default = |dir: &str, pad: Option<f64>, default: f64| match pad {
             Some(f) => {
-                log!(log::GRAPH, format!("using {} for {}", f, &name));
-                *f
+                log!(log::GRAPH, format!("using {} for {}-{}", f, name, dir));
+                f
             }
             None => default,
-        },
-        None => default,
+        };
+        Padding {
+            up: get_pad_or_default("up", side.up, up),
+            down: get_pad_or_default("down", side.down, down),
+            left: get_pad_or_default("left", side.left, left),
+            right: get_pad_or_default("right", side.right, right),
+        }
     };
 
-    let make_padding = |name: &str, up: f64, down: f64, left: f64, right: f64| Padding {
-        up: get_pad_or_default([name, "-", "up"].join(""), up),
-        down: get_pad_or_default([name, "-", "down"].join(""), down),
-        left: get_pad_or_default([name, "-", "left"].join(""), left),
-        right: get_pad_or_default([name, "-", "right"].join(""), right),
-    };
+    // the default padding separates the threads, and the instructions
+    // within them, in the direction they are laid out
+    let horizontal = opts.orientation == GraphOrientation::Horizontal;
 
     // layout information for the various parts of the graph
-    let layout_iw = Layout {
-        padding: make_padding("iw", 0.5, 1.0, 0.5, 0.5),
-        alignment: Align::Middle,
-        pos: None,
-        bb_pos: None,
-        show: true,
-        skinny: false,
-    };
-    let layout_threads = Layout {
-        padding: make_padding("threads", 0.0, 0.0, 0.0, 0.0),
-        alignment: Align::Left,
-        pos: None,
-        bb_pos: None,
-        show: true,
-        skinny: false,
-    };
-    let layout_thread = Layout {
-        padding: make_padding("thread", 0.0, 0.0, 0.0, 2.0),
-        alignment: Align::Left,
-        pos: None,
-        bb_pos: None,
-        show: true,
-        skinny: false,
-    };
+    let layout_iw = Layout::new(
+        if horizontal {
+            make_padding("iw", &opts.padding.iw, 0.5, 0.5, 0.5, 1.0)
+        } else {
+            make_padding("iw", &opts.padding.iw, 0.5, 1.0, 0.5, 0.5)
+        },
+        Align::Middle,
+    );
+    let layout_threads = Layout::new(make_padding("threads", &opts.padding.threads, 0.0, 0.0, 0.0, 0.0), Align::Left);
+    // threads are kept further apart when there are boxes drawn around them
+    let thread_gap = if opts.draw_thread_boxes { 2.0 } else { 1.0 };
+    let layout_thread = Layout::new(
+        if horizontal {
+            make_padding("thread", &opts.padding.thread, 0.0, thread_gap / 2.0, 0.0, 0.0)
+        } else {
+            make_padding("thread", &opts.padding.thread, 0.0, 0.0, 0.0, thread_gap)
+        },
+        Align::Left,
+    );
     // space around each instruction for layout space, border and opcode label
-    let layout_instr = Layout {
-        padding: make_padding("instr", 0.1, 0.45, 0.2, 0.2),
-        alignment: Align::Middle,
-        pos: None,
-        bb_pos: None,
-        show: true,
-        skinny: false,
-    };
+    let instr_gap = if opts.draw_instruction_boxes { 0.45 } else { 0.2 };
+    let layout_instr = Layout::new(
+        if horizontal {
+            make_padding("instr", &opts.padding.instr, 0.1, 0.2, 0.2, instr_gap)
+        } else {
+            make_padding("instr", &opts.padding.instr, 0.1, instr_gap, 0.2, 0.2)
+        },
+        Align::Middle,
+    );
     // by aligning events in the middle we make sure arrows up/down the same column are vertical
-    let layout_event = Layout {
-        padding: make_padding("event", 0.1, 0.1, 0.1, 0.8),
-        alignment: Align::Middle,
-        pos: None,
-        bb_pos: None,
-        show: true,
-        skinny: false,
-    };
+    let layout_event = Layout::new(make_padding("event", &opts.padding.event, 0.1, 0.1, 0.1, 0.8), Align::Middle);
 
-    let mut top_level_layout = GraphLayout { children: HashMap::new() };
+    let mut top_level_layout = GraphLayout::new();
     let iw_pgn = GVGridNode::Node(PositionedGraphNode {
         ev: GridNode {
             ev: None,
@@ -683,23 +351,30 @@ fn produce_node_layout<'ev>(
             node_style: "filled".to_string(),
             dimensions: (0.0, 0.0),
         },
+        measure: opts.text_measure.clone(),
     });
-    top_level_layout.children.insert((0, 0), GVGridChild { node: iw_pgn, layout: layout_iw });
+    // the threads are placed in the row (or column) after the initial
+    // state, which takes up no space when it is left out
+    if show_iw {
+        top_level_layout.insert(0, 0, iw_pgn, layout_iw);
+    }
 
-    let mut thread_layouts = GraphLayout { children: HashMap::new() };
+    let mut thread_layouts = GraphLayout::new();
 
-    let push_new = &mut |rc: (usize, usize), gn: GridNode<'ev, usize>, layout: &mut GraphLayout<'ev>| {
-        let style = event_style(&gn);
-        layout.children.insert(
-            rc,
-            GVGridChild { node: GVGridNode::Node(PositionedGraphNode { ev: gn, style }), layout: layout_event.clone() },
+    let push_new = &mut |(row, col): (usize, usize), gn: GridNode<'ev, usize>, layout: &mut GVGraphLayout<'ev>| {
+        let style = event_style(&gn, &opts.palette);
+        insert_event(
+            layout,
+            (row, col),
+            PositionedGraphNode { ev: gn, style, measure: opts.text_measure.clone() },
+            layout_event.clone(),
         );
     };
 
     for (tid, t) in grid.threads.into_iter().enumerate() {
-        let mut thread = GraphLayout { children: HashMap::new() };
+        let mut thread = GraphLayout::new();
         for (iid, i) in t.instr_instances.into_iter().enumerate() {
-            let mut instr = GraphLayout { children: HashMap::new() };
+            let mut instr = GraphLayout::new();
             match i {
                 SingleEventInstr(e) => {
                     push_new((0, 0), e, &mut instr);
@@ -710,7 +385,7 @@ fn produce_node_layout<'ev>(
                     }
                 }
                 MultiRowEventsInstr(m) => {
-                    let mut subinstr = GraphLayout { children: HashMap::new() };
+                    let mut subinstr = GraphLayout::new();
 
                     for (ridx, r) in m.into_iter().enumerate() {
                         for (cidx, gn) in r.into_iter().enumerate() {
@@ -719,23 +394,22 @@ fn produce_node_layout<'ev>(
                     }
                 }
             }
-            thread
-                .children
-                .insert((iid, 0), GVGridChild { node: GVGridNode::SubCluster(instr), layout: layout_instr.clone() });
+            let (row, col) = instr_addr(opts.orientation, iid);
+            thread.insert(row, col, GVGridNode::SubCluster(instr), layout_instr.clone());
         }
-        thread_layouts
-            .children
-            .insert((0, tid), GVGridChild { node: GVGridNode::SubCluster(thread), layout: layout_thread.clone() });
+        let (row, col) = thread_addr(opts.orientation, tid);
+        thread_layouts.insert(row, col, GVGridNode::SubCluster(thread), layout_thread.clone());
     }
 
-    let threads_node = GVGridNode::SubCluster(thread_layouts);
-    top_level_layout.children.insert((1, 0), GVGridChild { node: threads_node, layout: layout_threads });
+    let (row, col) = threads_addr(opts.orientation);
+    top_level_layout.insert(row, col, GVGridNode::SubCluster(thread_layouts), layout_threads);
 
     if opts.flatten {
         // explode out into a big flat grid,
         // then use that to align rows and columns and layout things
         let mut exploded = top_level_layout.clone();
-        let threads = exploded.children.get_mut(&(1, 0)).unwrap().unwrap_cluster_mut();
+        let (row, col) = threads_addr(opts.orientation);
+        let threads = exploded.get_mut(row, col).unwrap().unwrap_cluster_mut();
 
         // flatten each thread to keep `po` vertical etc
         for thread in threads.children.values_mut() {
@@ -751,21 +425,218 @@ fn produce_node_layout<'ev>(
             if let Some(tll_n) = top_level_layout.find_node_mut(&pge.name()) {
                 tll_n.layout.pos = n.layout.pos;
                 tll_n.layout.bb_pos = n.layout.bb_pos;
-
-                let pge2 = tll_n.unwrap_node_mut();
-                pge2.style.dimensions = pge.style.dimensions;
             }
         }
     } else {
         top_level_layout.accumulate_positions(0, 0);
     };
 
+    if opts.align_addresses {
+        align_address_columns(&mut top_level_layout, opts.orientation)
+    }
+
+    if let Some(layout_file) = &opts.layout_file {
+        place_from_layout_file(&mut top_level_layout, layout_file)
+    }
+
+    // graphviz is told the size (in inches) of each node shape
+    let scale = Scale::new(opts.scale);
+    for n in top_level_layout.iter_nodes_mut(false, false) {
+        let pgn = n.unwrap_node_mut();
+        pgn.style.dimensions =
+            (scale.inches_from_points(pgn.compute_width()), scale.inches_from_points(pgn.compute_height()));
+    }
+
     top_level_layout
 }
 
+/// Insert an event into a grid at the given position, or if that is
+/// already taken at the next free column along, so that an event is
+/// never overwritten (and lost from the graph) by another placed at
+/// the same position
+fn insert_event<'ev>(
+    layout: &mut GVGraphLayout<'ev>,
+    (row, col): (usize, usize),
+    pgn: PositionedGraphNode<'ev>,
+    event_layout: Layout,
+) -> (usize, usize) {
+    let mut free_col = col;
+    while layout.get(row, free_col).is_some() {
+        free_col += 1
+    }
+    if free_col != col {
+        log!(
+            log::GRAPH,
+            format!("warning: {} placed at ({}, {}) as ({}, {}) is already taken", pgn.name(), row, free_col, row, col)
+        );
+    }
+    layout.insert(row, free_col, GVGridNode::Node(pgn), event_layout);
+    (row, free_col)
+}
+
+/// Move the events found in a layout file to the positions given
+/// there, leaving the rest where the layout put them
+fn place_from_layout_file(layout: &mut GVGraphLayout<'_>, layout_file: &LayoutFile) {
+    for n in layout.iter_nodes_mut(false, false) {
+        let placement = match layout_file.events.get(&n.unwrap_node().name()) {
+            Some(placement) => placement,
+            None => continue,
+        };
+        if let Some((x, y)) = n.layout.pos {
+            let (dx, dy) = (placement.pos.0 - x, placement.pos.1 - y);
+            n.layout.pos = Some(placement.pos);
+            n.layout.bb_pos = n.layout.bb_pos.map(|(x, y)| (x + dx, y + dy));
+        }
+    }
+}
+
+/// The placement of each event drawn by [draw_graph_gv], which can be
+/// saved and given back as [GraphOpts::layout_file] to draw the graph
+/// the same way again
+pub fn dump_graph_layout(graph: &Graph) -> LayoutFile {
+    let (node_layout, _) = drawn_layout(graph);
+    LayoutFile {
+        events: node_layout.positions().map(|(name, pos, size)| (name, EventPlacement { pos, size })).collect(),
+    }
+}
+
+/// the smallest gap (in points) left between events in the same row
+/// when they are moved to line up addresses
+const MIN_EVENT_GAP: i64 = (POINTS_PER_INCH / 4.0) as i64;
+
+/// The physical address accessed by a read or write event
+fn event_address<'ev>(child: &GVGridChild<'ev>) -> Option<&'ev str> {
+    match child.node {
+        GVGridNode::Node(PositionedGraphNode { ev: GridNode { ev: Some(ev), .. }, .. }) => match ev.event_kind {
+            GraphEventKind::ReadMem | GraphEventKind::WriteMem(_) => ev.value.as_ref()?.address.as_deref(),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn node_rect(child: &GVGridChild<'_>) -> Option<Rect> {
+    let pgn = child.unwrap_node();
+    Some(Rect::around(child.layout.pos?, (pgn.compute_width(), pgn.compute_height())))
+}
+
+fn move_node(child: &mut GVGridChild<'_>, dx: i64) {
+    child.layout.pos = child.layout.pos.map(|(x, y)| (x + dx, y));
+    child.layout.bb_pos = child.layout.bb_pos.map(|(x, y)| (x + dx, y));
+}
+
+/// Push nodes to the right until none of them overlaps (or is closer
+/// than [MIN_EVENT_GAP] to) a node to its left in the same row
+fn separate_rows(nodes: &mut [&mut GVGridChild<'_>]) {
+    // nodes moved into the same column are kept in program order
+    nodes.sort_by_key(|node| (node.layout.pos.map(|(x, _)| x), program_order(node)));
+    for i in 1..nodes.len() {
+        let (placed, rest) = nodes.split_at_mut(i);
+        let node = &mut rest[0];
+        let placed: Vec<Rect> = placed.iter().filter_map(|other| node_rect(other)).collect();
+        while let Some(rect) = node_rect(node) {
+            let push = placed
+                .iter()
+                .filter(|other| {
+                    other.top < rect.bottom
+                        && rect.top < other.bottom
+                        && other.left < rect.right + MIN_EVENT_GAP
+                        && rect.left < other.right + MIN_EVENT_GAP
+                })
+                .map(|other| other.right + MIN_EVENT_GAP - rect.left)
+                .max();
+            match push {
+                Some(dx) if dx > 0 => move_node(node, dx),
+                _ => break,
+            }
+        }
+    }
+}
+
+/// Move the read and write events within each thread so that those
+/// to the same physical address line up in a column. Each address is
+/// given a column at the same distance from the left of every thread,
+/// so when threads are stacked as rows the events share an x
+/// coordinate. Events without an address are only moved to make room.
+fn align_address_columns<'ev>(layout: &mut GVGraphLayout<'ev>, orientation: GraphOrientation) {
+    let threads = match layout.children.get_mut(&threads_addr(orientation)) {
+        Some(GVGridChild { node: GVGridNode::SubCluster(threads), .. }) => threads,
+        _ => return,
+    };
+    let mut threads: Vec<(&(usize, usize), &mut GVGridChild<'ev>)> = threads.children.iter_mut().collect();
+    threads.sort_by_key(|(rc, _)| **rc);
+    let mut threads: Vec<&mut GVGraphLayout<'ev>> = threads
+        .into_iter()
+        .filter_map(|(_, thread)| match &mut thread.node {
+            GVGridNode::SubCluster(cluster) => Some(cluster),
+            _ => None,
+        })
+        .collect();
+
+    let lefts: Vec<Option<i64>> = threads.iter().map(|thread| thread.bounding_box(true).map(|(tl, _)| tl.0)).collect();
+    let shared_left = lefts.iter().flatten().min().copied();
+    let origin = |tid: usize| if orientation == GraphOrientation::Horizontal { shared_left } else { lefts[tid] };
+
+    // the furthest right any event to each address is from the left of its thread, and the widest such event
+    let mut columns: HashMap<&'ev str, (i64, i64)> = HashMap::new();
+    for (tid, thread) in threads.iter().enumerate() {
+        for node in thread.iter_nodes(true, true) {
+            if let (Some(addr), Some((x, _)), Some(origin)) = (event_address(node), node.layout.pos, origin(tid)) {
+                let width = node.unwrap_node().compute_width() as i64;
+                let column = columns.entry(addr).or_insert((x - origin, width));
+                *column = (column.0.max(x - origin), column.1.max(width))
+            }
+        }
+    }
+
+    // columns are kept in the order the addresses first appear, and
+    // spaced out so that events in neighbouring columns don't overlap
+    let mut columns: Vec<(&'ev str, (i64, i64))> = columns.into_iter().collect();
+    columns.sort_by_key(|(addr, (offset, _))| (*offset, *addr));
+    let mut offsets: HashMap<&'ev str, i64> = HashMap::new();
+    let mut previous: Option<(i64, i64)> = None;
+    for (addr, (offset, width)) in columns {
+        let offset = match previous {
+            Some((prev_offset, prev_width)) => offset.max(prev_offset + (prev_width + width) / 2 + MIN_EVENT_GAP),
+            None => offset,
+        };
+        offsets.insert(addr, offset);
+        previous = Some((offset, width))
+    }
+
+    let mut growth = Vec::new();
+    for (tid, thread) in threads.iter_mut().enumerate() {
+        let (origin, right) = match (origin(tid), thread.bounding_box(true)) {
+            (Some(origin), Some((_, br))) => (origin, br.0),
+            _ => {
+                growth.push(0);
+                continue;
+            }
+        };
+        let mut nodes = thread.iter_nodes_mut(true, true);
+        for node in nodes.iter_mut() {
+            if let (Some(addr), Some((x, _))) = (event_address(node), node.layout.pos) {
+                move_node(node, origin + offsets[addr] - x)
+            }
+        }
+        separate_rows(&mut nodes);
+        growth.push(thread.bounding_box(true).map_or(0, |(_, br)| br.0 - right))
+    }
+
+    // events only ever move right, so threads side by side are moved
+    // over by however much wider the threads to their left became
+    if orientation == GraphOrientation::Vertical {
+        let mut shift = 0;
+        for (thread, growth) in threads.iter_mut().zip(growth) {
+            thread.shift((shift, 0));
+            shift += growth
+        }
+    }
+}
+
 #[allow(clippy::many_single_char_names)]
 fn draw_box(
-    _graph: &Graph,
+    graph: &Graph,
     f: &mut dyn io::Write,
     ident: &str,
     label: &str,
@@ -774,94 +645,268 @@ fn draw_box(
     style: &str,
 ) -> io::Result<()> {
     if let GVGridNode::SubCluster(cluster) = &node.node {
-        let mut tl: (i64, i64) = (i64::MAX, i64::MAX);
-        let mut br: (i64, i64) = (0, 0);
-        // find top-left
-        for n in cluster.iter_nodes(false, true) {
-            if let GVGridNode::Node(pgn) = &n.node {
-                let (nw, nh) = (pgn.compute_width() as i64, pgn.compute_height() as i64);
-
-                // use the pos of the bounding box
-                // not the centre of the node
-                if let Some((x, y)) = n.layout.bb_pos {
-                    let (x, y) = (x, y);
-
-                    if br.0 < x + nw {
-                        br.0 = x + nw;
-                    }
-
-                    if br.1 < y + nh {
-                        br.1 = y + nh;
-                    }
-
-                    if x < tl.0 {
-                        tl.0 = x;
-                    }
-
-                    if y < tl.1 {
-                        tl.1 = y;
-                    }
-                };
-            };
-        }
+        let (tl, br) = cluster.bounding_box(false).unwrap_or(((i64::MAX, i64::MAX), (0, 0)));
 
         let (x, y) = tl;
         let (w, h) = (br.0 - tl.0, br.1 - tl.1);
 
         // border 0.5 inch around events
         // enough for whitespace and a label
-        let wiggle = (SCALE / 2.0) as i64;
+        let wiggle = (POINTS_PER_INCH / 2.0) as i64;
 
         let (llx, lly) = (x - wiggle, y + h + wiggle);
         let (urx, ury) = (x + w + wiggle, y - wiggle);
 
+        let scale = Scale::new(graph.opts.scale);
         writeln!(f, "subgraph cluster{} {{", ident)?;
-        writeln!(f, "    label = \"{}\";", label)?;
-        writeln!(f, "    graph [bb=\"{},{},{},{}\"{}];", llx, -lly, urx, -ury, graphstyle)?;
+        writeln!(f, "    label = {};", dot::quote(label))?;
+        writeln!(
+            f,
+            "    graph [bb=\"{},{},{},{}\"{}];",
+            scale.points(llx),
+            -scale.points(lly),
+            scale.points(urx),
+            -scale.points(ury),
+            graphstyle
+        )?;
         writeln!(f, "    {}", style)
     } else {
         panic!("draw_box should be passed a GraphLayout")
     }
 }
 
-// To build a digraph for each Graph we produce some
-// neato-compatible (with -n 1) graphviz with a fixed grid-like layout.
-//
-// We layout something as follows:
-//
-//         col0    col1    col2    col3    col4    col5    col6    col7
-//
-//                            [Thread #0]
-//        +------------------------------------------------+
-//        |                STR X0,[X1]                     |
-// row0   |          [T]     [T]     [T]     [T]           |
-// row1   |  [T]     [T]     [T]     [T]     [T]           |
-// row2   |  [T]     [T]     [T]     [T]     [T]           |
-// row3   |  [T]     [T]     [T]     [T]     [T]           |
-// row4   |  [T]     [T]     [T]     [T]     [T]     [W]   |
-//        |                                                |
-//        +------------------------------------------------+
-//
-//
-// Nodes are written like [label]
-//
-pub fn draw_graph_gv(f: &mut dyn io::Write, graph: &Graph, _opts: &GraphOpts) -> io::Result<()> {
-    writeln!(f, "digraph Exec {{")?;
-    writeln!(f, "    splines=true;")?;
-    writeln!(f, "    node [fontsize=44, fontname=aerial];")?;
-    writeln!(f, "    edge [fontsize=44, fontname=aerial, arrowsize=2];")?;
-    writeln!(f, "    graph [fontsize=40, fontname=aerial];")?;
-    log!(log::VERBOSE, "producing dot");
+/// The graph attributes for the box around a thread, which is shaded
+/// if the options give a list of thread colors
+fn thread_graphstyle(opts: &GraphOpts, tid: usize) -> String {
+    match &opts.thread_colors {
+        Some(colors) if !colors.is_empty() => {
+            format!("labeljust=l, bgcolor={}", dot::id(&colors[tid % colors.len()]))
+        }
+        _ => "labeljust=l".to_string(),
+    }
+}
+
+/// The relations whose edges are between events at the same location,
+/// which are labelled with that location when `edge_addresses` is set
+const SAME_LOCATION_RELATIONS: &[&str] =
+    &["rf", "rfe", "rfi", "co", "coe", "coi", "fr", "fre", "fri", "trf", "tco", "tfr"];
+
+/// The label for an edge of a relation, showing the relation as
+/// `name`, which for relations between events at the same location may
+/// include the name of that location
+fn edge_label(graph: &Graph, rel: &str, name: &str, from: &str, to: &str) -> String {
+    if graph.opts.edge_addresses && SAME_LOCATION_RELATIONS.contains(&rel) {
+        // the initial write is not an event, so look at the other end of its edges
+        let location = [from, to]
+            .iter()
+            .filter_map(|name| graph.events.get(*name))
+            .find_map(|ev| ev.address_name(&graph.opts, &graph.names));
+        if let Some(location) = location {
+            return format!(" {}[{}] ", name, location);
+        }
+    }
+    format!(" {} ", name)
+}
+
+/// the length (in points) of the arrowheads drawn with `arrowsize=2`
+const ARROW_LENGTH: f64 = 20.0;
+
+/// The `pos` and `lp` attributes (each followed by a comma) for an edge
+/// drawn along a route from [routing::route], as a spline made of
+/// straight segments with the label halfway along the middle one. The
+/// spline of a directed edge stops short of its end, leaving room for
+/// the arrowhead. Graphviz only uses these when rendering with
+/// `neato -n2`, and routes the edges itself with `-n1`.
+fn fmt_route(route: &[Point], directed: bool, scale: Scale) -> String {
+    let mut points: Vec<Point> = route.iter().map(|(x, y)| (scale.points(*x), -scale.points(*y))).collect();
+    let mut end = String::new();
+    if let [.., (x1, y1), (x2, y2)] = points[..] {
+        let (dx, dy) = ((x2 - x1) as f64, (y2 - y1) as f64);
+        let len = dx.hypot(dy);
+        if directed && len > ARROW_LENGTH {
+            end = format!("e,{},{} ", x2, y2);
+            let last = points.len() - 1;
+            points[last] =
+                (x2 - (dx / len * ARROW_LENGTH).round() as i64, y2 - (dy / len * ARROW_LENGTH).round() as i64)
+        }
+    }
+
+    let mut spline = vec![points[0]];
+    for segment in points.windows(2) {
+        spline.extend([segment[0], segment[1], segment[1]])
+    }
+    let spline: Vec<String> = spline.iter().map(|(x, y)| format!("{},{}", x, y)).collect();
+    let middle = points.len() / 2;
+    let (lx, ly) = ((points[middle - 1].0 + points[middle].0) / 2, (points[middle - 1].1 + points[middle].1) / 2);
+    format!("pos=\"{}{}\", lp=\"{},{}\", ", end, spline.join(" "), lx, ly)
+}
+
+/// The color for a relation's edges, along with any extra graphviz
+/// attributes (each followed by a comma) from a user-specified style
+fn relation_edge_style<'a>(opts: &'a GraphOpts, rel: &str) -> (&'a str, String) {
+    match opts.relation_styles.get(rel) {
+        Some(style) => {
+            let mut attrs = String::new();
+            if style.line != LineStyle::Solid {
+                attrs.push_str(&format!("style={},", style.line))
+            }
+            if let Some(arrowhead) = &style.arrowhead {
+                attrs.push_str(&format!("arrowhead={},", dot::id(arrowhead)))
+            }
+            (&style.color, attrs)
+        }
+        None => (relation_color(rel), "".to_string()),
+    }
+}
+
+/// The position of the event drawn by a node in program order, used
+/// to write the nodes of each instruction in a stable order
+fn program_order(child: &GVGridChild<'_>) -> Option<(ThreadId, usize, usize)> {
+    match child.node {
+        GVGridNode::Node(PositionedGraphNode { ev: GridNode { ev: Some(ev), .. }, .. }) => {
+            Some((ev.thread_id, ev.po, ev.iio))
+        }
+        _ => None,
+    }
+}
 
+/// Whether an edge is drawn without an arrowhead, which by default
+/// is when the relation also has the edge going the other way
+fn is_undirected(graph: &Graph, rel: &GraphRelation, from: &str, to: &str) -> bool {
+    match graph.opts.relation_direction(&rel.name) {
+        EdgeDirection::Auto => rel.edges.contains(&(to.to_string(), from.to_string())),
+        EdgeDirection::Undirected => true,
+        EdgeDirection::Directed => false,
+    }
+}
+
+/// The edges of each relation which will be drawn between the
+/// displayed events
+fn relation_edges_to_draw(
+    graph: &Graph,
+    displayed_event_names: &HashSet<String>,
+) -> HashMap<String, HashSet<(String, String)>> {
+    // we don't draw the full relations
+    // instead, some events are hidden
+    // then what's left we might transitively reduce/close etc
+    // and finally, we might hide some edges if they're superimposed on another.
+    let mut draw_relations: HashMap<String, HashSet<(String, String)>> = HashMap::new();
+
+    for rel in &graph.relations {
+        if !rel.edges.is_empty() {
+            let edges = &rel.all_edges;
+
+            // hide edges between hidden nodes
+            let edges: HashSet<(String, String)> = edges
+                .iter()
+                .filter(|(from, to)| displayed_event_names.contains(from) && displayed_event_names.contains(to))
+                .map(|(from, to)| (from.clone(), to.clone()))
+                .collect();
+
+            // now perform any transitive simplifications
+            let edges = simplify_edges(&rel.ty, &edges);
+            draw_relations.insert(rel.name.clone(), edges);
+        }
+    }
+
+    // now hide edges that are superceded by others
+    // need a copy of the original because the priority might be transitive...
+    let orig = draw_relations.clone();
+    for rel in &graph.relations {
+        if !rel.edges.is_empty() {
+            let pairs = draw_relations.get_mut(&rel.name).unwrap();
+            for (e1, e2) in pairs.clone() {
+                let k = (e1, e2);
+                for preferred_rel_name in rel.ty.preferred.iter() {
+                    let s = String::from(*preferred_rel_name);
+                    match orig.get(&s) {
+                        // if any "preferred" relation also contains the edge (e1,e2) then don't show this one.
+                        Some(preferred_rel) if preferred_rel.contains(&k) => {
+                            pairs.remove(&k);
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+    }
+
+    draw_relations
+}
+
+/// We do not show IW -(rf)-> R when R's address is not written by the test
+fn is_hidden_initial_rf(
+    graph: &Graph,
+    rel: &str,
+    from: &str,
+    to: &str,
+    mutated_pas_event_names: &HashSet<&String>,
+) -> bool {
+    match graph.events.get(to) {
+        Some(to_event) => {
+            !graph.opts.debug
+                && rel.ends_with("rf")
+                && from == "IW"
+                && !mutated_pas_event_names.contains(&to_event.name)
+                && !event_in_shows(&graph.opts.force_show_events, to_event)
+        }
+        None => false,
+    }
+}
+
+/// The names of the events displayed in a layout, including the
+/// initial state
+fn displayed_event_names(layout: &GVGraphLayout<'_>) -> HashSet<String> {
+    let mut names: HashSet<String> = layout
+        .iter_nodes(true, false)
+        .into_iter()
+        .filter_map(|c| match c.node {
+            GVGridNode::Node(PositionedGraphNode { ev: GridNode { ev: Some(ev), .. }, .. }) => Some(ev.name.clone()),
+            _ => None,
+        })
+        .collect();
+    names.insert("IW".to_string());
+    names
+}
+
+/// The number of events of each instruction, by thread id and program
+/// order, which are not displayed. Instructions with no displayed
+/// events have no box to show their count in.
+fn hidden_event_counts(graph: &Graph, displayed_event_names: &HashSet<String>) -> HashMap<(ThreadId, usize), usize> {
+    let mut counts = HashMap::new();
+    for ev in graph.events.values() {
+        if !displayed_event_names.contains(&ev.name) {
+            *counts.entry((ev.thread_id, ev.po)).or_insert(0) += 1
+        }
+    }
+    counts
+}
+
+/// Whether any relation edge to or from the initial state will be drawn
+fn draws_initial_state_edge(
+    graph: &Graph,
+    layout: &GVGraphLayout<'_>,
+    mutated_pas_event_names: &HashSet<&String>,
+) -> bool {
+    let draw_relations = relation_edges_to_draw(graph, &displayed_event_names(layout));
+    draw_relations.iter().any(|(rel, edges)| {
+        edges.iter().any(|(from, to)| {
+            (from == "IW" || to == "IW") && !is_hidden_initial_rf(graph, rel, from, to, mutated_pas_event_names)
+        })
+    })
+}
+
+/// Lay out a graph as it is drawn, leaving out the initial state if
+/// none of its edges would be. Also returns the names of the events
+/// which access a location written to in the test, which decide
+/// whether reads from the initial state are drawn (see
+/// [is_hidden_initial_rf]).
+fn drawn_layout(graph: &Graph) -> (GVGraphLayout<'_>, HashSet<&String>) {
     // keep track of all the PAs that were touched (written to)
     // in the execution, so we can decide whether to show an event later
     // or whether to use an event in layouting.
     let mut mutated_pas = HashSet::new();
-
-    let mut thread_ids = HashSet::new();
     for ev in graph.events.values() {
-        thread_ids.insert(ev.thread_id);
-
         // collect PAs from various write events.
         if let GraphEventKind::WriteMem(_) = &ev.event_kind {
             if let Some(v) = &ev.value {
@@ -883,17 +928,210 @@ pub fn draw_graph_gv(f: &mut dyn io::Write, graph: &Graph, _opts: &GraphOpts) ->
         .collect();
 
     log!(log::GRAPH, "producing GraphLayout ...");
-    let node_layout = produce_node_layout(graph, &graph.litmus_opts, &graph.opts, mutated_pas);
+    let mut node_layout = produce_node_layout(graph, &graph.litmus_opts, &graph.opts, mutated_pas.clone(), true);
+    // lay the graph out again without the initial state if it would
+    // otherwise be left floating above the threads
+    if graph.opts.hide_unused_initial_state && !draws_initial_state_edge(graph, &node_layout, &mutated_pas_event_names)
+    {
+        node_layout = produce_node_layout(graph, &graph.litmus_opts, &graph.opts, mutated_pas, false)
+    }
+
+    (node_layout, mutated_pas_event_names)
+}
+
+/// Synthesize the intra-instruction order between the displayed
+/// events, linking each event to the next displayed event of the same
+/// instruction. Hidden events are skipped over so the chain for each
+/// instruction stays connected.
+fn displayed_iio_edges(graph: &Graph, displayed_event_names: &HashSet<String>) -> Vec<(String, String)> {
+    let mut instrs: HashMap<(ThreadId, usize), Vec<&GraphEvent>> = HashMap::new();
+    for ev in graph.events.values() {
+        if displayed_event_names.contains(&ev.name) {
+            instrs.entry((ev.thread_id, ev.po)).or_default().push(ev)
+        }
+    }
+
+    let mut edges = Vec::new();
+    for events in instrs.values_mut() {
+        events.sort_by_key(|ev| ev.iio);
+        for pair in events.windows(2) {
+            edges.push((pair[0].name.clone(), pair[1].name.clone()))
+        }
+    }
+    edges.sort();
+    edges
+}
+
+/// Draw a legend showing the color of each of the given relations,
+/// placed to the right of the bounding box of the layout so it cannot
+/// overlap any of the thread clusters.
+fn draw_legend(
+    f: &mut dyn io::Write,
+    opts: &GraphOpts,
+    layout: &GVGraphLayout<'_>,
+    relations: &[&str],
+) -> io::Result<()> {
+    if relations.is_empty() {
+        return Ok(());
+    }
+
+    let ((_, top), (right, _)) = layout.bounding_box(false).unwrap_or(((0, 0), (0, 0)));
+
+    // leave room for the border drawn around each thread by draw_box
+    let x = right + POINTS_PER_INCH as i64 * 2;
+    let edge_len = POINTS_PER_INCH as i64 * 3;
+    let row_height = (POINTS_PER_INCH * 1.5) as i64;
+    let wiggle = (POINTS_PER_INCH / 2.0) as i64;
+
+    let (llx, lly) = (x - wiggle, top + row_height * relations.len() as i64 + wiggle);
+    let (urx, ury) = (x + edge_len + wiggle, top - row_height);
+
+    let scale = Scale::new(opts.scale);
+    writeln!(f, "subgraph clusterlegend {{")?;
+    writeln!(f, "    label = \"Legend\";")?;
+    writeln!(
+        f,
+        "    graph [bb=\"{},{},{},{}\", labeljust=l];",
+        scale.points(llx),
+        -scale.points(lly),
+        scale.points(urx),
+        -scale.points(ury)
+    )?;
+    writeln!(f, "    style=solid;")?;
+    let point_width = 0.1 * scale.factor;
+    for (i, rel) in relations.iter().enumerate() {
+        let y = top + row_height * i as i64;
+        let (color, attrs) = relation_edge_style(opts, rel);
+        writeln!(
+            f,
+            "    legend{}_from [shape=point, width={}, pos=\"{},{}!\"];",
+            i,
+            point_width,
+            scale.points(x),
+            -scale.points(y)
+        )?;
+        writeln!(
+            f,
+            "    legend{}_to [shape=point, width={}, pos=\"{},{}!\"];",
+            i,
+            point_width,
+            scale.points(x + edge_len),
+            -scale.points(y)
+        )?;
+        writeln!(
+            f,
+            "    legend{}_from -> legend{}_to [{}color={}, label={}, fontcolor={}];",
+            i,
+            i,
+            attrs,
+            dot::id(color),
+            dot::quote(&format!(" {} ", rel)),
+            dot::id(color)
+        )?;
+    }
+    writeln!(f, "}}")
+}
+
+// To build a digraph for each Graph we produce some
+// neato-compatible (with -n 1) graphviz with a fixed grid-like layout.
+//
+// We layout something as follows:
+//
+//         col0    col1    col2    col3    col4    col5    col6    col7
+//
+//                            [Thread #0]
+//        +------------------------------------------------+
+//        |                STR X0,[X1]                     |
+// row0   |          [T]     [T]     [T]     [T]           |
+// row1   |  [T]     [T]     [T]     [T]     [T]           |
+// row2   |  [T]     [T]     [T]     [T]     [T]           |
+// row3   |  [T]     [T]     [T]     [T]     [T]           |
+// row4   |  [T]     [T]     [T]     [T]     [T]     [W]   |
+//        |                                                |
+//        +------------------------------------------------+
+//
+//
+// Nodes are written like [label]
+//
+pub fn draw_graph_gv(f: &mut dyn io::Write, graph: &Graph, _opts: &GraphOpts) -> io::Result<()> {
+    draw_graph_gv_at(f, graph, "Exec", None, None).map(|_| ())
+}
+
+/// space (in points) left between executions drawn by [draw_graphs_gv]
+const EXECUTION_GAP: i64 = POINTS_PER_INCH as i64 * 2;
+
+/// Draw several executions into a single dot file, as one `digraph`
+/// section per execution. Each is titled with its index and whether it
+/// satisfied the final assertion, and is placed to the right of the
+/// previous one according to its bounding box, so that combining the
+/// sections with `gvpack -u` and rendering them with `neato -n` draws
+/// the executions side by side without overlapping.
+pub fn draw_graphs_gv<'g>(
+    f: &mut dyn io::Write,
+    graphs: impl IntoIterator<Item = (usize, bool, &'g Graph)>,
+) -> io::Result<()> {
+    let mut x = 0;
+    for (i, satisfied, graph) in graphs {
+        let title =
+            format!("Execution #{} ({} final assertion)", i, if satisfied { "satisfies" } else { "does not satisfy" });
+        let right = draw_graph_gv_at(f, graph, &format!("Exec{}", i), Some(&title), Some(x))?;
+        x = right + EXECUTION_GAP
+    }
+    Ok(())
+}
+
+/// Draw a graph as a `digraph` with the given name and an optional
+/// title above it. If `x_offset` is given, the whole drawing is moved
+/// so its left edge is at that position. Returns the position of the
+/// right edge of the drawing.
+fn draw_graph_gv_at(
+    f: &mut dyn io::Write,
+    graph: &Graph,
+    name: &str,
+    title: Option<&str>,
+    x_offset: Option<i64>,
+) -> io::Result<i64> {
+    let scale = Scale::new(graph.opts.scale);
+    writeln!(f, "digraph {} {{", dot::id(name))?;
+    write_graph_attrs(f, scale)?;
+    log!(log::VERBOSE, "producing dot");
+
+    let (mut node_layout, mutated_pas_event_names) = drawn_layout(graph);
+
+    // Everything below is written in a fixed order (threads by id,
+    // clusters by position, events by program order, and edges by
+    // name), rather than the order of the underlying hash maps, so the
+    // same execution always produces the same file
+    let thread_ids: BTreeSet<ThreadId> = graph.events.values().map(|ev| ev.thread_id).collect();
+
+    // the thread boxes drawn by draw_box extend past the nodes
+    let wiggle = (POINTS_PER_INCH / 2.0) as i64;
+    if let (Some(x), Some(((left, _), _))) = (x_offset, node_layout.bounding_box(false)) {
+        node_layout.shift((x - (left - wiggle), 0))
+    }
     let graph_event_nodes = node_layout.iter_nodes(true, false);
     log!(log::GRAPH, "produced node layout");
 
     if let Some(iw) = node_layout.children.get(&(0, 0)) {
-        writeln!(f, "{};", iw.fmt_as_node())?;
+        writeln!(f, "{};", iw.fmt_as_node(scale))?;
     }
 
-    if let Some(GVGridChild { node: GVGridNode::SubCluster(thread_clusters), .. }) = node_layout.children.get(&(1, 0)) {
-        let mut displayed_event_names: HashSet<String> = HashSet::new();
-        displayed_event_names.insert("IW".to_string());
+    let ((left, top), (right, _)) = node_layout.bounding_box(false).unwrap_or(((0, 0), (0, 0)));
+    if let Some(title) = title {
+        writeln!(
+            f,
+            "    title [shape=plaintext, fontsize={}, label={}, pos=\"{},{}!\"];",
+            scale.points(48),
+            dot::quote(title),
+            scale.points((left + right) / 2),
+            -scale.points(top - wiggle - POINTS_PER_INCH as i64)
+        )?;
+    }
+
+    if let Some(GVGridChild { node: GVGridNode::SubCluster(thread_clusters), .. }) =
+        node_layout.children.get(&threads_addr(graph.opts.orientation))
+    {
+        let displayed_event_names = displayed_event_names(&node_layout);
 
         let displayed_graph_events: Vec<&GraphEvent> = graph_event_nodes
             .iter()
@@ -903,6 +1141,12 @@ pub fn draw_graph_gv(f: &mut dyn io::Write, graph: &Graph, _opts: &GraphOpts) ->
             })
             .collect();
 
+        let hidden_counts = if graph.opts.show_hidden_counts {
+            hidden_event_counts(graph, &displayed_event_names)
+        } else {
+            HashMap::new()
+        };
+
         for tid in thread_ids {
             log!(log::GRAPH, &format!("drawing Thread#{}", tid));
             let mut events: Vec<&GraphEvent> = graph.events.values().filter(|ev| ev.thread_id == tid).collect();
@@ -912,53 +1156,72 @@ pub fn draw_graph_gv(f: &mut dyn io::Write, graph: &Graph, _opts: &GraphOpts) ->
                 displayed_graph_events.clone().into_iter().filter(|ge| ge.thread_id == tid).collect();
 
             // draw the events and boxes
-            if let Some(thread_child) = thread_clusters.children.get(&(0, tid)) {
-                if !displayed_thread_events.is_empty() {
-                    let thread_box_label = format!("Thread {}", tid);
+            if let Some(thread_child) = thread_clusters.children.get(&thread_addr(graph.opts.orientation, tid)) {
+                let thread_box_label = format!("Thread {}", tid);
+                let draw_thread_box = graph.opts.draw_thread_boxes && !displayed_thread_events.is_empty();
+                if draw_thread_box {
                     draw_box(
                         graph,
                         f,
                         &format!("{}", tid),
                         &thread_box_label,
                         thread_child,
-                        "labeljust=l",
+                        &thread_graphstyle(&graph.opts, tid),
                         "style=dashed;",
                     )?;
                 }
 
+                // without a thread box, its label moves onto the box around its first displayed instruction
+                let mut thread_label = if graph.opts.draw_thread_boxes { None } else { Some(thread_box_label) };
+
                 if let GVGridChild { node: GVGridNode::SubCluster(thread), .. } = thread_child {
-                    for ((po_row, _), instr) in thread.children.iter() {
+                    let mut instrs: Vec<_> = thread.children.iter().collect();
+                    instrs.sort_by_key(|(rc, _)| **rc);
+                    for ((po_row, po_col), instr) in instrs {
+                        // one of the row or column is always zero, depending on the orientation
+                        let iid = po_row + po_col;
                         if let GVGridNode::SubCluster(instr_cluster) = &instr.node {
                             if let Some(po) = instr_cluster.po() {
                                 let displayed_instr_events: Vec<&GraphEvent> =
                                     displayed_thread_events.clone().into_iter().filter(|ge| ge.po == po).collect();
 
-                                if displayed_instr_events.len() > 1 {
+                                // a box is also drawn around a single event if it has hidden
+                                // siblings, so there is somewhere to say how many
+                                let hidden = hidden_counts.get(&(tid, po)).copied().unwrap_or(0);
+                                let draw_instr_box = graph.opts.draw_instruction_boxes
+                                    && !displayed_instr_events.is_empty()
+                                    && (displayed_instr_events.len() > 1 || hidden > 0 || thread_label.is_some());
+
+                                if draw_instr_box {
+                                    let hidden_label = format!("(+{} hidden)", hidden);
+                                    let label = match thread_label.take() {
+                                        Some(thread_label) if hidden > 0 => {
+                                            format!("{} {}", thread_label, hidden_label)
+                                        }
+                                        Some(thread_label) => thread_label,
+                                        None if hidden > 0 => hidden_label,
+                                        None => String::new(),
+                                    };
                                     draw_box(
                                         graph,
                                         f,
-                                        &format!("{}_{}", tid, po_row),
-                                        "",
+                                        &format!("{}_{}", tid, iid),
+                                        &label,
                                         instr,
                                         "labeljust=l",
                                         "style=dashed;",
                                     )?;
                                 }
 
-                                for ev in instr_cluster.children.values() {
+                                let mut instr_events: Vec<_> = instr_cluster.children.iter().collect();
+                                instr_events.sort_by_key(|(rc, child)| (program_order(child), **rc));
+                                for (_, ev) in instr_events {
                                     if ev.layout.show {
-                                        if let GVGridNode::Node(PositionedGraphNode {
-                                            ev: GridNode { ev: Some(ev), .. },
-                                            ..
-                                        }) = ev.node
-                                        {
-                                            displayed_event_names.insert(ev.name.clone());
-                                        }
-                                        writeln!(f, "    {};", ev.fmt_as_node())?;
+                                        writeln!(f, "    {};", ev.fmt_as_node(scale))?;
                                     }
                                 }
 
-                                if displayed_instr_events.len() > 1 {
+                                if draw_instr_box {
                                     writeln!(f, "}}")?;
                                 }
                             }
@@ -966,56 +1229,18 @@ pub fn draw_graph_gv(f: &mut dyn io::Write, graph: &Graph, _opts: &GraphOpts) ->
                     }
                 }
 
-                if !displayed_thread_events.is_empty() {
+                if draw_thread_box {
                     writeln!(f, "}}")?;
                 }
             }
         }
 
-        // we don't draw the full relations
-        // instead, some events are hidden
-        // then what's left we might transitively reduce/close etc
-        // and finally, we might hide some edges if they're superimposed on another.
-        let mut draw_relations: HashMap<String, HashSet<(String, String)>> = HashMap::new();
+        let draw_relations = relation_edges_to_draw(graph, &displayed_event_names);
 
-        for rel in &graph.relations {
-            if !rel.edges.is_empty() {
-                let edges = &rel.all_edges;
-
-                // hide edges between hidden nodes
-                let edges: HashSet<(String, String)> = edges
-                    .iter()
-                    .filter(|(from, to)| displayed_event_names.contains(from) && displayed_event_names.contains(to))
-                    .map(|(from, to)| (from.clone(), to.clone()))
-                    .collect();
-
-                // now perform any transitive simplifications
-                let edges = simplify_edges(&rel.ty, &edges);
-                draw_relations.insert(rel.name.clone(), edges);
-            }
-        }
-
-        // now hide edges that are superceded by others
-        // need a copy of the original because the priority might be transitive...
-        let orig = draw_relations.clone();
-        for rel in &graph.relations {
-            if !rel.edges.is_empty() {
-                let pairs = draw_relations.get_mut(&rel.name).unwrap();
-                for (e1, e2) in pairs.clone() {
-                    let k = (e1, e2);
-                    for preferred_rel_name in rel.ty.preferred.iter() {
-                        let s = String::from(*preferred_rel_name);
-                        match orig.get(&s) {
-                            // if any "preferred" relation also contains the edge (e1,e2) then don't show this one.
-                            Some(preferred_rel) if preferred_rel.contains(&k) => {
-                                pairs.remove(&k);
-                            }
-                            _ => (),
-                        }
-                    }
-                }
-            }
-        }
+        // the shapes of the displayed nodes, so edges can be routed around those in their way
+        let boxes: HashMap<String, Rect> =
+            node_layout.positions().map(|(name, pos, size)| (name, Rect::around(pos, size))).collect();
+        let obstacles: Vec<Rect> = boxes.values().copied().collect();
 
         log!(log::GRAPH, "finished nodes, now writing relations...");
 
@@ -1023,6 +1248,9 @@ pub fn draw_graph_gv(f: &mut dyn io::Write, graph: &Graph, _opts: &GraphOpts) ->
             write!(f, "\x1D")?
         };
 
+        // the relations with at least one edge drawn, for the legend
+        let mut drawn_relations: Vec<&str> = Vec::new();
+
         for rel in &graph.relations {
             let mut symmetric_edges: HashSet<(String, String)> = HashSet::new();
 
@@ -1033,24 +1261,16 @@ pub fn draw_graph_gv(f: &mut dyn io::Write, graph: &Graph, _opts: &GraphOpts) ->
 
                 // some of the edges are to hidden nodes
                 // so we simply hide the edges, and re-compute the reductions
-                let edges: &HashSet<(String, String)> = draw_relations.get(&rel.name).unwrap();
+                let mut edges: Vec<&(String, String)> = draw_relations.get(&rel.name).unwrap().iter().collect();
+                edges.sort();
 
                 log!(log::GRAPH, &format!("drawing relation {} (#{})", rel.name, edges.len()));
                 for (from, to) in edges {
-                    // do not show IW -(rf)-> R
-                    // when R's addr is not written by the test
-                    if let Some(to_event) = &graph.events.get(to) {
-                        if !graph.opts.debug
-                            && rel.name.ends_with("rf")
-                            && from == "IW"
-                            && !mutated_pas_event_names.contains(&to)
-                            && !event_in_shows(&graph.opts.force_show_events, to_event)
-                        {
-                            continue;
-                        }
+                    if is_hidden_initial_rf(graph, &rel.name, from, to, &mutated_pas_event_names) {
+                        continue;
                     }
 
-                    let dir = if rel.edges.contains(&(to.clone(), from.clone())) {
+                    let dir = if is_undirected(graph, rel, from, to) {
                         if symmetric_edges.contains(&(to.clone(), from.clone())) {
                             continue;
                         } else {
@@ -1061,29 +1281,914 @@ pub fn draw_graph_gv(f: &mut dyn io::Write, graph: &Graph, _opts: &GraphOpts) ->
                         ""
                     };
 
-                    let labelattr =
-                        // for vertical, but relatively short, "po" edges
-                        // we try fit them "high" up near the tail to make the most use of space
-                        if &rel.name == "po" || &rel.name == "po-loc" {
-                            "taillabel"
-                        } else {
-                            "label"
-                        };
-                    let label = if rel.name != "po" || graph.opts.debug {
-                        format!("{}=\" {} \",", labelattr, rel.name)
-                    } else {
-                        "".to_string()
+                    let label = match graph.opts.edge_label(&rel.name) {
+                        EdgeLabel::Hide => "".to_string(),
+                        EdgeLabel::Abbreviate(abbrev) => {
+                            format!("label={},", dot::quote(&edge_label(graph, &rel.name, &abbrev, from, to)))
+                        }
+                        EdgeLabel::TailLabel => {
+                            format!("taillabel={},", dot::quote(&edge_label(graph, &rel.name, &rel.name, from, to)))
+                        }
+                        EdgeLabel::Label => {
+                            format!("label={},", dot::quote(&edge_label(graph, &rel.name, &rel.name, from, to)))
+                        }
+                    };
+                    let (color, attrs) = relation_edge_style(&graph.opts, &rel.name);
+                    let color = dot::id(color);
+                    let route = match (boxes.get(from), boxes.get(to)) {
+                        (Some(from_box), Some(to_box)) => {
+                            routing::route(from_box, to_box, &obstacles, POINTS_PER_INCH as i64)
+                        }
+                        _ => None,
                     };
-                    let color = relation_color(&rel.name);
-                    writeln!(f, " {} -> {} [{}color={}, {}fontcolor={}];", from, to, dir, color, label, color)?;
+                    let route = route.map(|route| fmt_route(&route, dir.is_empty(), scale)).unwrap_or_default();
+                    writeln!(
+                        f,
+                        " {} -> {} [{}{}{}color={}, {}fontcolor={}];",
+                        dot::id(from),
+                        dot::id(to),
+                        dir,
+                        attrs,
+                        route,
+                        color,
+                        label,
+                        color
+                    )?;
+                    if drawn_relations.last() != Some(&rel.name.as_str()) {
+                        drawn_relations.push(&rel.name)
+                    }
+                }
+            }
+        }
+
+        // The synthesized iio edges are drawn separately from the
+        // relations above, as they are never symmetric.
+        if graph.opts.show_iio {
+            let edges = displayed_iio_edges(graph, &displayed_event_names);
+            if !edges.is_empty() {
+                if graph.opts.control_delimit {
+                    writeln!(f, "\x1Eiio\x1F")?
+                };
+                for (from, to) in edges {
+                    writeln!(f, " {} -> {} [color=grey, penwidth=0.5, arrowsize=0.5];", dot::id(&from), dot::id(&to))?
                 }
             }
         }
+
         if graph.opts.control_delimit {
             write!(f, "\x1D")?
         }
+
+        if graph.opts.show_legend {
+            draw_legend(f, &graph.opts, &node_layout, &drawn_relations)?
+        }
+    }
+
+    // A caption noting anything which means the graph may be
+    // incomplete. This comes last so that the clusters above don't
+    // inherit it as their label.
+    if !graph.warnings.is_empty() {
+        let caption: Vec<String> = graph.warnings.iter().map(|warning| format!("warning: {}", warning)).collect();
+        writeln!(f, "    label={};", dot::quote(&caption.join("\n")))?;
+        writeln!(f, "    labelloc=b;")?
     }
 
     log!(log::VERBOSE, "generated graph");
+    writeln!(f, "}}")?;
+
+    // see draw_legend for the space taken up by the legend
+    if graph.opts.show_legend {
+        Ok(right + POINTS_PER_INCH as i64 * 5 + wiggle)
+    } else {
+        Ok(right + wiggle)
+    }
+}
+
+/// Add a dashed border to a node style, used for events which are
+/// only present in one of two diffed executions
+fn dashed_node_style(style: &str) -> String {
+    format!("\"{},dashed\"", style.trim_matches('"'))
+}
+
+/// Draw two executions of the same test over a single layout, so the
+/// differences between them can be seen at a glance. Events are
+/// matched by name, and those present in only one execution are drawn
+/// with a dashed border. Relation edges are green if only in the first
+/// graph, red if only in the second, and black if in both. The options
+/// of the first graph are used for layout.
+pub fn draw_graph_diff_gv(g1: &Graph, g2: &Graph, f: &mut dyn io::Write) -> io::Result<()> {
+    let mut events = g1.events.clone();
+    for (name, ev) in &g2.events {
+        events.entry(name.clone()).or_insert_with(|| ev.clone());
+    }
+    let union = Graph {
+        events,
+        sets: vec![],
+        relations: vec![],
+        show: vec![],
+        opts: g1.opts.clone(),
+        litmus_opts: g1.litmus_opts.clone(),
+        names: g1.names.clone(),
+        warnings: vec![],
+    };
+
+    let scale = Scale::new(union.opts.scale);
+    writeln!(f, "digraph ExecDiff {{")?;
+    write_graph_attrs(f, scale)?;
+
+    let mut node_layout = produce_node_layout(&union, &union.litmus_opts, &union.opts, HashSet::new(), true);
+    for node in node_layout.iter_nodes_mut(false, false) {
+        if let GVGridNode::Node(pge) = &mut node.node {
+            let name = pge.name();
+            if name != "IW" && !(g1.events.contains_key(&name) && g2.events.contains_key(&name)) {
+                pge.style.node_style = dashed_node_style(&pge.style.node_style)
+            }
+        }
+    }
+
+    if let Some(iw) = node_layout.children.get(&(0, 0)) {
+        writeln!(f, "{};", iw.fmt_as_node(scale))?;
+    }
+
+    let mut displayed_event_names: HashSet<String> = HashSet::new();
+    displayed_event_names.insert("IW".to_string());
+
+    if let Some(GVGridChild { node: GVGridNode::SubCluster(thread_clusters), .. }) =
+        node_layout.children.get(&threads_addr(union.opts.orientation))
+    {
+        let mut threads: Vec<(&(usize, usize), &GVGridChild<'_>)> = thread_clusters.children.iter().collect();
+        threads.sort_by_key(|(rc, _)| **rc);
+
+        for (tid, (_, thread_child)) in threads.into_iter().enumerate() {
+            if union.opts.draw_thread_boxes {
+                draw_box(
+                    &union,
+                    f,
+                    &format!("{}", tid),
+                    &format!("Thread {}", tid),
+                    thread_child,
+                    &thread_graphstyle(&union.opts, tid),
+                    "style=dashed;",
+                )?;
+            }
+
+            let mut nodes = thread_child.unwrap_cluster().iter_nodes(true, false);
+            nodes.sort_by_key(|node| node.unwrap_node().name());
+            for node in nodes {
+                displayed_event_names.insert(node.unwrap_node().name());
+                writeln!(f, "    {};", node.fmt_as_node(scale))?;
+            }
+
+            if union.opts.draw_thread_boxes {
+                writeln!(f, "}}")?;
+            }
+        }
+    }
+
+    let mut rel_names: Vec<&String> = g1.relations.iter().map(|rel| &rel.name).collect();
+    for rel in &g2.relations {
+        if !rel_names.contains(&&rel.name) {
+            rel_names.push(&rel.name)
+        }
+    }
+
+    let no_edges = HashSet::new();
+    for name in rel_names {
+        let rel1 = g1.relations.iter().find(|rel| &rel.name == name);
+        let rel2 = g2.relations.iter().find(|rel| &rel.name == name);
+        let edges1 = rel1.map(|rel| &rel.all_edges).unwrap_or(&no_edges);
+        let edges2 = rel2.map(|rel| &rel.all_edges).unwrap_or(&no_edges);
+
+        // Simplify the union of both relations, so an edge implied
+        // transitively in one execution is not reported as missing
+        let ty = rel1.or(rel2).map(|rel| &rel.ty).unwrap();
+        let all_edges: HashSet<(String, String)> = edges1
+            .union(edges2)
+            .filter(|(from, to)| displayed_event_names.contains(from) && displayed_event_names.contains(to))
+            .cloned()
+            .collect();
+        let mut edges: Vec<(String, String)> = simplify_edges(ty, &all_edges).into_iter().collect();
+        edges.sort();
+
+        for edge in edges {
+            let color = match (edges1.contains(&edge), edges2.contains(&edge)) {
+                (true, false) => "green",
+                (false, true) => "red",
+                _ => "black",
+            };
+            writeln!(
+                f,
+                " {} -> {} [color={}, label={}, fontcolor={}];",
+                dot::id(&edge.0),
+                dot::id(&edge.1),
+                color,
+                dot::quote(&format!(" {} ", name)),
+                color
+            )?;
+        }
+    }
+
     writeln!(f, "}}")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::graph::tests::{fault_event, test_graph};
+    use crate::graph::GraphMode;
+
+    pub(super) fn fault_graph(events: &[(ThreadId, usize, usize)]) -> Graph {
+        let events = events.iter().map(|(thread_id, po, iio)| fault_event(*thread_id, *po, *iio)).collect();
+        let opts = GraphOpts {
+            mode: GraphMode::Dot,
+            show_regs: HashSet::new(),
+            show_iio: true,
+            derive_fr: false,
+            ..GraphOpts::default()
+        };
+        test_graph(events, vec![], &opts)
+    }
+
+    #[test]
+    fn test_iio_edges_skip_hidden() {
+        let g = fault_graph(&[(0, 0, 0), (0, 0, 1), (0, 0, 2), (0, 0, 3), (0, 1, 0), (1, 0, 0), (1, 0, 1)]);
+        let displayed: HashSet<String> = g.events.keys().filter(|name| *name != "R0_0_2").cloned().collect();
+        let edges = displayed_iio_edges(&g, &displayed);
+        let edges: Vec<(&str, &str)> = edges.iter().map(|(from, to)| (from.as_str(), to.as_str())).collect();
+        // There is no iio edge between R0_0_3 and R0_1_0, which are different instructions
+        assert_eq!(edges, vec![("R0_0_0", "R0_0_1"), ("R0_0_1", "R0_0_3"), ("R1_0_0", "R1_0_1")]);
+    }
+
+    #[test]
+    fn test_draw_iio_edges() {
+        let mut g = fault_graph(&[(0, 0, 0), (0, 0, 1), (0, 0, 2)]);
+        let mut output = Vec::new();
+        draw_graph_gv(&mut output, &g, &g.opts).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains(" R0_0_0 -> R0_0_1 [color=grey"));
+        assert!(output.contains(" R0_0_1 -> R0_0_2 [color=grey"));
+
+        g.opts.show_iio = false;
+        let mut output = Vec::new();
+        draw_graph_gv(&mut output, &g, &g.opts).unwrap();
+        assert!(!String::from_utf8(output).unwrap().contains("color=grey"));
+    }
+
+    /// Draw a small two-thread execution with the given orientation
+    fn draw_small(orientation: GraphOrientation) -> String {
+        let mut g = fault_graph(&[(0, 0, 0), (0, 1, 0), (0, 2, 0), (1, 0, 0), (1, 1, 0)]);
+        g.opts.show_iio = false;
+        g.opts.orientation = orientation;
+        g.opts.hide_unused_initial_state = false;
+        let mut output = Vec::new();
+        draw_graph_gv(&mut output, &g, &g.opts).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_wrapped_label_dimensions() {
+        let node = |label: String| PositionedGraphNode {
+            ev: GridNode {
+                ev: None,
+                ev_label: ("a".to_string(), "".to_string()),
+                alignment: Align::Middle,
+                label,
+                annot: 0,
+            },
+            style: Style {
+                bg_color: "white".to_string(),
+                node_shape: "box".to_string(),
+                node_style: "filled".to_string(),
+                dimensions: (0.0, 0.0),
+            },
+            measure: Arc::new(HeuristicMeasure),
+        };
+        let label = "a: \"msr ttbr0_el1, x3\": T 0x205800 = 0x1".to_string();
+
+        let unwrapped = node(wrap_label(label.clone(), None));
+        assert_eq!(unwrapped.ev.label, label);
+        assert_eq!(unwrapped.compute_width(), (FONTSIZE * 3 / 5) * label.len());
+        assert_eq!(unwrapped.compute_height(), 72);
+
+        let wrapped = node(wrap_label(label, Some(16)));
+        assert_eq!(wrapped.ev.label, "a: \"msr\nttbr0_el1, x3\":\nT 0x205800 = 0x1");
+        assert_eq!(wrapped.compute_width(), (FONTSIZE * 3 / 5) * 16);
+        assert_eq!(wrapped.compute_height(), 72 + 2 * FONTSIZE);
+    }
+
+    #[test]
+    fn test_hide_unused_initial_state() {
+        let draw = |g: &Graph| {
+            let mut output = Vec::new();
+            draw_graph_gv(&mut output, g, &g.opts).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        let mut g = fault_graph(&[(0, 0, 0), (0, 1, 0), (1, 0, 0)]);
+
+        // the threads move up into the space left by the initial state
+        let output = draw(&g);
+        assert!(!output.contains("IW ["));
+        assert!(output.contains("R0_0_0 [fillcolor=gold, style=filled, pos=\"229,-50!\""));
+        g.opts.hide_unused_initial_state = false;
+        let output = draw(&g);
+        assert!(output.contains("IW ["));
+        assert!(output.contains("R0_0_0 [fillcolor=gold, style=filled, pos=\"229,-230!\""));
+        g.opts.hide_unused_initial_state = true;
+
+        // rf edges from the initial state to locations the test never
+        // writes are not drawn, so they do not count
+        g.relations = vec![relation("rf", &[("IW", "R0_0_0")])];
+        assert!(!draw(&g).contains("IW ["));
+        g.opts.debug = true;
+        let output = draw(&g);
+        assert!(output.contains("IW ["));
+        assert!(output.contains(" IW -> R0_0_0 ["));
+        g.opts.debug = false;
+
+        g.relations = vec![relation("fr", &[("R1_0_0", "IW")])];
+        assert!(draw(&g).contains("IW ["));
+    }
+
+    #[test]
+    fn test_golden_vertical() {
+        assert_eq!(draw_small(GraphOrientation::Vertical), include_str!("golden/vertical.dot"))
+    }
+
+    #[test]
+    fn test_golden_horizontal() {
+        assert_eq!(draw_small(GraphOrientation::Horizontal), include_str!("golden/horizontal.dot"))
+    }
+
+    #[test]
+    fn test_deterministic_output() {
+        let draw = |g: &Graph| {
+            let mut output = Vec::new();
+            draw_graph_gv(&mut output, g, &g.opts).unwrap();
+            output
+        };
+        let graph = || {
+            let mut g = fault_graph(&[(0, 0, 0), (0, 0, 1), (0, 0, 2), (0, 1, 0), (1, 0, 0), (1, 0, 1), (2, 0, 0)]);
+            g.relations = vec![
+                relation("rf", &[("R0_0_0", "R1_0_0"), ("R0_0_1", "R1_0_1"), ("R0_1_0", "R2_0_0")]),
+                relation("co", &[("R0_0_0", "R0_1_0"), ("R1_0_0", "R2_0_0"), ("R0_0_2", "R1_0_1")]),
+                relation("fr", &[("R1_0_0", "R0_0_0"), ("R0_0_0", "R1_0_0")]),
+            ];
+            g.opts.show_legend = true;
+            g
+        };
+
+        // each graph has its own hash maps, which will iterate in different orders
+        let g = graph();
+        let output = draw(&g);
+        assert_eq!(output, draw(&g));
+        for _ in 0..8 {
+            assert_eq!(output, draw(&graph()))
+        }
+    }
+
+    /// The x coordinates of every pinned position in a dot file, other
+    /// than that of the title
+    fn pinned_xs(output: &str) -> Vec<i64> {
+        output
+            .lines()
+            .filter(|line| !line.trim_start().starts_with("title "))
+            .flat_map(|line| line.split("pos=\"").skip(1))
+            .map(|pos| pos.split(',').next().unwrap().parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_box_levels() {
+        let draw = |instruction_boxes: bool, thread_boxes: bool| {
+            let mut g = fault_graph(&[(0, 0, 0), (0, 1, 0), (0, 1, 1), (1, 0, 0)]);
+            g.opts.draw_instruction_boxes = instruction_boxes;
+            g.opts.draw_thread_boxes = thread_boxes;
+            let mut output = Vec::new();
+            draw_graph_gv(&mut output, &g, &g.opts).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        // the label of each cluster, in the order they are drawn
+        let clusters = |output: &str| -> Vec<(String, String)> {
+            let lines: Vec<&str> = output.lines().collect();
+            lines
+                .windows(2)
+                .filter_map(|pair| {
+                    let ident = pair[0].strip_prefix("subgraph cluster")?.strip_suffix(" {")?;
+                    let label = pair[1].trim().strip_prefix("label = \"")?.strip_suffix("\";")?;
+                    Some((ident.to_string(), label.to_string()))
+                })
+                .collect()
+        };
+        let owned = |clusters: &[(&str, &str)]| -> Vec<(String, String)> {
+            clusters.iter().map(|(ident, label)| (ident.to_string(), label.to_string())).collect()
+        };
+
+        let both = draw(true, true);
+        assert_eq!(clusters(&both), owned(&[("0", "Thread 0"), ("0_1", ""), ("1", "Thread 1")]));
+
+        // the thread labels move onto the first instruction of each thread
+        let no_threads = draw(true, false);
+        assert_eq!(clusters(&no_threads), owned(&[("0_0", "Thread 0"), ("0_1", ""), ("1_0", "Thread 1")]));
+        dot::tests::parse_dot(&no_threads).unwrap();
+        // and the threads are moved closer together
+        assert!(pinned_xs(&no_threads).iter().max() < pinned_xs(&both).iter().max());
+
+        let no_instrs = draw(false, true);
+        assert_eq!(clusters(&no_instrs), owned(&[("0", "Thread 0"), ("1", "Thread 1")]));
+        dot::tests::parse_dot(&no_instrs).unwrap();
+
+        let neither = draw(false, false);
+        assert!(!neither.contains("subgraph"));
+        dot::tests::parse_dot(&neither).unwrap();
+    }
+
+    /// A graph with a read or write to one of the given addresses for each event
+    pub(super) fn accesses_graph(accesses: &[((ThreadId, usize, usize), &str, &str)]) -> Graph {
+        let mut g = fault_graph(&accesses.iter().map(|(ev, _, _)| *ev).collect::<Vec<_>>());
+        for ((tid, po, iio), kind, addr) in accesses {
+            let ev = g.events.get_mut(&format!("R{}_{}_{}", tid, po, iio)).unwrap();
+            ev.event_kind = match *kind {
+                "W" => GraphEventKind::WriteMem(WriteKind { to_translation_table_entry: None }),
+                _ => GraphEventKind::ReadMem,
+            };
+            ev.value = Some(GraphValue::from_fields(kind, Some(addr.to_string()), None, 8, Some("0x1".to_string())))
+        }
+        g
+    }
+
+    /// The box around each displayed event in a layout
+    fn event_rects(g: &Graph) -> HashMap<String, Rect> {
+        let layout = produce_node_layout(g, &g.litmus_opts, &g.opts, HashSet::new(), true);
+        layout.positions().map(|(name, pos, size)| (name, Rect::around(pos, size))).collect()
+    }
+
+    fn assert_no_overlaps(rects: &HashMap<String, Rect>) {
+        for (name1, r1) in rects {
+            for (name2, r2) in rects {
+                let overlap = r1.left < r2.right && r2.left < r1.right && r1.top < r2.bottom && r2.top < r1.bottom;
+                assert!(name1 == name2 || !overlap, "{} {:?} overlaps {} {:?}", name1, r1, name2, r2)
+            }
+        }
+    }
+
+    #[test]
+    fn test_align_addresses() {
+        // message passing, where thread 0 writes x then y and thread 1 reads y then x
+        let (x, y) = ("0x1000", "0x2000");
+        let mut g =
+            accesses_graph(&[((0, 0, 0), "W", x), ((0, 1, 0), "W", y), ((1, 0, 0), "R", y), ((1, 1, 0), "R", x)]);
+        g.opts.align_addresses = true;
+
+        // threads side by side line up their columns relative to the left of each thread
+        let rects = event_rects(&g);
+        let left = |tid: usize| {
+            rects.iter().filter(|(name, _)| name.starts_with(&format!("R{}_", tid))).map(|(_, r)| r.left).min().unwrap()
+        };
+        let offset = |name: &str, tid: usize| rects[name].centre().0 - left(tid);
+        assert_eq!(offset("R0_0_0", 0), offset("R1_1_0", 1));
+        assert_eq!(offset("R0_1_0", 0), offset("R1_0_0", 1));
+        assert!(offset("R0_0_0", 0) < offset("R0_1_0", 0));
+        assert_no_overlaps(&rects);
+
+        // threads stacked as rows share the same columns
+        g.opts.orientation = GraphOrientation::Horizontal;
+        let rects = event_rects(&g);
+        assert_eq!(rects["R0_0_0"].centre().0, rects["R1_1_0"].centre().0);
+        assert_eq!(rects["R0_1_0"].centre().0, rects["R1_0_0"].centre().0);
+        assert_ne!(rects["R0_0_0"].centre().0, rects["R0_1_0"].centre().0);
+        assert_no_overlaps(&rects);
+
+        // without the option, the events are left where they were
+        g.opts.align_addresses = false;
+        assert_ne!(event_rects(&g)["R0_1_0"], rects["R0_1_0"]);
+
+        // two events of an instruction to the same address are kept apart
+        let mut g =
+            accesses_graph(&[((0, 0, 0), "R", x), ((0, 0, 1), "W", x), ((1, 0, 0), "R", x), ((1, 1, 0), "R", y)]);
+        g.opts.align_addresses = true;
+        let rects = event_rects(&g);
+        assert!(rects["R0_0_0"].right + MIN_EVENT_GAP <= rects["R0_0_1"].left);
+        assert_no_overlaps(&rects);
+    }
+
+    #[test]
+    fn test_insert_event_collision() {
+        let g = fault_graph(&[(0, 0, 0), (0, 0, 1), (0, 0, 2)]);
+        let mut layout = GVGraphLayout::new();
+        let pgn = |name: &str| PositionedGraphNode {
+            ev: GridNode {
+                ev: Some(&g.events[name]),
+                label: name.to_string(),
+                ev_label: (name.to_string(), "".to_string()),
+                alignment: Align::Middle,
+                annot: 0,
+            },
+            style: Style {
+                bg_color: "white".to_string(),
+                node_shape: "box".to_string(),
+                node_style: "filled".to_string(),
+                dimensions: (0.0, 0.0),
+            },
+            measure: Arc::new(HeuristicMeasure),
+        };
+        let event_layout = Layout::new(Padding::default(), Align::Middle);
+        assert_eq!(insert_event(&mut layout, (0, 0), pgn("R0_0_0"), event_layout.clone()), (0, 0));
+        assert_eq!(insert_event(&mut layout, (0, 0), pgn("R0_0_1"), event_layout.clone()), (0, 1));
+        assert_eq!(insert_event(&mut layout, (0, 0), pgn("R0_0_2"), event_layout), (0, 2));
+        assert_eq!(layout.iter_nodes(false, false).len(), 3);
+    }
+
+    #[test]
+    fn test_paired_writes() {
+        // an instruction such as stp makes two explicit writes
+        let mut g =
+            accesses_graph(&[((0, 0, 0), "W", "0x1000"), ((0, 0, 1), "W", "0x1008"), ((1, 0, 0), "R", "0x1000")]);
+        g.relations = vec![relation("rf", &[("R0_0_0", "R1_0_0")])];
+        let mut output = Vec::new();
+        draw_graph_gv(&mut output, &g, &g.opts).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        for name in ["R0_0_0", "R0_0_1", "R1_0_0"] {
+            assert_eq!(output.matches(&format!("\n    {} [", name)).count(), 1, "{}", output)
+        }
+    }
+
+    #[test]
+    fn test_layout_file() {
+        let draw = |g: &Graph| {
+            let mut output = Vec::new();
+            draw_graph_gv(&mut output, g, &g.opts).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        let mut g = fault_graph(&[(0, 0, 0), (0, 1, 0), (1, 0, 0)]);
+        g.relations = vec![relation("rf", &[("R0_0_0", "R1_0_0")]), relation("co", &[("IW", "R0_1_0")])];
+        let rects = event_rects(&g);
+        let dot = draw(&g);
+
+        // a saved layout draws the graph exactly as before
+        let layout = LayoutFile::from_json(&dump_graph_layout(&g).to_json()).unwrap();
+        assert_eq!(layout.events.len(), 4);
+        assert_eq!(layout.events["R1_0_0"].pos, rects["R1_0_0"].centre());
+        g.opts.layout_file = Some(layout.clone());
+        assert_eq!(draw(&g), dot);
+
+        // a moved event is drawn where it was moved to, and events
+        // missing from the layout are placed as usual
+        let mut moved = layout;
+        moved.events.get_mut("R1_0_0").unwrap().pos = (1000, 2000);
+        moved.events.remove("R0_1_0");
+        g.opts.layout_file = Some(moved);
+        let moved_rects = event_rects(&g);
+        assert_eq!(moved_rects["R1_0_0"].centre(), (1000, 2000));
+        assert_eq!(moved_rects["R0_1_0"], rects["R0_1_0"]);
+        assert!(draw(&g).contains("pos=\"1000,-2000!\""));
+
+        assert!(LayoutFile::from_json("{\"events\": []}").is_err());
+    }
+
+    #[test]
+    fn test_hidden_event_counts() {
+        // instructions with six or more events are not displayed
+        let g = fault_graph(&[(0, 0, 0), (0, 0, 1), (0, 0, 2), (0, 0, 3), (0, 0, 4), (0, 0, 5), (1, 0, 0), (1, 0, 1)]);
+        let mut layout = produce_node_layout(&g, &g.litmus_opts, &g.opts, HashSet::new(), true);
+        layout.find_node_mut("R1_0_1").unwrap().layout.show = false;
+        let counts = hidden_event_counts(&g, &displayed_event_names(&layout));
+        assert_eq!(counts, [((0, 0), 6), ((1, 0), 1)].into_iter().collect());
+
+        // nothing is hidden, so the instruction boxes are unlabelled
+        let mut output = Vec::new();
+        draw_graph_gv(&mut output, &fault_graph(&[(0, 0, 0), (0, 0, 1)]), &g.opts).unwrap();
+        assert!(!String::from_utf8(output).unwrap().contains("hidden"));
+    }
+
+    #[test]
+    fn test_register_events() {
+        let mut g = fault_graph(&[(0, 0, 0), (0, 1, 0), (0, 1, 1)]);
+        for (name, kind, prefix) in
+            [("R0_1_0", GraphEventKind::ReadReg, "Rreg"), ("R0_1_1", GraphEventKind::WriteReg, "Wreg")]
+        {
+            let ev = g.events.get_mut(name).unwrap();
+            ev.event_kind = kind;
+            ev.value = Some(GraphValue::from_fields(
+                prefix,
+                Some("TTBR0_EL1".to_string()),
+                None,
+                8,
+                Some("0x1000".to_string()),
+            ));
+        }
+        let mut output = Vec::new();
+        draw_graph_gv(&mut output, &g, &g.opts).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        let node =
+            |name: &str| output.lines().find(|line| line.trim_start().starts_with(&format!("{} [", name))).unwrap();
+        for (name, label) in [("R0_1_0", "b0: Rreg TTBR0_EL1 = 0x1000"), ("R0_1_1", "b1: Wreg TTBR0_EL1 = 0x1000")] {
+            let node = node(name);
+            assert!(node.contains("shape=oval") && node.contains("fillcolor=lightgoldenrod"), "{}", node);
+            assert!(node.contains(&format!("label=\"{}\"", label)), "{}", node);
+        }
+        assert!(node("R0_0_0").contains("shape=box"));
+    }
+
+    #[test]
+    fn test_relation_directions() {
+        let mut g = fault_graph(&[(0, 0, 0), (0, 1, 0), (1, 0, 0)]);
+        g.relations = vec![relation("co", &[("R0_0_0", "R1_0_0"), ("R1_0_0", "R0_0_0"), ("R0_0_0", "R0_1_0")])];
+        let edges = |g: &Graph| {
+            let mut output = Vec::new();
+            draw_graph_gv(&mut output, g, &g.opts).unwrap();
+            let output = String::from_utf8(output).unwrap();
+            let mut edges: Vec<(String, bool)> = output
+                .lines()
+                .filter(|line| line.contains(" -> "))
+                .map(|line| {
+                    let (from, rest) = line.trim().split_once(" -> ").unwrap();
+                    let to = rest.split_once(' ').unwrap().0;
+                    (format!("{}->{}", from, to), !line.contains("dir=none"))
+                })
+                .collect();
+            edges.sort();
+            edges
+        };
+        let edge = |name: &str, directed: bool| (name.to_string(), directed);
+
+        // the 2-cycle is drawn as one undirected edge by default
+        assert_eq!(edges(&g), vec![edge("R0_0_0->R0_1_0", true), edge("R0_0_0->R1_0_0", false)]);
+
+        g.opts.relation_directions.insert("co".to_string(), EdgeDirection::Auto);
+        assert_eq!(edges(&g), vec![edge("R0_0_0->R0_1_0", true), edge("R0_0_0->R1_0_0", false)]);
+
+        g.opts.relation_directions.insert("co".to_string(), EdgeDirection::Directed);
+        assert_eq!(
+            edges(&g),
+            vec![edge("R0_0_0->R0_1_0", true), edge("R0_0_0->R1_0_0", true), edge("R1_0_0->R0_0_0", true)]
+        );
+
+        g.opts.relation_directions.insert("co".to_string(), EdgeDirection::Undirected);
+        assert_eq!(edges(&g), vec![edge("R0_0_0->R0_1_0", false), edge("R0_0_0->R1_0_0", false)]);
+
+        // other relations are unaffected
+        g.relations[0].name = "rf".to_string();
+        assert_eq!(edges(&g), vec![edge("R0_0_0->R0_1_0", true), edge("R0_0_0->R1_0_0", false)]);
+    }
+
+    #[test]
+    fn test_routed_edges() {
+        let mut g = fault_graph(&[(0, 0, 0), (1, 0, 0), (2, 0, 0)]);
+        g.relations = vec![relation("rf", &[("R0_0_0", "R1_0_0"), ("R0_0_0", "R2_0_0")])];
+        let mut output = Vec::new();
+        draw_graph_gv(&mut output, &g, &g.opts).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        dot::tests::parse_dot(&output).unwrap();
+
+        // the edge between neighbouring threads is left straight, but
+        // the one across the middle thread goes around its event
+        let edge = |to: &str| output.lines().find(|line| line.starts_with(&format!(" R0_0_0 -> {} ", to))).unwrap();
+        assert!(!edge("R1_0_0").contains("pos="));
+        let routed = edge("R2_0_0");
+        let pos = routed.split_once("pos=\"e,").unwrap().1.split_once('"').unwrap().0;
+        assert_eq!(pos.split(' ').count(), 1 + 10);
+        assert!(routed.contains("lp=\""));
+    }
+
+    #[test]
+    fn test_scale() {
+        fn positions(output: &str) -> Vec<(i64, i64)> {
+            output
+                .split("pos=\"")
+                .skip(1)
+                .map(|pos| {
+                    let (x, y) = pos.split_once('!').unwrap().0.split_once(',').unwrap();
+                    (x.parse().unwrap(), y.parse().unwrap())
+                })
+                .collect()
+        }
+        let draw = |g: &Graph| {
+            let mut output = Vec::new();
+            draw_graph_gv(&mut output, g, &g.opts).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        let mut g = fault_graph(&[(0, 0, 0), (0, 1, 0), (1, 0, 0)]);
+        g.opts.show_legend = true;
+        g.relations = vec![relation("rf", &[("R0_0_0", "R1_0_0")])];
+        let output = draw(&g);
+        g.opts.scale = 2.0;
+        let doubled = draw(&g);
+
+        let (ps, doubled_ps) = (positions(&output), positions(&doubled));
+        assert_eq!(ps.len(), 5);
+        assert_eq!(doubled_ps, ps.iter().map(|(x, y)| (2 * x, 2 * y)).collect::<Vec<_>>());
+        assert!(output.contains("node [fontsize=44, fontname=aerial];"));
+        assert!(doubled.contains("node [fontsize=88, fontname=aerial];"));
+        assert!(doubled.contains("legend0_from [shape=point, width=0.2,"));
+    }
+
+    #[test]
+    fn test_draw_graphs() {
+        let mut g1 = fault_graph(&[(0, 0, 0), (0, 1, 0), (1, 0, 0)]);
+        g1.opts.show_legend = true;
+        g1.relations = vec![relation("rf", &[("R0_0_0", "R1_0_0")])];
+        let g2 = fault_graph(&[(0, 0, 0), (1, 0, 0), (1, 1, 0), (2, 0, 0)]);
+
+        let mut output = Vec::new();
+        draw_graphs_gv(&mut output, vec![(1, true, &g1), (2, false, &g2)]).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        let sections: Vec<&str> = output.split("digraph ").skip(1).collect();
+        assert_eq!(sections.len(), 2);
+        assert!(sections[0].starts_with("Exec1 {"));
+        assert!(sections[0].contains("label=\"Execution #1 (satisfies final assertion)\""));
+        assert!(sections[1].starts_with("Exec2 {"));
+        assert!(sections[1].contains("label=\"Execution #2 (does not satisfy final assertion)\""));
+        for section in &sections {
+            dot::tests::parse_dot(&format!("digraph {}", section)).unwrap()
+        }
+
+        // the second execution starts to the right of the first, including its legend
+        let (xs1, xs2) = (pinned_xs(sections[0]), pinned_xs(sections[1]));
+        assert!(xs1.iter().max().unwrap() < xs2.iter().min().unwrap());
+
+        // each execution is only moved, not laid out differently
+        let mut single = Vec::new();
+        draw_graph_gv(&mut single, &g2, &g2.opts).unwrap();
+        let single_xs = pinned_xs(&String::from_utf8(single).unwrap());
+        let shift = xs2[0] - single_xs[0];
+        assert!(shift > 0);
+        assert_eq!(xs2, single_xs.iter().map(|x| x + shift).collect::<Vec<_>>())
+    }
+
+    pub(super) fn relation(name: &str, edges: &[(&str, &str)]) -> GraphRelation {
+        let edges: HashSet<(String, String)> =
+            edges.iter().map(|(from, to)| (from.to_string(), to.to_string())).collect();
+        GraphRelation {
+            name: name.to_string(),
+            ty: RelType { trans: RelTransType::Normal, preferred: Vec::new() },
+            edges: edges.clone(),
+            all_edges: edges,
+        }
+    }
+
+    #[test]
+    fn test_draw_diff() {
+        let mut g1 = fault_graph(&[(0, 0, 0), (0, 1, 0), (1, 0, 0)]);
+        let mut g2 = fault_graph(&[(0, 0, 0), (0, 1, 0), (1, 1, 0)]);
+        g1.relations = vec![relation("rf", &[("R0_0_0", "R0_1_0"), ("R0_0_0", "R1_0_0")])];
+        g2.relations = vec![relation("rf", &[("R0_0_0", "R0_1_0")]), relation("co", &[("R0_1_0", "R1_1_0")])];
+
+        let mut output = Vec::new();
+        draw_graph_diff_gv(&g1, &g2, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.contains(" R0_0_0 -> R0_1_0 [color=black, label=\" rf \""));
+        assert!(output.contains(" R0_0_0 -> R1_0_0 [color=green, label=\" rf \""));
+        assert!(output.contains(" R0_1_0 -> R1_1_0 [color=red, label=\" co \""));
+
+        let node_line = |name: &str| output.lines().find(|line| line.trim_start().starts_with(name)).unwrap();
+        assert!(!node_line("R0_0_0 ").contains("dashed"));
+        assert!(node_line("R1_0_0 ").contains("style=\"filled,dashed\""));
+        assert!(node_line("R1_1_0 ").contains("style=\"filled,dashed\""));
+    }
+
+    /// Rename an event of a graph, which must not have any edges yet
+    fn rename_event(g: &mut Graph, from: &str, to: &str) {
+        let mut ev = g.events.remove(from).unwrap();
+        ev.name = to.to_string();
+        g.events.insert(to.to_string(), ev);
+    }
+
+    #[test]
+    fn test_hostile_strings() {
+        use super::dot::tests::{hostile_strings, parse_dot};
+
+        for s in hostile_strings() {
+            let mut g = fault_graph(&[(0, 0, 0), (0, 1, 0), (1, 0, 0)]);
+            let name = format!("R0_0_0{}", s);
+            rename_event(&mut g, "R0_0_0", &name);
+            for ev in g.events.values_mut() {
+                ev.instr = Some(s.clone())
+            }
+            g.relations = vec![relation(&s, &[(&name, "R0_1_0"), (&name, "R1_0_0")])];
+            g.warnings = vec![s.clone()];
+            g.opts.debug = true;
+            g.opts.show_legend = true;
+            g.opts.palette.barrier = s.clone();
+            let style = RelationStyle { color: s.clone(), line: LineStyle::Dashed, arrowhead: Some(s.clone()) };
+            g.opts.relation_styles.insert(s.clone(), style);
+
+            let mut output = Vec::new();
+            draw_graph_gv(&mut output, &g, &g.opts).unwrap();
+            let output = String::from_utf8(output).unwrap();
+            if let Err(e) = parse_dot(&output) {
+                panic!("invalid dot for {:?}: {}\n{}", s, e, output)
+            }
+
+            let g2 = fault_graph(&[(0, 0, 0), (0, 1, 0)]);
+            let mut output = Vec::new();
+            draw_graph_diff_gv(&g, &g2, &mut output).unwrap();
+            let output = String::from_utf8(output).unwrap();
+            if let Err(e) = parse_dot(&output) {
+                panic!("invalid dot diff for {:?}: {}\n{}", s, e, output)
+            }
+        }
+    }
+
+    #[test]
+    fn test_thread_shading() {
+        let mut g = fault_graph(&[(0, 0, 0), (0, 0, 1), (1, 0, 0), (2, 0, 0), (3, 0, 0)]);
+        g.opts.thread_colors = Some(vec!["aliceblue".to_string(), "#fff0f0".to_string()]);
+
+        let mut output = Vec::new();
+        draw_graph_gv(&mut output, &g, &g.opts).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        let graph_line = |cluster: &str| {
+            let mut lines =
+                output.lines().skip_while(|line| !line.starts_with(&format!("subgraph cluster{} ", cluster)));
+            lines.nth(2).map(str::to_string)
+        };
+        assert!(graph_line("0").unwrap().ends_with("labeljust=l, bgcolor=aliceblue];"));
+        assert!(graph_line("1").unwrap().ends_with("labeljust=l, bgcolor=\"#fff0f0\"];"));
+        assert!(graph_line("2").unwrap().ends_with("labeljust=l, bgcolor=aliceblue];"));
+        assert!(graph_line("3").unwrap().ends_with("labeljust=l, bgcolor=\"#fff0f0\"];"));
+        // the instruction box stays transparent
+        assert!(!graph_line("0_0").unwrap().contains("bgcolor"));
+        assert_eq!(output.matches("bgcolor").count(), 4);
+    }
+
+    #[test]
+    fn test_edge_addresses() {
+        let mut g = fault_graph(&[(0, 0, 0), (0, 1, 0), (1, 0, 0), (1, 1, 0)]);
+        for (name, addr) in [("R0_0_0", "0x1000"), ("R0_1_0", "0x1000"), ("R1_0_0", "0x2000"), ("R1_1_0", "0x2000")] {
+            g.events.get_mut(name).unwrap().value =
+                Some(GraphValue::from_fields("R", Some(addr.to_string()), None, 8, Some("0x0".to_string())))
+        }
+        g.names.paddr_names.insert(0x1000, "x".to_string());
+        g.relations = vec![
+            relation("rf", &[("R0_0_0", "R0_1_0")]),
+            relation("co", &[("R1_0_0", "R1_1_0")]),
+            relation("ob", &[("R0_0_0", "R1_0_0")]),
+        ];
+
+        let draw = |g: &Graph| {
+            let mut output = Vec::new();
+            draw_graph_gv(&mut output, g, &g.opts).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        let output = draw(&g);
+        assert!(output.contains("label=\" rf \""));
+        assert!(output.contains("label=\" co \""));
+
+        g.opts.edge_addresses = true;
+        let output = draw(&g);
+        assert!(output.contains("label=\" rf[x] \""));
+        assert!(output.contains("label=\" co[0x2000] \""));
+        assert!(output.contains("label=\" ob \""));
+    }
+
+    #[test]
+    fn test_edge_labels() {
+        let mut g = fault_graph(&[(0, 0, 0), (0, 1, 0), (1, 0, 0), (1, 1, 0)]);
+        g.relations = vec![
+            relation("rf", &[("R0_0_0", "R1_0_0")]),
+            relation("trf", &[("R0_1_0", "R1_1_0")]),
+            relation("co", &[("R0_0_0", "R0_1_0")]),
+            relation("po", &[("R1_0_0", "R1_1_0")]),
+        ];
+        g.opts.edge_labels.insert("trf".to_string(), EdgeLabel::Abbreviate("τ".to_string()));
+        g.opts.edge_labels.insert("co".to_string(), EdgeLabel::Hide);
+        g.opts.edge_labels.insert("rf".to_string(), EdgeLabel::TailLabel);
+        g.opts.edge_labels.insert("po".to_string(), EdgeLabel::Label);
+
+        let mut output = Vec::new();
+        draw_graph_gv(&mut output, &g, &g.opts).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("taillabel=\" rf \""));
+        assert!(output.contains("label=\" τ \""));
+        assert!(!output.contains(" trf "));
+        assert!(!output.contains(" co "));
+        assert!(output.contains("label=\" po \""));
+    }
+
+    #[test]
+    fn test_warning_caption() {
+        let mut g = fault_graph(&[(0, 0, 0), (1, 0, 0)]);
+        let draw = |g: &Graph| {
+            let mut output = Vec::new();
+            draw_graph_gv(&mut output, g, &g.opts).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        assert!(!draw(&g).contains("labelloc=b"));
+
+        g.warnings = vec!["relation 'co' is drawn as empty".to_string(), "second".to_string()];
+        let output = draw(&g);
+        let lines: Vec<&str> = output.lines().collect();
+        // the caption is on the top-level graph, after every cluster
+        assert_eq!(lines[lines.len() - 3], "    label=\"warning: relation 'co' is drawn as empty\\nwarning: second\";");
+        assert_eq!(lines[lines.len() - 2], "    labelloc=b;");
+        assert_eq!(lines[lines.len() - 1], "}");
+    }
+}
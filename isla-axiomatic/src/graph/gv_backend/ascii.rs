@@ -0,0 +1,196 @@
+//! A plain text backend, for looking at executions in a terminal. The
+//! events are placed at the positions computed by the graphviz layout,
+//! scaled down to character cells, with the edges listed below them
+//! one per line.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+
+use super::*;
+
+/// the width (in points) of a character in a node label, which is
+/// also the width of a character cell in the text grid
+const CELL_WIDTH: i64 = (FONTSIZE * 3 / 5) as i64;
+
+/// A short one-line label for an event, such as `a1: W x=1`
+fn ascii_label(graph: &Graph, pgn: &PositionedGraphNode<'_>) -> String {
+    let ev = match pgn.ev.ev {
+        Some(ev) => ev,
+        None => return format!("{}: {}", format_ev_label(&pgn.ev.ev_label), pgn.ev.label),
+    };
+    let label = ev.fmt_label_short(&graph.opts, &pgn.ev.ev_label, &graph.names);
+    match ev.event_kind {
+        GraphEventKind::ReadMem | GraphEventKind::WriteMem(_) | GraphEventKind::ReadReg | GraphEventKind::WriteReg => {
+            match ev.value_name(&graph.opts, &graph.names) {
+                Some(value) => format!("{}={}", label, value),
+                None => label,
+            }
+        }
+        _ => label,
+    }
+}
+
+/// One line of text, which labels are placed on from left to right
+#[derive(Default)]
+struct Line {
+    text: String,
+}
+
+impl Line {
+    /// Put a label at a column, or just after the label before it if
+    /// that would overlap
+    fn place(&mut self, col: usize, label: &str) {
+        let len = self.text.chars().count();
+        if len < col {
+            self.text.extend(std::iter::repeat_n(' ', col - len))
+        } else if len > 0 {
+            self.text.push(' ')
+        }
+        self.text.push_str(label)
+    }
+}
+
+/// Draw a graph as text, using the same layout as [draw_graph_gv]
+/// with each event given a short label in the character cell nearest
+/// its position. The edges between the displayed events are listed
+/// below, grouped by relation, with undirected edges drawn without an
+/// arrowhead.
+pub fn draw_graph_ascii(graph: &Graph, f: &mut dyn io::Write) -> io::Result<()> {
+    let (node_layout, mutated_pas_event_names) = drawn_layout(graph);
+
+    // everything placed in the grid, as (top, left, label) in layout points
+    let mut placed: Vec<(i64, i64, String)> = Vec::new();
+    let mut ev_labels: HashMap<String, String> = HashMap::new();
+    for child in node_layout.iter_nodes(true, false) {
+        let pgn = child.unwrap_node();
+        ev_labels.insert(pgn.name(), format_ev_label(&pgn.ev.ev_label));
+        if let Some((x, y)) = child.layout.pos {
+            placed.push((y, x - pgn.compute_width() as i64 / 2, ascii_label(graph, pgn)))
+        }
+    }
+
+    if let Some(GVGridChild { node: GVGridNode::SubCluster(threads), .. }) =
+        node_layout.children.get(&threads_addr(graph.opts.orientation))
+    {
+        let mut threads: Vec<_> = threads.children.iter().collect();
+        threads.sort_by_key(|(rc, _)| **rc);
+        let headers: Vec<(i64, i64, String)> = threads
+            .into_iter()
+            .enumerate()
+            .filter_map(|(tid, (_, thread))| match &thread.node {
+                GVGridNode::SubCluster(cluster) => {
+                    let ((left, top), _) = cluster.bounding_box(true)?;
+                    Some((top - 1, left, format!("Thread {}", tid)))
+                }
+                _ => None,
+            })
+            .collect();
+        // side by side threads share one line of headers
+        let shared_top = match graph.opts.orientation {
+            GraphOrientation::Vertical => headers.iter().map(|(top, _, _)| *top).min(),
+            GraphOrientation::Horizontal => None,
+        };
+        placed.extend(headers.into_iter().map(|(top, left, header)| (shared_top.unwrap_or(top), left, header)))
+    }
+
+    let origin = placed.iter().map(|(_, left, _)| *left).min().unwrap_or(0);
+    let mut lines: BTreeMap<i64, Vec<(i64, String)>> = BTreeMap::new();
+    for (y, left, label) in placed {
+        lines.entry(y).or_default().push((left, label))
+    }
+    for (_, mut labels) in lines {
+        labels.sort();
+        let mut line = Line::default();
+        for (left, label) in labels {
+            line.place(((left - origin) / CELL_WIDTH) as usize, &label)
+        }
+        writeln!(f, "{}", line.text)?
+    }
+
+    let draw_relations = relation_edges_to_draw(graph, &displayed_event_names(&node_layout));
+    let ev_label = |name: &String| ev_labels.get(name).cloned().unwrap_or_else(|| name.clone());
+    for rel in &graph.relations {
+        let mut edges: Vec<&(String, String)> = match draw_relations.get(&rel.name) {
+            Some(edges) => edges.iter().collect(),
+            None => continue,
+        };
+        edges.sort();
+        let name = match graph.opts.edge_label(&rel.name) {
+            EdgeLabel::Abbreviate(abbrev) => abbrev,
+            _ => rel.name.clone(),
+        };
+        let mut lines: Vec<String> = Vec::new();
+        for (from, to) in edges {
+            if is_hidden_initial_rf(graph, &rel.name, from, to, &mutated_pas_event_names) {
+                continue;
+            }
+            if is_undirected(graph, rel, from, to) {
+                if from < to || !draw_relations[&rel.name].contains(&(to.clone(), from.clone())) {
+                    lines.push(format!("{} -{}- {}", ev_label(from), name, ev_label(to)))
+                }
+            } else {
+                lines.push(format!("{} -{}-> {}", ev_label(from), name, ev_label(to)))
+            }
+        }
+        if !lines.is_empty() {
+            writeln!(f)?;
+            for line in lines {
+                writeln!(f, "{}", line)?
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{accesses_graph, relation};
+    use super::*;
+
+    fn draw(g: &Graph) -> String {
+        let mut output = Vec::new();
+        draw_graph_ascii(g, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_ascii() {
+        // message passing, where thread 0 writes x then y and thread 1 reads y then x
+        let (x, y) = ("0x1000", "0x2000");
+        let mut g =
+            accesses_graph(&[((0, 0, 0), "W", x), ((0, 1, 0), "W", y), ((1, 0, 0), "R", y), ((1, 1, 0), "R", x)]);
+        g.relations = vec![
+            relation("rf", &[("R0_1_0", "R1_0_0"), ("IW", "R1_1_0")]),
+            relation("fr", &[("R1_1_0", "R0_0_0")]),
+            relation("po", &[]),
+            relation("ext", &[("R0_1_0", "R1_0_0"), ("R1_0_0", "R0_1_0")]),
+        ];
+        g.names.paddr_names.insert(0x1000, "x".to_string());
+        g.names.paddr_names.insert(0x2000, "y".to_string());
+
+        let expected = "                        IW: Initial State
+Thread 0                       Thread 1
+a: W x=0x1                     c: R y=0x1
+b: W y=0x1                     d: R x=0x1
+
+IW -rf-> d
+b -rf-> c
+
+d -fr-> a
+
+b -ext- c
+";
+        assert_eq!(draw(&g), expected);
+    }
+
+    #[test]
+    fn test_ascii_line() {
+        let mut line = Line::default();
+        line.place(2, "a");
+        line.place(3, "bcd");
+        line.place(4, "e");
+        line.place(10, "f");
+        assert_eq!(line.text, "  a bcd e f");
+    }
+}
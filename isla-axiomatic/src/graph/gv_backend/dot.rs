@@ -0,0 +1,364 @@
+//! Escaping for the strings we emit into graphviz dot files. Event
+//! names, relation names from cat files, and instruction text can all
+//! contain characters that are meaningful in dot syntax, so everything
+//! that did not come from a fixed string in the backend should go
+//! through one of these functions.
+
+use std::borrow::Cow;
+
+const KEYWORDS: &[&str] = &["node", "edge", "graph", "digraph", "subgraph", "strict"];
+
+fn is_plain_id(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => (),
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_') && !KEYWORDS.iter().any(|kw| kw.eq_ignore_ascii_case(s))
+}
+
+fn is_numeral(s: &str) -> bool {
+    let digits = s.strip_prefix('-').unwrap_or(s);
+    match digits.split_once('.') {
+        Some((whole, frac)) => {
+            !(whole.is_empty() && frac.is_empty())
+                && whole.chars().all(|c| c.is_ascii_digit())
+                && frac.chars().all(|c| c.is_ascii_digit())
+        }
+        None => !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()),
+    }
+}
+
+/// Quote a string for use in a double-quoted dot string, such as a
+/// plain (non-HTML) label. Newlines become centered line breaks, and
+/// other control characters are dropped.
+pub(super) fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if c.is_control() => (),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Quote a label which may span several lines, where each line is
+/// left-justified using the `\l` escape. Labels with a single line are
+/// quoted as by [quote], so they stay centered.
+pub(super) fn quote_left_justified(s: &str) -> String {
+    if !s.contains('\n') {
+        return quote(s);
+    }
+    let lines: Vec<String> = s
+        .lines()
+        .map(|line| {
+            let quoted = quote(line);
+            quoted[1..quoted.len() - 1].to_string()
+        })
+        .collect();
+    format!("\"{}\\l\"", lines.join("\\l"))
+}
+
+/// An identifier, such as a node name or an attribute value, which is
+/// left as-is when dot would accept it unquoted (so the common case of
+/// names like `R0_1_0` and colors like `wheat1` stays readable), and
+/// quoted otherwise.
+pub(super) fn id(s: &str) -> Cow<'_, str> {
+    if is_plain_id(s) || is_numeral(s) {
+        Cow::Borrowed(s)
+    } else {
+        Cow::Owned(quote(s))
+    }
+}
+
+/// Escape text for inclusion in an HTML-like label, which graphviz
+/// parses as XML. This is also used for SVG output.
+pub(super) fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c if c.is_control() && c != '\n' => (),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Eq)]
+    enum Token {
+        Id(String),
+        Punct(&'static str),
+    }
+
+    fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+        let mut tokens = Vec::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                // including the separators written by --graph-control-delimit
+                c if c.is_whitespace() => (),
+                '{' => tokens.push(Token::Punct("{")),
+                '}' => tokens.push(Token::Punct("}")),
+                '[' => tokens.push(Token::Punct("[")),
+                ']' => tokens.push(Token::Punct("]")),
+                ';' => tokens.push(Token::Punct(";")),
+                ',' => tokens.push(Token::Punct(",")),
+                '=' => tokens.push(Token::Punct("=")),
+                '-' if chars.peek() == Some(&'>') => {
+                    chars.next();
+                    tokens.push(Token::Punct("->"))
+                }
+                '"' => {
+                    let mut contents = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('"') => break,
+                            Some('\\') => match chars.next() {
+                                Some(c) => {
+                                    contents.push('\\');
+                                    contents.push(c)
+                                }
+                                None => return Err("unterminated escape".to_string()),
+                            },
+                            Some(c) => contents.push(c),
+                            None => return Err(format!("unterminated string in {:?}", s)),
+                        }
+                    }
+                    tokens.push(Token::Id(contents))
+                }
+                '<' => {
+                    let mut depth = 1;
+                    let mut contents = String::new();
+                    while depth > 0 {
+                        match chars.next() {
+                            Some('<') => depth += 1,
+                            Some('>') => depth -= 1,
+                            Some(c) => contents.push(c),
+                            None => return Err("unterminated HTML string".to_string()),
+                        }
+                    }
+                    tokens.push(Token::Id(contents))
+                }
+                c => {
+                    let mut word = c.to_string();
+                    while let Some(&c) = chars.peek() {
+                        if c.is_alphanumeric() || c == '_' || c == '.' || (c == '-' && word == "-") {
+                            word.push(c);
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                    if !is_plain_id(&word) && !is_numeral(&word) && !KEYWORDS.contains(&word.as_str()) {
+                        return Err(format!("invalid identifier '{}'", word));
+                    }
+                    tokens.push(Token::Id(word))
+                }
+            }
+        }
+        Ok(tokens)
+    }
+
+    struct Parser {
+        tokens: Vec<Token>,
+        pos: usize,
+    }
+
+    impl Parser {
+        fn peek(&self) -> Option<&Token> {
+            self.tokens.get(self.pos)
+        }
+
+        fn is_punct(&self, p: &str) -> bool {
+            matches!(self.peek(), Some(Token::Punct(q)) if *q == p)
+        }
+
+        fn expect_punct(&mut self, p: &str) -> Result<(), String> {
+            if self.is_punct(p) {
+                self.pos += 1;
+                Ok(())
+            } else {
+                Err(format!("expected '{}' at token {}, found {:?}", p, self.pos, self.peek()))
+            }
+        }
+
+        fn id(&mut self) -> Result<String, String> {
+            match self.peek() {
+                Some(Token::Id(id)) => {
+                    let id = id.clone();
+                    self.pos += 1;
+                    Ok(id)
+                }
+                tok => Err(format!("expected identifier at token {}, found {:?}", self.pos, tok)),
+            }
+        }
+
+        fn attr_list(&mut self) -> Result<(), String> {
+            while self.is_punct("[") {
+                self.pos += 1;
+                while !self.is_punct("]") {
+                    self.id()?;
+                    self.expect_punct("=")?;
+                    self.id()?;
+                    if self.is_punct(",") || self.is_punct(";") {
+                        self.pos += 1
+                    }
+                }
+                self.pos += 1
+            }
+            Ok(())
+        }
+
+        fn stmt_list(&mut self) -> Result<(), String> {
+            self.expect_punct("{")?;
+            while !self.is_punct("}") {
+                let id = self.id()?;
+                if id == "subgraph" {
+                    if !self.is_punct("{") {
+                        self.id()?;
+                    }
+                    self.stmt_list()?
+                } else if self.is_punct("=") {
+                    self.pos += 1;
+                    self.id()?;
+                } else {
+                    while self.is_punct("->") {
+                        self.pos += 1;
+                        self.id()?;
+                    }
+                    self.attr_list()?
+                }
+                if self.is_punct(";") {
+                    self.pos += 1
+                }
+            }
+            self.pos += 1;
+            Ok(())
+        }
+    }
+
+    /// A lightweight check that the output is syntactically valid dot
+    pub(in crate::graph::gv_backend) fn parse_dot(s: &str) -> Result<(), String> {
+        let mut parser = Parser { tokens: tokenize(s)?, pos: 0 };
+        if parser.id()? != "digraph" {
+            return Err("expected digraph".to_string());
+        }
+        if !parser.is_punct("{") {
+            parser.id()?;
+        }
+        parser.stmt_list()?;
+        match parser.peek() {
+            None => Ok(()),
+            Some(tok) => Err(format!("trailing token {:?}", tok)),
+        }
+    }
+
+    /// Deterministically generate strings made of characters that are
+    /// meaningful in dot, XML, and escaped strings
+    pub(in crate::graph::gv_backend) fn hostile_strings() -> Vec<String> {
+        const PIECES: &[&str] = &[
+            "\"", "\\", "<", ">", "{", "}", "[", "]", ";", ",", "=", "->", "--", "\n", "\r", "\t", "\0", " ", "&amp;",
+            "<b>", "</b>", "\\l", "\\\"", "node", "x", "0", "-1.5", "→", "#ff0000", "'", "//", "/*", "*/", "%",
+        ];
+        let mut strings: Vec<String> = PIECES.iter().map(|piece| piece.to_string()).collect();
+        strings.push(String::new());
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        for len in 1..=8 {
+            for _ in 0..32 {
+                let mut s = String::new();
+                for _ in 0..len {
+                    seed ^= seed << 13;
+                    seed ^= seed >> 7;
+                    seed ^= seed << 17;
+                    s.push_str(PIECES[(seed % PIECES.len() as u64) as usize])
+                }
+                strings.push(s)
+            }
+        }
+        strings
+    }
+
+    fn unquote(s: &str) -> String {
+        let mut unquoted = String::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('n') => unquoted.push('\n'),
+                    Some(c) => unquoted.push(c),
+                    None => unquoted.push('\\'),
+                },
+                c => unquoted.push(c),
+            }
+        }
+        unquoted
+    }
+
+    #[test]
+    fn test_quote_roundtrip() {
+        for s in hostile_strings() {
+            let tokens = tokenize(&quote(&s)).unwrap();
+            let expected: String = s.chars().filter(|c| !c.is_control() || *c == '\n').collect();
+            match tokens.as_slice() {
+                [Token::Id(contents)] => assert_eq!(unquote(contents), expected),
+                _ => panic!("{:?} did not quote to a single token: {:?}", s, tokens),
+            }
+        }
+    }
+
+    #[test]
+    fn test_quote_left_justified() {
+        assert_eq!(quote_left_justified("ldr x0"), "\"ldr x0\"");
+        assert_eq!(quote_left_justified("a: \"msr\nttbr0_el1\""), "\"a: \\\"msr\\lttbr0_el1\\\"\\l\"");
+        for s in hostile_strings() {
+            assert_eq!(tokenize(&quote_left_justified(&s)).unwrap().len(), 1, "{:?}", s)
+        }
+    }
+
+    #[test]
+    fn test_id() {
+        assert_eq!(id("R0_1_0"), "R0_1_0");
+        assert_eq!(id("wheat1"), "wheat1");
+        assert_eq!(id("0.5"), "0.5");
+        assert_eq!(id("#ff0000"), "\"#ff0000\"");
+        assert_eq!(id("node"), "\"node\"");
+        assert_eq!(id("po-loc"), "\"po-loc\"");
+        assert_eq!(id(""), "\"\"");
+        for s in hostile_strings() {
+            assert_eq!(tokenize(&id(&s)).unwrap().len(), 1, "{:?}", s)
+        }
+    }
+
+    #[test]
+    fn test_escape_html() {
+        for s in hostile_strings() {
+            let escaped = escape_html(&s);
+            assert!(!escaped.contains(['<', '>', '"']), "{:?}", escaped);
+            assert!(escaped.split('&').skip(1).all(|entity| entity.contains(';')), "{:?}", escaped)
+        }
+    }
+
+    #[test]
+    fn test_parse_dot() {
+        assert!(parse_dot("digraph Exec { a -> b [label=\" x \", color=red]; subgraph cluster0 { label = \"T\"; } }")
+            .is_ok());
+        assert!(parse_dot("digraph Exec { a -> b [label=\" x \" \", color=red]; }").is_err());
+        assert!(parse_dot("digraph Exec { a -> b [color=#ff0000]; }").is_err());
+        assert!(parse_dot("digraph Exec { a -> ; }").is_err());
+        assert!(parse_dot("digraph Exec { a }}").is_err());
+    }
+}
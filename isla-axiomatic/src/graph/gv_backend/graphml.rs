@@ -0,0 +1,190 @@
+//! A GraphML backend, for loading executions into graph tools such as
+//! yEd and Gephi. Event fields are written as GraphML data, and the
+//! positions computed by the graphviz layout are written using the
+//! yFiles extension, which tools that don't understand it ignore.
+
+use std::io;
+
+use super::*;
+
+/// The data keys written for each node, as (id, type)
+const NODE_KEYS: &[(&str, &str)] = &[
+    ("label", "string"),
+    ("thread", "int"),
+    ("po", "int"),
+    ("iio", "int"),
+    ("kind", "string"),
+    ("address", "string"),
+    ("value", "string"),
+];
+
+/// The kind of an event, using the same names as
+/// `EventPalette::KINDS` where there is one
+fn kind_name(kind: &GraphEventKind) -> &'static str {
+    match kind {
+        GraphEventKind::Ifetch => "ifetch",
+        GraphEventKind::ReadMem => "read",
+        GraphEventKind::WriteMem(WriteKind { to_translation_table_entry: Some(_) }) => "tte-write",
+        GraphEventKind::WriteMem(WriteKind { to_translation_table_entry: None }) => "write",
+        GraphEventKind::Translate(TranslateKind { stage: 1, .. }) => "translate-s1",
+        GraphEventKind::Translate(TranslateKind { stage: 2, .. }) => "translate-s2",
+        GraphEventKind::Translate(_) => "translate",
+        GraphEventKind::TranslateWalk => "translate-walk",
+        GraphEventKind::ReadReg => "read-reg",
+        GraphEventKind::WriteReg => "write-reg",
+        GraphEventKind::Barrier(_) => "barrier",
+        GraphEventKind::CacheOp => "cache-op",
+        GraphEventKind::Info => "other",
+    }
+}
+
+fn write_data(f: &mut dyn io::Write, key: &str, value: &str) -> io::Result<()> {
+    writeln!(f, "      <data key=\"{}\">{}</data>", key, dot::escape_html(value))
+}
+
+/// Draw a graph as GraphML, showing the same events and edges as
+/// [draw_graph_gv]. Each event node carries its thread, program
+/// order, intra-instruction order, kind, address, and value as data,
+/// and each edge the name of its relation. Symmetric relations are
+/// written as one directed edge each way.
+pub fn draw_graph_graphml(graph: &Graph, f: &mut dyn io::Write) -> io::Result<()> {
+    let (node_layout, mutated_pas_event_names) = drawn_layout(graph);
+    let scale = Scale::new(graph.opts.scale);
+
+    writeln!(f, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(
+        f,
+        "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xmlns:y=\"http://www.yworks.com/xml/graphml\" xsi:schemaLocation=\"http://graphml.graphdrawing.org/xmlns http://www.yworks.com/xml/schema/graphml/1.1/ygraphml.xsd\">"
+    )?;
+    for (key, ty) in NODE_KEYS {
+        writeln!(f, "  <key id=\"{}\" for=\"node\" attr.name=\"{}\" attr.type=\"{}\"/>", key, key, ty)?;
+    }
+    writeln!(f, "  <key id=\"relation\" for=\"edge\" attr.name=\"relation\" attr.type=\"string\"/>")?;
+    writeln!(f, "  <key id=\"nodegraphics\" for=\"node\" yfiles.type=\"nodegraphics\"/>")?;
+    writeln!(f, "  <graph id=\"G\" edgedefault=\"directed\">")?;
+
+    let mut nodes = node_layout.iter_nodes(true, false);
+    nodes.sort_by_key(|child| (program_order(child), child.unwrap_node().name()));
+    for child in nodes {
+        let pgn = child.unwrap_node();
+        writeln!(f, "    <node id=\"{}\">", dot::escape_html(&pgn.name()))?;
+        write_data(f, "label", &pgn.ev.label)?;
+        if let Some(ev) = pgn.ev.ev {
+            write_data(f, "thread", &ev.thread_id.to_string())?;
+            write_data(f, "po", &ev.po.to_string())?;
+            write_data(f, "iio", &ev.iio.to_string())?;
+            write_data(f, "kind", kind_name(&ev.event_kind))?;
+            if let Some(address) = ev.value.as_ref().and_then(|v| v.address.as_ref()) {
+                write_data(f, "address", address)?
+            }
+            if let Some(value) = ev.value.as_ref().and_then(|v| v.value.as_ref()) {
+                write_data(f, "value", value)?
+            }
+        }
+        if let Some((x, y)) = child.layout.pos {
+            let (w, h) = (pgn.compute_width() as i64, pgn.compute_height() as i64);
+            let shape = if pgn.style.node_shape == "oval" { "ellipse" } else { "rectangle" };
+            writeln!(
+                f,
+                "      <data key=\"nodegraphics\"><y:ShapeNode><y:Geometry x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/><y:NodeLabel>{}</y:NodeLabel><y:Shape type=\"{}\"/></y:ShapeNode></data>",
+                scale.points(x - w / 2),
+                scale.points(y - h / 2),
+                scale.points(w),
+                scale.points(h),
+                dot::escape_html(&pgn.ev.label),
+                shape
+            )?;
+        }
+        writeln!(f, "    </node>")?;
+    }
+
+    let draw_relations = relation_edges_to_draw(graph, &displayed_event_names(&node_layout));
+    let mut edge_id = 0;
+    for rel in &graph.relations {
+        let mut edges: Vec<&(String, String)> = match draw_relations.get(&rel.name) {
+            Some(edges) => edges.iter().collect(),
+            None => continue,
+        };
+        edges.sort();
+        for (from, to) in edges {
+            if is_hidden_initial_rf(graph, &rel.name, from, to, &mutated_pas_event_names) {
+                continue;
+            }
+            writeln!(
+                f,
+                "    <edge id=\"e{}\" source=\"{}\" target=\"{}\">",
+                edge_id,
+                dot::escape_html(from),
+                dot::escape_html(to)
+            )?;
+            write_data(f, "relation", &rel.name)?;
+            writeln!(f, "    </edge>")?;
+            edge_id += 1
+        }
+    }
+
+    writeln!(f, "  </graph>")?;
+    writeln!(f, "</graphml>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{fault_graph, relation};
+    use super::*;
+
+    fn draw(g: &Graph) -> String {
+        let mut output = Vec::new();
+        draw_graph_graphml(g, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_graphml() {
+        let mut g = fault_graph(&[(0, 0, 0), (0, 1, 0), (1, 0, 0)]);
+        g.relations = vec![relation("rf", &[("R0_0_0", "R1_0_0")]), relation("co", &[("IW", "R0_1_0")])];
+        let output = draw(&g);
+
+        assert!(output.starts_with("<?xml"));
+        assert!(output.trim_end().ends_with("</graphml>"));
+        assert!(output.contains("<node id=\"R1_0_0\">\n      <data key=\"label\">c0: fault: Fault</data>\n      <data key=\"thread\">1</data>\n      <data key=\"po\">0</data>\n      <data key=\"iio\">0</data>\n      <data key=\"kind\">barrier</data>"), "{}", output);
+        assert!(output.contains("<node id=\"IW\">"));
+        assert_eq!(output.matches("<node id=").count(), 4);
+        assert_eq!(output.matches("<y:Geometry").count(), 4);
+        assert!(output
+            .contains("<edge id=\"e0\" source=\"R0_0_0\" target=\"R1_0_0\">\n      <data key=\"relation\">rf</data>"));
+        assert!(output.contains("<edge id=\"e1\" source=\"IW\" target=\"R0_1_0\">"));
+    }
+
+    #[test]
+    fn test_graphml_hides_initial_state() {
+        let mut g = fault_graph(&[(0, 0, 0), (1, 0, 0)]);
+        g.relations = vec![relation("rf", &[("R0_0_0", "R1_0_0")])];
+        let output = draw(&g);
+        assert!(!output.contains("\"IW\""), "{}", output);
+        assert_eq!(output.matches("<edge ").count(), 1);
+    }
+
+    #[test]
+    fn test_graphml_hostile_strings() {
+        for s in dot::tests::hostile_strings() {
+            let mut g = fault_graph(&[(0, 0, 0), (1, 0, 0)]);
+            g.relations = vec![relation(&s, &[("R0_0_0", "R1_0_0")])];
+            for ev in g.events.values_mut() {
+                ev.opcode = s.clone();
+                ev.instr = Some(s.clone())
+            }
+            let output = draw(&g);
+            let texts = output.lines().filter_map(|line| {
+                let (_, text) = line.split_once("<data key=")?.1.split_once('>')?;
+                let text = match text.strip_prefix("<y:ShapeNode>") {
+                    Some(graphics) => graphics.split_once("<y:NodeLabel>")?.1,
+                    None => text,
+                };
+                Some(text.split_once("</")?.0)
+            });
+            for text in texts {
+                assert!(!text.contains(['<', '>', '"']), "{:?}", text)
+            }
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! An interactive HTML backend, for reviewing every candidate
+//! execution of a test in a browser. The events of each execution are
+//! placed at the positions computed by the graphviz layout and
+//! embedded as JSON along with the edges of each relation. A small
+//! inline script draws them as SVG, with a dropdown to pick the
+//! execution, a checkbox to toggle each relation, and pan and zoom with
+//! the mouse. The file has no external dependencies.
+
+use std::collections::HashSet;
+use std::io;
+
+use serde::Serialize;
+
+use super::svg::{positioned_nodes, svg_color};
+use super::*;
+
+const TEMPLATE: &str = include_str!("html_template.html");
+
+#[derive(Serialize)]
+struct HtmlNode {
+    id: String,
+    label: String,
+    /// shown as a tooltip when hovering over the node
+    title: String,
+    /// the centre of the node
+    x: i64,
+    y: i64,
+    width: i64,
+    height: i64,
+    fill: String,
+    oval: bool,
+}
+
+#[derive(Serialize)]
+struct HtmlEdge {
+    from: String,
+    to: String,
+    directed: bool,
+}
+
+#[derive(Serialize)]
+struct HtmlRelation {
+    name: String,
+    color: String,
+    line: String,
+    edges: Vec<HtmlEdge>,
+}
+
+#[derive(Serialize)]
+struct HtmlExecution {
+    title: String,
+    /// the initial view, as (x, y, width, height) in layout points
+    view_box: (i64, i64, i64, i64),
+    font_size: usize,
+    nodes: Vec<HtmlNode>,
+    relations: Vec<HtmlRelation>,
+}
+
+fn html_execution(title: String, graph: &Graph) -> HtmlExecution {
+    let (node_layout, mutated_pas_event_names) = drawn_layout(graph);
+
+    let mut children = Vec::new();
+    positioned_nodes(&node_layout, &mut children);
+    let mut nodes: Vec<HtmlNode> = children
+        .into_iter()
+        .filter_map(|(rc, child)| {
+            let (pgn, (x, y)) = match (&child.node, child.layout.pos) {
+                (GVGridNode::Node(pgn), Some(pos)) => (pgn, pos),
+                _ => return None,
+            };
+            let name = pgn.name();
+            let title = if name == "IW" {
+                pgn.ev.label.clone()
+            } else {
+                pgn.fmt_label_debug(&graph.opts, rc, &graph.names).replace('\\', "\"")
+            };
+            Some(HtmlNode {
+                id: name,
+                label: pgn.ev.label.clone(),
+                title,
+                x,
+                y,
+                width: pgn.compute_width() as i64,
+                height: pgn.compute_height() as i64,
+                fill: svg_color(&pgn.style.bg_color).to_string(),
+                oval: pgn.style.node_shape == "oval",
+            })
+        })
+        .collect();
+    nodes.sort_by(|n1, n2| n1.id.cmp(&n2.id));
+
+    let displayed: HashSet<String> = nodes.iter().map(|node| node.id.clone()).collect();
+    let draw_relations = relation_edges_to_draw(graph, &displayed);
+    let relations = graph
+        .relations
+        .iter()
+        .filter_map(|rel| {
+            let mut edges: Vec<&(String, String)> = draw_relations.get(&rel.name)?.iter().collect();
+            edges.sort();
+            let edges: Vec<HtmlEdge> = edges
+                .into_iter()
+                .filter(|(from, to)| !is_hidden_initial_rf(graph, &rel.name, from, to, &mutated_pas_event_names))
+                .map(|(from, to)| HtmlEdge {
+                    from: from.clone(),
+                    to: to.clone(),
+                    directed: !is_undirected(graph, rel, from, to),
+                })
+                .collect();
+            let line = graph.opts.relation_styles.get(&rel.name).map(|style| style.line).unwrap_or(LineStyle::Solid);
+            Some(HtmlRelation {
+                name: rel.name.clone(),
+                color: svg_color(relation_edge_style(&graph.opts, &rel.name).0).to_string(),
+                line: line.to_string(),
+                edges,
+            })
+        })
+        .collect();
+
+    let ((left, top), (right, bottom)) = node_layout.bounding_box(true).unwrap_or(((0, 0), (0, 0)));
+    let margin = POINTS_PER_INCH as i64;
+    HtmlExecution {
+        title,
+        view_box: (left - margin, top - margin, right - left + 2 * margin, bottom - top + 2 * margin),
+        font_size: FONTSIZE,
+        nodes,
+        relations,
+    }
+}
+
+/// Draw the graphs of several executions of a test into one
+/// self-contained HTML page, using the same layout as [draw_graph_gv].
+/// Each execution is given by its number and its outcome (e.g.
+/// `allow`), which are shown in the dropdown used to pick it.
+pub fn draw_graphs_html<'g>(
+    f: &mut dyn io::Write,
+    title: &str,
+    graphs: impl IntoIterator<Item = (usize, &'g str, &'g Graph)>,
+) -> io::Result<()> {
+    let executions: Vec<HtmlExecution> = graphs
+        .into_iter()
+        .map(|(i, state, graph)| html_execution(format!("Execution #{} ({})", i, state), graph))
+        .collect();
+    // `<` only appears within JSON strings, where escaping it keeps the
+    // script from being closed early by a `</script>` in an event label
+    let json = serde_json::to_string(&executions).map_err(io::Error::other)?.replace('<', "\\u003c");
+
+    let (head, tail) = TEMPLATE.split_once("{{EXECUTIONS}}").expect("HTML template has no executions placeholder");
+    write!(f, "{}{}{}", head.replace("{{TITLE}}", &dot::escape_html(title)), json, tail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{fault_graph, relation};
+    use super::*;
+
+    fn draw(graphs: &[(usize, &str, &Graph)]) -> String {
+        let mut output = Vec::new();
+        draw_graphs_html(&mut output, "MP", graphs.iter().copied()).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_html() {
+        let mut g1 = fault_graph(&[(0, 0, 0), (0, 1, 0), (1, 0, 0)]);
+        g1.relations = vec![relation("rf", &[("R0_0_0", "R1_0_0")]), relation("co", &[("R0_0_0", "R0_1_0")])];
+        let mut g2 = fault_graph(&[(0, 0, 0), (1, 0, 0)]);
+        g2.relations = vec![relation("fr", &[("R1_0_0", "R0_0_0")])];
+        let output = draw(&[(1, "allow", &g1), (2, "forbid", &g2)]);
+
+        assert!(output.starts_with("<!DOCTYPE html>"));
+        assert!(output.trim_end().ends_with("</html>"));
+        assert!(output.contains("<title>MP</title>"));
+        assert!(!output.contains("{{"));
+        // the page loads nothing from elsewhere
+        assert!(!output.contains("src=") && !output.contains("href="));
+
+        for id in ["R0_0_0", "R0_1_0", "R1_0_0"] {
+            assert!(output.contains(&format!("\"id\":\"{}\"", id)), "{}", id)
+        }
+        for rel in ["rf", "co", "fr"] {
+            assert!(output.contains(&format!("\"name\":\"{}\"", rel)), "{}", rel)
+        }
+        assert!(output.contains("\"title\":\"Execution #1 (allow)\""));
+        assert!(output.contains("\"title\":\"Execution #2 (forbid)\""));
+        assert!(output.contains("{\"from\":\"R0_0_0\",\"to\":\"R1_0_0\",\"directed\":true}"));
+        assert!(output.contains("\"color\":\"crimson\""));
+    }
+
+    #[test]
+    fn test_html_hostile_strings() {
+        for s in dot::tests::hostile_strings() {
+            let mut g = fault_graph(&[(0, 0, 0), (1, 0, 0)]);
+            g.relations = vec![relation(&s, &[("R0_0_0", "R1_0_0")])];
+            for ev in g.events.values_mut() {
+                ev.opcode = s.clone();
+                ev.instr = Some(s.clone())
+            }
+            let output = draw(&[(1, "allow", &g)]);
+            let script = output.split_once("const EXECUTIONS = ").unwrap().1;
+            let json = script.split_once(";\nconst SVG").unwrap().0;
+            assert!(!json.contains('<'), "{}", json);
+            let executions: serde_json::Value = serde_json::from_str(json).unwrap();
+            assert_eq!(executions[0]["relations"][0]["name"], serde_json::Value::String(s.clone()));
+        }
+    }
+}
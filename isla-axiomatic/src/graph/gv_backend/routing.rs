@@ -0,0 +1,191 @@
+//! Routing edges around the nodes in their way. Edges are normally
+//! drawn as straight lines between the nodes they connect, which can
+//! pass straight through the events in between, especially for long
+//! edges between threads. For those we look for an orthogonal detour
+//! through one of the gaps between the columns (or rows) of nodes,
+//! which include the gaps between the thread clusters.
+//!
+//! Everything here is in layout points, with y increasing downwards.
+
+pub(super) type Point = (i64, i64);
+
+/// An axis-aligned box, such as the shape of a node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Rect {
+    pub left: i64,
+    pub top: i64,
+    pub right: i64,
+    pub bottom: i64,
+}
+
+impl Rect {
+    /// the box of the given size around a centre point
+    pub(super) fn around((x, y): Point, (w, h): (usize, usize)) -> Self {
+        let (hw, hh) = (w as i64 / 2, h as i64 / 2);
+        Rect { left: x - hw, top: y - hh, right: x + hw, bottom: y + hh }
+    }
+
+    pub(super) fn centre(&self) -> Point {
+        ((self.left + self.right) / 2, (self.top + self.bottom) / 2)
+    }
+
+    /// The point where the line from the centre of the box towards
+    /// `to` crosses the edge of the box
+    pub(super) fn clip(&self, to: Point) -> Point {
+        let (cx, cy) = self.centre();
+        let (dx, dy) = ((to.0 - cx) as f64, (to.1 - cy) as f64);
+        if dx == 0.0 && dy == 0.0 {
+            return (cx, cy);
+        }
+        let (hw, hh) = ((self.right - self.left) as f64 / 2.0, (self.bottom - self.top) as f64 / 2.0);
+        let t = f64::min(
+            if dx == 0.0 { f64::INFINITY } else { hw / dx.abs() },
+            if dy == 0.0 { f64::INFINITY } else { hh / dy.abs() },
+        );
+        (cx + (dx * t).round() as i64, cy + (dy * t).round() as i64)
+    }
+}
+
+/// Whether the segment from `p` to `q` passes through the inside of a
+/// box. Segments which only touch its edges or corners do not.
+pub(super) fn segment_intersects(p: Point, q: Point, r: &Rect) -> bool {
+    // Liang-Barsky clipping of the segment against the box
+    let (dx, dy) = ((q.0 - p.0) as f64, (q.1 - p.1) as f64);
+    let (mut t0, mut t1) = (0.0_f64, 1.0_f64);
+    let bounds = [
+        (-dx, (p.0 - r.left) as f64),
+        (dx, (r.right - p.0) as f64),
+        (-dy, (p.1 - r.top) as f64),
+        (dy, (r.bottom - p.1) as f64),
+    ];
+    for (pk, qk) in bounds {
+        if pk == 0.0 {
+            // parallel to this edge, and outside of or along it
+            if qk <= 0.0 {
+                return false;
+            }
+        } else {
+            let t = qk / pk;
+            if pk < 0.0 {
+                t0 = t0.max(t)
+            } else {
+                t1 = t1.min(t)
+            }
+        }
+    }
+    t0 < t1
+}
+
+/// The coordinates along one axis which are clear of every interval:
+/// the middle of each gap between them, and a lane `margin` beyond
+/// each end
+fn lanes(mut intervals: Vec<(i64, i64)>, margin: i64) -> Vec<i64> {
+    intervals.sort_unstable();
+    let mut merged: Vec<(i64, i64)> = Vec::new();
+    for (lo, hi) in intervals {
+        match merged.last_mut() {
+            Some((_, last_hi)) if lo <= *last_hi => *last_hi = i64::max(*last_hi, hi),
+            _ => merged.push((lo, hi)),
+        }
+    }
+
+    let (first, last) = match (merged.first(), merged.last()) {
+        (Some(first), Some(last)) => (first.0, last.1),
+        _ => return Vec::new(),
+    };
+    let mut lanes = vec![first - margin];
+    lanes.extend(merged.windows(2).map(|pair| (pair[0].1 + pair[1].0) / 2));
+    lanes.push(last + margin);
+    lanes
+}
+
+fn is_clear(path: &[Point], obstacles: &[&Rect]) -> bool {
+    path.windows(2).all(|seg| obstacles.iter().all(|r| !segment_intersects(seg[0], seg[1], r)))
+}
+
+fn path_length(path: &[Point]) -> i64 {
+    path.windows(2).map(|seg| (seg[1].0 - seg[0].0).abs() + (seg[1].1 - seg[0].1).abs()).sum()
+}
+
+/// Find a route for an edge between two boxes around any of the
+/// obstacles in the way. Returns `None` if the straight line between
+/// the boxes is already clear, or if there is no detour through a
+/// single gap that avoids every obstacle. Otherwise the route is a
+/// list of points starting and ending on the edges of the two boxes.
+pub(super) fn route(from: &Rect, to: &Rect, obstacles: &[Rect], margin: i64) -> Option<Vec<Point>> {
+    let obstacles: Vec<&Rect> = obstacles.iter().filter(|r| *r != from && *r != to).collect();
+    let (start, end) = (from.centre(), to.centre());
+    if is_clear(&[start, end], &obstacles) {
+        return None;
+    }
+
+    let x_lanes = lanes(obstacles.iter().chain([&from, &to]).map(|r| (r.left, r.right)).collect(), margin);
+    let y_lanes = lanes(obstacles.iter().chain([&from, &to]).map(|r| (r.top, r.bottom)).collect(), margin);
+    let detours = x_lanes
+        .into_iter()
+        .map(|x| [start, (x, start.1), (x, end.1), end])
+        .chain(y_lanes.into_iter().map(|y| [start, (start.0, y), (end.0, y), end]));
+
+    let mut best = detours.filter(|path| is_clear(path, &obstacles)).min_by_key(|path| path_length(path))?;
+    best[0] = from.clip(best[1]);
+    best[3] = to.clip(best[2]);
+    Some(best.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(left: i64, top: i64, right: i64, bottom: i64) -> Rect {
+        Rect { left, top, right, bottom }
+    }
+
+    #[test]
+    fn test_segment_intersects() {
+        let r = rect(0, 0, 10, 10);
+        assert!(segment_intersects((-5, 5), (15, 5), &r));
+        assert!(segment_intersects((-5, -5), (15, 15), &r));
+        assert!(segment_intersects((5, 5), (20, 20), &r));
+        assert!(!segment_intersects((-5, 5), (-1, 5), &r));
+        assert!(!segment_intersects((-5, -5), (15, -5), &r));
+        // touching an edge or a corner isn't passing through
+        assert!(!segment_intersects((-5, 0), (15, 0), &r));
+        assert!(!segment_intersects((-5, 5), (5, -5), &r));
+        assert!(!segment_intersects((10, -5), (10, 15), &r));
+    }
+
+    #[test]
+    fn test_clip() {
+        let r = rect(-10, -5, 10, 5);
+        assert_eq!(r.clip((100, 0)), (10, 0));
+        assert_eq!(r.clip((0, -100)), (0, -5));
+        assert_eq!(r.clip((20, 20)), (5, 5));
+        assert_eq!(r.clip((0, 0)), (0, 0));
+    }
+
+    #[test]
+    fn test_lanes() {
+        assert_eq!(lanes(vec![(20, 30), (0, 10), (5, 15)], 4), vec![-4, 17, 34]);
+        assert_eq!(lanes(vec![(0, 10)], 4), vec![-4, 14]);
+        assert!(lanes(vec![], 4).is_empty());
+    }
+
+    #[test]
+    fn test_route() {
+        // three nodes in a row, where the middle one is in the way
+        let (a, b, c) = (rect(0, 0, 10, 10), rect(20, 0, 30, 10), rect(40, 0, 50, 10));
+        assert_eq!(route(&a, &b, &[a, b, c], 6), None);
+        assert_eq!(route(&a, &c, &[a, b, c], 6), Some(vec![(5, 0), (5, -6), (45, -6), (45, 0)]));
+
+        // a node in the way of a diagonal edge is passed through the gap beside it
+        let (a, b, c) = (rect(0, 0, 10, 10), rect(20, 20, 30, 30), rect(40, 40, 50, 50));
+        let path = route(&a, &c, &[a, b, c], 6).unwrap();
+        assert_eq!(path, vec![(10, 5), (15, 5), (15, 45), (40, 45)]);
+        assert!(is_clear(&path, &[&b]));
+
+        // nowhere to go
+        let walls = [rect(20, -100, 30, 100), rect(-100, -30, 100, -20), rect(-100, 30, 100, 40)];
+        let (a, c) = (rect(0, 0, 10, 10), rect(40, 0, 50, 10));
+        assert_eq!(route(&a, &c, &walls, 6), None);
+    }
+}
@@ -0,0 +1,269 @@
+//! A standalone SVG backend, which places nodes at the positions
+//! computed by the graphviz layout so the output needs no external
+//! tools to view. Each node and edge carries a `<title>` element, which
+//! browsers display as a tooltip when hovering over it.
+
+use std::collections::{BTreeSet, HashSet};
+use std::io;
+
+use super::*;
+
+/// space (in points) left around the edge of the image
+const MARGIN: i64 = 36;
+
+/// Graphviz accepts the X11 color scheme, which has numbered variants
+/// like `wheat1` that are not valid CSS colors, so we fall back to the
+/// base color name for those
+pub(super) fn svg_color(color: &str) -> &str {
+    if color.starts_with('#') {
+        color
+    } else {
+        color.trim_end_matches(|c: char| c.is_ascii_digit())
+    }
+}
+
+/// The point where the line from the centre of a box of the given
+/// half-width and half-height towards `to` crosses the edge of the box
+fn clip_to_box(centre: (i64, i64), (hw, hh): (f64, f64), to: (i64, i64)) -> (f64, f64) {
+    let (dx, dy) = ((to.0 - centre.0) as f64, (to.1 - centre.1) as f64);
+    if dx == 0.0 && dy == 0.0 {
+        return (centre.0 as f64, centre.1 as f64);
+    }
+    let scale = f64::min(
+        if dx == 0.0 { f64::INFINITY } else { hw / dx.abs() },
+        if dy == 0.0 { f64::INFINITY } else { hh / dy.abs() },
+    );
+    (centre.0 as f64 + dx * scale, centre.1 as f64 + dy * scale)
+}
+
+/// Collect every node in a layout along with its (row, column) within
+/// its enclosing cluster
+pub(super) fn positioned_nodes<'a, 'ev>(
+    layout: &'a GVGraphLayout<'ev>,
+    nodes: &mut Vec<((usize, usize), &'a GVGridChild<'ev>)>,
+) {
+    for (rc, child) in layout.children.iter() {
+        if !child.layout.show {
+            continue;
+        }
+        match &child.node {
+            GVGridNode::Node(_) => nodes.push((*rc, child)),
+            GVGridNode::SubCluster(cluster) => positioned_nodes(cluster, nodes),
+        }
+    }
+}
+
+struct SvgNode<'a> {
+    name: String,
+    centre: (i64, i64),
+    half_size: (f64, f64),
+    child: &'a GVGridChild<'a>,
+    rc: (usize, usize),
+}
+
+/// Draw a graph as a self-contained SVG image, using the same layout
+/// as [draw_graph_gv]. Hovering over an event shows its debug label
+/// (opcode, address, value, and translation stage), and hovering over
+/// an edge shows the name of its relation.
+pub fn draw_graph_svg(graph: &Graph, f: &mut dyn io::Write) -> io::Result<()> {
+    let node_layout = produce_node_layout(graph, &graph.litmus_opts, &graph.opts, HashSet::new(), true);
+
+    let mut children = Vec::new();
+    positioned_nodes(&node_layout, &mut children);
+    let mut nodes: Vec<SvgNode<'_>> = children
+        .into_iter()
+        .filter_map(|(rc, child)| match (&child.node, child.layout.pos) {
+            (GVGridNode::Node(pgn), Some(centre)) => Some(SvgNode {
+                name: pgn.name(),
+                centre,
+                half_size: (pgn.compute_width() as f64 / 2.0, pgn.compute_height() as f64 / 2.0),
+                child,
+                rc,
+            }),
+            _ => None,
+        })
+        .collect();
+    nodes.sort_by(|n1, n2| n1.name.cmp(&n2.name));
+
+    let ((left, top), (right, bottom)) = node_layout.bounding_box(true).unwrap_or(((0, 0), (0, 0)));
+    // leave room for the thread boxes, which extend past the nodes
+    let wiggle = (POINTS_PER_INCH / 2.0) as i64;
+    let (origin_x, origin_y) = (left - wiggle - MARGIN, top - wiggle - MARGIN - FONTSIZE as i64);
+    let (width, height) = (right - origin_x + wiggle + MARGIN, bottom - origin_y + wiggle + MARGIN);
+
+    // the scale only changes the size of the image, as everything
+    // inside it is drawn in layout points within the view box
+    let scale = Scale::new(graph.opts.scale);
+    writeln!(
+        f,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"{} {} {} {}\" font-family=\"monospace\" font-size=\"{}\">",
+        scale.points(width),
+        scale.points(height),
+        origin_x,
+        origin_y,
+        width,
+        height,
+        FONTSIZE
+    )?;
+
+    let mut edges: Vec<(&str, &str, &str)> = Vec::new();
+    let displayed: HashSet<&str> = nodes.iter().map(|node| node.name.as_str()).collect();
+    for rel in &graph.relations {
+        let mut rel_edges: Vec<&(String, String)> = rel
+            .edges
+            .iter()
+            .filter(|(from, to)| displayed.contains(from.as_str()) && displayed.contains(to.as_str()))
+            .collect();
+        rel_edges.sort();
+        for (from, to) in rel_edges {
+            edges.push((&rel.name, from, to))
+        }
+    }
+
+    // one arrowhead marker per edge color, as markers cannot inherit the stroke of their line
+    // (identified by index, as colors given in hex are not valid ids)
+    let colors: Vec<&str> = edges
+        .iter()
+        .map(|(rel, _, _)| svg_color(relation_edge_style(&graph.opts, rel).0))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    writeln!(f, "<defs>")?;
+    for (i, color) in colors.iter().enumerate() {
+        writeln!(
+            f,
+            "  <marker id=\"arrow-{}\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" markerWidth=\"6\" markerHeight=\"6\" orient=\"auto\"><path d=\"M 0 0 L 10 5 L 0 10 z\" fill=\"{}\"/></marker>",
+            i, color
+        )?;
+    }
+    writeln!(f, "</defs>")?;
+
+    if let Some(GVGridChild { node: GVGridNode::SubCluster(threads), .. }) =
+        node_layout.children.get(&threads_addr(graph.opts.orientation)).filter(|_| graph.opts.draw_thread_boxes)
+    {
+        let mut threads: Vec<_> = threads.children.iter().collect();
+        threads.sort_by_key(|(rc, _)| **rc);
+        for (tid, (_, thread)) in threads.into_iter().enumerate() {
+            if let GVGridNode::SubCluster(cluster) = &thread.node {
+                if let Some(((x1, y1), (x2, y2))) = cluster.bounding_box(true) {
+                    writeln!(
+                        f,
+                        "<g class=\"thread\"><rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"black\" stroke-dasharray=\"12,8\"/><text x=\"{}\" y=\"{}\">Thread {}</text></g>",
+                        x1 - wiggle,
+                        y1 - wiggle,
+                        x2 - x1 + 2 * wiggle,
+                        y2 - y1 + 2 * wiggle,
+                        x1 - wiggle,
+                        y1 - wiggle - FONTSIZE as i64 / 4,
+                        tid
+                    )?;
+                }
+            }
+        }
+    }
+
+    for (rel, from, to) in edges {
+        let (from, to) = match (nodes.iter().find(|n| n.name == from), nodes.iter().find(|n| n.name == to)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => continue,
+        };
+        let (x1, y1) = clip_to_box(from.centre, from.half_size, to.centre);
+        let (x2, y2) = clip_to_box(to.centre, to.half_size, from.centre);
+        let color = svg_color(relation_edge_style(&graph.opts, rel).0);
+        writeln!(
+            f,
+            "<g class=\"edge\"><title>{}</title><line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"{}\" stroke-width=\"3\" marker-end=\"url(#arrow-{})\"/></g>",
+            dot::escape_html(rel),
+            x1,
+            y1,
+            x2,
+            y2,
+            color,
+            colors.iter().position(|c| *c == color).unwrap()
+        )?;
+    }
+
+    for node in &nodes {
+        let pgn = node.child.unwrap_node();
+        let title = if node.name == "IW" {
+            pgn.ev.label.clone()
+        } else {
+            // the debug label escapes the instruction for graphviz, which we don't need
+            pgn.fmt_label_debug(&graph.opts, node.rc, &graph.names).replace('\\', "\"")
+        };
+        let dashes = if pgn.style.node_style.contains("dashed") { " stroke-dasharray=\"12,8\"" } else { "" };
+        let stroke_width = if pgn.style.node_style.contains("bold") { 4 } else { 2 };
+        let (x, y) = node.centre;
+        let (hw, hh) = node.half_size;
+        let shape = if pgn.style.node_shape == "oval" {
+            format!("<ellipse cx=\"{}\" cy=\"{}\" rx=\"{}\" ry=\"{}\"", x, y, hw, hh)
+        } else {
+            format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"",
+                x as f64 - hw,
+                y as f64 - hh,
+                hw * 2.0,
+                hh * 2.0
+            )
+        };
+        writeln!(
+            f,
+            "<g class=\"node\" id=\"{}\"><title>{}</title>{} fill=\"{}\" stroke=\"black\" stroke-width=\"{}\"{}/><text x=\"{}\" y=\"{}\" text-anchor=\"middle\" dominant-baseline=\"central\">{}</text></g>",
+            dot::escape_html(&node.name),
+            dot::escape_html(&title),
+            shape,
+            svg_color(&pgn.style.bg_color),
+            stroke_width,
+            dashes,
+            x,
+            y,
+            dot::escape_html(&pgn.ev.label)
+        )?;
+    }
+
+    writeln!(f, "</svg>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::fault_graph;
+    use super::*;
+
+    #[test]
+    fn test_svg_titles() {
+        let mut g = fault_graph(&[(0, 0, 0), (0, 1, 0), (1, 0, 0)]);
+        let edges: HashSet<(String, String)> = [("R0_0_0".to_string(), "R1_0_0".to_string())].into_iter().collect();
+        g.relations = vec![GraphRelation {
+            name: "rf".to_string(),
+            ty: RelType { trans: RelTransType::Normal, preferred: Vec::new() },
+            edges: edges.clone(),
+            all_edges: edges,
+        }];
+
+        let mut output = Vec::new();
+        draw_graph_svg(&g, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(output.trim_end().ends_with("</svg>"));
+        assert!(output.contains("<g class=\"node\" id=\"R0_0_0\"><title>R0_0_0 @ (0, 0): &quot;fault&quot;</title>"));
+        assert!(output.contains("<g class=\"node\" id=\"R1_0_0\">"));
+        assert!(output.contains("<g class=\"edge\"><title>rf</title>"));
+        assert!(output.contains("stroke=\"crimson\""));
+        assert_eq!(output.matches("<g class=\"thread\">").count(), 2);
+    }
+
+    #[test]
+    fn test_clip_to_box() {
+        assert_eq!(clip_to_box((0, 0), (10.0, 5.0), (100, 0)), (10.0, 0.0));
+        assert_eq!(clip_to_box((0, 0), (10.0, 5.0), (0, -100)), (0.0, -5.0));
+        assert_eq!(clip_to_box((0, 0), (10.0, 5.0), (20, 20)), (5.0, 5.0));
+    }
+
+    #[test]
+    fn test_svg_color() {
+        assert_eq!(svg_color("wheat1"), "wheat");
+        assert_eq!(svg_color("#ff0000"), "#ff0000");
+        assert_eq!(svg_color("white"), "white");
+    }
+}
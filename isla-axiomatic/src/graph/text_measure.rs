@@ -0,0 +1,223 @@
+//! Measuring the text in node labels, which decides how large each
+//! node is drawn and so how much room the layout leaves for it.
+//! Backends which know the font their output is rendered in can
+//! supply their own [TextMeasure] through `GraphOpts::text_measure`.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+
+/// A way of measuring (in points) lines of label text at a given font
+/// size. The lines never contain markup, see [strip_markup].
+pub trait TextMeasure: fmt::Debug + Send + Sync {
+    /// the width of a single line of text
+    fn line_width(&self, line: &str, font_size: usize) -> usize;
+
+    /// the distance between the baselines of consecutive lines
+    fn line_height(&self, font_size: usize) -> usize {
+        font_size
+    }
+}
+
+/// The size (in points) of the text of a label, which may span
+/// several lines and contain graphviz HTML-like markup, as the width of
+/// its longest line and the height of all its lines
+pub fn measure_label(measure: &dyn TextMeasure, label: &str, font_size: usize) -> (usize, usize) {
+    let text = strip_markup(label);
+    let width = text.lines().map(|line| measure.line_width(line, font_size)).max().unwrap_or(0);
+    (width, measure.line_height(font_size) * text.lines().count())
+}
+
+/// Estimates every character as three fifths of the font size wide,
+/// which fits a monospace font but overestimates narrow glyphs in a
+/// proportional one
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HeuristicMeasure;
+
+impl TextMeasure for HeuristicMeasure {
+    fn line_width(&self, line: &str, font_size: usize) -> usize {
+        (font_size * 3 / 5) * line.chars().count()
+    }
+}
+
+/// Measures text using the advance width of each character in a font,
+/// given in thousandths of the font size as in an AFM file
+#[derive(Debug, Clone)]
+pub struct FontMetrics {
+    widths: HashMap<char, u32>,
+    /// used for characters the font has no width for
+    default_width: u32,
+}
+
+/// The widths of the printable ASCII characters (from space to `~`)
+/// in Helvetica, from its Adobe font metrics
+const HELVETICA_WIDTHS: [u32; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556, 556, 556, 556, 556,
+    556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833,
+    722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556,
+    556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334,
+    260, 334, 584,
+];
+
+impl FontMetrics {
+    pub fn new(widths: HashMap<char, u32>, default_width: u32) -> Self {
+        FontMetrics { widths, default_width }
+    }
+
+    /// Metrics for Helvetica, which graphviz (and most SVG viewers)
+    /// substitute for sans-serif fonts they can't find
+    pub fn helvetica() -> Self {
+        let widths = (' '..='~').zip(HELVETICA_WIDTHS.iter().copied()).collect();
+        FontMetrics::new(widths, 556)
+    }
+
+    /// Read the character widths from the `CharMetrics` section of an
+    /// Adobe font metrics file, where each line looks like
+    /// `C 65 ; WX 667 ; N A ; B 14 0 654 718 ;`. Characters without a
+    /// code (`C -1`) are not in the standard encoding and are skipped.
+    pub fn from_afm(contents: &str) -> Result<Self, String> {
+        let mut widths = HashMap::new();
+        for line in contents.lines().filter(|line| line.starts_with("C ")) {
+            let mut code = None;
+            let mut width = None;
+            for field in line.split(';') {
+                match field.split_whitespace().collect::<Vec<_>>()[..] {
+                    ["C", c] => code = Some(c.parse::<i32>().map_err(|e| format!("bad code in '{}': {}", line, e))?),
+                    ["WX", w] => width = Some(w.parse::<u32>().map_err(|e| format!("bad width in '{}': {}", line, e))?),
+                    _ => (),
+                }
+            }
+            match (code, width) {
+                (Some(code), Some(width)) => {
+                    if let Some(c) = u32::try_from(code).ok().and_then(char::from_u32) {
+                        widths.insert(c, width);
+                    }
+                }
+                _ => return Err(format!("character metrics '{}' must give a code (C) and width (WX)", line)),
+            }
+        }
+        if widths.is_empty() {
+            return Err("no character metrics found".to_string());
+        }
+        let default_width = widths.get(&'0').copied().unwrap_or_else(|| widths.values().max().copied().unwrap_or(0));
+        Ok(FontMetrics::new(widths, default_width))
+    }
+}
+
+impl TextMeasure for FontMetrics {
+    fn line_width(&self, line: &str, font_size: usize) -> usize {
+        let thousandths: usize =
+            line.chars().map(|c| *self.widths.get(&c).unwrap_or(&self.default_width) as usize).sum();
+        (thousandths * font_size).div_ceil(1000)
+    }
+}
+
+/// The text of a label with any graphviz HTML-like markup removed,
+/// leaving what is actually drawn. Tags such as `<b>` are dropped,
+/// `<br/>` becomes a line break, and character entities are decoded.
+/// A `<` which does not start a tag is kept as it is.
+pub fn strip_markup(label: &str) -> Cow<'_, str> {
+    if !label.contains(['<', '&']) {
+        return Cow::Borrowed(label);
+    }
+    let mut text = String::with_capacity(label.len());
+    let mut rest = label;
+    while let Some(i) = rest.find(['<', '&']) {
+        text.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if rest.starts_with('<') {
+            let is_tag = rest[1..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '/');
+            match rest.find('>').filter(|_| is_tag) {
+                Some(end) => {
+                    let tag = rest[1..end].trim_start_matches('/').trim_end_matches('/').trim();
+                    if tag.eq_ignore_ascii_case("br") || tag.to_ascii_lowercase().starts_with("br ") {
+                        text.push('\n')
+                    }
+                    rest = &rest[end + 1..]
+                }
+                None => {
+                    text.push('<');
+                    rest = &rest[1..]
+                }
+            }
+        } else {
+            let entity =
+                rest.find(';').filter(|end| *end <= 8).and_then(|end| Some((decode_entity(&rest[1..end])?, end)));
+            match entity {
+                Some((c, end)) => {
+                    text.push(c);
+                    rest = &rest[end + 1..]
+                }
+                None => {
+                    text.push('&');
+                    rest = &rest[1..]
+                }
+            }
+        }
+    }
+    text.push_str(rest);
+    Cow::Owned(text)
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    match entity {
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "amp" => Some('&'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some(' '),
+        _ => {
+            let code = entity.strip_prefix('#')?;
+            let code = match code.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => code.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_markup() {
+        assert_eq!(strip_markup("a: W x=1"), "a: W x=1");
+        assert!(matches!(strip_markup("a: W x=1"), Cow::Borrowed(_)));
+        assert_eq!(strip_markup("<b>a</b>: W <font color=\"red\">x</font>=1"), "a: W x=1");
+        assert_eq!(strip_markup("a<br/>b<BR ALIGN=\"LEFT\"/>c"), "a\nb\nc");
+        assert_eq!(strip_markup("x &lt; y &amp;&amp; z&#62;0"), "x < y && z>0");
+        assert_eq!(strip_markup("x < 1 & y <2"), "x < 1 & y <2");
+    }
+
+    #[test]
+    fn test_measure_markup_label() {
+        let plain = measure_label(&HeuristicMeasure, "a: W x=1", 10);
+        assert_eq!(plain, (6 * 8, 10));
+        // the markup takes up no room, only the text it wraps
+        assert_eq!(measure_label(&HeuristicMeasure, "<b>a</b>: W <i>x</i>=1", 10), plain);
+        assert_eq!(measure_label(&HeuristicMeasure, "a: W<br/>x=1", 10), (6 * 4, 20));
+        assert_eq!(measure_label(&HeuristicMeasure, "x &lt; 1", 10).0, 6 * 5);
+    }
+
+    #[test]
+    fn test_font_metrics() {
+        let helvetica = FontMetrics::helvetica();
+        assert_eq!(helvetica.line_width("i", 1000), 222);
+        assert_eq!(helvetica.line_width("W", 1000), 944);
+        assert_eq!(helvetica.line_width("iiii", 10), 9);
+        // narrow glyphs are narrower than the heuristic guesses
+        assert!(helvetica.line_width("ill", 44) < HeuristicMeasure.line_width("ill", 44));
+        assert_eq!(measure_label(&helvetica, "<b>il</b>", 1000), measure_label(&helvetica, "il", 1000));
+
+        let afm = "StartCharMetrics 2\nC 32 ; WX 250 ; N space ; B 0 0 0 0 ;\nC 48 ; WX 500 ; N zero ;\nC -1 ; WX 300 ; N foo ;\nEndCharMetrics\n";
+        let metrics = FontMetrics::from_afm(afm).unwrap();
+        assert_eq!(metrics.line_width("0 0", 1000), 1250);
+        // characters missing from the font are as wide as a digit
+        assert_eq!(metrics.line_width("z", 1000), 500);
+        assert!(FontMetrics::from_afm("C 32 ; N space ;").is_err());
+        assert!(FontMetrics::from_afm("").is_err());
+    }
+}
@@ -44,10 +44,13 @@ lalrpop_mod!(
 );
 
 pub mod axiomatic;
+pub mod candidates;
 pub mod footprint_analysis;
 pub mod graph;
 pub mod litmus;
+pub mod multi_access;
 pub mod page_table;
+pub mod relation_stats;
 pub mod run_litmus;
 pub mod sandbox;
 pub mod sexp;
@@ -55,3 +58,4 @@ mod sexp_lexer;
 pub mod smt_events;
 pub mod smt_model;
 pub mod smt_relations;
+pub mod timing;
@@ -47,12 +47,14 @@ use isla_lib::smt::Solver;
 use isla_lib::value_parser::LocParser;
 use isla_lib::zencode;
 
+use crate::graph::EventSpec;
 use crate::page_table;
 use crate::sandbox::SandboxedCommand;
 
 pub mod exp;
 pub mod exp_lexer;
 mod format;
+pub mod reduce;
 lalrpop_mod!(
     #[allow(clippy::all)]
     pub exp_parser,
@@ -918,7 +920,7 @@ pub struct AssembledSection {
 
 #[derive(Debug, Clone)]
 pub struct LitmusGraphOpts {
-    pub force_show_events: Option<Vec<String>>,
+    pub force_show_events: Option<Vec<EventSpec>>,
     pub shows: Option<Vec<String>>,
 }
 
@@ -1098,7 +1100,11 @@ impl<B: BV> Litmus<B> {
             .and_then(|m| m.get("graph"))
             .and_then(|g| g.get("force_show_events"))
             .and_then(|t| t.as_array())
-            .and_then(|a| a.iter().map(|v| v.as_str().map(|s| s.to_string())).collect());
+            .and_then(|a| {
+                a.iter().map(|v| v.as_str().map(|s| s.parse::<EventSpec>())).collect::<Option<Result<Vec<_>, _>>>()
+            })
+            .transpose()
+            .map_err(|e| format!("Invalid meta.graph.force_show_events: {}", e))?;
 
         let graph_opts_shows = meta
             .and_then(|m| m.get("graph"))
@@ -35,7 +35,8 @@ use isla_lib::bitvector::BV;
 use isla_lib::error::ExecError;
 use isla_lib::ir::{Name, Reset, Symtab, Val};
 use isla_lib::memory::Memory;
-use isla_lib::smt::Solver;
+use isla_lib::smt::smtlib;
+use isla_lib::smt::{Config, Context, SmtResult, Solver, Sym};
 use isla_lib::source_loc::SourceLoc;
 use isla_lib::{primop, zencode};
 
@@ -733,6 +734,133 @@ pub fn collect_locs<'litmus>(exp: &'litmus Exp<String>, target: &mut HashSet<&'l
     }
 }
 
+/// The result of checking the final assertion of a litmus test in
+/// isolation, with every location it mentions taking a free value
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AssertionSanity {
+    Satisfiable,
+    /// No final state can satisfy the assertion, so the test is vacuous
+    Unsatisfiable,
+    /// Every final state satisfies the assertion
+    Tautology,
+    /// The assertion could not be checked, e.g. because it contains
+    /// functions that are only known to the candidate SMT problems
+    Unknown,
+}
+
+impl AssertionSanity {
+    pub fn short_name(self) -> &'static str {
+        use AssertionSanity::*;
+        match self {
+            Satisfiable => "satisfiable",
+            Unsatisfiable => "vacuous",
+            Tautology => "tautology",
+            Unknown => "unknown",
+        }
+    }
+
+    /// A message describing the problem with the final assertion, if
+    /// there is one
+    pub fn warning(self) -> Option<&'static str> {
+        use AssertionSanity::*;
+        match self {
+            Unsatisfiable => Some("final condition unsatisfiable — test is vacuous"),
+            Tautology => Some("final condition is a tautology"),
+            Satisfiable | Unknown => None,
+        }
+    }
+}
+
+fn sanity_value<V>(exp: &Exp<u64>) -> Option<(smtlib::Exp<V>, u32)> {
+    use Exp::*;
+    match exp {
+        Bits64(bits, len) => Some((smtlib::bits64(*bits, *len), *len)),
+        Nat(n) | Loc(n) => Some((smtlib::bits64(*n, 64), 64)),
+        _ => None,
+    }
+}
+
+fn sanity_prop<B: BV>(
+    exp: &Exp<u64>,
+    vars: &mut HashMap<Loc<u64>, (Sym, u32)>,
+    solver: &mut Solver<B>,
+) -> Option<smtlib::Exp<Sym>> {
+    use smtlib::Exp as SExp;
+    Some(match exp {
+        Exp::EqLoc(loc, value) => {
+            let (value, width) = sanity_value(value)?;
+            if let Loc::LastWriteTo { bytes, .. } = loc {
+                if bytes * 8 != width {
+                    return None;
+                }
+            }
+            let (v, var_width) = *vars
+                .entry(loc.clone())
+                .or_insert_with(|| (solver.declare_const(smtlib::Ty::BitVec(width), SourceLoc::unknown()), width));
+            if var_width != width {
+                return None;
+            }
+            SExp::Eq(Box::new(SExp::Var(v)), Box::new(value))
+        }
+        Exp::True => SExp::Bool(true),
+        Exp::False => SExp::Bool(false),
+        Exp::And(exps) => {
+            let mut conj = SExp::Bool(true);
+            for exp in exps {
+                conj = SExp::And(Box::new(conj), Box::new(sanity_prop(exp, vars, solver)?))
+            }
+            conj
+        }
+        Exp::Or(exps) => {
+            let mut disj = SExp::Bool(false);
+            for exp in exps {
+                disj = SExp::Or(Box::new(disj), Box::new(sanity_prop(exp, vars, solver)?))
+            }
+            disj
+        }
+        Exp::Not(exp) => SExp::Not(Box::new(sanity_prop(exp, vars, solver)?)),
+        Exp::Implies(exp1, exp2) => SExp::Or(
+            Box::new(SExp::Not(Box::new(sanity_prop(exp1, vars, solver)?))),
+            Box::new(sanity_prop(exp2, vars, solver)?),
+        ),
+        Exp::Loc(_)
+        | Exp::Label(_)
+        | Exp::Bin(_)
+        | Exp::Hex(_)
+        | Exp::Bits64(_, _)
+        | Exp::Nat(_)
+        | Exp::App(_, _, _) => return None,
+    })
+}
+
+/// Check whether a final assertion (with its addresses already
+/// resolved to physical addresses) is satisfiable or a tautology
+/// when each location it refers to can take any value. Such an
+/// assertion makes the allowed/forbidden verdict of the test
+/// meaningless, as it does not depend on the candidate executions.
+pub fn assertion_sanity<B: BV>(exp: &Exp<u64>) -> AssertionSanity {
+    let ctx = Context::new(Config::new());
+    let mut solver = Solver::<B>::new(&ctx);
+    let mut vars = HashMap::new();
+
+    let prop = match sanity_prop(exp, &mut vars, &mut solver) {
+        Some(prop) => prop,
+        None => return AssertionSanity::Unknown,
+    };
+
+    match solver.check_sat_with(&prop, SourceLoc::unknown()) {
+        SmtResult::Unsat => return AssertionSanity::Unsatisfiable,
+        SmtResult::Unknown => return AssertionSanity::Unknown,
+        SmtResult::Sat => (),
+    }
+
+    match solver.check_sat_with(&smtlib::Exp::Not(Box::new(prop)), SourceLoc::unknown()) {
+        SmtResult::Unsat => AssertionSanity::Tautology,
+        SmtResult::Sat => AssertionSanity::Satisfiable,
+        SmtResult::Unknown => AssertionSanity::Unknown,
+    }
+}
+
 // === impl Display for Loc ===
 
 pub struct LocDisplay<'l, 's, 'ir, A: fmt::Display> {
@@ -864,3 +992,50 @@ impl<A> Exp<A> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use isla_lib::bitvector::b64::B64;
+
+    fn x0_eq(thread_id: usize, value: u64) -> Exp<u64> {
+        Exp::EqLoc(Loc::Register { reg: Name::from_u32(0), thread_id }, Box::new(Exp::Bits64(value, 64)))
+    }
+
+    fn mem_eq(address: u64, value: u64) -> Exp<u64> {
+        Exp::EqLoc(Loc::LastWriteTo { address, bytes: 4 }, Box::new(Exp::Bits64(value, 32)))
+    }
+
+    #[test]
+    fn test_satisfiable_assertion() {
+        let exp = Exp::And(vec![x0_eq(0, 1), x0_eq(1, 0), mem_eq(0x1000, 2)]);
+        assert_eq!(assertion_sanity::<B64>(&exp), AssertionSanity::Satisfiable)
+    }
+
+    #[test]
+    fn test_vacuous_assertion() {
+        // The same register cannot end with two different values
+        let exp = Exp::And(vec![x0_eq(0, 1), mem_eq(0x1000, 2), x0_eq(0, 2)]);
+        assert_eq!(assertion_sanity::<B64>(&exp), AssertionSanity::Unsatisfiable);
+        assert_eq!(assertion_sanity::<B64>(&Exp::False), AssertionSanity::Unsatisfiable)
+    }
+
+    #[test]
+    fn test_tautological_assertion() {
+        let exp = Exp::Or(vec![mem_eq(0x1000, 1), Exp::Not(Box::new(mem_eq(0x1000, 1)))]);
+        assert_eq!(assertion_sanity::<B64>(&exp), AssertionSanity::Tautology);
+        let exp = Exp::Implies(Box::new(x0_eq(1, 3)), Box::new(Exp::Or(vec![x0_eq(1, 3), x0_eq(0, 1)])));
+        assert_eq!(assertion_sanity::<B64>(&exp), AssertionSanity::Tautology)
+    }
+
+    #[test]
+    fn test_unknown_assertion() {
+        // Functions are not evaluated by the sanity check
+        let exp = Exp::EqLoc(
+            Loc::Register { reg: Name::from_u32(0), thread_id: 0 },
+            Box::new(Exp::App("bvadd".to_string(), vec![Exp::Bits64(1, 64), Exp::Bits64(1, 64)], HashMap::new())),
+        );
+        assert_eq!(assertion_sanity::<B64>(&exp), AssertionSanity::Unknown)
+    }
+}
@@ -0,0 +1,367 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2024 Alasdair Armstrong
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// 1. Redistributions of source code must retain the above copyright
+// notice, this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright
+// notice, this list of conditions and the following disclaimer in the
+// documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! This module implements a simple automatic test-case reducer for
+//! litmus tests in the TOML format. Given a test that exhibits some
+//! interesting behaviour (as decided by a [ReductionOracle]), we
+//! repeatedly try removing instructions, final condition conjuncts,
+//! and initial values, keeping each change that preserves the
+//! behaviour, until either no further progress can be made or the
+//! time budget is exhausted.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use toml::Value;
+
+/// An oracle decides whether a candidate reduced litmus test still
+/// exhibits the behaviour we are interested in.
+pub trait ReductionOracle {
+    fn is_interesting(&mut self, litmus: &str) -> bool;
+}
+
+impl<F: FnMut(&str) -> bool> ReductionOracle for F {
+    fn is_interesting(&mut self, litmus: &str) -> bool {
+        self(litmus)
+    }
+}
+
+#[derive(Debug)]
+pub enum ReduceError {
+    /// The original test could not be parsed as TOML
+    Parse(String),
+    /// The original test does not satisfy the oracle, so there is nothing to reduce
+    NotInteresting,
+    /// Failed to serialize a candidate test back to TOML
+    Serialize(String),
+}
+
+impl fmt::Display for ReduceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ReduceError::*;
+        match self {
+            Parse(msg) => write!(f, "Failed to parse litmus test for reduction: {}", msg),
+            NotInteresting => write!(f, "The original litmus test is not interesting according to the oracle"),
+            Serialize(msg) => write!(f, "Failed to serialize reduced litmus test: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ReduceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+/// The result of a reduction
+#[derive(Debug)]
+pub struct Reduction {
+    /// The reduced litmus test in TOML format
+    pub litmus: String,
+    /// How many candidate reductions were accepted
+    pub accepted: usize,
+    /// How many times the oracle was invoked
+    pub attempts: usize,
+    /// True if we stopped because the time budget ran out, rather
+    /// than because we reached a fixpoint
+    pub timed_out: bool,
+}
+
+/// A single reduction step we can try applying to a test
+#[derive(Clone, Debug)]
+enum Step {
+    /// Remove the nth instruction line from a thread
+    RemoveInstruction { thread: String, line: usize },
+    /// Remove the nth top-level conjunct of the final assertion
+    RemoveConjunct(usize),
+    /// Remove an initial register value from a thread
+    RemoveInit { thread: String, reg: String },
+    /// Replace an initial register value with zero
+    ZeroInit { thread: String, reg: String },
+}
+
+fn is_instruction_line(line: &str) -> bool {
+    let line = line.trim();
+    !(line.is_empty() || line.ends_with(':') || line.starts_with("//") || line.starts_with(';'))
+}
+
+fn instruction_lines(code: &str) -> Vec<usize> {
+    code.split('\n').enumerate().filter(|(_, line)| is_instruction_line(line)).map(|(i, _)| i).collect()
+}
+
+/// Split an assertion into its top-level `&` separated conjuncts,
+/// respecting parentheses.
+fn split_conjuncts(assertion: &str) -> Vec<String> {
+    let mut conjuncts = Vec::new();
+    let mut depth: usize = 0;
+    let mut current = String::new();
+    for c in assertion.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            '&' if depth == 0 => {
+                conjuncts.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => (),
+        }
+        current.push(c)
+    }
+    conjuncts.push(current.trim().to_string());
+    conjuncts.retain(|c| !c.is_empty());
+    conjuncts
+}
+
+fn threads(litmus: &Value) -> Vec<(String, &Value)> {
+    match litmus.get("thread").and_then(Value::as_table) {
+        Some(threads) => threads.iter().map(|(name, thread)| (name.clone(), thread)).collect(),
+        None => Vec::new(),
+    }
+}
+
+fn assertion(litmus: &Value) -> Option<&str> {
+    litmus.get("final").and_then(|fin| fin.get("assertion")).and_then(Value::as_str)
+}
+
+/// Enumerate all the reduction steps that could currently be applied
+/// to a test. Instructions are tried from last to first, as later
+/// instructions are less likely to be depended on by earlier ones.
+fn steps(litmus: &Value) -> Vec<Step> {
+    let mut steps = Vec::new();
+
+    for (name, thread) in threads(litmus) {
+        if let Some(code) = thread.get("code").and_then(Value::as_str) {
+            for line in instruction_lines(code).into_iter().rev() {
+                steps.push(Step::RemoveInstruction { thread: name.clone(), line })
+            }
+        }
+    }
+
+    if let Some(assertion) = assertion(litmus) {
+        let conjuncts = split_conjuncts(assertion);
+        if conjuncts.len() > 1 || conjuncts.first().map(|c| c != "true").unwrap_or(false) {
+            for i in (0..conjuncts.len()).rev() {
+                steps.push(Step::RemoveConjunct(i))
+            }
+        }
+    }
+
+    for (name, thread) in threads(litmus) {
+        if let Some(init) = thread.get("init").and_then(Value::as_table) {
+            for (reg, value) in init {
+                steps.push(Step::RemoveInit { thread: name.clone(), reg: reg.clone() });
+                if value.as_str() != Some("0") {
+                    steps.push(Step::ZeroInit { thread: name.clone(), reg: reg.clone() })
+                }
+            }
+        }
+    }
+
+    steps
+}
+
+fn thread_mut<'a>(litmus: &'a mut Value, thread: &str) -> Option<&'a mut toml::value::Table> {
+    litmus.get_mut("thread")?.get_mut(thread)?.as_table_mut()
+}
+
+/// Apply a reduction step, returning None if the step no longer
+/// applies to the test.
+fn apply(litmus: &Value, step: &Step) -> Option<Value> {
+    let mut litmus = litmus.clone();
+    match step {
+        Step::RemoveInstruction { thread, line } => {
+            let thread = thread_mut(&mut litmus, thread)?;
+            let code = thread.get("code")?.as_str()?;
+            let lines: Vec<&str> = code.split('\n').collect();
+            if !is_instruction_line(lines.get(*line)?) {
+                return None;
+            }
+            let code = lines
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| i != line)
+                .map(|(_, line)| *line)
+                .collect::<Vec<_>>()
+                .join("\n");
+            thread.insert("code".to_string(), Value::String(code));
+        }
+        Step::RemoveConjunct(i) => {
+            let mut conjuncts = split_conjuncts(assertion(&litmus)?);
+            if *i >= conjuncts.len() {
+                return None;
+            }
+            conjuncts.remove(*i);
+            let assertion = if conjuncts.is_empty() { "true".to_string() } else { conjuncts.join(" & ") };
+            litmus.get_mut("final")?.as_table_mut()?.insert("assertion".to_string(), Value::String(assertion));
+        }
+        Step::RemoveInit { thread, reg } => {
+            let thread = thread_mut(&mut litmus, thread)?;
+            thread.get_mut("init")?.as_table_mut()?.remove(reg)?;
+        }
+        Step::ZeroInit { thread, reg } => {
+            let thread = thread_mut(&mut litmus, thread)?;
+            let init = thread.get_mut("init")?.as_table_mut()?;
+            if init.get(reg)?.as_str() == Some("0") {
+                return None;
+            }
+            init.insert(reg.clone(), Value::String("0".to_string()));
+        }
+    }
+    Some(litmus)
+}
+
+/// Reduce a litmus test (in TOML format), preserving whatever
+/// property the oracle checks for, within the given time budget.
+pub fn reduce<O: ReductionOracle>(source: &str, oracle: &mut O, budget: Duration) -> Result<Reduction, ReduceError> {
+    let start = Instant::now();
+
+    let mut litmus = source.parse::<Value>().map_err(|e| ReduceError::Parse(e.to_string()))?;
+
+    if !oracle.is_interesting(source) {
+        return Err(ReduceError::NotInteresting);
+    }
+
+    let mut reduction = Reduction { litmus: source.to_string(), accepted: 0, attempts: 1, timed_out: false };
+
+    'fixpoint: loop {
+        let mut progress = false;
+
+        for step in steps(&litmus) {
+            if start.elapsed() >= budget {
+                reduction.timed_out = true;
+                break 'fixpoint;
+            }
+
+            if let Some(candidate) = apply(&litmus, &step) {
+                let candidate_source =
+                    toml::to_string(&candidate).map_err(|e| ReduceError::Serialize(e.to_string()))?;
+                reduction.attempts += 1;
+                if oracle.is_interesting(&candidate_source) {
+                    litmus = candidate;
+                    reduction.litmus = candidate_source;
+                    reduction.accepted += 1;
+                    progress = true;
+                }
+            }
+        }
+
+        if !progress {
+            break;
+        }
+    }
+
+    Ok(reduction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST: &str = r#"
+arch = "AArch64"
+name = "MP+dmb+po"
+symbolic = ["x", "y"]
+
+[thread.0]
+init = { X3 = "y", X2 = "1", X1 = "x", X0 = "1" }
+code = """
+	STR X0,[X1]
+	DMB SY
+	STR X2,[X3]
+"""
+
+[thread.1]
+init = { X3 = "x", X1 = "y" }
+code = """
+	LDR X0,[X1]
+	DMB SY
+	LDR X2,[X3]
+"""
+
+[final]
+assertion = "1:X0 = 1 & 1:X2 = 0"
+"#;
+
+    fn thread_code(litmus: &str, thread: &str) -> String {
+        let litmus = litmus.parse::<Value>().unwrap();
+        litmus["thread"][thread]["code"].as_str().unwrap().to_string()
+    }
+
+    fn thread_1_has_dmb(litmus: &str) -> bool {
+        thread_code(litmus, "1").contains("DMB")
+    }
+
+    #[test]
+    fn test_split_conjuncts() {
+        assert_eq!(split_conjuncts("0:X0 = 1 & 1:X2 = 0"), vec!["0:X0 = 1", "1:X2 = 0"]);
+        assert_eq!(split_conjuncts("(0:X0 = 1 & 1:X2 = 0) & true"), vec!["(0:X0 = 1 & 1:X2 = 0)", "true"]);
+        assert_eq!(split_conjuncts("true"), vec!["true"]);
+    }
+
+    #[test]
+    fn test_reduce_to_dmb() {
+        let mut oracle = thread_1_has_dmb;
+        let reduction = reduce(TEST, &mut oracle, Duration::from_secs(60)).unwrap();
+        assert!(!reduction.timed_out);
+
+        assert_eq!(instruction_lines(&thread_code(&reduction.litmus, "0")).len(), 0);
+        let code = thread_code(&reduction.litmus, "1");
+        let lines = instruction_lines(&code);
+        assert_eq!(lines.len(), 1);
+        assert_eq!(code.split('\n').nth(lines[0]).unwrap().trim(), "DMB SY");
+
+        let litmus = reduction.litmus.parse::<Value>().unwrap();
+        assert_eq!(assertion(&litmus), Some("true"));
+        assert!(litmus["thread"]["0"]["init"].as_table().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_reduce_keeps_conjunct() {
+        let mut oracle = |litmus: &str| thread_1_has_dmb(litmus) && litmus.contains("1:X2 = 0");
+        let reduction = reduce(TEST, &mut oracle, Duration::from_secs(60)).unwrap();
+        let litmus = reduction.litmus.parse::<Value>().unwrap();
+        assert_eq!(assertion(&litmus), Some("1:X2 = 0"));
+    }
+
+    #[test]
+    fn test_reduce_not_interesting() {
+        let mut oracle = |_: &str| false;
+        assert!(matches!(reduce(TEST, &mut oracle, Duration::from_secs(60)), Err(ReduceError::NotInteresting)))
+    }
+
+    #[test]
+    fn test_reduce_no_budget() {
+        let mut oracle = thread_1_has_dmb;
+        let reduction = reduce(TEST, &mut oracle, Duration::from_secs(0)).unwrap();
+        assert!(reduction.timed_out);
+        assert_eq!(reduction.accepted, 0);
+        assert_eq!(reduction.litmus, TEST)
+    }
+}
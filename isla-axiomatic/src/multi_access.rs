@@ -0,0 +1,451 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2024 Alasdair Armstrong
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// 1. Redistributions of source code must retain the above copyright
+// notice, this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright
+// notice, this list of conditions and the following disclaimer in the
+// documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Instructions such as load/store pair, or misaligned accesses, can
+//! perform a single architectural access as multiple memory
+//! events. Different memory models want these treated differently,
+//! so this module provides a policy for how they should appear in
+//! candidate executions, along with a pass that merges adjacent
+//! accesses from the same instruction into a single wider access.
+
+use std::fmt;
+use std::str::FromStr;
+
+use isla_lib::bitvector::BV;
+use isla_lib::ir::Val;
+use isla_lib::primop_util::smt_value;
+use isla_lib::simplify::renumber_event;
+use isla_lib::smt::smtlib::{Def, Exp, Ty};
+use isla_lib::smt::{EvPath, Event, Sym};
+use isla_lib::source_loc::SourceLoc;
+
+/// How multiple memory events performed by the same instruction
+/// should appear in each candidate execution
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum MultiAccessPolicy {
+    /// Merge adjacent contiguous accesses of the same kind into a
+    /// single event
+    Merge,
+    /// Keep the events separate, but relate them with the built-in
+    /// `si` (same-instruction) relation
+    Linked,
+    /// Keep the events separate and unrelated (beyond `iio`)
+    #[default]
+    Unlinked,
+}
+
+impl FromStr for MultiAccessPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "merge" => Ok(MultiAccessPolicy::Merge),
+            "linked" => Ok(MultiAccessPolicy::Linked),
+            "unlinked" => Ok(MultiAccessPolicy::Unlinked),
+            _ => Err(format!("unknown multi-access policy '{}', must be one of merge, linked, or unlinked", s)),
+        }
+    }
+}
+
+impl fmt::Display for MultiAccessPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MultiAccessPolicy::Merge => write!(f, "merge"),
+            MultiAccessPolicy::Linked => write!(f, "linked"),
+            MultiAccessPolicy::Unlinked => write!(f, "unlinked"),
+        }
+    }
+}
+
+fn concrete_address<B: BV>(address: &Val<B>) -> Option<u64> {
+    match address {
+        Val::Bits(bv) if bv.len() <= 64 => Some(bv.lower_u64()),
+        _ => None,
+    }
+}
+
+/// Two accesses can be merged if they are the same kind of access to
+/// the same region, and the second starts where the first ends. Tagged
+/// accesses are never merged, as the merged access could not carry
+/// the tag of each part.
+fn contiguous<B: BV>(ev1: &Event<B>, ev2: &Event<B>) -> bool {
+    match (ev1, ev2) {
+        (
+            Event::ReadMem { read_kind: rk1, address: a1, bytes: b1, tag_value: t1, opts: o1, region: r1, .. },
+            Event::ReadMem { read_kind: rk2, address: a2, bytes: b2, tag_value: t2, opts: o2, region: r2, .. },
+        ) => {
+            rk1 == rk2
+                && t1.is_none()
+                && t2.is_none()
+                && r1 == r2
+                && !o1.is_ifetch
+                && !o2.is_ifetch
+                && o1.is_exclusive == o2.is_exclusive
+                && *b2 > 0
+                && contiguous_addresses(a1, *b1, a2, r1)
+        }
+        (
+            Event::WriteMem { write_kind: wk1, address: a1, bytes: b1, tag_value: t1, region: r1, .. },
+            Event::WriteMem { write_kind: wk2, address: a2, bytes: b2, tag_value: t2, region: r2, .. },
+        ) => wk1 == wk2 && t1.is_none() && t2.is_none() && r1 == r2 && *b2 > 0 && contiguous_addresses(a1, *b1, a2, r1),
+        _ => false,
+    }
+}
+
+fn contiguous_addresses<B: BV>(a1: &Val<B>, bytes1: u32, a2: &Val<B>, region: &str) -> bool {
+    // Never merge page table walks
+    if region == "stage 1" || region == "stage 2" {
+        return false;
+    }
+    match (concrete_address(a1), concrete_address(a2)) {
+        (Some(a1), Some(a2)) => a1.checked_add(bytes1 as u64) == Some(a2),
+        _ => false,
+    }
+}
+
+fn fresh(next: &mut u32) -> Sym {
+    let sym = Sym::from_u32(*next);
+    *next += 1;
+    sym
+}
+
+fn bits_exp<B: BV>(v: &Val<B>) -> Option<Exp<Sym>> {
+    match v {
+        Val::Bits(_) | Val::Symbolic(_) => smt_value(v, SourceLoc::unknown()).ok(),
+        _ => None,
+    }
+}
+
+/// Merge two contiguous accesses `ev1` (at the lower address) and
+/// `ev2`. Memory is assumed to be little-endian, so the data for the
+/// second access forms the upper bits of the merged access. Returns
+/// the events that should replace `ev2`, or None if the accesses
+/// cannot be merged.
+fn merge<B: BV>(ev1: &Event<B>, ev2: &Event<B>, next: &mut u32) -> Option<Vec<Event<B>>> {
+    match (ev1, ev2) {
+        (
+            Event::ReadMem { value: v1, read_kind, address, bytes: b1, opts, region, .. },
+            Event::ReadMem { value: v2, bytes: b2, .. },
+        ) => {
+            let (e1, e2) = (bits_exp(v1)?, bits_exp(v2)?);
+            let (w1, w2) = (b1 * 8, b2 * 8);
+            let v = fresh(next);
+            let extract = |hi, lo| Box::new(Exp::Extract(hi, lo, Box::new(Exp::Var(v))));
+            Some(vec![
+                Event::Smt(Def::DeclareConst(v, Ty::BitVec(w1 + w2)), Default::default(), SourceLoc::unknown()),
+                Event::Smt(
+                    Def::Assert(Exp::Eq(Box::new(e1), extract(w1 - 1, 0))),
+                    Default::default(),
+                    SourceLoc::unknown(),
+                ),
+                Event::Smt(
+                    Def::Assert(Exp::Eq(Box::new(e2), extract(w1 + w2 - 1, w1))),
+                    Default::default(),
+                    SourceLoc::unknown(),
+                ),
+                Event::ReadMem {
+                    value: Val::Symbolic(v),
+                    read_kind: read_kind.clone(),
+                    address: address.clone(),
+                    bytes: b1 + b2,
+                    tag_value: None,
+                    opts: opts.clone(),
+                    region,
+                },
+            ])
+        }
+        (
+            Event::WriteMem { value, write_kind, address, data: d1, bytes: b1, opts, region, .. },
+            Event::WriteMem { data: d2, bytes: b2, .. },
+        ) => {
+            let mut events = Vec::new();
+            let data = match (d1, d2) {
+                (Val::Bits(bv1), Val::Bits(bv2)) if bv2.append(*bv1).is_some() => Val::Bits(bv2.append(*bv1).unwrap()),
+                _ => {
+                    let (e1, e2) = (bits_exp(d1)?, bits_exp(d2)?);
+                    let d = fresh(next);
+                    events.push(Event::Smt(
+                        Def::DeclareConst(d, Ty::BitVec((b1 + b2) * 8)),
+                        Default::default(),
+                        SourceLoc::unknown(),
+                    ));
+                    events.push(Event::Smt(
+                        Def::Assert(Exp::Eq(Box::new(Exp::Var(d)), Box::new(Exp::Concat(Box::new(e2), Box::new(e1))))),
+                        Default::default(),
+                        SourceLoc::unknown(),
+                    ));
+                    Val::Symbolic(d)
+                }
+            };
+            events.push(Event::WriteMem {
+                value: *value,
+                write_kind: write_kind.clone(),
+                address: address.clone(),
+                data,
+                bytes: b1 + b2,
+                tag_value: None,
+                opts: opts.clone(),
+                region,
+            });
+            Some(events)
+        }
+        _ => None,
+    }
+}
+
+/// Merge adjacent contiguous memory accesses of the same kind that
+/// are performed by the same instruction into a single access. Two
+/// accesses are adjacent if there is no other memory access between
+/// them, and they are only merged if both have concrete addresses.
+///
+/// This should be applied to each thread trace before the symbolic
+/// variables in each trace are renumbered, as the merged values are
+/// given fresh variables.
+pub fn merge_multi_accesses<B: BV>(events: &mut EvPath<B>) {
+    let mut next = 0;
+    for event in events.iter_mut() {
+        renumber_event(event, &mut |id| {
+            next = u32::max(next, id + 1);
+            id
+        })
+    }
+
+    let mut merged: EvPath<B> = Vec::with_capacity(events.len());
+    // The index in `merged` of the last memory access in the current instruction
+    let mut last_access: Option<usize> = None;
+
+    for event in events.drain(..) {
+        if event.is_cycle() {
+            last_access = None;
+            merged.push(event);
+            continue;
+        }
+
+        if !event.is_memory_read_or_write() {
+            merged.push(event);
+            continue;
+        }
+
+        if let Some(i) = last_access {
+            if contiguous(&merged[i], &event) {
+                if let Some(mut replacement) = merge(&merged[i], &event, &mut next) {
+                    merged.remove(i);
+                    merged.append(&mut replacement);
+                    last_access = Some(merged.len() - 1);
+                    continue;
+                }
+            }
+        }
+
+        merged.push(event);
+        last_access = Some(merged.len() - 1)
+    }
+
+    *events = merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use isla_lib::bitvector::b129::B129;
+    use isla_lib::smt::{ReadOpts, WriteOpts};
+    use isla_mml::memory_model;
+
+    use crate::axiomatic::relations::same_instruction;
+    use crate::axiomatic::{AxEvent, TraceOrigin};
+
+    fn write(address: u64, data: Val<B129>, value: u32) -> Event<B129> {
+        Event::WriteMem {
+            value: Sym::from_u32(value),
+            write_kind: Val::Unit,
+            address: Val::Bits(B129::from_u64(address)),
+            data,
+            bytes: 8,
+            tag_value: None,
+            opts: WriteOpts::default(),
+            region: "default",
+        }
+    }
+
+    fn read(address: u64, value: u32) -> Event<B129> {
+        Event::ReadMem {
+            value: Val::Symbolic(Sym::from_u32(value)),
+            read_kind: Val::Unit,
+            address: Val::Bits(B129::from_u64(address)),
+            bytes: 8,
+            tag_value: None,
+            opts: ReadOpts::default(),
+            region: "default",
+        }
+    }
+
+    fn accesses(events: &EvPath<B129>) -> Vec<(u64, u32)> {
+        events
+            .iter()
+            .filter_map(|ev| match ev {
+                Event::ReadMem { address, bytes, .. } | Event::WriteMem { address, bytes, .. } => {
+                    Some((concrete_address(address).unwrap(), *bytes))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Create an axiomatic event for each memory access in a single instruction
+    fn ax_events(events: &EvPath<B129>) -> Vec<AxEvent<'_, B129>> {
+        events
+            .iter()
+            .filter(|ev| ev.is_memory_read_or_write())
+            .enumerate()
+            .map(|(iio, ev)| AxEvent {
+                opcode: None,
+                instruction_index: 0,
+                intra_instruction_index: iio,
+                in_program_order: false,
+                thread_id: 0,
+                name: format!("W0_{}_0", iio),
+                mm_name: memory_model::Name::from_u32(iio as u32),
+                base: vec![ev],
+                index_set: None,
+                extra: vec![],
+                is_ifetch: false,
+                translate: None,
+                trace_origin: TraceOrigin { thread_id: 0, index: iio },
+            })
+            .collect()
+    }
+
+    fn stp() -> EvPath<B129> {
+        vec![
+            Event::Cycle,
+            write(0x1000, Val::Bits(B129::from_u64(1)), 0),
+            write(0x1008, Val::Bits(B129::from_u64(2)), 1),
+        ]
+    }
+
+    #[test]
+    fn test_stp_policies() {
+        // Unlinked and linked keep both events, but only linked emits si
+        let events = stp();
+        let evs = ax_events(&events);
+        assert_eq!(evs.len(), 2);
+        assert!(same_instruction(&evs[0], &evs[1]));
+        assert!(same_instruction(&evs[1], &evs[0]));
+        assert!(!same_instruction(&evs[0], &evs[0]));
+
+        // Merge leaves a single event, so si is empty
+        let mut events = stp();
+        merge_multi_accesses(&mut events);
+        let evs = ax_events(&events);
+        assert_eq!(evs.len(), 1);
+        assert!(!same_instruction(&evs[0], &evs[0]));
+    }
+
+    #[test]
+    fn test_policy_from_str() {
+        for policy in [MultiAccessPolicy::Merge, MultiAccessPolicy::Linked, MultiAccessPolicy::Unlinked] {
+            assert_eq!(policy.to_string().parse::<MultiAccessPolicy>(), Ok(policy))
+        }
+        assert!("both".parse::<MultiAccessPolicy>().is_err())
+    }
+
+    #[test]
+    fn test_merge_stp_concrete() {
+        let mut events = vec![
+            Event::Cycle,
+            write(0x1000, Val::Bits(B129::from_u64(1)), 0),
+            write(0x1008, Val::Bits(B129::from_u64(2)), 1),
+            Event::Cycle,
+        ];
+        merge_multi_accesses(&mut events);
+        assert_eq!(accesses(&events), vec![(0x1000, 16)]);
+        match &events[1] {
+            Event::WriteMem { data: Val::Bits(bv), .. } => {
+                assert_eq!(bv.len(), 128);
+                assert_eq!(bv.extract(63, 0).unwrap().lower_u64(), 1);
+                assert_eq!(bv.extract(127, 64).unwrap().lower_u64(), 2);
+            }
+            ev => panic!("expected merged write, got {:?}", ev),
+        }
+    }
+
+    #[test]
+    fn test_merge_stp_symbolic() {
+        let mut events = vec![
+            Event::Cycle,
+            write(0x1000, Val::Symbolic(Sym::from_u32(5)), 0),
+            write(0x1008, Val::Symbolic(Sym::from_u32(6)), 1),
+        ];
+        merge_multi_accesses(&mut events);
+        assert_eq!(accesses(&events), vec![(0x1000, 16)]);
+        // The merged data is a fresh variable, defined by a concatenation
+        assert!(
+            matches!(&events[1], Event::Smt(Def::DeclareConst(v, Ty::BitVec(128)), _, _) if *v == Sym::from_u32(7))
+        );
+        assert!(matches!(&events[2], Event::Smt(Def::Assert(Exp::Eq(_, _)), _, _)));
+        assert!(matches!(&events[3], Event::WriteMem { data: Val::Symbolic(v), .. } if *v == Sym::from_u32(7)));
+    }
+
+    #[test]
+    fn test_merge_ldp() {
+        let mut events = vec![Event::Cycle, read(0x1000, 3), read(0x1008, 4), Event::Cycle, read(0x1010, 8)];
+        merge_multi_accesses(&mut events);
+        assert_eq!(accesses(&events), vec![(0x1000, 16), (0x1010, 8)]);
+        assert!(matches!(&events[4], Event::ReadMem { value: Val::Symbolic(v), .. } if *v == Sym::from_u32(9)));
+    }
+
+    #[test]
+    fn test_no_merge() {
+        // Non-contiguous accesses
+        let mut events = vec![Event::Cycle, read(0x1000, 0), read(0x2000, 1)];
+        merge_multi_accesses(&mut events);
+        assert_eq!(accesses(&events), vec![(0x1000, 8), (0x2000, 8)]);
+
+        // Accesses from different instructions
+        let mut events = vec![Event::Cycle, read(0x1000, 0), Event::Cycle, read(0x1008, 1)];
+        merge_multi_accesses(&mut events);
+        assert_eq!(accesses(&events), vec![(0x1000, 8), (0x1008, 8)]);
+
+        // A read and a write
+        let mut events = vec![Event::Cycle, read(0x1000, 0), write(0x1008, Val::Bits(B129::from_u64(0)), 1)];
+        merge_multi_accesses(&mut events);
+        assert_eq!(accesses(&events), vec![(0x1000, 8), (0x1008, 8)]);
+
+        // Tagged accesses
+        let mut tagged = write(0x1008, Val::Bits(B129::from_u64(2)), 1);
+        if let Event::WriteMem { tag_value, .. } = &mut tagged {
+            *tag_value = Some(Val::Bool(true))
+        }
+        let mut events = vec![Event::Cycle, write(0x1000, Val::Bits(B129::from_u64(1)), 0), tagged];
+        merge_multi_accesses(&mut events);
+        assert_eq!(accesses(&events), vec![(0x1000, 8), (0x1008, 8)]);
+        assert!(matches!(&events[2], Event::WriteMem { tag_value: Some(Val::Bool(true)), .. }));
+    }
+}
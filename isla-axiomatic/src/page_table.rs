@@ -27,6 +27,7 @@
 // (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
 // OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::convert::{From, Into};
 use std::fmt;
@@ -34,10 +35,11 @@ use std::ops::Range;
 use std::sync::Arc;
 
 use isla_lib::bitvector::{bzhi_u64, BV};
+use isla_lib::config::ISAConfig;
 use isla_lib::error::ExecError;
 use isla_lib::ir::Val;
 use isla_lib::log;
-use isla_lib::memory::{CustomRegion, Memory};
+use isla_lib::memory::{CustomRegion, Memory, Region};
 use isla_lib::primop_util::{length_bits, smt_sbits};
 use isla_lib::smt::{
     smtlib::{bits64, Exp, Ty},
@@ -1024,6 +1026,242 @@ impl<B: BV> CustomRegion<B> for ImmutablePageTables<B> {
     }
 }
 
+/// Describes where a set of translation tables built by [setup()]
+/// should live in memory.
+#[derive(Clone, Debug)]
+pub struct PageTableConfig {
+    /// The memory region name used for events generated by accesses
+    /// to the tables, e.g. "stage 1".
+    pub region: &'static str,
+    /// The physical address of the first table. Further tables are
+    /// allocated contiguously after it in 4K chunks.
+    pub base_addr: u64,
+    /// The maximum number of 4K tables that may be allocated.
+    pub max_tables: usize,
+}
+
+impl PageTableConfig {
+    /// The configuration used for the default stage 1 tables in
+    /// litmus tests, placed at the ISA config's `page_table_base`.
+    pub fn stage1<B>(isa_config: &ISAConfig<B>) -> Self {
+        PageTableConfig { region: "stage 1", base_addr: isa_config.page_table_base, max_tables: 512 }
+    }
+}
+
+/// A single stage 1 mapping from a virtual address to a physical
+/// address. Level 3 mappings are 4K pages, level 2 and level 1
+/// mappings are 2M and 1G blocks respectively.
+#[derive(Clone, Debug)]
+pub struct Mapping {
+    pub va: u64,
+    pub pa: u64,
+    pub level: u64,
+    pub attrs: S1PageAttrs,
+}
+
+impl Mapping {
+    pub fn new(va: u64, pa: u64, level: u64) -> Self {
+        Mapping { va, pa, level, attrs: S1PageAttrs::default() }
+    }
+
+    pub fn identity(addr: u64, level: u64) -> Self {
+        Self::new(addr, addr, level)
+    }
+
+    pub fn with_attrs(self, attrs: S1PageAttrs) -> Self {
+        Mapping { attrs, ..self }
+    }
+
+    /// The size in bytes of the region mapped. Only meaningful for
+    /// levels 1 to 3.
+    pub fn size(&self) -> u64 {
+        1 << (12 + 9 * (3 - self.level.min(3)))
+    }
+
+    fn va_range(&self) -> Range<u64> {
+        self.va..(self.va + self.size())
+    }
+}
+
+/// The result of [setup()], containing what is needed to point a
+/// translation table base register at the tables.
+#[derive(Clone, Debug)]
+pub struct PageTableHandles {
+    /// The physical address of the level 0 table
+    pub level0: u64,
+    /// The physical addresses occupied by all the allocated tables
+    pub range: Range<u64>,
+    /// The region name the tables were added to memory with
+    pub region: &'static str,
+}
+
+impl PageTableHandles {
+    /// The value for a TTBRn_ELx register pointing at the level 0
+    /// table with the given ASID.
+    pub fn ttbr(&self, asid: u16) -> u64 {
+        ((asid as u64) << 48) | self.level0
+    }
+}
+
+/// Errors reported by [setup()]. Mappings are identified by their index
+/// in the slice passed to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MappingError {
+    BadLevel { mapping: usize, level: u64 },
+    MisalignedVa { mapping: usize, va: u64, level: u64 },
+    MisalignedPa { mapping: usize, pa: u64, level: u64 },
+    AddressTooLarge { mapping: usize, addr: u64 },
+    UnknownAttrs { mapping: usize },
+    Overlap { first: usize, second: usize },
+    LevelConflict { block: usize, block_level: u64, inner: usize, inner_level: u64 },
+    TooManyTables { required: usize, max_tables: usize },
+    RegionOverlap { range: Range<u64>, region: &'static str },
+}
+
+impl fmt::Display for MappingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use MappingError::*;
+        match self {
+            BadLevel { mapping, level } => {
+                write!(
+                    f,
+                    "Mapping {}: level {} is not a valid leaf level for a 4K granule (expected 1, 2, or 3)",
+                    mapping, level
+                )
+            }
+            MisalignedVa { mapping, va, level } => {
+                write!(
+                    f,
+                    "Mapping {}: virtual address 0x{:x} is not aligned for a level {} mapping",
+                    mapping, va, level
+                )
+            }
+            MisalignedPa { mapping, pa, level } => {
+                write!(
+                    f,
+                    "Mapping {}: physical address 0x{:x} is not aligned for a level {} mapping",
+                    mapping, pa, level
+                )
+            }
+            AddressTooLarge { mapping, addr } => {
+                write!(f, "Mapping {}: address 0x{:x} does not fit in 48 bits", mapping, addr)
+            }
+            UnknownAttrs { mapping } => {
+                write!(f, "Mapping {}: page attributes must be fully known to build concrete tables", mapping)
+            }
+            Overlap { first, second } => {
+                write!(f, "Mappings {} and {} map overlapping virtual addresses", first, second)
+            }
+            LevelConflict { block, block_level, inner, inner_level } => write!(
+                f,
+                "Mapping {} at level {} lies inside the level {} block created by mapping {}",
+                inner, inner_level, block_level, block
+            ),
+            TooManyTables { required, max_tables } => {
+                write!(f, "Mappings require {} translation tables, but only {} are allowed", required, max_tables)
+            }
+            RegionOverlap { range, region } => write!(
+                f,
+                "Translation tables at 0x{:x}-0x{:x} overlap existing memory region {}",
+                range.start, range.end, region
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MappingError {}
+
+fn validate_mapping(i: usize, mapping: &Mapping) -> Result<(), MappingError> {
+    if !(1..=3).contains(&mapping.level) {
+        return Err(MappingError::BadLevel { mapping: i, level: mapping.level });
+    }
+
+    for addr in [mapping.va, mapping.pa] {
+        if addr >= 1 << 48 {
+            return Err(MappingError::AddressTooLarge { mapping: i, addr });
+        }
+    }
+
+    let mask = mapping.size() - 1;
+    if mapping.va & mask != 0 {
+        return Err(MappingError::MisalignedVa { mapping: i, va: mapping.va, level: mapping.level });
+    }
+    if mapping.pa & mask != 0 {
+        return Err(MappingError::MisalignedPa { mapping: i, pa: mapping.pa, level: mapping.level });
+    }
+
+    if mapping.attrs.bits().1 != 0 {
+        return Err(MappingError::UnknownAttrs { mapping: i });
+    }
+
+    Ok(())
+}
+
+/// Build stage 1 translation tables containing `mappings` using a 4K
+/// granule, and add them to `memory` as a custom region at the
+/// location given by `config`.
+///
+/// All mappings are checked before anything is allocated, so on error
+/// `memory` is left unchanged. Mappings must be at level 1 to 3, have
+/// both addresses aligned to the size of their level, and must not
+/// overlap each other. A page or block mapped inside a larger block is
+/// reported separately from two overlapping mappings at the same level,
+/// as the former usually means a mapping was given the wrong level.
+pub fn setup<B: BV>(
+    config: &PageTableConfig,
+    mappings: &[Mapping],
+    memory: &mut Memory<B>,
+) -> Result<PageTableHandles, MappingError> {
+    for (i, mapping) in mappings.iter().enumerate() {
+        validate_mapping(i, mapping)?
+    }
+
+    for (i, m1) in mappings.iter().enumerate() {
+        for (j, m2) in mappings.iter().enumerate().skip(i + 1) {
+            let (r1, r2) = (m1.va_range(), m2.va_range());
+            if r1.start < r2.end && r2.start < r1.end {
+                return Err(match m1.level.cmp(&m2.level) {
+                    Ordering::Equal => MappingError::Overlap { first: i, second: j },
+                    Ordering::Less => {
+                        MappingError::LevelConflict { block: i, block_level: m1.level, inner: j, inner_level: m2.level }
+                    }
+                    Ordering::Greater => {
+                        MappingError::LevelConflict { block: j, block_level: m2.level, inner: i, inner_level: m1.level }
+                    }
+                });
+            }
+        }
+    }
+
+    let mut tables = PageTables::<B>::new(config.region, config.base_addr);
+    let level0 = tables.alloc();
+
+    for mapping in mappings {
+        // Validation ensures the walk never encounters a block where
+        // it expects a table, so this cannot fail
+        tables
+            .map(level0, VirtualAddress::from_u64(mapping.va), mapping.pa, false, mapping.attrs.clone(), mapping.level)
+            .expect("validated mapping failed");
+    }
+
+    if tables.tables.len() > config.max_tables {
+        return Err(MappingError::TooManyTables { required: tables.tables.len(), max_tables: config.max_tables });
+    }
+
+    let range = tables.range();
+    for region in memory.regions() {
+        let other = region.region_range();
+        if range.start < other.end && other.start < range.end {
+            return Err(MappingError::RegionOverlap { range, region: memory.region_name_at(other.start) });
+        }
+    }
+
+    let handles = PageTableHandles { level0: table_address(level0), range: range.clone(), region: config.region };
+    memory.add_region(Region::Custom(range, Box::new(tables.freeze())));
+
+    Ok(handles)
+}
+
 pub struct TranslationTableWalk {
     pub(crate) l0pte: u64,
     pub(crate) l0desc: u64,
@@ -1288,4 +1526,79 @@ mod tests {
             panic!("simple_translation_table_walk failed")
         }
     }
+
+    fn read_desc(memory: &Memory<B64>, addr: u64) -> u64 {
+        match memory.read_initial(addr, 8) {
+            Ok(Val::Bits(bv)) => bv.lower_u64(),
+            _ => panic!("no initial descriptor at 0x{:x}", addr),
+        }
+    }
+
+    #[test]
+    fn test_setup_descriptor_encodings() {
+        let config = PageTableConfig { region: "test", base_addr: 0x5000_0000, max_tables: 16 };
+        let mappings = [
+            Mapping::new(0x1000, 0x8000_3000, 3),
+            Mapping::new(0x4020_0000, 0x8020_0000, 2),
+            Mapping::new(0x80_0000_0000, 0xC000_0000, 1),
+        ];
+        let mut memory = Memory::<B64>::new();
+        let handles = setup(&config, &mappings, &mut memory).unwrap();
+
+        assert_eq!(handles.level0, 0x5000_0000);
+        assert_eq!(handles.range, 0x5000_0000..0x5000_6000);
+        assert_eq!(handles.ttbr(1), 0x0001_0000_5000_0000);
+
+        // Table descriptors have bits 1-0 set to 0b11
+        assert_eq!(read_desc(&memory, 0x5000_0000), 0x5000_1003);
+        assert_eq!(read_desc(&memory, 0x5000_0008), 0x5000_5003);
+        assert_eq!(read_desc(&memory, 0x5000_1000), 0x5000_2003);
+        assert_eq!(read_desc(&memory, 0x5000_1008), 0x5000_4003);
+        assert_eq!(read_desc(&memory, 0x5000_2000), 0x5000_3003);
+
+        // With the default attributes AF (bit 10) is set and AP is 0b01
+        assert_eq!(read_desc(&memory, 0x5000_3008), 0x8000_3443);
+        assert_eq!(read_desc(&memory, 0x5000_4008), 0x8020_0441);
+        assert_eq!(read_desc(&memory, 0x5000_5000), 0xC000_0441);
+
+        assert_eq!(read_desc(&memory, 0x5000_3000), 0);
+    }
+
+    #[test]
+    fn test_setup_errors() {
+        use MappingError::*;
+
+        let config = PageTableConfig { region: "test", base_addr: 0x5000_0000, max_tables: 4 };
+        let mut memory = Memory::<B64>::new();
+
+        let mut check = |mappings: &[Mapping]| setup(&config, mappings, &mut memory).unwrap_err();
+
+        assert_eq!(check(&[Mapping::identity(0x1000, 0)]), BadLevel { mapping: 0, level: 0 });
+        assert_eq!(check(&[Mapping::new(0x1800, 0x2000, 3)]), MisalignedVa { mapping: 0, va: 0x1800, level: 3 });
+        assert_eq!(check(&[Mapping::new(0x20_0000, 0x1000, 2)]), MisalignedPa { mapping: 0, pa: 0x1000, level: 2 });
+        assert_eq!(check(&[Mapping::identity(1 << 48, 3)]), AddressTooLarge { mapping: 0, addr: 1 << 48 });
+        assert_eq!(
+            check(&[Mapping::identity(0x1000, 3).with_attrs(S1PageAttrs::unknown())]),
+            UnknownAttrs { mapping: 0 }
+        );
+        assert_eq!(
+            check(&[Mapping::identity(0x1000, 3), Mapping::new(0x1000, 0x2000, 3)]),
+            Overlap { first: 0, second: 1 }
+        );
+        assert_eq!(
+            check(&[Mapping::identity(0x1000, 3), Mapping::identity(0, 2)]),
+            LevelConflict { block: 1, block_level: 2, inner: 0, inner_level: 3 }
+        );
+        assert_eq!(
+            check(&[Mapping::identity(0x1000, 3), Mapping::identity(0x80_0000_0000, 3)]),
+            TooManyTables { required: 7, max_tables: 4 }
+        );
+
+        // Nothing is added to memory on failure
+        assert!(memory.regions().is_empty());
+
+        memory.add_zero_region(0x5000_1000..0x5000_2000);
+        let err = setup(&config, &[Mapping::identity(0x1000, 3)], &mut memory).unwrap_err();
+        assert_eq!(err, RegionOverlap { range: 0x5000_0000..0x5000_4000, region: "concrete" });
+    }
 }
@@ -0,0 +1,170 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2024 Alasdair Armstrong
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// 1. Redistributions of source code must retain the above copyright
+// notice, this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright
+// notice, this list of conditions and the following disclaimer in the
+// documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! This module collects cheap statistics about the relations defined
+//! by a memory model, namely how many edges each relation has in each
+//! candidate execution. This is useful when tuning models to spot
+//! relations that grow unexpectedly large.
+
+use std::collections::{BTreeMap, HashMap};
+
+use isla_lib::bitvector::BV;
+
+use crate::smt_model::Model;
+
+/// The number of edges in each relation for a single candidate
+/// execution, along with the number of events in that candidate.
+#[derive(Clone, Debug, Default)]
+pub struct EdgeCounts {
+    pub events: usize,
+    pub edges: HashMap<String, usize>,
+}
+
+/// Count the edges for each of the given relations in a model. Any
+/// names in `relations` which are not binary relations over events
+/// (e.g. sets or flags) are skipped.
+pub fn count_edges<B: BV>(model: &mut Model<'_, '_, B>, events: usize, relations: &[&str]) -> EdgeCounts {
+    let mut counts = EdgeCounts { events, edges: HashMap::new() };
+    for rel in relations {
+        if model.arity(rel) != Some(2) {
+            continue;
+        }
+        if let Ok(edges) = model.interpret_rel(rel) {
+            counts.edges.insert(rel.to_string(), edges.len());
+        }
+    }
+    counts
+}
+
+/// Summary statistics for a single relation over all the candidates
+/// in a test.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RelationSummary {
+    pub name: String,
+    pub candidates: usize,
+    pub min: usize,
+    pub median: usize,
+    pub max: usize,
+    /// The largest fraction of all possible edges (events squared)
+    /// present in any candidate
+    pub max_density: f64,
+}
+
+/// Accumulates edge counts over all the candidates of a test
+#[derive(Clone, Debug, Default)]
+pub struct RelationStats {
+    // For each relation, the (edges, events) pair for each candidate
+    counts: BTreeMap<String, Vec<(usize, usize)>>,
+}
+
+impl RelationStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, counts: &EdgeCounts) {
+        for (rel, edges) in &counts.edges {
+            self.counts.entry(rel.clone()).or_default().push((*edges, counts.events))
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// Summarise each relation, in alphabetical order
+    pub fn summary(&self) -> Vec<RelationSummary> {
+        self.counts
+            .iter()
+            .map(|(name, counts)| {
+                let mut edges: Vec<usize> = counts.iter().map(|(edges, _)| *edges).collect();
+                edges.sort_unstable();
+                let max_density = counts
+                    .iter()
+                    .map(|(edges, events)| if *events == 0 { 0.0 } else { *edges as f64 / (events * events) as f64 })
+                    .fold(0.0, f64::max);
+                RelationSummary {
+                    name: name.clone(),
+                    candidates: edges.len(),
+                    min: edges.first().copied().unwrap_or(0),
+                    median: edges.get(edges.len() / 2).copied().unwrap_or(0),
+                    max: edges.last().copied().unwrap_or(0),
+                    max_density,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use isla_lib::bitvector::b64::B64;
+
+    #[test]
+    fn test_count_edges() {
+        let smtlib = "(model (define-fun obs ((x!0 Event) (x!1 Event)) Bool
+                        (or (and (= x!0 W0) (= x!1 R1))
+                            (and (= x!0 IW) (= x!1 W0))
+                            (and (= x!0 W1) (= x!1 R0))
+                            (and (= x!0 IW) (= x!1 W1))))
+                        (define-fun rf ((x!0 Event) (x!1 Event)) Bool
+                        (and (= x!0 W0) (= x!1 R1)))
+                        (define-fun flag () Bool true)
+                        (define-fun W ((x!0 Event)) Bool (or (= x!0 W0) (= x!0 W1))))";
+        let evs = ["IW", "W0", "W1", "R0", "R1"];
+        let mut model = Model::<B64>::parse(&evs, smtlib).unwrap();
+        let counts = count_edges(&mut model, evs.len(), &["obs", "rf", "flag", "W", "missing"]);
+        assert_eq!(counts.edges.get("obs"), Some(&4));
+        assert_eq!(counts.edges.get("rf"), Some(&1));
+        assert_eq!(counts.edges.get("flag"), None);
+        assert_eq!(counts.edges.get("W"), None);
+        assert_eq!(counts.edges.len(), 2);
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut stats = RelationStats::new();
+        for (obs, rf) in [(4, 1), (2, 2), (10, 0)] {
+            let mut counts = EdgeCounts { events: 5, edges: HashMap::new() };
+            counts.edges.insert("obs".to_string(), obs);
+            counts.edges.insert("rf".to_string(), rf);
+            stats.record(&counts)
+        }
+        let summary = stats.summary();
+        assert_eq!(summary.len(), 2);
+        assert_eq!(summary[0].name, "obs");
+        assert_eq!((summary[0].min, summary[0].median, summary[0].max), (2, 4, 10));
+        assert_eq!(summary[0].max_density, 10.0 / 25.0);
+        assert_eq!(summary[1].name, "rf");
+        assert_eq!((summary[1].min, summary[1].median, summary[1].max), (0, 1, 2));
+        assert_eq!(summary[1].candidates, 3);
+    }
+}
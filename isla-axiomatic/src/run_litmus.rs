@@ -29,27 +29,31 @@
 
 use crossbeam::queue::{ArrayQueue, SegQueue};
 use isla_lib::init::InitArchWithConfig;
+use std::cell::Cell;
 use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::fmt;
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
-use std::process::Command;
+use std::process::{self, Command};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use isla_lib::bitvector::BV;
 use isla_lib::error::{ExecError, IslaError};
 use isla_lib::executor;
-use isla_lib::executor::{LocalFrame, TaskId, TaskInterrupt, TaskState, TraceError};
+use isla_lib::executor::{
+    LocalFrame, MemoryBudget, TaskId, TaskInterrupt, TaskState, TraceError, TraceFiles, TracePath,
+};
 use isla_lib::ir::*;
 use isla_lib::memory::Memory;
 use isla_lib::simplify;
 use isla_lib::simplify::{write_events_with_opts, WriteOpts};
 use isla_lib::smt::smtlib;
-use isla_lib::smt::{checkpoint, Checkpoint, Config, Context, EvPath, Event, Solver};
+use isla_lib::smt::{checkpoint, Checkpoint, Config, Context, EvPath, Event, EventFilter, EventKind, Solver};
 use isla_lib::source_loc::SourceLoc;
 use isla_lib::zencode;
 use isla_lib::{if_logging, log};
@@ -61,11 +65,13 @@ use isla_mml::smt::{write_sexps, SexpArena, SexpId};
 use crate::axiomatic::{Candidates, ExecutionInfo, ThreadId};
 use crate::footprint_analysis::{footprint_analysis, Footprint, FootprintError};
 use crate::graph::GraphOpts;
-use crate::litmus::exp::{partial_eval, reset_eval, Exp, Partial};
+use crate::litmus::exp::{assertion_sanity, partial_eval, reset_eval, AssertionSanity, Exp, Partial};
 use crate::litmus::{Litmus, Thread};
+use crate::multi_access::{merge_multi_accesses, MultiAccessPolicy};
 use crate::page_table::setup::{armv8_litmus_page_tables, PageTableSetup, SetupError};
 use crate::smt_events::smt_of_candidate;
 use crate::smt_model::Model;
+use crate::timing::{Phase, PhaseTimings, ProgressLine};
 
 #[derive(Debug)]
 pub enum LitmusRunError<E> {
@@ -75,6 +81,9 @@ pub enum LitmusRunError<E> {
     PageTableSetup(SetupError),
     Callback(Vec<E>),
     NoCandidates,
+    /// Tasks or traces could not be written to disk or read back
+    /// when running within [LitmusRunOpts::memory_budget]
+    Spill(String),
 }
 
 impl<E: IslaError> IslaError for LitmusRunError<E> {
@@ -105,6 +114,7 @@ impl<E: fmt::Display> fmt::Display for LitmusRunError<E> {
                 Ok(())
             }
             NoCandidates => write!(f, "There are no candidate executions"),
+            Spill(msg) => write!(f, "Error storing symbolic execution results on disk: {}", msg),
         }
     }
 }
@@ -131,6 +141,10 @@ pub struct LitmusRunOpts {
     /// currently used by both z3 evaluating candidates and the
     /// symbolic execution, so the effective timeout can be double.
     pub timeout: Option<u64>,
+    /// The optional timeout (in seconds) for each path through the
+    /// symbolic execution of a thread, so a path which the solver gets
+    /// stuck on fails rather than holding up the whole test
+    pub path_timeout: Option<u64>,
     /// Set a memory consumption limit for z3
     pub memory: Option<u64>,
     /// Set a limit on the number of times the program counter can be
@@ -155,11 +169,31 @@ pub struct LitmusRunOpts {
     /// sequence of events in a translation if a single read in that
     /// translation does not read from the initial state
     pub remove_uninteresting_translates: Option<bool>,
+    /// How memory accesses split into multiple events by a single
+    /// instruction (e.g. pair instructions) are represented
+    pub multi_access: MultiAccessPolicy,
+    /// Print rate-limited progress lines to stderr as symbolic
+    /// execution and candidate checking advance
+    pub progress: bool,
+    /// Ask z3 to give an interpretation for every declared function
+    /// in the model, including those it did not need to decide
+    /// satisfiability, so that no relation is missing from graphs
+    pub model_completion: bool,
+    /// When Some, symbolic execution writes the tasks it has not run
+    /// yet to disk once there are more than the budget allows, and
+    /// writes the trace of each path to disk as it finishes. The
+    /// traces are then read back one at a time, so only the events
+    /// kept for candidate executions are held in memory.
+    pub memory_budget: Option<MemoryBudget>,
 }
 
 pub struct LitmusRunInfo {
     pub candidates: usize,
     pub discarded: u32,
+    /// Whether the final assertion is vacuous or a tautology
+    pub assertion_sanity: AssertionSanity,
+    /// How long each phase of the run took
+    pub timings: PhaseTimings,
 }
 
 /// This is the result of the setup of a litmus test that can then be used either to run
@@ -179,12 +213,20 @@ pub struct LitmusSetup<B> {
     pub discarded: u32,
 }
 
-/// Run each thread in a litmus test symbolically, and returns a vector of
-/// traces for each litmus threads and the final assertion
+/// Numbers the merged trace files, so tests run at the same time with
+/// a memory budget never share one
+static TRACES_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Run each thread in a litmus test symbolically, returning the traces
+/// of each thread and the final assertion. Only events `record_filter`
+/// accepts are recorded during execution (see
+/// [TaskState::with_event_filter]), and the events in each path are
+/// then filtered by `event_filter`.
 pub fn run_litmus_setup<B, F, E>(
     opts: &LitmusRunOpts,
     litmus: &Litmus<B>,
     arch: &InitArchWithConfig<B>,
+    record_filter: Option<EventFilter>,
     event_filter: F,
 ) -> Result<LitmusSetup<B>, LitmusRunError<E>>
 where
@@ -280,6 +322,10 @@ where
             let mut task_state =
                 TaskState::new().with_reset_registers(reset).with_zero_announce_exit(isa_config.zero_announce_exit);
 
+            if let Some(filter) = &record_filter {
+                task_state = task_state.with_event_filter(filter.clone())
+            }
+
             for (n, interrupt) in thread.interrupts().iter().enumerate() {
                 let reset = interrupt
                     .reset
@@ -298,7 +344,7 @@ where
         })
         .collect();
     let mut lets = arch.lets.clone();
-    let tasks: Vec<_> = litmus
+    let mut tasks: Vec<_> = litmus
         .threads
         .iter()
         .enumerate()
@@ -332,28 +378,36 @@ where
             }
         })
         .collect();
+    for task in tasks.iter_mut() {
+        task.set_path_timeout(opts.path_timeout.map(Duration::from_secs))
+    }
 
     let mut threads: Vec<Vec<EvPath<B>>> = vec![Vec::new(); tasks.len()];
-    let queue = Arc::new(SegQueue::new());
 
     let now = Instant::now();
-    executor::start_multi(
-        opts.num_threads,
-        opts.timeout,
-        tasks,
-        shared_state,
-        queue.clone(),
-        &executor::trace_collector,
-    );
-    log!(log::VERBOSE, &format!("Symbolic execution took: {}ms", now.elapsed().as_millis()));
+    let progress = ProgressLine::new(opts.progress);
+    let last_progress = Cell::new(None);
+    let report_progress = |p: executor::ExecutionProgress| {
+        last_progress.set(Some(p));
+        progress.report(false, || format!("execution: {} paths, {}/{} threads", p.paths, p.tasks_finished, p.tasks))
+    };
 
     let mut discarded: u32 = 0;
+    let finish_execution = || {
+        if let Some(p) = last_progress.get() {
+            progress.report(true, || format!("execution: {} paths, {}/{} threads", p.paths, p.tasks_finished, p.tasks))
+        }
+        log!(log::VERBOSE, &format!("Symbolic execution took: {}ms", now.elapsed().as_millis()));
+    };
 
-    loop {
-        match queue.pop() {
-            Some(Ok((task_id, mut events))) => {
-                let mut events: EvPath<B> = events.drain(..).rev().filter(&event_filter).collect();
+    let mut add_path = |path: Result<TracePath<B>, TraceError>| -> Result<(), LitmusRunError<E>> {
+        match path {
+            Ok(TracePath { task_id, events, .. }) => {
+                let mut events: EvPath<B> = events.into_iter().filter(&event_filter).collect();
                 simplify::remove_unused(&mut events);
+                if opts.multi_access == MultiAccessPolicy::Merge {
+                    merge_multi_accesses(&mut events)
+                }
                 for event in events.iter_mut() {
                     let total = threads.len();
                     assert!(task_id.as_usize() < total);
@@ -362,21 +416,105 @@ where
                 threads[task_id.as_usize()].push(events)
             }
             // Error during execution
-            Some(Err(err)) => match err {
+            Err(err) => match err {
                 TraceError::Exec { err: ExecError::PCLimitReached(_), .. } => match opts.pc_limit_mode {
                     PCLimitMode::Error => return Err(LitmusRunError::Trace(err)),
                     PCLimitMode::Discard => discarded += 1,
                 },
                 _ => return Err(LitmusRunError::Trace(err)),
             },
-            // Empty queue
-            None => break,
+        }
+        Ok(())
+    };
+
+    if let Some(budget) = &opts.memory_budget {
+        let spill_err = |err: executor::CheckpointError| LitmusRunError::Spill(err.to_string());
+        let traces = Arc::new(TraceFiles::new(&budget.dir, opts.num_threads).map_err(spill_err)?);
+        let status = executor::start_multi(
+            tasks,
+            shared_state,
+            traces.clone(),
+            executor::MultiCollector::Plain(&executor::streaming_trace_collector),
+            &executor::MultiOpts {
+                num_threads: opts.num_threads,
+                timeout: opts.timeout,
+                progress: Some(&report_progress),
+                memory_budget: Some(budget),
+                ..executor::MultiOpts::default()
+            },
+        );
+        finish_execution();
+        let traces = Arc::into_inner(traces).unwrap();
+        if let executor::ExplorationStatus::SpillFailed(msg) = status {
+            return Err(LitmusRunError::Spill(msg));
+        }
+        while let Some(err) = traces.pop_error() {
+            add_path(Err(err))?
+        }
+        let merged = budget.dir.join(format!(
+            "isla-litmus-traces-{}-{}",
+            process::id(),
+            TRACES_COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        let reader = traces.merge(&merged).map_err(spill_err);
+        let result = reader.and_then(|reader| {
+            for path in reader {
+                add_path(Ok(path.map_err(spill_err)?))?
+            }
+            Ok(())
+        });
+        let _ = std::fs::remove_file(&merged);
+        result?
+    } else {
+        let queue = Arc::new(SegQueue::new());
+        executor::start_multi(
+            tasks,
+            shared_state,
+            queue.clone(),
+            executor::MultiCollector::Plain(&executor::structured_trace_collector),
+            &executor::MultiOpts {
+                num_threads: opts.num_threads,
+                timeout: opts.timeout,
+                progress: Some(&report_progress),
+                ..executor::MultiOpts::default()
+            },
+        );
+        finish_execution();
+        while let Some(path) = queue.pop() {
+            add_path(path)?
         }
     }
 
     Ok(LitmusSetup { threads, final_assertion, memory, page_table_setup, discarded })
 }
 
+/// The events kept from each path to build candidate executions
+fn is_candidate_event<B: BV>(ignore_ifetch: bool, ev: &Event<B>) -> bool {
+    (ev.is_memory_read_or_write() && !(ignore_ifetch && ev.is_ifetch()))
+        || ev.is_smt()
+        || ev.is_function()
+        || ev.is_instr()
+        || ev.is_cycle()
+        || ev.is_write_reg()
+        || ev.is_read_reg()
+        || ev.is_abstract()
+        || ev.is_branch()
+}
+
+/// Drops events during execution which [is_candidate_event] would
+/// remove afterwards, apart from those the executor always records
+fn candidate_record_filter(ignore_ifetch: bool) -> EventFilter {
+    Arc::new(move |kind| match kind {
+        EventKind::ReadMem { ifetch, .. } => !(ignore_ifetch && ifetch),
+        EventKind::WriteMem { .. }
+        | EventKind::Instr
+        | EventKind::ReadReg(_)
+        | EventKind::WriteReg(_)
+        | EventKind::Abstract { .. } => true,
+        _ => false,
+    })
+}
+
 pub struct Candidate<'c, B> {
     tid: ThreadId,
     events: &'c [&'c [Event<B>]],
@@ -401,22 +539,22 @@ where
     F: Sync + Send + Fn(Candidate<'_, B>) -> Result<(), E>,
     E: Send + std::fmt::Debug,
 {
+    let mut timings = PhaseTimings::new();
+
+    let now = Instant::now();
     let LitmusSetup { threads: thread_buckets, final_assertion, memory, page_table_setup, discarded } =
-        run_litmus_setup(opts, litmus, arch, |ev| {
-            (ev.is_memory_read_or_write() && !(opts.ignore_ifetch && ev.is_ifetch()))
-                || ev.is_smt()
-                || ev.is_function()
-                || ev.is_instr()
-                || ev.is_cycle()
-                || ev.is_write_reg()
-                || ev.is_read_reg()
-                || ev.is_abstract()
-                || ev.is_branch()
+        run_litmus_setup(opts, litmus, arch, Some(candidate_record_filter(opts.ignore_ifetch)), |ev| {
+            is_candidate_event(opts.ignore_ifetch, ev)
         })?;
+    timings.record(Phase::Execution, now.elapsed());
 
-    let footprints = footprint_analysis(opts.num_threads, &thread_buckets, footprint_arch, Some(cache.as_ref()))
+    let footprints = timings
+        .time(Phase::Footprint, || {
+            footprint_analysis(opts.num_threads, &thread_buckets, footprint_arch, Some(cache.as_ref()))
+        })
         .map_err(LitmusRunError::Footprint)?;
 
+    let now = Instant::now();
     let candidates = Candidates::new(&thread_buckets);
     let num_candidates = candidates.total();
     log!(log::VERBOSE, &format!("There are {} candidate executions", num_candidates));
@@ -433,9 +571,13 @@ where
     }
 
     log!(log::VERBOSE, &format!("with {} events", event_counts.join(", ")));
+    timings.record(Phase::Candidates, now.elapsed());
 
     let err_queue = ArrayQueue::new(num_candidates);
+    let progress = ProgressLine::new(opts.progress);
+    let checked = AtomicUsize::new(0);
 
+    let now = Instant::now();
     thread::scope(|scope| {
         for _ in 0..opts.num_threads {
             scope.spawn(|| {
@@ -450,10 +592,15 @@ where
                     }) {
                         err_queue.push(err).unwrap()
                     }
+                    let checked = checked.fetch_add(1, Ordering::SeqCst) + 1;
+                    progress.report(checked == num_candidates, || {
+                        format!("checking: {}/{} candidates", checked, num_candidates)
+                    })
                 }
             });
         }
     });
+    timings.record(Phase::Checking, now.elapsed());
 
     let mut callback_errors = Vec::new();
     while let Some(err) = err_queue.pop() {
@@ -461,7 +608,12 @@ where
     }
 
     if callback_errors.is_empty() {
-        Ok(LitmusRunInfo { candidates: num_candidates, discarded })
+        Ok(LitmusRunInfo {
+            candidates: num_candidates,
+            discarded,
+            assertion_sanity: assertion_sanity::<B>(&final_assertion),
+            timings,
+        })
     } else {
         Err(LitmusRunError::Callback(callback_errors))
     }
@@ -519,7 +671,7 @@ pub fn smt_output_per_candidate<B, P, F, E>(
     sexps: &SexpArena,
     memory_model: &[SexpId],
     memory_model_symtab: &memory_model::Symtab,
-    memory_model_accessors: &HashMap<memory_model::Name, memory_model::AccessorInfo>,
+    memory_model_accessors: &memory_model::Accessors,
     extra_smt: &[(String, String)],
     check_sat_using: Option<&str>,
     get_model: bool,
@@ -647,6 +799,7 @@ where
                     litmus,
                     opts.ignore_ifetch,
                     opts.armv8_page_tables,
+                    opts.multi_access == MultiAccessPolicy::Linked,
                     candidate.footprints,
                     candidate.memory,
                     &candidate.page_table_setup.initial_physical_addrs,
@@ -673,7 +826,8 @@ where
                         arch.shared_state,
                         &memory_model_symtab,
                         &mut sexps,
-                    );
+                    )
+                    .map_err(internal_err)?;
                     accessor_sexps.push(f);
                 }
                 let index_bitwidths = index_bitwidths(&exec.smt_events);
@@ -752,6 +906,9 @@ where
             if let Some(mem) = opts.memory {
                 z3_command.arg(format!("-memory:{}", mem));
             }
+            if opts.model_completion {
+                z3_command.arg("model.completion=true");
+            }
             z3_command.arg(&path);
 
             let z3 = z3_command.output().map_err(internal_err)?;
@@ -832,3 +989,313 @@ where
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use isla_lib::bitvector::{b129::B129, b64::B64};
+    use isla_lib::config::ISAConfig;
+    use isla_lib::init::initialize_architecture;
+    use isla_lib::smt::{ReadOpts, Sym};
+
+    use super::*;
+
+    const CONFIG: &str = r#"
+        pc = "PC"
+        assembler = "false"
+        objdump = "false"
+        nm = "false"
+        linker = "false"
+
+        [mmu]
+        page_table_base = "0x300000"
+        page_size = "4096"
+        s2_page_table_base = "0x200000"
+        s2_page_size = "4096"
+
+        [threads]
+        base = "0x400000"
+        top = "0x500000"
+        stride = "0x1000"
+
+        [symbolic_addrs]
+        base = "0x600000"
+        top = "0x700000"
+        stride = "0x10"
+    "#;
+
+    const STP: &str = r#"
+        arch = "test"
+        name = "stp"
+        symbolic = ["x"]
+
+        [thread.0]
+        call = "stp"
+
+        [final]
+        assertion = "true"
+    "#;
+
+    fn opts(multi_access: MultiAccessPolicy) -> LitmusRunOpts {
+        LitmusRunOpts {
+            num_threads: 1,
+            timeout: None,
+            path_timeout: None,
+            memory: None,
+            pc_limit: None,
+            pc_limit_mode: PCLimitMode::Error,
+            ignore_ifetch: true,
+            exhaustive: false,
+            armv8_page_tables: false,
+            merge_translations: None,
+            remove_uninteresting_translates: None,
+            multi_access,
+            progress: false,
+            model_completion: false,
+            memory_budget: None,
+        }
+    }
+
+    /// Run `f` with an architecture where the `stp` thread runs a
+    /// single instruction that writes 8 bytes to `x` and then 8 bytes
+    /// to `x + 8`, like an AArch64 store pair
+    fn with_stp_arch<T>(f: impl FnOnce(&InitArchWithConfig<B129>, &Litmus<B129>) -> T) -> T {
+        with_branching_stp_arch(0, f)
+    }
+
+    /// As [with_stp_arch], but the instruction first branches on
+    /// `branches` fresh symbolic booleans, so the thread has
+    /// 2^`branches` paths
+    fn with_branching_stp_arch<T>(branches: usize, f: impl FnOnce(&InitArchWithConfig<B129>, &Litmus<B129>) -> T) -> T {
+        use isla_lib::ir::Exp;
+
+        let mut symtab = isla_lib::ir::Symtab::new();
+        let pc = symtab.intern("zPC");
+        let main = symtab.intern("zmain");
+        let footprint = symtab.intern("zisla_footprint");
+        let stp = symtab.intern("zstp");
+        let cycle_count = symtab.intern("zcycle_count");
+        let write_mem = symtab.intern("zwrite_mem");
+        let [x, ok, b] = ["zx", "zok", "zb"].map(|id| symtab.intern(id));
+
+        let write = |address, data| {
+            Instr::Call(
+                Loc::Id(ok),
+                false,
+                write_mem,
+                vec![
+                    Exp::Unit,
+                    Exp::I128(64),
+                    Exp::Bits(B64::from_u64(address)),
+                    Exp::I128(8),
+                    Exp::Bits(B64::from_u64(data)),
+                ],
+                SourceLoc::unknown(),
+            )
+        };
+        let mut stp_body = vec![
+            Instr::Decl(ok, Ty::Bool, SourceLoc::unknown()),
+            Instr::Call(Loc::Id(RETURN), false, cycle_count, vec![Exp::Unit], SourceLoc::unknown()),
+        ];
+        for _ in 0..branches {
+            let next = stp_body.len() + 2;
+            stp_body.push(Instr::Decl(b, Ty::Bool, SourceLoc::unknown()));
+            stp_body.push(Instr::Jump(Exp::Id(b), next, SourceLoc::unknown()))
+        }
+        stp_body.extend([write(0x600000, 1), write(0x600008, 2), Instr::End]);
+        let unit_body = vec![Instr::Copy(Loc::Id(RETURN), Exp::Unit, SourceLoc::unknown()), Instr::End];
+        let mut defs: Vec<Def<Name, B129>> = vec![
+            Def::Register(pc, Ty::Bits(64), vec![]),
+            Def::Extern(cycle_count, false, "cycle_count".to_string(), vec![Ty::Unit], Ty::Unit),
+            Def::Extern(
+                write_mem,
+                false,
+                "write_mem".to_string(),
+                vec![Ty::Unit, Ty::I128, Ty::Bits(64), Ty::I128, Ty::Bits(64)],
+                Ty::Bool,
+            ),
+            Def::Val(main, vec![Ty::Unit], Ty::Unit),
+            Def::Fn(main, vec![x], unit_body.clone()),
+            Def::Val(footprint, vec![Ty::Bits(32)], Ty::Unit),
+            Def::Fn(footprint, vec![x], unit_body),
+            Def::Val(stp, vec![Ty::Unit], Ty::Unit),
+            Def::Fn(stp, vec![x], stp_body),
+        ];
+        let type_info = IRTypeInfo::new(&defs);
+        let isa_config: ISAConfig<B129> = ISAConfig::parse(CONFIG, None, &symtab, &type_info).unwrap();
+        let litmus = Litmus::parse(STP, &symtab, &type_info, &isa_config).unwrap();
+        let iarch =
+            initialize_architecture(&mut defs, symtab, type_info, &isa_config, AssertionMode::Optimistic, false);
+        let arch = InitArchWithConfig::from_initialized(&iarch, &isa_config);
+        f(&arch, &litmus)
+    }
+
+    /// The (address, bytes) of each memory access in the traces of each thread
+    fn accesses(threads: &[Vec<EvPath<B129>>]) -> Vec<Vec<Vec<(u64, u32)>>> {
+        threads
+            .iter()
+            .map(|paths| {
+                paths
+                    .iter()
+                    .map(|events| {
+                        events
+                            .iter()
+                            .filter_map(|ev| match ev {
+                                Event::WriteMem { address: Val::Bits(bv), bytes, .. } => Some((bv.lower_u64(), *bytes)),
+                                _ => None,
+                            })
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_multi_access_merge() {
+        with_stp_arch(|arch, litmus| {
+            let setup = |policy| {
+                let setup =
+                    run_litmus_setup::<_, _, ()>(&opts(policy), litmus, arch, None, |ev| is_candidate_event(true, ev))
+                        .unwrap();
+                accesses(&setup.threads)
+            };
+            assert_eq!(setup(MultiAccessPolicy::Merge), vec![vec![vec![(0x600000, 16)]]]);
+            assert_eq!(setup(MultiAccessPolicy::Linked), vec![vec![vec![(0x600000, 8), (0x600008, 8)]]]);
+            assert_eq!(setup(MultiAccessPolicy::Unlinked), vec![vec![vec![(0x600000, 8), (0x600008, 8)]]]);
+        })
+    }
+
+    #[test]
+    fn test_memory_budget_paths() {
+        let dir = std::env::temp_dir().join(format!("isla-test-litmus-budget-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (unbounded, bounded) = with_branching_stp_arch(8, |arch, litmus| {
+            let setup = |memory_budget| {
+                let opts = LitmusRunOpts { num_threads: 2, memory_budget, ..opts(MultiAccessPolicy::Unlinked) };
+                let setup =
+                    run_litmus_setup::<_, _, ()>(&opts, litmus, arch, None, |ev| is_candidate_event(true, ev)).unwrap();
+                accesses(&setup.threads)
+            };
+            (setup(None), setup(Some(MemoryBudget { max_queued_tasks: 2, dir: dir.clone() })))
+        });
+        // Every spilled task and trace file has been removed
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(unbounded[0].len(), 256);
+        assert_eq!(unbounded, bounded)
+    }
+
+    /// The definition of the si relation in the SMT generated for the
+    /// single candidate execution of the stp test
+    fn si_relation(policy: MultiAccessPolicy) -> String {
+        let cache = std::env::temp_dir().join(format!("isla-test-si-{}-{}", policy, std::process::id()));
+        std::fs::create_dir_all(&cache).unwrap();
+        with_stp_arch(|arch, litmus| {
+            // The solver may not be installed, so we ignore the result
+            // and only look at the SMT written for it
+            let _ = smt_output_per_candidate::<_, _, _, ExecError>(
+                "test",
+                &opts(policy),
+                litmus,
+                &GraphOpts::default(),
+                arch,
+                arch,
+                &SexpArena::new(),
+                &[],
+                &memory_model::Symtab::new(),
+                &memory_model::Accessors::default(),
+                &[],
+                None,
+                false,
+                &cache,
+                &|_, _, _, _, _, _| Ok(()),
+            );
+        });
+        let smt = std::fs::read_dir(&cache)
+            .unwrap()
+            .map(|entry| std::fs::read_to_string(entry.unwrap().path()).unwrap())
+            .find(|contents| contents.contains("(declare-fun si "))
+            .unwrap();
+        std::fs::remove_dir_all(&cache).unwrap();
+        let start = smt.find("(declare-fun si ").unwrap();
+        let len = smt[start..].find("\n\n").unwrap();
+        smt[start..start + len].to_string()
+    }
+
+    #[test]
+    fn test_multi_access_linked() {
+        let linked = si_relation(MultiAccessPolicy::Linked);
+        let unlinked = si_relation(MultiAccessPolicy::Unlinked);
+        let merged = si_relation(MultiAccessPolicy::Merge);
+        // The two writes of the store pair are related in both directions
+        assert!(linked.contains("(= ev1 W1_1_0)\n      (= ev2 W1_3_0)"));
+        assert!(linked.contains("(= ev1 W1_3_0)\n      (= ev2 W1_1_0)"));
+        assert!(unlinked.ends_with("false)))"));
+        assert_eq!(unlinked, merged);
+    }
+
+    fn execution_events() -> Vec<Event<B64>> {
+        let bits = |n| Val::Bits(B64::from_u64(n));
+        let read = |opts| Event::ReadMem {
+            value: bits(1),
+            read_kind: bits(0),
+            address: bits(0x1000),
+            bytes: 4,
+            tag_value: None,
+            opts,
+            region: "default",
+        };
+        vec![
+            Event::Smt(
+                smtlib::Def::DeclareConst(Sym::from_u32(0), smtlib::Ty::Bool),
+                Default::default(),
+                SourceLoc::unknown(),
+            ),
+            Event::Function { name: Name::from_u32(0), call: true },
+            read(ReadOpts::ifetch()),
+            Event::Instr(bits(0xd503201f)),
+            Event::SourceLine(SourceLoc::unknown()),
+            Event::ReadReg(Name::from_u32(1), Vec::new(), bits(2)),
+            Event::AssumeReg(Name::from_u32(1), Vec::new(), bits(2)),
+            Event::MarkReg { regs: vec![Name::from_u32(1)], mark: "ignore_edge".to_string() },
+            read(ReadOpts::default()),
+            Event::WriteMem {
+                value: Sym::from_u32(0),
+                write_kind: bits(0),
+                address: bits(0x1000),
+                data: bits(3),
+                bytes: 4,
+                tag_value: None,
+                opts: isla_lib::smt::WriteOpts::default(),
+                region: "default",
+            },
+            Event::WriteReg(Name::from_u32(1), Vec::new(), bits(3)),
+            Event::Branch { address: bits(0x2000) },
+            Event::Function { name: Name::from_u32(0), call: false },
+            Event::Cycle,
+        ]
+    }
+
+    fn retained(ignore_ifetch: bool, filter: Option<EventFilter>) -> (usize, Vec<String>) {
+        let ctx = Context::new(Config::new());
+        let mut solver = Solver::<B64>::new(&ctx);
+        solver.set_event_filter(filter);
+        for event in execution_events() {
+            solver.add_event(event)
+        }
+        let recorded = solver.trace().to_vec();
+        let kept = recorded.iter().filter(|ev| is_candidate_event(ignore_ifetch, ev)).map(|ev| format!("{:?}", ev));
+        (recorded.len(), kept.collect())
+    }
+
+    #[test]
+    fn record_filter_agrees_with_candidate_events() {
+        for ignore_ifetch in [false, true] {
+            let (unfiltered_len, unfiltered) = retained(ignore_ifetch, None);
+            let (filtered_len, filtered) = retained(ignore_ifetch, Some(candidate_record_filter(ignore_ifetch)));
+            assert_eq!(unfiltered, filtered);
+            assert!(filtered_len < unfiltered_len)
+        }
+    }
+}
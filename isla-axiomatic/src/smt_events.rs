@@ -578,6 +578,7 @@ pub fn smt_of_candidate<B: BV>(
     litmus: &Litmus<B>,
     ignore_ifetch: bool,
     armv8_page_tables: bool,
+    link_multi_accesses: bool,
     footprints: &HashMap<B, Footprint>,
     memory: &Memory<B>,
     initial_physical_addrs: &HashMap<u64, u64>,
@@ -919,6 +920,12 @@ pub fn smt_of_candidate<B: BV>(
 
     smt_basic_rel(|ev1, ev2| intra_instruction_ordered(ev1, ev2), events).write_rel(output, "iio")?;
 
+    if link_multi_accesses {
+        smt_basic_rel(same_instruction, events).write_rel(output, "si")?;
+    } else {
+        smt_empty().write_rel(output, "si")?;
+    }
+
     smt_basic_rel(internal, events).write_rel(output, "int")?;
     smt_basic_rel(external, events).write_rel(output, "ext")?;
     smt_basic_rel(same_translation, events).write_rel(output, "same-translation")?;
@@ -219,6 +219,16 @@ impl<'s, 'ev, B: BV> Model<'s, 'ev, B> {
         }
     }
 
+    /// Returns the number of parameters a function in the model takes,
+    /// or None if the name is not defined by the model
+    pub fn arity(&self, f: &str) -> Option<usize> {
+        match self.functions.get(f)? {
+            SmtFn::Fixed(SexpVal::Relation(_)) => Some(2),
+            SmtFn::Fixed(_) => Some(0),
+            SmtFn::Lambda(lf) => Some(lf.params.len()),
+        }
+    }
+
     /// Gives an entire relation as a Vec<(event,event)>
     pub fn interpret_rel(&mut self, f: &str) -> Result<Vec<(&'ev str, &'ev str)>, InterpretError<'s>> {
         let evs: Vec<&str> = self.env.events.keys().copied().collect();
@@ -0,0 +1,198 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2024 Alasdair Armstrong
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// 1. Redistributions of source code must retain the above copyright
+// notice, this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright
+// notice, this list of conditions and the following disclaimer in the
+// documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Per-phase timing and progress reporting for running litmus
+//! tests. A test is executed symbolically, has footprint analysis
+//! run over its traces, is split into candidate executions which are
+//! each checked against the memory model, and finally has the
+//! resulting graphs rendered. Each phase can take a long time, so we
+//! record how long each one took, and optionally print coarse
+//! progress lines as they advance.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The phases of running a single litmus test, in the order they
+/// happen
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Phase {
+    Execution,
+    Footprint,
+    Candidates,
+    Checking,
+    Graphs,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Phase::*;
+        // Use pad so the name can be aligned in tables
+        f.pad(match self {
+            Execution => "execution",
+            Footprint => "footprint",
+            Candidates => "candidates",
+            Checking => "checking",
+            Graphs => "graphs",
+        })
+    }
+}
+
+/// The wall-clock time spent in each phase. Phases are recorded in
+/// the order they finish, and a phase recorded more than once has its
+/// durations added together.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PhaseTimings {
+    pub phases: Vec<(Phase, Duration)>,
+}
+
+impl PhaseTimings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, phase: Phase, duration: Duration) {
+        match self.phases.iter_mut().find(|(p, _)| *p == phase) {
+            Some((_, total)) => *total += duration,
+            None => self.phases.push((phase, duration)),
+        }
+    }
+
+    /// Run `f`, recording the time it takes against `phase`
+    pub fn time<A, F: FnOnce() -> A>(&mut self, phase: Phase, f: F) -> A {
+        let now = Instant::now();
+        let result = f();
+        self.record(phase, now.elapsed());
+        result
+    }
+
+    pub fn get(&self, phase: Phase) -> Option<Duration> {
+        self.phases.iter().find(|(p, _)| *p == phase).map(|(_, duration)| *duration)
+    }
+
+    pub fn total(&self) -> Duration {
+        self.phases.iter().map(|(_, duration)| *duration).sum()
+    }
+
+    /// Append the phases from another set of timings
+    pub fn extend(&mut self, other: &PhaseTimings) {
+        for (phase, duration) in &other.phases {
+            self.record(*phase, *duration)
+        }
+    }
+}
+
+/// Prints progress lines to stderr, at most once per `interval`
+/// unless forced, so long running phases report how far they have got
+/// without flooding the log.
+pub struct ProgressLine {
+    enabled: bool,
+    interval: Duration,
+    last: Mutex<Option<Instant>>,
+}
+
+impl ProgressLine {
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+    pub fn new(enabled: bool) -> Self {
+        ProgressLine { enabled, interval: Self::DEFAULT_INTERVAL, last: Mutex::new(None) }
+    }
+
+    pub fn with_interval(self, interval: Duration) -> Self {
+        ProgressLine { interval, ..self }
+    }
+
+    /// Decide whether a line should be printed now, and if so note the
+    /// time. A forced line is always printed (when enabled), which is
+    /// used for the final line of each phase.
+    fn should_report(&self, force: bool) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        let mut last = self.last.lock().unwrap();
+        let now = Instant::now();
+        match *last {
+            Some(time) if !force && now.duration_since(time) < self.interval => false,
+            _ => {
+                *last = Some(now);
+                true
+            }
+        }
+    }
+
+    pub fn report<F: FnOnce() -> String>(&self, force: bool, message: F) {
+        if self.should_report(force) {
+            eprintln!("{}", message())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phase_timings() {
+        let mut timings = PhaseTimings::new();
+        timings.record(Phase::Execution, Duration::from_millis(20));
+        timings.record(Phase::Checking, Duration::from_millis(5));
+        timings.record(Phase::Execution, Duration::from_millis(10));
+
+        assert_eq!(timings.get(Phase::Execution), Some(Duration::from_millis(30)));
+        assert_eq!(timings.get(Phase::Graphs), None);
+        assert_eq!(timings.total(), Duration::from_millis(35));
+        assert_eq!(timings.phases.iter().map(|(p, _)| *p).collect::<Vec<_>>(), vec![Phase::Execution, Phase::Checking]);
+    }
+
+    #[test]
+    fn test_timed_phases_sum_to_total() {
+        let start = Instant::now();
+        let mut timings = PhaseTimings::new();
+        timings.time(Phase::Execution, || std::thread::sleep(Duration::from_millis(20)));
+        timings.time(Phase::Checking, || std::thread::sleep(Duration::from_millis(10)));
+        let total = start.elapsed();
+
+        assert!(timings.get(Phase::Execution).unwrap() >= Duration::from_millis(20));
+        assert!(timings.get(Phase::Checking).unwrap() >= Duration::from_millis(10));
+        assert!(timings.total() <= total);
+        assert!(total - timings.total() < Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_progress_rate_limit() {
+        let progress = ProgressLine::new(true).with_interval(Duration::from_secs(3600));
+        assert!(progress.should_report(false));
+        assert!(!progress.should_report(false));
+        assert!(progress.should_report(true));
+
+        let disabled = ProgressLine::new(false);
+        assert!(!disabled.should_report(true));
+    }
+}
@@ -411,16 +411,16 @@ fn transitive_closure_for(output: &mut dyn Write, id: &str, tc_id: &str) -> Resu
     writeln!(output, "(declare-fun |{}| (Event Event) Bool)", tc_id)?;
     writeln!(
         output,
-        "(assert (forall ((ev1 Event) (ev2 Event))\n  \
-         (=> (|{}| ev1 ev2) (|{}| ev1 ev2))))",
-        id, tc_id
+        "(assert (! (forall ((ev1 Event) (ev2 Event))\n  \
+         (=> (|{}| ev1 ev2) (|{}| ev1 ev2))) :named |{}:base|))",
+        id, tc_id, tc_id
     )?;
     writeln!(
         output,
-        "(assert (forall ((ev1 Event) (ev2 Event) (ev3 Event))\n  \
+        "(assert (! (forall ((ev1 Event) (ev2 Event) (ev3 Event))\n  \
          (=> (and (|{}| ev1 ev2) (|{}| ev2 ev3))\n      \
-         (|{}| ev1 ev3))))",
-        tc_id, tc_id, tc_id
+         (|{}| ev1 ev3))) :named |{}:trans|))",
+        tc_id, tc_id, tc_id, tc_id
     )?;
     Ok(())
 }
@@ -429,27 +429,31 @@ fn reflexive_transitive_closure_for(output: &mut dyn Write, id: &str, rtc_id: &s
     writeln!(output, "(declare-fun |{}| (Event Event) Bool)", rtc_id)?;
     writeln!(
         output,
-        "(assert (forall ((ev1 Event) (ev2 Event))\n  \
-         (=> (|{}| ev1 ev2) (|{}| ev1 ev2))))",
-        id, rtc_id
+        "(assert (! (forall ((ev1 Event) (ev2 Event))\n  \
+         (=> (|{}| ev1 ev2) (|{}| ev1 ev2))) :named |{}:base|))",
+        id, rtc_id, rtc_id
     )?;
     writeln!(
         output,
-        "(assert (forall ((ev1 Event) (ev2 Event))\n  \
-         (=> (|{}| ev1 ev2) (and (|{}| ev1 ev1) (|{}| ev2 ev2)))))",
-        id, rtc_id, rtc_id
+        "(assert (! (forall ((ev1 Event) (ev2 Event))\n  \
+         (=> (|{}| ev1 ev2) (and (|{}| ev1 ev1) (|{}| ev2 ev2)))) :named |{}:refl|))",
+        id, rtc_id, rtc_id, rtc_id
     )?;
     writeln!(
         output,
-        "(assert (forall ((ev1 Event) (ev2 Event) (ev3 Event))\n  \
+        "(assert (! (forall ((ev1 Event) (ev2 Event) (ev3 Event))\n  \
          (=> (and (|{}| ev1 ev2) (|{}| ev2 ev3))\n      \
-         (|{}| ev1 ev3))))",
-        rtc_id, rtc_id, rtc_id
+         (|{}| ev1 ev3))) :named |{}:trans|))",
+        rtc_id, rtc_id, rtc_id, rtc_id
     )?;
     Ok(())
 }
 
-/// Compile all the definitions in a cat model.
+/// Compile all the definitions in a cat model. Every assertion is
+/// named after the cat definition or check it comes from, e.g.
+/// `def:po-loc` or `assert:coherence`, so that (with
+/// `:produce-unsat-cores` set) the unsat core of a failed check lists
+/// the definitions which contribute to it.
 pub fn compile_cat(output: &mut dyn Write, cat: &Cat<Ty>) -> Result<(), Box<dyn Error>> {
     let mut known_empty = HashSet::new();
 
@@ -463,9 +467,9 @@ pub fn compile_cat(output: &mut dyn Write, cat: &Cat<Ty>) -> Result<(), Box<dyn
                     if sexp.is_false() {
                         known_empty.insert(id.clone());
                     }
-                    writeln!(output, "(assert (forall {}\n  (= (|{}| {})", exp_params(exp), id, exp_args(exp))?;
+                    writeln!(output, "(assert (! (forall {}\n  (= (|{}| {})", exp_params(exp), id, exp_args(exp))?;
                     sexp.write_to(output, true, 4, false)?;
-                    writeln!(output, ")))\n")?;
+                    writeln!(output, ")) :named |def:{}|))\n", id)?;
                 }
             }
 
@@ -482,29 +486,49 @@ pub fn compile_cat(output: &mut dyn Write, cat: &Cat<Ty>) -> Result<(), Box<dyn
                 writeln!(output, ")")?;
 
                 match check {
-                    Check::Empty => {
-                        writeln!(output, "(assert (forall ((ev1 Event) (ev2 Event)) (not (|check:{}| ev1 ev2))))", id)?
-                    }
+                    Check::Empty => writeln!(
+                        output,
+                        "(assert (! (forall ((ev1 Event) (ev2 Event)) (not (|check:{}| ev1 ev2))) :named |assert:{}|))",
+                        id, id
+                    )?,
                     Check::NonEmpty => {
                         writeln!(output, "(declare-const |ne1:{}| Event)", id)?;
                         writeln!(output, "(declare-const |ne2:{}| Event)", id)?;
-                        writeln!(output, "(assert (|check:{}| |ne1:{}| |ne2:{}|))", id, id, id)?;
-                    }
-                    Check::Irreflexive => {
-                        writeln!(output, "(assert (forall ((ev1 Event)) (not (|check:{}| ev1 ev1))))", id)?
+                        writeln!(
+                            output,
+                            "(assert (! (|check:{}| |ne1:{}| |ne2:{}|) :named |assert:{}|))",
+                            id, id, id, id
+                        )?;
                     }
+                    Check::Irreflexive => writeln!(
+                        output,
+                        "(assert (! (forall ((ev1 Event)) (not (|check:{}| ev1 ev1))) :named |assert:{}|))",
+                        id, id
+                    )?,
                     Check::NonIrreflexive => {
                         writeln!(output, "(declare-const |some:{}| Event)", id)?;
-                        writeln!(output, "(assert (|check:{}| |some:{}| |some:{}|))", id, id, id)?;
+                        writeln!(
+                            output,
+                            "(assert (! (|check:{}| |some:{}| |some:{}|) :named |assert:{}|))",
+                            id, id, id, id
+                        )?;
                     }
                     Check::Acyclic => {
                         transitive_closure_for(output, &format!("check:{}", id), &format!("acyclic:{}", id))?;
-                        writeln!(output, "(assert (forall ((ev1 Event)) (not (|acyclic:{}| ev1 ev1))))", id)?;
+                        writeln!(
+                            output,
+                            "(assert (! (forall ((ev1 Event)) (not (|acyclic:{}| ev1 ev1))) :named |assert:{}|))",
+                            id, id
+                        )?;
                     }
                     Check::NonAcyclic => {
                         transitive_closure_for(output, &format!("check:{}", id), &format!("non-acyclic:{}", id))?;
                         writeln!(output, "(declare-const |some:{}| Event)", id)?;
-                        writeln!(output, "(assert (|non-acyclic:{}| |some:{}| |some:{}|))", id, id, id)?;
+                        writeln!(
+                            output,
+                            "(assert (! (|non-acyclic:{}| |some:{}| |some:{}|) :named |assert:{}|))",
+                            id, id, id, id
+                        )?;
                     }
                 }
 
@@ -296,10 +296,12 @@ where
 
     fn set_slice(self, n: u32, update: Self) -> Self;
 
+    /// Extract the bits from `high` down to `low` inclusive. Returns
+    /// `None` if `high < low` or `high` is out of range, so the
+    /// result is never zero-width.
     fn extract(self, high: u32, low: u32) -> Option<Self> {
-        let len = (high - low) + 1;
-        if low <= high && high <= self.len() {
-            self.slice(low, len)
+        if low <= high && high < self.len() {
+            self.slice(low, (high - low) + 1)
         } else {
             None
         }
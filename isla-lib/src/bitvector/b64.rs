@@ -415,6 +415,12 @@ mod tests {
         assert!(sbits.extract(31, 0) == Some(B64::new(0x1234_ABCD, 32)));
         assert!(sbits.extract(63, 32) == Some(B64::new(0xCAFE_F00D, 32)));
         assert!(sbits.extract(7, 0) == Some(B64::new(0xCD, 8)));
+        assert!(sbits.extract(63, 0) == Some(sbits));
+        assert!(sbits.extract(64, 0).is_none());
+        assert!(sbits.extract(0, 1).is_none());
+        assert!(B64::new(0b101, 3).extract(3, 3).is_none());
+        assert!(B64::new(0b101, 3).extract(2, 2) == Some(B64::new(1, 1)));
+        assert!(B64::new(0, 0).extract(0, 0).is_none());
     }
 
     #[test]
@@ -41,7 +41,7 @@ use std::sync::Arc;
 use toml::Value;
 
 use crate::bitvector::BV;
-use crate::ir::{IRTypeInfo, Loc, Name, Reset, Symtab, URVal, Val};
+use crate::ir::{IRTypeInfo, Loc, Name, OutcomeStub, Reset, Symtab, URVal, Val};
 use crate::ir_lexer::new_ir_lexer;
 use crate::primop_util::symbolic_from_typedefs;
 use crate::smt::smtlib::Exp;
@@ -503,23 +503,23 @@ fn get_translation_function(config: &Value, symtab: &Symtab) -> Result<Option<Na
     }
 }
 
-fn get_trace_functions(config: &Value, symtab: &Symtab) -> Result<HashSet<Name>, String> {
-    let trace = config.get("trace");
+fn get_functions_set(config: &Value, set_name: &str, symtab: &Symtab) -> Result<HashSet<Name>, String> {
+    let functions = config.get(set_name);
 
-    if let Some(trace) = trace {
-        if let Some(trace) = trace.as_array() {
-            trace
+    if let Some(functions) = functions {
+        if let Some(functions) = functions.as_array() {
+            functions
                 .iter()
                 .map(|function| {
                     if let Some(function) = function.as_str().and_then(|f| symtab.get(&zencode::encode(f))) {
                         Ok(function)
                     } else {
-                        Err(format!("Could not find function {} when parsing trace in configuration", function))
+                        Err(format!("Could not find function {} when parsing {} in configuration", function, set_name))
                     }
                 })
                 .collect()
         } else {
-            Err("trace should be a list of function names".to_string())
+            Err(format!("{} should be a list of function names", set_name))
         }
     } else {
         Ok(HashSet::new())
@@ -591,6 +591,60 @@ fn get_default_sizeof(config: &Value) -> Result<u32, String> {
     }
 }
 
+/// Read the `[stubs]` section, which maps functions to the behaviour
+/// used when they are called without a body or primop, for example:
+///
+/// ```toml
+/// [stubs]
+/// read_physical_attributes = { constant = "0b00" }
+/// get_random_seed = "symbolic"
+/// choose_feature = { fork = [true, false] }
+/// ```
+fn get_outcome_stubs<B: BV>(
+    config: &Value,
+    symtab: &Symtab,
+    type_info: &IRTypeInfo,
+) -> Result<HashMap<Name, OutcomeStub<B>>, String> {
+    let Some(stubs) = config.get("stubs") else { return Ok(HashMap::new()) };
+
+    let Some(stubs) = stubs.as_table() else {
+        return Err("stubs should be a table of <function> = <behaviour> pairs".to_string());
+    };
+
+    stubs
+        .iter()
+        .map(|(function, behaviour)| {
+            let Some(name) = symtab.get(&zencode::encode(function)) else {
+                return Err(format!("Could not find function {} when parsing stubs in configuration", function));
+            };
+
+            let stub = match behaviour {
+                Value::String(s) if s == "symbolic" => OutcomeStub::Symbolic,
+                Value::Table(table) if table.len() == 1 => match table.iter().next() {
+                    Some((key, value)) if key == "constant" => {
+                        OutcomeStub::Constant(from_toml_value(value, symtab, type_info)?)
+                    }
+                    Some((key, Value::Array(values))) if key == "fork" && !values.is_empty() => OutcomeStub::Fork(
+                        values
+                            .iter()
+                            .map(|value| from_toml_value(value, symtab, type_info))
+                            .collect::<Result<_, _>>()?,
+                    ),
+                    _ => return Err(format!("Invalid behaviour for stubs.{} in configuration", function)),
+                },
+                _ => {
+                    return Err(format!(
+                        "stubs.{} should be \"symbolic\", {{ constant = <value> }}, or {{ fork = [<value>, ...] }}",
+                        function
+                    ))
+                }
+            };
+
+            Ok((name, stub))
+        })
+        .collect()
+}
+
 pub struct ISAConfig<B> {
     /// The identifier for the program counter register
     pub pc: Name,
@@ -635,6 +689,8 @@ pub struct ISAConfig<B> {
     pub reset_constraints: Vec<Exp<Loc<String>>>,
     /// Constant primops
     pub const_primops: HashMap<String, Reset<B>>,
+    /// Behaviours for functions without a body or primop
+    pub outcome_stubs: HashMap<Name, OutcomeStub<B>>,
     /// Assumptions to use about function behaviour
     pub function_assumptions: Vec<(String, Vec<Option<Exp<Loc<String>>>>, Exp<Loc<String>>)>,
     /// Register synonyms to rename
@@ -649,6 +705,8 @@ pub struct ISAConfig<B> {
     pub probe_functions: HashSet<Name>,
     /// Trace calls to functions in this set
     pub trace_functions: HashSet<Name>,
+    /// Functions without side effects, whose results can be memoized
+    pub pure_functions: HashSet<Name>,
     /// Address translation function
     pub translation_function: Option<Name>,
     /// The abstract events that should be included in program order
@@ -674,7 +732,7 @@ impl<B: BV> ISAConfig<B> {
         // Insert the translation_function into the set of functions
         // to trace, if it is provided by the config
         let translation_function = get_translation_function(&config, symtab)?;
-        let mut trace_functions = get_trace_functions(&config, symtab)?;
+        let mut trace_functions = get_functions_set(&config, "trace", symtab)?;
         if let Some(f) = translation_function {
             trace_functions.insert(f);
         }
@@ -704,6 +762,7 @@ impl<B: BV> ISAConfig<B> {
             reset_registers: get_reset_registers(&config, symtab, type_info)?,
             reset_constraints: get_reset_constraints(&config)?,
             const_primops: get_const_primops(&config, symtab, type_info)?,
+            outcome_stubs: get_outcome_stubs(&config, symtab, type_info)?,
             function_assumptions: Vec::new(),
             register_renames: get_register_renames(&config, symtab)?,
             ignored_registers: get_registers_set(&config, "ignore", symtab)?,
@@ -711,6 +770,7 @@ impl<B: BV> ISAConfig<B> {
             probes: HashSet::new(),
             probe_functions: HashSet::new(),
             trace_functions,
+            pure_functions: get_functions_set(&config, "pure", symtab)?,
             translation_function,
             in_program_order: get_in_program_order(&config, symtab)?,
             default_sizeof: get_default_sizeof(&config)?,
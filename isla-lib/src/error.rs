@@ -51,21 +51,40 @@ impl IslaError for VoidError {
     }
 }
 
+/// The function and instruction that was executing when an error
+/// occurred (see [ExecError::executing]).
+#[derive(Clone, Debug)]
+pub struct Executing {
+    pub function: String,
+    pub pc: usize,
+}
+
+impl fmt::Display for Executing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at instruction {}", self.function, self.pc)
+    }
+}
+
 #[derive(Debug)]
 pub enum ExecError {
     Type(String, SourceLoc),
-    VariableNotFound(String),
+    /// An identifier did not resolve to any local, global, register
+    /// or enumeration member. This usually means a let binding is
+    /// missing from the configuration.
+    VariableNotFound(String, Option<Executing>),
     Unimplemented,
     AssertionFailure(Option<String>, SourceLoc),
-    NoFunction(String, SourceLoc),
+    /// A call to a function which has no definition
+    NoFunction(String, SourceLoc, Option<Executing>),
     Overflow,
     /// SMTLIB only supports fixed-length bitvectors. This error is
     /// raised if a bitvector width would become symbolic.
     SymbolicLength(&'static str, SourceLoc),
     /// Returned when there is no symbolic representation for a
-    /// specific type. Certain types like strings are always assumed
-    /// to be concrete.
-    NoSymbolicType,
+    /// specific type, such as lists. Contains the type, and what was
+    /// being initialized with a symbolic value when this is known
+    /// (see [ExecError::initializing]).
+    NoSymbolicType(String, Option<String>),
     /// Used for cases that should be unreachable (i.e. are definite
     /// errors).
     Unreachable(String),
@@ -84,19 +103,69 @@ pub enum ExecError {
     /// Execution stopped because this function is in the stop_functions set
     Stopped(String),
     PCLimitReached(u64),
+    /// The path executed more instructions than the limit set for its
+    /// task, so was cut short
+    Fuel,
+    /// The path ran for longer than the time limit set for its task
+    PathTimeout,
+    /// A function jumped backwards more times than the limit set for
+    /// its task, with the function name and pc of the last jump
+    LoopLimit(String, usize, SourceLoc),
+    /// A monomorphized variable had more possible values than the
+    /// limit set for its task, with the variable and the limit
+    MonomorphizeLimit(String, u32, SourceLoc),
+    /// A function was called while as many calls to it as the limit
+    /// set for its task were already on the call stack, with the
+    /// function name and the limit
+    RecursionLimit(String, u32, SourceLoc),
+    /// A register write hook aborted the path with this message
+    RegisterHook(String, SourceLoc),
+    /// A register was read before being initialized while the task's
+    /// [crate::register::UninitPolicy] is `Error`
+    UninitializedRegister(String, SourceLoc),
+    /// The worker thread executing the path panicked with this message
+    WorkerPanic(String),
     InconsistentRegisterReset,
     BadInterrupt(&'static str),
 }
 
+impl ExecError {
+    /// Add a description of what was being initialized, e.g.
+    /// `register PSTATE`, to a [ExecError::NoSymbolicType] error
+    /// which does not already have one.
+    pub fn initializing(self, what: impl FnOnce() -> String) -> Self {
+        match self {
+            ExecError::NoSymbolicType(ty, None) => ExecError::NoSymbolicType(ty, Some(what())),
+            err => err,
+        }
+    }
+
+    /// Record which function and instruction were executing for
+    /// errors that do not already say so.
+    pub fn executing(self, function: impl FnOnce() -> String, pc: usize) -> Self {
+        use ExecError::*;
+        match self {
+            VariableNotFound(v, None) => VariableNotFound(v, Some(Executing { function: function(), pc })),
+            NoFunction(func, info, None) => NoFunction(func, info, Some(Executing { function: function(), pc })),
+            err => err,
+        }
+    }
+}
+
 impl IslaError for ExecError {
     fn source_loc(&self) -> SourceLoc {
         use ExecError::*;
         match self {
             Type(_, info)
             | AssertionFailure(_, info)
-            | NoFunction(_, info)
+            | NoFunction(_, info, _)
             | SymbolicLength(_, info)
-            | MatchFailure(info) => *info,
+            | MatchFailure(info)
+            | LoopLimit(_, _, info)
+            | MonomorphizeLimit(_, _, info)
+            | RecursionLimit(_, _, info)
+            | RegisterHook(_, info)
+            | UninitializedRegister(_, info) => *info,
             _ => SourceLoc::unknown(),
         }
     }
@@ -107,14 +176,19 @@ impl fmt::Display for ExecError {
         use ExecError::*;
         match self {
             Type(msg, _) => write!(f, "Type error: {}", msg),
-            VariableNotFound(v) => write!(f, "Variable {} not found", v),
+            VariableNotFound(v, None) => write!(f, "Variable {} not found", v),
+            VariableNotFound(v, Some(executing)) => write!(f, "Variable {} not found in {}", v, executing),
             Unimplemented => write!(f, "Unimplemented"),
             AssertionFailure(None, _) => write!(f, "Assertion failure"),
             AssertionFailure(Some(msg), _) => write!(f, "Assertion failure: {}", msg),
-            NoFunction(func, _) => write!(f, "Function {} does not exist", func),
+            NoFunction(func, _, None) => write!(f, "Function {} does not exist", func),
+            NoFunction(func, _, Some(executing)) => {
+                write!(f, "Function {} called from {} does not exist", func, executing)
+            }
             Overflow => write!(f, "Integer overflow"),
             SymbolicLength(func, _) => write!(f, "Symbolic (bit)vector length in {}", func),
-            NoSymbolicType => write!(f, "No symbolic representation for type"),
+            NoSymbolicType(ty, None) => write!(f, "No symbolic representation for type {}", ty),
+            NoSymbolicType(ty, Some(what)) => write!(f, "{} has unsupported type {}", what, ty),
             Unreachable(msg) => write!(f, "Unreachable: {}", msg),
             Unmapped => write!(f, "Unmapped memory"),
             BadRead(msg) => write!(f, "Bad read {}", msg),
@@ -128,6 +202,16 @@ impl fmt::Display for ExecError {
             Z3Unknown => write!(f, "SMT solver returned unknown"),
             Stopped(func) => write!(f, "Execution stopped at {}", func),
             PCLimitReached(pc_value) => write!(f, "Executed instruction at {} more than specified limit", pc_value),
+            Fuel => write!(f, "Path truncated after executing the maximum number of instructions"),
+            PathTimeout => write!(f, "Path timed out"),
+            LoopLimit(func, pc, _) => write!(f, "Loop limit reached by jump at {} in {}", pc, func),
+            MonomorphizeLimit(id, limit, _) => {
+                write!(f, "Variable {} has more than {} values to monomorphize", id, limit)
+            }
+            RecursionLimit(func, limit, _) => write!(f, "Recursion limit of {} calls reached by {}", limit, func),
+            RegisterHook(msg, _) => write!(f, "Register write hook aborted: {}", msg),
+            UninitializedRegister(reg, _) => write!(f, "Read of uninitialized register {}", reg),
+            WorkerPanic(msg) => write!(f, "Worker thread panicked: {}", msg),
             InconsistentRegisterReset => write!(f, "Inconsistent register reset constraints"),
             BadInterrupt(msg) => write!(f, "Bad task interrupt: {}", msg),
         }
@@ -33,13 +33,17 @@
 
 use crossbeam::deque::{Injector, Steal, Stealer, Worker};
 use crossbeam::queue::SegQueue;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
 use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
 use std::fmt;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::mpsc;
 use std::sync::mpsc::{Receiver, Sender};
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
 use std::time::{Duration, Instant};
 
@@ -48,19 +52,39 @@ use crate::error::{ExecError, IslaError};
 use crate::fraction::Fraction;
 use crate::ir::*;
 use crate::log;
+use crate::memory::FootprintEntry;
 use crate::primop;
 use crate::primop_util::{build_ite, ite_phi, smt_value, symbolic};
 use crate::probe;
+use crate::simplify;
 use crate::smt::smtlib::Def;
 use crate::smt::*;
 use crate::source_loc::SourceLoc;
 use crate::zencode;
 
+mod checkpoint;
+mod coverage;
+mod fork_tree;
 mod frame;
+mod memo;
+mod spill;
+mod stats;
 mod task;
 
-pub use frame::{freeze_frame, unfreeze_frame, Backtrace, Frame, LocalFrame, LocalState};
-use frame::{pop_call_stack, push_call_stack};
+pub use checkpoint::{load_checkpoint_file, load_tasks, save_tasks, CheckpointError, CheckpointOpts};
+pub use coverage::{
+    record_branches, record_source_lines, write_coverage_report, write_source_lines, BranchCoverage, Coverage,
+    SourceLineCounts,
+};
+pub use fork_tree::{write_fork_tree, ForkTree, PathCounts, PathOutcome};
+pub use frame::{
+    freeze_frame, unfreeze_frame, Backtrace, Frame, HookResult, LocalFrame, LocalState, RegisterWriteHook,
+    DEFAULT_MAX_BACKJUMPS, DEFAULT_MAX_MONOMORPHIZE, DEFAULT_MAX_RECURSION, DEFAULT_SHARE_DEPTH,
+};
+use frame::{pop_call_stack, push_call_stack, Stack};
+pub use memo::{Memo, MemoTable};
+pub use spill::{MemoryBudget, TraceFiles, TraceReader};
+pub use stats::{PathStats, PathStatsSummary};
 pub use task::{StopAction, StopConditions, Task, TaskId, TaskInterrupt, TaskState};
 
 /// Gets a value from a variable `Bindings` map. Note that this function is set up to handle the
@@ -85,7 +109,9 @@ fn get_and_initialize<'state, 'ir, B: BV>(
     Ok(match vars.get_mut(&v) {
         Some(uval) => match uval {
             UVal::Uninit(ty) => {
-                let sym = symbolic(ty, shared_state, solver, info)?;
+                let sym = symbolic(ty, shared_state, solver, info).map_err(|err| {
+                    err.initializing(|| format!("variable {}", zencode::decode(shared_state.symtab.to_str(v))))
+                })?;
                 *uval = UVal::Init(sym);
                 if let UVal::Init(value) = uval {
                     Some(value)
@@ -99,6 +125,14 @@ fn get_and_initialize<'state, 'ir, B: BV>(
     })
 }
 
+/// The fields of a nested access like `R.F1.F2` are pushed onto the
+/// accessor from the outermost (`F2`) inwards as the location or
+/// expression is unfolded, but events record the path from the
+/// register itself (`[F1, F2]`)
+fn accessor_path(accessor: &[Accessor]) -> Vec<Accessor> {
+    accessor.iter().rev().cloned().collect()
+}
+
 fn get_id_and_initialize<'state, 'ir, B: BV>(
     id: Name,
     local_state: &'state mut LocalState<'ir, B>,
@@ -112,12 +146,12 @@ fn get_id_and_initialize<'state, 'ir, B: BV>(
 
     Ok(match get_and_initialize(id, &mut local_state.vars, shared_state, solver, info)? {
         Some(value) => Borrowed(value),
-        None => match local_state.regs.get(id, shared_state, solver, info)? {
+        None => match local_state.regs.get(id, local_state.uninit_policy, shared_state, solver, info)? {
             Some(value) => {
                 let symbol = zencode::decode(shared_state.symtab.to_str(id));
                 // HACK: Don't store the entire TLB in the trace
                 if !for_write && symbol != "_TLB" {
-                    solver.add_event(Event::ReadReg(id, accessor.to_vec(), value.clone()));
+                    solver.add_event(Event::ReadReg(id, accessor_path(accessor), value.clone()));
                 }
                 Borrowed(value)
             }
@@ -128,7 +162,9 @@ fn get_id_and_initialize<'state, 'ir, B: BV>(
                         let enum_id = solver.get_enum(*enum_id, *enum_size);
                         Owned(Val::Enum(EnumMember { enum_id, member: *member }))
                     }
-                    None => return Err(ExecError::VariableNotFound(zencode::decode(shared_state.symtab.to_str(id)))),
+                    None => {
+                        return Err(ExecError::VariableNotFound(zencode::decode(shared_state.symtab.to_str(id)), None))
+                    }
                 },
             },
         },
@@ -235,19 +271,20 @@ fn read_register_from_vector<'ir, B: BV>(
     match index {
         ConcreteIndex(i) => {
             // This unwrap should be same as all register references must point to value registers
-            let value = local_state.regs.get(regs[i], shared_state, solver, info)?.unwrap();
+            let value = local_state.regs.get(regs[i], local_state.uninit_policy, shared_state, solver, info)?.unwrap();
             solver.add_event(Event::ReadReg(regs[i], Vec::new(), value.clone()));
             Ok(value.clone())
         }
         SymbolicIndex(i) => {
             // See above case for unwrap safety
-            let mut chain = local_state.regs.get(regs[0], shared_state, solver, info)?.unwrap().clone();
+            let mut chain =
+                local_state.regs.get(regs[0], local_state.uninit_policy, shared_state, solver, info)?.unwrap().clone();
             let mut reg_values = vec![chain.clone()];
             for (j, reg) in regs[1..].iter().enumerate() {
                 let choice = solver.with_def_attrs(DefAttrs::uninteresting(), |solver| {
                     solver.define_const(Eq(Box::new(Var(i)), Box::new(Bits64(B64::new((j + 1) as u64, rib)))), info)
                 });
-                let value = local_state.regs.get(*reg, shared_state, solver, info)?.unwrap();
+                let value = local_state.regs.get(*reg, local_state.uninit_policy, shared_state, solver, info)?.unwrap();
                 reg_values.push(value.clone());
                 chain = solver.with_def_attrs(DefAttrs::uninteresting(), |solver| {
                     build_ite(choice, value, &chain, solver, info)
@@ -322,6 +359,7 @@ fn write_register_from_vector<'ir, B: BV>(
         ConcreteIndex(i) => {
             // This unwrap should be same as all register references must point to value registers
             local_state.regs.assign(regs[i], value.clone(), shared_state);
+            local_state.register_written(regs[i], &value, solver, info)?;
             solver.add_event(Event::WriteReg(regs[i], Vec::new(), value))
         }
         SymbolicIndex(i) => {
@@ -331,7 +369,8 @@ fn write_register_from_vector<'ir, B: BV>(
                 let choice = solver.with_def_attrs(DefAttrs::uninteresting(), |solver| {
                     solver.define_const(Eq(Box::new(Var(i)), Box::new(Bits64(B64::new(j as u64, rib)))), info)
                 });
-                let current_value = local_state.regs.get(*reg, shared_state, solver, info)?.unwrap().clone();
+                let current_value =
+                    local_state.regs.get(*reg, local_state.uninit_policy, shared_state, solver, info)?.unwrap().clone();
                 local_state.regs.assign(
                     *reg,
                     solver.with_def_attrs(DefAttrs::uninteresting(), |solver| {
@@ -433,13 +472,23 @@ fn eval_exp_with_accessor<'state, 'ir, B: BV>(
 
                 Owned(Val::Ctor(ctor_b, v)) if *ctor_a == ctor_b => Owned(*v),
 
-                Borrowed(Val::SymbolicCtor(_, possibilities)) => match possibilities.get(ctor_a) {
-                    Some(v) => Borrowed(v),
+                // Unwrapping a symbolic constructor constrains its tag
+                // to the constructor, which any preceding kind check
+                // will already have implied
+                Borrowed(Val::SymbolicCtor(ctor_sym, possibilities)) => match possibilities.get(ctor_a) {
+                    Some(v) => {
+                        solver
+                            .assert(smtlib::Exp::Eq(Box::new(smtlib::Exp::Var(*ctor_sym)), Box::new(ctor_a.to_smt())));
+                        Borrowed(v)
+                    }
                     None => return Err(ExecError::Type("No possible value for constructor".to_string(), info)),
                 },
 
-                Owned(Val::SymbolicCtor(_, mut possibilities)) => match possibilities.remove(ctor_a) {
-                    Some(v) => Owned(v),
+                Owned(Val::SymbolicCtor(ctor_sym, mut possibilities)) => match possibilities.remove(ctor_a) {
+                    Some(v) => {
+                        solver.assert(smtlib::Exp::Eq(Box::new(smtlib::Exp::Var(ctor_sym)), Box::new(ctor_a.to_smt())));
+                        Owned(v)
+                    }
                     None => return Err(ExecError::Type("No possible value for constructor".to_string(), info)),
                 },
 
@@ -521,8 +570,9 @@ fn assign_with_accessor<'ir, B: BV>(
                 let symbol = zencode::decode(shared_state.symtab.to_str(*id));
                 // HACK: Don't store the entire TLB in the trace
                 if symbol != "_TLB" {
-                    solver.add_event(Event::WriteReg(*id, accessor.to_vec(), v.clone()))
+                    solver.add_event(Event::WriteReg(*id, accessor_path(accessor), v.clone()))
                 }
+                local_state.register_written(*id, &v, solver, info)?;
                 local_state.regs.assign(*id, v, shared_state);
             }
         }
@@ -599,18 +649,46 @@ fn assign<'ir, B: BV>(
 }
 
 #[derive(Copy, Clone, Debug)]
-struct Timeout {
+struct Timeout<'a> {
     start_time: Instant,
     duration: Option<Duration>,
+    /// When the path currently being executed must finish by
+    path_deadline: Option<Instant>,
+    /// Set by the orchestrating thread in [start_multi] once
+    /// [MultiOpts::deadline] passes, to stop every path at its next
+    /// instruction
+    stop: Option<&'a AtomicBool>,
 }
 
-impl Timeout {
+/// How many instructions are executed between checks of the path
+/// deadline, so the clock isn't read for every instruction
+const PATH_DEADLINE_CHECK_INTERVAL: u32 = 1024;
+
+impl<'a> Timeout<'a> {
     fn unlimited() -> Self {
-        Timeout { start_time: Instant::now(), duration: None }
+        Timeout { start_time: Instant::now(), duration: None, path_deadline: None, stop: None }
+    }
+
+    fn new(duration: Option<u64>) -> Self {
+        Timeout { duration: duration.map(Duration::from_secs), ..Timeout::unlimited() }
+    }
+
+    /// Start the deadline for a path, which runs from now
+    fn for_path(self, path_timeout: Option<Duration>) -> Self {
+        Timeout { path_deadline: path_timeout.map(|duration| Instant::now() + duration), ..self }
+    }
+
+    fn with_stop(self, stop: &'a AtomicBool) -> Self {
+        Timeout { stop: Some(stop), ..self }
     }
 
     fn timed_out(&self) -> bool {
-        self.duration.is_some() && self.start_time.elapsed() > self.duration.unwrap()
+        self.stop.is_some_and(|stop| stop.load(Ordering::Relaxed))
+            || (self.duration.is_some() && self.start_time.elapsed() > self.duration.unwrap())
+    }
+
+    fn path_timed_out(&self) -> bool {
+        self.path_deadline.is_some_and(|deadline| Instant::now() > deadline)
     }
 }
 
@@ -640,8 +718,13 @@ pub fn interrupt_pending<'ir, B: BV>(
     info: SourceLoc,
 ) -> Result<bool, ExecError> {
     for interrupt in &task_state.interrupts {
-        let Some(Val::Bits(reg_value)) =
-            frame.local_state.regs.get(interrupt.trigger_register, shared_state, solver, info)?
+        let Some(Val::Bits(reg_value)) = frame.local_state.regs.get(
+            interrupt.trigger_register,
+            frame.local_state.uninit_policy,
+            shared_state,
+            solver,
+            info,
+        )?
         else {
             return Err(ExecError::BadInterrupt(
                 "trigger register does not exist, or does not have a concrete bitvector value",
@@ -670,7 +753,7 @@ pub fn interrupt_pending<'ir, B: BV>(
                     &mut accessor,
                     info,
                 )?;
-                solver.add_event(Event::AssumeReg(loc.id(), accessor, value));
+                solver.add_event(Event::AssumeReg(loc.id(), accessor_path(&accessor), value));
             }
 
             return Ok(true);
@@ -707,14 +790,14 @@ pub fn reset_registers<'ir, B: BV>(
             // Note that these are just the assumptions from reset_registers; there
             // may also be assumptions from default register values, recorded at the
             // top level.
-            solver.add_event(Event::AssumeReg(reg_id, accessor, value));
+            solver.add_event(Event::AssumeReg(reg_id, accessor_path(&accessor), value));
         }
     }
     for (loc, reset) in &task_state.reset_registers {
         let value = reset(&frame.memory, shared_state.typedefs(), solver)?;
         let mut accessor = Vec::new();
         assign_with_accessor(loc, value.clone(), &mut frame.local_state, shared_state, solver, &mut accessor, info)?;
-        solver.add_event(Event::AssumeReg(loc.id(), accessor, value));
+        solver.add_event(Event::AssumeReg(loc.id(), accessor_path(&accessor), value));
     }
     if !shared_state.reset_constraints.is_empty() {
         for constraint in &shared_state.reset_constraints {
@@ -787,21 +870,33 @@ pub fn reset_registers<'ir, B: BV>(
     Ok(())
 }
 
+/// The result of executing a path, as given to a [Collector]
+type PathResult<'ir, B> = Result<(Run<B>, LocalFrame<'ir, B>), (ExecError, Backtrace)>;
+
+/// How a worker hands each finished path to whichever kind of
+/// collector the run was started with
+type CollectFn<'a, 'ir, B> = dyn 'a + Fn(TaskId, PathResult<'ir, B>, PathStats, Solver<B>);
+
 #[allow(clippy::too_many_arguments)]
 fn run<'ir, 'task, B: BV>(
     tid: usize,
     task_id: TaskId,
     task_fraction: &mut Fraction,
-    timeout: Timeout,
+    timeout: Timeout<'_>,
     stop_conditions: Option<&'task StopConditions>,
     queue: &Worker<Task<'ir, 'task, B>>,
     frame: &Frame<'ir, B>,
     task_state: &'task TaskState<B>,
     shared_state: &SharedState<'ir, B>,
     solver: &mut Solver<B>,
-) -> Result<(Run<B>, LocalFrame<'ir, B>), (ExecError, Backtrace)> {
+) -> (PathResult<'ir, B>, PathStats) {
+    let start_time = Instant::now();
+    let start_queries = solver.queries();
     let mut frame = unfreeze_frame(frame);
-    match run_loop(
+    let mut max_call_depth = frame.backtrace.len();
+    solver.set_concrete_fast_path(frame.concrete_fast_path);
+    solver.set_event_filter(task_state.event_filter.clone());
+    let result = run_loop(
         tid,
         task_id,
         task_fraction,
@@ -812,15 +907,70 @@ fn run<'ir, 'task, B: BV>(
         task_state,
         shared_state,
         solver,
-    ) {
-        Ok(run) => Ok((run, frame)),
+        &mut max_call_depth,
+    );
+    let stats = PathStats {
+        branches: frame.forks as u64,
+        solver_queries: solver.queries() - start_queries,
+        time: start_time.elapsed(),
+        max_call_depth,
+    };
+    match result {
+        Ok(run) => (Ok((run, frame)), stats),
         Err(err) => {
             frame.backtrace.push((frame.function_name, frame.pc));
-            Err((err, frame.backtrace))
+            (Err((err, frame.backtrace)), stats)
         }
     }
 }
 
+/// Create the closure which restores the caller's state when the
+/// function called by the `Call` instruction at `caller_pc` returns,
+/// assigning the return value to `loc`. The caller's count of
+/// backwards jumps is restored too, so calls made in a loop body do
+/// not reset it.
+fn return_to_caller<'ir, B: BV>(
+    tid: usize,
+    loc: &'ir Loc<Name>,
+    info: SourceLoc,
+    caller_pc: usize,
+    caller_backjumps: u32,
+    caller_instrs: &'ir [Instr<Name, B>],
+    caller_stack_call: Stack<'ir, B>,
+) -> Stack<'ir, B> {
+    Some(Arc::new(move |ret, frame, shared_state, solver| {
+        pop_call_stack(frame);
+        frame.set_probes(shared_state);
+        frame.exit_function(frame.function_name);
+        // could avoid putting caller_pc into the stack?
+        if let Some((name, _)) = frame.backtrace.pop() {
+            frame.function_name = name;
+        }
+        frame.pc = caller_pc + 1;
+        frame.backjumps = caller_backjumps;
+        frame.instrs = caller_instrs;
+        frame.stack_call = caller_stack_call.clone();
+        assign(tid, &loc.clone(), ret, &mut frame.local_state, shared_state, solver, info)
+    }))
+}
+
+/// Wrap the closure which returns from a call to a pure function so
+/// it records the result in the memo table, see [MemoTable::record]
+fn memoize_on_return<'ir, B: BV>(
+    f: Name,
+    args: Vec<Val<B>>,
+    events_before: usize,
+    caller: Stack<'ir, B>,
+) -> Stack<'ir, B> {
+    let caller = caller.expect("memoized call has no caller");
+    Some(Arc::new(move |ret, frame, shared_state, solver| {
+        let exception = matches!(frame.lets().get(&HAVE_EXCEPTION), Some(UVal::Init(Val::Bool(true))));
+        let events = solver.trace().recent(solver.trace().len() - events_before);
+        shared_state.memo_table.record(f, args.clone(), Some(&ret).filter(|_| !exception), events);
+        (*caller)(ret, frame, shared_state, solver)
+    }))
+}
+
 // A special primitive can either continue execution, or it can exit
 enum SpecialResult {
     Exit,
@@ -883,7 +1033,7 @@ fn run_special_primop<'ir, B: BV>(
                 Ok(cond_var) => cond_var.into_owned(),
                 // A variable not found error indicates that the block associated with this condition variable
                 // has not been executed
-                Err(ExecError::VariableNotFound(_)) => Val::Bool(false),
+                Err(ExecError::VariableNotFound(..)) => Val::Bool(false),
                 Err(err) => return Err(err),
             };
             match cond_var {
@@ -911,7 +1061,7 @@ fn run_special_primop<'ir, B: BV>(
         frame.pc += 1
     } else if f == REG_DEREF && args.len() == 1 {
         if let Val::Ref(reg) = eval_exp(&args[0], &mut frame.local_state, shared_state, solver, info)?.into_owned() {
-            match frame.regs_mut().get(reg, shared_state, solver, info)? {
+            match frame.local_state.regs.get(reg, frame.local_state.uninit_policy, shared_state, solver, info)? {
                 Some(value) => {
                     solver.add_event(Event::ReadReg(reg, Vec::new(), value.clone()));
                     assign(tid, loc, value.clone(), &mut frame.local_state, shared_state, solver, info)?
@@ -964,7 +1114,9 @@ fn run_special_primop<'ir, B: BV>(
         assert!(args.len() == 1);
         let opcode = eval_exp(&args[0], &mut frame.local_state, shared_state, solver, info)?.into_owned();
         if let Some((arch_pc, limit)) = task_state.pc_limit {
-            if let Some(reg) = frame.local_state.regs.get(arch_pc, shared_state, solver, info)? {
+            if let Some(reg) =
+                frame.local_state.regs.get(arch_pc, frame.local_state.uninit_policy, shared_state, solver, info)?
+            {
                 match reg {
                     Val::Bits(bv) => {
                         let count = frame.pc_counts.entry(*bv).or_insert(0);
@@ -997,11 +1149,82 @@ fn run_special_primop<'ir, B: BV>(
         frame.pc += 1
     } else {
         let symbol = zencode::decode(shared_state.symtab.to_str(f));
-        return Err(ExecError::NoFunction(symbol, info));
+        return Err(ExecError::NoFunction(symbol, info, None));
     }
     Ok(SpecialResult::Continue)
 }
 
+/// Call a function that has neither a body nor a primop using the
+/// behaviour given for it in the `[stubs]` section of the ISA
+/// config. Each call is recorded as an abstract event.
+#[allow(clippy::too_many_arguments)]
+fn run_outcome_stub<'ir, 'task, B: BV>(
+    loc: &Loc<Name>,
+    f: Name,
+    args: &[Exp<Name>],
+    info: SourceLoc,
+    tid: usize,
+    task_id: TaskId,
+    task_fraction: &mut Fraction,
+    stop_conditions: Option<&'task StopConditions>,
+    queue: &Worker<Task<'ir, 'task, B>>,
+    frame: &mut LocalFrame<'ir, B>,
+    task_state: &'task TaskState<B>,
+    shared_state: &SharedState<'ir, B>,
+    solver: &mut Solver<B>,
+) -> Result<(), ExecError> {
+    use smtlib::Exp::*;
+
+    let (ret_ty, stub) = &shared_state.outcome_stubs[&f];
+    let args = args
+        .iter()
+        .map(|arg| eval_exp(arg, &mut frame.local_state, shared_state, solver, info).map(Cow::into_owned))
+        .collect::<Result<Vec<Val<B>>, _>>()?;
+
+    let return_value = match stub {
+        OutcomeStub::Constant(value) => value.clone(),
+        OutcomeStub::Symbolic | OutcomeStub::Fork(_) => symbolic(ret_ty, shared_state, solver, info)?,
+    };
+    solver.add_event(Event::Abstract { name: f, primitive: false, args, return_value: return_value.clone() });
+
+    if let OutcomeStub::Fork(values) = stub {
+        let Val::Symbolic(v) = return_value else {
+            let symbol = zencode::decode(shared_state.symtab.to_str(f));
+            return Err(ExecError::Type(format!("Cannot fork over the return values of {}", symbol), info));
+        };
+
+        // Each choice after the first gets its own task, which
+        // continues after the call with the choice as the return value
+        for value in &values[1..] {
+            let choice = Eq(Box::new(Var(v)), Box::new(smt_value(value, info)?));
+            assign(tid, loc, value.clone(), &mut frame.local_state, shared_state, solver, info)?;
+            let point = checkpoint(solver);
+            let frozen = Frame { pc: frame.pc + 1, ..freeze_frame(frame) };
+            frame.forks += 1;
+            task_fraction.halve();
+            queue.push(Task {
+                id: task_id,
+                fraction: task_fraction.clone(),
+                frame: frozen,
+                checkpoint: point,
+                fork_cond: Some((Def::Assert(choice), Event::Fork(frame.forks - 1, v, 1, info))),
+                initial_defs: Vec::new(),
+                state: task_state,
+                stop_conditions,
+            });
+            solver.add_event(Event::Fork(frame.forks - 1, v, 0, info))
+        }
+
+        solver.add(Def::Assert(Eq(Box::new(Var(v)), Box::new(smt_value(&values[0], info)?))));
+        assign(tid, loc, values[0].clone(), &mut frame.local_state, shared_state, solver, info)?
+    } else {
+        assign(tid, loc, return_value, &mut frame.local_state, shared_state, solver, info)?
+    }
+
+    frame.pc += 1;
+    Ok(())
+}
+
 pub enum Run<B> {
     /// Returned when the model finishes executing
     Finished(Val<B>),
@@ -1016,413 +1239,495 @@ pub enum Run<B> {
     Suspended,
 }
 
+/// Add the definitions a task was given before it started, marked as
+/// initial so [Solver::unsat_core] can tell where they came from
+fn add_initial_defs<B: BV>(solver: &mut Solver<B>, defs: Vec<Def>) {
+    solver.set_def_attrs(DefAttrs::initial());
+    for def in defs {
+        solver.add(def)
+    }
+    solver.clear_def_attrs()
+}
+
 #[allow(clippy::too_many_arguments)]
 fn run_loop<'ir, 'task, B: BV>(
     tid: usize,
     task_id: TaskId,
     task_fraction: &mut Fraction,
-    timeout: Timeout,
+    timeout: Timeout<'_>,
     stop_conditions: Option<&'task StopConditions>,
     queue: &Worker<Task<'ir, 'task, B>>,
     frame: &mut LocalFrame<'ir, B>,
     task_state: &'task TaskState<B>,
     shared_state: &SharedState<'ir, B>,
     solver: &mut Solver<B>,
+    max_call_depth: &mut usize,
 ) -> Result<Run<B>, ExecError> {
-    let mut last_z3_reset = Instant::now();
-
-    'main_loop: loop {
-        if frame.pc >= frame.instrs.len() {
-            // Currently this happens when evaluating letbindings.
-            return Ok(Run::Finished(Val::Unit));
+    let mut since_deadline_check = 0;
+    loop {
+        if let Step::Done(run) = step(
+            tid,
+            task_id,
+            task_fraction,
+            timeout,
+            stop_conditions,
+            queue,
+            frame,
+            task_state,
+            shared_state,
+            solver,
+            max_call_depth,
+            &mut since_deadline_check,
+        )
+        .map_err(|err| err.executing(|| zencode::decode(shared_state.symtab.to_str(frame.function_name)), frame.pc))?
+        {
+            return Ok(run);
         }
+    }
+}
 
-        if timeout.timed_out() {
-            return Err(ExecError::Timeout);
-        }
+/// Which directions of a branch on the symbolic boolean `v` are
+/// possible, and whether they were checked with the solver rather than
+/// following from a value already known for `v`. A direction the
+/// solver cannot decide, e.g. because the frame's query timeout ran
+/// out, is assumed to be possible, and the frame marked approximate.
+fn branch_directions<B: BV>(
+    frame: &mut LocalFrame<'_, B>,
+    solver: &mut Solver<B>,
+    v: Sym,
+    info: SourceLoc,
+) -> Result<(bool, bool, bool), ExecError> {
+    use smtlib::Exp::*;
 
-        if last_z3_reset.elapsed() > Duration::from_millis(500) {
-            //let mut vars = HashSet::default();
-            //frame.collect_symbolic_variables(&mut vars);
-            //solver.reset(vars);
-            last_z3_reset = Instant::now()
-        };
+    if let Some(Bool(b)) = solver.known_value(v) {
+        return Ok((*b, !*b, false));
+    }
 
-        match &frame.instrs[frame.pc] {
-            Instr::Decl(v, ty, _) => {
-                frame.vars_mut().insert(*v, UVal::Uninit(ty));
-                frame.pc += 1;
-            }
+    let test_true = Var(v);
+    let test_false = Not(Box::new(Var(v)));
+    let limits = (frame.query_timeout, frame.query_rlimit);
 
-            Instr::Init(var, _, exp, info) => {
-                let value = eval_exp(exp, &mut frame.local_state, shared_state, solver, *info)?.into_owned();
-                frame.vars_mut().insert(*var, UVal::Init(value));
-                frame.pc += 1;
-            }
+    let can_be_true = check_direction(solver, &test_true, limits, info);
+    // A satisfiable path where v can't be true must allow it to be false
+    let can_be_false = if can_be_true == SmtResult::Unsat && solver.known_sat() {
+        SmtResult::Sat
+    } else {
+        check_direction(solver, &test_false, limits, info)
+    };
 
-            Instr::Jump(exp, target, info) => {
-                let value = eval_exp(exp, &mut frame.local_state, shared_state, solver, *info)?;
-                match *value.as_ref() {
-                    Val::Symbolic(v) => {
-                        use smtlib::Def::*;
-                        use smtlib::Exp::*;
-
-                        let test_true = Var(v);
-                        let test_false = Not(Box::new(Var(v)));
-
-                        let can_be_true = solver.check_sat_with(&test_true, *info).is_sat()?;
-                        let can_be_false = solver.check_sat_with(&test_false, *info).is_sat()?;
-
-                        if can_be_true && can_be_false {
-                            if_logging!(log::FORK, {
-                                log_from!(tid, log::FORK, info.location_string(shared_state.symtab.files()));
-                                probe::taint_info(log::FORK, v, Some(shared_state), solver)
-                            });
-
-                            let point = checkpoint(solver);
-                            let frozen = Frame { pc: frame.pc + 1, ..freeze_frame(frame) };
-                            frame.forks += 1;
-                            task_fraction.halve();
-                            queue.push(Task {
-                                id: task_id,
-                                fraction: task_fraction.clone(),
-                                frame: frozen,
-                                checkpoint: point,
-                                fork_cond: Some((Assert(test_false), Event::Fork(frame.forks - 1, v, 1, *info))),
-                                state: task_state,
-                                stop_conditions,
-                            });
-
-                            // Track which asserts are assocated with each fork in the trace, so we
-                            // can turn a set of traces into a tree later
-                            solver.add_event(Event::Fork(frame.forks - 1, v, 0, *info));
-
-                            solver.add(Assert(test_true));
-                            frame.pc = *target
-                        } else if can_be_true {
-                            solver.add(Assert(test_true));
-                            frame.pc = *target
-                        } else if can_be_false {
-                            solver.add(Assert(test_false));
-                            frame.pc += 1
-                        } else {
-                            return Ok(Run::Dead);
-                        }
-                    }
-                    Val::Bool(jump) => {
-                        if jump {
-                            frame.pc = *target
-                        } else {
-                            frame.pc += 1
-                        }
-                    }
-                    _ => {
-                        return Err(ExecError::Type(format!("Jump on non boolean {:?}", &value), *info));
-                    }
-                }
-            }
+    if can_be_true.is_unknown() || can_be_false.is_unknown() {
+        frame.approximate = true
+    }
+    Ok((can_be_true != SmtResult::Unsat, can_be_false != SmtResult::Unsat, true))
+}
 
-            Instr::Goto(target) => frame.pc = *target,
+/// Assert the direction taken at a branch checked by
+/// [branch_directions]. The assertions on an approximate path may be
+/// unsatisfiable, so the solver must not treat them as known to be
+/// satisfiable.
+fn assert_direction<B: BV>(frame: &LocalFrame<'_, B>, solver: &mut Solver<B>, test: smtlib::Exp<Sym>, checked: bool) {
+    if frame.approximate {
+        solver.assert(test)
+    } else {
+        solver.assert_branch(test, checked)
+    }
+}
 
-            Instr::Copy(loc, exp, info) => {
-                let value = eval_exp(exp, &mut frame.local_state, shared_state, solver, *info)?.into_owned();
-                assign(tid, loc, value, &mut frame.local_state, shared_state, solver, *info)?;
-                frame.pc += 1;
-            }
+fn check_direction<B: BV>(
+    solver: &mut Solver<B>,
+    test: &smtlib::Exp<Sym>,
+    (query_timeout, query_rlimit): (Option<u32>, Option<u32>),
+    info: SourceLoc,
+) -> SmtResult {
+    match (query_timeout, query_rlimit) {
+        (None, None) => solver.check_sat_with(test, info),
+        (millis, rlimit) => solver.check_sat_with_limits(test, millis, rlimit, info),
+    }
+}
 
-            Instr::PrimopUnary(loc, f, arg, info) => {
-                let arg = eval_exp(arg, &mut frame.local_state, shared_state, solver, *info)?.into_owned();
-                let value = f(arg, solver, *info)?;
-                assign(tid, loc, value, &mut frame.local_state, shared_state, solver, *info)?;
-                frame.pc += 1;
-            }
+/// What happened when a single instruction was executed by [step]
+enum Step<B> {
+    Continue,
+    Assigned(Name),
+    Called(Name),
+    Returned(Name),
+    Done(Run<B>),
+}
 
-            Instr::PrimopBinary(loc, f, arg1, arg2, info) => {
-                let arg1 = eval_exp(arg1, &mut frame.local_state, shared_state, solver, *info)?.into_owned();
-                let arg2 = eval_exp(arg2, &mut frame.local_state, shared_state, solver, *info)?.into_owned();
-                let value = f(arg1, arg2, solver, *info)?;
-                assign(tid, loc, value, &mut frame.local_state, shared_state, solver, *info)?;
-                frame.pc += 1;
-            }
+/// When logging verbosely, explain why the path became dead at `info`
+/// with the origins of an unsat core of its assertions
+fn log_dead_path<B: BV>(tid: usize, info: SourceLoc, shared_state: &SharedState<B>, solver: &Solver<B>) {
+    if_logging!(log::VERBOSE, {
+        let location = info.location_string(shared_state.symtab.files());
+        if let Some(core) = solver.unsat_core() {
+            let mut buf = Vec::new();
+            simplify::write_unsat_core(&mut buf, &core, shared_state).unwrap();
+            log_from!(tid, log::VERBOSE, format!("Path dead at {}, {}", location, String::from_utf8_lossy(&buf)))
+        } else {
+            log_from!(tid, log::VERBOSE, format!("Path dead at {}, but no unsat core was found", location))
+        }
+    })
+}
 
-            Instr::PrimopVariadic(loc, f, args, info) => {
-                let args = args
-                    .iter()
-                    .map(|arg| eval_exp(arg, &mut frame.local_state, shared_state, solver, *info).map(Cow::into_owned))
-                    .collect::<Result<_, _>>()?;
-                let value = f(args, solver, frame, *info)?;
-                assign(tid, loc, value, &mut frame.local_state, shared_state, solver, *info)?;
-                frame.pc += 1;
-            }
+/// Execute the instruction at the current pc of the frame. Any tasks
+/// forked by the instruction are pushed to `queue`.
+#[allow(clippy::too_many_arguments)]
+fn step<'ir, 'task, B: BV>(
+    tid: usize,
+    task_id: TaskId,
+    task_fraction: &mut Fraction,
+    timeout: Timeout<'_>,
+    stop_conditions: Option<&'task StopConditions>,
+    queue: &Worker<Task<'ir, 'task, B>>,
+    frame: &mut LocalFrame<'ir, B>,
+    task_state: &'task TaskState<B>,
+    shared_state: &SharedState<'ir, B>,
+    solver: &mut Solver<B>,
+    max_call_depth: &mut usize,
+    since_deadline_check: &mut u32,
+) -> Result<Step<B>, ExecError> {
+    if frame.pc >= frame.instrs.len() {
+        // Currently this happens when evaluating letbindings.
+        return Ok(Step::Done(Run::Finished(Val::Unit)));
+    }
 
-            Instr::PrimopReset(loc, reset, info) => {
-                let value = reset(&frame.memory, shared_state.typedefs(), solver)?;
-                assign(tid, loc, value, &mut frame.local_state, shared_state, solver, *info)?;
-                frame.pc += 1;
-            }
+    if timeout.timed_out() {
+        return Err(ExecError::Timeout);
+    }
 
-            Instr::Call(loc, _, f, args, info) => {
-                match shared_state.functions.get(f) {
-                    None => {
-                        match run_special_primop(
-                            loc,
-                            *f,
-                            args,
-                            *info,
-                            tid,
-                            task_id,
-                            frame,
-                            task_state,
-                            shared_state,
-                            solver,
-                        )? {
-                            SpecialResult::Continue => (),
-                            SpecialResult::Exit => return Ok(Run::Exit),
-                        }
-                    }
+    if let Some(fuel) = &mut frame.max_instructions {
+        if *fuel == 0 {
+            return Err(ExecError::Fuel);
+        }
+        *fuel -= 1
+    }
 
-                    Some((params, ret_ty, instrs)) => {
-                        frame.set_probes(shared_state);
+    *since_deadline_check += 1;
+    if *since_deadline_check == PATH_DEADLINE_CHECK_INTERVAL {
+        *since_deadline_check = 0;
+        if timeout.path_timed_out() {
+            return Err(ExecError::PathTimeout);
+        }
+    }
 
-                        let mut args = args
-                            .iter()
-                            .map(|arg| {
-                                eval_exp(arg, &mut frame.local_state, shared_state, solver, *info).map(Cow::into_owned)
-                            })
-                            .collect::<Result<Vec<Val<B>>, _>>()?;
+    if let Some(last_line) = frame.source_lines {
+        let info = frame.instrs[frame.pc].source_loc();
+        if !info.is_unknown() && info.line() != last_line {
+            frame.source_lines = Some(info.line());
+            solver.add_event(Event::SourceLine(info.line()))
+        }
+    }
 
-                        if frame.local_state.should_probe(shared_state, f) {
-                            log_from!(tid, log::PROBE, probe::call_info(*f, &args, shared_state, *info));
-                            probe::args_info(tid, &args, shared_state, solver)
-                        }
+    let step = match &frame.instrs[frame.pc] {
+        Instr::Decl(v, ty, _) => {
+            frame.vars_mut().insert(*v, UVal::Uninit(ty));
+            frame.pc += 1;
+            Step::Continue
+        }
 
-                        if shared_state.trace_functions.contains(f) {
-                            solver.trace_call(*f)
-                        }
+        Instr::Init(var, _, exp, info) => {
+            let value = eval_exp(exp, &mut frame.local_state, shared_state, solver, *info)?.into_owned();
+            frame.vars_mut().insert(*var, UVal::Init(value));
+            frame.pc += 1;
+            Step::Assigned(*var)
+        }
 
-                        if let Some(s) = stop_conditions {
-                            match s.should_stop(*f, frame.function_name, &frame.backtrace) {
-                                Some(StopAction::Kill) => {
-                                    let symbol = zencode::decode(shared_state.symtab.to_str(*f));
-                                    return Err(ExecError::Stopped(symbol));
-                                }
-                                Some(StopAction::Abstract) => {
-                                    solver.add_event(Event::Abstract {
-                                        name: *f,
-                                        args,
-                                        primitive: false,
-                                        return_value: Val::Poison,
-                                    });
-                                    return Ok(Run::Finished(Val::Poison));
-                                }
-                                None => (),
-                            }
-                        }
+        Instr::Jump(exp, target, info) => {
+            let value = eval_exp(exp, &mut frame.local_state, shared_state, solver, *info)?;
+            match *value.as_ref() {
+                Val::Symbolic(v) => {
+                    use smtlib::Def::*;
+                    use smtlib::Exp::*;
 
-                        if let Some(assumptions) = frame.function_assumptions.get(f) {
-                            for (required_args, result) in assumptions {
-                                if args.len() == required_args.len()
-                                    && required_args.iter().zip(args.iter()).all(|(req, arg)| {
-                                        primop::eq_anything(req.clone(), arg.clone(), solver, *info)
-                                            .map(|v| match v {
-                                                Val::Symbolic(var) => {
-                                                    solver.check_sat_with(
-                                                        &smtlib::Exp::Eq(
-                                                            Box::new(smtlib::Exp::Var(var)),
-                                                            Box::new(smtlib::Exp::Bool(false)),
-                                                        ),
-                                                        *info,
-                                                    ) == SmtResult::Unsat
-                                                }
-                                                Val::Bool(b) => b,
-                                                _ => panic!("TODO"),
-                                            })
-                                            .unwrap()
-                                    })
-                                {
-                                    assign(
-                                        tid,
-                                        loc,
-                                        result.clone(),
-                                        &mut frame.local_state,
-                                        shared_state,
-                                        solver,
-                                        *info,
-                                    )?;
-                                    solver.add_event(Event::UseFunAssumption {
-                                        name: *f,
-                                        args,
-                                        return_value: result.clone(),
-                                    });
-                                    frame.pc += 1;
-                                    continue 'main_loop;
-                                }
-                            }
-                        }
+                    let test_true = Var(v);
+                    let test_false = Not(Box::new(Var(v)));
 
-                        let caller_pc = frame.pc;
-                        let caller_instrs = frame.instrs;
-                        let caller_stack_call = frame.stack_call.clone();
-                        push_call_stack(frame);
-                        frame.backtrace.push((frame.function_name, caller_pc));
-                        frame.function_name = *f;
-                        frame.vars_mut().insert(RETURN, UVal::Uninit(ret_ty));
-
-                        // Set up a closure to restore our state when
-                        // the function we call returns
-                        frame.stack_call = Some(Arc::new(move |ret, frame, shared_state, solver| {
-                            pop_call_stack(frame);
-                            frame.set_probes(shared_state);
-                            // could avoid putting caller_pc into the stack?
-                            if let Some((name, _)) = frame.backtrace.pop() {
-                                frame.function_name = name;
-                            }
-                            frame.pc = caller_pc + 1;
-                            frame.instrs = caller_instrs;
-                            frame.stack_call = caller_stack_call.clone();
-                            assign(tid, &loc.clone(), ret, &mut frame.local_state, shared_state, solver, *info)
-                        }));
-
-                        for (i, arg) in args.drain(..).enumerate() {
-                            frame.vars_mut().insert(params[i].0, UVal::Init(arg));
-                        }
-                        frame.pc = 0;
-                        frame.instrs = instrs;
+                    if timeout.path_timed_out() {
+                        return Err(ExecError::PathTimeout);
+                    }
+                    let (can_be_true, can_be_false, checked) = branch_directions(frame, solver, v, *info)?;
+
+                    if can_be_true && can_be_false {
+                        if_logging!(log::FORK, {
+                            log_from!(tid, log::FORK, info.location_string(shared_state.symtab.files()));
+                            probe::taint_info(log::FORK, v, Some(shared_state), solver)
+                        });
+
+                        let point = checkpoint(solver);
+                        let frozen = Frame { pc: frame.pc + 1, ..freeze_frame(frame) };
+                        frame.forks += 1;
+                        task_fraction.halve();
+                        queue.push(Task {
+                            id: task_id,
+                            fraction: task_fraction.clone(),
+                            frame: frozen,
+                            checkpoint: point,
+                            fork_cond: Some((Assert(test_false), Event::Fork(frame.forks - 1, v, 1, *info))),
+                            initial_defs: Vec::new(),
+                            state: task_state,
+                            stop_conditions,
+                        });
+
+                        // Track which asserts are assocated with each fork in the trace, so we
+                        // can turn a set of traces into a tree later
+                        solver.add_event(Event::Fork(frame.forks - 1, v, 0, *info));
+
+                        assert_direction(frame, solver, test_true, checked);
+                        frame.jump(*target, shared_state, *info)?
+                    } else if can_be_true {
+                        solver.add_event(Event::OneSidedJump(true, *info));
+                        assert_direction(frame, solver, test_true, checked);
+                        frame.jump(*target, shared_state, *info)?
+                    } else if can_be_false {
+                        solver.add_event(Event::OneSidedJump(false, *info));
+                        assert_direction(frame, solver, test_false, checked);
+                        frame.pc += 1
+                    } else {
+                        log_dead_path(tid, *info, shared_state, solver);
+                        return Ok(Step::Done(Run::Dead));
                     }
                 }
+                Val::Bool(jump) => {
+                    if jump {
+                        frame.jump(*target, shared_state, *info)?
+                    } else {
+                        frame.pc += 1
+                    }
+                }
+                _ => {
+                    return Err(ExecError::Type(format!("Jump on non boolean {:?}", &value), *info));
+                }
             }
+            Step::Continue
+        }
 
-            Instr::End => match frame.vars().get(&RETURN) {
-                None => panic!("Return variable missing at end of function"),
-                Some(value) => {
-                    let value = match value {
-                        UVal::Uninit(ty) => symbolic(ty, shared_state, solver, SourceLoc::unknown())?,
-                        UVal::Init(value) => value.clone(),
-                    };
+        Instr::Goto(target) => {
+            frame.jump(*target, shared_state, SourceLoc::unknown())?;
+            Step::Continue
+        }
 
-                    if frame.local_state.should_probe(shared_state, &frame.function_name) {
-                        let symbol = zencode::decode(shared_state.symtab.to_str(frame.function_name));
-                        log_from!(
-                            tid,
-                            log::PROBE,
-                            &format!("Returning {} = {}", symbol, value.to_string(shared_state))
-                        );
-                        probe::args_info(tid, std::slice::from_ref(&value), shared_state, solver)
-                    }
+        Instr::Copy(loc, exp, info) => {
+            let value = eval_exp(exp, &mut frame.local_state, shared_state, solver, *info)?.into_owned();
+            assign(tid, loc, value, &mut frame.local_state, shared_state, solver, *info)?;
+            frame.pc += 1;
+            Step::Assigned(loc.id())
+        }
 
-                    if shared_state.trace_functions.contains(&frame.function_name) {
-                        solver.trace_return(frame.function_name)
-                    }
+        Instr::PrimopUnary(loc, f, arg, info) => {
+            let arg = eval_exp(arg, &mut frame.local_state, shared_state, solver, *info)?.into_owned();
+            let value = f(arg, solver, *info)?;
+            assign(tid, loc, value, &mut frame.local_state, shared_state, solver, *info)?;
+            frame.pc += 1;
+            Step::Assigned(loc.id())
+        }
 
-                    let caller = match &frame.stack_call {
-                        None => return Ok(Run::Finished(value)),
-                        Some(caller) => Arc::clone(caller),
-                    };
-                    (*caller)(value, frame, shared_state, solver)?
+        Instr::PrimopBinary(loc, f, arg1, arg2, info) => {
+            let arg1 = eval_exp(arg1, &mut frame.local_state, shared_state, solver, *info)?.into_owned();
+            let arg2 = eval_exp(arg2, &mut frame.local_state, shared_state, solver, *info)?.into_owned();
+            let value = f(arg1, arg2, solver, *info)?;
+            assign(tid, loc, value, &mut frame.local_state, shared_state, solver, *info)?;
+            frame.pc += 1;
+            Step::Assigned(loc.id())
+        }
+
+        Instr::PrimopVariadic(loc, f, args, info) => {
+            let args = args
+                .iter()
+                .map(|arg| eval_exp(arg, &mut frame.local_state, shared_state, solver, *info).map(Cow::into_owned))
+                .collect::<Result<_, _>>()?;
+            let value = f(args, solver, frame, *info)?;
+            assign(tid, loc, value, &mut frame.local_state, shared_state, solver, *info)?;
+            frame.pc += 1;
+            Step::Assigned(loc.id())
+        }
+
+        Instr::PrimopReset(loc, reset, info) => {
+            let value = reset(&frame.memory, shared_state.typedefs(), solver)?;
+            assign(tid, loc, value, &mut frame.local_state, shared_state, solver, *info)?;
+            frame.pc += 1;
+            Step::Assigned(loc.id())
+        }
+
+        Instr::Call(loc, _, f, args, info) => {
+            match shared_state.functions.get(f) {
+                None if shared_state.outcome_stubs.contains_key(f) => {
+                    run_outcome_stub(
+                        loc,
+                        *f,
+                        args,
+                        *info,
+                        tid,
+                        task_id,
+                        task_fraction,
+                        stop_conditions,
+                        queue,
+                        frame,
+                        task_state,
+                        shared_state,
+                        solver,
+                    )?;
+                    Step::Assigned(loc.id())
                 }
-            },
 
-            // The idea beind the Monomorphize operation is it takes a
-            // bitvector identifier, and if that identifer has a
-            // symbolic value, then it uses the SMT solver to find all
-            // the possible values for that bitvector and case splits
-            // (i.e. forks) on them. This allows us to guarantee that
-            // certain bitvectors are non-symbolic, at the cost of
-            // increasing the number of paths.
-            Instr::Monomorphize(id, info) => {
-                let val = get_id_and_initialize(
-                    *id,
-                    &mut frame.local_state,
-                    shared_state,
-                    solver,
-                    &mut Vec::new(),
-                    *info,
-                    false,
-                )?;
-                if let Val::Symbolic(v) = *val.as_ref() {
-                    use smtlib::bits64;
-                    use smtlib::Def::*;
-                    use smtlib::Exp::*;
-                    use smtlib::Ty::*;
+                None => {
+                    match run_special_primop(
+                        loc,
+                        *f,
+                        args,
+                        *info,
+                        tid,
+                        task_id,
+                        frame,
+                        task_state,
+                        shared_state,
+                        solver,
+                    )? {
+                        SpecialResult::Continue => Step::Assigned(loc.id()),
+                        SpecialResult::Exit => Step::Done(Run::Exit),
+                    }
+                }
 
-                    let point = checkpoint(solver);
+                Some((params, ret_ty, instrs)) => {
+                    frame.set_probes(shared_state);
 
-                    let len =
-                        solver.length(v).ok_or_else(|| ExecError::Type(format!("_monomorphize {:?}", &v), *info))?;
+                    let mut args = args
+                        .iter()
+                        .map(|arg| {
+                            eval_exp(arg, &mut frame.local_state, shared_state, solver, *info).map(Cow::into_owned)
+                        })
+                        .collect::<Result<Vec<Val<B>>, _>>()?;
 
-                    // For the variable v to appear in the model, there must be some assertion that references it
-                    let sym = solver.declare_const(BitVec(len), *info);
-                    solver.assert_eq(Var(v), Var(sym));
+                    if frame.local_state.should_probe(shared_state, f) {
+                        log_from!(tid, log::PROBE, probe::call_info(*f, &args, shared_state, *info));
+                        probe::args_info(tid, &args, shared_state, solver)
+                    }
 
-                    if solver.check_sat(*info).is_unsat()? {
-                        return Ok(Run::Dead);
+                    if shared_state.trace_functions.contains(f) {
+                        solver.trace_call(*f)
                     }
 
-                    let (result, size) = {
-                        let mut model = Model::new(solver);
-                        log_from!(tid, log::FORK, format!("Model: {:?}", model));
-                        match model.get_var(v) {
-                            Ok(Some(Bits64(bv))) => (bv.lower_u64(), bv.len()),
-                            // __monomorphize should have a 'n <= 64 constraint in Sail
-                            Ok(Some(other)) => {
-                                return Err(ExecError::Type(format!("__monomorphize {:?}", &other), *info))
+                    if let Some(s) = stop_conditions {
+                        match s.should_stop(*f, frame.function_name, &frame.backtrace) {
+                            Some(StopAction::Kill) => {
+                                let symbol = zencode::decode(shared_state.symtab.to_str(*f));
+                                return Err(ExecError::Stopped(symbol));
                             }
-                            Ok(None) => return Err(ExecError::Z3Error(format!("No value for variable v{}", v))),
-                            Err(error) => return Err(error),
+                            Some(StopAction::Abstract) => {
+                                solver.add_event(Event::Abstract {
+                                    name: *f,
+                                    args,
+                                    primitive: false,
+                                    return_value: Val::Poison,
+                                });
+                                return Ok(Step::Done(Run::Finished(Val::Poison)));
+                            }
+                            None => (),
                         }
-                    };
-
-                    log_from!(tid, log::FORK, format!("Fork @ monomorphizing v{}", v));
-
-                    frame.forks += 1;
-
-                    // Because we will likely case-split more times in the task we add to the queue,
-                    // give it a larger part of the fraction (otherwise the denominator becomes
-                    // small very fast).
-                    let child_frac = task_fraction.min_split(6);
-                    queue.push(Task {
-                        id: task_id,
-                        fraction: child_frac,
-                        frame: freeze_frame(frame),
-                        checkpoint: point,
-                        fork_cond: Some((
-                            Assert(Neq(Box::new(Var(v)), Box::new(bits64(result, size)))),
-                            Event::Fork(frame.forks - 1, v, 1, *info),
-                        )),
-                        state: task_state,
-                        stop_conditions,
-                    });
-
-                    solver.add_event(Event::Fork(frame.forks - 1, v, 0, *info));
+                    }
 
-                    solver.assert_eq(Var(v), bits64(result, size));
+                    if let Some(assumptions) = frame.function_assumptions.get(f) {
+                        for (required_args, result) in assumptions {
+                            if args.len() == required_args.len()
+                                && required_args.iter().zip(args.iter()).all(|(req, arg)| {
+                                    primop::eq_anything(req.clone(), arg.clone(), solver, *info)
+                                        .map(|v| match v {
+                                            Val::Symbolic(var) => {
+                                                solver.check_sat_with(
+                                                    &smtlib::Exp::Eq(
+                                                        Box::new(smtlib::Exp::Var(var)),
+                                                        Box::new(smtlib::Exp::Bool(false)),
+                                                    ),
+                                                    *info,
+                                                ) == SmtResult::Unsat
+                                            }
+                                            Val::Bool(b) => b,
+                                            _ => panic!("TODO"),
+                                        })
+                                        .unwrap()
+                                })
+                            {
+                                assign(tid, loc, result.clone(), &mut frame.local_state, shared_state, solver, *info)?;
+                                solver.add_event(Event::UseFunAssumption {
+                                    name: *f,
+                                    args,
+                                    return_value: result.clone(),
+                                });
+                                frame.pc += 1;
+                                return Ok(Step::Assigned(loc.id()));
+                            }
+                        }
+                    }
 
-                    assign(
+                    let memoize = shared_state.memo_table.is_pure(*f)
+                        && args.iter().all(|arg| !arg.is_symbolic())
+                        && match shared_state.memo_table.lookup(*f, &args) {
+                            Memo::Hit(value) => {
+                                if shared_state.trace_functions.contains(f) {
+                                    solver.trace_return(*f)
+                                }
+                                assign(tid, loc, value, &mut frame.local_state, shared_state, solver, *info)?;
+                                frame.pc += 1;
+                                return Ok(Step::Assigned(loc.id()));
+                            }
+                            Memo::Miss => true,
+                            Memo::Impure => false,
+                        };
+
+                    frame.enter_function(*f, shared_state, *info)?;
+                    let caller_pc = frame.pc;
+                    let caller_backjumps = frame.backjumps;
+                    let caller_instrs = frame.instrs;
+                    let caller_stack_call = frame.stack_call.clone();
+                    push_call_stack(frame);
+                    frame.backtrace.push((frame.function_name, caller_pc));
+                    *max_call_depth = (*max_call_depth).max(frame.backtrace.len());
+                    frame.function_name = *f;
+                    frame.backjumps = 0;
+                    frame.vars_mut().insert(RETURN, UVal::Uninit(ret_ty));
+
+                    // Set up a closure to restore our state when
+                    // the function we call returns
+                    frame.stack_call = return_to_caller(
                         tid,
-                        &Loc::Id(*id),
-                        Val::Bits(B::new(result, size)),
-                        &mut frame.local_state,
-                        shared_state,
-                        solver,
+                        loc,
                         *info,
-                    )?;
+                        caller_pc,
+                        caller_backjumps,
+                        caller_instrs,
+                        caller_stack_call,
+                    );
+                    if memoize {
+                        let events_before = solver.trace().len();
+                        frame.stack_call = memoize_on_return(*f, args.clone(), events_before, frame.stack_call.take())
+                    }
+
+                    for (i, arg) in args.drain(..).enumerate() {
+                        frame.vars_mut().insert(params[i].0, UVal::Init(arg));
+                    }
+                    frame.pc = 0;
+                    frame.instrs = instrs;
+                    Step::Called(*f)
                 }
-                frame.pc += 1
             }
+        }
+
+        Instr::End => match frame.vars().get(&RETURN) {
+            None => panic!("Return variable missing at end of function"),
+            Some(value) => {
+                let value = match value {
+                    UVal::Uninit(ty) => symbolic(ty, shared_state, solver, SourceLoc::unknown()).map_err(|err| {
+                        err.initializing(|| {
+                            let symbol = zencode::decode(shared_state.symtab.to_str(frame.function_name));
+                            format!("return value of {}", symbol)
+                        })
+                    })?,
+                    UVal::Init(value) => value.clone(),
+                };
 
-            // Arbitrary means return any value. It is used in the
-            // Sail->C compilation for exceptional control flow paths
-            // to avoid compiler warnings (which would also be UB in
-            // C++ compilers). The value should never be used, so we
-            // return Val::Poison here.
-            Instr::Arbitrary => {
                 if frame.local_state.should_probe(shared_state, &frame.function_name) {
                     let symbol = zencode::decode(shared_state.symtab.to_str(frame.function_name));
-                    log_from!(
-                        tid,
-                        log::PROBE,
-                        &format!("Returning via arbitrary {}[{:?}] = poison", symbol, frame.function_name)
-                    );
+                    log_from!(tid, log::PROBE, &format!("Returning {} = {}", symbol, value.to_string(shared_state)));
+                    probe::args_info(tid, std::slice::from_ref(&value), shared_state, solver)
                 }
 
                 if shared_state.trace_functions.contains(&frame.function_name) {
@@ -1430,56 +1735,429 @@ fn run_loop<'ir, 'task, B: BV>(
                 }
 
                 let caller = match &frame.stack_call {
-                    None => return Ok(Run::Finished(Val::Poison)),
+                    None => return Ok(Step::Done(Run::Finished(value))),
                     Some(caller) => Arc::clone(caller),
                 };
-                (*caller)(Val::Poison, frame, shared_state, solver)?
+                let callee = frame.function_name;
+                (*caller)(value, frame, shared_state, solver)?;
+                Step::Returned(callee)
             }
+        },
+
+        // The idea beind the Monomorphize operation is it takes a
+        // bitvector identifier, and if that identifer has a
+        // symbolic value, then it uses the SMT solver to find all
+        // the possible values for that bitvector and case splits
+        // (i.e. forks) on them. This allows us to guarantee that
+        // certain bitvectors are non-symbolic, at the cost of
+        // increasing the number of paths.
+        Instr::Monomorphize(id, info) => {
+            let val = get_id_and_initialize(
+                *id,
+                &mut frame.local_state,
+                shared_state,
+                solver,
+                &mut Vec::new(),
+                *info,
+                false,
+            )?;
+            if let Val::Symbolic(v) = *val.as_ref() {
+                use smtlib::bits64;
+                use smtlib::Def::*;
+                use smtlib::Exp::*;
+                use smtlib::Ty::*;
+
+                let point = checkpoint(solver);
+
+                let len = solver.length(v).ok_or_else(|| ExecError::Type(format!("_monomorphize {:?}", &v), *info))?;
+
+                // For the variable v to appear in the model, there must be some assertion that references it
+                let sym = solver.declare_const(BitVec(len), *info);
+                solver.assert_eq(Var(v), Var(sym));
 
-            Instr::Exit(cause, info) => {
-                return match cause {
-                    ExitCause::MatchFailure => Err(ExecError::MatchFailure(*info)),
-                    ExitCause::AssertionFailure => Err(ExecError::AssertionFailure(None, *info)),
-                    ExitCause::Explicit => Ok(Run::Exit),
+                if solver.check_sat(*info).is_unsat()? {
+                    log_dead_path(tid, *info, shared_state, solver);
+                    return Ok(Step::Done(Run::Dead));
                 }
-            }
-        }
-    }
-}
 
-/// A collector is run on the result of each path found via symbolic execution through the code. It
-/// takes the result of the execution, which is either a combination of the return value and local
-/// state at the end of the execution or an error, as well as the shared state and the SMT solver
-/// state associated with that execution. It build a final result for all the executions by
-/// collecting the results into a type R.
-pub type Collector<'ir, B, R> = dyn 'ir
-    + Sync
-    + Fn(usize, TaskId, Result<(Run<B>, LocalFrame<'ir, B>), (ExecError, Backtrace)>, &SharedState<'ir, B>, Solver<B>, &R);
+                if frame.monomorphized >= frame.max_monomorphize {
+                    let symbol = zencode::decode(shared_state.symtab.to_str(*id));
+                    return Err(ExecError::MonomorphizeLimit(symbol, frame.max_monomorphize, *info));
+                }
+
+                let (result, size) = {
+                    let model = solver.get_model(&[v])?;
+                    log_from!(tid, log::FORK, format!("Model: {:?}", model));
+                    match &model[&v] {
+                        ModelVal::Bits64(bv) => (bv.lower_u64(), bv.len()),
+                        // __monomorphize should have a 'n <= 64 constraint in Sail
+                        other => return Err(ExecError::Type(format!("__monomorphize {:?}", other), *info)),
+                    }
+                };
+
+                log_from!(tid, log::FORK, format!("Fork @ monomorphizing v{}", v));
+
+                frame.forks += 1;
+
+                // Because we will likely case-split more times in the task we add to the queue,
+                // give it a larger part of the fraction (otherwise the denominator becomes
+                // small very fast).
+                let child_frac = task_fraction.min_split(6);
+                queue.push(Task {
+                    id: task_id,
+                    fraction: child_frac,
+                    frame: Frame { monomorphized: frame.monomorphized + 1, ..freeze_frame(frame) },
+                    checkpoint: point,
+                    fork_cond: Some((
+                        Assert(Neq(Box::new(Var(v)), Box::new(bits64(result, size)))),
+                        Event::Fork(frame.forks - 1, v, 1, *info),
+                    )),
+                    initial_defs: Vec::new(),
+                    state: task_state,
+                    stop_conditions,
+                });
+
+                solver.add_event(Event::Fork(frame.forks - 1, v, 0, *info));
+
+                solver.assert_eq(Var(v), bits64(result, size));
+
+                assign(
+                    tid,
+                    &Loc::Id(*id),
+                    Val::Bits(B::new(result, size)),
+                    &mut frame.local_state,
+                    shared_state,
+                    solver,
+                    *info,
+                )?;
+                frame.monomorphized = 0;
+                frame.pc += 1;
+                Step::Assigned(*id)
+            } else {
+                frame.pc += 1;
+                Step::Continue
+            }
+        }
+
+        // Arbitrary means return any value. It is used in the
+        // Sail->C compilation for exceptional control flow paths
+        // to avoid compiler warnings (which would also be UB in
+        // C++ compilers). The value should never be used, so we
+        // return Val::Poison here.
+        Instr::Arbitrary => {
+            if frame.local_state.should_probe(shared_state, &frame.function_name) {
+                let symbol = zencode::decode(shared_state.symtab.to_str(frame.function_name));
+                log_from!(
+                    tid,
+                    log::PROBE,
+                    &format!("Returning via arbitrary {}[{:?}] = poison", symbol, frame.function_name)
+                );
+            }
+
+            if shared_state.trace_functions.contains(&frame.function_name) {
+                solver.trace_return(frame.function_name)
+            }
+
+            let caller = match &frame.stack_call {
+                None => return Ok(Step::Done(Run::Finished(Val::Poison))),
+                Some(caller) => Arc::clone(caller),
+            };
+            let callee = frame.function_name;
+            (*caller)(Val::Poison, frame, shared_state, solver)?;
+            Step::Returned(callee)
+        }
+
+        Instr::Exit(cause, info) => match cause {
+            ExitCause::MatchFailure => return Err(ExecError::MatchFailure(*info)),
+            ExitCause::AssertionFailure => return Err(ExecError::AssertionFailure(None, *info)),
+            ExitCause::Explicit => Step::Done(Run::Exit),
+        },
+    };
+    Ok(step)
+}
+
+/// What happened when a [Runner] executed a single instruction
+pub enum StepResult<'ir, 'task, B> {
+    /// Nothing that needs reporting happened, e.g. a jump
+    Continue,
+    /// The variable or register with the given name was assigned
+    Assigned(Name),
+    /// The path forked, with a task for each other branch. This is
+    /// reported in place of any other effect of the instruction.
+    Forked(Vec<Task<'ir, 'task, B>>),
+    /// The named function was called
+    Called(Name),
+    /// The named function returned to its caller
+    Returned(Name),
+    /// The path has finished, so there are no more instructions to
+    /// step through
+    Finished(Run<B>),
+}
+
+/// Executes a single path of a [Task] one instruction at a time with
+/// [Runner::step], e.g. for a debugger. Unlike [start_single], the
+/// tasks for any other branches are returned to the caller rather
+/// than executed.
+pub struct Runner<'ir, 'task, B> {
+    tid: usize,
+    task_id: TaskId,
+    fraction: Fraction,
+    timeout: Timeout<'static>,
+    stop_conditions: Option<&'task StopConditions>,
+    task_state: &'task TaskState<B>,
+    frame: LocalFrame<'ir, B>,
+    forks: Worker<Task<'ir, 'task, B>>,
+    max_call_depth: usize,
+    since_deadline_check: u32,
+}
+
+impl<'ir, 'task, B: BV> Runner<'ir, 'task, B> {
+    /// Start running a task, returning the runner along with a solver
+    /// for the task's path, which must be passed to every step.
+    pub fn new<'ctx>(tid: usize, task: Task<'ir, 'task, B>, ctx: &'ctx Context) -> (Self, Solver<'ctx, B>) {
+        let mut solver = Solver::from_checkpoint(ctx, task.checkpoint);
+        add_initial_defs(&mut solver, task.initial_defs);
+        if let Some((def, event)) = task.fork_cond {
+            solver.add_event(event);
+            solver.add(def)
+        };
+        let frame = unfreeze_frame(&task.frame);
+        solver.set_concrete_fast_path(frame.concrete_fast_path);
+        solver.set_event_filter(task.state.event_filter.clone());
+        let runner = Runner {
+            tid,
+            task_id: task.id,
+            fraction: task.fraction,
+            timeout: Timeout::unlimited().for_path(frame.path_timeout),
+            stop_conditions: task.stop_conditions,
+            task_state: task.state,
+            max_call_depth: frame.backtrace.len(),
+            frame,
+            forks: Worker::new_lifo(),
+            since_deadline_check: 0,
+        };
+        (runner, solver)
+    }
+
+    /// The current state of the path
+    pub fn frame(&self) -> &LocalFrame<'ir, B> {
+        &self.frame
+    }
+
+    /// The function being executed, and the index of the next
+    /// instruction in it
+    pub fn location(&self) -> (Name, usize) {
+        (self.frame.function_name, self.frame.pc)
+    }
+
+    /// Execute the next instruction. After the path has finished, or
+    /// an error is returned, the runner should not be stepped again.
+    pub fn step(
+        &mut self,
+        shared_state: &SharedState<'ir, B>,
+        solver: &mut Solver<B>,
+    ) -> Result<StepResult<'ir, 'task, B>, ExecError> {
+        let result = step(
+            self.tid,
+            self.task_id,
+            &mut self.fraction,
+            self.timeout,
+            self.stop_conditions,
+            &self.forks,
+            &mut self.frame,
+            self.task_state,
+            shared_state,
+            solver,
+            &mut self.max_call_depth,
+            &mut self.since_deadline_check,
+        )
+        .map_err(|err| {
+            err.executing(|| zencode::decode(shared_state.symtab.to_str(self.frame.function_name)), self.frame.pc)
+        })?;
+        let forks: Vec<_> = std::iter::from_fn(|| self.forks.pop()).collect();
+        if !forks.is_empty() {
+            return Ok(StepResult::Forked(forks));
+        }
+        Ok(match result {
+            Step::Continue => StepResult::Continue,
+            Step::Assigned(id) => StepResult::Assigned(id),
+            Step::Called(f) => StepResult::Called(f),
+            Step::Returned(f) => StepResult::Returned(f),
+            Step::Done(run) => StepResult::Finished(run),
+        })
+    }
+}
+
+/// A collector is run on the result of each path found via symbolic execution through the code. It
+/// takes the result of the execution, which is either a combination of the return value and local
+/// state at the end of the execution or an error, as well as the shared state and the SMT solver
+/// state associated with that execution. It build a final result for all the executions by
+/// collecting the results into a type R.
+pub type Collector<'ir, B, R> = dyn 'ir
+    + Sync
+    + Fn(
+        usize,
+        TaskId,
+        Result<(Run<B>, LocalFrame<'ir, B>), (ExecError, Backtrace)>,
+        PathStats,
+        &SharedState<'ir, B>,
+        Solver<B>,
+        &R,
+    );
+
+/// A handle given to a [GenerativeCollector] for adding tasks to the
+/// run whose paths it is collecting
+pub struct TaskInjector<'a, 'ir, 'task, B> {
+    global: &'a Injector<Task<'ir, 'task, B>>,
+    num_tasks: &'a AtomicUsize,
+}
+
+impl<'ir, 'task, B> TaskInjector<'_, 'ir, 'task, B> {
+    /// Add a task to the run. The run does not finish until every path
+    /// of the new task has been collected too.
+    pub fn push(&self, task: Task<'ir, 'task, B>) {
+        // Counted before the collector returns, so the orchestrating
+        // thread knows about the task before it sees the path which
+        // created it finish
+        self.num_tasks.fetch_add(1, Ordering::SeqCst);
+        self.global.push(task)
+    }
+}
+
+/// As [Collector], but also given a [TaskInjector] which can add
+/// follow-up tasks to the run, see [MultiCollector::Generative]. As such tasks
+/// are usually built from data the collector captures, it only needs
+/// to live for `'c`, rather than as long as the IR.
+pub type GenerativeCollector<'c, 'ir, 'task, B, R> = dyn 'c
+    + Sync
+    + Fn(
+        usize,
+        TaskId,
+        PathResult<'ir, B>,
+        PathStats,
+        &SharedState<'ir, B>,
+        Solver<B>,
+        &R,
+        &TaskInjector<'_, 'ir, 'task, B>,
+    );
+
+/// Whether symbolic execution explored every path, or stopped early
+/// because it reached the maximum number of paths it was given
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExplorationStatus {
+    Exhaustive,
+    Truncated,
+    /// Some worker threads panicked while executing a path, so the
+    /// rest of each such path was not explored
+    Panicked(Vec<WorkerPanic>),
+    /// The deadline given in [MultiOpts::deadline] passed
+    /// before every path was explored
+    TimedOut,
+    /// The collected results asked [MultiOpts::should_stop] to stop, e.g.
+    /// because a witness was found
+    Stopped,
+    /// Tasks written to disk to keep within [MultiOpts::memory_budget]
+    /// could not be loaded again, so their paths were not explored.
+    /// The exploration stops as for [MultiOpts::deadline].
+    SpillFailed(String),
+}
+
+/// A panic caught while a worker thread in [start_multi] was
+/// executing a path
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WorkerPanic {
+    pub tid: usize,
+    pub task_id: TaskId,
+    pub message: String,
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// The order in which each thread explores the paths forked from the
+/// tasks it executes. Threads in [start_multi] still steal work from
+/// each other, so only single-threaded execution is fully ordered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Strategy {
+    /// Explore the most recently forked path first
+    Dfs,
+    /// Explore the least recently forked path first
+    Bfs,
+    /// Explore the paths forked by each task in a random order, which
+    /// is the same each time for a given seed
+    Random(u64),
+}
+
+impl Strategy {
+    fn worker<T>(self) -> Worker<T> {
+        match self {
+            Strategy::Dfs | Strategy::Random(_) => Worker::new_lifo(),
+            Strategy::Bfs => Worker::new_fifo(),
+        }
+    }
+
+    /// The random number generator for the thread `tid`, if the
+    /// strategy uses one
+    fn rng(self, tid: usize) -> Option<StdRng> {
+        match self {
+            Strategy::Random(seed) => Some(StdRng::seed_from_u64(seed.wrapping_add(tid as u64))),
+            Strategy::Dfs | Strategy::Bfs => None,
+        }
+    }
+}
+
+/// Shuffle the tasks pushed to a queue since it held `before` tasks
+fn shuffle_forks<T>(queue: &Worker<T>, before: usize, rng: &mut StdRng) {
+    let forked = queue.len().saturating_sub(before);
+    let mut tasks: Vec<T> = std::iter::from_fn(|| queue.pop()).take(forked).collect();
+    tasks.shuffle(rng);
+    for task in tasks {
+        queue.push(task)
+    }
+}
 
 /// Start symbolically executing a Task using just the current thread, collecting the results using
-/// the given collector.
+/// the given collector. At most `max_paths` paths are executed, if given, in the order chosen by the
+/// strategy.
 pub fn start_single<'ir, B: BV, R>(
     task: Task<'ir, '_, B>,
+    max_paths: Option<usize>,
+    strategy: Strategy,
     shared_state: &SharedState<'ir, B>,
     collected: &R,
     collector: &Collector<'ir, B, R>,
-) {
-    let queue = Worker::new_lifo();
+) -> ExplorationStatus {
+    let queue = strategy.worker();
+    let mut rng = strategy.rng(0);
     queue.push(task);
+    let mut paths = 0;
     while let Some(mut task) = queue.pop() {
+        if max_paths.is_some_and(|max| paths >= max) {
+            return ExplorationStatus::Truncated;
+        }
+        paths += 1;
         let mut cfg = Config::new();
         cfg.set_param_value("model", "true");
         let ctx = Context::new(cfg);
         let mut solver = Solver::from_checkpoint(&ctx, task.checkpoint);
+        add_initial_defs(&mut solver, task.initial_defs);
         if let Some((def, event)) = task.fork_cond {
             solver.add_event(event);
             solver.add(def)
         };
-        let result = run(
+        let before = queue.len();
+        let (result, stats) = run(
             0,
             task.id,
             &mut task.fraction,
-            Timeout::unlimited(),
+            Timeout::unlimited().for_path(task.frame.path_timeout),
             task.stop_conditions,
             &queue,
             &task.frame,
@@ -1487,52 +2165,100 @@ pub fn start_single<'ir, B: BV, R>(
             shared_state,
             &mut solver,
         );
-        collector(0, task.id, result, shared_state, solver, collected)
+        if let Some(rng) = &mut rng {
+            shuffle_forks(&queue, before, rng)
+        }
+        collector(0, task.id, result, stats, shared_state, solver, collected)
+    }
+    ExplorationStatus::Exhaustive
+}
+
+/// Move the tasks pushed to a LIFO queue since it held `before` tasks
+/// onto the global queue if they were forked near enough to the root,
+/// see `LocalFrame::set_share_depth`. Other threads steal from the
+/// global queue first, so they pick up the large subtrees rather than
+/// whatever happens to be at the bottom of a busy thread's queue.
+fn share_shallow_forks<'ir, 'task, B>(
+    queue: &Worker<Task<'ir, 'task, B>>,
+    before: usize,
+    global: &Injector<Task<'ir, 'task, B>>,
+) {
+    let forked = queue.len().saturating_sub(before);
+    let tasks: Vec<_> = std::iter::from_fn(|| queue.pop()).take(forked).collect();
+    // Popping reversed the order the tasks were pushed in
+    for task in tasks.into_iter().rev() {
+        if task.frame.forks < task.frame.share_depth {
+            global.push(task)
+        } else {
+            queue.push(task)
+        }
     }
 }
 
 fn find_task<T>(local: &Worker<T>, global: &Injector<T>, stealers: &RwLock<Vec<Stealer<T>>>) -> Option<T> {
     let stealers = stealers.read().unwrap();
+    // The global queue holds the shallowest forks, so prefer it to
+    // stealing from other threads
     local.pop().or_else(|| {
-        std::iter::repeat_with(|| {
-            let stolen: Steal<T> = stealers.iter().map(|s| s.steal()).collect();
-            stolen.or_else(|| global.steal_batch_and_pop(local))
-        })
-        .find(|s| !s.is_retry())
-        .and_then(|s| s.success())
+        std::iter::repeat_with(|| global.steal().or_else(|| stealers.iter().map(|s| s.steal()).collect::<Steal<T>>()))
+            .find(|s| !s.is_retry())
+            .and_then(|s| s.success())
     })
 }
 
-fn do_work<'ir, 'task, B: BV, R>(
+/// Execute `task` with a solver created in `ctx`. Each worker thread
+/// reuses one context for all its tasks, as creating a context is
+/// slow compared to running a small task. Nothing is shared between
+/// the solvers, as each is restored from its task's checkpoint, so
+/// the context must not have any solver-specific parameters set.
+fn do_work<'ir, 'task, B: BV>(
     tid: usize,
-    timeout: Timeout,
+    timeout: Timeout<'_>,
     queue: &Worker<Task<'ir, 'task, B>>,
     mut task: Task<'ir, 'task, B>,
+    ctx: &Context,
     shared_state: &SharedState<'ir, B>,
-    collected: &R,
-    collector: &Collector<'ir, B, R>,
-) -> Fraction {
-    let cfg = Config::new();
-    let ctx = Context::new(cfg);
-    let mut solver = Solver::from_checkpoint(&ctx, task.checkpoint);
+    collect: &CollectFn<'_, 'ir, B>,
+) -> (Fraction, Option<WorkerPanic>) {
+    let mut solver = Solver::from_checkpoint(ctx, task.checkpoint);
+    add_initial_defs(&mut solver, task.initial_defs);
     if let Some((def, event)) = task.fork_cond {
         solver.add_event(event);
         solver.add(def)
     };
-    let result = run(
-        tid,
-        task.id,
-        &mut task.fraction,
-        timeout,
-        task.stop_conditions,
-        queue,
-        &task.frame,
-        task.state,
-        shared_state,
-        &mut solver,
-    );
-    collector(tid, task.id, result, shared_state, solver, collected);
-    task.fraction
+    // A panic while running the path is caught so the fraction of the
+    // task it was responsible for can still be reported as done,
+    // otherwise start_multi would wait for it forever. Any paths
+    // already forked from it are unaffected.
+    let ran = panic::catch_unwind(AssertUnwindSafe(|| {
+        run(
+            tid,
+            task.id,
+            &mut task.fraction,
+            timeout.for_path(task.frame.path_timeout),
+            task.stop_conditions,
+            queue,
+            &task.frame,
+            task.state,
+            shared_state,
+            &mut solver,
+        )
+    }));
+    let (result, stats, caught) = match ran {
+        Ok((result, stats)) => (result, stats, None),
+        Err(payload) => {
+            let message = panic_message(payload);
+            log_from!(tid, log::VERBOSE, format!("Worker panicked: {}", message));
+            let caught = WorkerPanic { tid, task_id: task.id, message: message.clone() };
+            (Err((ExecError::WorkerPanic(message), Vec::new())), PathStats::default(), Some(caught))
+        }
+    };
+    let collected = panic::catch_unwind(AssertUnwindSafe(|| collect(task.id, result, stats, solver)));
+    let caught = match collected {
+        Ok(()) => caught,
+        Err(payload) => caught.or_else(|| Some(WorkerPanic { tid, task_id: task.id, message: panic_message(payload) })),
+    };
+    (task.fraction, caught)
 }
 
 enum Response {
@@ -1541,34 +2267,200 @@ enum Response {
 }
 
 enum Progress {
-    Finished { tid: usize, task_id: TaskId, frac: Fraction },
+    Finished { tid: usize, task_id: TaskId, frac: Fraction, panicked: Option<WorkerPanic>, log: Option<log::TaskLog> },
     Idle { tid: usize },
 }
 
-/// Start symbolically executing a Task across `num_threads` new threads, collecting the results
-/// using the given collector.
-pub fn start_multi<'ir, B: BV, R>(
-    num_threads: usize,
-    timeout: Option<u64>,
-    tasks: Vec<Task<'ir, '_, B>>,
-    shared_state: &SharedState<'ir, B>,
+/// A snapshot of how far [start_multi] has got, see
+/// [MultiOpts::progress]. The total number of paths is not known until
+/// execution finishes, so only the number of tasks gives a fixed bound.
+#[derive(Clone, Copy, Debug)]
+pub struct ExecutionProgress {
+    /// The number of paths that have been fully executed
+    pub paths: usize,
+    /// The number of tasks for which every path has been executed
+    pub tasks_finished: usize,
+    pub tasks: usize,
+}
+
+/// Collected results which can end an exploration early, see
+/// [MultiOpts::should_stop]
+pub trait ShouldStop {
+    fn should_stop(&self) -> bool;
+}
+
+/// Options for [start_multi]. The default runs a single thread with
+/// no limits, exploring each thread's paths depth first.
+pub struct MultiOpts<'a, R> {
+    pub num_threads: usize,
+    /// Make each path fail with [ExecError::Timeout] once this many
+    /// seconds have elapsed since execution started
+    pub timeout: Option<u64>,
+    /// Stop taking new tasks once this many paths have been executed,
+    /// so up to one path per thread more may be executed by tasks that
+    /// were already running
+    pub max_paths: Option<usize>,
+    /// The order in which each thread explores the paths it forks
+    pub strategy: Strategy,
+    /// Stop the whole exploration once this has passed, returning
+    /// [ExplorationStatus::TimedOut]. Unlike `timeout`, which makes
+    /// each path fail but still runs every remaining task, paths which
+    /// are in flight at the deadline stop at their next instruction
+    /// with [ExecError::Timeout] and no further tasks are started.
+    pub deadline: Option<Instant>,
+    /// Stop the whole exploration, as for `deadline`, as soon as this
+    /// returns true for the collected results, returning
+    /// [ExplorationStatus::Stopped]. Workers check after each path
+    /// they collect. This is useful when only one witness is needed,
+    /// see [ShouldStop] and [reachability_collector].
+    pub should_stop: Option<&'a (dyn Sync + Fn(&R) -> bool)>,
+    /// Periodically save the tasks which have not been run to a
+    /// checkpoint file, from which they can be loaded with
+    /// [load_checkpoint_file] if execution does not finish. Running
+    /// the loaded tasks explores exactly the paths which had not been
+    /// collected when the checkpoint was written. A final checkpoint
+    /// is written once the workers stop, which is empty if the
+    /// exploration was exhaustive. Paths cut short by the deadline or
+    /// stop predicate are collected as timeouts and are not saved.
+    pub checkpoints: Option<&'a CheckpointOpts>,
+    /// Called from the orchestrating thread each time a path finishes
+    pub progress: Option<&'a dyn Fn(ExecutionProgress)>,
+    /// Write the tasks waiting to be run to disk once there are more
+    /// than the budget allows, see [MemoryBudget]. Spilled tasks are
+    /// included in checkpoints. Use [streaming_trace_collector] to
+    /// keep the traces out of memory as well.
+    pub memory_budget: Option<&'a MemoryBudget>,
+}
+
+impl<R> Default for MultiOpts<'_, R> {
+    fn default() -> Self {
+        MultiOpts {
+            num_threads: 1,
+            timeout: None,
+            max_paths: None,
+            strategy: Strategy::Dfs,
+            deadline: None,
+            should_stop: None,
+            checkpoints: None,
+            progress: None,
+            memory_budget: None,
+        }
+    }
+}
+
+impl<R> MultiOpts<'_, R> {
+    pub fn new(num_threads: usize) -> Self {
+        MultiOpts { num_threads, ..MultiOpts::default() }
+    }
+}
+
+/// The collector given to [start_multi]
+pub enum MultiCollector<'c, 'ir, 'task, B, R> {
+    Plain(&'c Collector<'ir, B, R>),
+    /// The collector can add follow-up tasks to the run as it collects
+    /// each path, for example to explore the same function again under
+    /// an extra assumption. Idle worker threads pick up the new tasks,
+    /// and the run only finishes once every path of every task,
+    /// including those added, has been collected.
+    Generative(&'c GenerativeCollector<'c, 'ir, 'task, B, R>),
+}
+
+// Derived impls would require `B: Copy` and `R: Copy`
+impl<B, R> Clone for MultiCollector<'_, '_, '_, B, R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<B, R> Copy for MultiCollector<'_, '_, '_, B, R> {}
+
+/// Start symbolically executing tasks across `opts.num_threads` new
+/// threads, collecting the results using the given collector. If a
+/// thread panics while executing a path, the collector is given a
+/// [ExecError::WorkerPanic] error for it, and the other paths are
+/// still explored, with the panics returned in
+/// [ExplorationStatus::Panicked].
+pub fn start_multi<'ir, 'task, B: BV, R>(
+    tasks: Vec<Task<'ir, 'task, B>>,
+    shared_state: &'ir SharedState<'ir, B>,
     collected: Arc<R>,
-    collector: &Collector<'ir, B, R>,
-) where
+    collector: MultiCollector<'_, 'ir, 'task, B, R>,
+    opts: &MultiOpts<'_, R>,
+) -> ExplorationStatus
+where
     R: Send + Sync,
 {
-    let timeout = Timeout { start_time: Instant::now(), duration: timeout.map(Duration::from_secs) };
+    let MultiOpts {
+        num_threads,
+        timeout,
+        max_paths,
+        strategy,
+        deadline,
+        should_stop,
+        checkpoints,
+        progress: report,
+        memory_budget,
+    } = *opts;
+    let progress_callback = |p| {
+        if let Some(report) = report {
+            report(p)
+        }
+    };
+    let stop = AtomicBool::new(false);
+    // Set along with stop when it was the collected results, rather
+    // than the deadline, which stopped the exploration
+    let found = AtomicBool::new(false);
+    let timeout = Timeout::new(timeout).with_stop(&stop);
+    let stopped = |stop: &AtomicBool| stop.load(Ordering::SeqCst);
 
     let (tx, rx): (Sender<Progress>, Receiver<Progress>) = mpsc::channel();
     let global: Arc<Injector<Task<B>>> = Arc::new(Injector::<Task<B>>::new());
     let stealers: Arc<RwLock<Vec<Stealer<Task<B>>>>> = Arc::new(RwLock::new(Vec::new()));
-
-    let mut progress: HashMap<TaskId, Fraction, ahash::RandomState> = HashMap::default();
+    // Workers hold this for reading while they take and run a task, so
+    // holding it for writing guarantees every task which has not been
+    // run is in one of the queues, see checkpoint::drain_to_checkpoint
+    let running = RwLock::new(());
+    let mut spill = memory_budget.map(spill::TaskSpill::new);
+    let mut spill_error = None;
+
+    // Tasks loaded from a checkpoint share their original task's id,
+    // and together only cover the part of it which had not been
+    // explored, so the rest is counted as explored from the start
+    let mut unexplored: HashMap<TaskId, Fraction, ahash::RandomState> = HashMap::default();
+    for task in &tasks {
+        *unexplored.entry(task.id).or_insert(Fraction::zero()) += task.fraction.clone()
+    }
+    let mut progress: HashMap<TaskId, Fraction, ahash::RandomState> =
+        unexplored.iter().map(|(task_id, frac)| (*task_id, frac.complement())).collect();
+    // Grows as a generative collector adds tasks
+    let num_tasks = AtomicUsize::new(progress.len());
+    let mut paths = 0;
+    let mut panics = Vec::new();
+    // The log lines of each task in the order the tasks finished,
+    // written together once the run finishes, see log::begin_task
+    let mut task_logs = Vec::new();
+
+    // Counts the paths as the workers finish them, so they can stop
+    // taking tasks without waiting on the orchestrating thread
+    let paths_done = Arc::new(AtomicUsize::new(0));
+    let limit_reached =
+        |paths_done: &AtomicUsize| max_paths.is_some_and(|max| paths_done.load(Ordering::SeqCst) >= max);
 
     for task in tasks {
         global.push(task);
     }
 
+    let mut record = |progress: &mut HashMap<TaskId, Fraction, ahash::RandomState>, task_id, frac| {
+        let current_fraction = progress.entry(task_id).or_insert(Fraction::zero());
+        *current_fraction += frac;
+        paths += 1;
+        progress_callback(ExecutionProgress {
+            paths,
+            tasks_finished: progress.values().filter(|frac| frac.is_one()).count(),
+            tasks: num_tasks.load(Ordering::SeqCst),
+        })
+    };
+
     thread::scope(|scope| {
         let mut poke_txs = Vec::new();
 
@@ -1583,18 +2475,55 @@ pub fn start_multi<'ir, B: BV, R>(
             let global = global.clone();
             let stealers = stealers.clone();
             let collected = collected.clone();
+            let paths_done = paths_done.clone();
+            let stop = &stop;
+            let found = &found;
+            let num_tasks = &num_tasks;
+            let running = &running;
 
             scope.spawn(move || {
-                let q = Worker::new_lifo();
+                let ctx = Context::new(Config::new());
+                let q = strategy.worker();
+                let mut rng = strategy.rng(tid);
+                let mut seq = 0;
                 {
                     let mut stealers = stealers.write().unwrap();
                     stealers.push(q.stealer());
                 }
+                let injector = TaskInjector { global: &global, num_tasks };
+                let collect = |task_id, result, stats, solver: Solver<B>| match collector {
+                    MultiCollector::Plain(collector) => {
+                        collector(tid, task_id, result, stats, shared_state, solver, collected.as_ref())
+                    }
+                    MultiCollector::Generative(collector) => {
+                        collector(tid, task_id, result, stats, shared_state, solver, collected.as_ref(), &injector)
+                    }
+                };
                 loop {
-                    while let Some(task) = find_task(&q, &global, &stealers) {
+                    while !limit_reached(&paths_done) && !stopped(stop) {
+                        let _running = running.read().unwrap();
+                        let Some(task) = find_task(&q, &global, &stealers) else { break };
                         let task_id = task.id;
-                        let frac = do_work(tid, timeout, &q, task, shared_state, collected.as_ref(), collector);
-                        thread_tx.send(Progress::Finished { tid, task_id, frac }).unwrap();
+                        let before = q.len();
+                        log::begin_task();
+                        let (frac, panicked) = do_work(tid, timeout, &q, task, &ctx, shared_state, &collect);
+                        let task_log = log::end_task(tid, seq);
+                        seq += 1;
+                        if let Some(rng) = &mut rng {
+                            shuffle_forks(&q, before, rng)
+                        }
+                        // A FIFO queue already hands out its shallowest tasks first
+                        if strategy != Strategy::Bfs {
+                            share_shallow_forks(&q, before, &global)
+                        }
+                        paths_done.fetch_add(1, Ordering::SeqCst);
+                        if should_stop.is_some_and(|should_stop| should_stop(collected.as_ref())) {
+                            found.store(true, Ordering::SeqCst);
+                            stop.store(true, Ordering::SeqCst)
+                        }
+                        thread_tx
+                            .send(Progress::Finished { tid, task_id, frac, panicked, log: Some(task_log) })
+                            .unwrap();
                     }
                     thread_tx.send(Progress::Idle { tid }).unwrap();
                     match poke_rx.recv().unwrap() {
@@ -1606,31 +2535,80 @@ pub fn start_multi<'ir, B: BV, R>(
         }
 
         let mut is_idle = vec![false; num_threads];
+        let mut is_killed = vec![false; num_threads];
+        let mut last_checkpoint = Instant::now();
         loop {
             loop {
                 match rx.try_recv() {
-                    Ok(Progress::Finished { tid, task_id, frac }) => {
-                        let current_fraction = progress.entry(task_id).or_insert(Fraction::zero());
-                        *current_fraction += frac;
+                    Ok(Progress::Finished { tid, task_id, frac, panicked, log }) => {
+                        record(&mut progress, task_id, frac);
+                        panics.extend(panicked);
+                        task_logs.extend(log);
                         is_idle[tid] = false
                     }
                     Ok(Progress::Idle { tid }) => is_idle[tid] = true,
                     Err(_) => break,
                 }
             }
+            if let Some(opts) = checkpoints {
+                if last_checkpoint.elapsed() >= opts.interval {
+                    // Wait for the busy workers to finish their paths
+                    let _paused = running.write().unwrap();
+                    let stealers = stealers.read().unwrap();
+                    if let Err(err) = checkpoint::drain_to_checkpoint(&global, &stealers, spill.as_ref(), &opts.path) {
+                        log!(log::VERBOSE, &format!("Failed to write checkpoint: {}", err))
+                    }
+                    last_checkpoint = Instant::now()
+                }
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                stop.store(true, Ordering::SeqCst)
+            }
+            // After the deadline (or once the collected results say
+            // to stop) busy threads stop at their next instruction,
+            // then go idle rather than taking another task, so they
+            // can be killed instead of woken up
+            if stopped(&stop) {
+                for (tid, idle) in is_idle.iter().enumerate() {
+                    if *idle && !is_killed[tid] {
+                        poke_txs[tid].send(Response::Kill).unwrap();
+                        is_killed[tid] = true
+                    }
+                }
+                if is_killed.iter().all(|killed| *killed) {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(1));
+                continue;
+            }
+            if let Some(spill) = &mut spill {
+                let stealers = stealers.read().unwrap();
+                if let Err(err) = spill.spill(&global, &stealers) {
+                    log!(log::VERBOSE, &format!("Failed to spill tasks: {}", err))
+                }
+                // The spilled tasks are not kept anywhere else, so the
+                // exploration can't be completed without them
+                if let Err(err) = spill.refill(&global, &stealers, shared_state) {
+                    log!(log::VERBOSE, &format!("Failed to load spilled tasks: {}", err));
+                    spill_error = Some(err);
+                    stop.store(true, Ordering::SeqCst);
+                    continue;
+                }
+            }
             // Try to wake up any idle threads
             for (tid, idle) in is_idle.iter().enumerate() {
                 if *idle {
                     poke_txs[tid].send(Response::Poke).unwrap()
                 }
             }
-            let mut all_tasks_complete = true;
-            for (_, frac) in progress.iter() {
-                if !frac.is_one() {
-                    all_tasks_complete = false;
-                }
-            }
-            if all_tasks_complete {
+            // A task added by a generative collector is only complete
+            // once it has reported progress, otherwise we could stop
+            // before any of its paths has finished
+            let all_tasks_complete =
+                progress.len() == num_tasks.load(Ordering::SeqCst) && progress.values().all(Fraction::is_one);
+            // Once the limit is reached the workers go idle instead of
+            // taking new tasks, so there is nothing left to wait for
+            if all_tasks_complete || (limit_reached(&paths_done) && is_idle.iter().all(|idle| *idle)) {
                 for poke_tx in poke_txs.iter() {
                     poke_tx.send(Response::Kill).unwrap()
                 }
@@ -1638,7 +2616,43 @@ pub fn start_multi<'ir, B: BV, R>(
             }
             thread::sleep(Duration::from_millis(1))
         }
-    })
+    });
+
+    // Workers which were still running a task when they were killed
+    // report it before they exit
+    while let Ok(message) = rx.try_recv() {
+        if let Progress::Finished { task_id, frac, panicked, log, .. } = message {
+            record(&mut progress, task_id, frac);
+            panics.extend(panicked);
+            task_logs.extend(log)
+        }
+    }
+
+    log::flush_task_logs(&task_logs);
+
+    // Each worker's queue outlives it in its stealer, so the tasks left
+    // when the exploration was truncated or stopped can still be saved
+    if let Some(opts) = checkpoints {
+        if let Err(err) =
+            checkpoint::drain_to_checkpoint(&global, &stealers.read().unwrap(), spill.as_ref(), &opts.path)
+        {
+            log!(log::VERBOSE, &format!("Failed to write checkpoint: {}", err))
+        }
+    }
+
+    if let Some(err) = spill_error {
+        ExplorationStatus::SpillFailed(err.to_string())
+    } else if !panics.is_empty() {
+        ExplorationStatus::Panicked(panics)
+    } else if stopped(&found) {
+        ExplorationStatus::Stopped
+    } else if stopped(&stop) {
+        ExplorationStatus::TimedOut
+    } else if progress.len() == num_tasks.load(Ordering::SeqCst) && progress.values().all(Fraction::is_one) {
+        ExplorationStatus::Exhaustive
+    } else {
+        ExplorationStatus::Truncated
+    }
 }
 
 type Spawner<'ir, 'task, B, R> = dyn Fn(&R) -> Vec<Task<'ir, 'task, B>>;
@@ -1661,7 +2675,7 @@ pub fn start_multi_per_task<'ir, 'task, B: BV, R>(
 where
     R: Send + Sync + Collection,
 {
-    let timeout = Timeout { start_time: Instant::now(), duration: timeout.map(Duration::from_secs) };
+    let timeout = Timeout::new(timeout);
 
     let (tx, rx): (Sender<Progress>, Receiver<Progress>) = mpsc::channel();
     let global: Arc<Injector<Task<B>>> = Arc::new(Injector::<Task<B>>::new());
@@ -1693,6 +2707,7 @@ where
             let collected_lock = &collected_lock;
 
             scope.spawn(move || {
+                let ctx = Context::new(Config::new());
                 let q = Worker::new_lifo();
                 {
                     let mut stealers = stealers.write().unwrap();
@@ -1703,8 +2718,13 @@ where
                         let task_id = task.id;
                         let collected = collected_lock.read().unwrap();
                         let task_results = collected.get(&task_id).unwrap();
-                        let frac = do_work(tid, timeout, &q, task, shared_state, task_results, collector);
-                        thread_tx.send(Progress::Finished { tid, task_id, frac }).unwrap();
+                        let before = q.len();
+                        let collect = |task_id, result, stats, solver: Solver<B>| {
+                            collector(tid, task_id, result, stats, shared_state, solver, task_results)
+                        };
+                        let (frac, panicked) = do_work(tid, timeout, &q, task, &ctx, shared_state, &collect);
+                        share_shallow_forks(&q, before, &global);
+                        thread_tx.send(Progress::Finished { tid, task_id, frac, panicked, log: None }).unwrap();
                     }
                     thread_tx.send(Progress::Idle { tid }).unwrap();
                     match poke_rx.recv().unwrap() {
@@ -1719,7 +2739,7 @@ where
         loop {
             loop {
                 match rx.try_recv() {
-                    Ok(Progress::Finished { tid, task_id, frac }) => {
+                    Ok(Progress::Finished { tid, task_id, frac, .. }) => {
                         let current_fraction = progress.entry(task_id).or_insert(Fraction::zero());
                         *current_fraction += frac;
                         is_idle[tid] = false
@@ -1771,6 +2791,18 @@ where
     collected_lock.into_inner().unwrap()
 }
 
+/// The function names (decoded) and pcs of a backtrace, innermost
+/// first
+pub fn decode_backtrace<B: BV>(backtrace: &Backtrace, shared_state: &SharedState<B>) -> Vec<(String, usize)> {
+    backtrace.iter().rev().map(|(f, pc)| (zencode::decode(shared_state.symtab.to_str(*f)), *pc)).collect()
+}
+
+fn log_backtrace<B: BV>(tid: usize, backtrace: &Backtrace, shared_state: &SharedState<B>) {
+    for (f, pc) in decode_backtrace(backtrace, shared_state) {
+        log_from!(tid, log::VERBOSE, format!("  {} @ {}", f, pc));
+    }
+}
+
 /// This `Collector` is used for boolean Sail functions. It returns
 /// true via an AtomicBool if all reachable paths through the program
 /// are unsatisfiable, which implies that the function always returns
@@ -1779,6 +2811,7 @@ pub fn all_unsat_collector<'ir, B: BV>(
     tid: usize,
     _: TaskId,
     result: Result<(Run<B>, LocalFrame<'ir, B>), (ExecError, Backtrace)>,
+    _: PathStats,
     shared_state: &SharedState<'ir, B>,
     mut solver: Solver<B>,
     collected: &AtomicBool,
@@ -1808,15 +2841,71 @@ pub fn all_unsat_collector<'ir, B: BV>(
         Err((err, backtrace)) => {
             if_logging!(log::VERBOSE, {
                 log_from!(tid, log::VERBOSE, &format!("Got error, {:?}", err));
-                for (f, pc) in backtrace.iter().rev() {
-                    log_from!(tid, log::VERBOSE, format!("  {} @ {}", shared_state.symtab.to_str(*f), pc));
-                }
+                log_backtrace(tid, &backtrace, shared_state)
             });
             collected.store(false, Ordering::Release)
         }
     }
 }
 
+type ReachabilityPredicate<B> = dyn Send + Sync + Fn(&Val<B>, &mut Solver<B>) -> bool;
+
+/// Whether a function can return a value satisfying a predicate,
+/// with the trace of the first path found that does, as collected by
+/// [reachability_collector]
+pub struct Reachability<B> {
+    predicate: Box<ReachabilityPredicate<B>>,
+    witness: Mutex<Option<(TaskId, Vec<Event<B>>)>>,
+}
+
+impl<B: BV> Reachability<B> {
+    /// The predicate is given the value returned by each path, and the
+    /// solver with that path's constraints, so it can check whether a
+    /// symbolic value could satisfy it
+    pub fn new(predicate: impl 'static + Send + Sync + Fn(&Val<B>, &mut Solver<B>) -> bool) -> Self {
+        Reachability { predicate: Box::new(predicate), witness: Mutex::new(None) }
+    }
+
+    pub fn found(&self) -> bool {
+        self.witness.lock().unwrap().is_some()
+    }
+
+    /// The task and trace of the first path which satisfied the
+    /// predicate, if any did
+    pub fn witness(&self) -> Option<(TaskId, Vec<Event<B>>)> {
+        self.witness.lock().unwrap().clone()
+    }
+}
+
+impl<B: BV> ShouldStop for Reachability<B> {
+    fn should_stop(&self) -> bool {
+        self.found()
+    }
+}
+
+/// Records the first path whose return value satisfies the
+/// predicate. Use it with [MultiOpts::should_stop] to stop exploring
+/// once such a path is found. Failing paths are ignored, as they include
+/// the paths cut short when the exploration stops.
+pub fn reachability_collector<'ir, B: BV>(
+    _: usize,
+    task_id: TaskId,
+    result: PathResult<'ir, B>,
+    _: PathStats,
+    _: &SharedState<'ir, B>,
+    mut solver: Solver<B>,
+    collected: &Reachability<B>,
+) {
+    if let Ok((Run::Finished(value), _)) = result {
+        if !collected.found() && (collected.predicate)(&value, &mut solver) {
+            let mut witness = collected.witness.lock().unwrap();
+            if witness.is_none() {
+                *witness = Some((task_id, solver.trace().to_vec().into_iter().cloned().collect()))
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum TraceError {
     /// This is returned when we get an unexpected value at the end of
@@ -1826,8 +2915,10 @@ pub enum TraceError {
     /// When the trace suspended itself, and we aren't expecting it
     /// to, we cannot return a complete trace.
     UnexpectedSuspension,
-    /// An execution error occured when generating the trace
-    Exec { err: ExecError, model: Option<String> },
+    /// An execution error occured when generating the trace. The
+    /// backtrace contains the function and pc of each call leading to
+    /// the error, innermost first.
+    Exec { err: ExecError, model: Option<String>, backtrace: Vec<(String, usize)> },
 }
 
 impl IslaError for TraceError {
@@ -1845,19 +2936,38 @@ impl fmt::Display for TraceError {
         match self {
             TraceError::UnexpectedValue(s) => write!(f, "Unexpected value {}", s),
             TraceError::UnexpectedSuspension => write!(f, "Unexpected suspension"),
-            TraceError::Exec { err, model: Some(s) } => write!(f, "{}\nModel: {}", err, s),
-            TraceError::Exec { err, model: None } => write!(f, "{}", err),
+            TraceError::Exec { err, model, backtrace } => {
+                write!(f, "{}", err)?;
+                for (function, pc) in backtrace {
+                    write!(f, "\n  {} @ {}", function, pc)?
+                }
+                if let Some(s) = model {
+                    write!(f, "\nModel: {}", s)?
+                }
+                Ok(())
+            }
         }
     }
 }
 
 impl TraceError {
     pub fn exec(err: ExecError) -> Self {
-        TraceError::Exec { err, model: None }
+        TraceError::Exec { err, model: None, backtrace: Vec::new() }
     }
 
     fn exec_model<B: BV>(err: ExecError, model: Model<B>) -> Self {
-        TraceError::Exec { err, model: Some(format!("{:?}", model)) }
+        TraceError::Exec { err, model: Some(format!("{:?}", model)), backtrace: Vec::new() }
+    }
+
+    /// Attach the backtrace of the path that failed to an execution
+    /// error
+    pub fn with_backtrace<B: BV>(self, backtrace: &Backtrace, shared_state: &SharedState<B>) -> Self {
+        match self {
+            TraceError::Exec { err, model, .. } => {
+                TraceError::Exec { err, model, backtrace: decode_backtrace(backtrace, shared_state) }
+            }
+            err => err,
+        }
     }
 
     fn unexpected_value<B: BV>(v: Val<B>) -> Self {
@@ -1871,10 +2981,49 @@ pub type TraceResultQueue<B> = SegQueue<Result<(TaskId, bool, Vec<Event<B>>), Tr
 
 pub type TraceValueQueue<B> = SegQueue<Result<(TaskId, Val<B>, Vec<Event<B>>), TraceError>>;
 
+/// A completed path, as collected by [structured_trace_collector]
+#[derive(Debug)]
+pub struct TracePath<B> {
+    pub task_id: TaskId,
+    /// The value returned by the path, or `None` if it exited
+    pub value: Option<Val<B>>,
+    /// The events of the path in the order they occured, with any
+    /// unused SMT definitions removed
+    pub events: Vec<Event<B>>,
+}
+
+pub type StructuredTraceQueue<B> = SegQueue<Result<TracePath<B>, TraceError>>;
+
+/// The final values of some registers on a path, by name, see
+/// `LocalFrame::register_snapshot`
+pub type RegisterSnapshot<B> = HashMap<String, Val<B>>;
+
+/// The register snapshot and trace of a path
+pub type FinalState<B> = Result<(TaskId, RegisterSnapshot<B>, Vec<Event<B>>), TraceError>;
+
+/// The registers to snapshot at the end of each path, and the
+/// snapshots taken by [final_state_collector]
+pub struct FinalStateQueue<B> {
+    registers: Vec<String>,
+    states: SegQueue<FinalState<B>>,
+}
+
+impl<B> FinalStateQueue<B> {
+    pub fn new(registers: &[&str]) -> Self {
+        FinalStateQueue { registers: registers.iter().map(|r| r.to_string()).collect(), states: SegQueue::new() }
+    }
+
+    /// Take the snapshot for one path, along with its trace
+    pub fn pop(&self) -> Option<FinalState<B>> {
+        self.states.pop()
+    }
+}
+
 pub fn trace_collector<'ir, B: BV>(
     tid: usize,
     task_id: TaskId,
     result: Result<(Run<B>, LocalFrame<'ir, B>), (ExecError, Backtrace)>,
+    _: PathStats,
     shared_state: &SharedState<'ir, B>,
     mut solver: Solver<B>,
     collected: &TraceQueue<B>,
@@ -1890,24 +3039,92 @@ pub fn trace_collector<'ir, B: BV>(
         Ok((Run::Dead, _)) => (),
         Err((err, backtrace)) => {
             log_from!(tid, log::VERBOSE, format!("Error {:?}", err));
-            for (f, pc) in backtrace.iter().rev() {
-                log_from!(tid, log::VERBOSE, format!("  {} @ {}", shared_state.symtab.to_str(*f), pc));
-            }
-            if solver.check_sat(SourceLoc::unknown()) == SmtResult::Sat {
+            log_backtrace(tid, &backtrace, shared_state);
+            let err = if solver.check_sat(SourceLoc::unknown()) == SmtResult::Sat {
                 let model = Model::new(&solver);
-                collected.push(Err(TraceError::exec_model(err, model)))
+                TraceError::exec_model(err, model)
             } else {
-                collected.push(Err(TraceError::exec(err)))
-            }
+                TraceError::exec(err)
+            };
+            collected.push(Err(err.with_backtrace(&backtrace, shared_state)))
         }
     }
 }
 
+/// The path collected by [structured_trace_collector], or `None` for
+/// a dead path
+fn structured_trace<'ir, B: BV>(
+    tid: usize,
+    task_id: TaskId,
+    result: Result<(Run<B>, LocalFrame<'ir, B>), (ExecError, Backtrace)>,
+    shared_state: &SharedState<'ir, B>,
+    mut solver: Solver<B>,
+) -> Option<Result<TracePath<B>, TraceError>> {
+    solver.report_performance(shared_state.symtab.get_directory(), shared_state.symtab.files());
+
+    let value = match result {
+        Ok((Run::Finished(value), _)) => Some(value),
+        Ok((Run::Exit, _)) => None,
+        Ok((Run::Suspended, _)) => return Some(Err(TraceError::UnexpectedSuspension)),
+        Ok((Run::Dead, _)) => return None,
+        Err((err, backtrace)) => {
+            log_from!(tid, log::VERBOSE, format!("Error {:?}", err));
+            log_backtrace(tid, &backtrace, shared_state);
+            let err = if solver.check_sat(SourceLoc::unknown()) == SmtResult::Sat {
+                let model = Model::new(&solver);
+                TraceError::exec_model(err, model)
+            } else {
+                TraceError::exec(err)
+            };
+            return Some(Err(err.with_backtrace(&backtrace, shared_state)));
+        }
+    };
+
+    let mut events: Vec<Event<B>> = solver.trace().to_vec().into_iter().rev().cloned().collect();
+    simplify::remove_unused(&mut events);
+    Some(Ok(TracePath { task_id, value, events }))
+}
+
+/// As [trace_collector], but also collects the value of each path,
+/// and puts the events in order and simplifies them so they can be
+/// used directly.
+pub fn structured_trace_collector<'ir, B: BV>(
+    tid: usize,
+    task_id: TaskId,
+    result: Result<(Run<B>, LocalFrame<'ir, B>), (ExecError, Backtrace)>,
+    _: PathStats,
+    shared_state: &SharedState<'ir, B>,
+    solver: Solver<B>,
+    collected: &StructuredTraceQueue<B>,
+) {
+    if let Some(path) = structured_trace(tid, task_id, result, shared_state, solver) {
+        collected.push(path)
+    }
+}
+
+/// As [structured_trace_collector], but writes each trace to the
+/// worker's file as soon as the path finishes, so the traces of a
+/// very large exploration are not held in memory, see [TraceFiles]
+pub fn streaming_trace_collector<'ir, B: BV>(
+    tid: usize,
+    task_id: TaskId,
+    result: Result<(Run<B>, LocalFrame<'ir, B>), (ExecError, Backtrace)>,
+    _: PathStats,
+    shared_state: &SharedState<'ir, B>,
+    solver: Solver<B>,
+    collected: &TraceFiles<B>,
+) {
+    if let Some(path) = structured_trace(tid, task_id, result, shared_state, solver) {
+        collected.push(tid, path)
+    }
+}
+
 pub fn trace_value_collector<'ir, B: BV>(
     _: usize,
     task_id: TaskId,
     result: Result<(Run<B>, LocalFrame<'ir, B>), (ExecError, Backtrace)>,
-    _: &SharedState<'ir, B>,
+    _: PathStats,
+    shared_state: &SharedState<'ir, B>,
     mut solver: Solver<B>,
     collected: &TraceValueQueue<B>,
 ) {
@@ -1918,13 +3135,14 @@ pub fn trace_value_collector<'ir, B: BV>(
         }
         Ok((Run::Exit | Run::Suspended, _)) => (),
         Ok((Run::Dead, _)) => (),
-        Err((err, _)) => {
-            if solver.check_sat(SourceLoc::unknown()) == SmtResult::Sat {
+        Err((err, backtrace)) => {
+            let err = if solver.check_sat(SourceLoc::unknown()) == SmtResult::Sat {
                 let model = Model::new(&solver);
-                collected.push(Err(TraceError::exec_model(err, model)))
+                TraceError::exec_model(err, model)
             } else {
-                collected.push(Err(TraceError::exec(err)))
-            }
+                TraceError::exec(err)
+            };
+            collected.push(Err(err.with_backtrace(&backtrace, shared_state)))
         }
     }
 }
@@ -1933,7 +3151,8 @@ pub fn trace_result_collector<'ir, B: BV>(
     _: usize,
     task_id: TaskId,
     result: Result<(Run<B>, LocalFrame<'ir, B>), (ExecError, Backtrace)>,
-    _: &SharedState<'ir, B>,
+    _: PathStats,
+    shared_state: &SharedState<'ir, B>,
     solver: Solver<B>,
     collected: &TraceResultQueue<B>,
 ) {
@@ -1946,7 +3165,93 @@ pub fn trace_result_collector<'ir, B: BV>(
         Ok((Run::Suspended, _)) => collected.push(Err(TraceError::UnexpectedSuspension)),
         Ok((Run::Finished(val), _)) => collected.push(Err(TraceError::unexpected_value(val))),
         Ok((Run::Dead, _)) => (),
-        Err((err, _)) => collected.push(Err(TraceError::exec(err))),
+        Err((err, backtrace)) => collected.push(Err(TraceError::exec(err).with_backtrace(&backtrace, shared_state))),
+    }
+}
+
+/// Records the final values of the registers named in the queue for
+/// each path that finishes or exits. Registers the path never touched
+/// are declared as fresh symbolic values, which appear in its trace.
+pub fn final_state_collector<'ir, B: BV>(
+    _: usize,
+    task_id: TaskId,
+    result: PathResult<'ir, B>,
+    _: PathStats,
+    shared_state: &SharedState<'ir, B>,
+    mut solver: Solver<B>,
+    collected: &FinalStateQueue<B>,
+) {
+    match result {
+        Ok((Run::Finished(_) | Run::Exit, mut frame)) => {
+            let registers: Vec<&str> = collected.registers.iter().map(String::as_str).collect();
+            match frame.register_snapshot(&registers, shared_state, &mut solver) {
+                Ok(snapshot) => {
+                    let mut events = solver.trace().to_vec();
+                    collected.states.push(Ok((task_id, snapshot, events.drain(..).cloned().collect())))
+                }
+                Err(err) => collected.states.push(Err(TraceError::exec(err))),
+            }
+        }
+        Ok((Run::Suspended, _)) => collected.states.push(Err(TraceError::UnexpectedSuspension)),
+        Ok((Run::Dead, _)) => (),
+        Err((err, backtrace)) => {
+            collected.states.push(Err(TraceError::exec(err).with_backtrace(&backtrace, shared_state)))
+        }
+    }
+}
+
+/// The union of the memory footprints of every path through each
+/// opcode, as collected by [memory_footprint_collector]
+pub struct MemoryFootprints<B> {
+    opcodes: Vec<B>,
+    footprints: Mutex<HashMap<B, Vec<FootprintEntry<B>>>>,
+    errors: SegQueue<TraceError>,
+}
+
+impl<B: BV> MemoryFootprints<B> {
+    /// The task with id `i` must be executing `opcodes[i]`
+    pub fn new(opcodes: Vec<B>) -> Self {
+        MemoryFootprints { opcodes, footprints: Mutex::new(HashMap::new()), errors: SegQueue::new() }
+    }
+
+    /// The footprint of an opcode, or `None` if no path through it
+    /// completed
+    pub fn footprint(&self, opcode: B) -> Option<Vec<FootprintEntry<B>>> {
+        self.footprints.lock().unwrap().get(&opcode).cloned()
+    }
+
+    pub fn pop_error(&self) -> Option<TraceError> {
+        self.errors.pop()
+    }
+}
+
+/// Adds the memory accesses of each path that finishes or exits to
+/// the footprint of the opcode executed by its task. Accesses are
+/// compared literally, so the same symbolic address on two different
+/// paths is only recorded once if it is the same variable.
+pub fn memory_footprint_collector<'ir, B: BV>(
+    _: usize,
+    task_id: TaskId,
+    result: PathResult<'ir, B>,
+    _: PathStats,
+    shared_state: &SharedState<'ir, B>,
+    _: Solver<B>,
+    collected: &MemoryFootprints<B>,
+) {
+    match result {
+        Ok((Run::Finished(_) | Run::Exit, frame)) => {
+            let opcode = collected.opcodes[task_id.as_usize()];
+            let mut footprints = collected.footprints.lock().unwrap();
+            let footprint = footprints.entry(opcode).or_default();
+            for entry in frame.memory().footprint() {
+                if !footprint.contains(entry) {
+                    footprint.push(entry.clone())
+                }
+            }
+        }
+        Ok((Run::Suspended, _)) => collected.errors.push(TraceError::UnexpectedSuspension),
+        Ok((Run::Dead, _)) => (),
+        Err((err, backtrace)) => collected.errors.push(TraceError::exec(err).with_backtrace(&backtrace, shared_state)),
     }
 }
 
@@ -1954,7 +3259,8 @@ pub fn footprint_collector<'ir, B: BV>(
     _: usize,
     task_id: TaskId,
     result: Result<(Run<B>, LocalFrame<'ir, B>), (ExecError, Backtrace)>,
-    _: &SharedState<'ir, B>,
+    _: PathStats,
+    shared_state: &SharedState<'ir, B>,
     solver: Solver<B>,
     collected: &TraceQueue<B>,
 ) {
@@ -1971,6 +3277,2240 @@ pub fn footprint_collector<'ir, B: BV>(
 
         Ok((Run::Suspended, _)) => collected.push(Err(TraceError::UnexpectedSuspension)),
 
-        Err((err, _)) => collected.push(Err(TraceError::exec(err))),
+        Err((err, backtrace)) => collected.push(Err(TraceError::exec(err).with_backtrace(&backtrace, shared_state))),
+    }
+}
+
+/// The unsat core of each dead path, or `None` if one could not be
+/// found, as collected by [dead_path_collector]
+pub type DeadPathQueue = SegQueue<(TaskId, Option<Vec<Origin>>)>;
+
+/// Explains each dead path with the origins of an unsat core of its
+/// assertions (see [Solver::unsat_core]), ignoring every other path.
+/// Finding the core replays the path, so this is for diagnosing
+/// unexpected dead paths, such as from contradictory initial
+/// constraints.
+pub fn dead_path_collector<'ir, B: BV>(
+    _: usize,
+    task_id: TaskId,
+    result: PathResult<'ir, B>,
+    _: PathStats,
+    _: &SharedState<'ir, B>,
+    solver: Solver<B>,
+    collected: &DeadPathQueue,
+) {
+    if let Ok((Run::Dead, _)) = result {
+        collected.push((task_id, solver.unsat_core()))
+    }
+}
+
+/// Gathers the statistics of every path, whatever its result
+pub fn stats_collector<'ir, B: BV>(
+    _: usize,
+    _: TaskId,
+    _: Result<(Run<B>, LocalFrame<'ir, B>), (ExecError, Backtrace)>,
+    stats: PathStats,
+    _: &SharedState<'ir, B>,
+    _: Solver<B>,
+    collected: &Mutex<PathStatsSummary>,
+) {
+    collected.lock().unwrap().record(&stats)
+}
+
+/// Records which way every branch went along each path, whatever its
+/// result
+pub fn coverage_collector<'ir, B: BV>(
+    _: usize,
+    _: TaskId,
+    _: Result<(Run<B>, LocalFrame<'ir, B>), (ExecError, Backtrace)>,
+    _: PathStats,
+    _: &SharedState<'ir, B>,
+    solver: Solver<B>,
+    collected: &Mutex<Coverage>,
+) {
+    record_branches(&mut collected.lock().unwrap(), solver.trace().to_vec())
+}
+
+/// How a path ended, for recording it in a [ForkTree]
+pub fn path_outcome<B>(result: &PathResult<'_, B>) -> PathOutcome {
+    match result {
+        Ok((Run::Finished(_) | Run::Exit | Run::Suspended, _)) => PathOutcome::Finished,
+        Ok((Run::Dead, _)) => PathOutcome::Dead,
+        Err(_) => PathOutcome::Failed,
+    }
+}
+
+/// Merges the forks along every path into a [ForkTree], counting how
+/// each path ended
+pub fn fork_tree_collector<'ir, B: BV>(
+    _: usize,
+    _: TaskId,
+    result: Result<(Run<B>, LocalFrame<'ir, B>), (ExecError, Backtrace)>,
+    _: PathStats,
+    _: &SharedState<'ir, B>,
+    solver: Solver<B>,
+    collected: &Mutex<ForkTree>,
+) {
+    collected.lock().unwrap().record_path(solver.trace().to_vec().into_iter().rev(), path_outcome(&result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ISAConfig;
+    use crate::init::initialize_architecture;
+    use crate::ir::Def;
+    use crate::memory::ReadOrWrite;
+    use crate::register::UninitPolicy;
+
+    const CONFIG: &str = r#"
+        pc = "PC"
+        assembler = "false"
+        objdump = "false"
+        nm = "false"
+        linker = "false"
+
+        [mmu]
+        page_table_base = "0x300000"
+        page_size = "4096"
+        s2_page_table_base = "0x200000"
+        s2_page_size = "4096"
+
+        [threads]
+        base = "0x400000"
+        top = "0x500000"
+        stride = "0x1000"
+
+        [symbolic_addrs]
+        base = "0x600000"
+        top = "0x700000"
+        stride = "0x10"
+    "#;
+
+    /// The value and trace of each path, as collected by [trace_value_collector]
+    type TraceValues = Vec<Result<(Val<B64>, Vec<Event<B64>>), TraceError>>;
+
+    /// Run a function which returns the result of calling an outcome
+    /// that has no primop, with the given extra configuration
+    fn run_outcome(extra_config: &str) -> TraceValues {
+        run_main(extra_config, None, |outcome, x| {
+            vec![Instr::Call(Loc::Id(RETURN), false, outcome, vec![Exp::Id(x)], SourceLoc::unknown()), Instr::End]
+        })
+    }
+
+    /// Run a main function with the given body, which is passed the
+    /// name of an outcome with no primop and of its argument
+    fn run_main(
+        extra_config: &str,
+        max_instructions: Option<u64>,
+        body: impl Fn(Name, Name) -> Vec<Instr<Name, B64>>,
+    ) -> TraceValues {
+        run_main_bounded(extra_config, |task| task.set_max_instructions(max_instructions), None, None, body).0
+    }
+
+    /// As [run_main], but with the task set up by `configure`, stopping
+    /// after `max_paths` paths, and using [start_multi] with the given
+    /// number of threads if there are any
+    fn run_main_bounded(
+        extra_config: &str,
+        configure: impl FnOnce(&mut Task<'_, '_, B64>),
+        max_paths: Option<usize>,
+        num_threads: Option<usize>,
+        body: impl Fn(Name, Name) -> Vec<Instr<Name, B64>>,
+    ) -> (TraceValues, ExplorationStatus) {
+        with_main(
+            extra_config,
+            |_, outcome, x| body(outcome, x),
+            |mut task, shared_state| {
+                configure(&mut task);
+                let queue = Arc::new(TraceValueQueue::new());
+                let status = match num_threads {
+                    Some(num_threads) => start_multi(
+                        vec![task],
+                        shared_state,
+                        queue.clone(),
+                        MultiCollector::Plain(&trace_value_collector),
+                        &MultiOpts { max_paths, ..MultiOpts::new(num_threads) },
+                    ),
+                    None => start_single(
+                        task,
+                        max_paths,
+                        Strategy::Dfs,
+                        shared_state,
+                        queue.as_ref(),
+                        &trace_value_collector,
+                    ),
+                };
+                let mut results = Vec::new();
+                while let Some(result) = queue.pop() {
+                    results.push(result.map(|(_, value, events)| (value, events)))
+                }
+                (results, status)
+            },
+        )
+    }
+
+    /// Set up a task for a main function with the given body, and pass
+    /// it to `f` to be executed. The body can add any other names it
+    /// needs to the symbol table.
+    fn with_main<T>(
+        extra_config: &str,
+        body: impl FnOnce(&mut Symtab, Name, Name) -> Vec<Instr<Name, B64>>,
+        f: impl for<'ir, 'task> FnOnce(Task<'ir, 'task, B64>, &'ir SharedState<'ir, B64>) -> T,
+    ) -> T {
+        with_main_and_defs(extra_config, |symtab, outcome, x| (body(symtab, outcome, x), Vec::new()), f)
+    }
+
+    /// As [with_main], but the body can also define other functions
+    fn with_main_and_defs<T>(
+        extra_config: &str,
+        body: impl FnOnce(&mut Symtab, Name, Name) -> (Vec<Instr<Name, B64>>, Vec<Def<Name, B64>>),
+        f: impl for<'ir, 'task> FnOnce(Task<'ir, 'task, B64>, &'ir SharedState<'ir, B64>) -> T,
+    ) -> T {
+        with_config_and_defs("", extra_config, body, f)
+    }
+
+    /// As [with_main_and_defs], with `top_config` added before the
+    /// rest of the config, where it can set top-level keys
+    fn with_config_and_defs<T>(
+        top_config: &str,
+        extra_config: &str,
+        body: impl FnOnce(&mut Symtab, Name, Name) -> (Vec<Instr<Name, B64>>, Vec<Def<Name, B64>>),
+        f: impl for<'ir, 'task> FnOnce(Task<'ir, 'task, B64>, &'ir SharedState<'ir, B64>) -> T,
+    ) -> T {
+        let mut symtab = Symtab::new();
+        let pc = symtab.intern("zPC");
+        let outcome = symtab.intern("zchoose_feature");
+        let main = symtab.intern("zmain");
+        let x = symtab.intern("zx");
+        let mut defs: Vec<Def<Name, B64>> = vec![
+            Def::Register(pc, Ty::Bits(64), vec![]),
+            Def::Extern(outcome, false, "choose_feature".to_string(), vec![Ty::Unit], Ty::Bits(2)),
+            Def::Val(main, vec![Ty::Unit], Ty::Bits(2)),
+        ];
+        let (main_body, extra_defs) = body(&mut symtab, outcome, x);
+        defs.push(Def::Fn(main, vec![x], main_body));
+        defs.extend(extra_defs);
+        let type_info = IRTypeInfo::new(&defs);
+        let isa_config: ISAConfig<B64> =
+            ISAConfig::parse(&format!("{}{}{}", top_config, CONFIG, extra_config), None, &symtab, &type_info).unwrap();
+        let iarch =
+            initialize_architecture(&mut defs, symtab, type_info, &isa_config, AssertionMode::Optimistic, false);
+        let shared_state = &iarch.shared_state;
+
+        let (args, ret_ty, instrs) = shared_state.functions.get(&main).unwrap();
+        let task_state = TaskState::new();
+        let task = LocalFrame::new(main, args, ret_ty, Some(&[Val::Unit]), instrs)
+            .add_lets(&iarch.lets)
+            .add_regs(&iarch.regs)
+            .task(TaskId::fresh(), &task_state);
+        f(task, shared_state)
+    }
+
+    #[test]
+    fn test_not_found_context() {
+        let results = run_outcome("");
+        let Err(TraceError::Exec { err, .. }) = &results[0] else { panic!("expected an execution error") };
+        assert!(matches!(err, ExecError::NoFunction(_, _, Some(executing)) if executing.pc == 0));
+        assert_eq!(err.to_string(), "Function choose_feature called from main at instruction 0 does not exist");
+
+        // The outcome is a function, so using it as a value fails
+        let results = run_main("", None, |outcome, _| {
+            vec![
+                Instr::Copy(Loc::Id(RETURN), Exp::Bits(B64::new(0, 2)), SourceLoc::unknown()),
+                Instr::Copy(Loc::Id(RETURN), Exp::Id(outcome), SourceLoc::unknown()),
+                Instr::End,
+            ]
+        });
+        assert_eq!(results.len(), 1);
+        let Err(TraceError::Exec { err, .. }) = &results[0] else { panic!("expected an execution error") };
+        assert_eq!(err.to_string(), "Variable choose_feature not found in main at instruction 1");
+    }
+
+    #[test]
+    fn test_outcome_stubs() {
+        let results = run_outcome("");
+        assert_eq!(results.len(), 1);
+        assert!(matches!(results[0], Err(TraceError::Exec { err: ExecError::NoFunction(..), .. })));
+
+        let results = run_outcome("[stubs]\nchoose_feature = { constant = \"0b10\" }");
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].as_ref().unwrap().0, Val::Bits(B64::new(0b10, 2)));
+
+        let results = run_outcome("[stubs]\nchoose_feature = \"symbolic\"");
+        assert_eq!(results.len(), 1);
+        assert!(results[0].as_ref().unwrap().0.is_symbolic());
+
+        let results = run_outcome("[stubs]\nchoose_feature = { fork = [\"0b01\", \"0b10\"] }");
+        assert_eq!(results.len(), 2);
+        let mut values = Vec::new();
+        for result in results {
+            let (value, events) = result.unwrap();
+            values.push(value);
+            // each path sees the call as an abstract event, returning a value fixed by the fork
+            let abstract_events: Vec<_> = events.iter().filter(|ev| ev.is_abstract()).collect();
+            assert_eq!(abstract_events.len(), 1);
+            assert!(events.iter().any(|ev| ev.is_fork()));
+        }
+        values.sort_by_key(|value| format!("{:?}", value));
+        assert_eq!(values, vec![Val::Bits(B64::new(0b01, 2)), Val::Bits(B64::new(0b10, 2))]);
+    }
+
+    #[test]
+    fn test_instruction_fuel() {
+        // after the call, main jumps back to the same instruction forever
+        let infinite_loop = |outcome, x| {
+            vec![Instr::Call(Loc::Id(RETURN), false, outcome, vec![Exp::Id(x)], SourceLoc::unknown()), Instr::Goto(1)]
+        };
+        let config = "[stubs]\nchoose_feature = { fork = [\"0b01\", \"0b10\"] }";
+
+        // both paths forked by the call inherit the limit
+        let results = run_main(config, Some(1000), infinite_loop);
+        assert_eq!(results.len(), 2);
+        for result in results {
+            assert!(matches!(result, Err(TraceError::Exec { err: ExecError::Fuel, .. })))
+        }
+
+        // a limit the path never reaches makes no difference
+        let results = run_main(config, Some(1000), |outcome, x| {
+            vec![Instr::Call(Loc::Id(RETURN), false, outcome, vec![Exp::Id(x)], SourceLoc::unknown()), Instr::End]
+        });
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(Result::is_ok));
+
+        // without any fuel the call is never reached, so never forks
+        let results = run_main(config, Some(0), infinite_loop);
+        assert_eq!(results.len(), 1);
+        assert!(matches!(results[0], Err(TraceError::Exec { err: ExecError::Fuel, .. })));
+    }
+
+    #[test]
+    fn test_path_timeout() {
+        let infinite_loop = |outcome, x| {
+            vec![Instr::Call(Loc::Id(RETURN), false, outcome, vec![Exp::Id(x)], SourceLoc::unknown()), Instr::Goto(1)]
+        };
+        let config = "[stubs]\nchoose_feature = { fork = [\"0b01\", \"0b10\"] }";
+        let timeout = Some(Duration::from_millis(50));
+
+        // both paths forked by the call inherit the limit
+        // the loop would otherwise reach the default backjump limit
+        let configure = |task: &mut Task<'_, '_, B64>| {
+            task.set_path_timeout(timeout);
+            task.set_max_backjumps(u32::MAX)
+        };
+
+        for num_threads in [None, Some(2)] {
+            let (results, _) = run_main_bounded(config, configure, None, num_threads, infinite_loop);
+            assert_eq!(results.len(), 2);
+            for result in results {
+                assert!(matches!(result, Err(TraceError::Exec { err: ExecError::PathTimeout, .. })))
+            }
+        }
+
+        let (results, _) = run_main_bounded(
+            config,
+            |task| task.set_path_timeout(timeout),
+            None,
+            None,
+            |outcome, x| {
+                vec![Instr::Call(Loc::Id(RETURN), false, outcome, vec![Exp::Id(x)], SourceLoc::unknown()), Instr::End]
+            },
+        );
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(Result::is_ok));
+    }
+
+    #[test]
+    fn test_deadline() {
+        let infinite_loop = |_: &mut Symtab, outcome, x| {
+            vec![Instr::Call(Loc::Id(RETURN), false, outcome, vec![Exp::Id(x)], SourceLoc::unknown()), Instr::Goto(1)]
+        };
+        let config = "[stubs]\nchoose_feature = { fork = [\"0b01\", \"0b10\"] }";
+
+        let start = Instant::now();
+        let (results, status) = with_main(config, infinite_loop, |mut task, shared_state| {
+            task.set_max_backjumps(u32::MAX);
+            let queue = Arc::new(TraceValueQueue::new());
+            let deadline = Instant::now() + Duration::from_millis(100);
+            let status = start_multi(
+                vec![task],
+                shared_state,
+                queue.clone(),
+                MultiCollector::Plain(&trace_value_collector),
+                &MultiOpts { deadline: Some(deadline), ..MultiOpts::new(2) },
+            );
+            (std::iter::from_fn(|| queue.pop()).collect::<Vec<_>>(), status)
+        });
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert_eq!(status, ExplorationStatus::TimedOut);
+        assert!(!results.is_empty());
+        for result in results {
+            assert!(matches!(result, Err(TraceError::Exec { err: ExecError::Timeout, .. })))
+        }
+    }
+
+    #[test]
+    fn test_stop_on_first_witness() {
+        // Monomorphizing a 6-bit variable gives 64 paths
+        let body = |_: &mut Symtab, _, x| {
+            vec![
+                Instr::Decl(x, Ty::Bits(6), SourceLoc::unknown()),
+                Instr::Monomorphize(x, SourceLoc::unknown()),
+                Instr::Copy(Loc::Id(RETURN), Exp::Bits(B64::new(0b01, 2)), SourceLoc::unknown()),
+                Instr::End,
+            ]
+        };
+        let run = |reachable: bool| {
+            with_main("", body, |task, shared_state| {
+                let checked = Arc::new(AtomicUsize::new(0));
+                let reachability = {
+                    let checked = checked.clone();
+                    Arc::new(Reachability::new(move |value: &Val<B64>, _: &mut Solver<B64>| {
+                        checked.fetch_add(1, Ordering::SeqCst);
+                        reachable && *value == Val::Bits(B64::new(0b01, 2))
+                    }))
+                };
+                let status = start_multi(
+                    vec![task],
+                    shared_state,
+                    reachability.clone(),
+                    MultiCollector::Plain(&reachability_collector),
+                    &MultiOpts { should_stop: Some(&Reachability::should_stop), ..MultiOpts::new(2) },
+                );
+                (status, checked.load(Ordering::SeqCst), reachability.witness().is_some())
+            })
+        };
+
+        let (status, checked, found) = run(true);
+        assert_eq!(status, ExplorationStatus::Stopped);
+        assert!(found);
+        assert!(checked < 64);
+
+        assert_eq!(run(false), (ExplorationStatus::Exhaustive, 64, false))
+    }
+
+    #[test]
+    fn test_generative_collector() {
+        // main forks on whether its argument, which is symbolic, is 0b01
+        let body = |_: &mut Symtab, _, x| {
+            let is_one = Exp::Call(Op::Eq, vec![Exp::Id(x), Exp::Bits(B64::new(0b01, 2))]);
+            vec![
+                Instr::Jump(is_one, 3, SourceLoc::unknown()),
+                Instr::Copy(Loc::Id(RETURN), Exp::Bits(B64::new(0b10, 2)), SourceLoc::unknown()),
+                Instr::End,
+                Instr::Copy(Loc::Id(RETURN), Exp::Bits(B64::new(0b01, 2)), SourceLoc::unknown()),
+                Instr::End,
+            ]
+        };
+
+        let (original, results) = with_main("", body, |mut task, shared_state| {
+            let ctx = Context::new(Config::new());
+            let mut solver = Solver::<B64>::new(&ctx);
+            let v = solver.declare_const(smtlib::Ty::BitVec(2), SourceLoc::unknown());
+            let point = checkpoint(&mut solver);
+            let x = shared_state.symtab.lookup("zx");
+            Arc::make_mut(&mut task.frame.local_state).vars.insert(x, UVal::Init(Val::Symbolic(v)));
+            task.checkpoint = point.clone();
+            let (original, frame, state) = (task.id, task.frame.clone(), task.state);
+
+            // re-run each path of the original task once, assuming the
+            // argument gives the same result
+            let queue = Arc::new(TraceValueQueue::new());
+            let status = start_multi(
+                vec![task],
+                shared_state,
+                queue.clone(),
+                MultiCollector::Generative(&move |tid,
+                                                  task_id,
+                                                  result,
+                                                  stats,
+                                                  shared_state,
+                                                  solver,
+                                                  collected,
+                                                  injector| {
+                    if let (true, Ok((Run::Finished(Val::Bits(bv)), _))) = (task_id == original, &result) {
+                        let is_one = smtlib::Exp::Eq(
+                            Box::new(smtlib::Exp::Var(v)),
+                            Box::new(smtlib::Exp::Bits64(B64::new(0b01, 2))),
+                        );
+                        let assumption =
+                            if bv.lower_u64() == 0b01 { is_one } else { smtlib::Exp::Not(Box::new(is_one)) };
+                        let ctx = Context::new(Config::new());
+                        let mut rerun = Solver::from_checkpoint(&ctx, point.clone());
+                        rerun.add(smtlib::Def::Assert(assumption));
+                        injector.push(unfreeze_frame(&frame).task_with_checkpoint(
+                            TaskId::fresh(),
+                            state,
+                            checkpoint(&mut rerun),
+                        ))
+                    }
+                    trace_value_collector(tid, task_id, result, stats, shared_state, solver, collected)
+                }),
+                &MultiOpts::new(2),
+            );
+            assert_eq!(status, ExplorationStatus::Exhaustive);
+            let results: Vec<(TaskId, Val<B64>)> = std::iter::from_fn(|| queue.pop())
+                .map(|result| result.map(|(id, value, _)| (id, value)).unwrap())
+                .collect();
+            (original, results)
+        });
+
+        // each re-run only has one feasible path, so the number of
+        // completed paths doubles
+        assert_eq!(results.len(), 4);
+        let values = |from_original: bool| {
+            let mut values: Vec<u64> = results
+                .iter()
+                .filter(|(id, _)| (*id == original) == from_original)
+                .map(|(_, value)| match value {
+                    Val::Bits(bv) => bv.lower_u64(),
+                    _ => panic!("expected bits"),
+                })
+                .collect();
+            values.sort();
+            values
+        };
+        assert_eq!(values(true), vec![0b01, 0b10]);
+        assert_eq!(values(false), vec![0b01, 0b10]);
+    }
+
+    #[test]
+    fn test_loop_limit() {
+        // loop until a counter equals a symbolic integer, which forks
+        // on every iteration and never ends
+        let body = |symtab: &mut Symtab, _, _| {
+            let n = symtab.intern("zn");
+            let i = symtab.intern("zi");
+            vec![
+                Instr::Decl(n, Ty::I64, SourceLoc::unknown()),
+                Instr::Init(i, Ty::I64, Exp::I64(0), SourceLoc::unknown()),
+                Instr::Jump(Exp::Call(Op::Eq, vec![Exp::Id(i), Exp::Id(n)]), 5, SourceLoc::unknown()),
+                Instr::Copy(Loc::Id(i), Exp::Call(Op::Add, vec![Exp::Id(i), Exp::I64(1)]), SourceLoc::unknown()),
+                Instr::Goto(2),
+                Instr::Copy(Loc::Id(RETURN), Exp::Bits(B64::new(0b00, 2)), SourceLoc::unknown()),
+                Instr::End,
+            ]
+        };
+
+        let results = with_main("", body, |mut task, shared_state| {
+            task.set_max_backjumps(10);
+            let queue = TraceValueQueue::new();
+            start_single(task, None, Strategy::Dfs, shared_state, &queue, &trace_value_collector);
+            std::iter::from_fn(|| queue.pop()).collect::<Vec<_>>()
+        });
+
+        // one path leaves the loop after each of the allowed iterations,
+        // and the path still looping stops at the jump back to the test
+        assert_eq!(results.len(), 12);
+        assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 11);
+        assert!(results.iter().any(|result| matches!(
+            result,
+            Err(TraceError::Exec { err: ExecError::LoopLimit(function, 4, _), .. }) if function == "main"
+        )))
+    }
+
+    #[test]
+    fn test_loop_limit_with_call() {
+        // as test_loop_limit, but the counter is incremented by calling
+        // a function, which must not reset the count of jumps in main
+        let body = |symtab: &mut Symtab, _, _| {
+            let n = symtab.intern("zn");
+            let i = symtab.intern("zi");
+            let f = symtab.intern("zf");
+            let x = symtab.intern("zx");
+            let main_body = vec![
+                Instr::Decl(n, Ty::I64, SourceLoc::unknown()),
+                Instr::Init(i, Ty::I64, Exp::I64(0), SourceLoc::unknown()),
+                Instr::Jump(Exp::Call(Op::Eq, vec![Exp::Id(i), Exp::Id(n)]), 5, SourceLoc::unknown()),
+                Instr::Call(Loc::Id(i), false, f, vec![Exp::Id(i)], SourceLoc::unknown()),
+                Instr::Goto(2),
+                Instr::Copy(Loc::Id(RETURN), Exp::Bits(B64::new(0b00, 2)), SourceLoc::unknown()),
+                Instr::End,
+            ];
+            let f_body = vec![
+                Instr::Copy(Loc::Id(RETURN), Exp::Call(Op::Add, vec![Exp::Id(x), Exp::I64(1)]), SourceLoc::unknown()),
+                Instr::End,
+            ];
+            (main_body, vec![Def::Val(f, vec![Ty::I64], Ty::I64), Def::Fn(f, vec![x], f_body)])
+        };
+
+        let results = with_main_and_defs("", body, |mut task, shared_state| {
+            task.set_max_backjumps(10);
+            let queue = TraceValueQueue::new();
+            start_single(task, None, Strategy::Dfs, shared_state, &queue, &trace_value_collector);
+            std::iter::from_fn(|| queue.pop()).collect::<Vec<_>>()
+        });
+
+        assert_eq!(results.len(), 12);
+        assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 11);
+        assert!(results.iter().any(|result| matches!(
+            result,
+            Err(TraceError::Exec { err: ExecError::LoopLimit(function, 4, _), .. }) if function == "main"
+        )))
+    }
+
+    #[test]
+    fn test_recursion_limit() {
+        // main calls a function which counts down from 5 recursively
+        // twice, so six calls to it are on the stack at the deepest
+        let run = |max_recursion: u32, f_limit: Option<u32>| {
+            let body = |symtab: &mut Symtab, _, _| {
+                let f = symtab.intern("zf");
+                let n = symtab.intern("zn");
+                let y = symtab.intern("zy");
+                let main_body = vec![
+                    Instr::Decl(y, Ty::I64, SourceLoc::unknown()),
+                    Instr::Call(Loc::Id(y), false, f, vec![Exp::I64(5)], SourceLoc::unknown()),
+                    Instr::Call(Loc::Id(y), false, f, vec![Exp::I64(5)], SourceLoc::unknown()),
+                    Instr::Copy(Loc::Id(RETURN), Exp::Bits(B64::new(0b00, 2)), SourceLoc::unknown()),
+                    Instr::End,
+                ];
+                let f_body = vec![
+                    Instr::Jump(Exp::Call(Op::Eq, vec![Exp::Id(n), Exp::I64(0)]), 3, SourceLoc::unknown()),
+                    Instr::Call(
+                        Loc::Id(RETURN),
+                        false,
+                        f,
+                        vec![Exp::Call(Op::Sub, vec![Exp::Id(n), Exp::I64(1)])],
+                        SourceLoc::unknown(),
+                    ),
+                    Instr::End,
+                    Instr::Copy(Loc::Id(RETURN), Exp::I64(0), SourceLoc::unknown()),
+                    Instr::End,
+                ];
+                (main_body, vec![Def::Val(f, vec![Ty::I64], Ty::I64), Def::Fn(f, vec![n], f_body)])
+            };
+
+            with_main_and_defs("", body, |mut task, shared_state| {
+                task.set_max_recursion(max_recursion);
+                if let Some(limit) = f_limit {
+                    let mut limits = HashMap::new();
+                    limits.insert(shared_state.symtab.lookup("zf"), limit);
+                    task.set_recursion_limits(limits)
+                }
+                let queue = TraceValueQueue::new();
+                start_single(task, None, Strategy::Dfs, shared_state, &queue, &trace_value_collector);
+                std::iter::from_fn(|| queue.pop()).collect::<Vec<_>>()
+            })
+        };
+
+        let results = run(6, None);
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_ok());
+
+        let results = run(6, Some(5));
+        assert_eq!(results.len(), 1);
+        assert!(matches!(
+            &results[0],
+            Err(TraceError::Exec { err: ExecError::RecursionLimit(function, 5, _), .. }) if function == "f"
+        ));
+
+        // the limit for f overrides the global limit, which main is within
+        let results = run(1, Some(6));
+        assert!(results[0].is_ok())
+    }
+
+    #[test]
+    fn test_symbolic_register_types() {
+        let run = |reg_ty: Ty<Name>, main_body: fn(Name, Name, Name) -> Vec<Instr<Name, B64>>| {
+            let body = |symtab: &mut Symtab, _, _| {
+                let reg = symtab.intern("zr");
+                let eq_string = symtab.intern("zeq_string");
+                let b = symtab.intern("zb");
+                let defs = vec![
+                    Def::Register(reg, reg_ty, vec![]),
+                    Def::Extern(eq_string, false, "eq_string".to_string(), vec![Ty::String, Ty::String], Ty::Bool),
+                ];
+                (main_body(reg, eq_string, b), defs)
+            };
+            with_main_and_defs("", body, |task, shared_state| {
+                let queue = TraceValueQueue::new();
+                start_single(task, None, Strategy::Dfs, shared_state, &queue, &trace_value_collector);
+                std::iter::from_fn(|| queue.pop()).collect::<Vec<_>>()
+            })
+        };
+
+        // lists have no symbolic representation
+        let results = run(Ty::List(Box::new(Ty::Bits(8))), |reg, _, b| {
+            vec![
+                Instr::Init(b, Ty::List(Box::new(Ty::Bits(8))), Exp::Id(reg), SourceLoc::unknown()),
+                Instr::Copy(Loc::Id(RETURN), Exp::Bits(B64::new(0b00, 2)), SourceLoc::unknown()),
+                Instr::End,
+            ]
+        });
+        assert_eq!(results.len(), 1);
+        match &results[0] {
+            Err(TraceError::Exec { err, .. }) => {
+                assert_eq!(err.to_string(), "register r has unsupported type list(bits(8))")
+            }
+            _ => panic!("expected an error initializing the list register"),
+        }
+
+        // a symbolic string can be equal to a concrete string or not
+        let results = run(Ty::String, |reg, eq_string, b| {
+            vec![
+                Instr::Decl(b, Ty::Bool, SourceLoc::unknown()),
+                Instr::Call(
+                    Loc::Id(b),
+                    false,
+                    eq_string,
+                    vec![Exp::Id(reg), Exp::String("foo".to_string())],
+                    SourceLoc::unknown(),
+                ),
+                Instr::Jump(Exp::Id(b), 5, SourceLoc::unknown()),
+                Instr::Copy(Loc::Id(RETURN), Exp::Bits(B64::new(0b01, 2)), SourceLoc::unknown()),
+                Instr::End,
+                Instr::Copy(Loc::Id(RETURN), Exp::Bits(B64::new(0b10, 2)), SourceLoc::unknown()),
+                Instr::End,
+            ]
+        });
+        let mut values: Vec<_> = results.into_iter().map(|result| result.unwrap().1).collect();
+        values.sort_by_key(|value| format!("{:?}", value));
+        assert_eq!(values, vec![Val::Bits(B64::new(0b01, 2)), Val::Bits(B64::new(0b10, 2))])
+    }
+
+    /// A main function body, given a register, its union constructors,
+    /// the eq_anything primop, and a variable
+    type UnionMainBody = fn(Name, [Name; 3], Name, Name) -> Vec<Instr<Name, B64>>;
+
+    #[test]
+    fn test_symbolic_union_register() {
+        let run = |main_body: UnionMainBody| {
+            let body = |symtab: &mut Symtab, _, _| {
+                let u = symtab.intern("zU");
+                let ctors = [symtab.intern("zA"), symtab.intern("zB"), symtab.intern("zC")];
+                let reg = symtab.intern("zr");
+                let eq_anything = symtab.intern("zeq_anything");
+                let y = symtab.intern("zy");
+                let defs = vec![
+                    // the list payload of C has no symbolic representation
+                    Def::Union(
+                        u,
+                        vec![
+                            (ctors[0], Ty::Bits(8)),
+                            (ctors[1], Ty::Unit),
+                            (ctors[2], Ty::List(Box::new(Ty::Bits(8)))),
+                        ],
+                    ),
+                    Def::Register(reg, Ty::Union(u), vec![]),
+                    Def::Extern(
+                        eq_anything,
+                        false,
+                        "eq_anything".to_string(),
+                        vec![Ty::Union(u), Ty::Union(u)],
+                        Ty::Bool,
+                    ),
+                ];
+                (main_body(reg, ctors, eq_anything, y), defs)
+            };
+            let results = with_main_and_defs("", body, |task, shared_state| {
+                let queue = TraceValueQueue::new();
+                start_single(task, None, Strategy::Dfs, shared_state, &queue, &trace_value_collector);
+                std::iter::from_fn(|| queue.pop()).collect::<Vec<_>>()
+            });
+            let mut values: Vec<_> = results.into_iter().map(|result| result.unwrap().1).collect();
+            values.sort_by_key(|value| format!("{:?}", value));
+            values
+        };
+        fn bits(bv: u64) -> Exp<Name> {
+            Exp::Bits(B64::new(bv, 2))
+        }
+
+        // the register can be A or B, but never C
+        let values = run(|reg, [a, _, c], _, y| {
+            vec![
+                Instr::Jump(Exp::Kind(a, Box::new(Exp::Id(reg))), 4, SourceLoc::unknown()),
+                Instr::Init(y, Ty::Bits(8), Exp::Unwrap(a, Box::new(Exp::Id(reg))), SourceLoc::unknown()),
+                Instr::Copy(Loc::Id(RETURN), bits(0b01), SourceLoc::unknown()),
+                Instr::End,
+                Instr::Jump(Exp::Kind(c, Box::new(Exp::Id(reg))), 7, SourceLoc::unknown()),
+                Instr::Copy(Loc::Id(RETURN), bits(0b11), SourceLoc::unknown()),
+                Instr::End,
+                Instr::Copy(Loc::Id(RETURN), bits(0b10), SourceLoc::unknown()),
+                Instr::End,
+            ]
+        });
+        assert_eq!(values, vec![Val::Bits(B64::new(0b01, 2)), Val::Bits(B64::new(0b10, 2))]);
+
+        // a symbolic union is always equal to itself
+        let values = run(|reg, _, eq_anything, b| {
+            vec![
+                Instr::Decl(b, Ty::Bool, SourceLoc::unknown()),
+                Instr::Call(Loc::Id(b), false, eq_anything, vec![Exp::Id(reg), Exp::Id(reg)], SourceLoc::unknown()),
+                Instr::Jump(Exp::Id(b), 5, SourceLoc::unknown()),
+                Instr::Copy(Loc::Id(RETURN), bits(0b01), SourceLoc::unknown()),
+                Instr::End,
+                Instr::Copy(Loc::Id(RETURN), bits(0b10), SourceLoc::unknown()),
+                Instr::End,
+            ]
+        });
+        assert_eq!(values, vec![Val::Bits(B64::new(0b10, 2))])
+    }
+
+    #[test]
+    fn test_error_backtrace() {
+        // main calls f, which calls an outcome without a primop
+        let body = |symtab: &mut Symtab, outcome, _| {
+            let f = symtab.intern("zf");
+            let y = symtab.intern("zy");
+            let main_body = vec![
+                Instr::Copy(Loc::Id(RETURN), Exp::Bits(B64::new(0b00, 2)), SourceLoc::unknown()),
+                Instr::Call(Loc::Id(RETURN), false, f, vec![Exp::Unit], SourceLoc::unknown()),
+                Instr::End,
+            ];
+            let f_body =
+                vec![Instr::Call(Loc::Id(RETURN), false, outcome, vec![Exp::Id(y)], SourceLoc::unknown()), Instr::End];
+            (main_body, vec![Def::Val(f, vec![Ty::Unit], Ty::Bits(2)), Def::Fn(f, vec![y], f_body)])
+        };
+
+        let results = with_main_and_defs("", body, |task, shared_state| {
+            let queue = TraceValueQueue::new();
+            start_single(task, None, Strategy::Dfs, shared_state, &queue, &trace_value_collector);
+            std::iter::from_fn(|| queue.pop()).collect::<Vec<_>>()
+        });
+        assert_eq!(results.len(), 1);
+        let Err(err @ TraceError::Exec { backtrace, .. }) = &results[0] else { panic!("expected an execution error") };
+        assert_eq!(backtrace, &vec![("f".to_string(), 0), ("main".to_string(), 1)]);
+        assert!(err.to_string().contains("\n  f @ 0\n  main @ 1"))
+    }
+
+    #[test]
+    fn test_structured_trace_collector() {
+        let config = "[stubs]\nchoose_feature = { fork = [\"0b01\", \"0b10\"] }";
+        let body = |_: &mut Symtab, outcome, x| {
+            vec![Instr::Call(Loc::Id(RETURN), false, outcome, vec![Exp::Id(x)], SourceLoc::unknown()), Instr::End]
+        };
+        let results = with_main(config, body, |task, shared_state| {
+            let queue = StructuredTraceQueue::new();
+            start_single(task, None, Strategy::Dfs, shared_state, &queue, &structured_trace_collector);
+            std::iter::from_fn(|| queue.pop()).collect::<Vec<_>>()
+        });
+        assert_eq!(results.len(), 2);
+        let mut values = Vec::new();
+        for result in results {
+            let path = result.unwrap();
+            values.push(path.value.unwrap());
+            // the events are in order, starting with the declaration of
+            // the value returned by the call, which forks afterwards
+            assert!(matches!(path.events[0], Event::Smt(smtlib::Def::DeclareConst(_, _), _, _)));
+            let call = path.events.iter().position(Event::is_abstract).unwrap();
+            let fork = path.events.iter().position(Event::is_fork).unwrap();
+            assert!(call < fork)
+        }
+        values.sort_by_key(|value| format!("{:?}", value));
+        assert_eq!(values, vec![Val::Bits(B64::new(0b01, 2)), Val::Bits(B64::new(0b10, 2))])
+    }
+
+    #[test]
+    fn test_register_write_hook() {
+        let run = |main_body: fn(Name, Name) -> Vec<Instr<Name, B64>>| {
+            let body = |symtab: &mut Symtab, _, _| {
+                let reg = symtab.intern("zr");
+                let y = symtab.intern("zy");
+                (main_body(reg, y), vec![Def::Register(reg, Ty::Bits(2), vec![])])
+            };
+            with_main_and_defs("", body, |mut task, shared_state| {
+                // a hook which forbids writing 0b11 to r
+                let reg = shared_state.symtab.lookup("zr");
+                let forbidden = B64::new(0b11, 2);
+                task.set_register_write_hook(Some(Arc::new(
+                    move |id, value: &Val<B64>, _: &mut Solver<B64>| match value {
+                        _ if id != reg => HookResult::Continue,
+                        Val::Bits(bv) if *bv == forbidden => HookResult::Abort("r = 0b11".to_string()),
+                        Val::Symbolic(v) => HookResult::Assert(smtlib::Exp::Neq(
+                            Box::new(smtlib::Exp::Var(*v)),
+                            Box::new(smtlib::Exp::Bits64(forbidden)),
+                        )),
+                        _ => HookResult::Continue,
+                    },
+                )));
+                let queue = TraceValueQueue::new();
+                start_single(task, None, Strategy::Dfs, shared_state, &queue, &trace_value_collector);
+                std::iter::from_fn(|| queue.pop()).collect::<Vec<_>>()
+            })
+        };
+        fn bits(bv: u64) -> Exp<Name> {
+            Exp::Bits(B64::new(bv, 2))
+        }
+
+        // writing the forbidden value concretely aborts the path
+        let results = run(|reg, _| {
+            vec![
+                Instr::Copy(Loc::Id(reg), bits(0b11), SourceLoc::unknown()),
+                Instr::Copy(Loc::Id(RETURN), bits(0b00), SourceLoc::unknown()),
+                Instr::End,
+            ]
+        });
+        assert_eq!(results.len(), 1);
+        match &results[0] {
+            Err(TraceError::Exec { err: ExecError::RegisterHook(msg, _), .. }) => assert_eq!(msg, "r = 0b11"),
+            _ => panic!("expected the register write hook to abort the path"),
+        }
+
+        // writing a symbolic value constrains it, so the path where it
+        // is the forbidden value dies at the branch
+        let results = run(|reg, y| {
+            vec![
+                Instr::Decl(y, Ty::Bits(2), SourceLoc::unknown()),
+                Instr::Copy(Loc::Id(reg), Exp::Id(y), SourceLoc::unknown()),
+                Instr::Jump(Exp::Call(Op::Eq, vec![Exp::Id(reg), bits(0b11)]), 5, SourceLoc::unknown()),
+                Instr::Copy(Loc::Id(RETURN), bits(0b00), SourceLoc::unknown()),
+                Instr::End,
+                Instr::Copy(Loc::Id(RETURN), bits(0b01), SourceLoc::unknown()),
+                Instr::End,
+            ]
+        });
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].as_ref().unwrap().1, Val::Bits(B64::new(0b00, 2)))
+    }
+
+    #[test]
+    fn test_nested_field_accessors() {
+        let body = |symtab: &mut Symtab, _, _| {
+            let (inner, outer) = (symtab.intern("zinner"), symtab.intern("zouter"));
+            let (f1, f2, g) = (symtab.intern("zf1"), symtab.intern("zf2"), symtab.intern("zg"));
+            let reg = symtab.intern("zr");
+            let r_f1 = Loc::Field(Box::new(Loc::Id(reg)), f1);
+            let main_body = vec![
+                Instr::Copy(Loc::Field(Box::new(r_f1), f2), Exp::Bits(B64::new(0b01, 2)), SourceLoc::unknown()),
+                Instr::Copy(
+                    Loc::Id(RETURN),
+                    Exp::Field(Box::new(Exp::Field(Box::new(Exp::Id(reg)), f1)), f2),
+                    SourceLoc::unknown(),
+                ),
+                Instr::End,
+            ];
+            let defs = vec![
+                Def::Struct(inner, vec![(f2, Ty::Bits(2)), (g, Ty::Bits(2))]),
+                Def::Struct(outer, vec![(f1, Ty::Struct(inner)), (g, Ty::Bits(2))]),
+                Def::Register(reg, Ty::Struct(outer), vec![]),
+            ];
+            (main_body, defs)
+        };
+        let (results, path) = with_main_and_defs("", body, |task, shared_state| {
+            let queue = TraceValueQueue::new();
+            start_single(task, None, Strategy::Dfs, shared_state, &queue, &trace_value_collector);
+            let symtab = &shared_state.symtab;
+            let path = vec![Accessor::Field(symtab.lookup("zf1")), Accessor::Field(symtab.lookup("zf2"))];
+            (std::iter::from_fn(|| queue.pop()).collect::<Vec<_>>(), path)
+        });
+        assert_eq!(results.len(), 1);
+        let (_, value, mut events) = results[0].as_ref().unwrap().clone();
+        assert_eq!(value, Val::Bits(B64::new(0b01, 2)));
+
+        // both accesses record the path from the register
+        let accessors: Vec<_> = events
+            .iter()
+            .filter_map(|ev| match ev {
+                Event::WriteReg(_, acc, _) => Some(("write", acc.clone())),
+                Event::ReadReg(_, acc, _) => Some(("read", acc.clone())),
+                _ => None,
+            })
+            .collect();
+        assert!(accessors.contains(&("write", path.clone())));
+        assert!(accessors.contains(&("read", path.clone())));
+
+        // so the written value can be restricted to the field
+        simplify::remove_extra_register_fields(&mut events);
+        let Accessor::Field(f1) = path[0];
+        for event in &events {
+            if let Event::WriteReg(_, acc, Val::Struct(outer)) = event {
+                assert_eq!(*acc, path);
+                let Some(Val::Struct(inner)) = outer.get(&f1) else { panic!("missing field") };
+                assert_eq!(outer.len(), 1);
+                assert_eq!(inner.len(), 1)
+            }
+        }
+    }
+
+    #[test]
+    fn test_vector_update() {
+        // main initializes a vector of three elements, updates one,
+        // and returns an element
+        let run = |update: i128, read: i128| {
+            run_main("", None, move |_, x| {
+                vec![
+                    Instr::Decl(x, Ty::FixedVector(3, Box::new(Ty::Bits(2))), SourceLoc::unknown()),
+                    Instr::Call(Loc::Id(x), false, INTERNAL_VECTOR_INIT, vec![Exp::I64(3)], SourceLoc::unknown()),
+                    Instr::Call(
+                        Loc::Id(x),
+                        false,
+                        INTERNAL_VECTOR_UPDATE,
+                        vec![Exp::Id(x), Exp::I128(update), Exp::Bits(B64::new(0b10, 2))],
+                        SourceLoc::unknown(),
+                    ),
+                    Instr::Copy(
+                        Loc::Id(RETURN),
+                        Exp::Call(Op::Bvaccess, vec![Exp::Id(x), Exp::I128(read)]),
+                        SourceLoc::unknown(),
+                    ),
+                    Instr::End,
+                ]
+            })
+        };
+
+        let results = run(1, 1);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].as_ref().unwrap().0, Val::Bits(B64::new(0b10, 2)));
+
+        // elements which were not updated are still uninitialized
+        let results = run(1, 2);
+        assert_eq!(results[0].as_ref().unwrap().0, Val::Poison);
+
+        for (update, read, primop) in [(3, 0, "vector_update"), (-1, 0, "vector_update"), (0, 3, "vector_access")] {
+            let results = run(update, read);
+            assert_eq!(results.len(), 1);
+            match &results[0] {
+                Err(TraceError::Exec { err: ExecError::OutOfBounds(name), .. }) => assert_eq!(*name, primop),
+                result => panic!("expected an out of bounds error, got {:?}", result.as_ref().map(|(value, _)| value)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_uninit_policy() {
+        let run = |policy: UninitPolicy, write_first: bool| {
+            let body = move |symtab: &mut Symtab, _, _| {
+                let reg = symtab.intern("zr");
+                let mut main_body = vec![Instr::Copy(Loc::Id(RETURN), Exp::Id(reg), SourceLoc::unknown()), Instr::End];
+                if write_first {
+                    main_body.insert(0, Instr::Copy(Loc::Id(reg), Exp::Bits(B64::new(0b10, 2)), SourceLoc::unknown()))
+                }
+                (main_body, vec![Def::Register(reg, Ty::Bits(2), vec![])])
+            };
+            with_main_and_defs("", body, |mut task, shared_state| {
+                task.set_uninit_policy(policy);
+                let queue = TraceValueQueue::new();
+                start_single(task, None, Strategy::Dfs, shared_state, &queue, &trace_value_collector);
+                let mut results = std::iter::from_fn(|| queue.pop()).collect::<Vec<_>>();
+                assert_eq!(results.len(), 1);
+                results.pop().unwrap().map(|(_, value, _)| value)
+            })
+        };
+
+        assert!(matches!(run(UninitPolicy::Symbolic, false), Ok(Val::Symbolic(_))));
+        assert_eq!(run(UninitPolicy::Zero, false).unwrap(), Val::Bits(B64::new(0, 2)));
+        match run(UninitPolicy::Error, false) {
+            Err(TraceError::Exec { err: ExecError::UninitializedRegister(reg, _), .. }) => assert_eq!(reg, "r"),
+            _ => panic!("expected reading r to fail"),
+        }
+        // Registers which are written before being read are unaffected
+        for policy in [UninitPolicy::Symbolic, UninitPolicy::Zero, UninitPolicy::Error] {
+            assert_eq!(run(policy, true).unwrap(), Val::Bits(B64::new(0b10, 2)))
+        }
+    }
+
+    #[test]
+    fn test_final_state_collector() {
+        let body = |symtab: &mut Symtab, _, _| {
+            let written = symtab.intern("zR0");
+            let untouched = symtab.intern("zR1");
+            let bits = |bv| Exp::Bits(B64::new(bv, 2));
+            let main_body = vec![
+                Instr::Copy(Loc::Id(written), bits(0b01), SourceLoc::unknown()),
+                Instr::Copy(Loc::Id(written), bits(0b11), SourceLoc::unknown()),
+                Instr::Copy(Loc::Id(RETURN), bits(0b00), SourceLoc::unknown()),
+                Instr::End,
+            ];
+            (
+                main_body,
+                vec![Def::Register(written, Ty::Bits(2), vec![]), Def::Register(untouched, Ty::Bits(2), vec![])],
+            )
+        };
+        with_main_and_defs("", body, |task, shared_state| {
+            let queue = FinalStateQueue::new(&["R0", "R1", "R2"]);
+            start_single(task, None, Strategy::Dfs, shared_state, &queue, &final_state_collector);
+            let (_, snapshot, events) = queue.pop().unwrap().unwrap();
+            assert!(queue.pop().is_none());
+
+            assert_eq!(snapshot.len(), 2);
+            assert_eq!(snapshot["R0"], Val::Bits(B64::new(0b11, 2)));
+            // R1 was never written, so is a symbolic value declared in the trace
+            let Val::Symbolic(v) = snapshot["R1"] else { panic!("expected R1 to be symbolic") };
+            assert!(events.iter().any(|event| event.defines() == Some(v)))
+        })
+    }
+
+    #[test]
+    fn test_initial_defs() {
+        let body = |symtab: &mut Symtab, _, _| {
+            let r0 = symtab.intern("zR0");
+            let bits = |bv| Exp::Bits(B64::new(bv, 2));
+            let main_body = vec![
+                Instr::Jump(Exp::Call(Op::Eq, vec![Exp::Id(r0), bits(0b01)]), 3, SourceLoc::unknown()),
+                Instr::Copy(Loc::Id(RETURN), bits(0b00), SourceLoc::unknown()),
+                Instr::End,
+                Instr::Copy(Loc::Id(RETURN), bits(0b01), SourceLoc::unknown()),
+                Instr::End,
+            ];
+            (main_body, vec![Def::Register(r0, Ty::Bits(2), vec![])])
+        };
+
+        let run = |constrain: bool| {
+            with_main_and_defs("", body, |task, shared_state| {
+                let ctx = Context::new(Config::new());
+                let mut solver = Solver::new(&ctx);
+                let mut frame = unfreeze_frame(&task.frame);
+                let r0 = frame.set_symbolic_register("R0", shared_state, &mut solver).unwrap().unwrap();
+                let mut task = frame.task_with_checkpoint(task.id, task.state, checkpoint(&mut solver));
+                if constrain {
+                    use smtlib::Exp::*;
+                    task.add_initial_defs([smtlib::Def::Assert(Eq(
+                        Box::new(Var(r0)),
+                        Box::new(smtlib::bits64(0b01, 2)),
+                    ))])
+                }
+                let queue = SegQueue::new();
+                start_single(task, None, Strategy::Dfs, shared_state, &queue, &trace_value_collector);
+                let mut values: Vec<_> = std::iter::from_fn(|| queue.pop()).map(|result| result.unwrap().1).collect();
+                values.sort_by_key(|value| format!("{:?}", value));
+                values
+            })
+        };
+
+        assert_eq!(run(false), vec![Val::Bits(B64::new(0b00, 2)), Val::Bits(B64::new(0b01, 2))]);
+        // R0 must be 0b01, so the jump can't fall through
+        assert_eq!(run(true), vec![Val::Bits(B64::new(0b01, 2))])
+    }
+
+    #[test]
+    fn test_dead_path_core() {
+        let body = |symtab: &mut Symtab, _, _| {
+            let r0 = symtab.intern("zR0");
+            let bits = |bv| Exp::Bits(B64::new(bv, 2));
+            let main_body = vec![
+                Instr::Jump(Exp::Call(Op::Eq, vec![Exp::Id(r0), bits(0b01)]), 3, SourceLoc::unknown()),
+                Instr::Copy(Loc::Id(RETURN), bits(0b00), SourceLoc::unknown()),
+                Instr::End,
+                Instr::Copy(Loc::Id(RETURN), bits(0b01), SourceLoc::unknown()),
+                Instr::End,
+            ];
+            (main_body, vec![Def::Register(r0, Ty::Bits(2), vec![])])
+        };
+
+        with_main_and_defs("", body, |task, shared_state| {
+            use smtlib::Exp::*;
+            let ctx = Context::new(Config::new());
+            let mut solver = Solver::new(&ctx);
+            let mut frame = unfreeze_frame(&task.frame);
+            let r0 = frame.set_symbolic_register("R0", shared_state, &mut solver).unwrap().unwrap();
+            let mut task = frame.task_with_checkpoint(task.id, task.state, checkpoint(&mut solver));
+            // R0 can't be both less than 0b10 and greater than 0b01
+            let below = Bvult(Box::new(Var(r0)), Box::new(smtlib::bits64(0b10, 2)));
+            let above = Bvugt(Box::new(Var(r0)), Box::new(smtlib::bits64(0b01, 2)));
+            task.add_initial_defs([smtlib::Def::Assert(below.clone()), smtlib::Def::Assert(above.clone())]);
+
+            let queue = DeadPathQueue::new();
+            start_single(task, None, Strategy::Dfs, shared_state, &queue, &dead_path_collector);
+            let (_, core) = queue.pop().unwrap();
+            assert!(queue.pop().is_none());
+            let core = core.unwrap();
+            assert_eq!(core, vec![Origin::Initial(below), Origin::Initial(above)]);
+
+            let mut buf = Vec::new();
+            simplify::write_unsat_core(&mut buf, &core, shared_state).unwrap();
+            assert_eq!(
+                String::from_utf8(buf).unwrap(),
+                format!("(unsat-core\n  (initial (bvult v{r0} #b10))\n  (initial (bvugt v{r0} #b01)))")
+            )
+        })
+    }
+
+    #[test]
+    fn test_memory_footprint_collector() {
+        let body = |symtab: &mut Symtab, _, _| {
+            let read_mem = symtab.intern("zread_mem");
+            let write_mem = symtab.intern("zwrite_mem");
+            let [addr, b, v, ok] = ["zaddr", "zb", "zv", "zok"].map(|id| symtab.intern(id));
+            // every path reads 4 bytes at 0x1000, and one also writes 8
+            // bytes to the symbolic address addr
+            let main_body = vec![
+                Instr::Decl(addr, Ty::Bits(64), SourceLoc::unknown()),
+                Instr::Decl(b, Ty::Bool, SourceLoc::unknown()),
+                Instr::Decl(v, Ty::Bits(32), SourceLoc::unknown()),
+                Instr::Decl(ok, Ty::Bool, SourceLoc::unknown()),
+                Instr::Call(
+                    Loc::Id(v),
+                    false,
+                    read_mem,
+                    vec![Exp::Unit, Exp::I128(64), Exp::Bits(B64::new(0x1000, 64)), Exp::I128(4)],
+                    SourceLoc::unknown(),
+                ),
+                Instr::Jump(Exp::Id(b), 7, SourceLoc::unknown()),
+                Instr::Call(
+                    Loc::Id(ok),
+                    false,
+                    write_mem,
+                    vec![Exp::Unit, Exp::I128(64), Exp::Id(addr), Exp::I128(8), Exp::Bits(B64::new(0xDEAD, 64))],
+                    SourceLoc::unknown(),
+                ),
+                Instr::Copy(Loc::Id(RETURN), Exp::Bits(B64::new(0b00, 2)), SourceLoc::unknown()),
+                Instr::End,
+            ];
+            let defs = vec![
+                Def::Extern(
+                    read_mem,
+                    false,
+                    "read_mem".to_string(),
+                    vec![Ty::Unit, Ty::I128, Ty::Bits(64), Ty::I128],
+                    Ty::Bits(32),
+                ),
+                Def::Extern(
+                    write_mem,
+                    false,
+                    "write_mem".to_string(),
+                    vec![Ty::Unit, Ty::I128, Ty::Bits(64), Ty::I128, Ty::Bits(64)],
+                    Ty::Bool,
+                ),
+            ];
+            (main_body, defs)
+        };
+        with_main_and_defs("", body, |mut task, shared_state| {
+            let opcode = B64::new(0xD503201F, 32);
+            task.id = TaskId::from_usize(0);
+            let footprints = MemoryFootprints::new(vec![opcode]);
+            start_single(task, None, Strategy::Dfs, shared_state, &footprints, &memory_footprint_collector);
+            assert!(footprints.pop_error().is_none());
+
+            // the read made by both paths is only included once
+            let mut footprint = footprints.footprint(opcode).unwrap();
+            footprint.sort_by_key(|entry| entry.bytes);
+            assert_eq!(footprint.len(), 2);
+            assert_eq!(
+                footprint[0],
+                FootprintEntry { address: Val::Bits(B64::new(0x1000, 64)), bytes: 4, kind: ReadOrWrite::Read }
+            );
+            assert!(matches!(
+                footprint[1],
+                FootprintEntry { address: Val::Symbolic(_), bytes: 8, kind: ReadOrWrite::Write }
+            ))
+        })
+    }
+
+    #[test]
+    fn test_runner_step() {
+        let body = |symtab: &mut Symtab, outcome, x| {
+            let f = symtab.intern("zf");
+            let y = symtab.intern("zy");
+            let main_body = vec![
+                Instr::Init(y, Ty::I64, Exp::I64(1), SourceLoc::unknown()),
+                Instr::Call(Loc::Id(y), false, f, vec![Exp::Unit], SourceLoc::unknown()),
+                Instr::Copy(Loc::Id(y), Exp::Call(Op::Add, vec![Exp::Id(y), Exp::I64(1)]), SourceLoc::unknown()),
+                Instr::Call(Loc::Id(RETURN), false, outcome, vec![Exp::Id(x)], SourceLoc::unknown()),
+                Instr::End,
+            ];
+            let f_body = vec![Instr::Copy(Loc::Id(RETURN), Exp::I64(5), SourceLoc::unknown()), Instr::End];
+            (main_body, vec![Def::Val(f, vec![Ty::Unit], Ty::I64), Def::Fn(f, vec![x], f_body)])
+        };
+        let config = "[stubs]\nchoose_feature = { fork = [\"0b01\", \"0b10\"] }";
+
+        with_main_and_defs(config, body, |task, shared_state| {
+            let f = shared_state.symtab.lookup("zf");
+            let y = shared_state.symtab.lookup("zy");
+            let value_of_y = |runner: &Runner<B64>| match runner.frame().vars().get(&y) {
+                Some(UVal::Init(value)) => Some(value.clone()),
+                _ => None,
+            };
+
+            let ctx = Context::new(Config::new());
+            let (mut runner, mut solver) = Runner::new(0, task, &ctx);
+            assert!(matches!(runner.step(shared_state, &mut solver), Ok(StepResult::Assigned(id)) if id == y));
+            assert_eq!(value_of_y(&runner), Some(Val::I64(1)));
+
+            // y is not in scope while f is running
+            assert!(matches!(runner.step(shared_state, &mut solver), Ok(StepResult::Called(id)) if id == f));
+            assert_eq!(runner.location(), (f, 0));
+            assert_eq!(value_of_y(&runner), None);
+            assert!(matches!(runner.step(shared_state, &mut solver), Ok(StepResult::Assigned(RETURN))));
+            assert!(matches!(runner.step(shared_state, &mut solver), Ok(StepResult::Returned(id)) if id == f));
+            assert_eq!(value_of_y(&runner), Some(Val::I64(5)));
+
+            assert!(matches!(runner.step(shared_state, &mut solver), Ok(StepResult::Assigned(id)) if id == y));
+            assert_eq!(value_of_y(&runner), Some(Val::I64(6)));
+
+            // the call forks, and the other branch can be stepped by its own runner
+            let mut forks = match runner.step(shared_state, &mut solver) {
+                Ok(StepResult::Forked(forks)) => forks,
+                _ => panic!("expected the call to fork"),
+            };
+            assert_eq!(forks.len(), 1);
+            let (mut fork_runner, mut fork_solver) = Runner::new(0, forks.pop().unwrap(), &ctx);
+            assert_eq!(value_of_y(&fork_runner), Some(Val::I64(6)));
+
+            let mut values = Vec::new();
+            for (runner, solver) in [(&mut runner, &mut solver), (&mut fork_runner, &mut fork_solver)] {
+                match runner.step(shared_state, solver) {
+                    Ok(StepResult::Finished(Run::Finished(value))) => values.push(value),
+                    _ => panic!("expected the path to finish"),
+                }
+            }
+            values.sort_by_key(|value| format!("{:?}", value));
+            assert_eq!(values, vec![Val::Bits(B64::new(0b01, 2)), Val::Bits(B64::new(0b10, 2))])
+        })
+    }
+
+    #[test]
+    fn test_trace_source_lines() {
+        let on_line = |line| SourceLoc::new(0, line, 4, line, 12);
+        let body = |_: Name, _: Name| {
+            let bits = |bv| Exp::Bits(B64::new(bv, 2));
+            vec![
+                Instr::Copy(Loc::Id(RETURN), bits(0b00), on_line(5)),
+                Instr::Copy(Loc::Id(RETURN), bits(0b01), on_line(5)),
+                Instr::Goto(3),
+                Instr::Copy(Loc::Id(RETURN), bits(0b10), on_line(6)),
+                Instr::Copy(Loc::Id(RETURN), bits(0b11), on_line(5)),
+                Instr::End,
+            ]
+        };
+        let source_lines = |enabled| {
+            let (results, _) = run_main_bounded("", |task| task.set_trace_source_lines(enabled), None, None, body);
+            let (value, events) = results.into_iter().next().unwrap().unwrap();
+            assert_eq!(value, Val::Bits(B64::new(0b11, 2)));
+            let mut lines: Vec<SourceLoc> = events
+                .iter()
+                .filter_map(|event| if let Event::SourceLine(line) = event { Some(*line) } else { None })
+                .collect();
+            lines.sort();
+            lines
+        };
+
+        // Consecutive instructions on line 5 are only recorded once
+        assert_eq!(source_lines(true), vec![on_line(5).line(), on_line(5).line(), on_line(6).line()]);
+        assert!(source_lines(false).is_empty())
+    }
+
+    #[test]
+    fn test_monomorphize() {
+        let run = |width, limit| {
+            let (results, _) = run_main_bounded(
+                "",
+                |task| task.set_max_monomorphize(limit),
+                None,
+                None,
+                |_, x| {
+                    let extend = Exp::Call(Op::Concat, vec![Exp::Bits(B64::new(0, 2 - width)), Exp::Id(x)]);
+                    vec![
+                        Instr::Decl(x, Ty::Bits(width), SourceLoc::unknown()),
+                        Instr::Monomorphize(x, SourceLoc::unknown()),
+                        Instr::Copy(Loc::Id(RETURN), extend, SourceLoc::unknown()),
+                        Instr::End,
+                    ]
+                },
+            );
+            let mut values = Vec::new();
+            let mut errors = 0;
+            for result in results {
+                match result {
+                    Ok((Val::Bits(bv), _)) => values.push(bv.lower_u64()),
+                    Err(TraceError::Exec { err: ExecError::MonomorphizeLimit(_, 3, _), .. }) => errors += 1,
+                    _ => panic!("unexpected result"),
+                }
+            }
+            values.sort();
+            (values, errors)
+        };
+
+        // A 1-bit variable splits into exactly two paths
+        assert_eq!(run(1, DEFAULT_MAX_MONOMORPHIZE), (vec![0b0, 0b1], 0));
+        assert_eq!(run(2, 4), (vec![0b00, 0b01, 0b10, 0b11], 0));
+        // The fourth value would exceed the limit
+        let (values, errors) = run(2, 3);
+        assert_eq!((values.len(), errors), (3, 1))
+    }
+
+    #[test]
+    fn test_concrete_fast_path() {
+        // m aliases n, which is fixed to a literal on each path by
+        // monomorphizing it, so the test of m is known on every path
+        let body = |symtab: &mut Symtab, _, _| {
+            let n = symtab.intern("zn");
+            let m = symtab.intern("zm");
+            let bits = |bv| Exp::Bits(B64::new(bv, 2));
+            vec![
+                Instr::Decl(n, Ty::Bits(2), SourceLoc::unknown()),
+                Instr::Init(m, Ty::Bits(2), Exp::Id(n), SourceLoc::unknown()),
+                Instr::Monomorphize(n, SourceLoc::unknown()),
+                Instr::Jump(Exp::Call(Op::Eq, vec![Exp::Id(m), bits(0b01)]), 6, SourceLoc::unknown()),
+                Instr::Copy(Loc::Id(RETURN), bits(0b00), SourceLoc::unknown()),
+                Instr::End,
+                Instr::Copy(Loc::Id(RETURN), bits(0b01), SourceLoc::unknown()),
+                Instr::End,
+            ]
+        };
+
+        let run = |enabled| {
+            with_main("", body, |mut task, shared_state| {
+                task.set_concrete_fast_path(enabled);
+                let queue = SegQueue::new();
+                let collector = |_: usize,
+                                 _: TaskId,
+                                 result: PathResult<'_, B64>,
+                                 stats: PathStats,
+                                 _: &SharedState<'_, B64>,
+                                 _: Solver<B64>,
+                                 queue: &SegQueue<(String, u64)>| {
+                    if let Ok((Run::Finished(value), _)) = result {
+                        queue.push((format!("{:?}", value), stats.solver_queries))
+                    }
+                };
+                start_single(task, None, Strategy::Dfs, shared_state, &queue, &collector);
+                let mut values = Vec::new();
+                let mut queries = 0;
+                while let Some((value, path_queries)) = queue.pop() {
+                    values.push(value);
+                    queries += path_queries
+                }
+                values.sort();
+                (values, queries)
+            })
+        };
+
+        let (values, queries) = run(true);
+        let (slow_values, slow_queries) = run(false);
+        assert_eq!(values.len(), 4);
+        assert_eq!(values, slow_values);
+        // each path skips both checks for the jump
+        assert_eq!(queries + 8, slow_queries)
+    }
+
+    #[test]
+    fn test_branch_checks() {
+        // x is symbolic, and main tests x == 0b01 and then x == 0b10,
+        // which can't also be true
+        let body = |_: &mut Symtab, _, x| {
+            let is = |bv| Exp::Call(Op::Eq, vec![Exp::Id(x), Exp::Bits(B64::new(bv, 2))]);
+            let bits = |bv| Exp::Bits(B64::new(bv, 2));
+            vec![
+                Instr::Decl(x, Ty::Bits(2), SourceLoc::unknown()),
+                Instr::Jump(is(0b01), 4, SourceLoc::unknown()),
+                Instr::Copy(Loc::Id(RETURN), bits(0b00), SourceLoc::unknown()),
+                Instr::End,
+                Instr::Jump(is(0b10), 7, SourceLoc::unknown()),
+                Instr::Copy(Loc::Id(RETURN), bits(0b01), SourceLoc::unknown()),
+                Instr::End,
+                Instr::Copy(Loc::Id(RETURN), bits(0b10), SourceLoc::unknown()),
+                Instr::End,
+            ]
+        };
+
+        let paths = with_main("", body, |task, shared_state| {
+            let queue = SegQueue::new();
+            let collector = |_: usize,
+                             _: TaskId,
+                             result: PathResult<'_, B64>,
+                             stats: PathStats,
+                             _: &SharedState<'_, B64>,
+                             _: Solver<B64>,
+                             queue: &SegQueue<(u64, u64)>| {
+                if let Ok((Run::Finished(Val::Bits(bv)), _)) = result {
+                    queue.push((bv.lower_u64(), stats.solver_queries))
+                }
+            };
+            start_single(task, None, Strategy::Dfs, shared_state, &queue, &collector);
+            let mut paths = std::iter::from_fn(|| queue.pop()).collect::<Vec<_>>();
+            paths.sort();
+            paths
+        });
+
+        assert_eq!(paths.iter().map(|(value, _)| *value).collect::<Vec<_>>(), vec![0b00, 0b01]);
+        // The path is known to be satisfiable after taking x == 0b01, so
+        // when x == 0b10 is unsatisfiable the other direction of that
+        // jump needs no check
+        assert_eq!(paths[1].1, 3)
+    }
+
+    #[test]
+    fn test_query_limit() {
+        // main branches on whether y * z is the product of two large
+        // primes, which the solver can't decide within a tiny resource
+        // limit, so both directions are explored and the paths are
+        // approximate
+        let body = |symtab: &mut Symtab, _, _| {
+            let y = symtab.intern("zy");
+            let z = symtab.intern("zz");
+            let p = symtab.intern("zp");
+            let product = Exp::I128(4294967291 * 4294967279);
+            let bits = |bv| Exp::Bits(B64::new(bv, 2));
+            vec![
+                Instr::Decl(y, Ty::I128, SourceLoc::unknown()),
+                Instr::Decl(z, Ty::I128, SourceLoc::unknown()),
+                Instr::Decl(p, Ty::I128, SourceLoc::unknown()),
+                Instr::PrimopBinary(Loc::Id(p), primop::mult_int, Exp::Id(y), Exp::Id(z), SourceLoc::unknown()),
+                Instr::Jump(Exp::Call(Op::Eq, vec![Exp::Id(p), product]), 7, SourceLoc::unknown()),
+                Instr::Copy(Loc::Id(RETURN), bits(0b00), SourceLoc::unknown()),
+                Instr::End,
+                Instr::Copy(Loc::Id(RETURN), bits(0b01), SourceLoc::unknown()),
+                Instr::End,
+            ]
+        };
+
+        let paths = with_main("", body, |mut task, shared_state| {
+            task.set_query_rlimit(Some(1));
+            let queue = SegQueue::new();
+            let collector = |_: usize,
+                             _: TaskId,
+                             result: PathResult<'_, B64>,
+                             _: PathStats,
+                             _: &SharedState<'_, B64>,
+                             _: Solver<B64>,
+                             queue: &SegQueue<(u64, bool)>| {
+                if let Ok((Run::Finished(Val::Bits(bv)), frame)) = result {
+                    queue.push((bv.lower_u64(), frame.is_approximate()))
+                }
+            };
+            start_single(task, None, Strategy::Dfs, shared_state, &queue, &collector);
+            let mut paths = std::iter::from_fn(|| queue.pop()).collect::<Vec<_>>();
+            paths.sort();
+            paths
+        });
+
+        assert_eq!(paths, vec![(0b00, true), (0b01, true)])
+    }
+
+    #[test]
+    fn test_max_paths() {
+        // six calls which each fork in two, so 64 paths in total
+        let branching = |outcome, x| {
+            let mut body: Vec<_> = (0..6)
+                .map(|_| Instr::Call(Loc::Id(RETURN), false, outcome, vec![Exp::Id(x)], SourceLoc::unknown()))
+                .collect();
+            body.push(Instr::End);
+            body
+        };
+        let config = "[stubs]\nchoose_feature = { fork = [\"0b01\", \"0b10\"] }";
+
+        let (results, status) = run_main_bounded(config, |_| (), None, Some(2), branching);
+        assert_eq!(results.len(), 64);
+        assert_eq!(status, ExplorationStatus::Exhaustive);
+
+        let (results, status) = run_main_bounded(config, |_| (), Some(64), None, branching);
+        assert_eq!(results.len(), 64);
+        assert_eq!(status, ExplorationStatus::Exhaustive);
+
+        let (results, status) = run_main_bounded(config, |_| (), Some(10), None, branching);
+        assert_eq!(results.len(), 10);
+        assert_eq!(status, ExplorationStatus::Truncated);
+
+        // each thread may finish the path it is running when the limit is reached
+        for num_threads in [1, 2, 4] {
+            let (results, status) = run_main_bounded(config, |_| (), Some(10), Some(num_threads), branching);
+            assert!(results.len() >= 10 && results.len() < 10 + num_threads, "{} paths", results.len());
+            assert!(results.iter().all(Result::is_ok));
+            assert_eq!(status, ExplorationStatus::Truncated);
+        }
+    }
+
+    #[test]
+    fn test_worker_panic() {
+        fn panicking_collector<'ir>(
+            tid: usize,
+            task_id: TaskId,
+            result: Result<(Run<B64>, LocalFrame<'ir, B64>), (ExecError, Backtrace)>,
+            stats: PathStats,
+            shared_state: &SharedState<'ir, B64>,
+            solver: Solver<B64>,
+            collected: &TraceValueQueue<B64>,
+        ) {
+            if let Ok((Run::Finished(Val::Bits(bv)), _)) = &result {
+                if bv.lower_u64() == 0b01 {
+                    panic!("collector panic")
+                }
+            }
+            trace_value_collector(tid, task_id, result, stats, shared_state, solver, collected)
+        }
+
+        let config = "[stubs]\nchoose_feature = { fork = [\"0b01\", \"0b10\"] }";
+        let body = |_: &mut Symtab, outcome, x| {
+            vec![Instr::Call(Loc::Id(RETURN), false, outcome, vec![Exp::Id(x)], SourceLoc::unknown()), Instr::End]
+        };
+        let (results, status) = with_main(config, body, |task, shared_state| {
+            let task_id = task.id;
+            let queue = Arc::new(TraceValueQueue::new());
+            let status = start_multi(
+                vec![task],
+                shared_state,
+                queue.clone(),
+                MultiCollector::Plain(&panicking_collector),
+                &MultiOpts { num_threads: 2, ..MultiOpts::default() },
+            );
+            let results = std::iter::from_fn(|| queue.pop()).collect::<Vec<_>>();
+            match status {
+                ExplorationStatus::Panicked(panics) => {
+                    assert_eq!(panics.len(), 1);
+                    assert_eq!(panics[0].task_id, task_id);
+                    (results, panics[0].message.clone())
+                }
+                status => panic!("expected a worker panic, got {:?}", status),
+            }
+        });
+        // the run still finishes, and the other path is collected
+        assert_eq!(status, "collector panic");
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].as_ref().unwrap().1, Val::Bits(B64::new(0b10, 2)));
+
+        // a panic while executing the path is given to the collector
+        let body = |symtab: &mut Symtab, outcome, x| {
+            let reg = symtab.intern("zr");
+            let main_body = vec![
+                Instr::Call(Loc::Id(reg), false, outcome, vec![Exp::Id(x)], SourceLoc::unknown()),
+                Instr::Copy(Loc::Id(RETURN), Exp::Id(reg), SourceLoc::unknown()),
+                Instr::End,
+            ];
+            (main_body, vec![Def::Register(reg, Ty::Bits(2), vec![])])
+        };
+        let results = with_main_and_defs(config, body, |mut task, shared_state| {
+            task.set_register_write_hook(Some(Arc::new(|_, value: &Val<B64>, _: &mut Solver<B64>| {
+                if *value == Val::Bits(B64::new(0b01, 2)) {
+                    panic!("hook panic")
+                }
+                HookResult::Continue
+            })));
+            let queue = Arc::new(TraceValueQueue::new());
+            let status = start_multi(
+                vec![task],
+                shared_state,
+                queue.clone(),
+                MultiCollector::Plain(&trace_value_collector),
+                &MultiOpts { num_threads: 2, ..MultiOpts::default() },
+            );
+            assert!(matches!(status, ExplorationStatus::Panicked(panics) if panics.len() == 1));
+            std::iter::from_fn(|| queue.pop()).collect::<Vec<_>>()
+        });
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().any(|result| matches!(
+            result,
+            Err(TraceError::Exec { err: ExecError::WorkerPanic(msg), .. }) if msg == "hook panic"
+        )))
+    }
+
+    #[test]
+    fn test_buffered_task_logs() {
+        fn logging_collector<'ir>(
+            tid: usize,
+            task_id: TaskId,
+            result: Result<(Run<B64>, LocalFrame<'ir, B64>), (ExecError, Backtrace)>,
+            stats: PathStats,
+            shared_state: &SharedState<'ir, B64>,
+            solver: Solver<B64>,
+            collected: &TraceValueQueue<B64>,
+        ) {
+            // the branches taken at each fork identify the path
+            let branches: String = solver
+                .trace()
+                .to_vec()
+                .iter()
+                .filter_map(|ev| match ev {
+                    Event::Fork(_, _, branch, _) => Some(branch.to_string()),
+                    _ => None,
+                })
+                .collect();
+            for i in 0..3 {
+                log::write(format!("path {} line {}", branches, i));
+                thread::yield_now()
+            }
+            trace_value_collector(tid, task_id, result, stats, shared_state, solver, collected)
+        }
+
+        // four calls which each fork in two, so 16 paths in total
+        let body = |_: &mut Symtab, outcome, x| {
+            let mut body: Vec<_> = (0..4)
+                .map(|_| Instr::Call(Loc::Id(RETURN), false, outcome, vec![Exp::Id(x)], SourceLoc::unknown()))
+                .collect();
+            body.push(Instr::End);
+            body
+        };
+        let config = "[stubs]\nchoose_feature = { fork = [\"0b01\", \"0b10\"] }";
+        let (status, output) = with_main(config, body, |task, shared_state| {
+            let queue = Arc::new(TraceValueQueue::new());
+            log::capture(|| {
+                start_multi(
+                    vec![task],
+                    shared_state,
+                    queue.clone(),
+                    MultiCollector::Plain(&logging_collector),
+                    &MultiOpts::new(2),
+                )
+            })
+        });
+        assert_eq!(status, ExplorationStatus::Exhaustive);
+
+        // each task's lines follow its header, and each thread's tasks
+        // are numbered in the order it ran them
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 16 * 4);
+        let mut next_seq = [0, 0];
+        let mut paths = HashSet::new();
+        for group in lines.chunks(4) {
+            let header = group[0].split("]: task ").collect::<Vec<_>>();
+            assert_eq!(header.len(), 2, "{}", group[0]);
+            let tid: usize = header[0].trim_end_matches("\x1b[0m").rsplit('m').next().unwrap().trim().parse().unwrap();
+            let seq: usize = header[1].parse().unwrap();
+            assert_eq!(seq, next_seq[tid]);
+            next_seq[tid] += 1;
+            let path = group[1].strip_suffix(" line 0").unwrap();
+            assert!(paths.insert(path));
+            for (i, line) in group[1..].iter().enumerate() {
+                assert_eq!(*line, format!("{} line {}", path, i))
+            }
+        }
+        assert_eq!(paths.len(), 16)
+    }
+
+    #[test]
+    fn test_strategy() {
+        // four calls which each fork in two, so 16 paths in total
+        let branching = |outcome, x| {
+            let mut body: Vec<_> = (0..4)
+                .map(|_| Instr::Call(Loc::Id(RETURN), false, outcome, vec![Exp::Id(x)], SourceLoc::unknown()))
+                .collect();
+            body.push(Instr::End);
+            body
+        };
+        let config = "[stubs]\nchoose_feature = { fork = [\"0b01\", \"0b10\"] }";
+
+        // the forks along each path, in the order the paths are explored
+        let explore = |strategy| {
+            with_main(
+                config,
+                |_, outcome, x| branching(outcome, x),
+                |task, shared_state| {
+                    let queue = TraceValueQueue::new();
+                    start_single(task, None, strategy, shared_state, &queue, &trace_value_collector);
+                    let mut paths = Vec::new();
+                    while let Some(result) = queue.pop() {
+                        let (_, _, events) = result.unwrap();
+                        let forks: Vec<(u32, u32)> = events
+                            .iter()
+                            .filter_map(|ev| match ev {
+                                Event::Fork(n, _, branch, _) => Some((*n, *branch)),
+                                _ => None,
+                            })
+                            .collect();
+                        paths.push(forks)
+                    }
+                    paths
+                },
+            )
+        };
+
+        let dfs = explore(Strategy::Dfs);
+        let bfs = explore(Strategy::Bfs);
+        let random = explore(Strategy::Random(7));
+        assert_eq!(random, explore(Strategy::Random(7)));
+        assert_ne!(dfs, bfs);
+        assert_ne!(dfs, random);
+
+        // every strategy explores the same paths
+        let mut sorted = dfs.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(sorted.len(), 16);
+        for mut paths in [bfs, random, explore(Strategy::Random(8))] {
+            paths.sort();
+            assert_eq!(paths, sorted)
+        }
+    }
+
+    #[test]
+    fn test_share_depth() {
+        // six calls which each fork in two, so 64 paths in total
+        let branching = |outcome, x| {
+            let mut body: Vec<_> = (0..6)
+                .map(|_| Instr::Call(Loc::Id(RETURN), false, outcome, vec![Exp::Id(x)], SourceLoc::unknown()))
+                .collect();
+            body.push(Instr::End);
+            body
+        };
+        let config = "[stubs]\nchoose_feature = { fork = [\"0b01\", \"0b10\"] }";
+
+        // the forks along each path, sorted as threads finish paths in any order
+        let explore = |strategy, depth| {
+            with_main(
+                config,
+                |_, outcome, x| branching(outcome, x),
+                |mut task, shared_state| {
+                    task.set_share_depth(depth);
+                    let queue = Arc::new(TraceValueQueue::new());
+                    let status = start_multi(
+                        vec![task],
+                        shared_state,
+                        queue.clone(),
+                        MultiCollector::Plain(&trace_value_collector),
+                        &MultiOpts { num_threads: 4, strategy, ..MultiOpts::default() },
+                    );
+                    assert_eq!(status, ExplorationStatus::Exhaustive);
+                    let mut paths = Vec::new();
+                    while let Some(result) = queue.pop() {
+                        let (_, _, events) = result.unwrap();
+                        let forks: Vec<(u32, u32)> = events
+                            .iter()
+                            .filter_map(|ev| match ev {
+                                Event::Fork(n, _, branch, _) => Some((*n, *branch)),
+                                _ => None,
+                            })
+                            .collect();
+                        paths.push(forks)
+                    }
+                    paths.sort();
+                    paths
+                },
+            )
+        };
+
+        // sharing forks only changes which thread explores each path
+        let unshared = explore(Strategy::Dfs, 0);
+        assert_eq!(unshared.len(), 64);
+        for (strategy, depth) in [
+            (Strategy::Dfs, DEFAULT_SHARE_DEPTH),
+            (Strategy::Dfs, u32::MAX),
+            (Strategy::Random(7), DEFAULT_SHARE_DEPTH),
+            (Strategy::Bfs, DEFAULT_SHARE_DEPTH),
+        ] {
+            assert_eq!(explore(strategy, depth), unshared)
+        }
+    }
+
+    #[test]
+    fn test_memoize_pure_functions() {
+        // main calls decode, which just returns its argument, three
+        // times, then read and write, which access a register, twice each
+        let body = |symtab: &mut Symtab, _, _| {
+            let (decode, read, write) = (symtab.intern("zdecode"), symtab.intern("zread"), symtab.intern("zwrite"));
+            let (reg, y, v) = (symtab.intern("zr"), symtab.intern("zy"), symtab.intern("zv"));
+            let call = |f| Instr::Call(Loc::Id(v), false, f, vec![Exp::Bits(B64::new(0b01, 2))], SourceLoc::unknown());
+            let mut main_body = vec![Instr::Decl(v, Ty::Bits(2), SourceLoc::unknown())];
+            main_body.extend([
+                call(decode),
+                call(decode),
+                call(read),
+                call(write),
+                call(read),
+                call(write),
+                call(decode),
+            ]);
+            main_body.extend([Instr::Copy(Loc::Id(RETURN), Exp::Id(v), SourceLoc::unknown()), Instr::End]);
+            let function = |f, body: Vec<Instr<Name, B64>>| {
+                [Def::Val(f, vec![Ty::Bits(2)], Ty::Bits(2)), Def::Fn(f, vec![y], [body, vec![Instr::End]].concat())]
+            };
+            let mut defs = vec![Def::Register(reg, Ty::Bits(2), vec![])];
+            defs.extend(function(decode, vec![Instr::Copy(Loc::Id(RETURN), Exp::Id(y), SourceLoc::unknown())]));
+            defs.extend(function(read, vec![Instr::Copy(Loc::Id(RETURN), Exp::Id(reg), SourceLoc::unknown())]));
+            defs.extend(function(
+                write,
+                vec![
+                    Instr::Copy(Loc::Id(reg), Exp::Id(y), SourceLoc::unknown()),
+                    Instr::Copy(Loc::Id(RETURN), Exp::Id(y), SourceLoc::unknown()),
+                ],
+            ));
+            (main_body, defs)
+        };
+
+        let run = |top_config| {
+            with_config_and_defs(top_config, "", body, |mut task, shared_state| {
+                task.set_max_instructions(Some(1000));
+                let queue = TraceValueQueue::new();
+                let fuel = Arc::new(Mutex::new(0));
+                let remaining = fuel.clone();
+                start_single(
+                    task,
+                    None,
+                    Strategy::Dfs,
+                    shared_state,
+                    &queue,
+                    &move |tid, task_id, result, stats, ss, solver, q| {
+                        if let Ok((_, frame)) = &result {
+                            *remaining.lock().unwrap() = frame.max_instructions.unwrap()
+                        }
+                        trace_value_collector(tid, task_id, result, stats, ss, solver, q)
+                    },
+                );
+                let instructions = 1000 - *fuel.lock().unwrap();
+                let (_, value, events) = queue.pop().unwrap().unwrap();
+                let memo = |f| {
+                    let f = shared_state.symtab.get("zdecode".replace("decode", f).as_str()).unwrap();
+                    shared_state.memo_table.lookup(f, &[Val::Bits(B64::new(0b01, 2))])
+                };
+                let memoized = [memo("decode"), memo("read"), memo("write")].map(|memo| match memo {
+                    Memo::Hit(value) => Some(value),
+                    Memo::Miss => None,
+                    Memo::Impure => Some(Val::Poison),
+                });
+                (value, events, memoized, instructions)
+            })
+        };
+
+        let trace = "trace = [\"decode\", \"read\", \"write\"]\n";
+        let (value, events, memoized, instructions) = run(trace);
+        assert_eq!(memoized, [None, None, None]);
+        let (memo_value, memo_events, memoized, memo_instructions) =
+            run(&format!("{}pure = [\"decode\", \"read\", \"write\"]\n", trace));
+        assert_eq!(value, memo_value);
+        // the last two calls to decode skip its two instructions
+        assert_eq!(instructions - memo_instructions, 4);
+        // decode is memoized, but read and write access a register so
+        // they are marked as impure
+        assert_eq!(memoized, [Some(Val::Bits(B64::new(0b01, 2))), Some(Val::Poison), Some(Val::Poison)]);
+
+        // decode has no internal events to skip, so the traces match
+        let to_string = |events: Vec<Event<B64>>| format!("{:?}", events);
+        assert_eq!(to_string(events), to_string(memo_events.clone()));
+        assert_eq!(memo_events.iter().filter(|ev| matches!(ev, Event::ReadReg(..))).count(), 2);
+        assert_eq!(memo_events.iter().filter(|ev| matches!(ev, Event::WriteReg(..))).count(), 2);
+    }
+
+    #[test]
+    fn test_branch_coverage() {
+        let (l1, l2, l3) =
+            (SourceLoc::new(0, 1, 0, 1, 1), SourceLoc::new(0, 2, 0, 2, 1), SourceLoc::new(0, 3, 0, 3, 1));
+        let body = |symtab: &mut Symtab, outcome, x| {
+            let b = symtab.intern("zb");
+            let never = Exp::Call(Op::And, vec![Exp::Id(b), Exp::Call(Op::Not, vec![Exp::Id(b)])]);
+            vec![
+                Instr::Decl(b, Ty::Bool, SourceLoc::unknown()),
+                // forks, so each path goes a different way at the second
+                // jump on the same condition
+                Instr::Jump(Exp::Id(b), 2, l1),
+                Instr::Jump(Exp::Id(b), 3, l2),
+                Instr::Jump(never, 4, l3),
+                Instr::Call(Loc::Id(RETURN), false, outcome, vec![Exp::Id(x)], SourceLoc::unknown()),
+                Instr::End,
+            ]
+        };
+        let config = "[stubs]\nchoose_feature = { constant = \"0b01\" }";
+
+        let coverage = with_main(config, body, |task, shared_state| {
+            let coverage = Arc::new(Mutex::new(Coverage::new()));
+            start_single(task, None, Strategy::Dfs, shared_state, coverage.as_ref(), &coverage_collector);
+            let coverage = coverage.lock().unwrap().clone();
+            coverage
+        });
+        assert_eq!(coverage.len(), 3);
+        assert_eq!(coverage[&l1], BranchCoverage { taken_true: 1, taken_false: 1 });
+        assert_eq!(coverage[&l2], BranchCoverage { taken_true: 1, taken_false: 1 });
+        assert_eq!(coverage[&l3], BranchCoverage { taken_true: 0, taken_false: 2 });
+
+        let mut report = Vec::new();
+        write_coverage_report(&mut report, &coverage, &[]).unwrap();
+        assert_eq!(String::from_utf8(report).unwrap(), "3:0 - 3:1: never true\n1 of 6 branch directions never taken\n")
+    }
+
+    #[test]
+    fn test_fork_tree_collector() {
+        // two calls which each fork in two, so 4 paths in total
+        let body = |_: &mut Symtab, outcome, x| {
+            let call = Instr::Call(Loc::Id(RETURN), false, outcome, vec![Exp::Id(x)], SourceLoc::unknown());
+            vec![call.clone(), call, Instr::End]
+        };
+        let config = "[stubs]\nchoose_feature = { fork = [\"0b01\", \"0b10\"] }";
+
+        let tree = with_main(config, body, |task, shared_state| {
+            let tree = Arc::new(Mutex::new(ForkTree::new()));
+            start_multi(
+                vec![task],
+                shared_state,
+                tree.clone(),
+                MultiCollector::Plain(&fork_tree_collector),
+                &MultiOpts::new(2),
+            );
+            let tree = tree.lock().unwrap().clone();
+            tree
+        });
+        assert_eq!(tree.paths, PathCounts { finished: 4, failed: 0, dead: 0 });
+        assert_eq!(tree.branches.len(), 2);
+        for subtree in tree.branches.values() {
+            assert_eq!(subtree.paths.finished, 2);
+            assert_eq!(subtree.branches.len(), 2);
+            assert!(subtree.branches.values().all(|leaf| leaf.paths.finished == 1 && leaf.fork.is_none()))
+        }
+    }
+
+    #[test]
+    fn test_path_stats() {
+        // four calls which each fork in two, so 16 paths in total
+        let branching = |outcome, x| {
+            let mut body: Vec<_> = (0..4)
+                .map(|_| Instr::Call(Loc::Id(RETURN), false, outcome, vec![Exp::Id(x)], SourceLoc::unknown()))
+                .collect();
+            body.push(Instr::End);
+            body
+        };
+        let config = "[stubs]\nchoose_feature = { fork = [\"0b01\", \"0b10\"] }";
+
+        let summary = with_main(
+            config,
+            |_, outcome, x| branching(outcome, x),
+            |task, shared_state| {
+                let summary = Arc::new(Mutex::new(PathStatsSummary::new()));
+                start_multi(
+                    vec![task],
+                    shared_state,
+                    summary.clone(),
+                    MultiCollector::Plain(&stats_collector),
+                    &MultiOpts::new(2),
+                );
+                let summary = summary.lock().unwrap().clone();
+                summary
+            },
+        );
+        assert_eq!(summary.paths, 16);
+        // the path taking the first choice at every call counts each
+        // fork, those taking the other choices start from a fork earlier
+        assert_eq!(summary.max.branches, 4);
+        assert!(summary.min.branches < 4);
+        assert_eq!(summary.time_histogram.iter().sum::<u64>(), 16);
+        assert!(summary.min.time <= summary.max.time);
+    }
+
+    #[test]
+    fn test_invalid_outcome_stubs() {
+        let symtab = Symtab::new();
+        let type_info = IRTypeInfo::new::<B64>(&[]);
+        for stub in ["f = \"constant\"", "f = { fork = [] }", "f = { constant = 1, fork = [1] }", "f = 3"] {
+            let config = format!("{}[stubs]\n{}", CONFIG, stub);
+            assert!(ISAConfig::<B64>::parse(&config, None, &symtab, &type_info).is_err(), "{}", stub)
+        }
+    }
+
+    /// The debug output of every path executed from the task
+    fn run_paths<'ir>(task: Task<'ir, '_, B64>, shared_state: &SharedState<'ir, B64>) -> Vec<String> {
+        let queue = TraceValueQueue::new();
+        start_single(task, None, Strategy::Dfs, shared_state, &queue, &trace_value_collector);
+        std::iter::from_fn(|| queue.pop()).map(|result| format!("{:?}", result)).collect()
+    }
+
+    #[test]
+    fn test_save_and_load_task() {
+        let body = |symtab: &mut Symtab, _, _| {
+            let f = symtab.intern("zf");
+            let y = symtab.intern("zy");
+            let b = symtab.intern("zb");
+            let z = symtab.intern("zz");
+            let main_body = vec![
+                Instr::Call(Loc::Id(RETURN), false, f, vec![Exp::Bits(B64::new(0b01, 2))], SourceLoc::unknown()),
+                Instr::End,
+            ];
+            let f_body = vec![
+                Instr::Decl(b, Ty::Bool, SourceLoc::unknown()),
+                // still uninitialized when the task is saved
+                Instr::Decl(z, Ty::Bits(2), SourceLoc::unknown()),
+                Instr::Jump(Exp::Id(b), 5, SourceLoc::unknown()),
+                Instr::Copy(Loc::Id(RETURN), Exp::Id(y), SourceLoc::unknown()),
+                Instr::End,
+                Instr::Copy(Loc::Id(RETURN), Exp::Id(z), SourceLoc::unknown()),
+                Instr::End,
+            ];
+            (main_body, vec![Def::Val(f, vec![Ty::Bits(2)], Ty::Bits(2)), Def::Fn(f, vec![y], f_body)])
+        };
+
+        with_main_and_defs("", body, |mut task, shared_state| {
+            // run the first path by hand, so the task forked inside f is
+            // left in the queue
+            let queue = Worker::new_lifo();
+            let ctx = Context::new(Config::new());
+            let mut solver = Solver::from_checkpoint(&ctx, task.checkpoint);
+            let (result, _) = run(
+                0,
+                task.id,
+                &mut task.fraction,
+                Timeout::unlimited(),
+                None,
+                &queue,
+                &task.frame,
+                task.state,
+                shared_state,
+                &mut solver,
+            );
+            assert!(matches!(result, Ok((Run::Finished(_), _))));
+            let forked = queue.pop().unwrap();
+            assert!(queue.is_empty());
+            assert_eq!(forked.frame.backtrace.len(), 1);
+
+            let mut saved = Vec::new();
+            save_tasks(&mut saved, [&forked].into_iter()).unwrap();
+            let memory = forked.frame.memory.as_ref().clone();
+            let state = forked.state;
+            let expected = run_paths(forked, shared_state);
+
+            let mut loaded = load_tasks(&saved[..], shared_state, &memory, state).unwrap();
+            assert_eq!(loaded.len(), 1);
+            let loaded = loaded.pop().unwrap();
+            assert_eq!(loaded.frame.backtrace.len(), 1);
+
+            // the loaded task returns from f to main, with the same result
+            // and trace as the original
+            let results = run_paths(loaded, shared_state);
+            assert_eq!(results.len(), 1);
+            assert!(results[0].starts_with("Ok("));
+            assert_eq!(results, expected)
+        })
+    }
+
+    #[test]
+    fn test_resume_from_checkpoint() {
+        // Monomorphizing a 6-bit variable gives 64 paths, each
+        // returning a different value
+        let body = |_: &mut Symtab, _, x| {
+            vec![
+                Instr::Decl(x, Ty::Bits(6), SourceLoc::unknown()),
+                Instr::Monomorphize(x, SourceLoc::unknown()),
+                Instr::Copy(Loc::Id(RETURN), Exp::Id(x), SourceLoc::unknown()),
+                Instr::End,
+            ]
+        };
+        let path = std::env::temp_dir().join(format!("isla-test-checkpoint-{}", std::process::id()));
+
+        let values = with_main("", body, |task, shared_state| {
+            let memory = task.frame.memory.as_ref().clone();
+            let state = task.state;
+            let values = |queue: &TraceValueQueue<B64>| {
+                std::iter::from_fn(|| queue.pop()).map(|result| result.unwrap().1).collect::<Vec<_>>()
+            };
+
+            // checkpoint as often as possible, and stop part way through,
+            // with some of the tasks left on disk
+            let checkpoints = CheckpointOpts { path: path.clone(), interval: Duration::ZERO };
+            let budget = MemoryBudget { max_queued_tasks: 2, dir: std::env::temp_dir() };
+            let queue = Arc::new(TraceValueQueue::new());
+            let status = start_multi(
+                vec![task],
+                shared_state,
+                queue.clone(),
+                MultiCollector::Plain(&trace_value_collector),
+                &MultiOpts {
+                    max_paths: Some(10),
+                    checkpoints: Some(&checkpoints),
+                    memory_budget: Some(&budget),
+                    ..MultiOpts::new(2)
+                },
+            );
+            assert_eq!(status, ExplorationStatus::Truncated);
+            let mut first = values(&queue);
+            assert!(first.len() >= 10 && first.len() < 64);
+
+            let tasks = load_checkpoint_file(&path, shared_state, &memory, state).unwrap();
+            assert!(!tasks.is_empty());
+            let queue = Arc::new(TraceValueQueue::new());
+            let status = start_multi(
+                tasks,
+                shared_state,
+                queue.clone(),
+                MultiCollector::Plain(&trace_value_collector),
+                &MultiOpts::new(2),
+            );
+            assert_eq!(status, ExplorationStatus::Exhaustive);
+            first.extend(values(&queue));
+            first
+        });
+        std::fs::remove_file(&path).unwrap();
+
+        // every path is explored exactly once across the two runs
+        assert_eq!(values.len(), 64);
+        let distinct: HashSet<String> = values.iter().map(|value| format!("{:?}", value)).collect();
+        assert_eq!(distinct.len(), 64)
+    }
+
+    #[test]
+    fn test_memory_budget() {
+        // Each monomorphized variable forks every path 16 ways, giving
+        // 256 paths, each returning a different value
+        let body = |symtab: &mut Symtab, _, x| {
+            let y = symtab.intern("zy");
+            vec![
+                Instr::Decl(x, Ty::Bits(4), SourceLoc::unknown()),
+                Instr::Monomorphize(x, SourceLoc::unknown()),
+                Instr::Decl(y, Ty::Bits(4), SourceLoc::unknown()),
+                Instr::Monomorphize(y, SourceLoc::unknown()),
+                Instr::Copy(Loc::Id(RETURN), Exp::Call(Op::Concat, vec![Exp::Id(x), Exp::Id(y)]), SourceLoc::unknown()),
+                Instr::End,
+            ]
+        };
+        let run = |budget: Option<&MemoryBudget>| {
+            with_main("", body, |task, shared_state| {
+                let dir = std::env::temp_dir();
+                let traces = Arc::new(TraceFiles::new(&dir, 2).unwrap());
+                let status = start_multi(
+                    vec![task],
+                    shared_state,
+                    traces.clone(),
+                    MultiCollector::Plain(&streaming_trace_collector),
+                    &MultiOpts { memory_budget: budget, ..MultiOpts::new(2) },
+                );
+                assert_eq!(status, ExplorationStatus::Exhaustive);
+                let traces = Arc::into_inner(traces).unwrap();
+                assert!(traces.pop_error().is_none());
+                let merged = dir.join(format!("isla-test-traces-{}-{}", std::process::id(), budget.is_some()));
+                let reader = traces.merge(&merged).unwrap();
+                let values: Vec<String> = reader.map(|path| format!("{:?}", path.unwrap().value)).collect();
+                std::fs::remove_file(&merged).unwrap();
+                values
+            })
+        };
+
+        let unbounded = run(None);
+        let budget = MemoryBudget { max_queued_tasks: 2, dir: std::env::temp_dir() };
+        let bounded = run(Some(&budget));
+        assert_eq!(unbounded.len(), 256);
+        assert_eq!(bounded.len(), unbounded.len());
+        let distinct: HashSet<String> = bounded.into_iter().collect();
+        assert_eq!(distinct.len(), 256)
+    }
+
+    #[test]
+    fn test_memory_budget_lost_spill() {
+        // Eight branches on fresh symbolic booleans, so after the first
+        // path the other direction of each branch is waiting to be run
+        let body = |_: &mut Symtab, _, x| {
+            let mut instrs = Vec::new();
+            for _ in 0..8 {
+                let next = instrs.len() + 2;
+                instrs.push(Instr::Decl(x, Ty::Bool, SourceLoc::unknown()));
+                instrs.push(Instr::Jump(Exp::Id(x), next, SourceLoc::unknown()))
+            }
+            instrs.push(Instr::Copy(Loc::Id(RETURN), Exp::Bits(B64::new(0, 2)), SourceLoc::unknown()));
+            instrs.push(Instr::End);
+            instrs
+        };
+        let dir = std::env::temp_dir().join(format!("isla-test-lost-spill-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let budget = MemoryBudget { max_queued_tasks: 2, dir: dir.clone() };
+        let (status, paths) = with_main("", body, |task, shared_state| {
+            // The first path waits for the queued tasks to be spilled,
+            // then deletes them, so they can't be loaded again
+            let spill_dir = dir.clone();
+            let spilled =
+                move || std::fs::read_dir(&spill_dir).unwrap().map(|entry| entry.unwrap().path()).collect::<Vec<_>>();
+            let deleted = AtomicBool::new(false);
+            let collector = move |_: usize,
+                                  _: TaskId,
+                                  _: PathResult<'_, B64>,
+                                  _: PathStats,
+                                  _: &SharedState<'_, B64>,
+                                  _: Solver<B64>,
+                                  paths: &AtomicUsize| {
+                if !deleted.swap(true, Ordering::SeqCst) {
+                    let start = Instant::now();
+                    while spilled().is_empty() && start.elapsed() < Duration::from_secs(10) {
+                        thread::sleep(Duration::from_millis(1))
+                    }
+                    spilled().iter().for_each(|file| std::fs::remove_file(file).unwrap())
+                }
+                paths.fetch_add(1, Ordering::SeqCst);
+            };
+            let paths = Arc::new(AtomicUsize::new(0));
+            let status = start_multi(
+                vec![task],
+                shared_state,
+                paths.clone(),
+                MultiCollector::Plain(&collector),
+                &MultiOpts { memory_budget: Some(&budget), ..MultiOpts::new(1) },
+            );
+            (status, paths.load(Ordering::SeqCst))
+        });
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(&status, ExplorationStatus::SpillFailed(msg) if msg.starts_with("IO error")));
+        assert!(paths < 256)
     }
 }
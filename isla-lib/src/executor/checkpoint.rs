@@ -0,0 +1,522 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2024 Alasdair Armstrong
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// 1. Redistributions of source code must retain the above copyright
+// notice, this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright
+// notice, this list of conditions and the following disclaimer in the
+// documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! This module allows tasks to be saved to disk and loaded again
+//! later, so a long-running execution can be resumed from the tasks
+//! it had left to execute.
+//!
+//! Most of a task can be serialized directly, but the call stack of
+//! its frame is a chain of closures. Instead we save the function,
+//! return pc, and local variables of each caller, and rebuild the
+//! closures from the `Call` instructions when loading. Uninitialized
+//! variables and registers refer to their types in the IR, so these
+//! are also looked up again when loading. The memory of a task
+//! contains callbacks, so it must be provided when loading, and only
+//! the footprint of accesses made so far is saved.
+
+use crossbeam::deque::{Injector, Steal, Stealer};
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::frame::{freeze_frame, LocalDebugProbes, LocalFrame, LocalState};
+use super::return_to_caller;
+use super::spill::TaskSpill;
+use super::task::{Task, TaskId, TaskState};
+use crate::bitvector::BV;
+use crate::fraction::Fraction;
+use crate::ir::*;
+use crate::memory::{FootprintEntry, Memory};
+use crate::register::{RegisterBindings, UninitPolicy, UntypedRegister};
+use crate::smt::smtlib::Def;
+use crate::smt::{Checkpoint, Event};
+use crate::source_loc::SourceLoc;
+use crate::zencode;
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    /// The call stack of the frame does not match its backtrace
+    MalformedCallStack,
+    NoFunction(String),
+    /// The instruction a caller would return to does not follow a call
+    NotACall(String, usize),
+    /// An uninitialized variable or register whose type could not be
+    /// found in the architecture
+    UnknownType(String),
+    /// A file of tasks does not hold as many as it was written with
+    TaskCount {
+        expected: usize,
+        found: u64,
+    },
+    Serialization(bincode::Error),
+    IOError(std::io::Error),
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use CheckpointError::*;
+        match self {
+            MalformedCallStack => write!(f, "Cannot save a task whose call stack does not match its backtrace"),
+            NoFunction(name) => write!(f, "Function {} in checkpoint does not exist", name),
+            NotACall(name, pc) => write!(f, "Checkpoint returns to {} at {}, which does not follow a call", name, pc),
+            UnknownType(name) => write!(f, "Could not find the type of uninitialized {} in checkpoint", name),
+            TaskCount { expected, found } => write!(f, "Expected {} tasks in file, but it holds {}", expected, found),
+            Serialization(err) => write!(f, "Failed to serialize checkpoint: {}", err),
+            IOError(err) => write!(f, "IO error with checkpoint: {}", err),
+        }
+    }
+}
+
+impl Error for CheckpointError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        None
+    }
+}
+
+impl From<bincode::Error> for CheckpointError {
+    fn from(err: bincode::Error) -> Self {
+        CheckpointError::Serialization(err)
+    }
+}
+
+impl From<std::io::Error> for CheckpointError {
+    fn from(err: std::io::Error) -> Self {
+        CheckpointError::IOError(err)
+    }
+}
+
+/// Periodically save the tasks which have not been run to `path`,
+/// see [super::MultiOpts::checkpoints]
+pub struct CheckpointOpts {
+    pub path: PathBuf,
+    pub interval: Duration,
+}
+
+#[derive(Serialize, Deserialize)]
+enum UntypedVal<B> {
+    Uninit,
+    Init(Val<B>),
+}
+
+type UntypedBindings<B> = Vec<(Name, UntypedVal<B>)>;
+
+/// The arguments and return value of each assumed function call
+type Assumptions<B> = Vec<(Vec<Val<B>>, Val<B>)>;
+
+/// A caller in the call stack of a saved frame
+#[derive(Serialize, Deserialize)]
+struct SavedCall<B> {
+    function: Name,
+    /// The pc following the call instruction
+    return_pc: usize,
+    vars: UntypedBindings<B>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedFrame<B> {
+    function_name: Name,
+    pc: usize,
+    forks: u32,
+    backjumps: u32,
+    max_backjumps: u32,
+    monomorphized: u32,
+    max_monomorphize: u32,
+    call_depths: Vec<(Name, u32)>,
+    max_recursion: u32,
+    recursion_limits: Vec<(Name, u32)>,
+    max_instructions: Option<u64>,
+    path_timeout: Option<Duration>,
+    share_depth: u32,
+    concrete_fast_path: bool,
+    query_timeout: Option<u32>,
+    query_rlimit: Option<u32>,
+    approximate: bool,
+    source_lines: Option<SourceLoc>,
+    uninit_policy: UninitPolicy,
+    vars: UntypedBindings<B>,
+    regs: Vec<(Name, UntypedRegister<B>)>,
+    lets: UntypedBindings<B>,
+    call_stack: Vec<SavedCall<B>>,
+    function_assumptions: Vec<(Name, Assumptions<B>)>,
+    pc_counts: Vec<(B, usize)>,
+    taken_interrupts: Vec<(usize, u8)>,
+    footprint: Vec<FootprintEntry<B>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedTask<B> {
+    id: usize,
+    fraction: Fraction,
+    frame: SavedFrame<B>,
+    next_var: u32,
+    events: Vec<Event<B>>,
+    fork_cond: Option<(Def, Event<B>)>,
+    initial_defs: Vec<Def>,
+}
+
+fn untyped_bindings<B: BV>(bindings: &Bindings<'_, B>) -> UntypedBindings<B> {
+    bindings
+        .iter()
+        .map(|(id, value)| match value {
+            UVal::Uninit(_) => (*id, UntypedVal::Uninit),
+            UVal::Init(value) => (*id, UntypedVal::Init(value.clone())),
+        })
+        .collect()
+}
+
+fn typed_bindings<'ir, B: BV>(
+    bindings: UntypedBindings<B>,
+    types: impl Fn(Name) -> Option<&'ir Ty<Name>>,
+    shared_state: &SharedState<'ir, B>,
+) -> Result<Bindings<'ir, B>, CheckpointError> {
+    bindings
+        .into_iter()
+        .map(|(id, value)| match value {
+            UntypedVal::Uninit => match types(id) {
+                Some(ty) => Ok((id, UVal::Uninit(ty))),
+                None => Err(CheckpointError::UnknownType(zencode::decode(shared_state.symtab.to_str(id)))),
+            },
+            UntypedVal::Init(value) => Ok((id, UVal::Init(value))),
+        })
+        .collect()
+}
+
+fn function_instrs<'ir, B: BV>(
+    function: Name,
+    shared_state: &SharedState<'ir, B>,
+) -> Result<&'ir [Instr<Name, B>], CheckpointError> {
+    match shared_state.functions.get(&function) {
+        Some((_, _, instrs)) => Ok(instrs),
+        None => Err(CheckpointError::NoFunction(zencode::decode(shared_state.symtab.to_str(function)))),
+    }
+}
+
+/// The types of the arguments, return value, and declared variables
+/// of a function
+fn variable_types<'ir, B: BV>(
+    function: Name,
+    shared_state: &SharedState<'ir, B>,
+) -> Result<HashMap<Name, &'ir Ty<Name>>, CheckpointError> {
+    let Some((args, ret_ty, instrs)) = shared_state.functions.get(&function) else {
+        return Err(CheckpointError::NoFunction(zencode::decode(shared_state.symtab.to_str(function))));
+    };
+    let instrs: &'ir [Instr<Name, B>] = instrs;
+    let mut types: HashMap<Name, &'ir Ty<Name>> = args.iter().copied().collect();
+    types.insert(RETURN, ret_ty);
+    for instr in instrs {
+        if let Instr::Decl(id, ty, _) = instr {
+            types.insert(*id, ty);
+        }
+    }
+    Ok(types)
+}
+
+/// The types of the uninitialized letbindings created for every frame
+/// by [LocalFrame::new]
+fn let_type<'ir>(id: Name) -> Option<&'ir Ty<Name>> {
+    match id {
+        CURRENT_EXCEPTION => Some(&Ty::Union(SAIL_EXCEPTION)),
+        THROW_LOCATION => Some(&Ty::String),
+        _ => None,
+    }
+}
+
+impl<'ir, 'task, B: BV> Task<'ir, 'task, B> {
+    /// Write the task to `w` so it can be resumed by [Task::load].
+    /// The stop conditions and register write hook of the task are
+    /// not saved.
+    pub fn save(&self, w: impl Write) -> Result<(), CheckpointError> {
+        let frame = &self.frame;
+
+        // The closure restoring each caller is rebuilt from the call at
+        // the pc stored in the backtrace, so there must be one entry in
+        // the backtrace per caller
+        if frame.backtrace.len() != frame.stack_vars.len() || frame.stack_call.is_some() == frame.backtrace.is_empty() {
+            return Err(CheckpointError::MalformedCallStack);
+        }
+
+        let call_stack = frame
+            .backtrace
+            .iter()
+            .zip(frame.stack_vars.iter())
+            .map(|((function, caller_pc), vars)| SavedCall {
+                function: *function,
+                return_pc: caller_pc + 1,
+                vars: untyped_bindings(vars),
+            })
+            .collect();
+
+        let saved_frame = SavedFrame {
+            function_name: frame.function_name,
+            pc: frame.pc,
+            forks: frame.forks,
+            backjumps: frame.backjumps,
+            max_backjumps: frame.max_backjumps,
+            monomorphized: frame.monomorphized,
+            max_monomorphize: frame.max_monomorphize,
+            call_depths: frame.call_depths.iter().map(|(f, depth)| (*f, *depth)).collect(),
+            max_recursion: frame.max_recursion,
+            recursion_limits: frame.recursion_limits.iter().map(|(f, limit)| (*f, *limit)).collect(),
+            max_instructions: frame.max_instructions,
+            path_timeout: frame.path_timeout,
+            share_depth: frame.share_depth,
+            concrete_fast_path: frame.concrete_fast_path,
+            query_timeout: frame.query_timeout,
+            query_rlimit: frame.query_rlimit,
+            approximate: frame.approximate,
+            source_lines: frame.source_lines,
+            uninit_policy: frame.local_state.uninit_policy,
+            vars: untyped_bindings(&frame.local_state.vars),
+            regs: frame.local_state.regs.iter().map(|(id, reg)| (*id, reg.untyped())).collect(),
+            lets: untyped_bindings(&frame.local_state.lets),
+            call_stack,
+            function_assumptions: frame.function_assumptions.iter().map(|(f, a)| (*f, a.clone())).collect(),
+            pc_counts: frame.pc_counts.iter().map(|(pc, count)| (*pc, *count)).collect(),
+            taken_interrupts: frame.taken_interrupts.iter().map(|(id, n)| (id.as_usize(), *n)).collect(),
+            footprint: frame.memory.footprint().to_vec(),
+        };
+
+        let saved = SavedTask {
+            id: self.id.as_usize(),
+            fraction: self.fraction.clone(),
+            frame: saved_frame,
+            next_var: self.checkpoint.next_var(),
+            events: self.checkpoint.events().into_iter().cloned().collect(),
+            fork_cond: self.fork_cond.clone(),
+            initial_defs: self.initial_defs.clone(),
+        };
+
+        bincode::serialize_into(w, &saved)?;
+        Ok(())
+    }
+
+    /// Read a task written by [Task::save]. The task is given a copy of
+    /// `memory`, and has no stop conditions or register write hook.
+    pub fn load(
+        r: impl Read,
+        shared_state: &'ir SharedState<'ir, B>,
+        memory: &Memory<B>,
+        state: &'task TaskState<B>,
+    ) -> Result<Self, CheckpointError> {
+        let saved: SavedTask<B> = bincode::deserialize_from(r)?;
+        let saved_frame = saved.frame;
+
+        let instrs = function_instrs(saved_frame.function_name, shared_state)?;
+        let this_function = variable_types(saved_frame.function_name, shared_state)?;
+
+        let mut stack_vars = Vec::new();
+        let mut backtrace = Vec::new();
+        let mut stack_call = None;
+        for call in saved_frame.call_stack {
+            let caller_instrs = function_instrs(call.function, shared_state)?;
+            let caller_pc = call.return_pc.wrapping_sub(1);
+            let Some(Instr::Call(loc, _, _, _, info)) = caller_instrs.get(caller_pc) else {
+                return Err(CheckpointError::NotACall(
+                    zencode::decode(shared_state.symtab.to_str(call.function)),
+                    call.return_pc,
+                ));
+            };
+            // Callers' counts of backwards jumps are not saved, so they
+            // start again from zero when the task returns to them
+            stack_call = return_to_caller(0, loc, *info, caller_pc, 0, caller_instrs, stack_call);
+            let types = variable_types(call.function, shared_state)?;
+            stack_vars.push(typed_bindings(call.vars, |id| types.get(&id).copied(), shared_state)?);
+            backtrace.push((call.function, caller_pc))
+        }
+
+        let vars = typed_bindings(saved_frame.vars, |id| this_function.get(&id).copied(), shared_state)?;
+        let lets = typed_bindings(saved_frame.lets, let_type, shared_state)?;
+        let mut regs = RegisterBindings::new();
+        for (id, reg) in saved_frame.regs {
+            let ty = match shared_state.registers.get(&id) {
+                Some(ty) => ty,
+                // The type is not needed once the register is initialized
+                None if reg.is_initialized() => &Ty::Unit,
+                None => return Err(CheckpointError::UnknownType(zencode::decode(shared_state.symtab.to_str(id)))),
+            };
+            regs.insert_register(id, reg.with_type(ty))
+        }
+
+        let mut frame = LocalFrame {
+            function_name: saved_frame.function_name,
+            pc: saved_frame.pc,
+            forks: saved_frame.forks,
+            backjumps: saved_frame.backjumps,
+            max_backjumps: saved_frame.max_backjumps,
+            monomorphized: saved_frame.monomorphized,
+            max_monomorphize: saved_frame.max_monomorphize,
+            call_depths: saved_frame.call_depths.into_iter().collect(),
+            max_recursion: saved_frame.max_recursion,
+            recursion_limits: Arc::new(saved_frame.recursion_limits.into_iter().collect()),
+            max_instructions: saved_frame.max_instructions,
+            path_timeout: saved_frame.path_timeout,
+            share_depth: saved_frame.share_depth,
+            concrete_fast_path: saved_frame.concrete_fast_path,
+            query_timeout: saved_frame.query_timeout,
+            query_rlimit: saved_frame.query_rlimit,
+            approximate: saved_frame.approximate,
+            source_lines: saved_frame.source_lines,
+            local_state: LocalState {
+                vars,
+                regs,
+                lets,
+                probes: LocalDebugProbes { probe_this_function: false },
+                register_write_hook: None,
+                uninit_policy: saved_frame.uninit_policy,
+            },
+            memory: memory.clone(),
+            instrs,
+            stack_vars,
+            stack_call,
+            backtrace,
+            function_assumptions: saved_frame.function_assumptions.into_iter().collect(),
+            pc_counts: saved_frame.pc_counts.into_iter().collect(),
+            taken_interrupts: saved_frame
+                .taken_interrupts
+                .into_iter()
+                .map(|(id, n)| (TaskId::from_usize(id), n))
+                .collect(),
+        };
+        frame.set_probes(shared_state);
+        frame.memory.set_footprint(saved_frame.footprint);
+
+        Ok(Task {
+            id: TaskId::from_usize(saved.id),
+            fraction: saved.fraction,
+            frame: freeze_frame(&frame),
+            checkpoint: Checkpoint::from_events(saved.next_var, saved.events),
+            fork_cond: saved.fork_cond,
+            initial_defs: saved.initial_defs,
+            state,
+            stop_conditions: None,
+        })
+    }
+}
+
+/// Write a sequence of tasks to `w`, which can be read by [load_tasks]
+pub fn save_tasks<'a, 'ir: 'a, 'task: 'a, B: BV>(
+    mut w: impl Write,
+    tasks: impl ExactSizeIterator<Item = &'a Task<'ir, 'task, B>>,
+) -> Result<(), CheckpointError> {
+    bincode::serialize_into(&mut w, &(tasks.len() as u64))?;
+    for task in tasks {
+        task.save(&mut w)?
+    }
+    Ok(())
+}
+
+/// Read a sequence of tasks written by [save_tasks], see [Task::load]
+pub fn load_tasks<'ir, 'task, B: BV>(
+    mut r: impl Read,
+    shared_state: &'ir SharedState<'ir, B>,
+    memory: &Memory<B>,
+    state: &'task TaskState<B>,
+) -> Result<Vec<Task<'ir, 'task, B>>, CheckpointError> {
+    let len: u64 = bincode::deserialize_from(&mut r)?;
+    (0..len).map(|_| Task::load(&mut r, shared_state, memory, state)).collect()
+}
+
+/// Read the tasks saved by [super::MultiOpts::checkpoints]
+pub fn load_checkpoint_file<'ir, 'task, B: BV>(
+    path: &Path,
+    shared_state: &'ir SharedState<'ir, B>,
+    memory: &Memory<B>,
+    state: &'task TaskState<B>,
+) -> Result<Vec<Task<'ir, 'task, B>>, CheckpointError> {
+    load_tasks(BufReader::new(File::open(path)?), shared_state, memory, state)
+}
+
+/// Save every task in the global queue, in each worker's queue, and
+/// spilled to disk to the checkpoint file, then put the tasks which
+/// were in memory back in the global queue for the workers to steal.
+/// No worker may be running a task while this is called, otherwise
+/// the tasks it forks would be missing from the checkpoint. The file
+/// is replaced atomically, so a crash while writing leaves the
+/// previous checkpoint intact.
+pub(super) fn drain_to_checkpoint<'ir, 'task, B: BV>(
+    global: &Injector<Task<'ir, 'task, B>>,
+    stealers: &[Stealer<Task<'ir, 'task, B>>],
+    spill: Option<&TaskSpill<'_, 'task, B>>,
+    path: &Path,
+) -> Result<(), CheckpointError> {
+    let mut tasks = Vec::new();
+    loop {
+        match global.steal() {
+            Steal::Success(task) => tasks.push(task),
+            Steal::Retry => continue,
+            Steal::Empty => break,
+        }
+    }
+    for stealer in stealers {
+        loop {
+            match stealer.steal() {
+                Steal::Success(task) => tasks.push(task),
+                Steal::Retry => continue,
+                Steal::Empty => break,
+            }
+        }
+    }
+
+    let tmp = path.with_extension("tmp");
+    let result = File::create(&tmp).map_err(CheckpointError::from).and_then(|file| {
+        let mut w = BufWriter::new(file);
+        let spilled = spill.map_or(0, TaskSpill::len);
+        bincode::serialize_into(&mut w, &((tasks.len() + spilled) as u64))?;
+        for task in &tasks {
+            task.save(&mut w)?
+        }
+        // The spilled tasks are copied from their files without loading
+        // them, skipping the count at the start of each file
+        for (spilled, len) in spill.into_iter().flat_map(TaskSpill::files) {
+            let mut r = BufReader::new(File::open(spilled)?);
+            let saved_len: u64 = bincode::deserialize_from(&mut r)?;
+            if saved_len != len as u64 {
+                return Err(CheckpointError::TaskCount { expected: len, found: saved_len });
+            }
+            io::copy(&mut r, &mut w)?;
+        }
+        w.flush()?;
+        Ok(())
+    });
+
+    for task in tasks {
+        global.push(task)
+    }
+
+    result?;
+    fs::rename(tmp, path)?;
+    Ok(())
+}
@@ -0,0 +1,153 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2024 Alasdair Armstrong
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// 1. Redistributions of source code must retain the above copyright
+// notice, this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright
+// notice, this list of conditions and the following disclaimer in the
+// documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Coverage of the branches in the IR, for checking which conditions
+//! in an architecture specification were exercised by a set of tests,
+//! and of the source lines recorded by tasks which trace them.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+
+use crate::smt::Event;
+use crate::source_loc::SourceLoc;
+
+/// How many paths went each way at a branch. For jumps the true
+/// direction is the one where the jump is taken. Other forks (such as
+/// outcome stubs choosing between several values) count their first
+/// choice as true and the others as false.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BranchCoverage {
+    pub taken_true: u64,
+    pub taken_false: u64,
+}
+
+/// The branches seen on any path, by their source location
+pub type Coverage = HashMap<SourceLoc, BranchCoverage>;
+
+/// Add the branches along a path, as recorded in its trace, to the
+/// coverage
+pub fn record_branches<'ev, B: 'ev>(coverage: &mut Coverage, events: impl IntoIterator<Item = &'ev Event<B>>) {
+    for event in events {
+        let (taken, info) = match event {
+            Event::Fork(_, _, branch, info) => (*branch == 0, info),
+            Event::OneSidedJump(taken, info) => (*taken, info),
+            _ => continue,
+        };
+        let branch = coverage.entry(*info).or_default();
+        if taken {
+            branch.taken_true += 1
+        } else {
+            branch.taken_false += 1
+        }
+    }
+}
+
+/// Write a line for each direction of a branch that no path took,
+/// ordered by location
+pub fn write_coverage_report(buf: &mut dyn Write, coverage: &Coverage, files: &[&str]) -> io::Result<()> {
+    let mut branches: Vec<(&SourceLoc, &BranchCoverage)> = coverage.iter().collect();
+    branches.sort_by_key(|(info, _)| **info);
+    let mut never_taken = 0;
+    for (info, branch) in branches {
+        for (count, direction) in [(branch.taken_true, "true"), (branch.taken_false, "false")] {
+            if count == 0 {
+                writeln!(buf, "{}: never {}", info.location_string(files), direction)?;
+                never_taken += 1
+            }
+        }
+    }
+    writeln!(buf, "{} of {} branch directions never taken", never_taken, coverage.len() * 2)
+}
+
+/// How many times each source line was reached, keyed by the start
+/// of the line
+pub type SourceLineCounts = BTreeMap<SourceLoc, u64>;
+
+/// Count the `Event::SourceLine` markers in a trace. These are only
+/// present if the task traced source lines, see
+/// `LocalFrame::set_trace_source_lines`.
+pub fn record_source_lines<'ev, B: 'ev>(
+    counts: &mut SourceLineCounts,
+    events: impl IntoIterator<Item = &'ev Event<B>>,
+) {
+    for event in events {
+        if let Event::SourceLine(line) = event {
+            *counts.entry(*line).or_default() += 1
+        }
+    }
+}
+
+/// Write a `file:line count` line for each source line that was
+/// reached, ordered by location
+pub fn write_source_lines(buf: &mut dyn Write, counts: &SourceLineCounts, files: &[&str]) -> io::Result<()> {
+    for (line, count) in counts {
+        writeln!(buf, "{} {}", line.line_string(files), count)?
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitvector::b64::B64;
+    use crate::smt::Sym;
+
+    #[test]
+    fn test_coverage_report() {
+        let (l1, l2) = (SourceLoc::new(0, 1, 2, 1, 8), SourceLoc::new(0, 3, 2, 3, 8));
+        let path1: Vec<Event<B64>> = vec![Event::Fork(0, Sym::from_u32(0), 0, l1), Event::OneSidedJump(false, l2)];
+        let path2: Vec<Event<B64>> = vec![Event::Fork(0, Sym::from_u32(0), 1, l1), Event::OneSidedJump(false, l2)];
+        let mut coverage = Coverage::new();
+        record_branches(&mut coverage, &path1);
+        record_branches(&mut coverage, &path2);
+        assert_eq!(coverage[&l1], BranchCoverage { taken_true: 1, taken_false: 1 });
+        assert_eq!(coverage[&l2], BranchCoverage { taken_true: 0, taken_false: 2 });
+
+        let mut report = Vec::new();
+        write_coverage_report(&mut report, &coverage, &["model.sail"]).unwrap();
+        assert_eq!(
+            String::from_utf8(report).unwrap(),
+            "model.sail 3:2 - 3:8: never true\n1 of 4 branch directions never taken\n"
+        )
+    }
+
+    #[test]
+    fn test_source_lines() {
+        let (l1, l3) = (SourceLoc::new(0, 1, 0, 1, 0), SourceLoc::new(0, 3, 0, 3, 0));
+        let path: Vec<Event<B64>> =
+            vec![Event::SourceLine(l3), Event::Cycle, Event::SourceLine(l1), Event::SourceLine(l3)];
+        let mut counts = SourceLineCounts::new();
+        record_source_lines(&mut counts, &path);
+        assert_eq!(counts[&l3], 2);
+
+        let mut listing = Vec::new();
+        write_source_lines(&mut listing, &counts, &["model.sail"]).unwrap();
+        assert_eq!(String::from_utf8(listing).unwrap(), "model.sail:1 1\nmodel.sail:3 2\n")
+    }
+}
@@ -0,0 +1,173 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2024 Alasdair Armstrong
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// 1. Redistributions of source code must retain the above copyright
+// notice, this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright
+// notice, this list of conditions and the following disclaimer in the
+// documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! A summary of how the paths of a run forked, for finding out which
+//! branches in an architecture specification lead to many paths.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::smt::Event;
+use crate::source_loc::SourceLoc;
+
+/// How a path ended, as recorded in a [ForkTree]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathOutcome {
+    Finished,
+    Failed,
+    Dead,
+}
+
+/// The number of paths with each outcome in a subtree
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PathCounts {
+    pub finished: u64,
+    pub failed: u64,
+    pub dead: u64,
+}
+
+impl PathCounts {
+    pub fn total(&self) -> u64 {
+        self.finished + self.failed + self.dead
+    }
+
+    fn record(&mut self, outcome: PathOutcome) {
+        match outcome {
+            PathOutcome::Finished => self.finished += 1,
+            PathOutcome::Failed => self.failed += 1,
+            PathOutcome::Dead => self.dead += 1,
+        }
+    }
+}
+
+impl fmt::Display for PathCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total();
+        write!(f, "{} {}", total, if total == 1 { "path" } else { "paths" })?;
+        let outcomes = [(self.finished, "finished"), (self.failed, "failed"), (self.dead, "dead")];
+        let outcomes: Vec<String> =
+            outcomes.iter().filter(|(n, _)| *n > 0).map(|(n, outcome)| format!("{} {}", n, outcome)).collect();
+        if !outcomes.is_empty() {
+            write!(f, " ({})", outcomes.join(", "))?
+        }
+        Ok(())
+    }
+}
+
+/// The paths of a run merged into a tree, where each node is a point
+/// at which the paths through it forked
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ForkTree {
+    /// The outcomes of every path through this node
+    pub paths: PathCounts,
+    /// Where the paths through this node forked, if any did
+    pub fork: Option<SourceLoc>,
+    /// The paths taking each direction at the fork, by branch number.
+    /// For jumps branch 0 is the one where the jump is taken.
+    pub branches: BTreeMap<u32, ForkTree>,
+}
+
+impl ForkTree {
+    pub fn new() -> Self {
+        ForkTree::default()
+    }
+
+    /// Add a path to the tree, given the events in its trace in the
+    /// order they happened
+    pub fn record_path<'ev, B: 'ev>(&mut self, events: impl IntoIterator<Item = &'ev Event<B>>, outcome: PathOutcome) {
+        let mut node = self;
+        node.paths.record(outcome);
+        for event in events {
+            let Event::Fork(_, _, branch, info) = event else { continue };
+            node.fork.get_or_insert(*info);
+            node = node.branches.entry(*branch).or_default();
+            node.paths.record(outcome)
+        }
+    }
+}
+
+fn write_fork_node(buf: &mut dyn Write, node: &ForkTree, files: &[&str], indent: usize) -> io::Result<()> {
+    if let Some(info) = node.fork {
+        writeln!(buf, "{:indent$}fork at {}", "", info.location_string(files), indent = indent)?;
+        for (branch, child) in node.branches.iter() {
+            writeln!(buf, "{:indent$}{}: {}", "", branch, child.paths, indent = indent + 2)?;
+            write_fork_node(buf, child, files, indent + 4)?
+        }
+    }
+    Ok(())
+}
+
+/// Write the fork tree as an indented list, with each fork followed
+/// by the number of paths taking each of its directions
+pub fn write_fork_tree(buf: &mut dyn Write, tree: &ForkTree, files: &[&str]) -> io::Result<()> {
+    writeln!(buf, "{}", tree.paths)?;
+    write_fork_node(buf, tree, files, 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitvector::b64::B64;
+    use crate::smt::Sym;
+
+    #[test]
+    fn test_fork_tree() {
+        let (l1, l2) = (SourceLoc::new(0, 1, 2, 1, 8), SourceLoc::new(0, 3, 2, 3, 8));
+        let fork = |n, branch, info| Event::Fork(n, Sym::from_u32(n), branch, info);
+        let paths: Vec<(Vec<Event<B64>>, PathOutcome)> = vec![
+            (vec![fork(0, 0, l1), Event::OneSidedJump(true, l2), fork(1, 0, l2)], PathOutcome::Finished),
+            (vec![fork(0, 0, l1), Event::OneSidedJump(true, l2), fork(1, 1, l2)], PathOutcome::Failed),
+            (vec![fork(0, 1, l1)], PathOutcome::Finished),
+            (vec![fork(0, 2, l1)], PathOutcome::Dead),
+        ];
+        let mut tree = ForkTree::new();
+        for (events, outcome) in &paths {
+            tree.record_path(events, *outcome)
+        }
+        assert_eq!(tree.paths, PathCounts { finished: 2, failed: 1, dead: 1 });
+        assert_eq!(tree.fork, Some(l1));
+        assert_eq!(tree.branches[&0].fork, Some(l2));
+        assert_eq!(tree.branches[&1].fork, None);
+
+        let mut rendered = Vec::new();
+        write_fork_tree(&mut rendered, &tree, &["model.sail"]).unwrap();
+        assert_eq!(
+            String::from_utf8(rendered).unwrap(),
+            "4 paths (2 finished, 1 failed, 1 dead)\n\
+             \x20 fork at model.sail 1:2 - 1:8\n\
+             \x20   0: 2 paths (1 finished, 1 failed)\n\
+             \x20     fork at model.sail 3:2 - 3:8\n\
+             \x20       0: 1 path (1 finished)\n\
+             \x20       1: 1 path (1 failed)\n\
+             \x20   1: 1 path (1 finished)\n\
+             \x20   2: 1 path (1 dead)\n"
+        )
+    }
+}
@@ -34,6 +34,7 @@ use ahash;
 use std::collections::{HashMap, HashSet};
 use std::mem;
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::bitvector::BV;
 use crate::error::ExecError;
@@ -41,20 +42,40 @@ use crate::executor::task::{Task, TaskId, TaskState};
 use crate::fraction::Fraction;
 use crate::ir::*;
 use crate::memory::Memory;
-use crate::register::RegisterBindings;
-use crate::smt::{Checkpoint, Solver, Sym};
+use crate::primop_util::symbolic;
+use crate::register::{RegisterBindings, UninitPolicy};
+use crate::smt::{smtlib, Checkpoint, Solver, Sym};
+use crate::source_loc::SourceLoc;
+use crate::zencode;
 
 #[derive(Clone)]
 pub struct LocalDebugProbes {
     pub probe_this_function: bool,
 }
 
+/// What a [RegisterWriteHook] asks the executor to do after a
+/// register write
+pub enum HookResult {
+    Continue,
+    /// Stop the path with [ExecError::RegisterHook] and the given message
+    Abort(String),
+    /// Assert the expression, so the path is discarded at the next
+    /// satisfiability check if it cannot hold
+    Assert(smtlib::Exp<Sym>),
+}
+
+/// A callback invoked with the name and new value of each register
+/// written, set with [LocalFrame::set_register_write_hook]
+pub type RegisterWriteHook<B> = Arc<dyn Fn(Name, &Val<B>, &mut Solver<B>) -> HookResult + Send + Sync>;
+
 #[derive(Clone)]
 pub struct LocalState<'ir, B> {
     pub(super) vars: Bindings<'ir, B>,
     pub(super) regs: RegisterBindings<'ir, B>,
     pub(super) lets: Bindings<'ir, B>,
     pub(super) probes: LocalDebugProbes,
+    pub(super) register_write_hook: Option<RegisterWriteHook<B>>,
+    pub(super) uninit_policy: UninitPolicy,
 }
 
 impl<'ir, B: BV> LocalState<'ir, B> {
@@ -66,6 +87,25 @@ impl<'ir, B: BV> LocalState<'ir, B> {
         shared_state.probes.contains(id)
     }
 
+    /// Run the register write hook, if there is one, for a write of
+    /// `value` to `reg`
+    pub(super) fn register_written(
+        &self,
+        reg: Name,
+        value: &Val<B>,
+        solver: &mut Solver<B>,
+        info: SourceLoc,
+    ) -> Result<(), ExecError> {
+        if let Some(hook) = &self.register_write_hook {
+            match hook(reg, value, solver) {
+                HookResult::Continue => (),
+                HookResult::Abort(msg) => return Err(ExecError::RegisterHook(msg, info)),
+                HookResult::Assert(exp) => solver.add(smtlib::Def::Assert(exp)),
+            }
+        }
+        Ok(())
+    }
+
     pub fn collect_symbolic_variables(&self, vars: &mut HashSet<Sym, ahash::RandomState>) {
         for (_, var) in self.vars.iter().chain(self.lets.iter()) {
             if let UVal::Init(value) = var {
@@ -92,6 +132,21 @@ pub(super) type Stack<'ir, B> = Option<
 
 pub type Backtrace = Vec<(Name, usize)>;
 
+/// The default for [LocalFrame::set_max_backjumps], which is large
+/// enough that it should only be reached by loops that never end.
+pub const DEFAULT_MAX_BACKJUMPS: u32 = 1_000_000;
+
+/// The default for [LocalFrame::set_max_monomorphize]
+pub const DEFAULT_MAX_MONOMORPHIZE: u32 = 1024;
+
+/// The default for [LocalFrame::set_max_recursion], which is large
+/// enough that it should only be reached by unbounded recursion.
+pub const DEFAULT_MAX_RECURSION: u32 = 1024;
+
+/// The default for [LocalFrame::set_share_depth]. Below this depth
+/// there are few enough forks that sharing them all is cheap.
+pub const DEFAULT_SHARE_DEPTH: u32 = 4;
+
 /// A `Frame` is an immutable snapshot of the program state while it
 /// is being symbolically executed.
 #[derive(Clone)]
@@ -100,6 +155,41 @@ pub struct Frame<'ir, B> {
     pub(super) pc: usize,
     pub(super) forks: u32,
     pub(super) backjumps: u32,
+    /// How many backwards jumps a function may make on this path
+    pub(super) max_backjumps: u32,
+    /// How many values the monomorphize instruction at the pc has
+    /// already been split on
+    pub(super) monomorphized: u32,
+    /// How many values a single variable may be monomorphized into
+    pub(super) max_monomorphize: u32,
+    /// How many calls to each function are on the call stack
+    pub(super) call_depths: Arc<HashMap<Name, u32>>,
+    /// How many calls to a function may be on the call stack at once,
+    /// unless it has its own limit in `recursion_limits`
+    pub(super) max_recursion: u32,
+    pub(super) recursion_limits: Arc<HashMap<Name, u32>>,
+    /// How many more instructions this path may execute, if limited
+    pub(super) max_instructions: Option<u64>,
+    /// How long each task executing this path may run for, if limited
+    pub(super) path_timeout: Option<Duration>,
+    /// Paths forked after fewer branches than this are shared with
+    /// every worker thread, rather than the thread which forked them
+    pub(super) share_depth: u32,
+    /// Whether branches on values the solver already knows skip the
+    /// satisfiability checks, see `Solver::known_value`
+    pub(super) concrete_fast_path: bool,
+    /// How long each check of a direction of a symbolic branch may
+    /// take, in milliseconds
+    pub(super) query_timeout: Option<u32>,
+    /// How many resources (in Z3's units) each check of a direction of
+    /// a symbolic branch may use
+    pub(super) query_rlimit: Option<u32>,
+    /// Whether the solver could not decide some branch on this path,
+    /// see `LocalFrame::is_approximate`
+    pub(super) approximate: bool,
+    /// When source lines are being traced, the line of the most
+    /// recent `Event::SourceLine` on this path
+    pub(super) source_lines: Option<SourceLoc>,
     pub(super) local_state: Arc<LocalState<'ir, B>>,
     pub(super) memory: Arc<Memory<B>>,
     pub(super) instrs: &'ir [Instr<Name, B>],
@@ -117,6 +207,20 @@ pub fn unfreeze_frame<'ir, B: BV>(frame: &Frame<'ir, B>) -> LocalFrame<'ir, B> {
         pc: frame.pc,
         forks: frame.forks,
         backjumps: frame.backjumps,
+        max_backjumps: frame.max_backjumps,
+        monomorphized: frame.monomorphized,
+        max_monomorphize: frame.max_monomorphize,
+        call_depths: (*frame.call_depths).clone(),
+        max_recursion: frame.max_recursion,
+        recursion_limits: frame.recursion_limits.clone(),
+        max_instructions: frame.max_instructions,
+        path_timeout: frame.path_timeout,
+        share_depth: frame.share_depth,
+        concrete_fast_path: frame.concrete_fast_path,
+        query_timeout: frame.query_timeout,
+        query_rlimit: frame.query_rlimit,
+        approximate: frame.approximate,
+        source_lines: frame.source_lines,
         local_state: (*frame.local_state).clone(),
         memory: (*frame.memory).clone(),
         instrs: frame.instrs,
@@ -137,6 +241,20 @@ pub struct LocalFrame<'ir, B> {
     pub(super) pc: usize,
     pub(super) forks: u32,
     pub(super) backjumps: u32,
+    pub(super) max_backjumps: u32,
+    pub(super) monomorphized: u32,
+    pub(super) max_monomorphize: u32,
+    pub(super) call_depths: HashMap<Name, u32>,
+    pub(super) max_recursion: u32,
+    pub(super) recursion_limits: Arc<HashMap<Name, u32>>,
+    pub(super) max_instructions: Option<u64>,
+    pub(super) path_timeout: Option<Duration>,
+    pub(super) share_depth: u32,
+    pub(super) concrete_fast_path: bool,
+    pub(super) query_timeout: Option<u32>,
+    pub(super) query_rlimit: Option<u32>,
+    pub(super) approximate: bool,
+    pub(super) source_lines: Option<SourceLoc>,
     pub(super) local_state: LocalState<'ir, B>,
     pub(super) memory: Memory<B>,
     pub(super) instrs: &'ir [Instr<Name, B>],
@@ -154,6 +272,20 @@ pub fn freeze_frame<'ir, B: BV>(frame: &LocalFrame<'ir, B>) -> Frame<'ir, B> {
         pc: frame.pc,
         forks: frame.forks,
         backjumps: frame.backjumps,
+        max_backjumps: frame.max_backjumps,
+        monomorphized: frame.monomorphized,
+        max_monomorphize: frame.max_monomorphize,
+        call_depths: Arc::new(frame.call_depths.clone()),
+        max_recursion: frame.max_recursion,
+        recursion_limits: frame.recursion_limits.clone(),
+        max_instructions: frame.max_instructions,
+        path_timeout: frame.path_timeout,
+        share_depth: frame.share_depth,
+        concrete_fast_path: frame.concrete_fast_path,
+        query_timeout: frame.query_timeout,
+        query_rlimit: frame.query_rlimit,
+        approximate: frame.approximate,
+        source_lines: frame.source_lines,
         local_state: Arc::new(frame.local_state.clone()),
         memory: Arc::new(frame.memory.clone()),
         instrs: frame.instrs,
@@ -193,6 +325,76 @@ impl<'ir, B: BV> LocalFrame<'ir, B> {
         &self.local_state.regs
     }
 
+    /// The last value written to the named register (the name is the
+    /// decoded Sail name, e.g. `R0`). A register the path never
+    /// accessed is initialized as the executor would, so this takes
+    /// the solver. Returns `None` if there is no such register.
+    pub fn final_register(
+        &mut self,
+        name: &str,
+        shared_state: &SharedState<'ir, B>,
+        solver: &mut Solver<B>,
+    ) -> Result<Option<Val<B>>, ExecError> {
+        let Some(id) = shared_state.symtab.get(&zencode::encode(name)) else {
+            return Ok(None);
+        };
+        if let Some(value) = self.local_state.regs.get_last_if_initialized(id) {
+            return Ok(Some(value.clone()));
+        }
+        let policy = self.local_state.uninit_policy;
+        Ok(self.local_state.regs.get(id, policy, shared_state, solver, SourceLoc::unknown())?.cloned())
+    }
+
+    /// The [final_register](LocalFrame::final_register) value of each
+    /// of the named registers which exist, by name
+    pub fn register_snapshot(
+        &mut self,
+        regs: &[&str],
+        shared_state: &SharedState<'ir, B>,
+        solver: &mut Solver<B>,
+    ) -> Result<HashMap<String, Val<B>>, ExecError> {
+        let mut snapshot = HashMap::new();
+        for name in regs {
+            if let Some(value) = self.final_register(name, shared_state, solver)? {
+                snapshot.insert(name.to_string(), value);
+            }
+        }
+        Ok(snapshot)
+    }
+
+    /// Set the named register (the decoded Sail name, as for
+    /// [final_register](LocalFrame::final_register)) to a fresh
+    /// variable declared in `solver`, returning the variable so the
+    /// caller can constrain the register's initial value. A task
+    /// created from the frame should use a checkpoint of `solver`.
+    /// Returns `None` if there is no such register.
+    pub fn set_symbolic_register(
+        &mut self,
+        name: &str,
+        shared_state: &SharedState<'ir, B>,
+        solver: &mut Solver<B>,
+    ) -> Result<Option<Sym>, ExecError> {
+        let Some(id) = shared_state.symtab.get(&zencode::encode(name)) else {
+            return Ok(None);
+        };
+        let Some(ty) = shared_state.registers.get(&id) else {
+            return Ok(None);
+        };
+        if !self.local_state.regs.contains_key(id) {
+            return Ok(None);
+        }
+        match symbolic(ty, shared_state, solver, SourceLoc::unknown())? {
+            Val::Symbolic(v) => {
+                self.local_state.regs.assign(id, Val::Symbolic(v), shared_state);
+                Ok(Some(v))
+            }
+            _ => Err(ExecError::Type(
+                format!("Register {} is not a single symbolic variable", name),
+                SourceLoc::unknown(),
+            )),
+        }
+    }
+
     pub fn add_regs(&mut self, regs: &RegisterBindings<'ir, B>) -> &mut Self {
         for (k, v) in regs {
             self.local_state.regs.insert_register(*k, v.clone())
@@ -245,6 +447,170 @@ impl<'ir, B: BV> LocalFrame<'ir, B> {
         self
     }
 
+    /// Limit the number of instructions that can be executed, after
+    /// which execution fails with `ExecError::Fuel`. Any tasks forked
+    /// from this frame share what remains of the limit at the fork.
+    pub fn set_max_instructions(&mut self, limit: Option<u64>) -> &mut Self {
+        self.max_instructions = limit;
+        self
+    }
+
+    /// Limit the number of backwards jumps each function can make
+    /// before execution fails with `ExecError::LoopLimit`, which stops
+    /// loops with symbolic bounds from running forever. Each call
+    /// counts its own jumps from zero, and the caller's count carries
+    /// on from where it was when the call returns.
+    pub fn set_max_backjumps(&mut self, limit: u32) -> &mut Self {
+        self.max_backjumps = limit;
+        self
+    }
+
+    /// Limit how many values a symbolic variable can be split into by
+    /// a monomorphize instruction. A variable with more possible values
+    /// fails with `ExecError::MonomorphizeLimit` rather than forking an
+    /// unbounded number of paths.
+    pub fn set_max_monomorphize(&mut self, limit: u32) -> &mut Self {
+        self.max_monomorphize = limit;
+        self
+    }
+
+    /// Limit how many calls to the same function can be on the call
+    /// stack at once, after which execution fails with
+    /// `ExecError::RecursionLimit`. Functions given their own limit by
+    /// [LocalFrame::set_recursion_limits] use that instead.
+    pub fn set_max_recursion(&mut self, limit: u32) -> &mut Self {
+        self.max_recursion = limit;
+        self
+    }
+
+    /// Set per-function limits on recursion depth, replacing any set
+    /// previously. Functions not in `limits` use the limit from
+    /// [LocalFrame::set_max_recursion].
+    pub fn set_recursion_limits(&mut self, limits: HashMap<Name, u32>) -> &mut Self {
+        self.recursion_limits = Arc::new(limits);
+        self
+    }
+
+    /// Count a call to `function`, failing if that would put more
+    /// calls to it on the call stack than its recursion limit allows
+    pub(super) fn enter_function(
+        &mut self,
+        function: Name,
+        shared_state: &SharedState<'ir, B>,
+        info: SourceLoc,
+    ) -> Result<(), ExecError> {
+        let limit = self.recursion_limits.get(&function).copied().unwrap_or(self.max_recursion);
+        let depth = self.call_depths.entry(function).or_insert(0);
+        if *depth >= limit {
+            let function = zencode::decode(shared_state.symtab.to_str(function));
+            return Err(ExecError::RecursionLimit(function, limit, info));
+        }
+        *depth += 1;
+        Ok(())
+    }
+
+    /// Count a return from `function`
+    pub(super) fn exit_function(&mut self, function: Name) {
+        if let Some(depth) = self.call_depths.get_mut(&function) {
+            *depth -= 1;
+            if *depth == 0 {
+                self.call_depths.remove(&function);
+            }
+        }
+    }
+
+    /// Call `hook` whenever a register is written on this path,
+    /// including in functions it calls and paths forked from it
+    pub fn set_register_write_hook(&mut self, hook: Option<RegisterWriteHook<B>>) -> &mut Self {
+        self.local_state.register_write_hook = hook;
+        self
+    }
+
+    /// Choose how registers are initialized when they are read before
+    /// being written, see [UninitPolicy]
+    pub fn set_uninit_policy(&mut self, policy: UninitPolicy) -> &mut Self {
+        self.local_state.uninit_policy = policy;
+        self
+    }
+
+    /// Jump to `target`, counting the jump if it goes backwards
+    pub(super) fn jump(
+        &mut self,
+        target: usize,
+        shared_state: &SharedState<'ir, B>,
+        info: SourceLoc,
+    ) -> Result<(), ExecError> {
+        if target <= self.pc {
+            if self.backjumps >= self.max_backjumps {
+                let function = zencode::decode(shared_state.symtab.to_str(self.function_name));
+                return Err(ExecError::LoopLimit(function, self.pc, info));
+            }
+            self.backjumps += 1
+        }
+        self.pc = target;
+        Ok(())
+    }
+
+    /// Limit how long a task executing this frame can run for, after
+    /// which execution fails with `ExecError::PathTimeout`. Unlike the
+    /// instruction limit, each task forked from this frame gets the
+    /// whole limit again, starting from when it is picked up.
+    pub fn set_path_timeout(&mut self, timeout: Option<Duration>) -> &mut Self {
+        self.path_timeout = timeout;
+        self
+    }
+
+    /// When running across multiple threads, paths forked after fewer
+    /// than `depth` branches are put on a queue shared by every
+    /// thread rather than the forking thread's own queue. Forks near
+    /// the root usually lead to the largest subtrees, so this spreads
+    /// the work more evenly. A depth of zero keeps every fork local.
+    pub fn set_share_depth(&mut self, depth: u32) -> &mut Self {
+        self.share_depth = depth;
+        self
+    }
+
+    /// Skip the satisfiability checks for branches on values which
+    /// the solver already knows are fixed to a literal (enabled by
+    /// default). Disabling this only makes sense to check the results
+    /// are the same either way.
+    pub fn set_concrete_fast_path(&mut self, enabled: bool) -> &mut Self {
+        self.concrete_fast_path = enabled;
+        self
+    }
+
+    /// Limit how long the solver may take to check whether each
+    /// direction of a branch on a symbolic value is possible. If it
+    /// cannot tell in time, the direction is assumed to be possible,
+    /// so both directions may be explored even though one of them is
+    /// infeasible, see `is_approximate`.
+    pub fn set_query_timeout(&mut self, millis: Option<u32>) -> &mut Self {
+        self.query_timeout = millis;
+        self
+    }
+
+    /// As `set_query_timeout`, but limiting the resources (in Z3's own
+    /// units) each check may use, which unlike time is deterministic
+    pub fn set_query_rlimit(&mut self, rlimit: Option<u32>) -> &mut Self {
+        self.query_rlimit = rlimit;
+        self
+    }
+
+    /// Whether the solver could not decide whether some branch taken
+    /// on this path was possible, usually because a query timed out.
+    /// The path's constraints are then not known to be satisfiable.
+    pub fn is_approximate(&self) -> bool {
+        self.approximate
+    }
+
+    /// Add an `Event::SourceLine` to the trace each time execution
+    /// reaches a different line of the Sail source (disabled by
+    /// default). Use `record_source_lines` to count them afterwards.
+    pub fn set_trace_source_lines(&mut self, enabled: bool) -> &mut Self {
+        self.source_lines = enabled.then(SourceLoc::unknown);
+        self
+    }
+
     pub fn new(
         name: Name,
         args: &[(Name, &'ir Ty<Name>)],
@@ -278,12 +644,36 @@ impl<'ir, B: BV> LocalFrame<'ir, B> {
         let probe_this_function = false;
         let probes = LocalDebugProbes { probe_this_function };
 
+        let mut call_depths = HashMap::new();
+        call_depths.insert(name, 1);
+
         LocalFrame {
             function_name: name,
             pc: 0,
             forks: 0,
             backjumps: 0,
-            local_state: LocalState { vars, regs, lets, probes },
+            max_backjumps: DEFAULT_MAX_BACKJUMPS,
+            monomorphized: 0,
+            max_monomorphize: DEFAULT_MAX_MONOMORPHIZE,
+            call_depths,
+            max_recursion: DEFAULT_MAX_RECURSION,
+            recursion_limits: Arc::new(HashMap::new()),
+            max_instructions: None,
+            path_timeout: None,
+            share_depth: DEFAULT_SHARE_DEPTH,
+            concrete_fast_path: true,
+            query_timeout: None,
+            query_rlimit: None,
+            approximate: false,
+            source_lines: None,
+            local_state: LocalState {
+                vars,
+                regs,
+                lets,
+                probes,
+                register_write_hook: None,
+                uninit_policy: UninitPolicy::default(),
+            },
             memory: Memory::new(),
             instrs,
             stack_vars: Vec::new(),
@@ -305,8 +695,22 @@ impl<'ir, B: BV> LocalFrame<'ir, B> {
     ) -> Self {
         let mut new_frame = LocalFrame::new(name, args, ret_ty, vals, instrs);
         new_frame.forks = self.forks;
+        new_frame.max_backjumps = self.max_backjumps;
+        new_frame.max_monomorphize = self.max_monomorphize;
+        new_frame.max_recursion = self.max_recursion;
+        new_frame.recursion_limits = self.recursion_limits.clone();
+        new_frame.max_instructions = self.max_instructions;
+        new_frame.path_timeout = self.path_timeout;
+        new_frame.share_depth = self.share_depth;
+        new_frame.concrete_fast_path = self.concrete_fast_path;
+        new_frame.query_timeout = self.query_timeout;
+        new_frame.query_rlimit = self.query_rlimit;
+        new_frame.approximate = self.approximate;
+        new_frame.source_lines = self.source_lines;
         new_frame.local_state.regs = self.local_state.regs.clone();
         new_frame.local_state.lets = self.local_state.lets.clone();
+        new_frame.local_state.register_write_hook = self.local_state.register_write_hook.clone();
+        new_frame.local_state.uninit_policy = self.local_state.uninit_policy;
         new_frame.memory = self.memory.clone();
         new_frame
     }
@@ -323,6 +727,7 @@ impl<'ir, B: BV> LocalFrame<'ir, B> {
             frame: freeze_frame(self),
             checkpoint,
             fork_cond: None,
+            initial_defs: Vec::new(),
             state,
             stop_conditions: None,
         }
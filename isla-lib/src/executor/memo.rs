@@ -0,0 +1,142 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2024 Alasdair Armstrong
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// 1. Redistributions of source code must retain the above copyright
+// notice, this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright
+// notice, this list of conditions and the following disclaimer in the
+// documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Memoization of calls to pure functions with concrete arguments,
+//! so functions such as register field decoders which are called
+//! many times along each path are only executed once per argument.
+
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::sync::RwLock;
+
+use crate::bitvector::BV;
+use crate::ir::{Name, Val};
+use crate::smt::smtlib::Def;
+use crate::smt::Event;
+
+/// The arguments to a memoized call. `Val` can't implement `Hash`
+/// because structs are stored as hash maps, so this hashes just the
+/// parts of each value that are cheap to hash.
+struct MemoKey<B>(Name, Vec<Val<B>>);
+
+impl<B: BV> PartialEq for MemoKey<B> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0 && self.1 == other.1
+    }
+}
+
+impl<B: BV> Eq for MemoKey<B> {}
+
+fn hash_val<B: BV, H: Hasher>(val: &Val<B>, state: &mut H) {
+    use Val::*;
+    std::mem::discriminant(val).hash(state);
+    match val {
+        I64(i) => i.hash(state),
+        I128(i) => i.hash(state),
+        Bool(b) => b.hash(state),
+        Bits(bv) => bv.hash(state),
+        String(s) => s.hash(state),
+        Enum(member) => member.member.hash(state),
+        Vector(vals) | List(vals) => vals.iter().for_each(|val| hash_val(val, state)),
+        Ctor(ctor, val) => {
+            ctor.hash(state);
+            hash_val(val, state)
+        }
+        Struct(fields) => fields.len().hash(state),
+        Symbolic(_) | MixedBits(_) | SymbolicCtor(_, _) | Unit | Ref(_) | Poison => (),
+    }
+}
+
+impl<B: BV> Hash for MemoKey<B> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+        self.1.iter().for_each(|val| hash_val(val, state))
+    }
+}
+
+/// The result of looking up a call in a [MemoTable]
+pub enum Memo<B> {
+    /// The function has returned this value before for the same arguments
+    Hit(Val<B>),
+    /// The call has not been seen before, so the function must be run
+    Miss,
+    /// The function had side effects the last time it was called with
+    /// these arguments, so it must always be run
+    Impure,
+}
+
+/// A table of the results of calls to functions which the ISA config
+/// lists as `pure`. Only calls where every argument and the return
+/// value are concrete are memoized, and a call is only recorded if it
+/// added no events to the trace other than function call tracing and
+/// definitions of intermediate values, so a listed function which
+/// reads or writes registers or memory, forks, or creates fresh
+/// symbolic values is still executed every time.
+pub struct MemoTable<B> {
+    pure_functions: HashSet<Name>,
+    results: RwLock<HashMap<MemoKey<B>, Option<Val<B>>>>,
+}
+
+impl<B: BV> MemoTable<B> {
+    pub fn new(pure_functions: HashSet<Name>) -> Self {
+        MemoTable { pure_functions, results: RwLock::new(HashMap::new()) }
+    }
+
+    /// Whether calls to `f` can be memoized
+    pub fn is_pure(&self, f: Name) -> bool {
+        self.pure_functions.contains(&f)
+    }
+
+    pub fn lookup(&self, f: Name, args: &[Val<B>]) -> Memo<B> {
+        match self.results.read().unwrap().get(&MemoKey(f, args.to_vec())) {
+            Some(Some(value)) => Memo::Hit(value.clone()),
+            Some(None) => Memo::Impure,
+            None => Memo::Miss,
+        }
+    }
+
+    /// Record the result of a call to `f`, given the events it added to
+    /// the trace. The result is `None` if the function returned
+    /// with an exception.
+    pub fn record<'ev>(
+        &self,
+        f: Name,
+        args: Vec<Val<B>>,
+        result: Option<&Val<B>>,
+        events: impl IntoIterator<Item = &'ev Event<B>>,
+    ) where
+        B: 'ev,
+    {
+        let pure = events.into_iter().all(|event| {
+            matches!(event, Event::Function { .. } | Event::Smt(Def::DefineConst(_, _) | Def::DefineEnum(_, _), _, _))
+        });
+        let result = result.filter(|value| pure && !value.is_symbolic()).cloned();
+        self.results.write().unwrap().insert(MemoKey(f, args), result);
+    }
+}
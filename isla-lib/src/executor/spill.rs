@@ -0,0 +1,350 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2024 Alasdair Armstrong
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// 1. Redistributions of source code must retain the above copyright
+// notice, this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright
+// notice, this list of conditions and the following disclaimer in the
+// documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! This module keeps the memory used by a very large exploration
+//! bounded. Once more tasks are waiting in the queues than a
+//! [MemoryBudget] allows, the rest are saved to disk using
+//! [Task::save], and loaded again once there is room. The traces of
+//! finished paths can also be written to a file per worker as soon as
+//! they are collected, see [TraceFiles].
+//!
+//! The parts of a task which [Task::save] cannot write, its memory,
+//! task state, stop conditions and register write hook, are kept in
+//! memory while it is on disk. These are shared by every task forked
+//! from the same original task, so they are cheap to keep.
+
+use crossbeam::deque::{Injector, Steal, Stealer};
+use crossbeam::queue::SegQueue;
+use serde::{Deserialize, Serialize};
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use super::checkpoint::CheckpointError;
+use super::frame::RegisterWriteHook;
+use super::task::{StopConditions, Task, TaskId, TaskState};
+use super::{TraceError, TracePath};
+use crate::bitvector::BV;
+use crate::ir::{SharedState, Val};
+use crate::memory::Memory;
+use crate::smt::Event;
+
+/// Numbers the files written by every exploration in this process, so
+/// explorations running at the same time never share a file
+static FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+fn fresh_file(dir: &Path, kind: &str) -> PathBuf {
+    dir.join(format!("isla-{}-{}-{}", kind, process::id(), FILE_COUNTER.fetch_add(1, Ordering::SeqCst)))
+}
+
+/// Bounds the number of tasks [super::start_multi] keeps in memory,
+/// see [super::MultiOpts::memory_budget]
+#[derive(Clone, Debug)]
+pub struct MemoryBudget {
+    /// Once more tasks than this are waiting to be run, the rest are
+    /// written to disk, half of this many to each file
+    pub max_queued_tasks: usize,
+    /// Where the spilled tasks are written, they are removed once
+    /// they have been loaded again
+    pub dir: PathBuf,
+}
+
+impl MemoryBudget {
+    fn chunk(&self) -> usize {
+        std::cmp::max(1, self.max_queued_tasks / 2)
+    }
+}
+
+/// What a task needs to be resumed which [Task::save] does not write
+struct Unsaved<'task, B> {
+    memory: Arc<Memory<B>>,
+    state: &'task TaskState<B>,
+    stop_conditions: Option<&'task StopConditions>,
+    register_write_hook: Option<RegisterWriteHook<B>>,
+}
+
+struct SpillFile<'task, B> {
+    path: PathBuf,
+    unsaved: Vec<Unsaved<'task, B>>,
+}
+
+/// The tasks of one exploration which have been written to disk
+pub(super) struct TaskSpill<'a, 'task, B> {
+    budget: &'a MemoryBudget,
+    files: Vec<SpillFile<'task, B>>,
+}
+
+fn queued<T>(global: &Injector<T>, stealers: &[Stealer<T>]) -> usize {
+    global.len() + stealers.iter().map(Stealer::len).sum::<usize>()
+}
+
+fn steal<T>(from: impl Fn() -> Steal<T>) -> Option<T> {
+    loop {
+        match from() {
+            Steal::Success(task) => return Some(task),
+            Steal::Retry => continue,
+            Steal::Empty => return None,
+        }
+    }
+}
+
+impl<'a, 'ir, 'task, B: BV> TaskSpill<'a, 'task, B> {
+    pub(super) fn new(budget: &'a MemoryBudget) -> Self {
+        TaskSpill { budget, files: Vec::new() }
+    }
+
+    /// The number of tasks on disk
+    pub(super) fn len(&self) -> usize {
+        self.files.iter().map(|file| file.unsaved.len()).sum()
+    }
+
+    /// Each file of tasks, in the format written by
+    /// [super::save_tasks], with the number of tasks in it
+    pub(super) fn files(&self) -> impl Iterator<Item = (&Path, usize)> {
+        self.files.iter().map(|file| (file.path.as_path(), file.unsaved.len()))
+    }
+
+    /// While more tasks are waiting than the budget allows, take them
+    /// from the queues, shallowest first for the workers' own queues,
+    /// and write them to disk. If a file cannot be written its tasks
+    /// are put back in the global queue.
+    pub(super) fn spill(
+        &mut self,
+        global: &Injector<Task<'ir, 'task, B>>,
+        stealers: &[Stealer<Task<'ir, 'task, B>>],
+    ) -> Result<(), CheckpointError> {
+        while queued(global, stealers) > self.budget.max_queued_tasks {
+            let mut tasks = Vec::new();
+            while tasks.len() < self.budget.chunk() {
+                let Some(task) =
+                    steal(|| global.steal()).or_else(|| stealers.iter().find_map(|stealer| steal(|| stealer.steal())))
+                else {
+                    break;
+                };
+                tasks.push(task)
+            }
+            if tasks.is_empty() {
+                break;
+            }
+
+            let path = fresh_file(&self.budget.dir, "spill");
+            let result = File::create(&path).map_err(CheckpointError::from).and_then(|file| {
+                let mut w = BufWriter::new(file);
+                super::save_tasks(&mut w, tasks.iter())?;
+                w.flush()?;
+                Ok(())
+            });
+            if let Err(err) = result {
+                let _ = fs::remove_file(&path);
+                for task in tasks {
+                    global.push(task)
+                }
+                return Err(err);
+            }
+
+            let unsaved = tasks
+                .into_iter()
+                .map(|task| Unsaved {
+                    memory: task.frame.memory.clone(),
+                    state: task.state,
+                    stop_conditions: task.stop_conditions,
+                    register_write_hook: task.frame.local_state.register_write_hook.clone(),
+                })
+                .collect();
+            self.files.push(SpillFile { path, unsaved })
+        }
+        Ok(())
+    }
+
+    /// Once there is room in the budget, load the most recently
+    /// spilled tasks back into the global queue
+    pub(super) fn refill(
+        &mut self,
+        global: &Injector<Task<'ir, 'task, B>>,
+        stealers: &[Stealer<Task<'ir, 'task, B>>],
+        shared_state: &'ir SharedState<'ir, B>,
+    ) -> Result<(), CheckpointError> {
+        while !self.files.is_empty() && queued(global, stealers) + self.budget.chunk() <= self.budget.max_queued_tasks {
+            let file = self.files.pop().unwrap();
+            let mut r = BufReader::new(File::open(&file.path)?);
+            let len: u64 = bincode::deserialize_from(&mut r)?;
+            if len != file.unsaved.len() as u64 {
+                return Err(CheckpointError::TaskCount { expected: file.unsaved.len(), found: len });
+            }
+            for unsaved in file.unsaved {
+                let mut task = Task::load(&mut r, shared_state, &unsaved.memory, unsaved.state)?;
+                task.stop_conditions = unsaved.stop_conditions;
+                if unsaved.register_write_hook.is_some() {
+                    task.set_register_write_hook(unsaved.register_write_hook)
+                }
+                global.push(task)
+            }
+            fs::remove_file(&file.path)?
+        }
+        Ok(())
+    }
+}
+
+impl<B> Drop for TaskSpill<'_, '_, B> {
+    fn drop(&mut self) {
+        for file in &self.files {
+            let _ = fs::remove_file(&file.path);
+        }
+    }
+}
+
+/// A path written by [TraceFiles]
+#[derive(Serialize, Deserialize)]
+struct SavedPath<B> {
+    task_id: usize,
+    value: Option<Val<B>>,
+    events: Vec<Event<B>>,
+}
+
+/// Collects the trace of each path into a file per worker as soon as
+/// the path finishes, rather than keeping it in memory, see
+/// [super::streaming_trace_collector]. Errors have no trace, so they
+/// are kept in memory.
+pub struct TraceFiles<B> {
+    files: Vec<(PathBuf, Mutex<BufWriter<File>>)>,
+    paths: AtomicUsize,
+    errors: SegQueue<TraceError>,
+    write_error: Mutex<Option<io::Error>>,
+    _phantom: PhantomData<B>,
+}
+
+impl<B: BV> TraceFiles<B> {
+    /// Create a file in `dir` for each of `num_threads` workers
+    pub fn new(dir: &Path, num_threads: usize) -> Result<Self, CheckpointError> {
+        let mut files = Vec::new();
+        for _ in 0..num_threads {
+            let path = fresh_file(dir, "traces");
+            let w = BufWriter::new(File::create(&path)?);
+            files.push((path, Mutex::new(w)))
+        }
+        Ok(TraceFiles {
+            files,
+            paths: AtomicUsize::new(0),
+            errors: SegQueue::new(),
+            write_error: Mutex::new(None),
+            _phantom: PhantomData,
+        })
+    }
+
+    pub(super) fn push(&self, tid: usize, path: Result<TracePath<B>, TraceError>) {
+        let TracePath { task_id, value, events } = match path {
+            Ok(path) => path,
+            Err(err) => return self.errors.push(err),
+        };
+        let saved = SavedPath { task_id: task_id.as_usize(), value, events };
+        let mut w = self.files[tid].1.lock().unwrap();
+        match bincode::serialize_into(&mut *w, &saved) {
+            Ok(()) => {
+                self.paths.fetch_add(1, Ordering::SeqCst);
+            }
+            Err(err) => {
+                let err = match *err {
+                    bincode::ErrorKind::Io(err) => err,
+                    err => io::Error::other(err.to_string()),
+                };
+                self.write_error.lock().unwrap().get_or_insert(err);
+            }
+        }
+    }
+
+    /// Take the next error, if any path failed
+    pub fn pop_error(&self) -> Option<TraceError> {
+        self.errors.pop()
+    }
+
+    /// Merge the files into a single file at `path`, removing the
+    /// per-worker files, and return a reader for the merged traces.
+    /// This fails if any trace could not be written.
+    pub fn merge(self, path: &Path) -> Result<TraceReader<B>, CheckpointError> {
+        let result = self.merge_into(path);
+        for (file, _) in &self.files {
+            let _ = fs::remove_file(file);
+        }
+        result?;
+        TraceReader::open(path)
+    }
+
+    fn merge_into(&self, path: &Path) -> Result<(), CheckpointError> {
+        if let Some(err) = self.write_error.lock().unwrap().take() {
+            return Err(err.into());
+        }
+        let mut out = BufWriter::new(File::create(path)?);
+        bincode::serialize_into(&mut out, &(self.paths.load(Ordering::SeqCst) as u64))?;
+        for (file, w) in &self.files {
+            w.lock().unwrap().flush()?;
+            io::copy(&mut BufReader::new(File::open(file)?), &mut out)?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+}
+
+/// Reads the traces merged by [TraceFiles::merge] one at a time
+pub struct TraceReader<B> {
+    r: BufReader<File>,
+    remaining: u64,
+    _phantom: PhantomData<B>,
+}
+
+impl<B: BV> TraceReader<B> {
+    pub fn open(path: &Path) -> Result<Self, CheckpointError> {
+        let mut r = BufReader::new(File::open(path)?);
+        let remaining = bincode::deserialize_from(&mut r)?;
+        Ok(TraceReader { r, remaining, _phantom: PhantomData })
+    }
+
+    /// The number of traces left to read
+    pub fn remaining(&self) -> usize {
+        self.remaining as usize
+    }
+}
+
+impl<B: BV> Iterator for TraceReader<B> {
+    type Item = Result<TracePath<B>, CheckpointError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        Some(bincode::deserialize_from(&mut self.r).map_err(CheckpointError::from).map(|saved: SavedPath<B>| {
+            TracePath { task_id: TaskId::from_usize(saved.task_id), value: saved.value, events: saved.events }
+        }))
+    }
+}
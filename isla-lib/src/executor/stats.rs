@@ -0,0 +1,181 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2024 Alasdair Armstrong
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// 1. Redistributions of source code must retain the above copyright
+// notice, this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright
+// notice, this list of conditions and the following disclaimer in the
+// documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Statistics about each path the executor runs, for finding out
+//! where the time goes when symbolic execution is slow.
+
+use std::fmt;
+use std::time::Duration;
+
+/// What it took to execute a single path, passed to the collector
+/// alongside its result
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PathStats {
+    /// The number of forks along the path, as counted by its frame
+    /// (so including those before the task executing it was started)
+    pub branches: u64,
+    /// The number of satisfiability checks made by the solver while
+    /// executing the task
+    pub solver_queries: u64,
+    /// How long the task took to execute
+    pub time: Duration,
+    /// The deepest the call stack got
+    pub max_call_depth: usize,
+}
+
+/// Totals and extremes of the [PathStats] for many paths, as gathered
+/// by `executor::stats_collector`
+#[derive(Clone, Debug, Default)]
+pub struct PathStatsSummary {
+    pub paths: u64,
+    pub total: PathStats,
+    pub min: PathStats,
+    pub max: PathStats,
+    /// The number of paths taking under 1ms, then under 2ms, 4ms, and
+    /// so on, doubling each time
+    pub time_histogram: Vec<u64>,
+}
+
+fn time_bucket(time: Duration) -> usize {
+    let millis = time.as_millis();
+    (u128::BITS - millis.leading_zeros()) as usize
+}
+
+impl PathStatsSummary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, stats: &PathStats) {
+        if self.paths == 0 {
+            self.min = *stats;
+            self.max = *stats
+        } else {
+            self.min.branches = self.min.branches.min(stats.branches);
+            self.min.solver_queries = self.min.solver_queries.min(stats.solver_queries);
+            self.min.time = self.min.time.min(stats.time);
+            self.min.max_call_depth = self.min.max_call_depth.min(stats.max_call_depth);
+            self.max.branches = self.max.branches.max(stats.branches);
+            self.max.solver_queries = self.max.solver_queries.max(stats.solver_queries);
+            self.max.time = self.max.time.max(stats.time);
+            self.max.max_call_depth = self.max.max_call_depth.max(stats.max_call_depth)
+        }
+        self.paths += 1;
+        self.total.branches += stats.branches;
+        self.total.solver_queries += stats.solver_queries;
+        self.total.time += stats.time;
+        self.total.max_call_depth += stats.max_call_depth;
+
+        let bucket = time_bucket(stats.time);
+        if self.time_histogram.len() <= bucket {
+            self.time_histogram.resize(bucket + 1, 0)
+        }
+        self.time_histogram[bucket] += 1
+    }
+}
+
+impl fmt::Display for PathStatsSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Paths: {}", self.paths)?;
+        if self.paths == 0 {
+            return Ok(());
+        }
+        let n = self.paths;
+        writeln!(f, "{:<16}{:>12}{:>12}{:>12}{:>12}", "", "total", "min", "mean", "max")?;
+        writeln!(
+            f,
+            "{:<16}{:>12}{:>12}{:>12}{:>12}",
+            "branches",
+            self.total.branches,
+            self.min.branches,
+            self.total.branches / n,
+            self.max.branches
+        )?;
+        writeln!(
+            f,
+            "{:<16}{:>12}{:>12}{:>12}{:>12}",
+            "solver queries",
+            self.total.solver_queries,
+            self.min.solver_queries,
+            self.total.solver_queries / n,
+            self.max.solver_queries
+        )?;
+        writeln!(
+            f,
+            "{:<16}{:>12}{:>12}{:>12}{:>12}",
+            "time (ms)",
+            self.total.time.as_millis(),
+            self.min.time.as_millis(),
+            (self.total.time / n as u32).as_millis(),
+            self.max.time.as_millis()
+        )?;
+        writeln!(
+            f,
+            "{:<16}{:>12}{:>12}{:>12}{:>12}",
+            "call depth",
+            "",
+            self.min.max_call_depth,
+            self.total.max_call_depth as u64 / n,
+            self.max.max_call_depth
+        )?;
+        writeln!(f, "Path times:")?;
+        for (bucket, count) in self.time_histogram.iter().enumerate() {
+            writeln!(f, "  < {:>8}ms {:>8}", 1u64 << bucket, count)?
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(branches: u64, millis: u64) -> PathStats {
+        PathStats { branches, solver_queries: branches * 2, time: Duration::from_millis(millis), max_call_depth: 3 }
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut summary = PathStatsSummary::new();
+        summary.record(&stats(4, 0));
+        summary.record(&stats(2, 5));
+        summary.record(&stats(6, 3));
+
+        assert_eq!(summary.paths, 3);
+        assert_eq!(summary.total.branches, 12);
+        assert_eq!(summary.total.solver_queries, 24);
+        assert_eq!(summary.min.branches, 2);
+        assert_eq!(summary.max.branches, 6);
+        assert_eq!(summary.min.time, Duration::from_millis(0));
+        assert_eq!(summary.max.time, Duration::from_millis(5));
+        // under 1ms, under 2ms, under 4ms, under 8ms
+        assert_eq!(summary.time_histogram, vec![1, 0, 1, 1]);
+        assert!(summary.to_string().starts_with("Paths: 3\n"))
+    }
+}
@@ -31,11 +31,15 @@
 
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
-use crate::executor::frame::{Backtrace, Frame};
+use crate::executor::frame::{Backtrace, Frame, RegisterWriteHook};
 use crate::fraction::Fraction;
 use crate::ir::{Loc, Name, Reset, SharedState};
-use crate::smt::{smtlib, Checkpoint, Event};
+use crate::register::UninitPolicy;
+use crate::smt::{smtlib, Checkpoint, Event, EventFilter};
+use crate::source_loc::SourceLoc;
 use crate::zencode;
 
 static TASK_COUNTER: AtomicUsize = AtomicUsize::new(0);
@@ -82,11 +86,19 @@ pub struct TaskState<B> {
     // Exit if we ever announce an instruction with all bits set to zero
     pub(super) zero_announce_exit: bool,
     pub(super) interrupts: Vec<TaskInterrupt<B>>,
+    // Events to record in the trace, see Solver::set_event_filter
+    pub(super) event_filter: Option<EventFilter>,
 }
 
 impl<B> TaskState<B> {
     pub fn new() -> Self {
-        TaskState { reset_registers: HashMap::new(), pc_limit: None, zero_announce_exit: true, interrupts: Vec::new() }
+        TaskState {
+            reset_registers: HashMap::new(),
+            pc_limit: None,
+            zero_announce_exit: true,
+            interrupts: Vec::new(),
+            event_filter: None,
+        }
     }
 
     pub fn with_reset_registers(self, reset_registers: HashMap<Loc<Name>, Reset<B>>) -> Self {
@@ -101,6 +113,14 @@ impl<B> TaskState<B> {
         TaskState { zero_announce_exit: b, ..self }
     }
 
+    /// Drop events `filter` rejects as they are added to the trace,
+    /// rather than leaving them for the consumer of the trace to
+    /// remove. Some events are always kept, see
+    /// `EventKind::is_always_recorded`.
+    pub fn with_event_filter(self, filter: EventFilter) -> Self {
+        TaskState { event_filter: Some(filter), ..self }
+    }
+
     pub fn add_interrupt(&mut self, interrupt: TaskInterrupt<B>) -> &mut Self {
         self.interrupts.push(interrupt);
         self
@@ -214,6 +234,9 @@ pub struct Task<'ir, 'task, B> {
     pub(crate) frame: Frame<'ir, B>,
     pub(crate) checkpoint: Checkpoint<B>,
     pub(crate) fork_cond: Option<(smtlib::Def, Event<B>)>,
+    /// Added to the solver before the task starts, but not to the
+    /// tasks forked from it, which inherit them from its trace
+    pub(crate) initial_defs: Vec<smtlib::Def>,
     pub(crate) state: &'task TaskState<B>,
     pub(crate) stop_conditions: Option<&'task StopConditions>,
 }
@@ -222,4 +245,97 @@ impl<'ir, 'task, B> Task<'ir, 'task, B> {
     pub fn set_stop_conditions(&mut self, new_fns: &'task StopConditions) {
         self.stop_conditions = Some(new_fns);
     }
+
+    /// Limit the number of instructions the task (and every task
+    /// forked from it) can execute, see `LocalFrame::set_max_instructions`
+    pub fn set_max_instructions(&mut self, limit: Option<u64>) {
+        self.frame.max_instructions = limit;
+    }
+
+    /// Limit the number of backwards jumps each function can make in
+    /// the task (and every task forked from it), see
+    /// `LocalFrame::set_max_backjumps`
+    pub fn set_max_backjumps(&mut self, limit: u32) {
+        self.frame.max_backjumps = limit;
+    }
+
+    /// Limit how deeply each function can recurse in this task and
+    /// tasks forked from it, see `LocalFrame::set_max_recursion`
+    pub fn set_max_recursion(&mut self, limit: u32) {
+        self.frame.max_recursion = limit;
+    }
+
+    /// Give functions their own recursion limits in this task and
+    /// tasks forked from it, see `LocalFrame::set_recursion_limits`
+    pub fn set_recursion_limits(&mut self, limits: HashMap<Name, u32>) {
+        self.frame.recursion_limits = Arc::new(limits);
+    }
+
+    /// Limit how many values each monomorphized variable can take in
+    /// the task (and each task forked from it), see
+    /// `LocalFrame::set_max_monomorphize`
+    pub fn set_max_monomorphize(&mut self, limit: u32) {
+        self.frame.max_monomorphize = limit;
+    }
+
+    /// Call `hook` whenever a register is written by the task (or any
+    /// task forked from it), see `LocalFrame::set_register_write_hook`
+    pub fn set_register_write_hook(&mut self, hook: Option<RegisterWriteHook<B>>)
+    where
+        B: Clone,
+    {
+        Arc::make_mut(&mut self.frame.local_state).register_write_hook = hook;
+    }
+
+    /// Choose how the task (and each task forked from it) initializes
+    /// registers read before being written, see `LocalFrame::set_uninit_policy`
+    pub fn set_uninit_policy(&mut self, policy: UninitPolicy)
+    where
+        B: Clone,
+    {
+        Arc::make_mut(&mut self.frame.local_state).uninit_policy = policy;
+    }
+
+    /// Enable or disable the concrete fast path for the task (and each
+    /// task forked from it), see `LocalFrame::set_concrete_fast_path`
+    pub fn set_concrete_fast_path(&mut self, enabled: bool) {
+        self.frame.concrete_fast_path = enabled;
+    }
+
+    /// Limit how long each branch check in the task (and each task
+    /// forked from it) can take, see `LocalFrame::set_query_timeout`
+    pub fn set_query_timeout(&mut self, millis: Option<u32>) {
+        self.frame.query_timeout = millis;
+    }
+
+    /// Limit the resources each branch check in the task (and each
+    /// task forked from it) can use, see `LocalFrame::set_query_rlimit`
+    pub fn set_query_rlimit(&mut self, rlimit: Option<u32>) {
+        self.frame.query_rlimit = rlimit;
+    }
+
+    /// Limit how long the task (and each task forked from it) can run
+    /// for, see `LocalFrame::set_path_timeout`
+    pub fn set_path_timeout(&mut self, timeout: Option<Duration>) {
+        self.frame.path_timeout = timeout;
+    }
+
+    /// Choose which paths forked from the task are shared with every
+    /// worker thread, see `LocalFrame::set_share_depth`
+    pub fn set_share_depth(&mut self, depth: u32) {
+        self.frame.share_depth = depth;
+    }
+
+    /// Trace the source lines executed by the task (and each task
+    /// forked from it), see `LocalFrame::set_trace_source_lines`
+    pub fn set_trace_source_lines(&mut self, enabled: bool) {
+        self.frame.source_lines = enabled.then(SourceLoc::unknown);
+    }
+
+    /// Add definitions or assertions to the solver before the task
+    /// starts, e.g. to constrain a register declared by
+    /// `LocalFrame::set_symbolic_register`
+    pub fn add_initial_defs(&mut self, defs: impl IntoIterator<Item = smtlib::Def>) {
+        self.initial_defs.extend(defs)
+    }
 }
@@ -31,8 +31,9 @@ use std::ops::AddAssign;
 
 use num_bigint::BigUint;
 use num_traits::identities::{One, Zero};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Fraction {
     num: BigUint,
     denom_pow: u64,
@@ -78,6 +79,11 @@ impl Fraction {
     pub fn is_one(&self) -> bool {
         self.num == BigUint::one() << self.denom_pow
     }
+
+    /// `1 - frac`, for a fraction which is at most one
+    pub fn complement(&self) -> Self {
+        Fraction { num: (BigUint::one() << self.denom_pow) - &self.num, denom_pow: self.denom_pow }
+    }
 }
 
 impl AddAssign for Fraction {
@@ -112,4 +118,17 @@ mod tests {
         total += f;
         assert!(total.is_one())
     }
+
+    #[test]
+    fn test_fraction_complement() {
+        let mut f = Fraction::one();
+        let rest = f.min_split(6);
+        let mut total = rest.complement();
+        assert!(!total.is_one());
+        total += rest;
+        assert!(total.is_one());
+        let mut total = Fraction::one().complement();
+        total += Fraction::one();
+        assert!(total.is_one())
+    }
 }
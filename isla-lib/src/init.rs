@@ -56,7 +56,7 @@ use std::sync::Mutex;
 
 use crate::bitvector::BV;
 use crate::config::ISAConfig;
-use crate::executor::start_single;
+use crate::executor::{start_single, Strategy};
 use crate::executor::{LocalFrame, TaskId, TaskState};
 use crate::ir::*;
 use crate::log;
@@ -83,9 +83,11 @@ fn initialize_letbinding<'ir, B: BV>(
 
     start_single(
         task,
+        None,
+        Strategy::Dfs,
         shared_state,
         &letbindings,
-        &move |_tid, _task_id, result, shared_state, _solver, letbindings| match result {
+        &move |_tid, _task_id, result, _stats, shared_state, _solver, letbindings| match result {
             Ok((_, frame)) => {
                 for (id, _) in bindings.iter() {
                     match frame.vars().get(id) {
@@ -148,9 +150,11 @@ fn initialize_register<'ir, B: BV>(
 
             start_single(
                 task,
+                None,
+                Strategy::Dfs,
                 shared_state,
                 &(),
-                &move |_tid, _task_id, result, _shared_state, _solver, _| match result {
+                &move |_tid, _task_id, result, _stats, _shared_state, _solver, _| match result {
                     Ok((_, frame)) => {
                         if let Some(v) = frame.regs().get_last_if_initialized(*id) {
                             let mut regs = registers.lock().unwrap();
@@ -202,7 +206,7 @@ pub fn initialize_architecture<'ir, B: BV>(
     insert_monomorphize(arch);
     insert_primops(arch, mode, isa_config);
 
-    let shared_state = SharedState::new(
+    let mut shared_state = SharedState::new(
         symtab,
         arch,
         type_info,
@@ -213,6 +217,8 @@ pub fn initialize_architecture<'ir, B: BV>(
         isa_config.reset_constraints.clone(),
         isa_config.function_assumptions.clone(),
     );
+    shared_state.set_outcome_stubs(arch, isa_config.outcome_stubs.clone());
+    shared_state.set_pure_functions(isa_config.pure_functions.clone());
 
     let lets = Mutex::new(HashMap::default());
     let regs = Mutex::new(RegisterBindings::new());
@@ -53,6 +53,7 @@ use std::sync::Arc;
 use crate::bitvector::{b64::B64, BV};
 use crate::config::ISAConfig;
 use crate::error::ExecError;
+use crate::executor::MemoTable;
 use crate::memory::Memory;
 use crate::primop::{self, Binary, Primops, Unary, Variadic};
 use crate::smt::{smtlib, Accessor, EnumMember, Solver, Sym};
@@ -149,6 +150,30 @@ pub enum Ty<A> {
     RoundingMode,
 }
 
+impl Ty<Name> {
+    /// Print a type using Sail syntax, as used in error messages
+    pub fn to_string(&self, symtab: &Symtab) -> String {
+        use Ty::*;
+        match self {
+            I64 | I128 => "int".to_string(),
+            AnyBits => "bits".to_string(),
+            Bits(sz) => format!("bits({})", sz),
+            Unit => "unit".to_string(),
+            Bool => "bool".to_string(),
+            Bit => "bit".to_string(),
+            String => "string".to_string(),
+            Real => "real".to_string(),
+            Enum(id) | Struct(id) | Union(id) => zencode::decode(symtab.to_str(*id)),
+            Vector(ty) => format!("vector({})", ty.to_string(symtab)),
+            FixedVector(sz, ty) => format!("vector({}, {})", sz, ty.to_string(symtab)),
+            List(ty) => format!("list({})", ty.to_string(symtab)),
+            Ref(ty) => format!("register({})", ty.to_string(symtab)),
+            Float(fpty) => format!("float{}", fpty.exponent_width() + fpty.significand_width()),
+            RoundingMode => "rounding_mode".to_string(),
+        }
+    }
+}
+
 /// A [Loc] is a location that can be assigned to.
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Loc<A> {
@@ -214,7 +239,7 @@ pub enum Op {
     IsEmpty,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum BitsSegment<B> {
     Symbolic(Sym),
     Concrete(B),
@@ -231,7 +256,7 @@ pub enum BitsSegment<B> {
 ///
 /// Note that the equality trait implements a literal equality, see
 /// [crate::primop] for a semantic comparison.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Val<B> {
     Symbolic(Sym),
     I64(i64),
@@ -607,6 +632,28 @@ pub enum Instr<A, B> {
     End,
 }
 
+impl<A, B> Instr<A, B> {
+    /// The source location of the instruction, which is unknown for
+    /// the few instructions that don't have one
+    pub fn source_loc(&self) -> SourceLoc {
+        use Instr::*;
+        match self {
+            Decl(_, _, info)
+            | Init(_, _, _, info)
+            | Jump(_, _, info)
+            | Copy(_, _, info)
+            | Monomorphize(_, info)
+            | Call(_, _, _, _, info)
+            | PrimopUnary(_, _, _, info)
+            | PrimopBinary(_, _, _, _, info)
+            | PrimopVariadic(_, _, _, info)
+            | PrimopReset(_, _, info)
+            | Exit(_, info) => *info,
+            Goto(_) | Arbitrary | End => SourceLoc::unknown(),
+        }
+    }
+}
+
 impl<A: fmt::Debug, B: fmt::Debug> fmt::Debug for Instr<A, B> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use Instr::*;
@@ -972,6 +1019,22 @@ type ExternDecl<'ir> = (&'ir [Ty<Name>], &'ir Ty<Name>, &'ir str);
 pub type Reset<B> =
     Arc<dyn 'static + Send + Sync + Fn(&Memory<B>, Typedefs, &mut Solver<B>) -> Result<Val<B>, ExecError>>;
 
+/// A canned behaviour for a function that has neither a body nor a
+/// primop, such as a Sail outcome for some platform behaviour (like
+/// physical memory attributes, or a source of random numbers) that
+/// isla does not model. Without a stub, any path calling such a
+/// function would fail. Stubs are set in the `[stubs]` section of the
+/// ISA config.
+#[derive(Clone, Debug)]
+pub enum OutcomeStub<B> {
+    /// Always return the given value
+    Constant(Val<B>),
+    /// Return a fresh symbolic value of the function's return type
+    Symbolic,
+    /// Fork, with one path for each of the given return values
+    Fork(Vec<Val<B>>),
+}
+
 /// The `IRTypeInfo` type is the part of the `SharedState` that
 /// contains all the information about the type definitions in the IR.
 /// It is a separate type included as a field in the shared state as
@@ -1025,6 +1088,13 @@ pub struct SharedState<'ir, B> {
     /// given arguments has the given result, skipping execution
     /// derived from the ISA config
     pub function_assumptions: Vec<(String, Vec<Option<smtlib::Exp<Loc<String>>>>, smtlib::Exp<Loc<String>>)>,
+    /// `outcome_stubs` give the behaviour of functions that have
+    /// neither a body nor a primop, along with their return types
+    /// derived from the ISA config
+    pub outcome_stubs: HashMap<Name, (&'ir Ty<Name>, OutcomeStub<B>)>,
+    /// `memo_table` holds the results of calls to functions listed as
+    /// pure in the ISA config, see [MemoTable]
+    pub memo_table: MemoTable<B>,
 }
 
 #[derive(Copy, Clone)]
@@ -1133,9 +1203,47 @@ impl<'ir, B: BV> SharedState<'ir, B> {
             reset_registers,
             reset_constraints,
             function_assumptions,
+            outcome_stubs: HashMap::new(),
+            memo_table: MemoTable::new(HashSet::new()),
         }
     }
 
+    /// Set the stubs for functions that have neither a body nor a
+    /// primop. The return type of each is taken from its declaration
+    /// in `defs`, and stubs for names that are not declared as
+    /// functions there are ignored with a warning.
+    pub fn set_outcome_stubs(&mut self, defs: &'ir [Def<Name, B>], outcome_stubs: HashMap<Name, OutcomeStub<B>>) {
+        let mut ret_tys: HashMap<Name, &'ir Ty<Name>> = HashMap::new();
+        for def in defs {
+            match def {
+                Def::Val(f, _, ret_ty) | Def::Extern(f, _, _, _, ret_ty) => {
+                    ret_tys.entry(*f).or_insert(ret_ty);
+                }
+                _ => (),
+            }
+        }
+
+        self.outcome_stubs = outcome_stubs
+            .into_iter()
+            .filter_map(|(f, stub)| match ret_tys.get(&f) {
+                Some(ret_ty) => Some((f, (*ret_ty, stub))),
+                None => {
+                    eprintln!(
+                        "Warning: Ignoring stub for {}, which is not a function",
+                        zencode::decode(self.symtab.to_str(f))
+                    );
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Set the functions whose calls with concrete arguments are
+    /// memoized, see [MemoTable]
+    pub fn set_pure_functions(&mut self, pure_functions: HashSet<Name>) {
+        self.memo_table = MemoTable::new(pure_functions)
+    }
+
     pub fn typedefs(&self) -> Typedefs {
         Typedefs {
             structs: &self.type_info.structs,
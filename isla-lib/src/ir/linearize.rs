@@ -481,7 +481,13 @@ pub fn self_test<B: BV>(
         .task(executor::TaskId::fresh(), &task_state);
     let result = Arc::new(AtomicBool::new(true));
 
-    executor::start_multi(num_threads, None, vec![task], &shared_state, result.clone(), &executor::all_unsat_collector);
+    executor::start_multi(
+        vec![task],
+        &shared_state,
+        result.clone(),
+        executor::MultiCollector::Plain(&executor::all_unsat_collector),
+        &executor::MultiOpts::new(num_threads),
+    );
 
     result.load(Ordering::Acquire)
 }
@@ -27,10 +27,24 @@
 // (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
 // OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
-use std::sync::atomic::{AtomicU32, Ordering::*};
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering::*};
 
 pub static FLAGS: AtomicU32 = AtomicU32::new(0);
 
+static STREAMING: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// Lines logged by the task the current thread is running, if
+    /// they are being buffered, see [begin_task]
+    static TASK_BUFFER: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+
+    /// Where [flush_task_logs] writes when the current thread is
+    /// capturing its output, see [capture]
+    static CAPTURE: RefCell<Option<Vec<u8>>> = const { RefCell::new(None) };
+}
+
 pub fn color(tid: usize) -> &'static str {
     match tid % 14 {
         0 => "\x1b[91m",
@@ -63,11 +77,85 @@ pub fn set_flags(flags: u32) {
     FLAGS.store(flags, SeqCst);
 }
 
+/// By default the lines logged by each task in a multi-threaded run
+/// are buffered and written together once the run finishes, see
+/// [begin_task]. Streaming writes each line as soon as it is logged,
+/// so lines from different threads interleave. Setting the
+/// `ISLA_LOG_STREAM` environment variable also enables streaming.
+pub fn set_streaming(streaming: bool) {
+    STREAMING.store(streaming, SeqCst)
+}
+
+pub fn streaming() -> bool {
+    STREAMING.load(Relaxed) || std::env::var_os("ISLA_LOG_STREAM").is_some()
+}
+
+/// The lines logged while a worker thread ran one task
+#[derive(Clone, Debug)]
+pub struct TaskLog {
+    pub tid: usize,
+    /// The number of tasks the worker thread ran before this one
+    pub seq: usize,
+    pub lines: Vec<String>,
+}
+
+/// Buffer the lines logged by the current thread until [end_task],
+/// unless logging is [streaming]
+pub fn begin_task() {
+    if !streaming() {
+        TASK_BUFFER.with(|buffer| *buffer.borrow_mut() = Some(Vec::new()))
+    }
+}
+
+/// Stop buffering the lines logged by the current thread, returning
+/// those logged since [begin_task]
+pub fn end_task(tid: usize, seq: usize) -> TaskLog {
+    let lines = TASK_BUFFER.with(|buffer| buffer.borrow_mut().take()).unwrap_or_default();
+    TaskLog { tid, seq, lines }
+}
+
+/// Write a log line, or add it to the current task's buffer
+pub fn write(line: String) {
+    TASK_BUFFER.with(|buffer| match &mut *buffer.borrow_mut() {
+        Some(lines) => lines.push(line),
+        None => eprintln!("{}", line),
+    })
+}
+
+/// Write the lines of each task together, in the order the tasks are
+/// given, preceded by the thread and sequence number of the task
+pub fn flush_task_logs(logs: &[TaskLog]) {
+    let write_logs = |out: &mut dyn Write| -> io::Result<()> {
+        for log in logs.iter().filter(|log| !log.lines.is_empty()) {
+            writeln!(out, "[{}{:<3}\x1b[0m]: task {}", color(log.tid), log.tid, log.seq)?;
+            for line in &log.lines {
+                writeln!(out, "{}", line)?
+            }
+        }
+        Ok(())
+    };
+    CAPTURE.with(|capture| match &mut *capture.borrow_mut() {
+        Some(captured) => write_logs(captured).unwrap(),
+        None => {
+            let _ = write_logs(&mut io::stderr().lock());
+        }
+    })
+}
+
+/// Run `f`, returning what [flush_task_logs] writes on the current
+/// thread instead of writing it to stderr
+pub fn capture<T>(f: impl FnOnce() -> T) -> (T, String) {
+    CAPTURE.with(|capture| *capture.borrow_mut() = Some(Vec::new()));
+    let result = f();
+    let captured = CAPTURE.with(|capture| capture.borrow_mut().take()).unwrap_or_default();
+    (result, String::from_utf8_lossy(&captured).into_owned())
+}
+
 #[macro_export]
 macro_rules! log {
     ($flags: expr, $msg: expr) => {
         if log::FLAGS.load(std::sync::atomic::Ordering::Relaxed) & $flags > 0u32 {
-            eprintln!("[log]: {}", $msg)
+            log::write(format!("[log]: {}", $msg))
         }
     };
 }
@@ -76,7 +164,7 @@ macro_rules! log {
 macro_rules! log_from {
     ($tid: expr, $flags: expr, $msg: expr) => {
         if log::FLAGS.load(std::sync::atomic::Ordering::Relaxed) & $flags > 0u32 {
-            eprintln!("[{}{:<3}\x1b[0m]: {}", log::color($tid), $tid, $msg)
+            log::write(format!("[{}{:<3}\x1b[0m]: {}", log::color($tid), $tid, $msg))
         }
     };
 }
@@ -42,6 +42,8 @@ use std::fmt;
 use std::ops::Range;
 use std::sync::Arc;
 
+use serde::{Deserialize, Serialize};
+
 use crate::bitvector::BV;
 use crate::error::ExecError;
 use crate::ir;
@@ -228,10 +230,26 @@ fn make_bv_bit_pair<B>(left: Val<B>, right: Val<B>) -> Val<B> {
     Val::Struct(fields)
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReadOrWrite {
+    Read,
+    Write,
+}
+
+/// A memory access made by a path. The address is the one given to
+/// [Memory::read] or [Memory::write], so it may be symbolic.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FootprintEntry<B> {
+    pub address: Val<B>,
+    pub bytes: u32,
+    pub kind: ReadOrWrite,
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct Memory<B> {
     regions: Vec<Region<B>>,
     client_info: Option<Box<dyn MemoryCallbacks<B>>>,
+    footprint: Vec<FootprintEntry<B>>,
 }
 
 static DEFAULT_REGION_NAME: &str = "default";
@@ -243,7 +261,17 @@ enum Overlap {
 
 impl<B: BV> Memory<B> {
     pub fn new() -> Self {
-        Memory { regions: Vec::new(), client_info: None }
+        Memory { regions: Vec::new(), client_info: None, footprint: Vec::new() }
+    }
+
+    /// Every read and write made through this memory so far, in the
+    /// order they were made
+    pub fn footprint(&self) -> &[FootprintEntry<B>] {
+        &self.footprint
+    }
+
+    pub(crate) fn set_footprint(&mut self, footprint: Vec<FootprintEntry<B>>) {
+        self.footprint = footprint
     }
 
     pub fn regions(&self) -> &[Region<B>] {
@@ -436,13 +464,31 @@ impl<B: BV> Memory<B> {
 
     /// Read from the memory region determined by the address. If the address is symbolic the read
     /// value is always also symbolic. The number of bytes must be concrete otherwise will return a
-    /// SymbolicLength error.
+    /// SymbolicLength error. Each successful read is added to the
+    /// [Memory::footprint].
     ///
     /// # Panics
     ///
     /// Panics if the number of bytes to read is concrete but does not fit
     /// in a u32, which should never be the case.
     pub fn read(
+        &mut self,
+        read_kind: Val<B>,
+        address: Val<B>,
+        bytes: Val<B>,
+        solver: &mut Solver<B>,
+        tag: bool,
+        opts: ReadOpts,
+    ) -> Result<Val<B>, ExecError> {
+        let value = self.read_region(read_kind, address.clone(), bytes.clone(), solver, tag, opts)?;
+        // A read only succeeds if the number of bytes is concrete
+        if let Val::I128(bytes) = bytes.widen_int() {
+            self.footprint.push(FootprintEntry { address, bytes: bytes as u32, kind: ReadOrWrite::Read })
+        }
+        Ok(value)
+    }
+
+    fn read_region(
         &self,
         read_kind: Val<B>,
         address: Val<B>,
@@ -536,7 +582,7 @@ impl<B: BV> Memory<B> {
                         ExecError::BadRead("Possible symbolic address overlap"),
                         solver,
                     )? {
-                        Overlap::Unique(concrete_addr) => self.read(
+                        Overlap::Unique(concrete_addr) => self.read_region(
                             read_kind,
                             Val::Bits(B::new(concrete_addr, 64)),
                             Val::I128(bytes as i128),
@@ -557,6 +603,8 @@ impl<B: BV> Memory<B> {
         }
     }
 
+    /// Write to the memory region determined by the address, adding
+    /// the write to the [Memory::footprint] if it succeeds.
     pub fn write(
         &mut self,
         write_kind: Val<B>,
@@ -565,6 +613,21 @@ impl<B: BV> Memory<B> {
         solver: &mut Solver<B>,
         tag: Option<Val<B>>,
         opts: WriteOpts,
+    ) -> Result<Val<B>, ExecError> {
+        let bytes = crate::primop_util::length_bits(&data, solver, SourceLoc::unknown())? / 8;
+        let value = self.write_region(write_kind, address.clone(), data, solver, tag, opts)?;
+        self.footprint.push(FootprintEntry { address, bytes, kind: ReadOrWrite::Write });
+        Ok(value)
+    }
+
+    fn write_region(
+        &mut self,
+        write_kind: Val<B>,
+        address: Val<B>,
+        data: Val<B>,
+        solver: &mut Solver<B>,
+        tag: Option<Val<B>>,
+        opts: WriteOpts,
     ) -> Result<Val<B>, ExecError> {
         log!(log::MEMORY, &format!("Write: {:?} {:?} {:?} {:?}", write_kind, address, data, tag));
 
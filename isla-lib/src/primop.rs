@@ -727,8 +727,13 @@ macro_rules! extension {
         ) -> Result<Val<B>, ExecError> {
             match (replace_mixed_bits(bits, solver, info)?, len) {
                 (Val::Bits(bits), Val::I128(len)) => {
+                    if len < bits.len() as i128 {
+                        return Err(ExecError::Type(format!("{} (cannot shrink) {:?} {:?}", $name, &bits, &len), info));
+                    }
                     let len = len as u32;
-                    if len > B::MAX_WIDTH {
+                    if len == bits.len() {
+                        Ok(Val::Bits(bits))
+                    } else if len > B::MAX_WIDTH {
                         let ext = len - bits.len();
                         solver.define_const($smt_extension(ext, Box::new(smt_sbits(bits))), info).into()
                     } else {
@@ -737,20 +742,27 @@ macro_rules! extension {
                 }
                 (Val::Symbolic(bits), Val::I128(len)) => {
                     let ext = match solver.length(bits) {
-                        Some(orig_len) => len as u32 - orig_len,
-                        None => return Err(ExecError::Type($name, info)),
+                        Some(orig_len) if len == orig_len as i128 => return Ok(Val::Symbolic(bits)),
+                        Some(orig_len) if len > orig_len as i128 => len as u32 - orig_len,
+                        Some(_) => {
+                            return Err(ExecError::Type(
+                                format!("{} (cannot shrink) {:?} {:?}", $name, &bits, &len),
+                                info,
+                            ))
+                        }
+                        None => return Err(ExecError::Type($name.to_string(), info)),
                     };
                     solver.define_const($smt_extension(ext, Box::new(Exp::Var(bits))), info).into()
                 }
                 (_, Val::Symbolic(_)) => Err(ExecError::SymbolicLength("extension", info)),
-                (_, _) => Err(ExecError::Type($name, info)),
+                (_, _) => Err(ExecError::Type($name.to_string(), info)),
             }
         }
     };
 }
 
-extension!(zero_extend, "zero_extend".to_string(), Exp::ZeroExtend, B::zero_extend);
-extension!(sign_extend, "sign_extend".to_string(), Exp::SignExtend, B::sign_extend);
+extension!(zero_extend, "zero_extend", Exp::ZeroExtend, B::zero_extend);
+extension!(sign_extend, "sign_extend", Exp::SignExtend, B::sign_extend);
 
 pub(crate) fn op_zero_extend<B: BV>(
     bits: Val<B>,
@@ -760,6 +772,10 @@ pub(crate) fn op_zero_extend<B: BV>(
 ) -> Result<Val<B>, ExecError> {
     let bits = replace_mixed_bits(bits, solver, info)?;
     match bits {
+        Val::Bits(bits) if len < bits.len() => {
+            Err(ExecError::Type(format!("op_zero_extend (cannot shrink) {:?} {}", &bits, len), info))
+        }
+        Val::Bits(bits) if len == bits.len() => Ok(Val::Bits(bits)),
         Val::Bits(bits) => {
             if len > 64 {
                 let ext = len - bits.len();
@@ -770,7 +786,11 @@ pub(crate) fn op_zero_extend<B: BV>(
         }
         Val::Symbolic(bits) => {
             let ext = match solver.length(bits) {
-                Some(orig_len) => len - orig_len,
+                Some(orig_len) if len == orig_len => return Ok(Val::Symbolic(bits)),
+                Some(orig_len) if len > orig_len => len - orig_len,
+                Some(_) => {
+                    return Err(ExecError::Type(format!("op_zero_extend (cannot shrink) {:?} {}", &bits, len), info))
+                }
                 None => {
                     return Err(ExecError::Type(
                         format!("op_zero_extend (solver cannot determine length) {:?}", &bits),
@@ -1008,9 +1028,18 @@ pub fn subrange_internal<B: BV>(
     info: SourceLoc,
 ) -> Result<Val<B>, ExecError> {
     match (bits, high, low) {
-        (Val::Symbolic(bits), Val::I128(high), Val::I128(low)) => {
-            solver.define_const(Exp::Extract(high as u32, low as u32, Box::new(Exp::Var(bits))), info).into()
+        (bits, Val::I128(high), Val::I128(low)) if high < low || low < 0 => {
+            Err(ExecError::Type(format!("subrange_internal (invalid range) {:?} {:?} {:?}", &bits, &high, &low), info))
         }
+        (Val::Symbolic(bits), Val::I128(high), Val::I128(low)) => match solver.length(bits) {
+            Some(len) if high < len as i128 => {
+                solver.define_const(Exp::Extract(high as u32, low as u32, Box::new(Exp::Var(bits))), info).into()
+            }
+            _ => Err(ExecError::Type(
+                format!("subrange_internal (cannot extract) {:?} {:?} {:?}", &bits, &high, &low),
+                info,
+            )),
+        },
         (Val::Bits(bits), Val::I128(high), Val::I128(low)) => match bits.extract(high as u32, low as u32) {
             Some(bits) => Ok(Val::Bits(bits)),
             None => Err(ExecError::Type(
@@ -1020,6 +1049,12 @@ pub fn subrange_internal<B: BV>(
         },
         (Val::MixedBits(ref segments), Val::I128(high), Val::I128(low)) => {
             let bits_length = segments_length(segments, solver, info)?;
+            if high >= bits_length as i128 {
+                return Err(ExecError::Type(
+                    format!("subrange_internal (cannot extract) {:?} {:?} {:?}", segments, &high, &low),
+                    info,
+                ));
+            }
             mixed_bits_slice(segments, bits_length, low as u32, (high - low + 1) as u32, solver, info)
         }
         (_, _, Val::Symbolic(_)) => Err(ExecError::SymbolicLength("subrange_internal", info)),
@@ -1429,7 +1464,11 @@ pub(crate) fn vector_access<B: BV>(
             Some(bit) => Ok(Val::Bits(bit)),
             None => Err(ExecError::Type(format!("vector_access {:?} {:?}", &bits, &n), info)),
         },
-        (Val::Vector(vec), Val::I128(n)) => match vec.get(n as usize) {
+        (Val::Vector(vec), Val::I128(n)) => match usize::try_from(n).ok().and_then(|n| vec.get(n)) {
+            Some(elem) => Ok(elem.clone()),
+            None => Err(ExecError::OutOfBounds("vector_access")),
+        },
+        (Val::Vector(vec), Val::I64(n)) => match usize::try_from(n).ok().and_then(|n| vec.get(n)) {
             Some(elem) => Ok(elem.clone()),
             None => Err(ExecError::OutOfBounds("vector_access")),
         },
@@ -1713,14 +1752,20 @@ pub fn vector_update<B: BV>(
     let arg0 = replace_mixed_bits(arg0, solver, info)?;
     match arg0 {
         Val::Vector(mut vec) => match args[1] {
-            Val::I128(n) => {
-                vec[n as usize] = args[2].clone();
-                Ok(Val::Vector(vec))
-            }
-            Val::I64(n) => {
-                vec[n as usize] = args[2].clone();
-                Ok(Val::Vector(vec))
-            }
+            Val::I128(n) => match usize::try_from(n).ok().and_then(|n| vec.get_mut(n)) {
+                Some(item) => {
+                    *item = args[2].clone();
+                    Ok(Val::Vector(vec))
+                }
+                None => Err(ExecError::OutOfBounds("vector_update")),
+            },
+            Val::I64(n) => match usize::try_from(n).ok().and_then(|n| vec.get_mut(n)) {
+                Some(item) => {
+                    *item = args[2].clone();
+                    Ok(Val::Vector(vec))
+                }
+                None => Err(ExecError::OutOfBounds("vector_update")),
+            },
             Val::Symbolic(n) => {
                 for (i, item) in vec.iter_mut().enumerate() {
                     let var = solver.fresh();
@@ -1833,9 +1878,12 @@ fn unimplemented<B: BV>(
     Err(ExecError::Unimplemented)
 }
 
-fn eq_string<B: BV>(lhs: Val<B>, rhs: Val<B>, _: &mut Solver<B>, info: SourceLoc) -> Result<Val<B>, ExecError> {
+fn eq_string<B: BV>(lhs: Val<B>, rhs: Val<B>, solver: &mut Solver<B>, info: SourceLoc) -> Result<Val<B>, ExecError> {
     match (lhs, rhs) {
         (Val::String(lhs), Val::String(rhs)) => Ok(Val::Bool(lhs == rhs)),
+        // Symbolic strings are opaque tokens, see smt_string
+        (lhs @ Val::Symbolic(_), rhs @ (Val::Symbolic(_) | Val::String(_)))
+        | (lhs @ Val::String(_), rhs @ Val::Symbolic(_)) => eq_anything(lhs, rhs, solver, info),
         (lhs, rhs) => Err(ExecError::Type(format!("eq_string {:?} {:?}", &lhs, &rhs), info)),
     }
 }
@@ -1880,6 +1928,15 @@ fn string_to_i128<B: BV>(s: Val<B>, _: &mut Solver<B>, info: SourceLoc) -> Resul
     }
 }
 
+/// Define a boolean test, unless the solver already knows whether it
+/// holds (see `Solver::known_exp`)
+fn define_test<B: BV>(test: Exp<Sym>, solver: &mut Solver<B>, info: SourceLoc) -> Result<Val<B>, ExecError> {
+    match solver.known_exp(&test) {
+        Some(Exp::Bool(b)) => Ok(Val::Bool(b)),
+        _ => solver.define_const(test, info).into(),
+    }
+}
+
 pub fn eq_anything<B: BV>(
     lhs: Val<B>,
     rhs: Val<B>,
@@ -1888,13 +1945,13 @@ pub fn eq_anything<B: BV>(
 ) -> Result<Val<B>, ExecError> {
     match (replace_mixed_bits(lhs, solver, info)?, replace_mixed_bits(rhs, solver, info)?) {
         (Val::Symbolic(lhs), Val::Symbolic(rhs)) => {
-            solver.define_const(Exp::Eq(Box::new(Exp::Var(lhs)), Box::new(Exp::Var(rhs))), info).into()
+            define_test(Exp::Eq(Box::new(Exp::Var(lhs)), Box::new(Exp::Var(rhs))), solver, info)
         }
         (lhs, Val::Symbolic(rhs)) => {
-            solver.define_const(Exp::Eq(Box::new(smt_value(&lhs, info)?), Box::new(Exp::Var(rhs))), info).into()
+            define_test(Exp::Eq(Box::new(smt_value(&lhs, info)?), Box::new(Exp::Var(rhs))), solver, info)
         }
         (Val::Symbolic(lhs), rhs) => {
-            solver.define_const(Exp::Eq(Box::new(Exp::Var(lhs)), Box::new(smt_value(&rhs, info)?)), info).into()
+            define_test(Exp::Eq(Box::new(Exp::Var(lhs)), Box::new(smt_value(&rhs, info)?)), solver, info)
         }
 
         (Val::Bits(lhs), Val::Bits(rhs)) => Ok(Val::Bool(lhs == rhs)),
@@ -1902,6 +1959,7 @@ pub fn eq_anything<B: BV>(
         (Val::Bool(lhs), Val::Bool(rhs)) => Ok(Val::Bool(lhs == rhs)),
         (Val::I128(lhs), Val::I128(rhs)) => Ok(Val::Bool(lhs == rhs)),
         (Val::I64(lhs), Val::I64(rhs)) => Ok(Val::Bool(lhs == rhs)),
+        (Val::String(lhs), Val::String(rhs)) => Ok(Val::Bool(lhs == rhs)),
         (Val::Struct(lhs), Val::Struct(rhs)) => {
             let mut vars = vec![];
             for (k, lhs_v) in lhs {
@@ -1935,6 +1993,33 @@ pub fn eq_anything<B: BV>(
                 Ok(Val::Bool(false))
             }
         }
+        (Val::SymbolicCtor(tag, mut possibilities), Val::Ctor(ctor, value))
+        | (Val::Ctor(ctor, value), Val::SymbolicCtor(tag, mut possibilities)) => match possibilities.remove(&ctor) {
+            Some(possible_value) => {
+                let is_ctor = solver.define_const(Exp::Eq(Box::new(Exp::Var(tag)), Box::new(ctor.to_smt())), info);
+                let same_value = eq_anything(possible_value, *value, solver, info)?;
+                and_bool(Val::Symbolic(is_ctor), same_value, solver, info)
+            }
+            None => Ok(Val::Bool(false)),
+        },
+        (Val::SymbolicCtor(lhs_tag, lhs), Val::SymbolicCtor(rhs_tag, mut rhs)) => {
+            // The tags must be the same, and so must the payloads of
+            // whichever constructor they choose
+            let mut lhs: Vec<_> = lhs.into_iter().collect();
+            lhs.sort_by_key(|(ctor, _)| *ctor);
+            let mut same_value = Val::Bool(false);
+            for (ctor, lhs_value) in lhs {
+                if let Some(rhs_value) = rhs.remove(&ctor) {
+                    let is_ctor =
+                        solver.define_const(Exp::Eq(Box::new(Exp::Var(lhs_tag)), Box::new(ctor.to_smt())), info);
+                    let same_ctor_value = eq_anything(lhs_value, rhs_value, solver, info)?;
+                    let same_ctor_value = and_bool(Val::Symbolic(is_ctor), same_ctor_value, solver, info)?;
+                    same_value = or_bool(same_value, same_ctor_value, solver, info)?
+                }
+            }
+            let same_tag = solver.define_const(Exp::Eq(Box::new(Exp::Var(lhs_tag)), Box::new(Exp::Var(rhs_tag))), info);
+            and_bool(Val::Symbolic(same_tag), same_value, solver, info)
+        }
         (Val::Unit, Val::Unit) => Ok(Val::Bool(true)),
 
         (lhs, rhs) => Err(ExecError::Type(format!("eq_anything {:?} {:?}", &lhs, &rhs), info)),
@@ -2202,7 +2287,7 @@ fn read_mem<B: BV>(
     frame: &mut LocalFrame<B>,
     _: SourceLoc,
 ) -> Result<Val<B>, ExecError> {
-    frame.memory().read(args[0].clone(), args[2].clone(), args[3].clone(), solver, false, ReadOpts::default())
+    frame.memory_mut().read(args[0].clone(), args[2].clone(), args[3].clone(), solver, false, ReadOpts::default())
 }
 
 fn read_mem_ifetch<B: BV>(
@@ -2211,7 +2296,7 @@ fn read_mem_ifetch<B: BV>(
     frame: &mut LocalFrame<B>,
     _: SourceLoc,
 ) -> Result<Val<B>, ExecError> {
-    frame.memory().read(args[0].clone(), args[2].clone(), args[3].clone(), solver, false, ReadOpts::ifetch())
+    frame.memory_mut().read(args[0].clone(), args[2].clone(), args[3].clone(), solver, false, ReadOpts::ifetch())
 }
 
 fn read_mem_exclusive<B: BV>(
@@ -2220,7 +2305,7 @@ fn read_mem_exclusive<B: BV>(
     frame: &mut LocalFrame<B>,
     _: SourceLoc,
 ) -> Result<Val<B>, ExecError> {
-    frame.memory().read(args[0].clone(), args[2].clone(), args[3].clone(), solver, false, ReadOpts::exclusive())
+    frame.memory_mut().read(args[0].clone(), args[2].clone(), args[3].clone(), solver, false, ReadOpts::exclusive())
 }
 
 fn read_memt<B: BV>(
@@ -2229,7 +2314,7 @@ fn read_memt<B: BV>(
     frame: &mut LocalFrame<B>,
     _: SourceLoc,
 ) -> Result<Val<B>, ExecError> {
-    frame.memory().read(args[0].clone(), args[1].clone(), args[2].clone(), solver, true, ReadOpts::default())
+    frame.memory_mut().read(args[0].clone(), args[1].clone(), args[2].clone(), solver, true, ReadOpts::default())
 }
 
 fn bad_read<B: BV>(_: Val<B>, _: &mut Solver<B>, _: SourceLoc) -> Result<Val<B>, ExecError> {
@@ -2754,4 +2839,63 @@ mod tests {
         assert!(solver.check_sat(SourceLoc::unknown()) == SmtResult::Sat);
         Ok(())
     }
+
+    #[test]
+    fn subrange_boundaries() {
+        let cfg = Config::new();
+        let ctx = Context::new(cfg);
+        let mut solver = Solver::<B64>::new(&ctx);
+        let info = SourceLoc::unknown();
+        for width in 1..=8u32 {
+            let concrete = Val::Bits(B64::new(0b1010_0110, 8).slice(0, width).unwrap());
+            let symbolic = Val::Symbolic(solver.declare_const(Ty::BitVec(width), info));
+            for bits in [concrete, symbolic] {
+                for high in -1..=(width as i128 + 1) {
+                    for low in -1..=(width as i128 + 1) {
+                        let valid = 0 <= low && low <= high && high < width as i128;
+                        match subrange_internal(bits.clone(), Val::I128(high), Val::I128(low), &mut solver, info) {
+                            Ok(result) => {
+                                assert!(valid, "{:?}[{}..{}] should be rejected", bits, high, low);
+                                assert_eq!(length_bits(&result, &mut solver, info).unwrap() as i128, high - low + 1)
+                            }
+                            Err(_) => assert!(!valid, "{:?}[{}..{}] should be accepted", bits, high, low),
+                        }
+                    }
+                }
+            }
+        }
+        assert!(solver.check_sat(info) == SmtResult::Sat)
+    }
+
+    #[test]
+    fn extension_boundaries() {
+        let cfg = Config::new();
+        let ctx = Context::new(cfg);
+        let mut solver = Solver::<B64>::new(&ctx);
+        let info = SourceLoc::unknown();
+        for width in 1..=8u32 {
+            let concrete = Val::Bits(B64::new(0b1010_0110, 8).slice(0, width).unwrap());
+            let symbolic = Val::Symbolic(solver.declare_const(Ty::BitVec(width), info));
+            for bits in [concrete, symbolic] {
+                for len in 0..=(width + 2) {
+                    let results = [
+                        zero_extend(bits.clone(), Val::I128(len as i128), &mut solver, info),
+                        sign_extend(bits.clone(), Val::I128(len as i128), &mut solver, info),
+                        op_zero_extend(bits.clone(), len, &mut solver, info),
+                    ];
+                    for result in results {
+                        if len < width {
+                            assert!(result.is_err(), "extending {:?} to {} should be rejected", bits, len)
+                        } else if len == width {
+                            // Width-preserving extensions return the argument unchanged
+                            assert_eq!(result.unwrap(), bits)
+                        } else {
+                            assert_eq!(length_bits(&result.unwrap(), &mut solver, info).unwrap(), len)
+                        }
+                    }
+                }
+            }
+        }
+        assert!(solver.check_sat(info) == SmtResult::Sat)
+    }
 }
@@ -47,7 +47,7 @@ fn read_mem<B: BV>(
     frame: &mut LocalFrame<B>,
     _: SourceLoc,
 ) -> Result<Val<B>, ExecError> {
-    frame.memory().read(args[0].clone(), args[2].clone(), args[3].clone(), solver, false, ReadOpts::default())
+    frame.memory_mut().read(args[0].clone(), args[2].clone(), args[3].clone(), solver, false, ReadOpts::default())
 }
 
 fn read_mem_ifetch<B: BV>(
@@ -56,7 +56,7 @@ fn read_mem_ifetch<B: BV>(
     frame: &mut LocalFrame<B>,
     _: SourceLoc,
 ) -> Result<Val<B>, ExecError> {
-    frame.memory().read(args[0].clone(), args[2].clone(), args[3].clone(), solver, false, ReadOpts::ifetch())
+    frame.memory_mut().read(args[0].clone(), args[2].clone(), args[3].clone(), solver, false, ReadOpts::ifetch())
 }
 
 fn read_mem_exclusive<B: BV>(
@@ -65,7 +65,7 @@ fn read_mem_exclusive<B: BV>(
     frame: &mut LocalFrame<B>,
     _: SourceLoc,
 ) -> Result<Val<B>, ExecError> {
-    frame.memory().read(args[0].clone(), args[2].clone(), args[3].clone(), solver, false, ReadOpts::exclusive())
+    frame.memory_mut().read(args[0].clone(), args[2].clone(), args[3].clone(), solver, false, ReadOpts::exclusive())
 }
 
 fn write_mem<B: BV>(
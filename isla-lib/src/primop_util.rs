@@ -32,6 +32,8 @@
 //! primitive operations, including converting IR values into SMT
 //! equivalents.
 
+use sha2::{Digest, Sha256};
+
 use std::collections::{hash_map::Entry, HashMap};
 
 use crate::bitvector::b64::B64;
@@ -39,7 +41,7 @@ use crate::bitvector::BV;
 use crate::error::ExecError;
 use crate::ir::{BitsSegment, Name, SharedState, Ty, Typedefs, Val};
 use crate::smt::smtlib::{self, bits64, Exp};
-use crate::smt::{Solver, Sym};
+use crate::smt::{EnumMember, Solver, Sym};
 use crate::source_loc::SourceLoc;
 
 #[allow(clippy::needless_range_loop)]
@@ -57,6 +59,20 @@ pub fn smt_i64<V>(i: i64) -> Exp<V> {
     Exp::Bits64(B64::new(i as u64, 64))
 }
 
+/// The width of the bitvector used as an opaque token for a symbolic
+/// string
+pub const STRING_TOKEN_WIDTH: u32 = 64;
+
+/// Symbolic strings only support equality, so they are opaque tokens
+/// which concrete strings are compared against by the first 64 bits
+/// of their SHA-256 hash.
+pub fn smt_string<V>(s: &str) -> Exp<V> {
+    let hash = Sha256::digest(s.as_bytes());
+    let mut token = [0; 8];
+    token.copy_from_slice(&hash[0..8]);
+    Exp::Bits64(B64::new(u64::from_le_bytes(token), STRING_TOKEN_WIDTH))
+}
+
 pub fn smt_u8<V>(i: u8) -> Exp<V> {
     Exp::Bits64(B64::new(i as u64, 8))
 }
@@ -188,6 +204,7 @@ pub fn smt_value<B: BV>(v: &Val<B>, info: SourceLoc) -> Result<Exp<Sym>, ExecErr
         Val::Bits(bv) => smt_sbits(*bv),
         Val::Bool(b) => Exp::Bool(*b),
         Val::Enum(e) => Exp::Enum(*e),
+        Val::String(s) => smt_string(s),
         Val::Symbolic(v) => Exp::Var(*v),
         _ => return Err(ExecError::Type(format!("smt_value {:?}", &v), info)),
     })
@@ -335,6 +352,18 @@ pub fn symbolic_from_typedefs<B: BV>(
     typedefs: Typedefs,
     solver: &mut Solver<B>,
     info: SourceLoc,
+) -> Result<Val<B>, ExecError> {
+    symbolic_in_unions(ty, typedefs, &mut Vec::new(), solver, info)
+}
+
+/// Create a symbolic value inside the payloads of `unions`, which
+/// are used to stop recursive unions being unfolded forever.
+fn symbolic_in_unions<B: BV>(
+    ty: &Ty<Name>,
+    typedefs: Typedefs,
+    unions: &mut Vec<Name>,
+    solver: &mut Solver<B>,
+    info: SourceLoc,
 ) -> Result<Val<B>, ExecError> {
     let smt_ty = match ty {
         Ty::Unit => return Ok(Val::Unit),
@@ -350,7 +379,7 @@ pub fn symbolic_from_typedefs<B: BV>(
             if let Some(field_types) = typedefs.structs.get(name) {
                 let field_values = field_types
                     .iter()
-                    .map(|(f, ty)| match symbolic_from_typedefs(ty, typedefs, solver, info) {
+                    .map(|(f, ty)| match symbolic_in_unions(ty, typedefs, unions, solver, info) {
                         Ok(value) => Ok((*f, value)),
                         Err(error) => Err(error),
                     })
@@ -371,14 +400,37 @@ pub fn symbolic_from_typedefs<B: BV>(
             if let Some(ctor_types) = typedefs.unions.get(name) {
                 use smtlib::Exp::*;
 
-                let sym = solver.declare_const(Name::smt_ty(), info);
-                let mut name_exp = Bool(false);
-                let mut possibilities = HashMap::default();
+                if unions.contains(name) {
+                    return Err(ExecError::NoSymbolicType(ty.to_string(typedefs.symtab), None));
+                }
 
+                // Constructors with a payload that cannot be symbolic
+                // (including those that refer back to this union) are
+                // left out, so the symbolic tag can never choose them.
+                unions.push(*name);
+                let mut possibilities = HashMap::default();
                 for (ctor, ty) in ctor_types {
+                    match symbolic_in_unions(ty, typedefs, unions, solver, info) {
+                        Ok(value) => {
+                            possibilities.insert(*ctor, value);
+                        }
+                        Err(ExecError::NoSymbolicType(..)) => (),
+                        Err(error) => {
+                            unions.pop();
+                            return Err(error);
+                        }
+                    }
+                }
+                unions.pop();
+
+                if possibilities.is_empty() {
+                    return Err(ExecError::NoSymbolicType(ty.to_string(typedefs.symtab), None));
+                }
+
+                let sym = solver.declare_const(Name::smt_ty(), info);
+                let mut name_exp = Bool(false);
+                for (ctor, _) in ctor_types.iter().filter(|(ctor, _)| possibilities.contains_key(ctor)) {
                     name_exp = Or(Box::new(Eq(Box::new(Var(sym)), Box::new(ctor.to_smt()))), Box::new(name_exp));
-                    let value = symbolic_from_typedefs(ty, typedefs, solver, info)?;
-                    possibilities.insert(*ctor, value);
                 }
 
                 solver.assert(name_exp);
@@ -390,12 +442,15 @@ pub fn symbolic_from_typedefs<B: BV>(
 
         Ty::FixedVector(sz, ty) => {
             let values =
-                (0..*sz).map(|_| symbolic_from_typedefs(ty, typedefs, solver, info)).collect::<Result<_, _>>()?;
+                (0..*sz).map(|_| symbolic_in_unions(ty, typedefs, unions, solver, info)).collect::<Result<_, _>>()?;
             return Ok(Val::Vector(values));
         }
 
         Ty::Float(f) => f.to_smt(),
         Ty::RoundingMode => smtlib::Ty::RoundingMode,
+        Ty::String => smtlib::Ty::BitVec(STRING_TOKEN_WIDTH),
+
+        Ty::List(_) => return Err(ExecError::NoSymbolicType(ty.to_string(typedefs.symtab), None)),
 
         // Some things we just can't represent symbolically, but we can continue in the hope that
         // they never actually get used.
@@ -408,8 +463,10 @@ pub fn symbolic_from_typedefs<B: BV>(
 /// Create a Symbolic value of a specified type. Can return a concrete value if the type only
 /// permits a single value, such as for the unit type or the zero-length bitvector type (which is
 /// ideal because SMT solvers don't allow zero-length bitvectors). Compound types like structs will
-/// be a concrete structure with symbolic values for each field. Returns the `NoSymbolicType` error
-/// if the type cannot be represented in the SMT solver.
+/// be a concrete structure with symbolic values for each field, and unions have a symbolic tag
+/// choosing between a payload for each constructor. Strings are opaque tokens that can
+/// only be compared for equality. Returns the `NoSymbolicType` error if the type cannot be
+/// represented in the SMT solver.
 pub fn symbolic<B: BV>(
     ty: &Ty<Name>,
     shared_state: &SharedState<B>,
@@ -418,3 +475,79 @@ pub fn symbolic<B: BV>(
 ) -> Result<Val<B>, ExecError> {
     symbolic_from_typedefs(ty, shared_state.typedefs(), solver, info)
 }
+
+/// Create an all-zero value of a specified type, as an alternative
+/// to [symbolic] for initializing registers. Enumerations take their
+/// first member and unions their first constructor with a zero
+/// payload. Types without an obvious zero (such as floating point
+/// values) are still initialized symbolically.
+pub fn zero<B: BV>(
+    ty: &Ty<Name>,
+    shared_state: &SharedState<B>,
+    solver: &mut Solver<B>,
+    info: SourceLoc,
+) -> Result<Val<B>, ExecError> {
+    zero_from_typedefs(ty, shared_state.typedefs(), solver, info)
+}
+
+fn zero_from_typedefs<B: BV>(
+    ty: &Ty<Name>,
+    typedefs: Typedefs,
+    solver: &mut Solver<B>,
+    info: SourceLoc,
+) -> Result<Val<B>, ExecError> {
+    Ok(match ty {
+        Ty::Unit => Val::Unit,
+        Ty::I64 => Val::I64(0),
+        Ty::I128 => Val::I128(0),
+        Ty::Bits(sz) => Val::Bits(B::zeros(*sz)),
+        Ty::Bit => Val::Bits(B::zeros(1)),
+        Ty::Bool => Val::Bool(false),
+        Ty::String => Val::String(String::new()),
+        Ty::List(_) => Val::List(Vec::new()),
+
+        Ty::Struct(name) => {
+            if let Some(field_types) = typedefs.structs.get(name) {
+                let field_values = field_types
+                    .iter()
+                    .map(|(f, ty)| Ok((*f, zero_from_typedefs(ty, typedefs, solver, info)?)))
+                    .collect::<Result<_, ExecError>>()?;
+                Val::Struct(field_values)
+            } else {
+                return Err(ExecError::Unreachable(format!("Struct {:?} does not appear to exist!", name)));
+            }
+        }
+
+        Ty::Enum(name) => {
+            let enum_size = typedefs.enums.get(name).unwrap().len();
+            let enum_id = solver.get_enum(*name, enum_size);
+            Val::Enum(EnumMember { enum_id, member: 0 })
+        }
+
+        Ty::Union(name) => {
+            if let Some(ctor_types) = typedefs.unions.get(name) {
+                for (ctor, ty) in ctor_types {
+                    // Recursive unions must have some constructor
+                    // that does not refer back to the union
+                    if let Ty::Union(inner) = ty {
+                        if inner == name {
+                            continue;
+                        }
+                    }
+                    if let Ok(value) = zero_from_typedefs(ty, typedefs, solver, info) {
+                        return Ok(Val::Ctor(*ctor, Box::new(value)));
+                    }
+                }
+                return Err(ExecError::NoSymbolicType(ty.to_string(typedefs.symtab), None));
+            } else {
+                return Err(ExecError::Unreachable(format!("Union {:?} does not appear to exist!", name)));
+            }
+        }
+
+        Ty::FixedVector(sz, ty) => {
+            Val::Vector((0..*sz).map(|_| zero_from_typedefs(ty, typedefs, solver, info)).collect::<Result<_, _>>()?)
+        }
+
+        _ => symbolic_from_typedefs(ty, typedefs, solver, info)?,
+    })
+}
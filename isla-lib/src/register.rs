@@ -53,7 +53,8 @@
 //!   is uninitialised when we read it, we initialise it to an
 //!   symbolic unknown value of the correct type, setting both the
 //!   last read value and the last written value to that unknown
-//!   value.
+//!   value. Alternatively, an [UninitPolicy] can initialise it to
+//!   zero, or make reading it an error.
 //!
 //! * When we _write_ to a register we simply store the value as the
 //!   last written value. We also set the last read value to the last
@@ -75,16 +76,45 @@
 //! last written value.
 
 use ahash;
+use serde::{Deserialize, Serialize};
 use std::collections::{hash_map, HashMap, HashSet};
 
 use crate::bitvector::BV;
 use crate::error::ExecError;
 use crate::ir::*;
-use crate::primop_util::{ite_choice, symbolic};
+use crate::primop_util::{ite_choice, symbolic, zero};
 use crate::smt::{Solver, Sym};
 use crate::source_loc::SourceLoc;
 use crate::zencode;
 
+/// What happens when a register is read before it has been
+/// initialized
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UninitPolicy {
+    /// Initialize the register with a fresh symbolic value
+    #[default]
+    Symbolic,
+    /// Initialize the register with zero (see [crate::primop_util::zero])
+    Zero,
+    /// Fail with [ExecError::UninitializedRegister]
+    Error,
+}
+
+impl UninitPolicy {
+    fn initial_value<B: BV>(
+        self,
+        ty: &Ty<Name>,
+        shared_state: &SharedState<B>,
+        solver: &mut Solver<B>,
+        info: SourceLoc,
+    ) -> Result<Val<B>, ExecError> {
+        match self {
+            UninitPolicy::Zero => zero(ty, shared_state, solver, info),
+            _ => symbolic(ty, shared_state, solver, info),
+        }
+    }
+}
+
 #[derive(Clone)]
 enum RelaxedVal<'ir, B> {
     Uninit(&'ir Ty<Name>),
@@ -97,6 +127,34 @@ pub struct Register<'ir, B> {
     value: RelaxedVal<'ir, B>,
 }
 
+/// The last written, last read, and older written values of an
+/// initialized register
+type InitVal<B> = (Val<B>, Option<Val<B>>, Vec<Val<B>>);
+
+/// A register without the type of its uninitialized value, which
+/// allows it to be serialized. The type must be provided again to
+/// turn it back into a [Register].
+#[derive(Serialize, Deserialize)]
+pub(crate) struct UntypedRegister<B> {
+    relaxed: bool,
+    value: Option<InitVal<B>>,
+}
+
+impl<B: BV> UntypedRegister<B> {
+    pub(crate) fn is_initialized(&self) -> bool {
+        self.value.is_some()
+    }
+
+    /// The type is only used if the register is uninitialized.
+    pub(crate) fn with_type<'ir>(self, ty: &'ir Ty<Name>) -> Register<'ir, B> {
+        let value = match self.value {
+            None => RelaxedVal::Uninit(ty),
+            Some((last_write, last_read, old_writes)) => RelaxedVal::Init { last_write, last_read, old_writes },
+        };
+        Register { relaxed: self.relaxed, value }
+    }
+}
+
 impl<'ir, B: BV> RelaxedVal<'ir, B> {
     fn unwrap_last_write(&self) -> &Val<B> {
         if let RelaxedVal::Init { last_write, .. } = self {
@@ -112,13 +170,14 @@ impl<'ir, B: BV> RelaxedVal<'ir, B> {
     // return the same value.
     fn read<'a>(
         &'a mut self,
+        policy: UninitPolicy,
         shared_state: &SharedState<'ir, B>,
         solver: &mut Solver<B>,
         info: SourceLoc,
     ) -> Result<&'a Val<B>, ExecError> {
         match self {
             RelaxedVal::Uninit(ty) => {
-                let sym = symbolic(ty, shared_state, solver, info)?;
+                let sym = policy.initial_value(ty, shared_state, solver, info)?;
                 *self = RelaxedVal::Init { last_write: sym.clone(), last_read: Some(sym), old_writes: Vec::new() };
                 Ok(self.unwrap_last_write())
             }
@@ -134,13 +193,14 @@ impl<'ir, B: BV> RelaxedVal<'ir, B> {
     // Read the last written value
     fn read_last<'a>(
         &'a mut self,
+        policy: UninitPolicy,
         shared_state: &SharedState<'ir, B>,
         solver: &mut Solver<B>,
         info: SourceLoc,
     ) -> Result<&'a Val<B>, ExecError> {
         match self {
             RelaxedVal::Uninit(ty) => {
-                let sym = symbolic(ty, shared_state, solver, info)?;
+                let sym = policy.initial_value(ty, shared_state, solver, info)?;
                 *self = RelaxedVal::Init { last_write: sym, last_read: None, old_writes: Vec::new() };
                 Ok(self.unwrap_last_write())
             }
@@ -195,14 +255,15 @@ impl<'ir, B: BV> RelaxedVal<'ir, B> {
 impl<'ir, B: BV> Register<'ir, B> {
     pub fn read<'a>(
         &'a mut self,
+        policy: UninitPolicy,
         shared_state: &SharedState<'ir, B>,
         solver: &mut Solver<B>,
         info: SourceLoc,
     ) -> Result<&'a Val<B>, ExecError> {
         if self.relaxed {
-            self.value.read(shared_state, solver, info)
+            self.value.read(policy, shared_state, solver, info)
         } else {
-            self.value.read_last(shared_state, solver, info)
+            self.value.read_last(policy, shared_state, solver, info)
         }
     }
 
@@ -246,6 +307,16 @@ impl<'ir, B: BV> Register<'ir, B> {
             self.value.forget_last_read()
         }
     }
+
+    pub(crate) fn untyped(&self) -> UntypedRegister<B> {
+        let value = match &self.value {
+            RelaxedVal::Uninit(_) => None,
+            RelaxedVal::Init { last_write, last_read, old_writes } => {
+                Some((last_write.clone(), last_read.clone(), old_writes.clone()))
+            }
+        };
+        UntypedRegister { relaxed: self.relaxed, value }
+    }
 }
 
 #[derive(Clone)]
@@ -284,15 +355,23 @@ impl<'ir, B: BV> RegisterBindings<'ir, B> {
         self.map.insert(id, v);
     }
 
+    /// Read a register, initializing it according to `policy` if it
+    /// has not been written to or read from yet.
     pub fn get<'a>(
         &'a mut self,
         id: Name,
+        policy: UninitPolicy,
         shared_state: &SharedState<'ir, B>,
         solver: &mut Solver<B>,
         info: SourceLoc,
     ) -> Result<Option<&'a Val<B>>, ExecError> {
         if let Some(reg) = self.map.get_mut(&id) {
-            let val = reg.read(shared_state, solver, info)?;
+            if policy == UninitPolicy::Error && matches!(reg.value, RelaxedVal::Uninit(_)) {
+                return Err(ExecError::UninitializedRegister(zencode::decode(shared_state.symtab.to_str(id)), info));
+            }
+            let val = reg.read(policy, shared_state, solver, info).map_err(|err| {
+                err.initializing(|| format!("register {}", zencode::decode(shared_state.symtab.to_str(id))))
+            })?;
             Ok(Some(val))
         } else {
             Ok(None)
@@ -40,7 +40,7 @@ use crate::bitvector::{write_bits64, BV};
 use crate::ir::{BitsSegment, Loc, Name, SharedState, Symtab, Val, HAVE_EXCEPTION};
 use crate::smt::smtlib::{self, *};
 use crate::smt::Event::*;
-use crate::smt::{Accessor, Event, Sym};
+use crate::smt::{Accessor, Event, Origin, Sym};
 use crate::source_loc::SourceLoc;
 use crate::zencode;
 
@@ -89,7 +89,7 @@ where
                 renumber_val(v, f);
             }
         }
-        Cycle | MarkReg { .. } | Function { .. } | Assume(_) => (),
+        Cycle | MarkReg { .. } | Function { .. } | Assume(_) | OneSidedJump(..) | SourceLine(_) => (),
     }
 }
 
@@ -424,6 +424,7 @@ fn calculate_more_uses<B, E: Borrow<Event<B>>>(events: &[E], uses: &mut HashMap<
             Fork(_, sym, _, _) => {
                 uses.insert(*sym, uses.get(sym).unwrap_or(&0) + 1);
             }
+            OneSidedJump(..) | SourceLine(_) => (),
             Cycle => (),
             Instr(val) => uses_in_value(uses, val),
             MarkReg { .. } => (),
@@ -504,6 +505,7 @@ fn calculate_required_uses<B, E: Borrow<Event<B>>>(events: &[E]) -> HashMap<Sym,
             Fork(_, sym, _, _) => {
                 uses.insert(*sym, uses.get(sym).unwrap_or(&0) + 1);
             }
+            OneSidedJump(..) | SourceLine(_) => (),
             Cycle => (),
             Instr(val) => uses_in_value(&mut uses, val),
             MarkReg { .. } => (),
@@ -821,6 +823,71 @@ pub fn remove_unused_tree<B: BV>(event_tree: &mut EventTree<B>) {
     }
 }
 
+/// The events a memory model cares about by default when pruning a
+/// trace with [prune_unreachable]: memory events, branches and
+/// instruction boundaries.
+pub fn is_memory_root<B: BV>(event: &Event<B>) -> bool {
+    use Event::*;
+    matches!(
+        event,
+        ReadMem { .. }
+            | WriteMem { .. }
+            | Branch { .. }
+            | AddressAnnounce { .. }
+            | Fork(..)
+            | OneSidedJump(..)
+            | Cycle
+            | Instr(_)
+            | Abstract { .. }
+    )
+}
+
+/// Removes every event that is not a root according to `is_root`,
+/// along with every SMT definition or assertion which is not
+/// transitively referenced by a root. Unlike [remove_unused], which
+/// keeps every non-SMT event and assertion, this can remove register
+/// initialization that is irrelevant to the roots.
+pub fn prune_unreachable<B: BV, E: Borrow<Event<B>>, F: Fn(&Event<B>) -> bool>(events: &mut Vec<E>, is_root: F) {
+    let mut reachable: HashMap<Sym, u32> = HashMap::new();
+    let roots: Vec<&Event<B>> = events.iter().map(Borrow::borrow).filter(|event| is_root(event)).collect();
+    calculate_more_uses(&roots, &mut reachable);
+
+    // An assertion constrains every symbol it mentions, so if any one
+    // of them is reachable the others become reachable too
+    loop {
+        let before = reachable.len();
+        for event in events.iter().rev() {
+            match event.borrow() {
+                Smt(Def::DefineConst(v, exp), _, _) if reachable.contains_key(v) => uses_in_exp(&mut reachable, exp),
+                Smt(Def::Assert(exp), _, _) => {
+                    let mut uses = HashMap::new();
+                    uses_in_exp(&mut uses, exp);
+                    if uses.keys().any(|v| reachable.contains_key(v)) {
+                        reachable.extend(uses)
+                    }
+                }
+                _ => (),
+            }
+        }
+        if reachable.len() == before {
+            break;
+        }
+    }
+
+    events.retain(|event| match event.borrow() {
+        Smt(Def::DeclareConst(v, _), _, _)
+        | Smt(Def::DeclareFun(v, _, _), _, _)
+        | Smt(Def::DefineConst(v, _), _, _) => reachable.contains_key(v),
+        Smt(Def::Assert(exp), _, _) => {
+            let mut uses = HashMap::new();
+            uses_in_exp(&mut uses, exp);
+            uses.keys().any(|v| reachable.contains_key(v))
+        }
+        Smt(Def::DefineEnum(..), _, _) => true,
+        event => is_root(event),
+    })
+}
+
 fn propagate_forwards_used_once_core<B: BV, E: BorrowMut<Event<B>>>(
     rev: bool,
     cross_segment: &HashSet<Sym>,
@@ -1626,6 +1693,8 @@ pub fn write_events_in_context<B: BV>(
                 write!(buf, "\n{}  (branch {} \"{}\")", indent, n, loc.location_string(symtab.files()))
             }
 
+            SourceLine(loc) => write!(buf, "\n{}  (source-line \"{}\")", indent, loc.line_string(symtab.files())),
+
             Function { name, call } => {
                 let name = zencode::decode(symtab.to_str(*name));
                 if *call {
@@ -1762,7 +1831,8 @@ pub fn write_events_in_context<B: BV>(
                 Ok(())
             }
 
-            Smt(..) => Ok(()),
+            // Only used for coverage, so not part of the written trace
+            Smt(..) | OneSidedJump(..) => Ok(()),
 
             ReadMem { value, read_kind, address, bytes, tag_value, opts: _, region: _ } => {
                 write!(buf, "\n{}  (read-mem ", indent)?;
@@ -1892,6 +1962,35 @@ pub fn write_events<B: BV>(buf: &mut dyn Write, events: &[Event<B>], shared_stat
     write_events_with_opts(buf, events, shared_state, &WriteOpts::default()).unwrap()
 }
 
+/// Write the origins of the assertions which make a path dead, as
+/// found by [crate::smt::Solver::unsat_core]
+pub fn write_unsat_core<B: BV>(
+    buf: &mut dyn Write,
+    core: &[Origin],
+    shared_state: &SharedState<B>,
+) -> std::io::Result<()> {
+    let opts = WriteOpts::default();
+    let files = shared_state.symtab.files();
+    write!(buf, "(unsat-core")?;
+    for origin in core {
+        match origin {
+            Origin::Initial(exp) => {
+                write!(buf, "\n  (initial ")?;
+                write_exp(buf, exp, shared_state, &opts)?;
+                write!(buf, ")")?
+            }
+            Origin::Branch(taken, info) => write!(buf, "\n  (branch {} \"{}\")", taken, info.location_string(files))?,
+            Origin::Fork(branch, info) => write!(buf, "\n  (fork {} \"{}\")", branch, info.location_string(files))?,
+            Origin::Assert(exp, info) => {
+                write!(buf, "\n  (assert ")?;
+                write_exp(buf, exp, shared_state, &opts)?;
+                write!(buf, " \"{}\")", info.location_string(files))?
+            }
+        }
+    }
+    write!(buf, ")")
+}
+
 fn write_event_tree_with_opts<B: BV>(
     buf: &mut dyn Write,
     evtree: &EventTree<B>,
@@ -2047,6 +2146,51 @@ mod tests {
         assert_eq!(evtree.forks[1].prefix.len(), 3);
     }
 
+    #[test]
+    fn prune_register_initialization() {
+        use crate::smt::{DefAttrs, ReadOpts};
+        let smt = |def| Event::Smt(def, DefAttrs::default(), SourceLoc::unknown());
+        let v = Sym::from_u32;
+
+        // initialize and read ten registers, then read memory at an
+        // address computed from the first (events are in reverse order)
+        let mut events: Vec<Event<B64>> = vec![Event::ReadMem {
+            value: Val::Symbolic(v(12)),
+            read_kind: Val::Bool(false),
+            address: Val::Symbolic(v(11)),
+            bytes: 8,
+            tag_value: None,
+            opts: ReadOpts::default(),
+            region: "default",
+        }];
+        events.push(smt(Def::DeclareConst(v(12), Ty::BitVec(64))));
+        events.push(smt(Def::DefineConst(v(11), Exp::Bvadd(Box::new(Exp::Var(v(0))), Box::new(Exp::Var(v(10)))))));
+        events.push(smt(Def::DeclareConst(v(10), Ty::BitVec(64))));
+        for i in 0..10 {
+            events.push(Event::ReadReg(Name::from_u32(i), vec![], Val::Symbolic(v(i))));
+            events.push(smt(Def::Assert(Exp::Eq(Box::new(Exp::Var(v(i))), Box::new(Exp::Bits64(B64::from_u64(0)))))));
+            events.push(smt(Def::DeclareConst(v(i), Ty::BitVec(64))));
+        }
+        assert_eq!(events.len(), 34);
+
+        // remove_unused keeps every register read, so can't remove anything
+        let mut unused_removed = events.clone();
+        remove_unused(&mut unused_removed);
+        assert_eq!(unused_removed.len(), 34);
+
+        // only the memory read, its address and value, and the
+        // definition and initial value of the first register remain
+        let mut pruned = events.clone();
+        prune_unreachable(&mut pruned, is_memory_root);
+        assert_eq!(pruned.len(), 6);
+        assert!(!pruned.iter().any(Event::is_reg));
+
+        // the register events can be kept as roots too
+        let mut pruned = events;
+        prune_unreachable(&mut pruned, |event| is_memory_root(event) || event.is_reg());
+        assert_eq!(pruned.len(), 34);
+    }
+
     #[test]
     fn remove_repeated_regs() {
         let event = Event::ReadReg(Name::from_u32(0), vec![], Val::Bits(B64::from_u64(0x123)));
@@ -50,9 +50,7 @@ use petgraph::{
 
 use z3_sys::*;
 
-#[cfg(feature = "smtperf")]
-use std::time::{Duration, Instant};
-
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::convert::TryInto;
 use std::error::Error;
@@ -62,7 +60,9 @@ use std::io::Write;
 use std::mem;
 use std::path::Path;
 use std::ptr;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use crate::bitvector::b64::B64;
 use crate::bitvector::BV;
@@ -73,7 +73,7 @@ use crate::zencode;
 
 /// A newtype wrapper for symbolic variables, which are `u32` under
 /// the hood.
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Sym {
     pub(crate) id: u32,
 }
@@ -96,12 +96,12 @@ impl fmt::Display for Sym {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct EnumId {
     id: Name,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct EnumMember {
     pub enum_id: EnumId,
     pub member: usize,
@@ -122,7 +122,10 @@ impl EnumId {
     }
 }
 
+pub mod arena;
+pub mod backend;
 pub mod smtlib;
+use backend::{Backend, SolverBackend};
 use smtlib::*;
 
 /// Snapshot of interaction with underlying solver that can be
@@ -144,6 +147,36 @@ impl<B> Checkpoint<B> {
     }
 }
 
+impl<B: BV> Checkpoint<B> {
+    /// The events needed to rebuild the solver state at this
+    /// checkpoint, in the order they were added
+    pub fn events(&self) -> Vec<&Event<B>> {
+        match &*self.trace {
+            Some(trace) => {
+                let mut events = trace.to_vec();
+                events.reverse();
+                events
+            }
+            None => Vec::new(),
+        }
+    }
+
+    pub fn next_var(&self) -> u32 {
+        self.next_var
+    }
+
+    /// Create a checkpoint which replays the given events, for example
+    /// as returned by [Checkpoint::events].
+    pub fn from_events(next_var: u32, events: Vec<Event<B>>) -> Self {
+        if events.is_empty() {
+            Checkpoint { num: 0, next_var, trace: Arc::new(None) }
+        } else {
+            let trace = Trace { checkpoints: 0, head: events, tail: Arc::new(None) };
+            Checkpoint { num: 1, next_var, trace: Arc::new(Some(trace)) }
+        }
+    }
+}
+
 /// For the concurrency models, register accesses must be logged at a
 /// subfield level granularity (e.g. for PSTATE in ARM ASL), which is
 /// what the Accessor type is for.
@@ -167,7 +200,7 @@ impl Accessor {
     }
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct ReadOpts {
     pub is_exclusive: bool,
     pub is_ifetch: bool,
@@ -183,7 +216,7 @@ impl ReadOpts {
     }
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct WriteOpts {
     is_exclusive: bool,
 }
@@ -194,7 +227,7 @@ impl WriteOpts {
     }
 }
 
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
 pub struct DefAttrs {
     attrs: u8,
 }
@@ -214,13 +247,79 @@ impl DefAttrs {
     pub fn is_uninteresting(self) -> bool {
         self.attrs & 1 > 0
     }
+
+    /// An 'initial' definition was given to the task before it
+    /// started, rather than being made while executing it. This is
+    /// used to explain dead paths, see [Solver::unsat_core].
+    pub fn initial() -> Self {
+        DefAttrs { attrs: 2 }
+    }
+
+    pub fn is_initial(self) -> bool {
+        self.attrs & 2 > 0
+    }
+}
+
+/// Where an assertion in the trace came from, as reported by
+/// [Solver::unsat_core]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Origin {
+    /// An assertion given to the task before it started, e.g. a
+    /// constraint on the initial value of a register
+    Initial(Exp<Sym>),
+    /// Taking one direction of a jump on a symbolic condition: whether
+    /// the jump was taken, and the location of the jump
+    Branch(bool, SourceLoc),
+    /// Following a path of some other fork, such as a monomorphize
+    /// split: the branch number, and the location of the fork
+    Fork(u32, SourceLoc),
+    /// Any other assertion, with the location it was made at
+    Assert(Exp<Sym>, SourceLoc),
+}
+
+/// Memory region names in events are static strings, so when events
+/// are deserialized each distinct region name is leaked once. The
+/// alias stops serde assuming the name is borrowed from the input.
+type RegionName = &'static str;
+
+mod region_name {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::collections::HashSet;
+    use std::sync::Mutex;
+
+    static NAMES: Mutex<Option<HashSet<&'static str>>> = Mutex::new(None);
+
+    pub fn serialize<S: Serializer>(name: &&'static str, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(name)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<&'static str, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        let mut names = NAMES.lock().unwrap();
+        let names = names.get_or_insert_with(HashSet::new);
+        if let Some(interned) = names.get(name.as_str()) {
+            Ok(interned)
+        } else {
+            let interned: &'static str = Box::leak(name.into_boxed_str());
+            names.insert(interned);
+            Ok(interned)
+        }
+    }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Event<B> {
     Smt(Def, DefAttrs, SourceLoc),
     /// Fork ID, assertion, branch number, source location
     Fork(u32, Sym, u32, SourceLoc),
+    /// A jump on a symbolic condition which only one direction was
+    /// possible for, so there was no fork: whether the jump was taken,
+    /// source location
+    OneSidedJump(bool, SourceLoc),
+    /// Execution reached a new line of the source, only recorded when
+    /// a task traces source lines (see
+    /// `LocalFrame::set_trace_source_lines`)
+    SourceLine(SourceLoc),
     /// Used to delimit function calls and returns in the trace
     Function {
         name: Name,
@@ -243,7 +342,8 @@ pub enum Event<B> {
         bytes: u32,
         tag_value: Option<Val<B>>,
         opts: ReadOpts,
-        region: &'static str,
+        #[serde(with = "region_name")]
+        region: RegionName,
     },
     WriteMem {
         value: Sym,
@@ -253,7 +353,8 @@ pub enum Event<B> {
         bytes: u32,
         tag_value: Option<Val<B>>,
         opts: WriteOpts,
-        region: &'static str,
+        #[serde(with = "region_name")]
+        region: RegionName,
     },
     MarkReg {
         regs: Vec<Name>,
@@ -313,6 +414,30 @@ impl<B: BV> Event<B> {
         }
     }
 
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Event::Smt(..) => EventKind::Smt,
+            Event::Fork(..) => EventKind::Fork,
+            Event::OneSidedJump(..) => EventKind::OneSidedJump,
+            Event::SourceLine(_) => EventKind::SourceLine,
+            Event::Function { name, call } => EventKind::Function { name: *name, call: *call },
+            Event::Abstract { name, primitive, .. } => EventKind::Abstract { name: *name, primitive: *primitive },
+            Event::ReadReg(name, _, _) => EventKind::ReadReg(*name),
+            Event::WriteReg(name, _, _) => EventKind::WriteReg(*name),
+            Event::AssumeReg(name, _, _) => EventKind::AssumeReg(*name),
+            Event::ReadMem { opts, .. } => EventKind::ReadMem { ifetch: opts.is_ifetch, exclusive: opts.is_exclusive },
+            Event::WriteMem { opts, .. } => EventKind::WriteMem { exclusive: opts.is_exclusive },
+            Event::MarkReg { .. } => EventKind::MarkReg,
+            Event::AddressAnnounce { .. } => EventKind::AddressAnnounce,
+            Event::Branch { .. } => EventKind::Branch,
+            Event::Cycle => EventKind::Cycle,
+            Event::Instr(_) => EventKind::Instr,
+            Event::Assume(_) => EventKind::Assume,
+            Event::AssumeFun { name, .. } => EventKind::AssumeFun { name: *name },
+            Event::UseFunAssumption { name, .. } => EventKind::UseFunAssumption { name: *name },
+        }
+    }
+
     pub fn is_smt(&self) -> bool {
         matches!(self, Event::Smt(..))
     }
@@ -416,6 +541,46 @@ impl<B: BV> Event<B> {
     }
 }
 
+/// A description of an [Event] without its values, which an
+/// [EventFilter] uses to decide whether to record the event
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EventKind {
+    Smt,
+    Fork,
+    OneSidedJump,
+    SourceLine,
+    Function { name: Name, call: bool },
+    Abstract { name: Name, primitive: bool },
+    ReadReg(Name),
+    WriteReg(Name),
+    AssumeReg(Name),
+    ReadMem { ifetch: bool, exclusive: bool },
+    WriteMem { exclusive: bool },
+    MarkReg,
+    AddressAnnounce,
+    Branch,
+    Cycle,
+    Instr,
+    Assume,
+    AssumeFun { name: Name },
+    UseFunAssumption { name: Name },
+}
+
+impl EventKind {
+    /// Events of these kinds are recorded regardless of any
+    /// [EventFilter], as the solver, forks, and later analysis of the
+    /// trace rely on them
+    pub fn is_always_recorded(self) -> bool {
+        use EventKind::*;
+        matches!(self, Smt | Fork | OneSidedJump | Function { .. } | Branch | Cycle)
+    }
+}
+
+/// A predicate which decides whether an event is recorded in the
+/// trace when it is added to a [Solver], see
+/// [Solver::set_event_filter]
+pub type EventFilter = Arc<dyn Fn(EventKind) -> bool + Send + Sync>;
+
 /// turn a (Read|Write)Reg event
 /// into a human-readable string like
 /// "ESR_EL1.ISS"
@@ -469,6 +634,46 @@ impl<B: BV> Trace<B> {
         Checkpoint { num: self.checkpoints, trace: tail, next_var }
     }
 
+    /// The number of events in the trace, including those shared with
+    /// earlier checkpoints
+    pub fn len(&self) -> usize {
+        let mut len = self.head.len();
+        let mut current_tail = self.tail.as_ref();
+        while let Some(trace) = current_tail {
+            len += trace.head.len();
+            current_tail = trace.tail.as_ref();
+        }
+        len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The last `n` events in the trace, most recent first. Unlike
+    /// [Trace::to_vec] this only visits as much of the trace as needed.
+    pub fn recent<'a>(&'a self, n: usize) -> Vec<&'a Event<B>> {
+        let mut vec: Vec<&'a Event<B>> = Vec::new();
+
+        let mut current_head = &self.head;
+        let mut current_tail = self.tail.as_ref();
+        loop {
+            for def in current_head.iter().rev() {
+                if vec.len() == n {
+                    return vec;
+                }
+                vec.push(def)
+            }
+            match current_tail {
+                Some(trace) if vec.len() < n => {
+                    current_head = &trace.head;
+                    current_tail = trace.tail.as_ref();
+                }
+                _ => return vec,
+            }
+        }
+    }
+
     pub fn to_vec<'a>(&'a self) -> Vec<&'a Event<B>> {
         let mut vec: Vec<&'a Event<B>> = Vec::new();
 
@@ -490,15 +695,88 @@ impl<B: BV> Trace<B> {
 }
 
 /// Config is a wrapper around the `Z3_config` type from the C
-/// API. `Z3_del_config` is called when it is dropped.
+/// API. `Z3_del_config` is called when it is dropped. It also
+/// chooses which solver decides satisfiability, see [backend].
 pub struct Config {
     z3_cfg: Z3_config,
+    backend: Backend,
+    timeout: Option<u32>,
+    unsat_cores: bool,
 }
 
 impl Config {
+    /// Create a configuration using the default backend, timeout, and
+    /// resource limit, see [set_default_timeout] and
+    /// [set_default_rlimit]
     pub fn new() -> Self {
-        unsafe { Config { z3_cfg: Z3_mk_config() } }
+        let mut cfg = unsafe {
+            Config { z3_cfg: Z3_mk_config(), backend: backend::default_backend(), timeout: None, unsat_cores: false }
+        };
+        let timeout = DEFAULT_TIMEOUT.load(Ordering::Relaxed);
+        if timeout != 0 {
+            cfg.set_timeout(Some(timeout))
+        }
+        let rlimit = DEFAULT_RLIMIT.load(Ordering::Relaxed);
+        if rlimit != 0 {
+            cfg.set_rlimit(Some(rlimit))
+        }
+        cfg
+    }
+
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.backend = backend
+    }
+
+    /// Limit how long each satisfiability check may take, in
+    /// milliseconds. Checks which run out of time return
+    /// [SmtResult::Unknown].
+    pub fn set_timeout(&mut self, millis: Option<u32>) {
+        self.timeout = millis;
+        self.set_param_value("timeout", &millis.unwrap_or(u32::MAX).to_string())
     }
+
+    /// Limit the resources (in Z3's own units, which unlike time are
+    /// deterministic) each satisfiability check may use. Checks which
+    /// run out return [SmtResult::Unknown]. Only Z3 supports this.
+    pub fn set_rlimit(&mut self, rlimit: Option<u32>) {
+        self.set_param_value("rlimit", &rlimit.unwrap_or(0).to_string())
+    }
+
+    /// Track the labels of assertions made with [Solver::add_named],
+    /// so [Solver::unsat_core_labels] can report them. This is off by
+    /// default as it makes solving slower.
+    pub fn set_unsat_cores(&mut self, enabled: bool) {
+        self.unsat_cores = enabled;
+        self.set_param_value("unsat_core", if enabled { "true" } else { "false" })
+    }
+}
+
+/// Zero means no limit for both of these
+static DEFAULT_TIMEOUT: AtomicU32 = AtomicU32::new(0);
+static DEFAULT_RLIMIT: AtomicU32 = AtomicU32::new(0);
+
+/// Set the timeout used by configurations created after this, see
+/// [Config::set_timeout]
+pub fn set_default_timeout(millis: Option<u32>) {
+    DEFAULT_TIMEOUT.store(millis.unwrap_or(0), Ordering::Relaxed)
+}
+
+/// Set the resource limit used by configurations created after this,
+/// see [Config::set_rlimit]
+pub fn set_default_rlimit(rlimit: Option<u32>) {
+    DEFAULT_RLIMIT.store(rlimit.unwrap_or(0), Ordering::Relaxed)
+}
+
+/// Zero means slow queries are not dumped
+static SLOW_QUERY_THRESHOLD: AtomicU32 = AtomicU32::new(0);
+static SLOW_QUERY_DUMPS: AtomicU32 = AtomicU32::new(0);
+
+/// Whenever a satisfiability check takes at least `millis`
+/// milliseconds, write the problem to
+/// `isla_slow_query_<pid>_<n>.smt2` in the current directory, see
+/// [Solver::dump_smtlib]
+pub fn set_slow_query_dump(millis: Option<u32>) {
+    SLOW_QUERY_THRESHOLD.store(millis.unwrap_or(0), Ordering::Relaxed)
 }
 
 impl Drop for Config {
@@ -530,11 +808,25 @@ pub fn global_set_param_value(id: &str, value: &str) {
 /// Context is a wrapper around `Z3_context`.
 pub struct Context {
     z3_ctx: Z3_context,
+    backend: Backend,
+    timeout: Option<u32>,
+    unsat_cores: bool,
 }
 
 impl Context {
     pub fn new(cfg: Config) -> Self {
-        unsafe { Context { z3_ctx: Z3_mk_context_rc(cfg.z3_cfg) } }
+        unsafe {
+            Context {
+                z3_ctx: Z3_mk_context_rc(cfg.z3_cfg),
+                backend: cfg.backend,
+                timeout: cfg.timeout,
+                unsat_cores: cfg.unsat_cores,
+            }
+        }
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.backend
     }
 
     fn error(&self) -> ExecError {
@@ -601,19 +893,30 @@ impl<'ctx> Enums<'ctx> {
             self.enums.insert(name, Enum { sort, consts, testers });
         }
     }
-}
 
-impl<'ctx> Drop for Enums<'ctx> {
-    fn drop(&mut self) {
+    fn remove_enum(&mut self, name: Name) {
+        if let Some(e) = self.enums.remove(&name) {
+            self.release(e)
+        }
+    }
+
+    fn release(&self, e: Enum) {
         unsafe {
             let ctx = self.ctx.z3_ctx;
-            for (_, e) in self.enums.drain() {
-                for i in 0..e.consts.len() {
-                    Z3_dec_ref(ctx, Z3_func_decl_to_ast(ctx, e.consts[i]));
-                    Z3_dec_ref(ctx, Z3_func_decl_to_ast(ctx, e.testers[i]))
-                }
-                Z3_dec_ref(ctx, Z3_sort_to_ast(ctx, e.sort))
+            for i in 0..e.consts.len() {
+                Z3_dec_ref(ctx, Z3_func_decl_to_ast(ctx, e.consts[i]));
+                Z3_dec_ref(ctx, Z3_func_decl_to_ast(ctx, e.testers[i]))
             }
+            Z3_dec_ref(ctx, Z3_sort_to_ast(ctx, e.sort))
+        }
+    }
+}
+
+impl<'ctx> Drop for Enums<'ctx> {
+    fn drop(&mut self) {
+        let enums: Vec<Enum> = self.enums.drain().map(|(_, e)| e).collect();
+        for e in enums {
+            self.release(e)
         }
     }
 }
@@ -1452,6 +1755,39 @@ pub struct Solver<'ctx, B> {
     z3_solver: Z3_solver,
     ctx: &'ctx Context,
     performance_info: PerformanceInfo,
+    /// The number of satisfiability checks made
+    queries: u64,
+    /// Literal values of variables implied by the definitions and
+    /// assertions added so far, see [Solver::known_value]
+    known: HashMap<Sym, Exp<Sym>>,
+    concrete_fast_path: bool,
+    /// Whether a check since the last assertion found the assertions
+    /// satisfiable, see [Solver::known_sat]
+    known_sat: bool,
+    /// Events this rejects are not recorded, see
+    /// [Solver::set_event_filter]
+    event_filter: Option<EventFilter>,
+    /// If set, this decides satisfiability rather than `z3_solver`
+    backend: Option<RefCell<Box<dyn SolverBackend>>>,
+    /// Why the backend failed, see [Solver::backend_error]
+    backend_error: Option<ExecError>,
+    /// The scopes opened by [Solver::push], innermost last
+    scopes: Vec<Scope>,
+    /// The literals tracking assertions made with
+    /// [Solver::add_named], and their labels
+    labels: Vec<(Ast<'ctx>, String)>,
+}
+
+/// The state of a [Solver] when a scope was opened, which closing it
+/// restores
+struct Scope {
+    trace_len: usize,
+    known: HashMap<Sym, Exp<Sym>>,
+    known_sat: bool,
+    cycles: i128,
+    /// Enumerations first defined inside the scope
+    enums: Vec<Name>,
+    labels_len: usize,
 }
 
 impl<'ctx, B> Drop for Solver<'ctx, B> {
@@ -1494,8 +1830,10 @@ pub struct Model<'ctx, B> {
 
 impl<'ctx, B> Drop for Model<'ctx, B> {
     fn drop(&mut self) {
-        unsafe {
-            Z3_model_dec_ref(self.ctx.z3_ctx, self.z3_model);
+        if !self.z3_model.is_null() {
+            unsafe {
+                Z3_model_dec_ref(self.ctx.z3_ctx, self.z3_model);
+            }
         }
     }
 }
@@ -1504,6 +1842,9 @@ impl<'ctx, B> Drop for Model<'ctx, B> {
 // variable names (albeit with the same numbers that appear in the trace).
 impl<'ctx, B> fmt::Debug for Model<'ctx, B> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.z3_model.is_null() {
+            return write!(f, "<{:?} model>", self.ctx.backend);
+        }
         unsafe {
             let z3_string = CStr::from_ptr(Z3_model_to_string(self.ctx.z3_ctx, self.z3_model));
             write!(f, "{}", z3_string.to_string_lossy())
@@ -1513,6 +1854,9 @@ impl<'ctx, B> fmt::Debug for Model<'ctx, B> {
 
 impl<'ctx, B: BV> Model<'ctx, B> {
     pub fn new(solver: &'ctx Solver<'ctx, B>) -> Self {
+        if solver.backend.is_some() {
+            return Model { z3_model: ptr::null_mut(), solver, ctx: solver.ctx, complete_model: false };
+        }
         unsafe {
             let z3_model = Z3_solver_get_model(solver.ctx.z3_ctx, solver.z3_solver);
             Z3_model_inc_ref(solver.ctx.z3_ctx, z3_model);
@@ -1557,10 +1901,16 @@ impl<'ctx, B: BV> Model<'ctx, B> {
             None => return Err(ExecError::Type(format!("Unbound variable {:?}", &var), SourceLoc::unknown())),
             Some(ast) => ast.clone(),
         };
+        if let Some(backend) = &self.solver.backend {
+            return backend.borrow_mut().get_value(&Exp::Var(var), self.complete_model);
+        }
         self.get_ast(var_ast)
     }
 
     pub fn get_exp(&mut self, exp: &Exp<Sym>) -> Result<Option<Exp<Sym>>, ExecError> {
+        if let Some(backend) = &self.solver.backend {
+            return backend.borrow_mut().get_value(exp, self.complete_model);
+        }
         let ast = self.solver.translate_exp(exp);
         self.get_ast(ast)
     }
@@ -1624,6 +1974,42 @@ impl<'ctx, B: BV> Model<'ctx, B> {
     }
 }
 
+/// A value of a variable in a model, see [Solver::get_model]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ModelVal {
+    Bool(bool),
+    /// A bitvector of at most 64 bits
+    Bits64(B64),
+    /// A wider bitvector, least significant bit first
+    Bits(Vec<bool>),
+    Enum(EnumMember),
+}
+
+impl ModelVal {
+    fn from_exp(exp: Exp<Sym>) -> Option<Self> {
+        match exp {
+            Exp::Bool(b) => Some(ModelVal::Bool(b)),
+            Exp::Bits64(bv) => Some(ModelVal::Bits64(bv)),
+            Exp::Bits(bv) if bv.len() <= 64 => {
+                let n = bv.iter().rev().fold(0, |n, bit| (n << 1) | (*bit as u64));
+                Some(ModelVal::Bits64(B64::new(n, bv.len() as u32)))
+            }
+            Exp::Bits(bv) => Some(ModelVal::Bits(bv)),
+            Exp::Enum(e) => Some(ModelVal::Enum(e)),
+            _ => None,
+        }
+    }
+
+    pub fn to_exp(&self) -> Exp<Sym> {
+        match self {
+            ModelVal::Bool(b) => Exp::Bool(*b),
+            ModelVal::Bits64(bv) => Exp::Bits64(*bv),
+            ModelVal::Bits(bv) => Exp::Bits(bv.clone()),
+            ModelVal::Enum(e) => Exp::Enum(*e),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum SmtResult {
     Sat,
@@ -1656,6 +2042,23 @@ impl SmtResult {
 }
 
 static QFAUFBV_STR: &[u8] = b"qfaufbv\0";
+static TIMEOUT_STR: &[u8] = b"timeout\0";
+static RLIMIT_STR: &[u8] = b"rlimit\0";
+
+fn is_literal(exp: &Exp<Sym>) -> bool {
+    matches!(exp, Exp::Bits(_) | Exp::Bits64(_) | Exp::Bool(_) | Exp::Enum(_))
+}
+
+/// Compare two literals, if they have the same representation
+fn literal_eq(lhs: &Exp<Sym>, rhs: &Exp<Sym>) -> Option<bool> {
+    match (lhs, rhs) {
+        (Exp::Bits(lhs), Exp::Bits(rhs)) => Some(lhs == rhs),
+        (Exp::Bits64(lhs), Exp::Bits64(rhs)) => Some(lhs == rhs),
+        (Exp::Bool(lhs), Exp::Bool(rhs)) => Some(lhs == rhs),
+        (Exp::Enum(lhs), Exp::Enum(rhs)) => Some(lhs == rhs),
+        _ => None,
+    }
+}
 
 impl<'ctx, B: BV> Solver<'ctx, B> {
     pub fn new(ctx: &'ctx Context) -> Self {
@@ -1669,9 +2072,31 @@ impl<'ctx, B: BV> Solver<'ctx, B> {
             // The QF_AUFBV solver has good performance on our problems, but we need to initialise it
             // using a tactic rather than the logic name to ensure that the enumerations are supported,
             // otherwise Z3 may crash.
-            let qfaufbv_tactic = Z3_mk_tactic(ctx.z3_ctx, CStr::from_bytes_with_nul_unchecked(QFAUFBV_STR).as_ptr());
-            Z3_tactic_inc_ref(ctx.z3_ctx, qfaufbv_tactic);
-            let z3_solver = Z3_mk_solver_from_tactic(ctx.z3_ctx, qfaufbv_tactic);
+            // Solvers created from tactics cannot produce unsat cores
+            let z3_solver = if ctx.unsat_cores {
+                Z3_mk_simple_solver(ctx.z3_ctx)
+            } else {
+                let qfaufbv_tactic =
+                    Z3_mk_tactic(ctx.z3_ctx, CStr::from_bytes_with_nul_unchecked(QFAUFBV_STR).as_ptr());
+                Z3_tactic_inc_ref(ctx.z3_ctx, qfaufbv_tactic);
+                Z3_mk_solver_from_tactic(ctx.z3_ctx, qfaufbv_tactic)
+            };
+            let mut solver = Solver::from_z3_solver(ctx, z3_solver);
+            match ctx.backend.start() {
+                Ok(backend) => solver.backend = backend.map(RefCell::new),
+                Err(err) => solver.backend_error = Some(err),
+            }
+            if ctx.timeout.is_some() {
+                solver.set_query_timeout(ctx.timeout)
+            }
+            solver
+        }
+    }
+
+    /// Take ownership of `z3_solver`, which must have been created in
+    /// `ctx` with no other references to it
+    fn from_z3_solver(ctx: &'ctx Context, z3_solver: Z3_solver) -> Self {
+        unsafe {
             Z3_solver_inc_ref(ctx.z3_ctx, z3_solver);
 
             Solver {
@@ -1685,6 +2110,15 @@ impl<'ctx, B: BV> Solver<'ctx, B> {
                 func_decls: HashMap::new(),
                 enums: Enums::new(ctx),
                 performance_info: PerformanceInfo::new(),
+                queries: 0,
+                known: HashMap::new(),
+                concrete_fast_path: true,
+                known_sat: false,
+                event_filter: None,
+                backend: None,
+                backend_error: None,
+                scopes: Vec::new(),
+                labels: Vec::new(),
             }
         }
     }
@@ -1872,12 +2306,18 @@ impl<'ctx, B: BV> Solver<'ctx, B> {
                         }
                     }
                 }
-                self.z3_assert(exp)
+                self.learn_assertion(exp);
+                self.known_sat = false;
+                match &self.backend {
+                    Some(_) => self.add_to_backend(def),
+                    None => self.z3_assert(exp),
+                }
             }
             Def::DeclareConst(v, ty) => {
                 self.performance_info.add_var_node(*v, info);
                 let fd = FuncDecl::new(self.ctx, *v, &self.enums, &[], ty);
                 self.decls.insert(*v, Ast::mk_constant(&fd));
+                self.add_to_backend(def)
             }
             Def::DeclareFun(v, arg_tys, result_ty) => {
                 if cfg!(feature = "smtperf") {
@@ -1885,6 +2325,7 @@ impl<'ctx, B: BV> Solver<'ctx, B> {
                 }
                 let fd = FuncDecl::new(self.ctx, *v, &self.enums, arg_tys, result_ty);
                 self.func_decls.insert(*v, fd);
+                self.add_to_backend(def)
             }
             Def::DefineConst(v, exp) => {
                 if cfg!(feature = "smtperf") {
@@ -1894,20 +2335,155 @@ impl<'ctx, B: BV> Solver<'ctx, B> {
                         self.performance_info.add_var_edge(used, v)
                     }
                 }
+                if let Some(value) = self.known_literal(exp) {
+                    self.known.insert(*v, value);
+                }
                 let mut ast = self.translate_exp(exp);
                 ast.simplify();
                 self.decls.insert(*v, ast);
+                self.add_to_backend(def)
             }
             Def::DefineEnum(name, size) => {
                 if !self.enums.enums.contains_key(name) {
                     let z3_name = self.fresh();
                     let members: Vec<Sym> = (0..*size).map(|_| self.fresh()).collect();
-                    self.enums.add_enum(*name, z3_name, &members)
+                    self.enums.add_enum(*name, z3_name, &members);
+                    if let Some(scope) = self.scopes.last_mut() {
+                        scope.enums.push(*name)
+                    }
+                    self.add_to_backend(def)
                 }
             }
         }
     }
 
+    fn add_to_backend(&mut self, def: &Def) {
+        if let Some(backend) = &self.backend {
+            if let Err(err) = backend.borrow_mut().add(def) {
+                self.backend_error.get_or_insert(err);
+            }
+        }
+    }
+
+    /// The first error from the solver backend, either starting it
+    /// or sending it a definition. Once the backend has failed every
+    /// satisfiability check is [SmtResult::Unknown].
+    pub fn backend_error(&self) -> Option<&ExecError> {
+        self.backend_error.as_ref()
+    }
+
+    /// Record any variable that `exp` fixes to a literal when asserted
+    fn learn_assertion(&mut self, exp: &Exp<Sym>) {
+        match exp {
+            Exp::Var(v) => {
+                self.known.insert(*v, Exp::Bool(true));
+            }
+            Exp::Not(exp) => {
+                if let Exp::Var(v) = **exp {
+                    self.known.insert(v, Exp::Bool(false));
+                }
+            }
+            Exp::Eq(lhs, rhs) => match (&**lhs, &**rhs) {
+                (Exp::Var(v), lit) | (lit, Exp::Var(v)) if is_literal(lit) => {
+                    self.known.insert(*v, lit.clone());
+                }
+                _ => (),
+            },
+            Exp::And(lhs, rhs) => {
+                self.learn_assertion(lhs);
+                self.learn_assertion(rhs)
+            }
+            _ => (),
+        }
+    }
+
+    /// Evaluate `exp` to a literal, if every variable it uses has a
+    /// known value
+    fn known_literal(&self, exp: &Exp<Sym>) -> Option<Exp<Sym>> {
+        if self.known.is_empty() {
+            return None;
+        }
+        let mut all_known = true;
+        let mut exp = exp.clone();
+        exp.modify(&mut |exp| {
+            if let Exp::Var(v) = exp {
+                match self.known.get(v) {
+                    Some(value) => *exp = value.clone(),
+                    None => all_known = false,
+                }
+            }
+        });
+        if !all_known {
+            return None;
+        }
+        match exp.eval() {
+            Exp::Eq(lhs, rhs) => literal_eq(&lhs, &rhs).map(Exp::Bool),
+            Exp::Neq(lhs, rhs) => literal_eq(&lhs, &rhs).map(|eq| Exp::Bool(!eq)),
+            exp if is_literal(&exp) => Some(exp),
+            _ => None,
+        }
+    }
+
+    /// The values of `syms` in a model of the assertions, which must
+    /// have just been found satisfiable by [Solver::check_sat]. Each
+    /// variable is given a value, even if no assertion constrains it.
+    pub fn get_model(&mut self, syms: &[Sym]) -> Result<HashMap<Sym, ModelVal>, ExecError> {
+        let mut model = Model::new(self);
+        model.set_complete_model(true);
+        let mut values = HashMap::new();
+        for sym in syms {
+            let value = match model.get_var(*sym)? {
+                Some(exp) => ModelVal::from_exp(exp),
+                None => None,
+            };
+            let value = value.ok_or_else(|| ExecError::Z3Error(format!("No value for variable v{}", sym)))?;
+            values.insert(*sym, value);
+        }
+        Ok(values)
+    }
+
+    /// If the definitions and assertions made so far fix `v` to a
+    /// literal value, return it. This is a cheap check which avoids a
+    /// query, so `None` does not mean `v` can take multiple values.
+    pub fn known_value(&self, v: Sym) -> Option<&Exp<Sym>> {
+        if self.concrete_fast_path {
+            self.known.get(&v)
+        } else {
+            None
+        }
+    }
+
+    /// As [Solver::known_value], but for an expression
+    pub fn known_exp(&self, exp: &Exp<Sym>) -> Option<Exp<Sym>> {
+        if self.concrete_fast_path {
+            self.known_literal(exp)
+        } else {
+            None
+        }
+    }
+
+    /// True if the assertions made so far are known to be
+    /// satisfiable, because a check has found them (or them together
+    /// with some extra assumption) satisfiable since the last was
+    /// added. Definitions and declarations can't make the assertions
+    /// unsatisfiable, so only adding an assertion resets this.
+    pub fn known_sat(&self) -> bool {
+        self.known_sat
+    }
+
+    /// Enable or disable [Solver::known_value] and [Solver::known_exp]
+    /// (enabled by default), so callers always query the solver
+    pub fn set_concrete_fast_path(&mut self, enabled: bool) {
+        self.concrete_fast_path = enabled
+    }
+
+    /// Only record events in the trace which `filter` accepts (all
+    /// events are recorded by default). Events for which
+    /// [EventKind::is_always_recorded] holds are recorded regardless.
+    pub fn set_event_filter(&mut self, filter: Option<EventFilter>) {
+        self.event_filter = filter
+    }
+
     pub fn length(&mut self, v: Sym) -> Option<u32> {
         match self.decls.get(&v) {
             Some(ast) => unsafe {
@@ -1965,6 +2541,28 @@ impl<'ctx, B: BV> Solver<'ctx, B> {
         self.trace.head.push(Event::Smt(def, self.def_attrs, SourceLoc::unknown()))
     }
 
+    /// Add a definition, labelling it if it is an assertion so it can
+    /// be reported by [Solver::unsat_core_labels]. Labels are only
+    /// tracked by Z3 when unsat cores are enabled (see
+    /// [Config::set_unsat_cores]), otherwise this is the same as
+    /// [Solver::add]. The trace records a plain assertion, so labels
+    /// do not survive a [Checkpoint].
+    pub fn add_named(&mut self, def: Def, label: &str) {
+        match &def {
+            Def::Assert(exp) if self.ctx.unsat_cores && self.backend.is_none() => {
+                self.learn_assertion(exp);
+                self.known_sat = false;
+                let literal = self.fresh();
+                let literal = Ast::mk_constant(&FuncDecl::new(self.ctx, literal, &self.enums, &[], &Ty::Bool));
+                let ast = self.translate_exp(exp);
+                unsafe { Z3_solver_assert_and_track(self.ctx.z3_ctx, self.z3_solver, ast.z3_ast, literal.z3_ast) }
+                self.labels.push((literal, label.to_string()));
+                self.trace.head.push(Event::Smt(def, self.def_attrs, SourceLoc::unknown()))
+            }
+            _ => self.add(def),
+        }
+    }
+
     pub fn add_with_location(&mut self, def: Def, info: SourceLoc) {
         self.add_internal(&def, info);
         self.trace.head.push(Event::Smt(def, self.def_attrs, info))
@@ -1996,6 +2594,68 @@ impl<'ctx, B: BV> Solver<'ctx, B> {
         self.add(Def::Assert(exp))
     }
 
+    /// Assert the direction taken at a branch. If `checked`, a check
+    /// found `exp` satisfiable together with the assertions made so
+    /// far, otherwise it must be implied by them (see
+    /// [Solver::known_value]). Either way this keeps them
+    /// [known](Solver::known_sat) to be satisfiable if they were.
+    pub fn assert_branch(&mut self, exp: Exp<Sym>, checked: bool) {
+        let known_sat = checked || self.known_sat;
+        self.assert(exp);
+        self.known_sat = known_sat
+    }
+
+    /// Open a scope. The definitions, assertions, and other events
+    /// added until the matching [Solver::pop] are discarded by it,
+    /// both by the solver and from its [trace](Solver::trace), so this
+    /// is a cheap way to test several hypotheses against the same
+    /// assertions. Variables declared in the scope are not reused
+    /// afterwards.
+    ///
+    /// As a [Checkpoint] cannot represent events which may later be
+    /// discarded, [checkpoint] panics while a scope is open.
+    pub fn push(&mut self) {
+        self.scopes.push(Scope {
+            trace_len: self.trace.head.len(),
+            known: self.known.clone(),
+            known_sat: self.known_sat,
+            cycles: self.cycles,
+            enums: Vec::new(),
+            labels_len: self.labels.len(),
+        });
+        match &self.backend {
+            Some(backend) => backend.borrow_mut().push(),
+            None => unsafe { Z3_solver_push(self.ctx.z3_ctx, self.z3_solver) },
+        }
+    }
+
+    /// Close the innermost scope opened by [Solver::push]
+    pub fn pop(&mut self) {
+        let scope = self.scopes.pop().expect("Solver::pop called without a matching push");
+        match &self.backend {
+            Some(backend) => backend.borrow_mut().pop(),
+            None => unsafe { Z3_solver_pop(self.ctx.z3_ctx, self.z3_solver, 1) },
+        }
+        for event in self.trace.head.drain(scope.trace_len..) {
+            match event {
+                Event::Smt(Def::DeclareConst(v, _) | Def::DefineConst(v, _), ..) => {
+                    self.decls.remove(&v);
+                }
+                Event::Smt(Def::DeclareFun(v, ..), ..) => {
+                    self.func_decls.remove(&v);
+                }
+                _ => (),
+            }
+        }
+        for name in scope.enums {
+            self.enums.remove_enum(name)
+        }
+        self.labels.truncate(scope.labels_len);
+        self.known = scope.known;
+        self.known_sat = scope.known_sat;
+        self.cycles = scope.cycles
+    }
+
     pub fn cycle_count(&mut self) {
         self.cycles += 1;
         self.add_event(Event::Cycle)
@@ -2013,6 +2673,12 @@ impl<'ctx, B: BV> Solver<'ctx, B> {
 
     pub fn add_event(&mut self, event: Event<B>) {
         self.add_event_internal(&event);
+        if let Some(filter) = &self.event_filter {
+            let kind = event.kind();
+            if !kind.is_always_recorded() && !filter(kind) {
+                return;
+            }
+        }
         self.trace.head.push(event)
     }
 
@@ -2125,30 +2791,135 @@ impl<'ctx, B: BV> Solver<'ctx, B> {
         solver
     }
 
+    /// Check whether `exp` is satisfiable together with the assertions
+    /// made so far, without keeping it. The solver is given `exp` as
+    /// an assumption for the check, which is cheaper than asserting it
+    /// between [Solver::push] and [Solver::pop].
     pub fn check_sat_with(&mut self, exp: &Exp<Sym>, _info: SourceLoc) -> SmtResult {
         self.performance_info.start();
+        self.queries += 1;
+        let start = Instant::now();
 
-        let ast = self.translate_exp(exp);
-        let result = unsafe {
-            let z3_result = Z3_solver_check_assumptions(self.ctx.z3_ctx, self.z3_solver, 1, &ast.z3_ast);
-            if z3_result == Z3_L_TRUE {
-                Sat
-            } else if z3_result == Z3_L_FALSE {
-                Unsat
-            } else {
-                Unknown
+        let result = if self.backend_error.is_some() {
+            Unknown
+        } else if let Some(backend) = &self.backend {
+            backend.borrow_mut().check_sat_with(exp)
+        } else {
+            let ast = self.translate_exp(exp);
+            unsafe {
+                let z3_result = Z3_solver_check_assumptions(self.ctx.z3_ctx, self.z3_solver, 1, &ast.z3_ast);
+                if z3_result == Z3_L_TRUE {
+                    Sat
+                } else if z3_result == Z3_L_FALSE {
+                    Unsat
+                } else {
+                    Unknown
+                }
             }
         };
+        self.dump_if_slow(start, Some(exp));
 
         self.performance_info.assign_cost(exp);
+        if result == Sat {
+            self.known_sat = true
+        }
         result
     }
 
+    /// As [Solver::check_sat_with], but giving up with
+    /// [SmtResult::Unknown] after `millis` milliseconds. Afterwards
+    /// the solver goes back to the timeout from its configuration.
+    pub fn check_sat_with_timeout(&mut self, exp: &Exp<Sym>, millis: u32, info: SourceLoc) -> SmtResult {
+        self.check_sat_with_limits(exp, Some(millis), None, info)
+    }
+
+    /// As [Solver::check_sat_with_timeout], additionally (or instead)
+    /// giving up after using `rlimit` resources (see
+    /// [Config::set_rlimit]). Unlike a timeout this gives the same
+    /// answer every time. Only Z3 supports resource limits, other
+    /// backends ignore them.
+    pub fn check_sat_with_limits(
+        &mut self,
+        exp: &Exp<Sym>,
+        millis: Option<u32>,
+        rlimit: Option<u32>,
+        info: SourceLoc,
+    ) -> SmtResult {
+        if millis.is_some() {
+            self.set_query_timeout(millis)
+        }
+        if let Some(rlimit) = rlimit {
+            self.set_query_rlimit(rlimit)
+        }
+        let result = self.check_sat_with(exp, info);
+        if millis.is_some() {
+            self.set_query_timeout(self.ctx.timeout)
+        }
+        if rlimit.is_some() {
+            // Zero leaves only the limit from the configuration, if any
+            self.set_query_rlimit(0)
+        }
+        result
+    }
+
+    fn set_query_rlimit(&mut self, rlimit: u32) {
+        if self.backend.is_some() {
+            return;
+        }
+        unsafe {
+            let z3_ctx = self.ctx.z3_ctx;
+            let params = Z3_mk_params(z3_ctx);
+            Z3_params_inc_ref(z3_ctx, params);
+            let sym = Z3_mk_string_symbol(z3_ctx, CStr::from_bytes_with_nul_unchecked(RLIMIT_STR).as_ptr());
+            Z3_params_set_uint(z3_ctx, params, sym, rlimit);
+            Z3_solver_set_params(z3_ctx, self.z3_solver, params);
+            Z3_params_dec_ref(z3_ctx, params);
+        }
+    }
+
+    fn set_query_timeout(&mut self, millis: Option<u32>) {
+        if let Some(backend) = &self.backend {
+            return backend.borrow_mut().set_timeout(millis);
+        }
+        unsafe {
+            let z3_ctx = self.ctx.z3_ctx;
+            let params = Z3_mk_params(z3_ctx);
+            Z3_params_inc_ref(z3_ctx, params);
+            let timeout = Z3_mk_string_symbol(z3_ctx, CStr::from_bytes_with_nul_unchecked(TIMEOUT_STR).as_ptr());
+            Z3_params_set_uint(z3_ctx, params, timeout, millis.unwrap_or(u32::MAX));
+            Z3_solver_set_params(z3_ctx, self.z3_solver, params);
+            Z3_params_dec_ref(z3_ctx, params);
+        }
+    }
+
     pub fn trace(&self) -> &Trace<B> {
         &self.trace
     }
 
+    /// The number of satisfiability checks made since the solver was
+    /// created (a solver restored from a checkpoint starts from zero)
+    pub fn queries(&self) -> u64 {
+        self.queries
+    }
+
     pub fn check_sat(&mut self, _info: SourceLoc) -> SmtResult {
+        self.queries += 1;
+        let start = Instant::now();
+        let result = self.check_sat_internal();
+        self.dump_if_slow(start, None);
+        if result == Sat {
+            self.known_sat = true
+        }
+        result
+    }
+
+    fn check_sat_internal(&self) -> SmtResult {
+        if self.backend_error.is_some() {
+            return Unknown;
+        }
+        if let Some(backend) = &self.backend {
+            return backend.borrow_mut().check_sat();
+        }
         unsafe {
             let result = Z3_solver_check(self.ctx.z3_ctx, self.z3_solver);
             if result == Z3_L_TRUE {
@@ -2161,6 +2932,149 @@ impl<'ctx, B: BV> Solver<'ctx, B> {
         }
     }
 
+    /// If the assertions made on this path are unsatisfiable, the
+    /// origins of a subset of them which is also unsatisfiable (an
+    /// unsat core), in the order they were made. The trace is replayed
+    /// into a separate solver which tracks every assertion, so this
+    /// is only worth doing to explain why a path is dead. Returns
+    /// `None` if the assertions are satisfiable, or the solver cannot
+    /// tell.
+    pub fn unsat_core(&self) -> Option<Vec<Origin>> {
+        let mut events = self.trace.to_vec();
+        events.reverse();
+
+        let mut core_solver: Solver<B> =
+            Solver::from_z3_solver(self.ctx, unsafe { Z3_mk_simple_solver(self.ctx.z3_ctx) });
+        core_solver.next_var = self.next_var;
+
+        let mut tracked: Vec<(Ast<'ctx>, Origin)> = Vec::new();
+        // The fork or one-sided jump event just before an assertion
+        // says which decision it makes
+        let mut decision = None;
+        for event in events {
+            match event {
+                Event::Fork(..) | Event::OneSidedJump(..) => decision = Some(event),
+                Event::Smt(Def::Assert(exp), attrs, info) => {
+                    let origin = match decision.take() {
+                        _ if attrs.is_initial() => Origin::Initial(exp.clone()),
+                        Some(Event::OneSidedJump(taken, info)) => Origin::Branch(*taken, *info),
+                        Some(Event::Fork(_, v, branch, info)) => match exp {
+                            Exp::Var(w) if w == v => Origin::Branch(true, *info),
+                            Exp::Not(negated) if **negated == Exp::Var(*v) => Origin::Branch(false, *info),
+                            _ => Origin::Fork(*branch, *info),
+                        },
+                        _ => Origin::Assert(exp.clone(), *info),
+                    };
+                    let literal = core_solver.fresh();
+                    let literal =
+                        Ast::mk_constant(&FuncDecl::new(self.ctx, literal, &core_solver.enums, &[], &Ty::Bool));
+                    let ast = core_solver.translate_exp(exp);
+                    unsafe {
+                        Z3_solver_assert_and_track(self.ctx.z3_ctx, core_solver.z3_solver, ast.z3_ast, literal.z3_ast)
+                    }
+                    tracked.push((literal, origin))
+                }
+                Event::Smt(def, _, info) => core_solver.add_internal(def, *info),
+                _ => (),
+            }
+        }
+
+        unsafe {
+            if Z3_solver_check(self.ctx.z3_ctx, core_solver.z3_solver) != Z3_L_FALSE {
+                return None;
+            }
+            let core = Z3_solver_get_unsat_core(self.ctx.z3_ctx, core_solver.z3_solver);
+            Z3_ast_vector_inc_ref(self.ctx.z3_ctx, core);
+            let size = Z3_ast_vector_size(self.ctx.z3_ctx, core);
+            let in_core: Vec<Z3_ast> = (0..size).map(|i| Z3_ast_vector_get(self.ctx.z3_ctx, core, i)).collect();
+            let origins = tracked
+                .into_iter()
+                .filter(|(literal, _)| in_core.iter().any(|ast| Z3_is_eq_ast(self.ctx.z3_ctx, *ast, literal.z3_ast)))
+                .map(|(_, origin)| origin)
+                .collect();
+            Z3_ast_vector_dec_ref(self.ctx.z3_ctx, core);
+            Some(origins)
+        }
+    }
+
+    /// The labels of the [named](Solver::add_named) assertions in an
+    /// unsat core, in the order they were made. Only meaningful
+    /// immediately after a check returned [SmtResult::Unsat], and
+    /// always empty unless unsat cores are enabled (see
+    /// [Config::set_unsat_cores]). Unlike [Solver::unsat_core] this
+    /// does not replay the trace, so unlabelled assertions are
+    /// assumed to hold.
+    pub fn unsat_core_labels(&self) -> Vec<String> {
+        if self.labels.is_empty() {
+            return Vec::new();
+        }
+        unsafe {
+            let core = Z3_solver_get_unsat_core(self.ctx.z3_ctx, self.z3_solver);
+            Z3_ast_vector_inc_ref(self.ctx.z3_ctx, core);
+            let size = Z3_ast_vector_size(self.ctx.z3_ctx, core);
+            let in_core: Vec<Z3_ast> = (0..size).map(|i| Z3_ast_vector_get(self.ctx.z3_ctx, core, i)).collect();
+            let labels = self
+                .labels
+                .iter()
+                .filter(|(literal, _)| in_core.iter().any(|ast| Z3_is_eq_ast(self.ctx.z3_ctx, *ast, literal.z3_ast)))
+                .map(|(_, label)| label.clone())
+                .collect();
+            Z3_ast_vector_dec_ref(self.ctx.z3_ctx, core);
+            labels
+        }
+    }
+
+    /// Write every definition and assertion made so far as a
+    /// standalone SMT-LIB2 problem ending in `(check-sat)`, which
+    /// other solvers should find satisfiable exactly when this one
+    /// does. Unlike [Solver::dump_solver], this is written from the
+    /// trace, so it is independent of the backend.
+    pub fn dump_smtlib(&self, w: &mut dyn Write) -> std::io::Result<()> {
+        self.write_smtlib(w, None)
+    }
+
+    /// As [Solver::dump_smtlib], additionally asserting `assumption`
+    fn write_smtlib(&self, w: &mut dyn Write, assumption: Option<&Exp<Sym>>) -> std::io::Result<()> {
+        let mut tcx = HashMap::new();
+        let mut ftcx = HashMap::new();
+        let mut enums = HashSet::new();
+        for event in self.trace.to_vec().into_iter().rev() {
+            if let Event::Smt(def, ..) = event {
+                match def {
+                    Def::DeclareConst(v, ty) => {
+                        tcx.insert(*v, ty.clone());
+                    }
+                    Def::DeclareFun(v, arg_tys, result_ty) => {
+                        ftcx.insert(*v, (arg_tys.clone(), result_ty.clone()));
+                    }
+                    Def::DefineEnum(name, _) if !enums.insert(*name) => continue,
+                    _ => (),
+                }
+                backend::write_def(w, def, &tcx, &ftcx)?;
+                writeln!(w)?;
+                if let Def::DefineConst(v, exp) = def {
+                    tcx.insert(*v, exp.infer(&tcx, &ftcx).unwrap());
+                }
+            }
+        }
+        if let Some(exp) = assumption {
+            backend::write_def(w, &Def::Assert(exp.clone()), &tcx, &ftcx)?;
+            writeln!(w)?;
+        }
+        writeln!(w, "(check-sat)")
+    }
+
+    fn dump_if_slow(&self, start: Instant, assumption: Option<&Exp<Sym>>) {
+        let threshold = SLOW_QUERY_THRESHOLD.load(Ordering::Relaxed);
+        if threshold == 0 || start.elapsed() < Duration::from_millis(threshold as u64) {
+            return;
+        }
+        let n = SLOW_QUERY_DUMPS.fetch_add(1, Ordering::Relaxed);
+        let filename = format!("isla_slow_query_{}_{}.smt2", std::process::id(), n);
+        let mut file = std::fs::File::create(&filename).expect("Failed to open slow query dump file");
+        self.write_smtlib(&mut file, assumption).expect("Failed to write slow query dump")
+    }
+
     pub fn dump_solver(&mut self, filename: &str) {
         let mut file = std::fs::File::create(filename).expect("Failed to open solver dump file");
         unsafe {
@@ -2191,7 +3105,10 @@ impl<'ctx, B: BV> Solver<'ctx, B> {
     }
 }
 
+/// Snapshot the solver's trace. Panics if a scope opened by
+/// [Solver::push] has not been closed.
 pub fn checkpoint<B: BV>(solver: &mut Solver<B>) -> Checkpoint<B> {
+    assert!(solver.scopes.is_empty(), "Cannot take a checkpoint inside a solver scope");
     solver.trace.checkpoint(solver.next_var)
 }
 
@@ -2312,6 +3229,59 @@ mod tests {
         }
     }
 
+    #[test]
+    fn large_enum() {
+        // Enumerations are Z3 enumeration sorts rather than
+        // bitvectors, so members past 255 are distinct values
+        let mut cfg = Config::new();
+        cfg.set_param_value("model", "true");
+        let ctx = Context::new(cfg);
+        let mut solver = Solver::<B64>::new(&ctx);
+        let e = solver.get_enum(Name::from_u32(0), 300);
+        let member = |member| Box::new(Exp::Enum(EnumMember { enum_id: e, member }));
+        let v = solver.declare_const(Ty::Enum(e), SourceLoc::unknown());
+        solver.add(Assert(Not(Box::new(Eq(Box::new(Var(v)), member(299))))));
+        assert!(solver.check_sat_with(&Eq(Box::new(Var(v)), member(299)), SourceLoc::unknown()) == Unsat);
+        // 299 would truncate to 43 in 8 bits
+        assert!(solver.check_sat_with(&Eq(Box::new(Var(v)), member(43)), SourceLoc::unknown()) == Sat);
+        solver.add(Assert(Eq(Box::new(Var(v)), member(298))));
+        assert!(solver.check_sat(SourceLoc::unknown()) == Sat);
+        let mut model = Model::new(&solver);
+        match model.get_var(v).unwrap() {
+            Some(Exp::Enum(m)) => assert_eq!(m.member, 298),
+            value => panic!("unexpected value {:?}", value),
+        }
+    }
+
+    #[test]
+    fn shared_context() {
+        // Solvers created one after another on the same context must
+        // not see each other's declarations, even though they reuse
+        // the same symbols for different enums and variables
+        let mut cfg = Config::new();
+        cfg.set_param_value("model", "true");
+        let ctx = Context::new(cfg);
+        for (size, offset) in [(3, false), (2, true), (3, true), (2, false)] {
+            let mut solver = Solver::<B64>::new(&ctx);
+            if offset {
+                solver.declare_const(Ty::BitVec(4), SourceLoc::unknown());
+            }
+            let e = solver.get_enum(Name::from_u32(0), size);
+            let v0 = solver.declare_const(Ty::Enum(e), SourceLoc::unknown());
+            let v1 = solver.declare_const(Ty::Enum(e), SourceLoc::unknown());
+            let v2 = solver.declare_const(Ty::Enum(e), SourceLoc::unknown());
+            solver.add(Assert(Not(Box::new(Eq(Box::new(Var(v0)), Box::new(Var(v1)))))));
+            solver.add(Assert(Not(Box::new(Eq(Box::new(Var(v0)), Box::new(Var(v2)))))));
+            solver.add(Assert(Not(Box::new(Eq(Box::new(Var(v1)), Box::new(Var(v2)))))));
+            let expected = if size == 3 { Sat } else { Unsat };
+            assert!(solver.check_sat(SourceLoc::unknown()) == expected);
+            if size == 3 {
+                let mut model = Model::new(&solver);
+                assert!(model.get_var(v0).unwrap().is_some())
+            }
+        }
+    }
+
     #[test]
     fn smt_func() {
         let mut cfg = Config::new();
@@ -2350,4 +3320,222 @@ mod tests {
         )));
         assert!(solver.check_sat(SourceLoc::unknown()) == Unsat);
     }
+
+    /// Whether x * y is the product of two large primes, for x and y
+    /// other than one, which takes far longer than a millisecond to
+    /// decide
+    fn factor(x: Sym, y: Sym) -> Exp<Sym> {
+        let n = 4294967291u128 * 4294967279u128;
+        let product = Bits((0..128).map(|i| (n >> i) & 1 == 1).collect());
+        let wide = |v| Box::new(ZeroExtend(64, Box::new(Var(v))));
+        let one = || Box::new(Bits64(B64::new(1, 64)));
+        And(
+            Box::new(Eq(Box::new(Bvmul(wide(x), wide(y))), Box::new(product))),
+            Box::new(And(Box::new(Bvugt(Box::new(Var(x)), one())), Box::new(Bvugt(Box::new(Var(y)), one())))),
+        )
+    }
+
+    #[test]
+    fn query_timeout() {
+        let ctx = Context::new(Config::new());
+        let mut solver = Solver::<B64>::new(&ctx);
+        let x = solver.declare_const(Ty::BitVec(64), SourceLoc::unknown());
+        let y = solver.declare_const(Ty::BitVec(64), SourceLoc::unknown());
+        assert!(solver.check_sat_with_timeout(&factor(x, y), 1, SourceLoc::unknown()) == Unknown);
+        // The timeout only applies to that query
+        assert!(solver.check_sat(SourceLoc::unknown()) == Sat);
+    }
+
+    #[test]
+    fn query_rlimit() {
+        let ctx = Context::new(Config::new());
+        let mut solver = Solver::<B64>::new(&ctx);
+        let x = solver.declare_const(Ty::BitVec(64), SourceLoc::unknown());
+        let y = solver.declare_const(Ty::BitVec(64), SourceLoc::unknown());
+        assert!(solver.check_sat_with_limits(&factor(x, y), None, Some(1), SourceLoc::unknown()) == Unknown);
+        // The limit only applies to that query
+        assert!(solver.check_sat(SourceLoc::unknown()) == Sat);
+    }
+
+    #[test]
+    fn config_limits() {
+        let mut cfg = Config::new();
+        cfg.set_timeout(Some(1));
+        let ctx = Context::new(cfg);
+        let mut solver = Solver::<B64>::new(&ctx);
+        let x = solver.declare_const(Ty::BitVec(64), SourceLoc::unknown());
+        let y = solver.declare_const(Ty::BitVec(64), SourceLoc::unknown());
+        solver.add(Assert(factor(x, y)));
+        assert!(solver.check_sat(SourceLoc::unknown()) == Unknown);
+
+        let mut cfg = Config::new();
+        cfg.set_rlimit(Some(1000));
+        let ctx = Context::new(cfg);
+        let mut solver = Solver::<B64>::new(&ctx);
+        let x = solver.declare_const(Ty::BitVec(64), SourceLoc::unknown());
+        let y = solver.declare_const(Ty::BitVec(64), SourceLoc::unknown());
+        solver.add(Assert(factor(x, y)));
+        assert!(solver.check_sat(SourceLoc::unknown()) == Unknown);
+    }
+
+    #[test]
+    fn get_model() {
+        let ctx = Context::new(Config::new());
+        let mut solver = Solver::<B64>::new(&ctx);
+        let e = solver.get_enum(Name::from_u32(0), 4);
+        let b = solver.declare_const(Ty::Bool, SourceLoc::unknown());
+        let x1 = solver.declare_const(Ty::BitVec(1), SourceLoc::unknown());
+        let x64 = solver.declare_const(Ty::BitVec(64), SourceLoc::unknown());
+        let x65 = solver.declare_const(Ty::BitVec(65), SourceLoc::unknown());
+        let m = solver.declare_const(Ty::Enum(e), SourceLoc::unknown());
+        let unconstrained = solver.declare_const(Ty::BitVec(8), SourceLoc::unknown());
+        let big = Bits((0..65).map(|i| i % 3 == 0).collect());
+        solver.assert(Not(Box::new(Var(b))));
+        solver.assert_eq(Var(x1), bv!("1"));
+        solver.assert_eq(Var(x64), Bits64(B64::new(0xdead_beef_0000_0001, 64)));
+        solver.assert_eq(Var(x65), big.clone());
+        solver.assert_eq(Var(m), Enum(EnumMember { enum_id: e, member: 2 }));
+        assert!(solver.check_sat(SourceLoc::unknown()) == Sat);
+
+        let model = solver.get_model(&[b, x1, x64, x65, m, unconstrained]).unwrap();
+        assert_eq!(model[&b], ModelVal::Bool(false));
+        assert_eq!(model[&x1], ModelVal::Bits64(B64::new(1, 1)));
+        assert_eq!(model[&x64], ModelVal::Bits64(B64::new(0xdead_beef_0000_0001, 64)));
+        assert_eq!(model[&x65].to_exp(), big);
+        assert_eq!(model[&m], ModelVal::Enum(EnumMember { enum_id: e, member: 2 }));
+        assert!(matches!(model[&unconstrained], ModelVal::Bits64(bv) if bv.len() == 8));
+        assert!(solver.get_model(&[Sym::from_u32(1000)]).is_err())
+    }
+
+    #[test]
+    fn push_pop() {
+        let ctx = Context::new(Config::new());
+        let mut solver = Solver::<B64>::new(&ctx);
+        let x = solver.declare_const(Ty::BitVec(4), SourceLoc::unknown());
+        solver.assert(Bvugt(Box::new(Var(x)), Box::new(bv!("0010"))));
+        let len = solver.trace().len();
+
+        solver.push();
+        let e = solver.get_enum(Name::from_u32(0), 2);
+        let y = solver.declare_const(Ty::Enum(e), SourceLoc::unknown());
+        solver.assert_eq(Var(x), bv!("0001"));
+        assert!(solver.check_sat(SourceLoc::unknown()) == Unsat);
+        solver.pop();
+
+        assert_eq!(solver.trace().len(), len);
+        assert!(!solver.trace().to_vec().iter().any(|event| matches!(event, Event::Smt(DefineEnum(..), ..))));
+        assert!(solver.check_sat(SourceLoc::unknown()) == Sat);
+        assert!(Model::new(&solver).get_var(y).is_err());
+
+        // The enumeration defined in the scope can be defined again
+        let e = solver.get_enum(Name::from_u32(0), 3);
+        let z = solver.declare_const(Ty::Enum(e), SourceLoc::unknown());
+        solver.assert(Neq(Box::new(Var(z)), Box::new(Enum(e.first_member()))));
+        let mut solver = Solver::<B64>::from_checkpoint(&ctx, checkpoint(&mut solver));
+        assert!(solver.check_sat(SourceLoc::unknown()) == Sat);
+    }
+
+    #[test]
+    #[should_panic]
+    fn checkpoint_in_scope() {
+        let ctx = Context::new(Config::new());
+        let mut solver = Solver::<B64>::new(&ctx);
+        solver.push();
+        checkpoint(&mut solver);
+    }
+
+    /// Run a problem written by [Solver::dump_smtlib] through Z3's
+    /// text interface
+    fn eval_smtlib(problem: &[u8]) -> SmtResult {
+        let ctx = Context::new(Config::new());
+        let problem = CString::new(problem).unwrap();
+        let output = unsafe { CStr::from_ptr(Z3_eval_smtlib2_string(ctx.z3_ctx, problem.as_ptr())) };
+        match output.to_str().unwrap().trim() {
+            "sat" => Sat,
+            "unsat" => Unsat,
+            _ => Unknown,
+        }
+    }
+
+    #[test]
+    fn dump_smtlib_round_trip() {
+        let ctx = Context::new(Config::new());
+        let mut solver = Solver::<B64>::new(&ctx);
+        let e = solver.get_enum(Name::from_u32(0), 3);
+        let x = solver.declare_const(Ty::BitVec(8), SourceLoc::unknown());
+        let y = solver.declare_const(Ty::Enum(e), SourceLoc::unknown());
+        let f = solver.fresh();
+        solver.add(DeclareFun(f, vec![Ty::BitVec(8)], Ty::Bool));
+        let z = solver.define_const(Bvadd(Box::new(Var(x)), Box::new(bv!("00000001"))), SourceLoc::unknown());
+        solver.assert(App(f, vec![Var(z)]));
+        solver.assert(Neq(Box::new(Var(y)), Box::new(Enum(e.first_member()))));
+        solver.assert(Bvugt(Box::new(Var(x)), Box::new(bv!("11111101"))));
+
+        let mut problem = Vec::new();
+        solver.dump_smtlib(&mut problem).unwrap();
+        assert!(solver.check_sat(SourceLoc::unknown()) == Sat);
+        assert!(eval_smtlib(&problem) == Sat);
+
+        // x + 1 overflows for x = 0xFF
+        solver.assert(Eq(Box::new(Var(z)), Box::new(bv!("00000000"))));
+        solver.assert(Not(Box::new(App(f, vec![bv!("00000000")]))));
+        let mut problem = Vec::new();
+        solver.dump_smtlib(&mut problem).unwrap();
+        assert!(solver.check_sat(SourceLoc::unknown()) == Unsat);
+        assert!(eval_smtlib(&problem) == Unsat);
+    }
+
+    #[test]
+    fn unsat_core_labels() {
+        let mut cfg = Config::new();
+        cfg.set_unsat_cores(true);
+        let ctx = Context::new(cfg);
+        let mut solver = Solver::<B64>::new(&ctx);
+        let x = solver.declare_const(Ty::BitVec(4), SourceLoc::unknown());
+        let y = solver.declare_const(Ty::BitVec(4), SourceLoc::unknown());
+        solver.add_named(Assert(Bvult(Box::new(Var(y)), Box::new(bv!("0100")))), "y_small");
+        solver.add_named(Assert(Bvugt(Box::new(Var(x)), Box::new(bv!("0110")))), "x_big");
+        solver.add_named(Assert(Eq(Box::new(Var(y)), Box::new(Var(y)))), "trivial");
+        solver.add_named(Assert(Bvult(Box::new(Var(x)), Box::new(bv!("0011")))), "x_small");
+        assert!(solver.check_sat(SourceLoc::unknown()) == Unsat);
+        assert_eq!(solver.unsat_core_labels(), vec!["x_big".to_string(), "x_small".to_string()]);
+
+        // Labels made inside a scope are forgotten when it is closed
+        solver.push();
+        solver.add_named(Assert(Bool(false)), "false");
+        solver.pop();
+        assert!(solver.check_sat(SourceLoc::unknown()) == Unsat);
+        assert_eq!(solver.unsat_core_labels(), vec!["x_big".to_string(), "x_small".to_string()]);
+    }
+
+    #[test]
+    fn cvc5_backend() {
+        if std::process::Command::new("cvc5").arg("--version").output().is_err() {
+            return;
+        }
+        let mut cfg = Config::new();
+        cfg.set_backend(Backend::Cvc5);
+        let ctx = Context::new(cfg);
+        let mut solver = Solver::<B64>::new(&ctx);
+        let e = solver.get_enum(Name::from_u32(0), 3);
+        let x = solver.declare_const(Ty::BitVec(4), SourceLoc::unknown());
+        let y = solver.declare_const(Ty::Enum(e), SourceLoc::unknown());
+        let z = solver.declare_const(Ty::BitVec(8), SourceLoc::unknown());
+        solver.add(Assert(Bvsgt(Box::new(Var(x)), Box::new(bv!("0110")))));
+        solver.add(Assert(Neq(Box::new(Var(y)), Box::new(Enum(e.first_member())))));
+        let point = checkpoint(&mut solver);
+        assert!(solver.check_sat_with(&Bvslt(Box::new(Var(x)), Box::new(bv!("0111"))), SourceLoc::unknown()) == Unsat);
+        assert!(solver.check_sat(SourceLoc::unknown()) == Sat);
+        {
+            let mut model = Model::new(&solver);
+            assert_eq!(model.get_var(x).unwrap(), Some(Bits64(B64::new(0b0111, 4))));
+            assert!(matches!(model.get_var(y).unwrap(), Some(Enum(m)) if m.member != 0));
+            assert!(model.get_var(z).unwrap().is_none());
+        }
+
+        // Replaying the checkpoint must restore the definitions in a new cvc5 process
+        let mut solver = Solver::<B64>::from_checkpoint(&ctx, point);
+        solver.add(Assert(Eq(Box::new(Var(y)), Box::new(Enum(e.first_member())))));
+        assert!(solver.check_sat(SourceLoc::unknown()) == Unsat);
+    }
 }
@@ -0,0 +1,345 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2024 Alasdair Armstrong
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// 1. Redistributions of source code must retain the above copyright
+// notice, this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright
+// notice, this list of conditions and the following disclaimer in the
+// documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! This module provides a hash-consed representation of SMT
+//! expressions. An [ExpArena] stores each distinct subexpression
+//! once as a [Node] whose children are [ExpRef] indices, so
+//! structurally identical subtrees (such as the extract and concat
+//! chains over the same registers which fill large traces) share a
+//! single node, and comparing two interned expressions is just
+//! comparing their indices.
+//!
+//! The rest of isla still uses the boxed [Exp] type, so expressions
+//! are converted with [ExpArena::intern] and [ExpArena::to_exp] at
+//! the edges.
+
+use ahash;
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use super::smtlib::*;
+use super::{EnumMember, Sym};
+use crate::bitvector::b64::B64;
+
+/// An index of a node in an [ExpArena]. Two expressions interned in
+/// the same arena are structurally equal exactly when their indices
+/// are.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ExpRef(u32);
+
+/// The operators of [Exp], without their subexpressions
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Op {
+    Not,
+    Bvnot,
+    Bvneg,
+    Eq,
+    Neq,
+    And,
+    Or,
+    Bvand,
+    Bvor,
+    Bvxor,
+    Bvnand,
+    Bvnor,
+    Bvxnor,
+    Bvadd,
+    Bvsub,
+    Bvmul,
+    Bvudiv,
+    Bvsdiv,
+    Bvurem,
+    Bvsrem,
+    Bvsmod,
+    Bvult,
+    Bvslt,
+    Bvule,
+    Bvsle,
+    Bvuge,
+    Bvsge,
+    Bvugt,
+    Bvsgt,
+    Bvshl,
+    Bvlshr,
+    Bvashr,
+    Concat,
+    Select,
+    Extract(u32, u32),
+    ZeroExtend(u32),
+    SignExtend(u32),
+    Ite,
+    App(Sym),
+    Store,
+    Distinct,
+    FPUnary(FPUnary),
+    FPRoundingUnary(FPRoundingUnary),
+    FPBinary(FPBinary),
+    FPRoundingBinary(FPRoundingBinary),
+    FPfma,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Node<V> {
+    Var(V),
+    Bits(Vec<bool>),
+    Bits64(B64),
+    Enum(EnumMember),
+    Bool(bool),
+    FPConstant(FPConstant, u32, u32),
+    FPRoundingMode(FPRoundingMode),
+    Op(Op, Vec<ExpRef>),
+}
+
+pub struct ExpArena<V> {
+    nodes: Vec<Node<V>>,
+    table: HashMap<Node<V>, ExpRef, ahash::RandomState>,
+}
+
+impl<V: Clone + Eq + Hash> ExpArena<V> {
+    pub fn new() -> Self {
+        ExpArena { nodes: Vec::new(), table: HashMap::default() }
+    }
+
+    /// The number of distinct nodes in the arena
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn node(&self, exp: ExpRef) -> &Node<V> {
+        &self.nodes[exp.0 as usize]
+    }
+
+    /// Add a node, returning the existing one if an identical node
+    /// has already been added. The children must belong to this
+    /// arena.
+    pub fn mk(&mut self, node: Node<V>) -> ExpRef {
+        if let Some(exp) = self.table.get(&node) {
+            return *exp;
+        }
+        let exp = ExpRef(self.nodes.len() as u32);
+        self.nodes.push(node.clone());
+        self.table.insert(node, exp);
+        exp
+    }
+
+    fn mk_op(&mut self, op: Op, args: &[&Exp<V>]) -> ExpRef {
+        let args = args.iter().map(|arg| self.intern(arg)).collect();
+        self.mk(Node::Op(op, args))
+    }
+
+    /// Add every subexpression of `exp` to the arena
+    pub fn intern(&mut self, exp: &Exp<V>) -> ExpRef {
+        use Exp::*;
+        match exp {
+            Var(v) => self.mk(Node::Var(v.clone())),
+            Bits(bv) => self.mk(Node::Bits(bv.clone())),
+            Bits64(bv) => self.mk(Node::Bits64(*bv)),
+            Enum(e) => self.mk(Node::Enum(*e)),
+            Bool(b) => self.mk(Node::Bool(*b)),
+            FPConstant(c, ebits, sbits) => self.mk(Node::FPConstant(*c, *ebits, *sbits)),
+            FPRoundingMode(rm) => self.mk(Node::FPRoundingMode(*rm)),
+            Not(exp) => self.mk_op(Op::Not, &[exp]),
+            Bvnot(exp) => self.mk_op(Op::Bvnot, &[exp]),
+            Bvneg(exp) => self.mk_op(Op::Bvneg, &[exp]),
+            Eq(lhs, rhs) => self.mk_op(Op::Eq, &[lhs, rhs]),
+            Neq(lhs, rhs) => self.mk_op(Op::Neq, &[lhs, rhs]),
+            And(lhs, rhs) => self.mk_op(Op::And, &[lhs, rhs]),
+            Or(lhs, rhs) => self.mk_op(Op::Or, &[lhs, rhs]),
+            Bvand(lhs, rhs) => self.mk_op(Op::Bvand, &[lhs, rhs]),
+            Bvor(lhs, rhs) => self.mk_op(Op::Bvor, &[lhs, rhs]),
+            Bvxor(lhs, rhs) => self.mk_op(Op::Bvxor, &[lhs, rhs]),
+            Bvnand(lhs, rhs) => self.mk_op(Op::Bvnand, &[lhs, rhs]),
+            Bvnor(lhs, rhs) => self.mk_op(Op::Bvnor, &[lhs, rhs]),
+            Bvxnor(lhs, rhs) => self.mk_op(Op::Bvxnor, &[lhs, rhs]),
+            Bvadd(lhs, rhs) => self.mk_op(Op::Bvadd, &[lhs, rhs]),
+            Bvsub(lhs, rhs) => self.mk_op(Op::Bvsub, &[lhs, rhs]),
+            Bvmul(lhs, rhs) => self.mk_op(Op::Bvmul, &[lhs, rhs]),
+            Bvudiv(lhs, rhs) => self.mk_op(Op::Bvudiv, &[lhs, rhs]),
+            Bvsdiv(lhs, rhs) => self.mk_op(Op::Bvsdiv, &[lhs, rhs]),
+            Bvurem(lhs, rhs) => self.mk_op(Op::Bvurem, &[lhs, rhs]),
+            Bvsrem(lhs, rhs) => self.mk_op(Op::Bvsrem, &[lhs, rhs]),
+            Bvsmod(lhs, rhs) => self.mk_op(Op::Bvsmod, &[lhs, rhs]),
+            Bvult(lhs, rhs) => self.mk_op(Op::Bvult, &[lhs, rhs]),
+            Bvslt(lhs, rhs) => self.mk_op(Op::Bvslt, &[lhs, rhs]),
+            Bvule(lhs, rhs) => self.mk_op(Op::Bvule, &[lhs, rhs]),
+            Bvsle(lhs, rhs) => self.mk_op(Op::Bvsle, &[lhs, rhs]),
+            Bvuge(lhs, rhs) => self.mk_op(Op::Bvuge, &[lhs, rhs]),
+            Bvsge(lhs, rhs) => self.mk_op(Op::Bvsge, &[lhs, rhs]),
+            Bvugt(lhs, rhs) => self.mk_op(Op::Bvugt, &[lhs, rhs]),
+            Bvsgt(lhs, rhs) => self.mk_op(Op::Bvsgt, &[lhs, rhs]),
+            Bvshl(lhs, rhs) => self.mk_op(Op::Bvshl, &[lhs, rhs]),
+            Bvlshr(lhs, rhs) => self.mk_op(Op::Bvlshr, &[lhs, rhs]),
+            Bvashr(lhs, rhs) => self.mk_op(Op::Bvashr, &[lhs, rhs]),
+            Concat(lhs, rhs) => self.mk_op(Op::Concat, &[lhs, rhs]),
+            Select(lhs, rhs) => self.mk_op(Op::Select, &[lhs, rhs]),
+            Extract(i, j, exp) => self.mk_op(Op::Extract(*i, *j), &[exp]),
+            ZeroExtend(n, exp) => self.mk_op(Op::ZeroExtend(*n), &[exp]),
+            SignExtend(n, exp) => self.mk_op(Op::SignExtend(*n), &[exp]),
+            Ite(cond, then_exp, else_exp) => self.mk_op(Op::Ite, &[cond, then_exp, else_exp]),
+            App(f, args) => self.mk_op(Op::App(*f), &args.iter().collect::<Vec<_>>()),
+            Store(array, index, val) => self.mk_op(Op::Store, &[array, index, val]),
+            Distinct(exps) => self.mk_op(Op::Distinct, &exps.iter().collect::<Vec<_>>()),
+            FPUnary(op, exp) => self.mk_op(Op::FPUnary(*op), &[exp]),
+            FPRoundingUnary(op, rm, exp) => self.mk_op(Op::FPRoundingUnary(*op), &[rm, exp]),
+            FPBinary(op, lhs, rhs) => self.mk_op(Op::FPBinary(*op), &[lhs, rhs]),
+            FPRoundingBinary(op, rm, lhs, rhs) => self.mk_op(Op::FPRoundingBinary(*op), &[rm, lhs, rhs]),
+            FPfma(rm, x, y, z) => self.mk_op(Op::FPfma, &[rm, x, y, z]),
+        }
+    }
+
+    /// Convert back to an [Exp]. Shared subexpressions are copied
+    /// wherever they occur.
+    pub fn to_exp(&self, exp: ExpRef) -> Exp<V> {
+        match self.node(exp) {
+            Node::Var(v) => Exp::Var(v.clone()),
+            Node::Bits(bv) => Exp::Bits(bv.clone()),
+            Node::Bits64(bv) => Exp::Bits64(*bv),
+            Node::Enum(e) => Exp::Enum(*e),
+            Node::Bool(b) => Exp::Bool(*b),
+            Node::FPConstant(c, ebits, sbits) => Exp::FPConstant(*c, *ebits, *sbits),
+            Node::FPRoundingMode(rm) => Exp::FPRoundingMode(*rm),
+            Node::Op(op, args) => {
+                let arg = |i: usize| Box::new(self.to_exp(args[i]));
+                match op {
+                    Op::Not => Exp::Not(arg(0)),
+                    Op::Bvnot => Exp::Bvnot(arg(0)),
+                    Op::Bvneg => Exp::Bvneg(arg(0)),
+                    Op::Eq => Exp::Eq(arg(0), arg(1)),
+                    Op::Neq => Exp::Neq(arg(0), arg(1)),
+                    Op::And => Exp::And(arg(0), arg(1)),
+                    Op::Or => Exp::Or(arg(0), arg(1)),
+                    Op::Bvand => Exp::Bvand(arg(0), arg(1)),
+                    Op::Bvor => Exp::Bvor(arg(0), arg(1)),
+                    Op::Bvxor => Exp::Bvxor(arg(0), arg(1)),
+                    Op::Bvnand => Exp::Bvnand(arg(0), arg(1)),
+                    Op::Bvnor => Exp::Bvnor(arg(0), arg(1)),
+                    Op::Bvxnor => Exp::Bvxnor(arg(0), arg(1)),
+                    Op::Bvadd => Exp::Bvadd(arg(0), arg(1)),
+                    Op::Bvsub => Exp::Bvsub(arg(0), arg(1)),
+                    Op::Bvmul => Exp::Bvmul(arg(0), arg(1)),
+                    Op::Bvudiv => Exp::Bvudiv(arg(0), arg(1)),
+                    Op::Bvsdiv => Exp::Bvsdiv(arg(0), arg(1)),
+                    Op::Bvurem => Exp::Bvurem(arg(0), arg(1)),
+                    Op::Bvsrem => Exp::Bvsrem(arg(0), arg(1)),
+                    Op::Bvsmod => Exp::Bvsmod(arg(0), arg(1)),
+                    Op::Bvult => Exp::Bvult(arg(0), arg(1)),
+                    Op::Bvslt => Exp::Bvslt(arg(0), arg(1)),
+                    Op::Bvule => Exp::Bvule(arg(0), arg(1)),
+                    Op::Bvsle => Exp::Bvsle(arg(0), arg(1)),
+                    Op::Bvuge => Exp::Bvuge(arg(0), arg(1)),
+                    Op::Bvsge => Exp::Bvsge(arg(0), arg(1)),
+                    Op::Bvugt => Exp::Bvugt(arg(0), arg(1)),
+                    Op::Bvsgt => Exp::Bvsgt(arg(0), arg(1)),
+                    Op::Bvshl => Exp::Bvshl(arg(0), arg(1)),
+                    Op::Bvlshr => Exp::Bvlshr(arg(0), arg(1)),
+                    Op::Bvashr => Exp::Bvashr(arg(0), arg(1)),
+                    Op::Concat => Exp::Concat(arg(0), arg(1)),
+                    Op::Select => Exp::Select(arg(0), arg(1)),
+                    Op::Extract(i, j) => Exp::Extract(*i, *j, arg(0)),
+                    Op::ZeroExtend(n) => Exp::ZeroExtend(*n, arg(0)),
+                    Op::SignExtend(n) => Exp::SignExtend(*n, arg(0)),
+                    Op::Ite => Exp::Ite(arg(0), arg(1), arg(2)),
+                    Op::App(f) => Exp::App(*f, args.iter().map(|arg| self.to_exp(*arg)).collect()),
+                    Op::Store => Exp::Store(arg(0), arg(1), arg(2)),
+                    Op::Distinct => Exp::Distinct(args.iter().map(|arg| self.to_exp(*arg)).collect()),
+                    Op::FPUnary(op) => Exp::FPUnary(*op, arg(0)),
+                    Op::FPRoundingUnary(op) => Exp::FPRoundingUnary(*op, arg(0), arg(1)),
+                    Op::FPBinary(op) => Exp::FPBinary(*op, arg(0), arg(1)),
+                    Op::FPRoundingBinary(op) => Exp::FPRoundingBinary(*op, arg(0), arg(1), arg(2)),
+                    Op::FPfma => Exp::FPfma(arg(0), arg(1), arg(2), arg(3)),
+                }
+            }
+        }
+    }
+}
+
+impl<V: Clone + Eq + Hash> Default for ExpArena<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn var(id: u32) -> Exp<Sym> {
+        Exp::Var(Sym::from_u32(id))
+    }
+
+    fn extract(hi: u32, lo: u32, exp: Exp<Sym>) -> Exp<Sym> {
+        Exp::Extract(hi, lo, Box::new(exp))
+    }
+
+    fn concat(lhs: Exp<Sym>, rhs: Exp<Sym>) -> Exp<Sym> {
+        Exp::Concat(Box::new(lhs), Box::new(rhs))
+    }
+
+    #[test]
+    fn shares_subexpressions() {
+        let mut arena = ExpArena::new();
+        let half = concat(extract(63, 32, var(0)), extract(31, 0, var(0)));
+        let exp = concat(half.clone(), half.clone());
+        let r = arena.intern(&exp);
+        // v0, the two extracts, the inner concat, and the outer concat
+        assert_eq!(arena.len(), 5);
+        assert_eq!(arena.intern(&concat(half.clone(), half)), r);
+        assert_eq!(arena.to_exp(r), exp);
+        assert_ne!(arena.intern(&extract(31, 0, var(1))), arena.intern(&extract(31, 0, var(0))))
+    }
+
+    #[test]
+    fn round_trip() {
+        let exps: Vec<Exp<Sym>> = vec![
+            Exp::Ite(
+                Box::new(Exp::Eq(Box::new(var(0)), Box::new(bits64(3, 4)))),
+                Box::new(Exp::Bits(vec![true; 70])),
+                Box::new(Exp::ZeroExtend(66, Box::new(var(1)))),
+            ),
+            Exp::App(Sym::from_u32(2), vec![var(0), Exp::Bool(true)]),
+            Exp::Distinct(vec![var(0), var(1), var(0)]),
+            Exp::FPRoundingBinary(
+                FPRoundingBinary::Add,
+                Box::new(Exp::FPRoundingMode(FPRoundingMode::RoundTowardZero)),
+                Box::new(Exp::FPConstant(FPConstant::Inf { negative: true }, 8, 24)),
+                Box::new(Exp::FPUnary(FPUnary::FromIEEE(8, 24), Box::new(var(3)))),
+            ),
+        ];
+        let mut arena = ExpArena::new();
+        for exp in exps {
+            let r = arena.intern(&exp);
+            assert_eq!(arena.to_exp(r), exp)
+        }
+    }
+}
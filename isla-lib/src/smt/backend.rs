@@ -0,0 +1,709 @@
+// BSD 2-Clause License
+//
+// Copyright (c) 2024 Alasdair Armstrong
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// 1. Redistributions of source code must retain the above copyright
+// notice, this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright
+// notice, this list of conditions and the following disclaimer in the
+// documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! This module contains the SMT solvers that can be used in place of
+//! the built-in Z3 one to decide satisfiability. The [super::Solver]
+//! still builds and simplifies terms with Z3 and owns the trace, so
+//! fresh variables, checkpoints, and replay work the same whichever
+//! backend is used. A [SolverBackend] is only sent the definitions
+//! and assertions, and asked about satisfiability and model values.
+//! This means Z3 is always linked, even when it never decides
+//! satisfiability, so another backend does not remove the dependency
+//! on it.
+//!
+//! The backend is chosen with [super::Config::set_backend]. The
+//! default for new configurations is Z3 unless changed by
+//! [set_default_backend] or the `ISLA_SMT_BACKEND` environment
+//! variable, which allows the tests to be run against another solver.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use super::smtlib::*;
+use super::{EnumMember, SmtResult, Sym};
+use crate::bitvector::b64::B64;
+use crate::bitvector::BV;
+use crate::error::ExecError;
+use crate::simplify::write_bits;
+use crate::source_loc::SourceLoc;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Backend {
+    Z3,
+    Cvc5,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "z3" => Ok(Backend::Z3),
+            "cvc5" => Ok(Backend::Cvc5),
+            _ => Err(format!("Unknown SMT solver backend {}, expected z3 or cvc5", s)),
+        }
+    }
+}
+
+impl Backend {
+    pub(super) fn start(self) -> Result<Option<Box<dyn SolverBackend>>, ExecError> {
+        match self {
+            Backend::Z3 => Ok(None),
+            Backend::Cvc5 => Ok(Some(Box::new(Cvc5::new()?))),
+        }
+    }
+
+    /// Check that the solver can be started, so a missing executable
+    /// can be reported before any solver is created
+    pub fn check_available(self) -> Result<(), ExecError> {
+        self.start().map(|_| ())
+    }
+}
+
+/// Zero means no default has been set
+static DEFAULT_BACKEND: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_default_backend(backend: Backend) {
+    let n = match backend {
+        Backend::Z3 => 1,
+        Backend::Cvc5 => 2,
+    };
+    DEFAULT_BACKEND.store(n, Ordering::Relaxed)
+}
+
+pub fn default_backend() -> Backend {
+    match DEFAULT_BACKEND.load(Ordering::Relaxed) {
+        1 => Backend::Z3,
+        2 => Backend::Cvc5,
+        _ => std::env::var("ISLA_SMT_BACKEND").ok().and_then(|s| s.parse().ok()).unwrap_or(Backend::Z3),
+    }
+}
+
+/// The operations a [super::Solver] needs from an SMT solver other
+/// than Z3.
+pub trait SolverBackend {
+    fn add(&mut self, def: &Def) -> Result<(), ExecError>;
+
+    fn check_sat(&mut self) -> SmtResult;
+
+    /// Check satisfiability under an extra assumption, which is not
+    /// kept afterwards
+    fn check_sat_with(&mut self, exp: &Exp<Sym>) -> SmtResult;
+
+    /// The value of `exp` in the model from the last satisfiable
+    /// check. If `complete` is false, variables which no assertion
+    /// mentions have no value.
+    fn get_value(&mut self, exp: &Exp<Sym>, complete: bool) -> Result<Option<Exp<Sym>>, ExecError>;
+
+    /// Limit how long each following check may take, in milliseconds
+    fn set_timeout(&mut self, millis: Option<u32>);
+
+    fn push(&mut self);
+
+    /// Discard everything added since the matching `push`
+    fn pop(&mut self);
+}
+
+fn write_ty(buf: &mut dyn Write, ty: &Ty) -> std::io::Result<()> {
+    use Ty::*;
+    match ty {
+        Bool => write!(buf, "Bool"),
+        BitVec(sz) => write!(buf, "(_ BitVec {})", sz),
+        Enum(e) => write!(buf, "e{}", e.to_name()),
+        Array(dom, codom) => {
+            write!(buf, "(Array ")?;
+            write_ty(buf, dom)?;
+            write!(buf, " ")?;
+            write_ty(buf, codom)?;
+            write!(buf, ")")
+        }
+        Float(ebits, sbits) => write!(buf, "(_ FloatingPoint {} {})", ebits, sbits),
+        RoundingMode => write!(buf, "RoundingMode"),
+    }
+}
+
+fn write_op(buf: &mut dyn Write, op: &str, args: &[&Exp<Sym>]) -> std::io::Result<()> {
+    write!(buf, "({}", op)?;
+    for arg in args {
+        write!(buf, " ")?;
+        write_exp(buf, arg)?
+    }
+    write!(buf, ")")
+}
+
+/// Write an expression in SMTLIB format. Variables are written as
+/// `v<n>`, and enumerations as `e<n>` with members `e<n>_<member>`,
+/// where `<n>` is the name of the enumeration.
+pub(crate) fn write_exp(buf: &mut dyn Write, exp: &Exp<Sym>) -> std::io::Result<()> {
+    use Exp::*;
+    match exp {
+        Var(v) => write!(buf, "v{}", v),
+        Bits(bv) => write_bits(buf, bv),
+        Bits64(bv) => write_bits(buf, &(0..bv.len()).map(|n| (bv.lower_u64() >> n) & 1 == 1).collect::<Vec<_>>()),
+        Enum(e) => write!(buf, "e{}_{}", e.enum_id.to_name(), e.member),
+        Bool(b) => write!(buf, "{}", b),
+        Eq(lhs, rhs) => write_op(buf, "=", &[lhs, rhs]),
+        Neq(lhs, rhs) => write_op(buf, "distinct", &[lhs, rhs]),
+        And(lhs, rhs) => write_op(buf, "and", &[lhs, rhs]),
+        Or(lhs, rhs) => write_op(buf, "or", &[lhs, rhs]),
+        Not(exp) => write_op(buf, "not", &[exp]),
+        Bvnot(exp) => write_op(buf, "bvnot", &[exp]),
+        Bvand(lhs, rhs) => write_op(buf, "bvand", &[lhs, rhs]),
+        Bvor(lhs, rhs) => write_op(buf, "bvor", &[lhs, rhs]),
+        Bvxor(lhs, rhs) => write_op(buf, "bvxor", &[lhs, rhs]),
+        Bvnand(lhs, rhs) => write_op(buf, "bvnand", &[lhs, rhs]),
+        Bvnor(lhs, rhs) => write_op(buf, "bvnor", &[lhs, rhs]),
+        Bvxnor(lhs, rhs) => write_op(buf, "bvxnor", &[lhs, rhs]),
+        Bvneg(exp) => write_op(buf, "bvneg", &[exp]),
+        Bvadd(lhs, rhs) => write_op(buf, "bvadd", &[lhs, rhs]),
+        Bvsub(lhs, rhs) => write_op(buf, "bvsub", &[lhs, rhs]),
+        Bvmul(lhs, rhs) => write_op(buf, "bvmul", &[lhs, rhs]),
+        Bvudiv(lhs, rhs) => write_op(buf, "bvudiv", &[lhs, rhs]),
+        Bvsdiv(lhs, rhs) => write_op(buf, "bvsdiv", &[lhs, rhs]),
+        Bvurem(lhs, rhs) => write_op(buf, "bvurem", &[lhs, rhs]),
+        Bvsrem(lhs, rhs) => write_op(buf, "bvsrem", &[lhs, rhs]),
+        Bvsmod(lhs, rhs) => write_op(buf, "bvsmod", &[lhs, rhs]),
+        Bvult(lhs, rhs) => write_op(buf, "bvult", &[lhs, rhs]),
+        Bvslt(lhs, rhs) => write_op(buf, "bvslt", &[lhs, rhs]),
+        Bvule(lhs, rhs) => write_op(buf, "bvule", &[lhs, rhs]),
+        Bvsle(lhs, rhs) => write_op(buf, "bvsle", &[lhs, rhs]),
+        Bvuge(lhs, rhs) => write_op(buf, "bvuge", &[lhs, rhs]),
+        Bvsge(lhs, rhs) => write_op(buf, "bvsge", &[lhs, rhs]),
+        Bvugt(lhs, rhs) => write_op(buf, "bvugt", &[lhs, rhs]),
+        Bvsgt(lhs, rhs) => write_op(buf, "bvsgt", &[lhs, rhs]),
+        Extract(i, j, exp) => write_op(buf, &format!("(_ extract {} {})", i, j), &[exp]),
+        ZeroExtend(n, exp) => write_op(buf, &format!("(_ zero_extend {})", n), &[exp]),
+        SignExtend(n, exp) => write_op(buf, &format!("(_ sign_extend {})", n), &[exp]),
+        Bvshl(lhs, rhs) => write_op(buf, "bvshl", &[lhs, rhs]),
+        Bvlshr(lhs, rhs) => write_op(buf, "bvlshr", &[lhs, rhs]),
+        Bvashr(lhs, rhs) => write_op(buf, "bvashr", &[lhs, rhs]),
+        Concat(lhs, rhs) => write_op(buf, "concat", &[lhs, rhs]),
+        Ite(cond, then_exp, else_exp) => write_op(buf, "ite", &[cond, then_exp, else_exp]),
+        App(f, args) => write_op(buf, &format!("v{}", f), &args.iter().collect::<Vec<_>>()),
+        Select(array, index) => write_op(buf, "select", &[array, index]),
+        Store(array, index, val) => write_op(buf, "store", &[array, index, val]),
+        Distinct(exps) => write_op(buf, "distinct", &exps.iter().collect::<Vec<_>>()),
+        FPConstant(c, ebits, sbits) => {
+            use super::smtlib::FPConstant::*;
+            let c = match c {
+                NaN => "NaN",
+                Inf { negative: false } => "+oo",
+                Inf { negative: true } => "-oo",
+                Zero { negative: false } => "+zero",
+                Zero { negative: true } => "-zero",
+            };
+            write!(buf, "(_ {} {} {})", c, ebits, sbits)
+        }
+        FPRoundingMode(rm) => {
+            use super::smtlib::FPRoundingMode::*;
+            match rm {
+                RoundNearestTiesToEven => write!(buf, "roundNearestTiesToEven"),
+                RoundNearestTiesToAway => write!(buf, "roundNearestTiesToAway"),
+                RoundTowardPositive => write!(buf, "roundTowardPositive"),
+                RoundTowardNegative => write!(buf, "roundTowardNegative"),
+                RoundTowardZero => write!(buf, "roundTowardZero"),
+            }
+        }
+        FPUnary(op, exp) => {
+            use super::smtlib::FPUnary::*;
+            let op = match op {
+                Abs => "fp.abs".to_string(),
+                Neg => "fp.neg".to_string(),
+                IsNormal => "fp.isNormal".to_string(),
+                IsSubnormal => "fp.isSubnormal".to_string(),
+                IsZero => "fp.isZero".to_string(),
+                IsInfinite => "fp.isInfinite".to_string(),
+                IsNaN => "fp.isNaN".to_string(),
+                IsNegative => "fp.isNegative".to_string(),
+                IsPositive => "fp.isPositive".to_string(),
+                FromIEEE(ebits, sbits) => format!("(_ to_fp {} {})", ebits, sbits),
+            };
+            write_op(buf, &op, &[exp])
+        }
+        FPRoundingUnary(op, rm, exp) => {
+            use super::smtlib::FPRoundingUnary::*;
+            let op = match op {
+                Sqrt => "fp.sqrt".to_string(),
+                RoundToIntegral => "fp.roundToIntegral".to_string(),
+                Convert(ebits, sbits) | FromSigned(ebits, sbits) => format!("(_ to_fp {} {})", ebits, sbits),
+                FromUnsigned(ebits, sbits) => format!("(_ to_fp_unsigned {} {})", ebits, sbits),
+                ToSigned(sz) => format!("(_ fp.to_sbv {})", sz),
+                ToUnsigned(sz) => format!("(_ fp.to_ubv {})", sz),
+            };
+            write_op(buf, &op, &[rm, exp])
+        }
+        FPBinary(op, lhs, rhs) => {
+            use super::smtlib::FPBinary::*;
+            let op = match op {
+                Rem => "fp.rem",
+                Min => "fp.min",
+                Max => "fp.max",
+                Leq => "fp.leq",
+                Lt => "fp.lt",
+                Geq => "fp.geq",
+                Gt => "fp.gt",
+                Eq => "fp.eq",
+            };
+            write_op(buf, op, &[lhs, rhs])
+        }
+        FPRoundingBinary(op, rm, lhs, rhs) => {
+            use super::smtlib::FPRoundingBinary::*;
+            let op = match op {
+                Add => "fp.add",
+                Sub => "fp.sub",
+                Mul => "fp.mul",
+                Div => "fp.div",
+            };
+            write_op(buf, op, &[rm, lhs, rhs])
+        }
+        FPfma(rm, x, y, z) => write_op(buf, "fp.fma", &[rm, x, y, z]),
+    }
+}
+
+/// Write a definition as an SMTLIB command. A constant definition
+/// needs the type of its body, which `tcx` and `ftcx` must give,
+/// otherwise this fails with [std::io::ErrorKind::InvalidData].
+pub(crate) fn write_def(
+    buf: &mut dyn Write,
+    def: &Def,
+    tcx: &HashMap<Sym, Ty>,
+    ftcx: &HashMap<Sym, (Vec<Ty>, Ty)>,
+) -> std::io::Result<()> {
+    match def {
+        Def::DeclareConst(v, ty) => {
+            write!(buf, "(declare-const v{} ", v)?;
+            write_ty(buf, ty)?;
+            write!(buf, ")")
+        }
+        Def::DeclareFun(v, arg_tys, result_ty) => {
+            write!(buf, "(declare-fun v{} (", v)?;
+            for (i, ty) in arg_tys.iter().enumerate() {
+                if i > 0 {
+                    write!(buf, " ")?
+                }
+                write_ty(buf, ty)?
+            }
+            write!(buf, ") ")?;
+            write_ty(buf, result_ty)?;
+            write!(buf, ")")
+        }
+        Def::DefineConst(v, exp) => {
+            let ty = exp.infer(tcx, ftcx).ok_or_else(|| {
+                std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Could not infer type of v{}", v))
+            })?;
+            write!(buf, "(define-fun v{} () ", v)?;
+            write_ty(buf, &ty)?;
+            write!(buf, " ")?;
+            write_exp(buf, exp)?;
+            write!(buf, ")")
+        }
+        Def::DefineEnum(name, size) => {
+            write!(buf, "(declare-datatypes ((e{} 0)) ((", name)?;
+            for i in 0..*size {
+                if i > 0 {
+                    write!(buf, " ")?
+                }
+                write!(buf, "(e{}_{})", name, i)?
+            }
+            write!(buf, ")))")
+        }
+        Def::Assert(exp) => {
+            write!(buf, "(assert ")?;
+            write_exp(buf, exp)?;
+            write!(buf, ")")
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Sexp {
+    Atom(String),
+    List(Vec<Sexp>),
+}
+
+fn parse_sexp(s: &str) -> Option<Sexp> {
+    fn parse(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<Sexp> {
+        while chars.peek()?.is_whitespace() {
+            chars.next();
+        }
+        match chars.next()? {
+            '(' => {
+                let mut list = Vec::new();
+                loop {
+                    while chars.peek()?.is_whitespace() {
+                        chars.next();
+                    }
+                    if *chars.peek()? == ')' {
+                        chars.next();
+                        return Some(Sexp::List(list));
+                    }
+                    list.push(parse(chars)?)
+                }
+            }
+            ')' => None,
+            c @ ('|' | '"') => {
+                let mut atom = String::new();
+                loop {
+                    match chars.next()? {
+                        end if end == c => return Some(Sexp::Atom(atom)),
+                        next => atom.push(next),
+                    }
+                }
+            }
+            c => {
+                let mut atom = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || next == '(' || next == ')' {
+                        break;
+                    }
+                    atom.push(next);
+                    chars.next();
+                }
+                Some(Sexp::Atom(atom))
+            }
+        }
+    }
+    parse(&mut s.chars().peekable())
+}
+
+/// Bits of an SMTLIB bitvector literal, least significant first
+fn parse_bits(s: &str) -> Option<Vec<bool>> {
+    if let Some(bin) = s.strip_prefix("#b") {
+        bin.chars()
+            .rev()
+            .map(|c| match c {
+                '0' => Some(false),
+                '1' => Some(true),
+                _ => None,
+            })
+            .collect()
+    } else if let Some(hex) = s.strip_prefix("#x") {
+        let mut bits = Vec::new();
+        for c in hex.chars().rev() {
+            let digit = c.to_digit(16)?;
+            bits.extend((0..4).map(|n| (digit >> n) & 1 == 1))
+        }
+        Some(bits)
+    } else {
+        None
+    }
+}
+
+fn value_from_sexp(value: &Sexp, ty: &Ty) -> Option<Exp<Sym>> {
+    match (value, ty) {
+        (Sexp::Atom(b), Ty::Bool) => b.parse().ok().map(Exp::Bool),
+        (Sexp::Atom(bv), Ty::BitVec(sz)) => {
+            let bits = parse_bits(bv)?;
+            if bits.len() != *sz as usize {
+                None
+            } else if *sz > 64 {
+                Some(Exp::Bits(bits))
+            } else {
+                let n = bits.iter().rev().fold(0, |n, bit| (n << 1) | (*bit as u64));
+                Some(Exp::Bits64(B64::new(n, *sz)))
+            }
+        }
+        (Sexp::Atom(member), Ty::Enum(enum_id)) => {
+            let (name, member) = member.strip_prefix('e')?.split_once('_')?;
+            if name != enum_id.to_name().to_string() {
+                return None;
+            }
+            Some(Exp::Enum(EnumMember { enum_id: *enum_id, member: member.parse().ok()? }))
+        }
+        _ => None,
+    }
+}
+
+/// Runs the cvc5 executable, named by the `ISLA_CVC5` environment
+/// variable or found on the path, and talks to it in SMTLIB over a
+/// pipe.
+pub struct Cvc5 {
+    process: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    tcx: HashMap<Sym, Ty>,
+    ftcx: HashMap<Sym, (Vec<Ty>, Ty)>,
+    /// The variables each constant definition depends on
+    deps: HashMap<Sym, Vec<Sym>>,
+    /// Variables mentioned by some assertion, directly or through a
+    /// definition
+    used: HashSet<Sym>,
+    /// The used variables when each open scope was pushed
+    scopes: Vec<HashSet<Sym>>,
+    assumptions: u32,
+}
+
+impl Cvc5 {
+    pub fn new() -> Result<Self, ExecError> {
+        Cvc5::with_path(&std::env::var("ISLA_CVC5").unwrap_or_else(|_| "cvc5".to_string()))
+    }
+
+    /// Run the cvc5 executable at `path`
+    pub fn with_path(path: &str) -> Result<Self, ExecError> {
+        let mut process = Command::new(path)
+            .args(["--lang=smt2", "--incremental", "--produce-models"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|err| ExecError::Z3Error(format!("Failed to start cvc5 ({}): {}", path, err)))?;
+        let stdin = process.stdin.take().unwrap();
+        let stdout = BufReader::new(process.stdout.take().unwrap());
+        let mut cvc5 = Cvc5 {
+            process,
+            stdin,
+            stdout,
+            tcx: HashMap::new(),
+            ftcx: HashMap::new(),
+            deps: HashMap::new(),
+            used: HashSet::new(),
+            scopes: Vec::new(),
+            assumptions: 0,
+        };
+        cvc5.send("(set-logic ALL)")?;
+        Ok(cvc5)
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), ExecError> {
+        writeln!(self.stdin, "{}", command)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|err| ExecError::Z3Error(format!("Failed to write to cvc5: {}", err)))
+    }
+
+    /// Read one complete response, which may span several lines
+    fn response(&mut self) -> Result<String, ExecError> {
+        let mut response = String::new();
+        loop {
+            let mut line = String::new();
+            match self.stdout.read_line(&mut line) {
+                Ok(0) => return Err(ExecError::Z3Error("cvc5 exited unexpectedly".to_string())),
+                Ok(_) => (),
+                Err(err) => return Err(ExecError::Z3Error(format!("Failed to read from cvc5: {}", err))),
+            }
+            response.push_str(&line);
+            let depth = response.chars().fold(0, |depth, c| match c {
+                '(' => depth + 1,
+                ')' => depth - 1,
+                _ => depth,
+            });
+            if depth <= 0 && !response.trim().is_empty() {
+                return Ok(response.trim().to_string());
+            }
+        }
+    }
+
+    fn check(&mut self, command: &str) -> SmtResult {
+        if self.send(command).is_err() {
+            return SmtResult::Unknown;
+        }
+        match self.response().as_deref() {
+            Ok("sat") => SmtResult::Sat,
+            Ok("unsat") => SmtResult::Unsat,
+            _ => SmtResult::Unknown,
+        }
+    }
+}
+
+/// Mark `v` as used, along with every variable its definition depends
+/// on. Definitions can form very long chains, so this uses a worklist
+/// rather than recursion.
+fn mark_used(used: &mut HashSet<Sym>, deps: &HashMap<Sym, Vec<Sym>>, v: Sym) {
+    let mut worklist = vec![v];
+    while let Some(v) = worklist.pop() {
+        if used.insert(v) {
+            worklist.extend(deps.get(&v).into_iter().flatten().copied())
+        }
+    }
+}
+
+impl Drop for Cvc5 {
+    fn drop(&mut self) {
+        let _ = self.send("(exit)");
+        let _ = self.process.wait();
+    }
+}
+
+impl SolverBackend for Cvc5 {
+    fn add(&mut self, def: &Def) -> Result<(), ExecError> {
+        let mut buf = Vec::new();
+        write_def(&mut buf, def, &self.tcx, &self.ftcx)
+            .map_err(|err| ExecError::Z3Error(format!("Failed to write definition for cvc5: {}", err)))?;
+        match def {
+            Def::DeclareConst(v, ty) => {
+                self.tcx.insert(*v, ty.clone());
+            }
+            Def::DeclareFun(v, arg_tys, result_ty) => {
+                self.ftcx.insert(*v, (arg_tys.clone(), result_ty.clone()));
+            }
+            Def::DefineConst(v, exp) => {
+                let ty = exp
+                    .infer(&self.tcx, &self.ftcx)
+                    .ok_or_else(|| ExecError::Type(format!("definition of v{}", v), SourceLoc::unknown()))?;
+                self.tcx.insert(*v, ty);
+                self.deps.insert(*v, exp.variables().into_iter().collect());
+            }
+            Def::Assert(exp) => {
+                for v in exp.variables() {
+                    mark_used(&mut self.used, &self.deps, v)
+                }
+            }
+            Def::DefineEnum(..) => (),
+        }
+        self.send(&String::from_utf8_lossy(&buf))
+    }
+
+    fn check_sat(&mut self) -> SmtResult {
+        self.check("(check-sat)")
+    }
+
+    fn check_sat_with(&mut self, exp: &Exp<Sym>) -> SmtResult {
+        let mut buf = Vec::new();
+        write!(buf, "(define-fun a{} () Bool ", self.assumptions).unwrap();
+        write_exp(&mut buf, exp).unwrap();
+        write!(buf, ")").unwrap();
+        if self.send(&String::from_utf8_lossy(&buf)).is_err() {
+            return SmtResult::Unknown;
+        }
+        let result = self.check(&format!("(check-sat-assuming (a{}))", self.assumptions));
+        self.assumptions += 1;
+        result
+    }
+
+    fn set_timeout(&mut self, millis: Option<u32>) {
+        let _ = self.send(&format!("(set-option :tlimit-per {})", millis.unwrap_or(0)));
+    }
+
+    fn push(&mut self) {
+        self.scopes.push(self.used.clone());
+        let _ = self.send("(push 1)");
+    }
+
+    fn pop(&mut self) {
+        if let Some(used) = self.scopes.pop() {
+            self.used = used
+        }
+        let _ = self.send("(pop 1)");
+    }
+
+    fn get_value(&mut self, exp: &Exp<Sym>, complete: bool) -> Result<Option<Exp<Sym>>, ExecError> {
+        if let Exp::Var(v) = exp {
+            if !complete && !self.used.contains(v) {
+                return Ok(None);
+            }
+        }
+        let ty = exp
+            .infer(&self.tcx, &self.ftcx)
+            .ok_or_else(|| ExecError::Type("get_value".to_string(), SourceLoc::unknown()))?;
+        let mut buf = Vec::new();
+        write!(buf, "(get-value (").unwrap();
+        write_exp(&mut buf, exp).unwrap();
+        write!(buf, "))").unwrap();
+        self.send(&String::from_utf8_lossy(&buf))?;
+        let response = self.response()?;
+        match parse_sexp(&response) {
+            Some(Sexp::List(mut pairs)) if pairs.len() == 1 => match pairs.pop() {
+                Some(Sexp::List(mut pair)) if pair.len() == 2 => {
+                    let value = pair.pop().unwrap();
+                    match value_from_sexp(&value, &ty) {
+                        Some(value) => Ok(Some(value)),
+                        None => Err(ExecError::Type("get_value".to_string(), SourceLoc::unknown())),
+                    }
+                }
+                _ => Err(ExecError::Z3Error(response)),
+            },
+            _ => Err(ExecError::Z3Error(response)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::Name;
+    use crate::smt::EnumId;
+
+    #[test]
+    fn parse_values() {
+        let response = parse_sexp("((v3 #b0110) (|v 4| #x1f))").unwrap();
+        let Sexp::List(pairs) = response else { panic!("not a list") };
+        let Sexp::List(pair) = &pairs[0] else { panic!("not a pair") };
+        assert_eq!(value_from_sexp(&pair[1], &Ty::BitVec(4)), Some(Exp::Bits64(B64::new(0b0110, 4))));
+        let Sexp::List(pair) = &pairs[1] else { panic!("not a pair") };
+        assert_eq!(pair[0], Sexp::Atom("v 4".to_string()));
+        assert_eq!(value_from_sexp(&pair[1], &Ty::BitVec(8)), Some(Exp::Bits64(B64::new(0x1f, 8))));
+        assert_eq!(value_from_sexp(&pair[1], &Ty::BitVec(4)), None);
+
+        let enum_id = EnumId::from_name(Name::from_u32(7));
+        assert_eq!(
+            value_from_sexp(&Sexp::Atom("e7_2".to_string()), &Ty::Enum(enum_id)),
+            Some(Exp::Enum(EnumMember { enum_id, member: 2 }))
+        );
+        assert_eq!(value_from_sexp(&Sexp::Atom("true".to_string()), &Ty::Bool), Some(Exp::Bool(true)));
+    }
+
+    #[test]
+    fn write_defs() {
+        let x = Sym::from_u32(0);
+        let y = Sym::from_u32(1);
+        let mut tcx = HashMap::new();
+        tcx.insert(x, Ty::BitVec(4));
+        let mut buf = Vec::new();
+        let def = Def::DefineConst(
+            y,
+            Exp::Bvadd(Box::new(Exp::Var(x)), Box::new(Exp::Bits(vec![true, false, false, false]))),
+        );
+        write_def(&mut buf, &def, &tcx, &HashMap::new()).unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap(), "(define-fun v1 () (_ BitVec 4) (bvadd v0 #x1))");
+
+        let mut buf = Vec::new();
+        write_def(&mut buf, &Def::DefineEnum(Name::from_u32(3), 2), &tcx, &HashMap::new()).unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap(), "(declare-datatypes ((e3 0)) (((e3_0) (e3_1))))");
+
+        // The type of x is not known
+        let err = write_def(&mut Vec::new(), &def, &HashMap::new(), &HashMap::new()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData)
+    }
+
+    #[test]
+    fn missing_cvc5() {
+        assert!(matches!(Cvc5::with_path("/nonexistent/cvc5"), Err(ExecError::Z3Error(_))))
+    }
+
+    #[test]
+    fn mark_long_chain_used() {
+        // Each variable is defined in terms of the previous one
+        let deps: HashMap<Sym, Vec<Sym>> =
+            (1..1_000_000).map(|n| (Sym::from_u32(n), vec![Sym::from_u32(n - 1)])).collect();
+        let mut used = HashSet::new();
+        mark_used(&mut used, &deps, Sym::from_u32(999_999));
+        assert_eq!(used.len(), 1_000_000)
+    }
+}
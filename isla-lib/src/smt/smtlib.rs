@@ -33,6 +33,7 @@
 //! theory of quantifier-free bitvectors and arrays.
 
 use ahash;
+use serde::{Deserialize, Serialize};
 
 use std::collections::{HashMap, HashSet};
 use std::ops::{Add, BitAnd, BitOr, BitXor, Deref, Shr, Sub};
@@ -42,7 +43,7 @@ use crate::bitvector::b64::B64;
 use crate::bitvector::{ParsedBits, BV};
 use crate::ir::Name;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Ty {
     Bool,
     BitVec(u32),
@@ -52,7 +53,7 @@ pub enum Ty {
     RoundingMode,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum FPRoundingMode {
     RoundNearestTiesToEven,
     RoundNearestTiesToAway,
@@ -61,7 +62,7 @@ pub enum FPRoundingMode {
     RoundTowardZero,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum FPConstant {
     NaN,
     /// If negative is true, then -∞ rather than +∞, and similarly for the Zero constructor
@@ -73,7 +74,7 @@ pub enum FPConstant {
     },
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum FPUnary {
     Abs,
     Neg,
@@ -99,7 +100,7 @@ impl FPUnary {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum FPRoundingUnary {
     Sqrt,
     RoundToIntegral,
@@ -126,7 +127,7 @@ impl FPRoundingUnary {
 /// Note that SMTLIB is slightly inconsistent w.r.t. whether it uses
 /// le or leq as a suffix for less than or equal to between bitvectors
 /// and floating point. We follow SMTLIB exactly here.
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum FPBinary {
     Rem,
     Min,
@@ -146,7 +147,7 @@ impl FPBinary {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum FPRoundingBinary {
     Add,
     Sub,
@@ -154,7 +155,7 @@ pub enum FPRoundingBinary {
     Div,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Exp<V> {
     Var(V),
     Bits(Vec<bool>),
@@ -1128,7 +1129,7 @@ impl Exp<Sym> {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Def {
     DeclareConst(Sym, Ty),
     DeclareFun(Sym, Vec<Ty>, Ty),
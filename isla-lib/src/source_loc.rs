@@ -39,7 +39,7 @@ pub static GREEN: &str = "\x1b[0;32m";
 pub static BLUE: &str = "\x1b[0;34m";
 pub static NO_COLOR: &str = "\x1b[0m";
 
-#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct SourceLoc {
     file: i16,
     line1: u32,
@@ -211,6 +211,20 @@ impl SourceLoc {
         }
     }
 
+    /// The start of the first line this location covers
+    pub fn line(self) -> Self {
+        SourceLoc { char1: 0, line2: self.line1, char2: 0, ..self }
+    }
+
+    /// Just the file and first line of this location, as `file:line`
+    pub fn line_string(self, files: &[&str]) -> String {
+        if let Some(file) = TryInto::<usize>::try_into(self.file).ok().and_then(|i| files.get(i)) {
+            format!("{}:{}", file, self.line1)
+        } else {
+            format!("{}", self.line1)
+        }
+    }
+
     pub fn location_string(self, files: &[&str]) -> String {
         if let Some(file) = TryInto::<usize>::try_into(self.file).ok().and_then(|i| files.get(i)) {
             format!("{} {}:{} - {}:{}", file, self.line1, self.char1, self.line2, self.char2)
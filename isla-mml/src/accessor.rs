@@ -33,7 +33,9 @@
 //! memory model.
 
 use std::borrow::Borrow;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::error;
+use std::fmt;
 
 use isla_lib::bitvector::{required_index_bits, BV};
 use isla_lib::ir::{SharedState, Val};
@@ -42,7 +44,7 @@ use isla_lib::smt::{Event, Sym};
 use isla_lib::zencode;
 
 use crate::memory_model::constants::*;
-use crate::memory_model::{Accessor, AccessorInfo, Name, Symtab};
+use crate::memory_model::{Accessor, AccessorInfo, Accessors, Error, MemoryModel, Name, Symtab};
 use crate::smt::{Sexp, SexpArena, SexpId};
 
 /// Because isla-axiomatic imports isla-mml, we don't know the
@@ -67,12 +69,102 @@ pub enum AccessorTree<'a> {
 
 static ACCESSORTREE_LEAF: AccessorTree<'static> = AccessorTree::Leaf;
 
+/// The default limit on how deeply `.match` accessors may be nested
+/// within each other, see [AccessorTree::from_accessors].
+pub const DEFAULT_MAX_MATCH_DEPTH: usize = 32;
+
+#[derive(Debug)]
+pub enum AccessorTreeErrorKind {
+    /// A `Match(n)` with fewer than `n` constructors before it
+    MatchWithoutCtors { arms: usize, available: usize },
+    /// Constructors at the end of the sequence that no `Match` consumes
+    TrailingCtors { count: usize },
+    /// Matches nested more deeply than the configured limit
+    TooDeep { depth: usize, limit: usize },
+    /// A subvector or extension that does not fit the width of the
+    /// value it is applied to in some event
+    OutOfRange { accessor: String, width: u32 },
+}
+
+/// An error for an accessor sequence that does not form a valid
+/// accessor tree, which should not happen for any sequence produced
+/// by the parser, or that cannot be applied to an event's value.
+#[derive(Debug)]
+pub struct AccessorTreeError {
+    pub kind: AccessorTreeErrorKind,
+    /// The rendered accessor sequence
+    pub sequence: Vec<String>,
+    /// The index of the accessor in the sequence where the error was found
+    pub position: usize,
+}
+
+impl fmt::Display for AccessorTreeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use AccessorTreeErrorKind::*;
+        write!(f, "Malformed accessor sequence [{}] at position {}: ", self.sequence.join(", "), self.position)?;
+        match &self.kind {
+            MatchWithoutCtors { arms, available } => {
+                write!(f, "match expects {} constructors, but only {} precede it", arms, available)
+            }
+            TrailingCtors { count } => write!(f, "{} constructors are not used by any match", count),
+            TooDeep { depth, limit } => {
+                write!(f, "matches are nested {} deep, which exceeds the limit of {}", depth, limit)
+            }
+            OutOfRange { accessor, width } => {
+                write!(f, "{} cannot be applied to a value of width {}", accessor, width)
+            }
+        }
+    }
+}
+
+impl error::Error for AccessorTreeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        None
+    }
+}
+
+fn describe_accessor(accessor: &Accessor, symtab: &Symtab) -> String {
+    use Accessor::*;
+    match accessor {
+        Address => ".address()".to_string(),
+        Bits(bv) => format!("bits({})", bv.len()),
+        Ctor(ctor) => format!("{} =>", &symtab[*ctor]),
+        Data => ".data()".to_string(),
+        Exts(n) => format!(".exts({})", n),
+        Extz(n) => format!(".extz({})", n),
+        Field(name) => format!(".{}", &symtab[*name]),
+        Id(id) => symtab[*id].to_string(),
+        Is(name) => format!("is {}", &symtab[*name]),
+        Length(n) => format!("length({})", n),
+        Match(n) => format!("match({})", n),
+        Opcode => ".opcode()".to_string(),
+        Return => ".return()".to_string(),
+        Subvec(hi, lo) => format!("[{} .. {}]", hi, lo),
+        Tuple(n) => format!(".{}", n),
+        Wildcard => "_ =>".to_string(),
+    }
+}
+
 impl<'a> AccessorTree<'a> {
-    pub fn from_accessors(accessors: &'a [Accessor]) -> Self {
+    /// Build the tree for an accessor sequence, which is stored
+    /// innermost accessor first, with the constructors for each match
+    /// arm preceding the `Match` that consumes them. Matches nested
+    /// more than `max_depth` deep are rejected.
+    pub fn from_accessors(
+        accessors: &'a [Accessor],
+        symtab: &Symtab,
+        max_depth: usize,
+    ) -> Result<Self, AccessorTreeError> {
+        let err = |kind, position| AccessorTreeError {
+            kind,
+            sequence: accessors.iter().map(|acc| describe_accessor(acc, symtab)).collect(),
+            position,
+        };
+
         let mut constructor_stack = Vec::new();
         let mut cur = AccessorTree::Leaf;
 
-        for accessor in accessors {
+        for (position, accessor) in accessors.iter().enumerate() {
             match accessor {
                 Accessor::Ctor(ctor) => {
                     constructor_stack.push((Some(*ctor), cur));
@@ -83,16 +175,40 @@ impl<'a> AccessorTree<'a> {
                     cur = AccessorTree::Leaf
                 }
                 Accessor::Match(n) => {
-                    let mut arms = constructor_stack.split_off(constructor_stack.len() - n);
-                    cur = AccessorTree::Match { arms: arms.drain(..).collect() }
+                    if *n > constructor_stack.len() {
+                        let kind =
+                            AccessorTreeErrorKind::MatchWithoutCtors { arms: *n, available: constructor_stack.len() };
+                        return Err(err(kind, position));
+                    }
+                    let arms = constructor_stack.split_off(constructor_stack.len() - n);
+                    cur = AccessorTree::Match { arms: arms.into_iter().collect() };
+                    let depth = cur.depth();
+                    if depth > max_depth {
+                        return Err(err(AccessorTreeErrorKind::TooDeep { depth, limit: max_depth }, position));
+                    }
                 }
                 acc => cur = AccessorTree::Node { elem: acc, child: Box::new(cur) },
             }
         }
 
-        assert!(constructor_stack.is_empty());
+        if !constructor_stack.is_empty() {
+            let kind = AccessorTreeErrorKind::TrailingCtors { count: constructor_stack.len() };
+            return Err(err(kind, accessors.len()));
+        }
 
-        cur
+        Ok(cur)
+    }
+
+    /// The number of matches nested along the deepest path through the tree
+    pub fn depth(&self) -> usize {
+        let mut tree = self;
+        loop {
+            match tree {
+                AccessorTree::Node { child, .. } => tree = child,
+                AccessorTree::Match { arms } => return 1 + arms.values().map(AccessorTree::depth).max().unwrap_or(0),
+                AccessorTree::Leaf => return 0,
+            }
+        }
     }
 }
 
@@ -127,6 +243,9 @@ struct View<'ev, B> {
     name: Option<String>,
     // This map is used for any 'special' attributes an event may have, accessed using `.attr()`.
     special: HashMap<String, AccessorVal<'ev, B>>,
+    // Fields of a barrier or cache-op operand that can be accessed
+    // directly on the event as `.field`, see `OPERAND_FIELDS`.
+    operand_fields: HashMap<&'static str, &'ev Val<B>>,
     // If we have an abstract event that took multiple arguments, they
     // will be stored here until we select one.
     values: Option<&'ev [Val<B>]>,
@@ -136,19 +255,19 @@ struct View<'ev, B> {
 
 impl<'ev, B: BV> Default for View<'ev, B> {
     fn default() -> Self {
-        View { name: None, special: HashMap::new(), values: None, value: None }
+        View { name: None, special: HashMap::new(), operand_fields: HashMap::new(), values: None, value: None }
     }
 }
 
 macro_rules! access_extension {
     ($id: ident, $smt_extension: ident, $concrete_extension: path) => {
-        fn $id(&mut self, n: u32, types: &HashMap<Sym, Ty>, sexps: &mut SexpArena) {
+        /// Returns the width of the value if it is wider than `n`
+        fn $id(&mut self, n: u32, types: &HashMap<Sym, Ty>, sexps: &mut SexpArena) -> Result<(), u32> {
             if let Some(len) = self.simplify_to_sexp_or_bits(types, sexps) {
                 if n == len {
-                    return;
+                    return Ok(());
                 } else if n < len {
-                    *self = Self::default();
-                    return;
+                    return Err(len);
                 }
                 match self.value {
                     Some(AccessorVal::Sexp(sexp)) => {
@@ -172,6 +291,7 @@ macro_rules! access_extension {
             } else {
                 *self = Self::default()
             }
+            Ok(())
         }
     };
 }
@@ -195,6 +315,26 @@ impl<'ev, B: BV> View<'ev, B> {
         self
     }
 
+    fn with_operand_fields(mut self, outcome_name: &str, shared_state: &SharedState<B>) -> Self {
+        let operand = match self.value {
+            Some(AccessorVal::Val(operand)) => operand,
+            _ => return self,
+        };
+        for (outcome, special, fields) in OPERAND_FIELDS {
+            if *outcome != outcome_name {
+                continue;
+            }
+            if let Some(value) = fields.iter().find_map(|field| operand_field(operand, field, shared_state)) {
+                // Like memory events, the address of a cache op is also available as `.address()`
+                if *special == "address" {
+                    self.special.insert(special.to_string(), AccessorVal::Val(value));
+                }
+                self.operand_fields.insert(special, value);
+            }
+        }
+        self
+    }
+
     fn with_value(mut self, value: &'ev Val<B>) -> Self {
         self.value = Some(AccessorVal::Val(value));
         self
@@ -260,11 +400,10 @@ impl<'ev, B: BV> View<'ev, B> {
             let ctor_name = shared_state.symtab.to_str_demangled(*ctor_name);
             self.set_value(value);
             let n = &symtab.lookup(&zencode::decode(ctor_name));
-            return match arms.get(n) {
-                Some(accessor_tree) => accessor_tree,
-                // If the constructor isn't in the match arms, return the wildcard using None
-                None => &arms[&None],
-            };
+            // If the constructor isn't in the match arms, use the wildcard arm (keyed by None) if there is one
+            if let Some(accessor_tree) = arms.get(n).or_else(|| arms.get(&None)) {
+                return accessor_tree;
+            }
         }
 
         *self = Self::default();
@@ -298,6 +437,10 @@ impl<'ev, B: BV> View<'ev, B> {
                     }
                 }
             }
+            if let Some(value) = self.operand_fields.get(sym) {
+                self.set_value(value);
+                return;
+            }
         }
         *self = Self::default()
     }
@@ -322,8 +465,19 @@ impl<'ev, B: BV> View<'ev, B> {
         }
     }
 
-    fn access_subvec(&mut self, n: u32, m: u32, types: &HashMap<Sym, Ty>, sexps: &mut SexpArena) {
-        self.simplify_to_sexp_or_bits(types, sexps);
+    /// Extract bits `n` down to `m` from the current value. Constant
+    /// accessors with `n < m` are rejected by the parser, but the
+    /// width of the value is only known here, so this returns the
+    /// width if the range does not fit within it.
+    fn access_subvec(&mut self, n: u32, m: u32, types: &HashMap<Sym, Ty>, sexps: &mut SexpArena) -> Result<(), u32> {
+        match self.simplify_to_sexp_or_bits(types, sexps) {
+            Some(len) if m <= n && n < len => (),
+            Some(len) => return Err(len),
+            None => {
+                *self = Self::default();
+                return Ok(());
+            }
+        }
 
         match self.value {
             Some(AccessorVal::Sexp(sexp)) => {
@@ -332,15 +486,13 @@ impl<'ev, B: BV> View<'ev, B> {
                 let extract = sexps.alloc(Sexp::List(vec![sexps.underscore, sexps.extract, n, m]));
                 self.set_sexp(sexps.alloc(Sexp::List(vec![extract, sexp])))
             }
-            Some(AccessorVal::Bits(bv)) => {
-                if let Some(extracted) = bv.extract(n, m) {
-                    self.set_bits(extracted)
-                } else {
-                    *self = Self::default()
-                }
-            }
+            Some(AccessorVal::Bits(bv)) => match bv.extract(n, m) {
+                Some(extracted) => self.set_bits(extracted),
+                None => return Err(bv.len()),
+            },
             _ => *self = Self::default(),
         }
+        Ok(())
     }
 
     access_extension!(access_extz, zero_extend, B::zero_extend);
@@ -348,7 +500,7 @@ impl<'ev, B: BV> View<'ev, B> {
 }
 
 fn generate_ite_chain<B: BV>(
-    event_values: &HashMap<Name, Vec<(View<'_, B>, &AccessorTree)>>,
+    event_values: &HashMap<Name, Vec<View<'_, B>>>,
     ty: SexpId,
     index_width: Option<u32>,
     sexps: &mut SexpArena,
@@ -357,7 +509,7 @@ fn generate_ite_chain<B: BV>(
 
     for (ev, views) in event_values {
         if let Some(width) = index_width {
-            for (i, (event_view, _)) in views.iter().enumerate() {
+            for (i, event_view) in views.iter().enumerate() {
                 let result = event_view.value.and_then(|v| v.to_sexp(sexps));
                 if let Some(id) = result {
                     let ev = sexps.alloc(Sexp::Atom(*ev));
@@ -370,7 +522,7 @@ fn generate_ite_chain<B: BV>(
             }
         } else {
             assert!(views.len() == 1);
-            let result = views[0].0.value.and_then(|v| v.to_sexp(sexps));
+            let result = views[0].value.and_then(|v| v.to_sexp(sexps));
             if let Some(id) = result {
                 let ev = sexps.alloc(Sexp::Atom(*ev));
                 let comparison = sexps.alloc(Sexp::List(vec![sexps.eq, ev, sexps.ev1]));
@@ -387,8 +539,8 @@ pub fn infer_accessor_type(accessors: &[Accessor], sexps: &mut SexpArena) -> Sex
 
     if let Some(accessor) = accessors.iter().next() {
         match accessor {
-            Subvec(hi, lo) => sexps.alloc_bitvec((hi - lo) + 1),
-            Extz(n) | Exts(n) => sexps.alloc_bitvec(*n),
+            Subvec(hi, lo) if lo <= hi => sexps.alloc_bitvec((hi - lo) + 1),
+            Extz(n) | Exts(n) if *n > 0 => sexps.alloc_bitvec(*n),
             _ => sexps.alloc_bitvec(64),
         }
     } else {
@@ -396,6 +548,31 @@ pub fn infer_accessor_type(accessors: &[Accessor], sexps: &mut SexpArena) -> Sex
     }
 }
 
+/// The special names defined on barrier and cache maintenance events,
+/// so models can distinguish e.g. `DMB ISH` from `DMB SY` without
+/// matching on how the Sail model lays out the operand. Each entry is
+/// the outcome, the special name, and the operand fields (tried in
+/// order) that it is taken from.
+const OPERAND_FIELDS: &[(&str, &str, &[&str])] = &[
+    ("sail_barrier", "domain", &["domain"]),
+    ("sail_barrier", "types", &["types"]),
+    ("sail_cache_op", "cacheop", &["cacheop", "kind"]),
+    ("sail_cache_op", "address", &["address", "vaddress"]),
+];
+
+/// Find a field of an outcome operand by its name in the Sail source,
+/// looking through any union constructors wrapping the struct.
+fn operand_field<'ev, B: BV>(value: &'ev Val<B>, field: &str, shared_state: &SharedState<B>) -> Option<&'ev Val<B>> {
+    match value {
+        Val::Ctor(_, value) => operand_field(value, field, shared_state),
+        Val::Struct(fields) => fields
+            .iter()
+            .find(|(name, _)| zencode::decode(shared_state.symtab.to_str_demangled(**name)) == field)
+            .map(|(_, value)| value),
+        _ => None,
+    }
+}
+
 fn event_view<'ev, B: BV>(ev: &'ev Event<B>, opcode: Option<B>, shared_state: &SharedState<B>) -> Option<View<'ev, B>> {
     match ev {
         Event::ReadMem { address, value, read_kind, .. } => Some(
@@ -415,13 +592,91 @@ fn event_view<'ev, B: BV>(ev: &'ev Event<B>, opcode: Option<B>, shared_state: &S
         Event::Abstract { name: outcome_name, primitive, args, return_value } if *primitive => {
             // This will be the original name of the outcome in the Sail source
             let outcome_name = zencode::decode(shared_state.symtab.to_str_demangled(*outcome_name));
-            Some(View::new(opcode).with_name(outcome_name).with_values(args).with_special("return", return_value))
+            let view = View::new(opcode).with_values(args).with_operand_fields(&outcome_name, shared_state);
+            Some(view.with_name(outcome_name).with_special("return", return_value))
         }
         Event::ReadReg(_, _, value) | Event::WriteReg(_, _, value) => Some(View::new(opcode).with_value(value)),
         _ => None,
     }
 }
 
+/// The names an architecture makes available to memory model
+/// accessors, i.e. outcomes, struct fields, and union constructors,
+/// decoded as they would be written in a cat file.
+pub struct InterfaceNames {
+    names: HashSet<String>,
+}
+
+impl InterfaceNames {
+    pub fn new<B: BV>(shared_state: &SharedState<B>) -> Self {
+        let decode = |n: &isla_lib::ir::Name| zencode::decode(shared_state.symtab.to_str_demangled(*n));
+
+        // The names given to memory events by `event_view`
+        let mut names: HashSet<String> = ["sail_mem_read", "sail_mem_write"].iter().map(|s| s.to_string()).collect();
+        names.extend(OPERAND_FIELDS.iter().map(|(_, special, _)| special.to_string()));
+
+        names.extend(shared_state.externs.keys().map(decode));
+        for fields in shared_state.type_info.structs.values() {
+            names.extend(fields.keys().map(decode))
+        }
+        for ctors in shared_state.type_info.unions.values() {
+            names.extend(ctors.iter().map(|(ctor, _)| decode(ctor)))
+        }
+
+        InterfaceNames { names }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.names.contains(name)
+    }
+}
+
+/// Check that every name listed in a `requires` declaration is
+/// defined by the architecture. Returns an error for each missing
+/// name, so they can all be reported at once.
+pub fn check_requires(mm: &MemoryModel, interface: &InterfaceNames, symtab: &Symtab) -> Result<(), Vec<Error>> {
+    let errors: Vec<Error> = mm
+        .requires()
+        .filter(|name| !interface.contains(&symtab[name.node]))
+        .map(|name| Error {
+            message: format!(
+                "Memory model requires '{}', which is not defined by the architecture",
+                &symtab[name.node]
+            ),
+            file: name.file,
+            span: name.span,
+        })
+        .collect();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Returns the field, constructor, and outcome names used by
+/// accessors that the architecture does not define. Such accessors
+/// will always produce default values.
+pub fn unresolved_accessor_names(accessors: &Accessors, interface: &InterfaceNames, symtab: &Symtab) -> Vec<String> {
+    let mut unresolved = BTreeSet::new();
+
+    for info in accessors.values() {
+        for accessor in info.accessors {
+            match accessor {
+                Accessor::Field(name) | Accessor::Ctor(name) | Accessor::Is(name)
+                    if !interface.contains(&symtab[*name]) =>
+                {
+                    unresolved.insert(symtab[*name].to_string());
+                }
+                _ => (),
+            }
+        }
+    }
+
+    unresolved.into_iter().collect()
+}
+
 pub fn index_bitwidths<'ev, B: BV, E: ModelEvent<'ev, B>, V: Borrow<E>>(events: &[V]) -> HashMap<Name, u32> {
     let mut max_events = HashMap::new();
 
@@ -436,6 +691,55 @@ pub fn index_bitwidths<'ev, B: BV, E: ModelEvent<'ev, B>, V: Borrow<E>>(events:
     max_events.drain().map(|(k, v)| (k, required_index_bits(v))).collect()
 }
 
+/// Follow the accessor tree through an event's data, leaving the
+/// result in the view. Fails with the accessor and the width of the
+/// value if a subvector or extension does not fit that width.
+fn walk_accessor_tree<'a, B: BV>(
+    view: &mut View<'_, B>,
+    acctree: &AccessorTree<'a>,
+    types: &HashMap<Sym, Ty>,
+    shared_state: &SharedState<B>,
+    symtab: &Symtab,
+    sexps: &mut SexpArena,
+) -> Result<(), (&'a Accessor, u32)> {
+    use Accessor::*;
+
+    let mut acctree = acctree;
+    loop {
+        match acctree {
+            AccessorTree::Node { elem, child } => {
+                let elem: &'a Accessor = elem;
+                match elem {
+                    Extz(n) => view.access_extz(*n, types, sexps).map_err(|width| (elem, width))?,
+                    Exts(n) => view.access_exts(*n, types, sexps).map_err(|width| (elem, width))?,
+                    Subvec(hi, lo) => view.access_subvec(*hi, *lo, types, sexps).map_err(|width| (elem, width))?,
+                    Tuple(n) => view.access_tuple(*n, shared_state),
+                    Bits(_bitvec) => (),
+                    Id(id) => view.access_literal_id(*id, sexps),
+                    Field(name) => view.access_field(*name, symtab, shared_state),
+                    Length(_n) => (),
+                    Address => view.access_special("address"),
+                    Data => view.access_special("data"),
+                    Opcode => view.access_special("opcode"),
+                    Return => view.access_special("return"),
+                    Is(expected) => view.access_is_name(&symtab[*expected]),
+
+                    // Should not occur as an accessortree node
+                    Ctor(_) | Wildcard | Match(_) => unreachable!(),
+                }
+                // Operand fields are only accessible directly on the event
+                view.operand_fields.clear();
+                acctree = child
+            }
+            AccessorTree::Match { arms } => {
+                acctree = view.access_match(arms, symtab, shared_state);
+                view.operand_fields.clear()
+            }
+            AccessorTree::Leaf => return Ok(()),
+        }
+    }
+}
+
 pub fn generate_function<'ev, B: BV, E: ModelEvent<'ev, B>, V: Borrow<E>>(
     fn_name: Name,
     acc_info: AccessorInfo,
@@ -444,11 +748,9 @@ pub fn generate_function<'ev, B: BV, E: ModelEvent<'ev, B>, V: Borrow<E>>(
     shared_state: &SharedState<B>,
     symtab: &Symtab,
     sexps: &mut SexpArena,
-) -> SexpId {
-    use Accessor::*;
-
-    let acctree = &AccessorTree::from_accessors(acc_info.accessors);
-    let mut event_values: HashMap<Name, Vec<(View<'ev, B>, &AccessorTree)>> = HashMap::new();
+) -> Result<SexpId, AccessorTreeError> {
+    let acctree = AccessorTree::from_accessors(acc_info.accessors, symtab, DEFAULT_MAX_MATCH_DEPTH)?;
+    let mut event_values: HashMap<Name, Vec<View<'ev, B>>> = HashMap::new();
 
     for event in events {
         let event = event.borrow();
@@ -457,48 +759,25 @@ pub fn generate_function<'ev, B: BV, E: ModelEvent<'ev, B>, V: Borrow<E>>(
         match event.base_events() {
             &[ev] if event.index_set() == acc_info.index_set => {
                 let view = event_view(ev, opcode, shared_state).unwrap_or_default();
-                event_values.insert(name, vec![(view, acctree)]);
+                event_values.insert(name, vec![view]);
             }
             events if event.index_set() == acc_info.index_set && acc_info.index_set.is_some() => {
-                let mut views: Vec<View<'ev, B>> =
+                let views: Vec<View<'ev, B>> =
                     events.iter().map(|ev| event_view(ev, opcode, shared_state).unwrap_or_default()).collect();
-                event_values.insert(name, views.drain(..).map(|view| (view, acctree)).collect());
+                event_values.insert(name, views);
             }
             _ => (),
         }
     }
 
     for views in event_values.values_mut() {
-        for (view, acctree) in views.iter_mut() {
-            loop {
-                match acctree {
-                    AccessorTree::Node { elem, child } => {
-                        match *elem {
-                            Extz(n) => view.access_extz(*n, types, sexps),
-                            Exts(n) => view.access_exts(*n, types, sexps),
-                            Subvec(hi, lo) => view.access_subvec(*hi, *lo, types, sexps),
-                            Tuple(n) => view.access_tuple(*n, shared_state),
-                            Bits(_bitvec) => (),
-                            Id(id) => view.access_literal_id(*id, sexps),
-                            Field(name) => view.access_field(*name, symtab, shared_state),
-                            Length(_n) => (),
-                            Address => view.access_special("address"),
-                            Data => view.access_special("data"),
-                            Opcode => view.access_special("opcode"),
-                            Return => view.access_special("return"),
-                            Is(expected) => view.access_is_name(&symtab[*expected]),
-
-                            // Should not occur as an accessortree node
-                            Ctor(_) | Wildcard | Match(_) => unreachable!(),
-                        }
-                        *acctree = child
-                    }
-                    AccessorTree::Match { arms } => {
-                        let child = view.access_match(arms, symtab, shared_state);
-                        *acctree = child
-                    }
-                    AccessorTree::Leaf => break,
-                }
+        for view in views.iter_mut() {
+            if let Err((accessor, width)) = walk_accessor_tree(view, &acctree, types, shared_state, symtab, sexps) {
+                return Err(AccessorTreeError {
+                    kind: AccessorTreeErrorKind::OutOfRange { accessor: describe_accessor(accessor, symtab), width },
+                    sequence: acc_info.accessors.iter().map(|acc| describe_accessor(acc, symtab)).collect(),
+                    position: acc_info.accessors.iter().position(|acc| std::ptr::eq(acc, accessor)).unwrap_or(0),
+                });
             }
         }
     }
@@ -518,5 +797,426 @@ pub fn generate_function<'ev, B: BV, E: ModelEvent<'ev, B>, V: Borrow<E>>(
     let accessor_ite = generate_ite_chain(&event_values, accessor_ty, index_bits, sexps);
 
     let accessor_fn = sexps.alloc(Sexp::Atom(fn_name));
-    sexps.alloc(Sexp::List(vec![sexps.define_fun, accessor_fn, accessor_params, accessor_ty, accessor_ite]))
+    Ok(sexps.alloc(Sexp::List(vec![sexps.define_fun, accessor_fn, accessor_params, accessor_ty, accessor_ite])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use isla_lib::bitvector::b64::B64;
+    use isla_lib::ir::{Def, IRTypeInfo, Ty as IRTy};
+    use isla_lib::smt::{EnumId, EnumMember};
+    use std::collections::HashSet;
+
+    use crate::memory_model::{ExpArena, MemoryModel};
+    use crate::smt::{compile_memory_model, write_sexps};
+
+    fn test_values(width: u32) -> (Val<B64>, Val<B64>, HashMap<Sym, Ty>) {
+        let v = Sym::from_u32(0);
+        let types = [(v, Ty::BitVec(width))].into_iter().collect();
+        (Val::Bits(B64::new(0b1010_0110, 8).slice(0, width).unwrap()), Val::Symbolic(v), types)
+    }
+
+    #[test]
+    fn test_subvec_boundaries() {
+        let mut sexps = SexpArena::new();
+        for width in 1..=8 {
+            let (concrete, symbolic, types) = test_values(width);
+            for value in [&concrete, &symbolic] {
+                for hi in 0..=(width + 1) {
+                    for lo in 0..=(width + 1) {
+                        let mut view = View::default().with_value(value);
+                        let result = view.access_subvec(hi, lo, &types, &mut sexps);
+                        let valid = lo <= hi && hi < width;
+                        assert_eq!(result, if valid { Ok(()) } else { Err(width) }, "{:?}[{}..{}]", value, hi, lo);
+                        if !valid {
+                            continue;
+                        }
+                        assert!(view.value.is_some(), "{:?}[{}..{}]", value, hi, lo);
+                        if let Some(AccessorVal::Bits(bv)) = view.value {
+                            assert_eq!(bv.len(), hi - lo + 1)
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_extension_boundaries() {
+        let mut sexps = SexpArena::new();
+        for width in 1..=8 {
+            let (concrete, symbolic, types) = test_values(width);
+            for value in [&concrete, &symbolic] {
+                for n in 0..=(width + 2) {
+                    let mut zview = View::default().with_value(value);
+                    let zresult = zview.access_extz(n, &types, &mut sexps);
+                    let mut sview = View::default().with_value(value);
+                    let sresult = sview.access_exts(n, &types, &mut sexps);
+                    for (view, result) in [(zview, zresult), (sview, sresult)] {
+                        assert_eq!(result.is_err(), n < width);
+                        match view.value {
+                            _ if n < width => assert_eq!(result, Err(width)),
+                            None => panic!("extension of a bitvector has no value"),
+                            Some(AccessorVal::Bits(bv)) => {
+                                assert!(n >= width);
+                                assert_eq!(bv.len(), n);
+                                if n == width {
+                                    assert_eq!(Val::Bits(bv), concrete)
+                                }
+                            }
+                            Some(AccessorVal::Sexp(sexp)) => {
+                                assert!(n >= width);
+                                // Width-preserving extensions leave the variable untouched
+                                if n == width {
+                                    assert!(matches!(sexps[sexp], Sexp::Symbolic(_)))
+                                }
+                            }
+                            Some(AccessorVal::Val(_)) => panic!("unexpected unsimplified value"),
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_constant_accessors() {
+        let mut symtab = Symtab::new();
+        let mut arena = ExpArena::new();
+        let invalid = [
+            "accessor a: bits(4) = [3 .. 7]",
+            "accessor b: bits(4) = .data[0 .. 1]",
+            "accessor c: bits(8) = .extz(0)",
+            "accessor d: bits(8) = .exts(0)",
+        ];
+        for contents in invalid {
+            assert!(MemoryModel::from_string("test.cat", 0, contents, &mut arena, &mut symtab).is_err(), "{}", contents)
+        }
+        let valid = ["accessor a: bits(1) = [7 .. 7]", "accessor b: bits(64) = .data.extz(64)"];
+        for contents in valid {
+            assert!(MemoryModel::from_string("test.cat", 0, contents, &mut arena, &mut symtab).is_ok(), "{}", contents)
+        }
+    }
+
+    #[test]
+    fn test_malformed_accessor_sequences() {
+        use AccessorTreeErrorKind::*;
+        let mut symtab = Symtab::new();
+        let a = symtab.intern("A");
+        let f = symtab.intern("f");
+
+        let match_only = [Accessor::Match(1)];
+        let err = AccessorTree::from_accessors(&match_only, &symtab, DEFAULT_MAX_MATCH_DEPTH).unwrap_err();
+        assert!(matches!(err.kind, MatchWithoutCtors { arms: 1, available: 0 }));
+        assert_eq!(err.position, 0);
+
+        let too_few_ctors = [Accessor::Field(f), Accessor::Ctor(a), Accessor::Match(2)];
+        let err = AccessorTree::from_accessors(&too_few_ctors, &symtab, DEFAULT_MAX_MATCH_DEPTH).unwrap_err();
+        assert!(matches!(err.kind, MatchWithoutCtors { arms: 2, available: 1 }));
+        assert_eq!(err.position, 2);
+        assert!(format!("{}", err).starts_with("Malformed accessor sequence [.f, A =>, match(2)] at position 2"));
+
+        let trailing_ctor = [Accessor::Field(f), Accessor::Ctor(a)];
+        let err = AccessorTree::from_accessors(&trailing_ctor, &symtab, DEFAULT_MAX_MATCH_DEPTH).unwrap_err();
+        assert!(matches!(err.kind, TrailingCtors { count: 1 }));
+        assert_eq!(err.position, 2);
+    }
+
+    #[test]
+    fn test_nested_match() {
+        let mut ir_symtab = isla_lib::ir::Symtab::new();
+        let ir_ctors: Vec<_> = ["zC0", "zC1", "zC2", "zC3", "zC4", "zC5"].map(|ctor| ir_symtab.intern(ctor)).to_vec();
+        let other = ir_symtab.intern("zOther");
+        let defs: Vec<Def<isla_lib::ir::Name, B64>> = Vec::new();
+        let type_info = IRTypeInfo::new(&defs);
+        let shared_state = SharedState::new(
+            ir_symtab,
+            &defs,
+            type_info,
+            HashSet::new(),
+            HashSet::new(),
+            HashSet::new(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+        );
+
+        let mut symtab = Symtab::new();
+        let mut sexps = SexpArena::new();
+        let types = HashMap::new();
+
+        // .match { C0 => .match { C1 => ... .match { C5 => <leaf>, _ => default } ..., _ => default }, _ => default }
+        let mut accessors = Vec::new();
+        for i in (0..6).rev() {
+            accessors.push(Accessor::Ctor(symtab.intern(&format!("C{}", i))));
+            accessors.push(Accessor::Id(DEFAULT.name()));
+            accessors.push(Accessor::Wildcard);
+            accessors.push(Accessor::Match(2))
+        }
+
+        let tree = AccessorTree::from_accessors(&accessors, &symtab, DEFAULT_MAX_MATCH_DEPTH).unwrap();
+        assert_eq!(tree.depth(), 6);
+        let err = AccessorTree::from_accessors(&accessors, &symtab, 5).unwrap_err();
+        assert!(matches!(err.kind, AccessorTreeErrorKind::TooDeep { depth: 6, limit: 5 }));
+        assert_eq!(err.position, accessors.len() - 1);
+
+        let payload = Val::Bits(B64::new(0xA, 4));
+        let nested = |ctors: &[isla_lib::ir::Name]| {
+            ctors.iter().rev().fold(payload.clone(), |value, ctor| Val::Ctor(*ctor, Box::new(value)))
+        };
+
+        let value = nested(&ir_ctors);
+        let mut view = View::default().with_value(&value);
+        walk_accessor_tree(&mut view, &tree, &types, &shared_state, &symtab, &mut sexps).unwrap();
+        assert!(matches!(view.value, Some(AccessorVal::Val(v)) if *v == payload));
+
+        // Diverging at any level takes the wildcard arm
+        for level in 0..6 {
+            let mut ctors = ir_ctors.clone();
+            ctors[level] = other;
+            let value = nested(&ctors);
+            let mut view = View::default().with_value(&value);
+            walk_accessor_tree(&mut view, &tree, &types, &shared_state, &symtab, &mut sexps).unwrap();
+            assert!(view.value.is_none(), "level {}", level)
+        }
+    }
+
+    fn stub_interface() -> InterfaceNames {
+        let mut ir_symtab = isla_lib::ir::Symtab::new();
+        let barrier = ir_symtab.intern("zsail_barrier");
+        let request = ir_symtab.intern("zMem_request");
+        let kind = ir_symtab.intern("zaccess_kind");
+        let defs: Vec<Def<isla_lib::ir::Name, B64>> = vec![
+            Def::Extern(barrier, true, "sail_barrier".to_string(), vec![IRTy::Unit], IRTy::Unit),
+            Def::Struct(request, vec![(kind, IRTy::Bool)]),
+        ];
+        let type_info = IRTypeInfo::new(&defs);
+        let shared_state = SharedState::new(
+            ir_symtab,
+            &defs,
+            type_info,
+            HashSet::new(),
+            HashSet::new(),
+            HashSet::new(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+        );
+        InterfaceNames::new(&shared_state)
+    }
+
+    #[test]
+    fn test_requires() {
+        let interface = stub_interface();
+        let mut symtab = Symtab::new();
+        let mut arena = ExpArena::new();
+
+        let contents = "requires sail_barrier, access_kind\naccessor is_barrier: bool = is sail_barrier";
+        let mm = MemoryModel::from_string("test.cat", 0, contents, &mut arena, &mut symtab).unwrap();
+        assert!(check_requires(&mm, &interface, &symtab).is_ok());
+
+        let contents = "requires sail_barrier, sail_cache_op\nrequires sail_tlbi, access_kind";
+        let mm = MemoryModel::from_string("test.cat", 0, contents, &mut arena, &mut symtab).unwrap();
+        let errors = check_requires(&mm, &interface, &symtab).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].message.contains("'sail_cache_op'"));
+        assert_eq!(&contents[errors[0].span.0..errors[0].span.1], "sail_cache_op");
+        assert!(errors[1].message.contains("'sail_tlbi'"));
+        assert_eq!(&contents[errors[1].span.0..errors[1].span.1], "sail_tlbi");
+    }
+
+    #[test]
+    fn test_unresolved_accessor_names() {
+        let interface = stub_interface();
+        let mut symtab = Symtab::new();
+        let mut arena = ExpArena::new();
+        let mut sexps = SexpArena::new();
+
+        let contents = "accessor a: bool = is sail_barrier\naccessor b: bool = is sail_dmb\naccessor c: bool = .access_kind\naccessor d: bool = .acess_kind";
+        let mm = MemoryModel::from_string("test.cat", 0, contents, &mut arena, &mut symtab).unwrap();
+        let ir_symtab = isla_lib::ir::Symtab::new();
+        let typedefs = isla_lib::ir::Typedefs {
+            structs: &HashMap::new(),
+            enums: &HashMap::new(),
+            unions: &HashMap::new(),
+            symtab: &ir_symtab,
+        };
+        let accessors = mm.accessors(typedefs, &arena, &mut sexps, &mut symtab).unwrap();
+        assert_eq!(unresolved_accessor_names(&accessors, &interface, &symtab), vec!["acess_kind", "sail_dmb"])
+    }
+
+    struct TestEvent<'ev> {
+        name: Name,
+        base: Vec<&'ev Event<B64>>,
+    }
+
+    impl<'ev> ModelEvent<'ev, B64> for TestEvent<'ev> {
+        fn name(&self) -> Name {
+            self.name
+        }
+
+        fn base_events(&self) -> &[&'ev Event<B64>] {
+            &self.base
+        }
+
+        fn index_set(&self) -> Option<Name> {
+            None
+        }
+
+        fn opcode(&self) -> Option<B64> {
+            None
+        }
+    }
+
+    #[test]
+    fn test_operand_fields() {
+        let mut ir_symtab = isla_lib::ir::Symtab::new();
+        let sail_barrier = ir_symtab.intern("zsail_barrier");
+        let sail_cache_op = ir_symtab.intern("zsail_cache_op");
+        let domain_ty = ir_symtab.intern("zMBReqDomain");
+        let ish = ir_symtab.intern("zMBReqDomain_InnerShareable");
+        let sy = ir_symtab.intern("zMBReqDomain_FullSystem");
+        let dxb = ir_symtab.intern("zDxB");
+        let domain = ir_symtab.intern("zdomain");
+        let barrier_ty = ir_symtab.intern("zBarrier");
+        let barrier_dmb = ir_symtab.intern("zBarrier_DMB");
+        let cacheop_ty = ir_symtab.intern("zCacheOp");
+        let clean = ir_symtab.intern("zCacheOp_Clean");
+        let invalidate = ir_symtab.intern("zCacheOp_Invalidate");
+        let record = ir_symtab.intern("zCacheRecord");
+        let cacheop = ir_symtab.intern("zcacheop");
+        let vaddress = ir_symtab.intern("zvaddress");
+        let defs: Vec<Def<isla_lib::ir::Name, B64>> = vec![
+            Def::Enum(domain_ty, vec![ish, sy]),
+            Def::Struct(dxb, vec![(domain, IRTy::Enum(domain_ty))]),
+            Def::Union(barrier_ty, vec![(barrier_dmb, IRTy::Struct(dxb))]),
+            Def::Extern(sail_barrier, true, "sail_barrier".to_string(), vec![IRTy::Union(barrier_ty)], IRTy::Unit),
+            Def::Enum(cacheop_ty, vec![clean, invalidate]),
+            Def::Struct(record, vec![(cacheop, IRTy::Enum(cacheop_ty)), (vaddress, IRTy::Bits(64))]),
+            Def::Extern(sail_cache_op, true, "sail_cache_op".to_string(), vec![IRTy::Struct(record)], IRTy::Unit),
+        ];
+        let type_info = IRTypeInfo::new(&defs);
+        let shared_state = SharedState::new(
+            ir_symtab,
+            &defs,
+            type_info,
+            HashSet::new(),
+            HashSet::new(),
+            HashSet::new(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+        );
+
+        let dmb = |member: usize| Event::Abstract {
+            name: sail_barrier,
+            primitive: true,
+            args: vec![Val::Ctor(
+                barrier_dmb,
+                Box::new(Val::Struct(
+                    [(domain, Val::Enum(EnumMember { enum_id: EnumId::from_name(domain_ty), member }))]
+                        .into_iter()
+                        .collect(),
+                )),
+            )],
+            return_value: Val::Unit,
+        };
+        let dc = Event::Abstract {
+            name: sail_cache_op,
+            primitive: true,
+            args: vec![Val::Struct(
+                [
+                    (cacheop, Val::Enum(EnumMember { enum_id: EnumId::from_name(cacheop_ty), member: 0 })),
+                    (vaddress, Val::Bits(B64::new(0x1000, 64))),
+                ]
+                .into_iter()
+                .collect(),
+            )],
+            return_value: Val::Unit,
+        };
+        let (dmb_ish, dmb_sy) = (dmb(0), dmb(1));
+
+        let mut symtab = Symtab::new();
+        let mut arena = ExpArena::new();
+        let mut sexps = SexpArena::new();
+        let contents = "accessor barrier_domain: MBReqDomain = .domain\n\
+                        accessor cacheop: CacheOp = .cacheop\n\
+                        accessor cache_address: bits(64) = .address()\n\
+                        accessor match_domain: MBReqDomain = .match { Barrier_DMB => .domain, _ => default }\n\
+                        accessor high_address: bits(8) = .address()[70 .. 63]\n\
+                        define DMB_ISH(ev: Event): bool = barrier_domain(ev) == MBReqDomain_InnerShareable\n\
+                        define DMB_SY(ev: Event): bool = barrier_domain(ev) == MBReqDomain_FullSystem";
+        let mm = MemoryModel::from_string("test.cat", 0, contents, &mut arena, &mut symtab).unwrap();
+        let accessors = mm.accessors(shared_state.typedefs(), &arena, &mut sexps, &mut symtab).unwrap();
+
+        let events = [
+            TestEvent { name: symtab.intern("ev_ish"), base: vec![&dmb_ish] },
+            TestEvent { name: symtab.intern("ev_sy"), base: vec![&dmb_sy] },
+            TestEvent { name: symtab.intern("ev_dc"), base: vec![&dc] },
+        ];
+
+        let mut generate = |name: &str| {
+            let fn_name = symtab.lookup(name).unwrap();
+            let f = generate_function::<B64, TestEvent, _>(
+                fn_name,
+                accessors[&fn_name],
+                &events,
+                &HashMap::new(),
+                &shared_state,
+                &symtab,
+                &mut sexps,
+            )
+            .unwrap();
+            let mut buf = Vec::new();
+            write_sexps(&mut buf, &[f], &sexps, &symtab, shared_state.typedefs(), &HashMap::new()).unwrap();
+            String::from_utf8(buf).unwrap()
+        };
+
+        // The domain is taken from inside the union constructor, so
+        // both barriers are distinguished and the cache op has none
+        let domain_fn = generate("barrier_domain");
+        assert!(domain_fn.contains("(ite (= ev_ish ev1) MBReqDomain_InnerShareable"), "{}", domain_fn);
+        assert!(domain_fn.contains("(ite (= ev_sy ev1) MBReqDomain_FullSystem"), "{}", domain_fn);
+        assert!(!domain_fn.contains("ev_dc"), "{}", domain_fn);
+
+        // Struct fields with the same name still take priority
+        assert_eq!(generate("match_domain").matches("(ite").count(), 2);
+
+        let cacheop_fn = generate("cacheop");
+        assert!(cacheop_fn.contains("(ite (= ev_dc ev1) CacheOp_Clean"), "{}", cacheop_fn);
+        assert!(!cacheop_fn.contains("ev_ish"), "{}", cacheop_fn);
+
+        let address_fn = generate("cache_address");
+        assert!(address_fn.contains("(ite (= ev_dc ev1) #x0000000000001000"), "{}", address_fn);
+
+        // The width of the address is only known from the event
+        let fn_name = symtab.lookup("high_address").unwrap();
+        let err = generate_function::<B64, TestEvent, _>(
+            fn_name,
+            accessors[&fn_name],
+            &events,
+            &HashMap::new(),
+            &shared_state,
+            &symtab,
+            &mut sexps,
+        )
+        .unwrap_err();
+        assert!(
+            matches!(&err.kind, AccessorTreeErrorKind::OutOfRange { accessor, width: 64 } if accessor == "[70 .. 63]"),
+            "{}",
+            err
+        );
+
+        let mut compiled = Vec::new();
+        compile_memory_model(&mm, shared_state.typedefs(), &arena, &Vec::new(), &mut sexps, &mut symtab, &mut compiled)
+            .unwrap();
+        let mut buf = Vec::new();
+        write_sexps(&mut buf, &compiled, &sexps, &symtab, shared_state.typedefs(), &HashMap::new()).unwrap();
+        let model = String::from_utf8(buf).unwrap();
+        assert!(
+            model.contains("(define-fun DMB_ISH ((ev Event)) bool (= (barrier_domain ev) MBReqDomain_InnerShareable))")
+        );
+        assert!(model.contains("(define-fun DMB_SY ((ev Event)) bool (= (barrier_domain ev) MBReqDomain_FullSystem))"));
+    }
 }
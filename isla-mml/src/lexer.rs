@@ -225,6 +225,7 @@ pub enum Tok<'input> {
     PlusPlus,
     Question,
     Relation,
+    Requires,
     Return,
     SemiColon,
     Set,
@@ -302,6 +303,7 @@ impl<'input> fmt::Display for Tok<'input> {
             PlusPlus => write!(f, "++"),
             Question => write!(f, "?"),
             Relation => write!(f, "relation"),
+            Requires => write!(f, "requires"),
             Return => write!(f, "return"),
             SemiColon => write!(f, ";"),
             Set => write!(f, "set"),
@@ -364,6 +366,7 @@ lazy_static! {
     pub static ref KW_OPCODE: Keyword = Keyword::new("opcode", Tok::Opcode);
     pub static ref KW_PLUS_PLUS: Keyword = Keyword::new("++", Tok::PlusPlus);
     pub static ref KW_RELATION: Keyword = Keyword::new("relation", Tok::Relation);
+    pub static ref KW_REQUIRES: Keyword = Keyword::new("requires", Tok::Requires);
     pub static ref KW_RETURN: Keyword = Keyword::new("return", Tok::Return);
     pub static ref KW_SET: Keyword = Keyword::new("set", Tok::Set);
     pub static ref KW_SHOW: Keyword = Keyword::new("show", Tok::Show);
@@ -430,6 +433,7 @@ impl<'input> Iterator for Lexer<'input> {
             lex_regex!(self, Id, ID_REGEX)
         } else if next == 'r' {
             lex_keyword!(self, KW_RELATION);
+            lex_keyword!(self, KW_REQUIRES);
             lex_keyword!(self, KW_RETURN);
             lex_regex!(self, Id, ID_REGEX)
         } else if next == 's' {
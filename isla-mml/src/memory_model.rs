@@ -36,7 +36,7 @@ use id_arena::{Arena, Id};
 use lalrpop_util::ParseError;
 
 use std::borrow::Cow;
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::env;
 use std::fs::File;
 use std::io::{Read, Write};
@@ -455,6 +455,60 @@ pub struct AccessorInfo<'a> {
     pub accessors: &'a [Accessor],
 }
 
+/// The accessors used by a memory model, as returned by
+/// [MemoryModel::accessors]. These are ordered by their encoded names
+/// (the names in the symbol table, which for inline accessors are
+/// generated from the accessor sequence), so that anything generated
+/// from them, like the SMT accessor functions, is always produced in
+/// the same order.
+#[derive(Clone, Debug, Default)]
+pub struct Accessors<'a> {
+    accessors: Vec<(Name, AccessorInfo<'a>)>,
+}
+
+impl<'a> Accessors<'a> {
+    pub fn get(&self, name: &Name) -> Option<&AccessorInfo<'a>> {
+        self.accessors.iter().find(|(n, _)| n == name).map(|(_, info)| info)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, (Name, AccessorInfo<'a>)> {
+        self.accessors.iter()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = Name> + '_ {
+        self.accessors.iter().map(|(name, _)| *name)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &AccessorInfo<'a>> {
+        self.accessors.iter().map(|(_, info)| info)
+    }
+
+    pub fn len(&self) -> usize {
+        self.accessors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accessors.is_empty()
+    }
+}
+
+impl<'a> Index<&Name> for Accessors<'a> {
+    type Output = AccessorInfo<'a>;
+
+    fn index(&self, name: &Name) -> &Self::Output {
+        self.get(name).expect("no accessor with the given name")
+    }
+}
+
+impl<'b, 'a> IntoIterator for &'b Accessors<'a> {
+    type Item = &'b (Name, AccessorInfo<'a>);
+    type IntoIter = std::slice::Iter<'b, (Name, AccessorInfo<'a>)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.accessors.iter()
+    }
+}
+
 impl Exp {
     fn add_accessors<'a>(
         &'a self,
@@ -544,6 +598,7 @@ pub enum Def {
     IndexedAccessor(Name, Name, ExpId, Vec<Accessor>),
     Let(Name, Vec<(Name, TyAnnot)>, TyAnnot, ExpId),
     Relation(u32, Name),
+    Requires(Vec<Spanned<Name>>),
     Show(Vec<Name>),
     Variants(Vec<Name>),
 }
@@ -553,11 +608,16 @@ pub struct MemoryModel {
     pub(crate) defs: Vec<Spanned<Def>>,
 }
 
-/// An iterator over names to be displayed by default (shown) in the model
+/// An iterator over names to be displayed by default (shown) in the
+/// model. Names are produced in the order they are declared, after any
+/// includes have been replaced by the definitions of the included
+/// file, and a name shown more than once is only produced the first
+/// time.
 pub struct Shows<'a> {
     defs: &'a [Spanned<Def>],
     def: usize,
     show: usize,
+    seen: HashSet<Name>,
 }
 
 impl<'a> Iterator for Shows<'a> {
@@ -569,11 +629,12 @@ impl<'a> Iterator for Shows<'a> {
                 Some(Spanned { node: Def::Show(shows), .. }) => {
                     if let Some(name) = shows.get(self.show) {
                         self.show += 1;
-                        return Some(*name);
+                        if self.seen.insert(*name) {
+                            return Some(*name);
+                        }
                     } else {
                         self.show = 0;
                         self.def += 1;
-                        break;
                     }
                 }
                 Some(_) => self.def += 1,
@@ -588,6 +649,7 @@ pub enum ModelParseError {
     ParseInt { error: ParseIntError, span: (usize, usize) },
     Lex { pos: usize },
     NullaryRelation { span: (usize, usize) },
+    InvalidAccessor { message: String, span: (usize, usize) },
 }
 
 fn format_expected_tokens(expected: &[String]) -> String {
@@ -620,6 +682,7 @@ fn format_parse_error(
             ModelParseError::ParseInt { error, span } => (format!("{}", error), span),
             ModelParseError::Lex { pos } => ("could not lex input".to_string(), (pos, pos)),
             ModelParseError::NullaryRelation { span } => ("found nullary relation declaration".to_string(), span),
+            ModelParseError::InvalidAccessor { message, span } => (message, span),
         },
     };
     let source_loc = span_to_source_loc(span, 0, contents);
@@ -651,13 +714,15 @@ pub fn format_error(error: &Error) -> String {
 }
 
 impl MemoryModel {
+    /// Collect the accessors used by the model, both those declared
+    /// with `accessor` and those used inline in expressions.
     pub fn accessors<'a>(
         &'a self,
         typedefs: Typedefs,
         exps: &'a ExpArena,
         sexps: &mut SexpArena,
         symtab: &mut Symtab,
-    ) -> Result<HashMap<Name, AccessorInfo<'a>>, Error> {
+    ) -> Result<Accessors<'a>, Error> {
         let mut collection = HashMap::new();
         for def in &self.defs {
             match &def.node {
@@ -683,6 +748,7 @@ impl MemoryModel {
                 }
                 Def::Include(_)
                 | Def::Relation(_, _)
+                | Def::Requires(_)
                 | Def::Show(_)
                 | Def::Declare(_, _, _)
                 | Def::Enum(_, _)
@@ -690,7 +756,9 @@ impl MemoryModel {
                 Def::Variants(_) => (),
             }
         }
-        Ok(collection)
+        let mut accessors: Vec<(Name, AccessorInfo<'a>)> = collection.into_iter().collect();
+        accessors.sort_by(|(n1, _), (n2, _)| symtab[*n1].cmp(&symtab[*n2]));
+        Ok(Accessors { accessors })
     }
 
     pub fn variants(&self) -> Vec<&Name> {
@@ -705,9 +773,18 @@ impl MemoryModel {
         names
     }
 
-    /// Returns an iterator over the relation names that should be shown by default
+    /// Returns an iterator over the relation names that should be
+    /// shown by default, see [Shows] for the order they are produced in
     pub fn shows(&self) -> Shows<'_> {
-        Shows { defs: &self.defs, def: 0, show: 0 }
+        Shows { defs: &self.defs, def: 0, show: 0, seen: HashSet::new() }
+    }
+
+    /// Returns an iterator over the names listed in `requires` declarations
+    pub fn requires(&self) -> impl Iterator<Item = &Spanned<Name>> {
+        self.defs.iter().flat_map(|def| match &def.node {
+            Def::Requires(names) => names.as_slice(),
+            _ => &[],
+        })
     }
 
     /// Returns the tag for the toplevel file, if it has one (the string that proceeds other definitions).
@@ -839,3 +916,47 @@ pub fn resolve_includes(
         .collect();
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shows_order() {
+        let dir = env::temp_dir().join(format!("isla-mml-shows-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.cat"), "show x, y\n").unwrap();
+        std::fs::write(dir.join("b.cat"), "show y, z\ninclude \"a.cat\"\n").unwrap();
+        std::fs::write(dir.join("top.cat"), "include \"b.cat\"\nshow w, x\ninclude \"a.cat\"\nshow z, v\n").unwrap();
+
+        let mut symtab = Symtab::new();
+        let mut arena = ExpArena::new();
+        let mm = load_memory_model(dir.join("top.cat").to_str().unwrap(), &mut arena, &mut symtab);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let mm = mm.unwrap();
+
+        let shows: Vec<&str> = mm.shows().map(|name| &symtab[name]).collect();
+        assert_eq!(shows, vec!["y", "z", "x", "w", "v"])
+    }
+
+    #[test]
+    fn test_accessors_order() {
+        let mut symtab = Symtab::new();
+        let mut arena = ExpArena::new();
+        let mut sexps = SexpArena::new();
+
+        let contents = "accessor zeta: bool = .data\n\
+                        accessor alpha: bool = .address()\n\
+                        define uses_inline(ev: Event): bool = ev.data\n\
+                        accessor mu: bool = .data";
+        let mm = MemoryModel::from_string("test.cat", 0, contents, &mut arena, &mut symtab).unwrap();
+        let ir_symtab = isla_lib::ir::Symtab::new();
+        let typedefs =
+            Typedefs { structs: &HashMap::new(), enums: &HashMap::new(), unions: &HashMap::new(), symtab: &ir_symtab };
+
+        let accessors = mm.accessors(typedefs, &arena, &mut sexps, &mut symtab).unwrap();
+        let names: Vec<&str> = accessors.names().map(|name| &symtab[name]).collect();
+        assert_eq!(names, vec!["accfzdata", "alpha", "mu", "zeta"]);
+        assert!(accessors.get(&symtab.lookup("mu").unwrap()).is_some())
+    }
+}
@@ -1137,6 +1137,9 @@ pub fn compile_def(
 
         Def::Relation(_, _) | Def::Show(_) => Ok(()),
 
+        // `requires` declarations are checked against the architecture before compilation
+        Def::Requires(_) => Ok(()),
+
         Def::Accessor(..) => Ok(()),
 
         Def::IndexedAccessor(..) => Ok(()),
@@ -39,28 +39,38 @@ use std::fs::{self, File};
 use std::io::{prelude::*, BufReader, Lines};
 use std::path::{Path, PathBuf};
 use std::process::{self, exit, Command};
+use std::sync::Arc;
 use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use isla_axiomatic::graph::{
-    draw_graph_ascii, draw_graph_gv, graph_from_unsat, graph_from_z3_output, Graph, GraphMode, GraphOpts,
-    GraphValueNames,
+    draw_graph_ascii, draw_graph_graphml, draw_graph_gv, draw_graph_svg, draw_graphs_gv, draw_graphs_html,
+    dump_graph_layout, graph_from_unsat, graph_from_z3_output, EdgeDirection, EdgeLabel, EventPalette, EventSpec,
+    Graph, GraphMode, GraphOpts, GraphStyle, GraphValueNames, HeuristicMeasure, LayoutFile, RelationStyle,
 };
 
 use isla_axiomatic::axiomatic::{final_state_from_z3_output, FinalLocValuesError};
-use isla_axiomatic::litmus::exp::{collect_locs, Loc as LitmusLoc};
+use isla_axiomatic::litmus::exp::{collect_locs, AssertionSanity, Loc as LitmusLoc};
+use isla_axiomatic::litmus::reduce;
 use isla_axiomatic::litmus::Litmus;
+use isla_axiomatic::multi_access::MultiAccessPolicy;
 use isla_axiomatic::page_table::{name_initial_walk_bitvectors, VirtualAddress};
+use isla_axiomatic::relation_stats::{count_edges, RelationStats};
 use isla_axiomatic::run_litmus;
 use isla_axiomatic::run_litmus::{LitmusRunOpts, PCLimitMode};
+use isla_axiomatic::sexp::SexpVal;
+use isla_axiomatic::smt_model::Model;
+use isla_axiomatic::timing::{Phase, PhaseTimings};
 use isla_lib::bitvector::{b129::B129, BV};
 use isla_lib::config::ISAConfig;
 use isla_lib::error::IslaError;
+use isla_lib::executor::MemoryBudget;
 use isla_lib::init::{initialize_architecture, InitArchWithConfig};
 use isla_lib::ir::*;
 use isla_lib::log;
+use isla_mml::accessor::{check_requires, unresolved_accessor_names, InterfaceNames};
 use isla_mml::memory_model;
-use isla_mml::smt::{compile_memory_model, SexpArena};
+use isla_mml::smt::{compile_memory_model, SexpArena, SexpId};
 
 mod opts;
 use opts::CommonOpts;
@@ -127,6 +137,30 @@ impl AxResult {
     }
 }
 
+/// The overall result of a test is an error if any candidate was
+/// an error, otherwise allowed if any candidate was allowed.
+fn overall_result(results: &[AxResult]) -> Option<&AxResult> {
+    if let Some(err) = results.iter().find(|result| result.is_error()) {
+        Some(err)
+    } else if let Some(allowed) = results.iter().find(|result| result.is_allowed()) {
+        Some(allowed)
+    } else {
+        results.first()
+    }
+}
+
+/// What property of a test should be preserved by `--reduce`
+#[derive(Clone, Debug)]
+enum ReduceOracle {
+    /// The overall verdict differs from the reference result (or the
+    /// `expect` field of the test if no reference is given)
+    Mismatch,
+    /// The named memory model flag holds in some allowed candidate
+    Flag(String),
+    /// The test produces an error
+    Error,
+}
+
 struct GroupIndex<'a, A> {
     i: usize,
     group_id: usize,
@@ -166,22 +200,47 @@ fn make_cmdline_opts() -> getopts::Options {
     opts.optopt("", "thread-groups", "number threads per group", "<n>");
     opts.optopt("", "only-group", "only perform jobs for one thread group", "<n>");
     opts.optopt("s", "timeout", "Add a timeout (in seconds)", "<n>");
+    opts.optopt("", "path-timeout", "Add a timeout (in seconds) for each symbolic execution path", "<n>");
     opts.optopt("", "pc-limit", "Limit the number of times each instruction can be visited", "<n>");
     opts.optopt("", "pc-limit-mode", "What to do when the pc-limit is exceeded (default error)", "<error|discard>");
     opts.optopt("", "memory", "Add a max memory consumption (in megabytes)", "<n>");
+    opts.optopt(
+        "",
+        "memory-budget",
+        "Write symbolic execution tasks beyond this many, and finished traces, to the cache directory",
+        "<n>",
+    );
     opts.optopt("m", "model", "Memory model in cat format", "<path>");
+    opts.optflag("", "strict-model", "Warn when memory model accessors refer to names not defined by the architecture");
     opts.optflag("", "ifetch", "Generate ifetch events");
     opts.optflag("", "armv8-page-tables", "Automatically set up ARMv8 page tables");
     opts.optflag("", "merge-translations", "Merge consecutive translate events into a single event");
     opts.optflag("", "merge-split-stages", "Split stages when merging translations");
     opts.optopt("", "remove-uninteresting", "Remove uninteresting translate events", "all/safe");
+    opts.optopt(
+        "",
+        "multi-access",
+        "How to represent multiple accesses performed by one instruction (default unlinked)",
+        "<merge|linked|unlinked>",
+    );
     opts.optflag("e", "exhaustive", "Attempt to exhaustively enumerate all possible rf combinations");
     opts.optmulti("", "extra-smt", "additional SMT appended to each candidate", "<file>");
     opts.optopt("", "check-sat-using", "Use z3 tactic for checking satisfiablity", "tactic");
+    opts.optflag("", "model-completion", "Ask z3 for an interpretation of every relation in the model");
     opts.optopt("", "latex", "generate latex version of input files in specified directory", "<path>");
-    opts.optopt("", "graph", "Draw graphs of executions", "<ascii|dot|none>");
+    opts.optopt("", "graph", "Draw graphs of executions", "<ascii|dot|svg|graphml|html|none>");
     add_graph_opts(&mut opts);
     opts.optopt("", "refs", "references to compare output with", "<path>");
+    opts.optflag("", "relation-stats", "Print the number of edges in each memory model relation per candidate");
+    opts.optflag("", "timing", "Print the time taken by each phase of running a test, and progress as they advance");
+    opts.optopt("", "reduce", "Minimise each test, placing the reduced tests in the specified directory", "<path>");
+    opts.optopt(
+        "",
+        "reduce-oracle",
+        "Property preserved when reducing tests (default mismatch)",
+        "<mismatch|flag=NAME|error>",
+    );
+    opts.optopt("", "reduce-budget", "Time budget for reducing each test (in seconds, default 300)", "<n>");
     opts.optopt(
         "",
         "cache",
@@ -194,13 +253,29 @@ fn make_cmdline_opts() -> getopts::Options {
 
 /// add graph-specific options
 fn add_graph_opts(opts: &mut getopts::Options) {
-    opts.optopt("", "dot", "Place generated graphviz dot files in specified directory", "<path>");
+    opts.optopt("", "dot", "Place generated graphviz dot (or svg/graphml) files in specified directory", "<path>");
     opts.optflag("", "temp-dot", "Generate graphviz dot files in TMPDIR or /tmp");
+    opts.optflag(
+        "",
+        "dot-single-file",
+        "Write the dot graphs for all executions of a test into a single file, with one digraph per execution",
+    );
 
     opts.optflag("", "graph-show-forbidden", "Try draw graph of forbidden executions too");
+    opts.optopt(
+        "",
+        "graph-style",
+        "Start from a preset style, which any other graph flags override",
+        "<paper|debug|web|minimal>",
+    );
 
     opts.optflag("", "graph-debug", "Show everything, all trace events and full information in the nodes");
     opts.optopt("", "graph-shows", "Overwrite showed relations", "<show,show,...>");
+    opts.optflag(
+        "",
+        "graph-show-all",
+        "Show the builtin relations as well as those named by the litmus test or memory model",
+    );
     opts.optflag(
         "",
         "graph-human-readable",
@@ -212,9 +287,50 @@ fn add_graph_opts(opts: &mut getopts::Options) {
         "Overwrite default padding",
         "<iw-left=4,threads-down=2,...,(iw|threads|thread|instr|event)-(up|down|left|right)=value,...>",
     );
-    opts.optopt("", "graph-force-show-events", "Overwrite hiding of event", "<ev1,ev2,...>");
+    opts.optopt(
+        "",
+        "graph-force-show-events",
+        "Overwrite hiding of events, given by name, by <thread>:<po>[-<po>] for whole instructions, or by T<thread>:<po>[-<po>]:s<stage>l<level> for translations",
+        "<ev1,ev2,...>",
+    );
     opts.optopt("", "graph-force-hide-events", "Overwrite hiding of event", "<ev1,ev2,...>");
+    opts.optopt(
+        "",
+        "graph-show-registers",
+        "Always draw the read and write events of these registers",
+        "<reg1,reg2,...>",
+    );
     opts.optflag("", "graph-show-all-reads", "Always show read events (including translations and ifetches)");
+    opts.optmulti(
+        "",
+        "graph-color",
+        "Overwrite the fill color for a kind of event",
+        "<(translate-s1|translate-s2|tte-write|ifetch|read|write|barrier|other)=color,...>",
+    );
+    opts.optmulti(
+        "",
+        "graph-edge",
+        "Overwrite the style used to draw a relation",
+        "<rel=color[,solid|dashed|dotted[,arrowhead]]>",
+    );
+    opts.optmulti(
+        "",
+        "graph-edge-direction",
+        "Draw a relation with arrows (directed), without (undirected), or without only for pairs of edges in opposite directions (auto, the default)",
+        "<rel=auto|undirected|directed>",
+    );
+    opts.optmulti(
+        "",
+        "graph-label",
+        "Hide the labels on the edges of a relation, abbreviate them, or place them at the middle (label) or tail (taillabel) of each edge",
+        "<rel=hide|label|taillabel|abbrev,...>",
+    );
+    opts.optmulti(
+        "",
+        "graph-transitive-reduce",
+        "Draw the transitive reduction of a relation, rather than every edge",
+        "<rel,rel,...>",
+    );
     opts.optflag(
         "",
         "graph-flatten",
@@ -225,6 +341,85 @@ fn add_graph_opts(opts: &mut getopts::Options) {
         "graph-squash-translation-labels",
         "Squash translation event labels from `T s1:pte3(x)` into `Ts1l3` to save space in diagrams",
     );
+    opts.optflag(
+        "",
+        "graph-collapse-translations",
+        "Replace the translation table walk of each instruction with a single event",
+    );
+    opts.optopt(
+        "",
+        "graph-orientation",
+        "Draw threads side by side as columns, or stacked as rows (default vertical)",
+        "<vertical|horizontal>",
+    );
+    opts.optflag(
+        "",
+        "graph-show-iio",
+        "Draw the order of the displayed events within each instruction (dot graphs only)",
+    );
+    opts.optflag("", "graph-legend", "Draw a legend showing the color of each relation (dot graphs only)");
+    opts.optflag(
+        "",
+        "graph-edge-addresses",
+        "Label rf, co, and fr edges with the location they concern, e.g. rf[x] (dot graphs only)",
+    );
+    opts.optopt(
+        "",
+        "graph-max-label-width",
+        "Wrap event labels longer than this many characters onto multiple lines (dot graphs only)",
+        "<n>",
+    );
+    opts.optopt(
+        "",
+        "graph-scale",
+        "Multiply every position, node size, and font size in the graph by this factor (default 1.0)",
+        "<factor>",
+    );
+    opts.optflag(
+        "",
+        "graph-no-hidden-counts",
+        "Don't label instructions with the number of their events which are hidden (dot graphs only)",
+    );
+    opts.optopt(
+        "",
+        "graph-split-by-addr",
+        "Draw a relation as one relation per address, such as co[x], each in its own color",
+        "<rel,rel,...>",
+    );
+    opts.optflag(
+        "",
+        "graph-no-derived-fr",
+        "Don't draw fr derived from rf and co when the memory model doesn't define it",
+    );
+    opts.optflag("", "graph-align-addresses", "Line up reads and writes to the same address in columns across threads");
+    opts.optflag("", "graph-no-instruction-boxes", "Don't draw boxes around the events of each instruction");
+    opts.optflag(
+        "",
+        "graph-no-thread-boxes",
+        "Don't draw boxes around each thread, putting the thread name on its first instruction box instead",
+    );
+    opts.optopt(
+        "",
+        "graph-layout-file",
+        "Place events at the positions in this layout file (see --graph-dump-layout), laying out any others as usual",
+        "<path>",
+    );
+    opts.optflag(
+        "",
+        "graph-dump-layout",
+        "Write the layout of each graph to a JSON layout file next to its dot (or svg/graphml) file",
+    );
+    opts.optflag(
+        "",
+        "graph-show-initial-state",
+        "Always draw the initial state, even when none of its edges are drawn (dot graphs only)",
+    );
+    opts.optflagopt(
+        "",
+        "graph-shade-threads",
+        "Shade the background of each thread, cycling through the given colors (dot graphs only)",
+        "<color1,color2,...>",
+    );
     opts.optflag(
         "",
         "view",
@@ -317,6 +512,14 @@ fn isla_main() -> i32 {
         None => None,
     };
 
+    let multi_access: MultiAccessPolicy = match matches.opt_get_default("multi-access", MultiAccessPolicy::Unlinked) {
+        Ok(policy) => policy,
+        Err(e) => {
+            eprintln!("Invalid option for --multi-access flag. {}", e);
+            return 1;
+        }
+    };
+
     let pc_limit: Option<usize> = match matches.opt_get("pc-limit") {
         Ok(limit) => limit,
         Err(e) => {
@@ -333,18 +536,164 @@ fn isla_main() -> i32 {
         }
     };
 
-    let graph_flatten = matches.opt_present("graph-flatten");
-    let graph_dbg_info = matches.opt_present("graph-debug");
-    let graph_human_readable = matches.opt_present("graph-human-readable");
+    // a style gives the defaults for the graph flags below, so any
+    // which are given explicitly override it
+    let graph_defaults = match matches.opt_get::<GraphStyle>("graph-style") {
+        Ok(Some(style)) => GraphOpts::with_style(style),
+        Ok(None) => GraphOpts::default(),
+        Err(e) => {
+            eprintln!("Invalid option for --graph-style flag. {}", e);
+            return 1;
+        }
+    };
+    let graph_flatten = matches.opt_present("graph-flatten") || graph_defaults.flatten;
+    let graph_dbg_info = matches.opt_present("graph-debug") || graph_defaults.debug;
+    let graph_human_readable = matches.opt_present("graph-human-readable") || graph_defaults.human_readable_values;
     let graph_shows = matches.opt_str("graph-shows");
-    let graph_show_all_reads = matches.opt_present("graph-show-all-reads");
-    let graph_squash_translations = matches.opt_present("graph-squash-translation-labels");
-    let graph_padding = matches.opt_str("graph-padding");
-    let graph_force_show_events = matches.opt_str("graph-force-show-events");
+    let graph_show_all = matches.opt_present("graph-show-all");
+    let graph_control_delimit = graph_defaults.control_delimit;
+    let graph_show_all_reads = matches.opt_present("graph-show-all-reads") || graph_defaults.show_all_reads;
+    let graph_squash_translations =
+        matches.opt_present("graph-squash-translation-labels") || graph_defaults.squash_translation_labels;
+    let graph_legend = matches.opt_present("graph-legend") || graph_defaults.show_legend;
+    let graph_show_iio = matches.opt_present("graph-show-iio") || graph_defaults.show_iio;
+    let graph_collapse_translations =
+        matches.opt_present("graph-collapse-translations") || graph_defaults.collapse_translations;
+    let graph_edge_addresses = matches.opt_present("graph-edge-addresses") || graph_defaults.edge_addresses;
+    let graph_hide_unused_initial_state =
+        !matches.opt_present("graph-show-initial-state") && graph_defaults.hide_unused_initial_state;
+    let graph_thread_colors: Option<Vec<String>> = if matches.opt_present("graph-shade-threads") {
+        match matches.opt_str("graph-shade-threads") {
+            Some(colors) => Some(colors.split(',').map(String::from).collect()),
+            None => Some(GraphOpts::DEFAULT_THREAD_COLORS.iter().cloned().map(String::from).collect()),
+        }
+    } else {
+        graph_defaults.thread_colors.clone()
+    };
+    let graph_orientation = match matches.opt_get_default("graph-orientation", graph_defaults.orientation) {
+        Ok(orientation) => orientation,
+        Err(e) => {
+            eprintln!("Invalid option for --graph-orientation flag. {}", e);
+            return 1;
+        }
+    };
+    let graph_show_hidden_counts = !matches.opt_present("graph-no-hidden-counts") && graph_defaults.show_hidden_counts;
+    let graph_draw_instruction_boxes =
+        !matches.opt_present("graph-no-instruction-boxes") && graph_defaults.draw_instruction_boxes;
+    let graph_draw_thread_boxes = !matches.opt_present("graph-no-thread-boxes") && graph_defaults.draw_thread_boxes;
+    let graph_align_addresses = matches.opt_present("graph-align-addresses") || graph_defaults.align_addresses;
+    let graph_layout_file = match matches.opt_str("graph-layout-file") {
+        Some(path) => match LayoutFile::load(Path::new(&path)) {
+            Ok(layout_file) => Some(layout_file),
+            Err(e) => {
+                eprintln!("Invalid option for --graph-layout-file flag. {}", e);
+                return 1;
+            }
+        },
+        None => graph_defaults.layout_file.clone(),
+    };
+    let graph_dump_layout = matches.opt_present("graph-dump-layout");
+    let graph_derive_fr = !matches.opt_present("graph-no-derived-fr") && graph_defaults.derive_fr;
+    let graph_max_label_width = match matches.opt_get::<usize>("graph-max-label-width") {
+        Ok(width) => width.or(graph_defaults.max_label_width),
+        Err(e) => {
+            eprintln!("Invalid option for --graph-max-label-width flag. {}", e);
+            return 1;
+        }
+    };
+    let graph_scale = match matches.opt_get_default("graph-scale", graph_defaults.scale) {
+        Ok(scale) if scale > 0.0 && f64::is_finite(scale) => scale,
+        Ok(scale) => {
+            eprintln!("Invalid option for --graph-scale flag. The scale must be positive, not {}", scale);
+            return 1;
+        }
+        Err(e) => {
+            eprintln!("Invalid option for --graph-scale flag. {}", e);
+            return 1;
+        }
+    };
+    let graph_padding = match matches.opt_get_default("graph-padding", graph_defaults.padding) {
+        Ok(padding) => padding,
+        Err(e) => {
+            eprintln!("Invalid option for --graph-padding flag. {}", e);
+            return 1;
+        }
+    };
+    let graph_force_show_events =
+        match matches.opt_str("graph-force-show-events").map(|s| EventSpec::parse_list(&s)).transpose() {
+            Ok(events) => events,
+            Err(e) => {
+                eprintln!("Invalid option for --graph-force-show-events flag. {}", e);
+                return 1;
+            }
+        };
     let graph_force_hide_events = matches.opt_str("graph-force-hide-events");
+    let graph_show_registers: Vec<String> = match matches.opt_str("graph-show-registers") {
+        Some(regs) => regs.split(',').map(String::from).collect(),
+        None => Vec::new(),
+    };
     let graph_show_forbidden = matches.opt_present("graph-show-forbidden");
     let graph_mode = matches.opt_str("graph");
 
+    let mut graph_palette = EventPalette::default();
+    for colors in matches.opt_strs("graph-color") {
+        for coloreq in colors.split(',') {
+            let result = match *coloreq.split('=').collect::<Vec<&str>>().as_slice() {
+                [kind, color] => graph_palette.set(kind, color),
+                _ => Err("--graph-color must be of form kind=color".to_string()),
+            };
+            if let Err(msg) = result {
+                eprintln!("{}", msg);
+                return 1;
+            }
+        }
+    }
+
+    let mut graph_relation_styles = HashMap::new();
+    for binding in matches.opt_strs("graph-edge") {
+        match RelationStyle::parse_binding(&binding) {
+            Ok((rel, style)) => {
+                graph_relation_styles.insert(rel, style);
+            }
+            Err(msg) => {
+                eprintln!("Invalid option for --graph-edge flag. {}", msg);
+                return 1;
+            }
+        }
+    }
+
+    let mut graph_relation_directions = HashMap::new();
+    for binding in matches.opt_strs("graph-edge-direction") {
+        match EdgeDirection::parse_binding(&binding) {
+            Ok((rel, direction)) => {
+                graph_relation_directions.insert(rel, direction);
+            }
+            Err(msg) => {
+                eprintln!("Invalid option for --graph-edge-direction flag. {}", msg);
+                return 1;
+            }
+        }
+    }
+
+    let mut graph_edge_labels = HashMap::new();
+    for binding in matches.opt_strs("graph-label").iter().flat_map(|bindings| bindings.split(',')) {
+        match EdgeLabel::parse_binding(binding) {
+            Ok((rel, label)) => {
+                graph_edge_labels.insert(rel, label);
+            }
+            Err(msg) => {
+                eprintln!("Invalid option for --graph-label flag. {}", msg);
+                return 1;
+            }
+        }
+    }
+
+    let graph_transitive_reduce: HashSet<String> =
+        matches.opt_strs("graph-transitive-reduce").iter().flat_map(|rels| rels.split(',')).map(String::from).collect();
+
+    let graph_split_by_address: HashSet<String> =
+        matches.opt_strs("graph-split-by-addr").iter().flat_map(|rels| rels.split(',')).map(String::from).collect();
+
     let dot_path = match matches.opt_str("dot").map(PathBuf::from) {
         Some(path) => {
             if !path.is_dir() {
@@ -363,14 +712,18 @@ fn isla_main() -> i32 {
     };
 
     let view = matches.opt_present("view");
+    let dot_single_file = matches.opt_present("dot-single-file");
     let print_like_herd7 = matches.opt_present("herd7");
 
     let graph_mode = match graph_mode {
         None => GraphMode::Disabled,
         Some(m) if m == "ascii" => GraphMode::ASCII,
         Some(m) if m == "dot" => GraphMode::Dot,
+        Some(m) if m == "svg" => GraphMode::Svg,
+        Some(m) if m == "graphml" => GraphMode::GraphML,
+        Some(m) if m == "html" => GraphMode::Html,
         Some(m) if m == "none" => GraphMode::Disabled,
-        Some(m) => panic!("--graph unknown mode '{}', must be one of {{ascii,dot,none}}", m),
+        Some(m) => panic!("--graph unknown mode '{}', must be one of {{ascii,dot,svg,graphml,html,none}}", m),
     };
 
     if graph_mode != GraphMode::Disabled && !dot_path.is_some() {
@@ -383,6 +736,13 @@ fn isla_main() -> i32 {
 
     let get_z3_model = !matches.opt_present("no-z3-model");
 
+    let relation_stats = matches.opt_present("relation-stats");
+    let timing = matches.opt_present("timing");
+    if relation_stats && !get_z3_model {
+        eprintln!("--relation-stats requires a z3 model, so cannot be used with --no-z3-model");
+        return 1;
+    }
+
     let isla_litmus_path = matches.opt_str("isla-litmus");
     let litmus_translator_path = matches.opt_str("litmus-translator");
 
@@ -394,6 +754,7 @@ fn isla_main() -> i32 {
     }
 
     let check_sat_using = matches.opt_str("check-sat-using");
+    let model_completion = matches.opt_present("model-completion");
 
     let latex_path = match matches.opt_str("latex").map(PathBuf::from) {
         Some(path) => {
@@ -416,6 +777,14 @@ fn isla_main() -> i32 {
         }
     };
 
+    let path_timeout: Option<u64> = match matches.opt_get("path-timeout") {
+        Ok(timeout) => timeout,
+        Err(e) => {
+            eprintln!("Failed to parse --path-timeout: {}", e);
+            return 1;
+        }
+    };
+
     let memory: Option<u64> = match matches.opt_get("memory") {
         Ok(memory) => memory,
         Err(e) => {
@@ -424,6 +793,47 @@ fn isla_main() -> i32 {
         }
     };
 
+    let memory_budget: Option<MemoryBudget> = match matches.opt_get("memory-budget") {
+        Ok(max_queued_tasks) => {
+            max_queued_tasks.map(|max_queued_tasks| MemoryBudget { max_queued_tasks, dir: cache.clone() })
+        }
+        Err(e) => {
+            eprintln!("Failed to parse --memory-budget: {}", e);
+            return 1;
+        }
+    };
+
+    let reduce_path = match matches.opt_str("reduce").map(PathBuf::from) {
+        Some(path) => {
+            if !path.is_dir() {
+                eprintln!("Invalid directory for reduced test output");
+                return 1;
+            }
+            Some(path)
+        }
+        None => None,
+    };
+
+    let reduce_oracle = match matches.opt_str("reduce-oracle").as_deref() {
+        Some("mismatch") | None => ReduceOracle::Mismatch,
+        Some("error") => ReduceOracle::Error,
+        Some(oracle) => match oracle.strip_prefix("flag=") {
+            Some(flag) => ReduceOracle::Flag(flag.to_string()),
+            None => {
+                eprintln!("Unknown value for --reduce-oracle flag. Must be 'mismatch', 'error', or 'flag=NAME'");
+                return 1;
+            }
+        },
+    };
+
+    let reduce_budget = match matches.opt_get_default("reduce-budget", 300) {
+        Ok(secs) => Duration::from_secs(secs),
+        Err(e) => {
+            eprintln!("Failed to parse --reduce-budget: {}", e);
+            return 1;
+        }
+    };
+
     let refs = if let Some(refs_file) = matches.opt_str("refs") {
         match process_refs(&refs_file) {
             Ok(refs) => refs,
@@ -466,6 +876,18 @@ fn isla_main() -> i32 {
             return 1;
         }
     };
+    let interface_names = InterfaceNames::new(&iarch.shared_state);
+    if let Err(missing) = check_requires(&mm, &interface_names, &mm_symtab) {
+        for error in missing {
+            eprintln!("{}", memory_model::format_error(&error))
+        }
+        return 1;
+    }
+    if matches.opt_present("strict-model") {
+        for name in unresolved_accessor_names(&accessors, &interface_names, &mm_symtab) {
+            eprintln!("Warning: memory model accessor refers to '{}', which is not defined by the architecture", name)
+        }
+    }
     let mut mm_compiled = Vec::new();
     let variants = matches.opt_strs("variant");
     if let Err(compile_error) = compile_memory_model(
@@ -515,6 +937,9 @@ fn isla_main() -> i32 {
     };
     let only_group: Option<usize> = matches.opt_get("only-group").unwrap();
 
+    let relation_names: Vec<&str> = mm_symtab.iter_toplevel().map(|name| &mm_symtab[name]).collect();
+    let graph_model_shows: Vec<String> = mm.shows().map(|name| mm_symtab[name].to_string()).collect();
+
     thread::scope(|scope| {
         for group_id in 0..thread_groups {
             if only_group.is_some() && group_id != only_group.unwrap() {
@@ -534,23 +959,36 @@ fn isla_main() -> i32 {
             let cache = &cache;
             let dot_path = &dot_path;
             let latex_path = &latex_path;
+            let reduce_path = &reduce_path;
+            let reduce_oracle = &reduce_oracle;
             let sexps = &sexps;
             let graph_mode = graph_mode;
             let mm_compiled = &mm_compiled;
             let mm_symtab = &mm_symtab;
+            let relation_names = &relation_names;
             let accessors = &accessors;
             let extra_smt = &extra_smt;
             let graph_shows = graph_shows.as_ref();
-            let graph_padding = graph_padding.as_ref();
+            let graph_model_shows = &graph_model_shows;
+            let graph_palette = &graph_palette;
+            let graph_thread_colors = &graph_thread_colors;
+            let graph_relation_styles = &graph_relation_styles;
+            let graph_relation_directions = &graph_relation_directions;
+            let graph_edge_labels = &graph_edge_labels;
+            let graph_transitive_reduce = &graph_transitive_reduce;
+            let graph_split_by_address = &graph_split_by_address;
             let graph_force_show_events = graph_force_show_events.as_ref();
             let graph_force_hide_events = graph_force_hide_events.as_ref();
+            let graph_show_registers = &graph_show_registers;
+            let graph_layout_file = &graph_layout_file;
             let check_sat_using = check_sat_using.as_deref();
             let isla_litmus_path = isla_litmus_path.as_ref();
             let litmus_translator_path = litmus_translator_path.as_ref();
+            let memory_budget = memory_budget.as_ref();
 
             scope.spawn(move || {
                 for (i, litmus_file) in GroupIndex::new(tests, group_id, thread_groups).enumerate() {
-                    let litmus = if litmus_file.extension() == Some(OsStr::new("litmus")) {
+                    let litmus_src = if litmus_file.extension() == Some(OsStr::new("litmus")) {
                         // first try Ben Stokes' `litmus-translator` tool
                         let mut translator_path =
                             litmus_translator_path.cloned().unwrap_or_else(|| "litmus-translator".to_string());
@@ -614,7 +1052,7 @@ fn isla_main() -> i32 {
                         }
                     };
 
-                    let litmus = match Litmus::parse(&litmus, symtab, type_info, isa_config) {
+                    let litmus = match Litmus::parse(&litmus_src, symtab, type_info, isa_config) {
                         Ok(litmus) => litmus,
                         Err(msg) => {
                             eprintln!("Failed to parse litmus file: {}\n{}", litmus_file.display(), msg);
@@ -646,10 +1084,12 @@ fn isla_main() -> i32 {
 
                     let now = Instant::now();
                     let result_queue = SegQueue::new();
+                    let stats_queue = SegQueue::new();
 
                     let opts = LitmusRunOpts {
                         num_threads: threads_per_test,
                         timeout,
+                        path_timeout,
                         pc_limit,
                         pc_limit_mode,
                         memory,
@@ -658,6 +1098,10 @@ fn isla_main() -> i32 {
                         armv8_page_tables,
                         merge_translations,
                         remove_uninteresting_translates,
+                        multi_access,
+                        progress: timing,
+                        model_completion,
+                        memory_budget: memory_budget.cloned(),
                     };
 
                     let mut graph_show_regs: HashSet<String> =
@@ -666,34 +1110,44 @@ fn isla_main() -> i32 {
                         graph_show_regs.extend(GraphOpts::ARMV8_ADDR_TRANS_SHOW_REGS.iter().cloned().map(String::from));
                     }
 
-                    let padding: Option<HashMap<String, f64>> = graph_padding.map(|padstr| {
-                        padstr
-                            .split(',')
-                            .map(|padeq| match *padeq.split('=').collect::<Vec<&str>>().as_slice() {
-                                [lhs, rhs] => (
-                                    lhs.to_string(),
-                                    rhs.parse::<f64>().unwrap_or_else(|_| {
-                                        panic!("--graph-padding value must be a valid 64-bit float")
-                                    }),
-                                ),
-                                _ => panic!("--graph-padding must be of form name-direction=value"),
-                            })
-                            .collect()
-                    });
-
                     let graph_opts = GraphOpts {
                         mode: graph_mode,
                         show_regs: graph_show_regs,
+                        show_registers: graph_show_registers.clone(),
                         flatten: graph_flatten,
                         debug: graph_dbg_info,
                         show_all_reads: graph_show_all_reads,
                         shows: graph_shows.map(|s| s.split(',').map(String::from).collect()),
-                        padding,
+                        model_shows: graph_model_shows.clone(),
+                        show_all: graph_show_all,
+                        padding: graph_padding,
                         human_readable_values: graph_human_readable,
-                        force_show_events: graph_force_show_events.map(|s| s.split(',').map(String::from).collect()),
+                        force_show_events: graph_force_show_events.cloned(),
                         force_hide_events: graph_force_hide_events.map(|s| s.split(',').map(String::from).collect()),
                         squash_translation_labels: graph_squash_translations,
-                        control_delimit: false,
+                        control_delimit: graph_control_delimit,
+                        palette: graph_palette.clone(),
+                        show_legend: graph_legend,
+                        relation_styles: graph_relation_styles.clone(),
+                        relation_directions: graph_relation_directions.clone(),
+                        edge_labels: graph_edge_labels.clone(),
+                        transitive_reduce: graph_transitive_reduce.clone(),
+                        show_iio: graph_show_iio,
+                        orientation: graph_orientation,
+                        collapse_translations: graph_collapse_translations,
+                        thread_colors: graph_thread_colors.clone(),
+                        edge_addresses: graph_edge_addresses,
+                        hide_unused_initial_state: graph_hide_unused_initial_state,
+                        max_label_width: graph_max_label_width,
+                        scale: graph_scale,
+                        show_hidden_counts: graph_show_hidden_counts,
+                        draw_instruction_boxes: graph_draw_instruction_boxes,
+                        draw_thread_boxes: graph_draw_thread_boxes,
+                        align_addresses: graph_align_addresses,
+                        layout_file: graph_layout_file.clone(),
+                        derive_fr: graph_derive_fr,
+                        split_by_address: graph_split_by_address.clone(),
+                        text_measure: Arc::new(HeuristicMeasure),
                     };
 
                     let final_assertion_locs = {
@@ -777,6 +1231,18 @@ fn isla_main() -> i32 {
                                 }
                             }
 
+                            if let Some(model_buf) = z3_output.strip_prefix("sat").filter(|_| relation_stats) {
+                                let mut event_names: Vec<&str> =
+                                    exec.smt_events.iter().map(|ev| ev.name.as_ref()).collect();
+                                event_names.push("IW");
+                                match Model::<B129>::parse(&event_names, model_buf) {
+                                    Ok(mut model) => {
+                                        stats_queue.push(count_edges(&mut model, event_names.len(), relation_names))
+                                    }
+                                    Err(err) => eprintln!("Failed to parse model for relation statistics: {}", err),
+                                }
+                            }
+
                             if z3_output.starts_with("sat") {
                                 let graph = if graph_mode != GraphMode::Disabled {
                                     match graph_from_z3_output(
@@ -833,30 +1299,47 @@ fn isla_main() -> i32 {
 
                     let ref_result = refs.get(&litmus.name);
 
-                    if let Err(err) = run_info {
-                        let msg = format!("{}", err);
-                        eprintln!(
-                            "{}",
-                            err.source_loc().message(source_path.as_ref(), symtab.files(), &msg, true, true)
-                        );
-                        print_results(
-                            print_like_herd7,
-                            &litmus,
-                            shared_state,
-                            now,
-                            &[Error(None, "".to_string())],
-                            ref_result,
-                        );
-                        continue;
-                    }
+                    let (assertion_sanity, mut timings) = match run_info {
+                        Ok(run_info) => (run_info.assertion_sanity, run_info.timings),
+                        Err(err) => {
+                            let msg = format!("{}", err);
+                            eprintln!(
+                                "{}",
+                                err.source_loc().message(source_path.as_ref(), symtab.files(), &msg, true, true)
+                            );
+                            print_results(
+                                print_like_herd7,
+                                &litmus,
+                                shared_state,
+                                now,
+                                &[Error(None, "".to_string())],
+                                ref_result,
+                                AssertionSanity::Unknown,
+                            );
+                            continue;
+                        }
+                    };
 
                     let mut results: Vec<AxResult> = Vec::new();
                     while let Some(result) = result_queue.pop() {
                         results.push(result)
                     }
 
-                    print_results(print_like_herd7, &litmus, shared_state, now, &results, ref_result);
+                    print_results(print_like_herd7, &litmus, shared_state, now, &results, ref_result, assertion_sanity);
+
+                    if relation_stats {
+                        let mut stats = RelationStats::new();
+                        while let Some(counts) = stats_queue.pop() {
+                            stats.record(&counts)
+                        }
+                        print_relation_stats(&litmus.name, &stats)
+                    }
 
+                    let graphs_start = Instant::now();
+                    // with --dot-single-file, the executions which got a result are drawn together at the end
+                    let mut single_file_graphs: Vec<(usize, bool, &Graph)> = Vec::new();
+                    // with --graph html, every execution is drawn into one page at the end
+                    let mut html_graphs: Vec<(usize, &str, &Graph)> = Vec::new();
                     for (i, allowed) in results.iter().enumerate() {
                         let (maybe_graph, state) = match allowed {
                             Allowed(_, graph) => (graph, "allow"),
@@ -865,8 +1348,53 @@ fn isla_main() -> i32 {
                         };
 
                         if let Some(graph) = maybe_graph {
+                            for warning in &graph.warnings {
+                                eprintln!("Warning: execution #{} of {}: {}", i + 1, litmus.name, warning)
+                            }
+
+                            if let (true, Some(dot_path)) = (graph_dump_layout, dot_path) {
+                                let layout_path =
+                                    dot_path.join(format!("{}_{}_{}.layout.json", litmus.name, state, i + 1));
+                                if let Err(e) = dump_graph_layout(graph).save(&layout_path) {
+                                    eprintln!("{}", e)
+                                }
+                            }
+
                             match graph_opts.mode {
                                 GraphMode::Disabled => (),
+                                GraphMode::Svg | GraphMode::GraphML => {
+                                    if let Some(dot_path) = dot_path {
+                                        let ext = if graph_opts.mode == GraphMode::Svg { "svg" } else { "graphml" };
+                                        let out_file =
+                                            dot_path.join(format!("{}_{}_{}.{}", litmus.name, state, i + 1, ext));
+                                        log!(
+                                            log::VERBOSE,
+                                            &format!(
+                                                "generating {} for execution #{} for {}: path {}",
+                                                ext,
+                                                i + 1,
+                                                litmus.name,
+                                                out_file.display()
+                                            )
+                                        );
+
+                                        let outcome = std::fs::File::create(&out_file).and_then(|mut out| {
+                                            if graph_opts.mode == GraphMode::Svg {
+                                                draw_graph_svg(graph, &mut out)
+                                            } else {
+                                                draw_graph_graphml(graph, &mut out)
+                                            }
+                                        });
+                                        if let Err(e) = outcome {
+                                            eprintln!("failed to render graph to {}: {e}", out_file.display());
+                                            continue;
+                                        }
+                                    }
+                                }
+                                GraphMode::Html => html_graphs.push((i + 1, state, graph.as_ref())),
+                                GraphMode::Dot if dot_single_file && state != "err" => {
+                                    single_file_graphs.push((i + 1, state == "allow", graph.as_ref()))
+                                }
                                 GraphMode::Dot => {
                                     if let Some(dot_path) = dot_path {
                                         let dot_file_buf =
@@ -924,7 +1452,7 @@ fn isla_main() -> i32 {
                                         writeln!(&mut stderr),
                                         writeln!(&mut stderr, "Candidate {}/{} ({}):", i + 1, results.len(), state),
                                         writeln!(&mut stderr),
-                                        draw_graph_ascii(&mut stderr, graph, &graph_opts),
+                                        draw_graph_ascii(graph, &mut stderr),
                                     ]
                                     .into_iter()
                                     .collect();
@@ -939,6 +1467,92 @@ fn isla_main() -> i32 {
                             }
                         }
                     }
+
+                    if let (Some(dot_path), false) = (dot_path, single_file_graphs.is_empty()) {
+                        let dot_file = dot_path.join(format!("{}_executions.dot", litmus.name));
+                        let outcome = std::fs::File::create(&dot_file)
+                            .and_then(|mut dotf| draw_graphs_gv(&mut dotf, single_file_graphs));
+                        if let Err(e) = outcome {
+                            eprintln!("failed to render graphs to {}: {e}", dot_file.display())
+                        }
+                    }
+
+                    if let (Some(dot_path), false) = (dot_path, html_graphs.is_empty()) {
+                        let html_file = dot_path.join(format!("{}_executions.html", litmus.name));
+                        let outcome = std::fs::File::create(&html_file)
+                            .and_then(|mut htmlf| draw_graphs_html(&mut htmlf, &litmus.name, html_graphs));
+                        if let Err(e) = outcome {
+                            eprintln!("failed to render graphs to {}: {e}", html_file.display())
+                        }
+                    }
+
+                    timings.record(Phase::Graphs, graphs_start.elapsed());
+                    if timing {
+                        print_timings(&litmus.name, &timings, now.elapsed())
+                    }
+
+                    if let Some(reduce_path) = reduce_path {
+                        let expected_from_test = expected_from_toml(&litmus_src);
+                        let expected = ref_result.or(expected_from_test.as_ref());
+                        let flag = match reduce_oracle {
+                            ReduceOracle::Flag(flag) => Some(flag.as_str()),
+                            _ => None,
+                        };
+
+                        let mut oracle = |candidate_src: &str| {
+                            let candidate = match Litmus::parse(candidate_src, symtab, type_info, isa_config) {
+                                Ok(candidate) => candidate,
+                                Err(_) => return false,
+                            };
+                            let verdicts = litmus_verdicts(
+                                &format!("g{}t{}r", group_id, i),
+                                &opts,
+                                &candidate,
+                                &graph_opts,
+                                iarch,
+                                fiarch,
+                                sexps,
+                                mm_compiled,
+                                mm_symtab,
+                                accessors,
+                                extra_smt,
+                                check_sat_using,
+                                cache,
+                                flag,
+                            );
+                            match (reduce_oracle, verdicts) {
+                                (ReduceOracle::Error, Err(_)) => true,
+                                (ReduceOracle::Error, Ok((results, _))) => results.iter().any(AxResult::is_error),
+                                (_, Err(_)) => false,
+                                (ReduceOracle::Flag(_), Ok((_, flagged))) => flagged,
+                                (ReduceOracle::Mismatch, Ok((results, _))) => {
+                                    match (overall_result(&results), expected) {
+                                        (Some(got), Some(expected)) => !got.is_error() && !got.matches(expected),
+                                        _ => false,
+                                    }
+                                }
+                            }
+                        };
+
+                        match reduce::reduce(&litmus_src, &mut oracle, reduce_budget) {
+                            Ok(reduction) => {
+                                let reduced_file = reduce_path.join(format!("{}.toml", litmus.name));
+                                if let Err(e) = fs::write(&reduced_file, &reduction.litmus) {
+                                    eprintln!("Failed to write reduced test {}: {}", reduced_file.display(), e);
+                                    continue;
+                                }
+                                println!(
+                                    "Reduced {} to {} ({} of {} reductions accepted{})",
+                                    litmus.name,
+                                    reduced_file.display(),
+                                    reduction.accepted,
+                                    reduction.attempts - 1,
+                                    if reduction.timed_out { ", budget exhausted" } else { "" }
+                                )
+                            }
+                            Err(e) => eprintln!("Could not reduce {}: {}", litmus.name, e),
+                        }
+                    }
                 }
             });
         }
@@ -951,21 +1565,141 @@ fn isla_main() -> i32 {
     }
 }
 
+/// Get the expected result from the `expect` field of a litmus test
+/// in TOML format, if it has one.
+fn expected_from_toml(litmus_src: &str) -> Option<AxResult> {
+    let litmus_toml = litmus_src.parse::<toml::Value>().ok()?;
+    match litmus_toml.get("final")?.get("expect")?.as_str()? {
+        "sat" => Some(AxResult::Allowed(None, None)),
+        "unsat" => Some(AxResult::Forbidden(None, None)),
+        _ => None,
+    }
+}
+
+/// Run a litmus test to find just the verdict for each candidate,
+/// without computing final states or graphs. If a flag is given, we
+/// also report whether that memory model flag held in any allowed
+/// candidate.
+#[allow(clippy::too_many_arguments)]
+fn litmus_verdicts(
+    uid: &str,
+    opts: &LitmusRunOpts,
+    litmus: &Litmus<B129>,
+    graph_opts: &GraphOpts,
+    iarch: &InitArchWithConfig<B129>,
+    fiarch: &InitArchWithConfig<B129>,
+    sexps: &SexpArena,
+    mm_compiled: &[SexpId],
+    mm_symtab: &memory_model::Symtab,
+    accessors: &memory_model::Accessors,
+    extra_smt: &[(String, String)],
+    check_sat_using: Option<&str>,
+    cache: &Path,
+    flag: Option<&str>,
+) -> Result<(Vec<AxResult>, bool), String> {
+    let result_queue = SegQueue::new();
+    let flagged = AtomicBool::new(false);
+
+    run_litmus::smt_output_per_candidate::<B129, _, _, FinalLocValuesError>(
+        uid,
+        opts,
+        litmus,
+        graph_opts,
+        iarch,
+        fiarch,
+        sexps,
+        mm_compiled,
+        mm_symtab,
+        accessors,
+        extra_smt,
+        check_sat_using,
+        flag.is_some(),
+        cache,
+        &|exec, _, _, _, _, z3_output| {
+            if let Some(model_buf) = z3_output.strip_prefix("sat") {
+                if let Some(flag) = flag {
+                    let mut event_names: Vec<&str> = exec.smt_events.iter().map(|ev| ev.name.as_ref()).collect();
+                    event_names.push("IW");
+                    if let Ok(mut model) = Model::<B129>::parse(&event_names, model_buf) {
+                        if let Ok(SexpVal::Bool(true)) = model.interpret(flag, &[]) {
+                            flagged.store(true, Ordering::Relaxed)
+                        }
+                    }
+                }
+                result_queue.push(AxResult::Allowed(None, None))
+            } else if z3_output.starts_with("unsat") {
+                result_queue.push(AxResult::Forbidden(None, None))
+            } else {
+                result_queue.push(AxResult::Error(None, z3_output.to_string()))
+            }
+            Ok(())
+        },
+    )
+    .map_err(|err| format!("{}", err))?;
+
+    let mut results = Vec::new();
+    while let Some(result) = result_queue.pop() {
+        results.push(result)
+    }
+
+    Ok((results, flagged.load(Ordering::Relaxed)))
+}
+
+fn print_timings(name: &str, timings: &PhaseTimings, total: Duration) {
+    // Take the lock so the table is not interleaved with output from other threads
+    let mut stdout = std::io::stdout().lock();
+    let _ = writeln!(stdout, "{} timing ({}ms total):", name, total.as_millis());
+    for (phase, duration) in &timings.phases {
+        let _ = writeln!(stdout, "  {:10} {}ms", phase, duration.as_millis());
+    }
+    let other = total.saturating_sub(timings.total());
+    let _ = writeln!(stdout, "  {:10} {}ms", "other", other.as_millis());
+}
+
+fn print_relation_stats(name: &str, stats: &RelationStats) {
+    if stats.is_empty() {
+        return;
+    }
+
+    let summary = stats.summary();
+    let width = summary.iter().map(|rel| rel.name.len()).max().unwrap_or(0);
+
+    // Take the lock so the table is not interleaved with output from other threads
+    let mut stdout = std::io::stdout().lock();
+    let _ = writeln!(stdout, "{} relation statistics (min/median/max edges, max density):", name);
+    for rel in summary {
+        let _ = writeln!(
+            stdout,
+            "  {:width$} {}/{}/{} {:.3}",
+            rel.name,
+            rel.min,
+            rel.median,
+            rel.max,
+            rel.max_density,
+            width = width
+        );
+    }
+}
+
 #[allow(unused)]
-fn print_results_legacy(name: &str, start_time: Instant, results: &[AxResult], expected: Option<&AxResult>) {
+fn print_results_legacy(
+    name: &str,
+    start_time: Instant,
+    results: &[AxResult],
+    expected: Option<&AxResult>,
+    sanity: AssertionSanity,
+) {
+    if let Some(warning) = sanity.warning() {
+        eprintln!("{}: {}", name, warning)
+    }
+
     if results.is_empty() {
         let prefix = format!("{} no executions {}", name, start_time.elapsed().as_millis());
         println!("{:.<100} \x1b[95m\x1b[1merror\x1b[0m", prefix);
         return;
     }
 
-    let got = if let Some(err) = results.iter().find(|result| result.is_error()) {
-        err
-    } else if let Some(allowed) = results.iter().find(|result| result.is_allowed()) {
-        allowed
-    } else {
-        results.first().unwrap()
-    };
+    let got = overall_result(results).unwrap();
 
     if let AxResult::Error(_, z3_output) = got {
         eprintln!("Error in parsing smt output to get allowed/forbidden ...");
@@ -984,17 +1718,25 @@ fn print_results_legacy(name: &str, start_time: Instant, results: &[AxResult], e
 
     let count = format!("{} of {}", results.iter().filter(|result| result.is_allowed()).count(), results.len());
 
+    // A vacuous or tautological final condition is reported as a
+    // separate verdict alongside the result of the candidates
+    let verdict = if sanity.warning().is_some() {
+        format!("{} [{}]", got.short_name(), sanity.short_name())
+    } else {
+        got.short_name().to_string()
+    };
+
     let prefix = if let Some(reference) = expected {
         format!(
             "{} {} ({}) reference: {} {}ms ",
             name,
-            got.short_name(),
+            verdict,
             count,
             reference.short_name(),
             start_time.elapsed().as_millis()
         )
     } else {
-        format!("{} {} ({}) {}ms ", name, got.short_name(), count, start_time.elapsed().as_millis())
+        format!("{} {} ({}) {}ms ", name, verdict, count, start_time.elapsed().as_millis())
     };
 
     let result = if let Some(reference) = expected {
@@ -1021,7 +1763,12 @@ fn print_results_herd7<'ir>(
     start_time: Instant,
     results: &[AxResult],
     expected: Option<&AxResult>,
+    sanity: AssertionSanity,
 ) {
+    if let Some(warning) = sanity.warning() {
+        eprintln!("{}: {}", litmus.name, warning)
+    }
+
     let positive = results.iter().filter(|r| r.is_allowed()).count();
     let negative = results.iter().filter(|r| r.is_forbidden()).count();
 
@@ -1143,11 +1890,12 @@ fn print_results<'ir>(
     start_time: Instant,
     results: &[AxResult],
     expected: Option<&AxResult>,
+    sanity: AssertionSanity,
 ) {
     if herd_style {
-        print_results_herd7(litmus, shared_state, start_time, results, expected)
+        print_results_herd7(litmus, shared_state, start_time, results, expected, sanity)
     } else {
-        print_results_legacy(&litmus.name, start_time, results, expected)
+        print_results_legacy(&litmus.name, start_time, results, expected, sanity)
     }
 }
 
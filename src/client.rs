@@ -127,12 +127,11 @@ fn execute_opcode(
     write_answer(stream, Answer::StartTraces)?;
 
     executor::start_multi(
-        num_threads,
-        None,
         vec![task],
         shared_state,
         queue.clone(),
-        &executor::trace_result_collector,
+        executor::MultiCollector::Plain(&executor::trace_result_collector),
+        &executor::MultiOpts::new(num_threads),
     );
 
     Ok(loop {
@@ -34,14 +34,17 @@ use std::convert::TryFrom;
 use std::io::Write;
 use std::process::exit;
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
 use isla_lib::bitvector::b129::B129;
 use isla_lib::bitvector::BV;
 use isla_lib::error::ExecError;
 use isla_lib::executor;
-use isla_lib::executor::{reset_registers, Backtrace, LocalFrame, Run, StopAction, StopConditions, TaskId, TaskState};
+use isla_lib::executor::{
+    path_outcome, reset_registers, write_fork_tree, Backtrace, ForkTree, LocalFrame, PathStats, PathStatsSummary, Run,
+    StopAction, StopConditions, TaskId, TaskState,
+};
 use isla_lib::init::{initialize_architecture, Initialized};
 use isla_lib::ir::*;
 use isla_lib::ir_lexer::new_ir_lexer;
@@ -72,6 +75,7 @@ fn isla_main() -> i32 {
     opts.optflag("", "error-traces", "print execution traces for paths that fail");
     opts.optflag("s", "simplify", "simplify function traces");
     opts.optflag("", "simplify-registers", "simplify register accesses in traces");
+    opts.optflag("", "prune", "only keep memory events, branches, and the result in traces");
     opts.optflag("m", "model", "query SMT model to fill in variables");
     opts.optmulti(
         "k",
@@ -87,6 +91,9 @@ fn isla_main() -> i32 {
     );
     opts.optopt("", "timeout", "Add a timeout (in seconds)", "<n>");
     opts.optflag("", "executable", "make trace executable");
+    opts.optflag("", "stats", "print statistics about the executed paths");
+    opts.optflag("", "fork-tree", "print a tree of where the executed paths forked");
+    opts.optflag("", "source-lines", "record each Sail source line reached in the execution traces");
 
     let mut hasher = Sha256::new();
     let (matches, arch) = opts::parse::<B129>(&mut hasher, &opts);
@@ -169,20 +176,45 @@ fn isla_main() -> i32 {
 
     let mut task = frame.task_with_checkpoint(TaskId::fresh(), &task_state, smt::checkpoint(&mut solver));
     task.set_stop_conditions(&stop_conditions);
+    task.set_trace_source_lines(matches.opt_present("source-lines"));
 
     let traces = matches.opt_present("traces");
     let tree = matches.opt_present("tree");
     let error_traces = matches.opt_present("error-traces");
     let models = matches.opt_present("model");
-    let collecting = Arc::new((SegQueue::new(), tree | traces | error_traces, models));
+    let fork_tree = matches.opt_present("fork-tree").then(|| Mutex::new(ForkTree::new()));
+    let collecting = Arc::new((
+        SegQueue::new(),
+        tree | traces | error_traces,
+        models,
+        Mutex::new(PathStatsSummary::new()),
+        fork_tree,
+    ));
     let now = Instant::now();
-    executor::start_multi(num_threads, timeout, vec![task], &shared_state, collecting.clone(), &model_collector);
+    executor::start_multi(
+        vec![task],
+        &shared_state,
+        collecting.clone(),
+        executor::MultiCollector::Plain(&model_collector),
+        &executor::MultiOpts { num_threads, timeout, ..executor::MultiOpts::default() },
+    );
 
     eprintln!("Execution took: {}ms", now.elapsed().as_millis());
 
-    let (queue, _, _) = collecting.as_ref();
+    let (queue, _, _, stats, fork_tree) = collecting.as_ref();
+    if matches.opt_present("stats") {
+        eprint!("{}", stats.lock().unwrap())
+    }
+    if let Some(fork_tree) = fork_tree {
+        write_fork_tree(&mut std::io::stderr(), &fork_tree.lock().unwrap(), shared_state.symtab.files()).unwrap()
+    }
 
     let write_events = |mut events, handle: &mut dyn Write| {
+        if matches.opt_present("prune") {
+            simplify::prune_unreachable(&mut events, |event| {
+                simplify::is_memory_root(event) || event.is_write_reg_of(final_result_register)
+            });
+        }
         if matches.opt_present("simplify") {
             // Don't do simplify::hide_initialization(&mut events); because
             // individual functions might not have a separate initialization
@@ -299,14 +331,23 @@ fn concrete_value<B: BV>(model: &mut Model<B>, val: &Val<B>) -> Val<B> {
 
 type AllTraceValueQueue<B> = SegQueue<Result<(TaskId, Val<B>, Vec<Event<B>>), (String, Vec<Event<B>>)>>;
 
+/// The results of each path, whether to keep their traces and query
+/// models, and the statistics and fork tree for all the paths
+type ModelCollection<B> = (AllTraceValueQueue<B>, bool, bool, Mutex<PathStatsSummary>, Option<Mutex<ForkTree>>);
+
 fn model_collector<'ir, B: BV>(
     tid: usize,
     task_id: TaskId,
     result: Result<(Run<B>, LocalFrame<'ir, B>), (ExecError, Backtrace)>,
+    stats: PathStats,
     shared_state: &SharedState<'ir, B>,
     mut solver: Solver<B>,
-    (collected, trace, models): &(AllTraceValueQueue<B>, bool, bool),
+    (collected, trace, models, path_stats, fork_tree): &ModelCollection<B>,
 ) {
+    path_stats.lock().unwrap().record(&stats);
+    if let Some(fork_tree) = fork_tree {
+        fork_tree.lock().unwrap().record_path(solver.trace().to_vec().into_iter().rev(), path_outcome(&result))
+    }
     let events: Vec<Event<B>> = if *trace { solver.trace().to_vec().drain(..).cloned().collect() } else { vec![] };
     match result {
         Ok((Run::Finished(val), _)) => {
@@ -609,7 +609,13 @@ fn isla_main() -> i32 {
     let queue = Arc::new(SegQueue::new());
 
     let now = Instant::now();
-    executor::start_multi(num_threads, timeout, vec![task], shared_state, queue.clone(), &executor::trace_collector);
+    executor::start_multi(
+        vec![task],
+        shared_state,
+        queue.clone(),
+        executor::MultiCollector::Plain(&executor::trace_collector),
+        &executor::MultiOpts { num_threads, timeout, ..executor::MultiOpts::default() },
+    );
     log!(log::VERBOSE, &format!("Execution took: {}ms", now.elapsed().as_millis()));
 
     let mut paths = Vec::new();
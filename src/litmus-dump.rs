@@ -45,6 +45,7 @@ use std::time::Instant;
 use isla_axiomatic::footprint_analysis::{footprint_analysis, Footprint};
 use isla_axiomatic::litmus::exp;
 use isla_axiomatic::litmus::Litmus;
+use isla_axiomatic::multi_access::MultiAccessPolicy;
 use isla_axiomatic::run_litmus;
 use isla_axiomatic::run_litmus::{LitmusRunOpts, LitmusSetup, PCLimitMode};
 use isla_lib::bitvector::{b64::B64, BV};
@@ -220,17 +221,22 @@ fn isla_main() -> i32 {
     let opts = LitmusRunOpts {
         num_threads,
         timeout: None,
+        path_timeout: None,
         memory,
         ignore_ifetch: true,
         exhaustive: true,
         armv8_page_tables: false,
         merge_translations: None,
         remove_uninteresting_translates: None,
+        multi_access: MultiAccessPolicy::Unlinked,
         pc_limit: None,
         pc_limit_mode: PCLimitMode::Error,
+        progress: false,
+        model_completion: false,
+        memory_budget: None,
     };
 
-    let setup = run_litmus::run_litmus_setup::<B64, _, ()>(&opts, &litmus, &iarch_config, |_| true).unwrap();
+    let setup = run_litmus::run_litmus_setup::<B64, _, ()>(&opts, &litmus, &iarch_config, None, |_| true).unwrap();
 
     let footprints =
         footprint_analysis(opts.num_threads, &setup.threads, &fiarch_config, Some(cache.as_ref())).unwrap();
@@ -49,6 +49,7 @@ use isla_lib::ir::*;
 use isla_lib::ir_parser;
 use isla_lib::log;
 use isla_lib::primop_util::symbolic_from_typedefs;
+use isla_lib::smt;
 use isla_lib::smt::z3_version;
 use isla_lib::smt_parser;
 use isla_lib::source_loc::SourceLoc;
@@ -106,6 +107,10 @@ pub fn common_opts() -> Options {
     opts.optmulti("", "fun-assumption", "add an assumption about the behaviour of a Sail function", "<assumption>");
     opts.optflag("", "no-model-reg-init", "don't use register initializers from the model");
     opts.optflag("", "version", "print out version and stop.");
+    opts.optopt("", "smt-backend", "SMT solver used to check satisfiability (z3 or cvc5)", "<solver>");
+    opts.optopt("", "smt-timeout", "give up on each satisfiability check after this long", "<milliseconds>");
+    opts.optopt("", "smt-rlimit", "give up on each satisfiability check after using this many resources", "<n>");
+    opts.optopt("", "smt-dump-slow", "write satisfiability checks taking this long to .smt2 files", "<milliseconds>");
     opts
 }
 
@@ -197,6 +202,44 @@ pub fn parse<B: BV>(hasher: &mut Sha256, opts: &Options) -> (Matches, Architectu
         | (if debug_opts.contains('p') { log::PROBE } else { 0u32 });
     log::set_flags(logging_flags);
 
+    match matches.opt_get("smt-timeout") {
+        Ok(timeout) => smt::set_default_timeout(timeout),
+        Err(f) => {
+            eprintln!("Could not parse --smt-timeout option: {}", f);
+            print_usage(opts, "", 1)
+        }
+    }
+    match matches.opt_get("smt-rlimit") {
+        Ok(rlimit) => smt::set_default_rlimit(rlimit),
+        Err(f) => {
+            eprintln!("Could not parse --smt-rlimit option: {}", f);
+            print_usage(opts, "", 1)
+        }
+    }
+    match matches.opt_get("smt-dump-slow") {
+        Ok(millis) => smt::set_slow_query_dump(millis),
+        Err(f) => {
+            eprintln!("Could not parse --smt-dump-slow option: {}", f);
+            print_usage(opts, "", 1)
+        }
+    }
+
+    if let Some(backend) = matches.opt_str("smt-backend") {
+        match backend.parse::<smt::backend::Backend>() {
+            Ok(backend) => {
+                if let Err(err) = backend.check_available() {
+                    eprintln!("{}", err);
+                    exit(1)
+                }
+                smt::backend::set_default_backend(backend)
+            }
+            Err(msg) => {
+                eprintln!("{}", msg);
+                exit(1)
+            }
+        }
+    }
+
     let arch = {
         let file = matches.opt_str("arch").unwrap();
         match load_ir(hasher, &file) {
@@ -77,7 +77,13 @@ fn isla_main() -> i32 {
         .task(TaskId::fresh(), &task_state);
     let result = Arc::new(AtomicBool::new(true));
 
-    executor::start_multi(num_threads, None, vec![task], &shared_state, result.clone(), &executor::all_unsat_collector);
+    executor::start_multi(
+        vec![task],
+        &shared_state,
+        result.clone(),
+        executor::MultiCollector::Plain(&executor::all_unsat_collector),
+        &executor::MultiOpts::new(num_threads),
+    );
 
     if result.load(Ordering::Acquire) {
         println!("ok");
@@ -46,6 +46,8 @@ interface GraphResponse {
   objdump: string
   candidates: number
   shows: string[]
+  final_condition: string
+  timings: [string, number][]
 }
 
 interface ErrorResponse {
@@ -208,6 +210,14 @@ export class IslaUI {
           } else {
             this.getView().state.console += "Forbidden: 0 satisfiable solutions out of " + content.candidates + " candidates\n"
           }
+          if (content.final_condition == 'vacuous') {
+            this.getView().state.console += "Warning: final condition unsatisfiable, test is vacuous\n"
+          } else if (content.final_condition == 'tautology') {
+            this.getView().state.console += "Warning: final condition is a tautology\n"
+          }
+          if (content.timings.length > 0) {
+            this.getView().state.console += "Timing: " + content.timings.map(([phase, ms]) => phase + " " + ms + "ms").join(", ") + "\n"
+          }
           this.getView().emit('update')
         } else if (response.tag = 'Error') {
           if (response.content != undefined) {
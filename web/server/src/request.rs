@@ -61,5 +61,13 @@ pub struct JsGraph {
 pub enum Response {
     InternalError,
     Error { message: String },
-    Done { graphs: Vec<JsGraph>, objdump: String, candidates: i32, shows: Vec<String> },
+    Done {
+        graphs: Vec<JsGraph>,
+        objdump: String,
+        candidates: i32,
+        shows: Vec<String>,
+        final_condition: String,
+        /// Milliseconds spent in each phase of the run
+        timings: Vec<(String, u64)>,
+    },
 }
@@ -42,8 +42,9 @@ use std::path::PathBuf;
 use std::process::Stdio;
 use std::time::Instant;
 
-use isla_axiomatic::graph::{draw_graph_gv, graph_from_z3_output, GraphMode, GraphOpts, GraphValueNames};
+use isla_axiomatic::graph::{draw_graph_gv, graph_from_z3_output, GraphMode, GraphOpts, GraphStyle, GraphValueNames};
 use isla_axiomatic::litmus::Litmus;
+use isla_axiomatic::multi_access::MultiAccessPolicy;
 use isla_axiomatic::page_table::{name_initial_walk_bitvectors, VirtualAddress};
 use isla_axiomatic::run_litmus;
 use isla_axiomatic::run_litmus::{PCLimitMode, LitmusRunOpts};
@@ -55,6 +56,7 @@ use isla_lib::init::{initialize_architecture, InitArchWithConfig};
 use isla_lib::ir::serialize::{read_serialized_architecture, DeserializedArchitecture};
 use isla_lib::ir::*;
 use isla_lib::source_loc::SourceLoc;
+use isla_mml::accessor::{check_requires, InterfaceNames};
 use isla_mml::memory_model;
 use isla_mml::smt::{compile_memory_model, SexpArena};
 
@@ -306,6 +308,10 @@ fn handle_request() -> Result<Response, Box<dyn Error>> {
             return Ok(Response::Error { message: memory_model::format_error(&compile_error) })
         }
     };
+    if let Err(missing) = check_requires(&mm, &InterfaceNames::new(&iarch.shared_state), &mm_symtab) {
+        let message = missing.iter().map(memory_model::format_error).collect::<Vec<_>>().join("\n");
+        return Ok(Response::Error { message })
+    }
     let mut mm_compiled = Vec::new();
     if let Err(compile_error) =
         compile_memory_model(&mm, iarch.shared_state.typedefs(), &mm_arena, &Vec::new(), &mut sexps, &mut mm_symtab, &mut mm_compiled)
@@ -318,6 +324,7 @@ fn handle_request() -> Result<Response, Box<dyn Error>> {
     let litmus_opts = LitmusRunOpts {
         num_threads: THREADS,
         timeout: None,
+        path_timeout: None,
         pc_limit: None,
         pc_limit_mode: PCLimitMode::Error,
         memory: None,
@@ -326,21 +333,16 @@ fn handle_request() -> Result<Response, Box<dyn Error>> {
         armv8_page_tables: req.armv8_page_tables,
         merge_translations: if req.merge_translations { Some(req.merge_split_stages) } else { None },
         remove_uninteresting_translates: if req.remove_uninteresting { Some(true) } else { None },
+        multi_access: MultiAccessPolicy::Unlinked,
+        progress: false,
+        model_completion: false,
+        memory_budget: None,
     };
 
     let graph_opts = GraphOpts {
         mode: GraphMode::Dot,
         show_regs: HashSet::new(),
-        flatten: false,
-        debug: false,
-        show_all_reads: true,
-        shows: None,
-        padding: None,
-        human_readable_values: true,
-        force_show_events: None,
-        force_hide_events: None,
-        squash_translation_labels: false,
-        control_delimit: true,
+        ..GraphOpts::with_style(GraphStyle::Web)
     };
 
     let graph_queue = SegQueue::new();
@@ -456,6 +458,13 @@ fn handle_request() -> Result<Response, Box<dyn Error>> {
                 objdump: litmus.objdump.objdump,
                 candidates: i32::try_from(run_info.candidates).expect("Candidates did not fit in i32"),
                 shows: Vec::new(),
+                final_condition: run_info.assertion_sanity.short_name().to_string(),
+                timings: run_info
+                    .timings
+                    .phases
+                    .iter()
+                    .map(|(phase, duration)| (phase.to_string(), duration.as_millis() as u64))
+                    .collect(),
             }
         }
         Err(run_error) => Response::Error { message: format!("{}", run_error) },